@@ -7,10 +7,10 @@ use crate::spawn_future;
 use futures::task::{Context, Poll};
 use futures::{Future, Stream, StreamExt};
 use log::*;
-use nymsphinx::acknowledgements::AckKey;
+use nymsphinx::acknowledgements::{AckDelayDistribution, AckKey};
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::cover::generate_loop_cover_packet;
-use nymsphinx::params::PacketSize;
+use nymsphinx::params::{PacketMode, PacketSize};
 use nymsphinx::utils::sample_poisson_duration;
 use rand::{rngs::OsRng, CryptoRng, Rng};
 use std::pin::Pin;
@@ -31,8 +31,8 @@ where
     /// Key used to encrypt and decrypt content of an ACK packet.
     ack_key: Arc<AckKey>,
 
-    /// Average delay an acknowledgement packet is going to get delay at a single mixnode.
-    average_ack_delay: Duration,
+    /// Distribution of the delays an acknowledgement packet is going to get at each mixnode.
+    ack_delay_distribution: AckDelayDistribution,
 
     /// Average delay a data packet is going to get delay at a single mixnode.
     average_packet_delay: Duration,
@@ -63,6 +63,9 @@ where
 
     /// Predefined packet size used for the loop cover messages.
     packet_size: PacketSize,
+
+    /// Mode of the loop cover messages, which has to be the same as of the real ones.
+    packet_mode: PacketMode,
 }
 
 impl<R> Stream for LoopCoverTrafficStream<R>
@@ -110,7 +113,7 @@ impl LoopCoverTrafficStream<OsRng> {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         ack_key: Arc<AckKey>,
-        average_ack_delay: Duration,
+        ack_delay_distribution: AckDelayDistribution,
         average_packet_delay: Duration,
         average_cover_message_sending_delay: Duration,
         mix_tx: BatchMixMessageSender,
@@ -127,7 +130,7 @@ impl LoopCoverTrafficStream<OsRng> {
 
         LoopCoverTrafficStream {
             ack_key,
-            average_ack_delay,
+            ack_delay_distribution,
             average_packet_delay,
             average_cover_message_sending_delay,
             next_delay,
@@ -136,6 +139,7 @@ impl LoopCoverTrafficStream<OsRng> {
             rng,
             topology_access,
             packet_size: Default::default(),
+            packet_mode: Default::default(),
         }
     }
 
@@ -143,6 +147,10 @@ impl LoopCoverTrafficStream<OsRng> {
         self.packet_size = packet_size;
     }
 
+    pub fn set_custom_packet_mode(&mut self, packet_mode: PacketMode) {
+        self.packet_mode = packet_mode;
+    }
+
     async fn on_new_message(&mut self) {
         trace!("next cover message!");
 
@@ -166,9 +174,10 @@ impl LoopCoverTrafficStream<OsRng> {
             topology_ref,
             &self.ack_key,
             &self.our_full_destination,
-            self.average_ack_delay,
+            self.ack_delay_distribution,
             self.average_packet_delay,
             self.packet_size,
+            self.packet_mode,
         )
         .expect("Somehow failed to generate a loop cover message with a valid topology");
 
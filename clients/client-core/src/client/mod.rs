@@ -8,6 +8,8 @@ pub mod real_messages_control;
 pub mod received_buffer;
 #[cfg(feature = "reply-surb")]
 pub mod reply_key_storage;
+#[cfg(feature = "reply-surb")]
+pub mod reply_surb_generator;
 pub mod topology_control;
 
 // This is *NOT* used to signal shutdown.
@@ -1,8 +1,10 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use super::timeout_estimator::TimeoutEstimator;
 use super::PendingAcknowledgement;
 use crate::client::real_messages_control::acknowledgement_control::RetransmissionRequestSender;
+use crate::client::real_messages_control::{FailedTransmission, FailedTransmissionSender};
 use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use futures::StreamExt;
 use log::*;
@@ -13,10 +15,37 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_timer::Instant;
+
 pub(crate) type ActionSender = UnboundedSender<Action>;
 
 // The actual data being sent off as well as potential key to the delay queue
-type PendingAckEntry = (Arc<PendingAcknowledgement>, Option<QueueKey>);
+struct PendingAckEntry {
+    data: Arc<PendingAcknowledgement>,
+    queue_key: Option<QueueKey>,
+
+    /// Time at which the timer of the latest transmission was started.
+    timer_started: Option<Instant>,
+
+    /// Number of times the data was retransmitted. If it's non-zero, we can't tell which
+    /// transmission got acknowledged and hence the round trip time can't be measured.
+    retransmissions: u32,
+}
+
+impl PendingAckEntry {
+    fn new(data: PendingAcknowledgement) -> Self {
+        PendingAckEntry {
+            data: Arc::new(data),
+            queue_key: None,
+            timer_started: None,
+            retransmissions: 0,
+        }
+    }
+}
 
 // we can either:
 // - have a completely new set of packets we just sent and need to create entries for
@@ -63,25 +92,37 @@ impl Action {
 
 /// Configurable parameters of the `ActionController`
 pub(super) struct Config {
-    /// Given ack timeout in the form a * BASE_DELAY + b, it specifies the additive part `b`
+    /// Given ack timeout in the form a * BASE_DELAY + b, it specifies the initial additive part `b`
     ack_wait_addition: Duration,
 
-    /// Given ack timeout in the form a * BASE_DELAY + b, it specifies the multiplier `a`
+    /// Given ack timeout in the form a * BASE_DELAY + b, it specifies the initial multiplier `a`
     ack_wait_multiplier: f64,
+
+    /// Maximum number of retransmissions of a packet before it's considered lost.
+    /// If not set, the packets are retransmitted until they get acknowledged.
+    maximum_retransmissions: Option<u32>,
 }
 
 impl Config {
-    pub(super) fn new(ack_wait_addition: Duration, ack_wait_multiplier: f64) -> Self {
+    pub(super) fn new(
+        ack_wait_addition: Duration,
+        ack_wait_multiplier: f64,
+        maximum_retransmissions: Option<u32>,
+    ) -> Self {
         Config {
             ack_wait_addition,
             ack_wait_multiplier,
+            maximum_retransmissions,
         }
     }
 }
 
 pub(super) struct ActionController {
-    /// Configurable parameters of the `ActionController`
-    config: Config,
+    /// Estimates the ack timeouts based on the round trip times of the previously sent packets.
+    timeout_estimator: TimeoutEstimator,
+
+    /// Maximum number of retransmissions of a packet before it's considered lost.
+    maximum_retransmissions: Option<u32>,
 
     /// Contains a map between `FragmentIdentifier` and its full `PendingAcknowledgement` as well as
     /// key to its `AckDelayQueue` entry if it was started.
@@ -99,21 +140,31 @@ pub(super) struct ActionController {
 
     /// Channel for notifying `RetransmissionRequestListener` about expired acknowledgements.
     retransmission_sender: RetransmissionRequestSender,
+
+    /// Channel for notifying about packets that were never acknowledged despite being
+    /// retransmitted the maximum number of times.
+    failed_transmission_sender: Option<FailedTransmissionSender>,
 }
 
 impl ActionController {
     pub(super) fn new(
         config: Config,
         retransmission_sender: RetransmissionRequestSender,
+        failed_transmission_sender: Option<FailedTransmissionSender>,
     ) -> (Self, ActionSender) {
         let (sender, receiver) = mpsc::unbounded();
         (
             ActionController {
-                config,
+                timeout_estimator: TimeoutEstimator::new(
+                    config.ack_wait_addition,
+                    config.ack_wait_multiplier,
+                ),
+                maximum_retransmissions: config.maximum_retransmissions,
                 pending_acks_data: HashMap::new(),
                 pending_acks_timers: NonExhaustiveDelayQueue::new(),
                 incoming_actions: receiver,
                 retransmission_sender,
+                failed_transmission_sender,
             },
             sender,
         )
@@ -126,7 +177,7 @@ impl ActionController {
 
             if self
                 .pending_acks_data
-                .insert(frag_id, (Arc::new(pending_ack), None))
+                .insert(frag_id, PendingAckEntry::new(pending_ack))
                 .is_some()
             {
                 panic!("Tried to insert duplicate pending ack")
@@ -137,18 +188,21 @@ impl ActionController {
     fn handle_start_timer(&mut self, frag_id: FragmentIdentifier) {
         trace!("{} is starting its timer", frag_id);
 
-        if let Some((pending_ack_data, queue_key)) = self.pending_acks_data.get_mut(&frag_id) {
-            if queue_key.is_some() {
+        if let Some(entry) = self.pending_acks_data.get_mut(&frag_id) {
+            if entry.queue_key.is_some() {
                 // this branch should be IMPOSSIBLE under ANY condition. It would imply starting
                 // timer TWICE for the SAME PendingAcknowledgement
                 panic!("Tried to start an already started ack timer!")
             }
-            let timeout = (pending_ack_data.delay.clone() * self.config.ack_wait_multiplier)
-                .to_duration()
-                + self.config.ack_wait_addition;
+            let timeout = self.timeout_estimator.timeout(
+                entry.data.recipient.gateway(),
+                &entry.data.delay,
+                entry.retransmissions,
+            );
 
             let new_queue_key = self.pending_acks_timers.insert(frag_id, timeout);
-            *queue_key = Some(new_queue_key)
+            entry.queue_key = Some(new_queue_key);
+            entry.timer_started = Some(Instant::now());
         } else {
             debug!(
                 "Tried to START TIMER on pending ack that is already gone! - {}",
@@ -167,8 +221,16 @@ impl ActionController {
                     frag_id
                 );
             }
-            Some((_, queue_key)) => {
-                if let Some(queue_key) = queue_key {
+            Some(entry) => {
+                if let (Some(timer_started), 0) = (entry.timer_started, entry.retransmissions) {
+                    self.timeout_estimator.record(
+                        entry.data.recipient.gateway(),
+                        &entry.data.delay,
+                        timer_started.elapsed(),
+                    );
+                }
+
+                if let Some(queue_key) = entry.queue_key {
                     // there are no possible checks here, we must GUARANTEE that we NEVER try
                     // to remove an entry that doesn't exist (and we MUST GUARANTEE that
                     // we do not have a stale key)
@@ -191,15 +253,16 @@ impl ActionController {
     fn handle_update_delay(&mut self, frag_id: FragmentIdentifier, delay: SphinxDelay) {
         trace!("{} is updating its delay", frag_id);
         // TODO: is it possible to solve this without either locking or temporarily removing the value?
-        if let Some((pending_ack_data, queue_key)) = self.pending_acks_data.remove(&frag_id) {
+        if let Some(mut entry) = self.pending_acks_data.remove(&frag_id) {
             // this Action is triggered by `RetransmissionRequestListener` which held the other potential
             // reference to this Arc. HOWEVER, before the Action was pushed onto the queue, the reference
             // was dropped hence this unwrap is safe.
-            let mut inner_data = Arc::try_unwrap(pending_ack_data).unwrap();
+            let mut inner_data = Arc::try_unwrap(entry.data).unwrap();
             inner_data.update_delay(delay);
 
-            self.pending_acks_data
-                .insert(frag_id, (Arc::new(inner_data), queue_key));
+            entry.data = Arc::new(inner_data);
+            entry.retransmissions += 1;
+            self.pending_acks_data.insert(frag_id, entry);
         } else {
             debug!(
                 "Tried to UPDATE TIMER on pending ack that is already gone! - {}",
@@ -217,18 +280,22 @@ impl ActionController {
 
         trace!("{} has expired", frag_id);
 
-        if let Some((pending_ack_data, queue_key)) = self.pending_acks_data.get_mut(&frag_id) {
-            if queue_key.is_none() {
+        if let Some(entry) = self.pending_acks_data.get_mut(&frag_id) {
+            if entry.queue_key.is_none() {
                 // this branch should be IMPOSSIBLE under ANY condition. It would imply the timeout
                 // happened before it even started.
                 panic!("Ack expired before it was even scheduled!")
             }
-            *queue_key = None;
+            entry.queue_key = None;
+            if matches!(self.maximum_retransmissions, Some(max) if entry.retransmissions >= max) {
+                self.handle_failed_transmission(frag_id);
+                return;
+            }
             // downgrading an arc and then upgrading vs cloning is difference of 30ns vs 15ns
             // so it's literally a NO difference while it might prevent us from unnecessarily
             // resending data (in maybe 1 in 1 million cases, but it's something)
             self.retransmission_sender
-                .unbounded_send(Arc::downgrade(pending_ack_data))
+                .unbounded_send(Arc::downgrade(&entry.data))
                 .unwrap()
         } else {
             // this shouldn't cause any issues but shouldn't have happened to begin with!
@@ -236,6 +303,27 @@ impl ActionController {
         }
     }
 
+    // the packet is given up on, so that it wouldn't be retransmitted forever if, for example,
+    // its recipient went offline
+    fn handle_failed_transmission(&mut self, frag_id: FragmentIdentifier) {
+        if let Some(entry) = self.pending_acks_data.remove(&frag_id) {
+            warn!(
+                "{} was not acknowledged despite being retransmitted {} times. Giving up on it",
+                frag_id, entry.retransmissions
+            );
+
+            if let Some(failed_transmission_sender) = &self.failed_transmission_sender {
+                // the receiver might no longer be interested in the notifications
+                let _ = failed_transmission_sender.unbounded_send(FailedTransmission {
+                    recipient: entry.data.recipient,
+                    lane: entry.data.lane,
+                    fragment_id: frag_id,
+                    retransmissions: entry.retransmissions,
+                });
+            }
+        }
+    }
+
     fn process_action(&mut self, action: Action) {
         match action {
             Action::InsertPending(pending_acks) => self.handle_insert(pending_acks),
@@ -288,3 +376,76 @@ impl ActionController {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client_connections::TransmissionLane;
+    use crypto::asymmetric::{encryption, identity};
+    use nymsphinx::addressing::clients::Recipient;
+    use nymsphinx::chunking::split_into_sets;
+    use rand::rngs::OsRng;
+
+    fn pending_ack(lane: TransmissionLane) -> PendingAcknowledgement {
+        let mut rng = OsRng;
+        let recipient = Recipient::new(
+            *identity::KeyPair::new(&mut rng).public_key(),
+            *encryption::KeyPair::new(&mut rng).public_key(),
+            *identity::KeyPair::new(&mut rng).public_key(),
+        );
+        let fragment = split_into_sets(&mut rng, b"foomp", 1024)
+            .pop()
+            .unwrap()
+            .pop()
+            .unwrap();
+
+        PendingAcknowledgement::new(fragment, SphinxDelay::new_from_nanos(0), recipient, lane)
+    }
+
+    async fn expire_next_timer(controller: &mut ActionController) {
+        let expired_ack = controller.pending_acks_timers.next().await.unwrap();
+        controller.handle_expired_ack_timer(expired_ack);
+    }
+
+    #[tokio::test]
+    async fn failed_transmission_is_announced_once_retransmissions_are_exhausted() {
+        let (retransmission_sender, mut retransmission_receiver) = mpsc::unbounded();
+        let (failed_transmission_sender, mut failed_transmission_receiver) = mpsc::unbounded();
+        let config = Config::new(Duration::from_millis(1), 0.0, Some(1));
+        let (mut controller, _) = ActionController::new(
+            config,
+            retransmission_sender,
+            Some(failed_transmission_sender),
+        );
+
+        let lane = TransmissionLane::ConnectionId(42);
+        let pending_ack = pending_ack(lane);
+        let frag_id = pending_ack.message_chunk.fragment_identifier();
+        let recipient = pending_ack.recipient;
+
+        controller.process_action(Action::new_insert(vec![pending_ack]));
+        controller.process_action(Action::new_start_timer(frag_id));
+
+        // the first expiry results in a retransmission
+        expire_next_timer(&mut controller).await;
+        assert!(retransmission_receiver.try_next().unwrap().is_some());
+        assert!(failed_transmission_receiver.try_next().is_err());
+
+        controller.process_action(Action::new_update_delay(
+            frag_id,
+            SphinxDelay::new_from_nanos(0),
+        ));
+        controller.process_action(Action::new_start_timer(frag_id));
+
+        // while the second one hits the cap
+        expire_next_timer(&mut controller).await;
+        assert!(retransmission_receiver.try_next().is_err());
+        assert!(controller.pending_acks_data.is_empty());
+
+        let failed = failed_transmission_receiver.try_next().unwrap().unwrap();
+        assert_eq!(failed.fragment_id, frag_id);
+        assert_eq!(failed.lane, lane);
+        assert_eq!(failed.retransmissions, 1);
+        assert_eq!(failed.recipient.to_bytes(), recipient.to_bytes());
+    }
+}
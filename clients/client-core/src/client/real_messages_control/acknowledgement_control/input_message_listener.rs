@@ -12,7 +12,8 @@ use client_connections::TransmissionLane;
 use futures::StreamExt;
 use log::*;
 use nymsphinx::anonymous_replies::ReplySurb;
-use nymsphinx::preparer::MessagePreparer;
+use nymsphinx::chunking::fragment::Fragment;
+use nymsphinx::preparer::{MessagePreparer, PreparedFragment};
 use nymsphinx::{acknowledgements::AckKey, addressing::clients::Recipient};
 use rand::{CryptoRng, Rng};
 use std::sync::Arc;
@@ -20,6 +21,11 @@ use std::sync::Arc;
 #[cfg(feature = "reply-surb")]
 use crate::client::reply_key_storage::ReplyKeyStorage;
 
+/// Number of fragments starting from which the message is going to be prepared in parallel.
+/// For smaller messages it's not worth the overhead of moving the work to another thread.
+#[cfg(not(target_arch = "wasm32"))]
+const PARALLEL_PREPARATION_THRESHOLD: usize = 16;
+
 /// Module responsible for dealing with the received messages: splitting them, creating acknowledgements,
 /// putting everything into sphinx packets, etc.
 /// It also makes an initial sending attempt for said messages.
@@ -104,6 +110,7 @@ where
         recipient: Recipient,
         content: Vec<u8>,
         with_reply_surb: bool,
+        lane: TransmissionLane,
     ) -> Option<Vec<RealMessage>> {
         log::trace!("handling msg size: {}", content.len());
         let topology_permit = self.topology_access.get_read_permit().await;
@@ -134,17 +141,59 @@ where
         let _reply_key = reply_key;
 
         // encrypt chunks, put them inside sphinx packets and generate acks
-        let mut pending_acks = Vec::with_capacity(split_message.len());
-        let mut real_messages = Vec::with_capacity(split_message.len());
-        for message_chunk in split_message {
-            // we need to clone it because we need to keep it in memory in case we had to retransmit
-            // it. And then we'd need to recreate entire ACK again.
-            let chunk_clone = message_chunk.clone();
-            let prepared_fragment = self
+        // note that we need to clone them because we need to keep them in memory in case we had to
+        // retransmit them. And then we'd need to recreate entire ACK again.
+        // big messages are prepared in parallel, away from the runtime, so that they wouldn't stall it
+        #[cfg(not(target_arch = "wasm32"))]
+        if split_message.len() >= PARALLEL_PREPARATION_THRESHOLD {
+            let prepared_fragments = self
                 .message_preparer
-                .prepare_chunk_for_sending(chunk_clone, topology, &self.ack_key, &recipient)
+                .prepare_chunks_in_background(
+                    split_message.clone(),
+                    topology.clone(),
+                    Arc::clone(&self.ack_key),
+                    recipient,
+                    &mut Vec::new(),
+                )
+                .await
                 .unwrap();
+            return Some(self.insert_pending_acks(
+                split_message,
+                prepared_fragments,
+                recipient,
+                lane,
+            ));
+        }
 
+        let prepared_fragments = split_message
+            .iter()
+            .map(|message_chunk| {
+                self.message_preparer
+                    .prepare_chunk_for_sending(
+                        message_chunk.clone(),
+                        topology,
+                        &self.ack_key,
+                        &recipient,
+                    )
+                    .unwrap()
+            })
+            .collect();
+
+        Some(self.insert_pending_acks(split_message, prepared_fragments, recipient, lane))
+    }
+
+    /// Tells the action controller to start waiting for the acknowledgements of the prepared
+    /// fragments and returns the corresponding messages to be sent out.
+    fn insert_pending_acks(
+        &self,
+        fragments: Vec<Fragment>,
+        prepared_fragments: Vec<PreparedFragment>,
+        recipient: Recipient,
+        lane: TransmissionLane,
+    ) -> Vec<RealMessage> {
+        let mut pending_acks = Vec::with_capacity(fragments.len());
+        let mut real_messages = Vec::with_capacity(fragments.len());
+        for (message_chunk, prepared_fragment) in fragments.into_iter().zip(prepared_fragments) {
             real_messages.push(RealMessage::new(
                 prepared_fragment.mix_packet,
                 message_chunk.fragment_identifier(),
@@ -154,6 +203,7 @@ where
                 message_chunk,
                 prepared_fragment.total_delay,
                 recipient,
+                lane,
             ));
         }
 
@@ -162,7 +212,7 @@ where
             .unbounded_send(Action::new_insert(pending_acks))
             .unwrap();
 
-        Some(real_messages)
+        real_messages
     }
 
     async fn on_input_message(&mut self, msg: InputMessage) {
@@ -173,7 +223,7 @@ where
                 with_reply_surb,
                 lane,
             } => (
-                self.handle_fresh_message(recipient, data, with_reply_surb)
+                self.handle_fresh_message(recipient, data, with_reply_surb, lane)
                     .await,
                 lane,
             ),
@@ -8,14 +8,16 @@ use self::{
     sent_notification_listener::SentNotificationListener,
 };
 use super::real_traffic_stream::BatchRealMessageSender;
+use super::FailedTransmissionSender;
 use crate::client::{inbound_messages::InputMessageReceiver, topology_control::TopologyAccessor};
 use crate::spawn_future;
+use client_connections::TransmissionLane;
 use futures::channel::mpsc;
 use gateway_client::AcknowledgementReceiver;
 use log::*;
-use nymsphinx::params::PacketSize;
+use nymsphinx::params::{PacketMode, PacketSize};
 use nymsphinx::{
-    acknowledgements::AckKey,
+    acknowledgements::{AckDelayDistribution, AckKey},
     addressing::clients::Recipient,
     chunking::fragment::{Fragment, FragmentIdentifier},
     preparer::MessagePreparer,
@@ -35,6 +37,7 @@ mod action_controller;
 mod input_message_listener;
 mod retransmission_request_listener;
 mod sent_notification_listener;
+mod timeout_estimator;
 
 /// Channel used for indicating that the particular `Fragment` should be retransmitted.
 type RetransmissionRequestSender = mpsc::UnboundedSender<Weak<PendingAcknowledgement>>;
@@ -56,15 +59,22 @@ pub(crate) struct PendingAcknowledgement {
     message_chunk: Fragment,
     delay: SphinxDelay,
     recipient: Recipient,
+    lane: TransmissionLane,
 }
 
 impl PendingAcknowledgement {
     /// Creates new instance of `PendingAcknowledgement` using the provided data.
-    fn new(message_chunk: Fragment, delay: SphinxDelay, recipient: Recipient) -> Self {
+    fn new(
+        message_chunk: Fragment,
+        delay: SphinxDelay,
+        recipient: Recipient,
+        lane: TransmissionLane,
+    ) -> Self {
         PendingAcknowledgement {
             message_chunk,
             delay,
             recipient,
+            lane,
         }
     }
 
@@ -91,6 +101,10 @@ pub(super) struct AcknowledgementControllerConnectors {
 
     /// Channel used for receiving acknowledgements from the mix network.
     ack_receiver: AcknowledgementReceiver,
+
+    /// Optional channel used for notifying about packets that were given up on after
+    /// the maximum number of retransmissions.
+    failed_transmission_sender: Option<FailedTransmissionSender>,
 }
 
 impl AcknowledgementControllerConnectors {
@@ -99,12 +113,14 @@ impl AcknowledgementControllerConnectors {
         input_receiver: InputMessageReceiver,
         sent_notifier: SentPacketNotificationReceiver,
         ack_receiver: AcknowledgementReceiver,
+        failed_transmission_sender: Option<FailedTransmissionSender>,
     ) -> Self {
         AcknowledgementControllerConnectors {
             real_message_sender,
             input_receiver,
             sent_notifier,
             ack_receiver,
+            failed_transmission_sender,
         }
     }
 }
@@ -117,29 +133,37 @@ pub(super) struct Config {
     /// Given ack timeout in the form a * BASE_DELAY + b, it specifies the multiplier `a`
     ack_wait_multiplier: f64,
 
-    /// Average delay an acknowledgement packet is going to get delayed at a single mixnode.
-    average_ack_delay: Duration,
+    /// Distribution of the delays an acknowledgement packet is going to get at each mixnode.
+    ack_delay_distribution: AckDelayDistribution,
 
     /// Average delay a data packet is going to get delayed at a single mixnode.
     average_packet_delay: Duration,
 
     /// Predefined packet size used for the encapsulated messages.
     packet_size: PacketSize,
+
+    /// Mode, and consequently the format, of the packets sent out.
+    packet_mode: PacketMode,
+
+    /// Maximum number of retransmissions of a packet before it's considered lost.
+    maximum_retransmissions: Option<u32>,
 }
 
 impl Config {
     pub(super) fn new(
         ack_wait_addition: Duration,
         ack_wait_multiplier: f64,
-        average_ack_delay: Duration,
+        ack_delay_distribution: AckDelayDistribution,
         average_packet_delay: Duration,
     ) -> Self {
         Config {
             ack_wait_addition,
             ack_wait_multiplier,
-            average_ack_delay,
+            ack_delay_distribution,
             average_packet_delay,
             packet_size: Default::default(),
+            packet_mode: Default::default(),
+            maximum_retransmissions: None,
         }
     }
 
@@ -147,6 +171,16 @@ impl Config {
         self.packet_size = packet_size;
         self
     }
+
+    pub fn with_custom_packet_mode(mut self, packet_mode: PacketMode) -> Self {
+        self.packet_mode = packet_mode;
+        self
+    }
+
+    pub fn with_maximum_retransmissions(mut self, maximum_retransmissions: Option<u32>) -> Self {
+        self.maximum_retransmissions = maximum_retransmissions;
+        self
+    }
 }
 
 pub(super) struct AcknowledgementController<R>
@@ -176,18 +210,25 @@ where
     ) -> Self {
         let (retransmission_tx, retransmission_rx) = mpsc::unbounded();
 
-        let action_config =
-            action_controller::Config::new(config.ack_wait_addition, config.ack_wait_multiplier);
-        let (action_controller, action_sender) =
-            ActionController::new(action_config, retransmission_tx);
+        let action_config = action_controller::Config::new(
+            config.ack_wait_addition,
+            config.ack_wait_multiplier,
+            config.maximum_retransmissions,
+        );
+        let (action_controller, action_sender) = ActionController::new(
+            action_config,
+            retransmission_tx,
+            connectors.failed_transmission_sender,
+        );
 
         let message_preparer = MessagePreparer::new(
             rng,
             ack_recipient,
             config.average_packet_delay,
-            config.average_ack_delay,
+            config.ack_delay_distribution,
         )
-        .with_custom_real_message_packet_size(config.packet_size);
+        .with_custom_real_message_packet_size(config.packet_size)
+        .with_packet_mode(config.packet_mode);
 
         // will listen for any acks coming from the network
         let acknowledgement_listener = AcknowledgementListener::new(
@@ -0,0 +1,365 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use nymsphinx::addressing::nodes::NodeIdentity;
+use nymsphinx::Delay as SphinxDelay;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Number of observed round trip times required before we trust our estimate more than
+// the configured values.
+const MIN_SAMPLES: u32 = 16;
+// Weight of a new sample in the moving averages once we have collected enough of them.
+const SAMPLE_WEIGHT: f64 = 1.0 / 32.0;
+// Number of standard deviations of the residual error added on top of the expected round trip time.
+const DEVIATION_FACTOR: f64 = 4.0;
+// Bounds of the estimated multiplier. The acks can't arrive any faster than the delays put
+// in their headers and anything above the upper bound is not explained by the delays anymore.
+const MIN_MULTIPLIER: f64 = 1.0;
+const MAX_MULTIPLIER: f64 = 3.0;
+// Gains of the smoothed round trip time and its variation, as recommended by RFC 6298.
+const SRTT_GAIN: f64 = 1.0 / 8.0;
+const RTTVAR_GAIN: f64 = 1.0 / 4.0;
+// Lower bound on the variation part of the timeout so that a perfectly stable gateway
+// wouldn't cause spurious retransmissions on the slightest hiccup.
+const MIN_VARIATION_MARGIN: Duration = Duration::from_millis(100);
+// Retransmission timeouts stop being doubled after this many retransmissions.
+const MAX_BACKOFF_EXPONENT: u32 = 6;
+
+/// TCP-style smoothed estimate of the part of the round trip time that is not explained
+/// by the delays put in the packets, i.e. the latency of the gateway and the network itself.
+struct SmoothedRtt {
+    srtt: f64,
+    rttvar: f64,
+}
+
+impl SmoothedRtt {
+    fn new(residual: f64) -> Self {
+        SmoothedRtt {
+            srtt: residual,
+            rttvar: residual / 2.0,
+        }
+    }
+
+    fn update(&mut self, residual: f64) {
+        self.rttvar =
+            (1.0 - RTTVAR_GAIN) * self.rttvar + RTTVAR_GAIN * (self.srtt - residual).abs();
+        self.srtt = (1.0 - SRTT_GAIN) * self.srtt + SRTT_GAIN * residual;
+    }
+
+    fn margin(&self) -> f64 {
+        self.srtt + (DEVIATION_FACTOR * self.rttvar).max(MIN_VARIATION_MARGIN.as_secs_f64())
+    }
+}
+
+/// Estimates the timeout after which a sent packet is assumed to be lost, in the form of
+/// `a * BASE_DELAY + b`, where `BASE_DELAY` is the sum of the delays of the packet and its ack.
+///
+/// Both `a` and `b` are estimated from the round trip times of the previously acknowledged
+/// packets by regressing them on their base delays. As those delays are drawn from the
+/// distributions chosen for the packets and the acks, the estimate automatically adjusts
+/// to whichever of them is used.
+/// Until enough round trip times are observed, the configured `a` and `b` are used instead.
+///
+/// Once `a` is known, `b` is further refined for each gateway the packets are sent to,
+/// in the same way TCP smooths its round trip time and variance, as different recipients
+/// might be behind gateways with very different latencies.
+/// Finally, the timeout of a retransmitted packet is exponentially backed off.
+pub(super) struct TimeoutEstimator {
+    /// Given ack timeout in the form a * BASE_DELAY + b, it specifies the initial additive part `b`
+    ack_wait_addition: Duration,
+
+    /// Given ack timeout in the form a * BASE_DELAY + b, it specifies the initial multiplier `a`
+    ack_wait_multiplier: f64,
+
+    /// Number of observed round trip times.
+    samples: u32,
+
+    // exponentially weighted moments of the base delays (x) and the round trip times (y), in seconds
+    mean_delay: f64,
+    mean_rtt: f64,
+    delay_variance: f64,
+    rtt_variance: f64,
+    covariance: f64,
+
+    /// Smoothed round trip times of the packets sent through particular gateways.
+    gateways: HashMap<[u8; 32], SmoothedRtt>,
+}
+
+impl TimeoutEstimator {
+    pub(super) fn new(ack_wait_addition: Duration, ack_wait_multiplier: f64) -> Self {
+        TimeoutEstimator {
+            ack_wait_addition,
+            ack_wait_multiplier,
+            samples: 0,
+            mean_delay: 0.0,
+            mean_rtt: 0.0,
+            delay_variance: 0.0,
+            rtt_variance: 0.0,
+            covariance: 0.0,
+            gateways: HashMap::new(),
+        }
+    }
+
+    /// Records round trip time of a packet with the given base delay, sent through the given
+    /// gateway, that was acknowledged after being sent exactly once.
+    pub(super) fn record(&mut self, gateway: &NodeIdentity, delay: &SphinxDelay, rtt: Duration) {
+        self.samples = self.samples.saturating_add(1);
+        // until we have enough samples, all of them are weighted equally
+        let weight = (1.0 / self.samples as f64).max(SAMPLE_WEIGHT);
+
+        let delay_diff = delay.to_duration().as_secs_f64() - self.mean_delay;
+        let rtt_diff = rtt.as_secs_f64() - self.mean_rtt;
+
+        self.mean_delay += weight * delay_diff;
+        self.mean_rtt += weight * rtt_diff;
+        self.delay_variance = (1.0 - weight) * (self.delay_variance + weight * delay_diff.powi(2));
+        self.rtt_variance = (1.0 - weight) * (self.rtt_variance + weight * rtt_diff.powi(2));
+        self.covariance = (1.0 - weight) * (self.covariance + weight * delay_diff * rtt_diff);
+
+        if let Some(multiplier) = self.multiplier() {
+            let residual =
+                (rtt.as_secs_f64() - multiplier * delay.to_duration().as_secs_f64()).max(0.0);
+            self.gateways
+                .entry(gateway.to_bytes())
+                .and_modify(|smoothed| smoothed.update(residual))
+                .or_insert_with(|| SmoothedRtt::new(residual));
+        }
+    }
+
+    /// Returns the estimated `a`, if enough round trip times were observed already.
+    fn multiplier(&self) -> Option<f64> {
+        if self.samples < MIN_SAMPLES {
+            return None;
+        }
+
+        if self.delay_variance > f64::EPSILON {
+            Some((self.covariance / self.delay_variance).clamp(MIN_MULTIPLIER, MAX_MULTIPLIER))
+        } else {
+            // all packets had the same delay so we can't tell how the rtt depends on it
+            Some(MIN_MULTIPLIER)
+        }
+    }
+
+    /// Returns the `b` estimated from all the packets, including the margin for the error.
+    fn addition(&self, multiplier: f64) -> f64 {
+        let addition = (self.mean_rtt - multiplier * self.mean_delay).max(0.0);
+        let residual_variance = self.rtt_variance - 2.0 * multiplier * self.covariance
+            + multiplier.powi(2) * self.delay_variance;
+
+        addition + DEVIATION_FACTOR * residual_variance.max(0.0).sqrt()
+    }
+
+    /// Returns the timeout for a packet with the given base delay, sent through the given gateway,
+    /// that has already been retransmitted the specified number of times.
+    pub(super) fn timeout(
+        &self,
+        gateway: &NodeIdentity,
+        delay: &SphinxDelay,
+        retransmissions: u32,
+    ) -> Duration {
+        let timeout = match self.multiplier() {
+            None => {
+                (delay.clone() * self.ack_wait_multiplier).to_duration() + self.ack_wait_addition
+            }
+            Some(multiplier) => {
+                let addition = match self.gateways.get(&gateway.to_bytes()) {
+                    Some(smoothed) => smoothed.margin(),
+                    None => self.addition(multiplier),
+                };
+                (delay.clone() * multiplier).to_duration() + Duration::from_secs_f64(addition)
+            }
+        };
+
+        timeout * (1 << retransmissions.min(MAX_BACKOFF_EXPONENT))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::asymmetric::identity;
+    use nymsphinx::acknowledgements::AckDelayDistribution;
+    use rand::rngs::OsRng;
+    use rand::Rng;
+
+    const ACK_WAIT_ADDITION: Duration = Duration::from_millis(1_500);
+    const ACK_WAIT_MULTIPLIER: f64 = 1.5;
+
+    fn gateway() -> NodeIdentity {
+        *identity::KeyPair::new(&mut OsRng).public_key()
+    }
+
+    fn delay(millis: u64) -> SphinxDelay {
+        SphinxDelay::new_from_nanos(millis * 1_000_000)
+    }
+
+    fn assert_close(actual: Duration, expected: Duration) {
+        let difference = actual.as_secs_f64() - expected.as_secs_f64();
+        assert!(
+            difference.abs() < 0.01,
+            "{:?} is not close to {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn configured_values_are_used_until_enough_samples_are_observed() {
+        let gateway = gateway();
+        let mut estimator = TimeoutEstimator::new(ACK_WAIT_ADDITION, ACK_WAIT_MULTIPLIER);
+
+        for i in 1..MIN_SAMPLES as u64 {
+            estimator.record(&gateway, &delay(100 * i), Duration::from_millis(10 * i));
+        }
+        assert_eq!(
+            estimator.timeout(&gateway, &delay(1_000), 0),
+            Duration::from_millis(1_500) + ACK_WAIT_ADDITION
+        );
+    }
+
+    #[test]
+    fn regression_recovers_linear_dependency_on_delays() {
+        let gateway = gateway();
+        let mut estimator = TimeoutEstimator::new(ACK_WAIT_ADDITION, ACK_WAIT_MULTIPLIER);
+
+        // every round trip takes twice the delays plus 300ms
+        for i in 1..=64u64 {
+            let millis = (i * 37) % 1_000 + 100;
+            estimator.record(
+                &gateway,
+                &delay(millis),
+                Duration::from_millis(2 * millis + 300),
+            );
+        }
+
+        let multiplier = estimator.multiplier().unwrap();
+        assert!((multiplier - 2.0).abs() < 1e-6);
+
+        // the perfectly stable latency only gets the minimum variation margin on top
+        assert_close(
+            estimator.timeout(&gateway, &delay(500), 0),
+            Duration::from_millis(1_000 + 300) + MIN_VARIATION_MARGIN,
+        );
+    }
+
+    #[test]
+    fn multiplier_is_clamped() {
+        let gateway = gateway();
+        let mut estimator = TimeoutEstimator::new(ACK_WAIT_ADDITION, ACK_WAIT_MULTIPLIER);
+
+        // the round trip times grow much faster than the delays could explain
+        for i in 1..=32u64 {
+            estimator.record(&gateway, &delay(10 * i), Duration::from_millis(100 * i));
+        }
+        assert_eq!(estimator.multiplier(), Some(MAX_MULTIPLIER));
+    }
+
+    #[test]
+    fn round_trip_times_are_smoothed_per_gateway() {
+        let fast_gateway = gateway();
+        let slow_gateway = gateway();
+        let unknown_gateway = gateway();
+        let mut estimator = TimeoutEstimator::new(ACK_WAIT_ADDITION, ACK_WAIT_MULTIPLIER);
+
+        for i in 1..=64u64 {
+            let millis = (i * 37) % 1_000 + 100;
+            let (gateway, latency) = if i % 2 == 0 {
+                (&fast_gateway, 100)
+            } else {
+                (&slow_gateway, 2_000)
+            };
+            estimator.record(
+                gateway,
+                &delay(millis),
+                Duration::from_millis(2 * millis + latency),
+            );
+        }
+
+        let fast = estimator.timeout(&fast_gateway, &delay(500), 0);
+        let slow = estimator.timeout(&slow_gateway, &delay(500), 0);
+        let unknown = estimator.timeout(&unknown_gateway, &delay(500), 0);
+
+        assert!(fast < slow);
+        assert!(fast < Duration::from_millis(2 * 500 + 2_000));
+        assert!(slow > Duration::from_millis(2 * 500 + 2_000));
+        // without any samples of its own, a gateway falls back to the estimate based on all of them
+        assert_close(
+            unknown,
+            (delay(500) * estimator.multiplier().unwrap()).to_duration()
+                + Duration::from_secs_f64(estimator.addition(estimator.multiplier().unwrap())),
+        );
+    }
+
+    // simulates acknowledged packets whose base delays are drawn from the provided distribution
+    // for the 3 hops of the packet and the 3 hops of its ack, with a gateway latency between
+    // 200ms and 250ms, and checks the timeouts of the subsequent packets
+    fn check_convergence(distribution: AckDelayDistribution) {
+        let gateway = gateway();
+        let mut estimator = TimeoutEstimator::new(ACK_WAIT_ADDITION, ACK_WAIT_MULTIPLIER);
+
+        let mut sample = || {
+            let base_delay: Duration = distribution
+                .generate_delays(&mut OsRng, 6)
+                .iter()
+                .map(SphinxDelay::to_duration)
+                .sum();
+            let latency = Duration::from_millis(OsRng.gen_range(200, 251));
+            (
+                SphinxDelay::new_from_nanos(base_delay.as_nanos() as u64),
+                base_delay + latency,
+            )
+        };
+
+        for _ in 0..1_000 {
+            let (delay, rtt) = sample();
+            estimator.record(&gateway, &delay, rtt);
+        }
+
+        let multiplier = estimator.multiplier().unwrap();
+        assert!(
+            (multiplier - 1.0).abs() < 0.1,
+            "multiplier {} has not converged",
+            multiplier
+        );
+
+        let mut total_slack = Duration::ZERO;
+        for _ in 0..1_000 {
+            let (delay, rtt) = sample();
+            let timeout = estimator.timeout(&gateway, &delay, 0);
+            // no ack should be considered lost prematurely
+            assert!(timeout >= rtt, "{:?} timed out after {:?}", rtt, timeout);
+            total_slack += timeout - rtt;
+        }
+        // while not waiting for the lost ones for much longer than necessary
+        assert!(total_slack / 1_000 < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn timeouts_converge_with_exponential_ack_delays() {
+        check_convergence(AckDelayDistribution::Exponential(Duration::from_millis(50)))
+    }
+
+    #[test]
+    fn timeouts_converge_with_uniform_ack_delays() {
+        check_convergence(AckDelayDistribution::Uniform(Duration::from_millis(50)))
+    }
+
+    #[test]
+    fn retransmission_timeouts_are_exponentially_backed_off() {
+        let gateway = gateway();
+        let estimator = TimeoutEstimator::new(ACK_WAIT_ADDITION, ACK_WAIT_MULTIPLIER);
+
+        let initial = estimator.timeout(&gateway, &delay(1_000), 0);
+        assert_eq!(estimator.timeout(&gateway, &delay(1_000), 1), initial * 2);
+        assert_eq!(estimator.timeout(&gateway, &delay(1_000), 3), initial * 8);
+
+        // the backoff stops growing at some point
+        let capped = initial * (1 << MAX_BACKOFF_EXPONENT);
+        assert_eq!(
+            estimator.timeout(&gateway, &delay(1_000), MAX_BACKOFF_EXPONENT),
+            capped
+        );
+        assert_eq!(estimator.timeout(&gateway, &delay(1_000), 100), capped);
+    }
+}
@@ -14,13 +14,14 @@ use crate::client::{
     topology_control::TopologyAccessor,
 };
 use crate::spawn_future;
-use client_connections::ClosedConnectionReceiver;
+use client_connections::{ClosedConnectionReceiver, TransmissionLane};
 use futures::channel::mpsc;
 use gateway_client::AcknowledgementReceiver;
 use log::*;
-use nymsphinx::acknowledgements::AckKey;
+use nymsphinx::acknowledgements::{AckDelayDistribution, AckKey};
 use nymsphinx::addressing::clients::Recipient;
-use nymsphinx::params::PacketSize;
+use nymsphinx::chunking::fragment::FragmentIdentifier;
+use nymsphinx::params::{PacketMode, PacketSize};
 use rand::{rngs::OsRng, CryptoRng, Rng};
 use std::sync::Arc;
 use std::time::Duration;
@@ -31,6 +32,19 @@ use crate::client::reply_key_storage::ReplyKeyStorage;
 mod acknowledgement_control;
 mod real_traffic_stream;
 
+/// Notification about a message fragment that has never been acknowledged, despite being
+/// retransmitted the maximum allowed number of times, and hence is considered lost.
+#[derive(Debug)]
+pub struct FailedTransmission {
+    pub recipient: Recipient,
+    pub lane: TransmissionLane,
+    pub fragment_id: FragmentIdentifier,
+    pub retransmissions: u32,
+}
+
+pub type FailedTransmissionSender = mpsc::UnboundedSender<FailedTransmission>;
+pub type FailedTransmissionReceiver = mpsc::UnboundedReceiver<FailedTransmission>;
+
 // TODO: ack_key and self_recipient shouldn't really be part of this config
 pub struct Config {
     /// Key used to decrypt contents of received SURBAcks
@@ -51,8 +65,8 @@ pub struct Config {
     /// Average delay a data packet is going to get delayed at a single mixnode.
     average_packet_delay_duration: Duration,
 
-    /// Average delay an acknowledgement packet is going to get delayed at a single mixnode.
-    average_ack_delay_duration: Duration,
+    /// Distribution of the delays an acknowledgement packet is going to get at each mixnode.
+    ack_delay_distribution: AckDelayDistribution,
 
     /// Controls whether the main packet stream constantly produces packets according to the predefined
     /// poisson distribution.
@@ -60,6 +74,16 @@ pub struct Config {
 
     /// Predefined packet size used for the encapsulated messages.
     packet_size: PacketSize,
+
+    /// Mode, and consequently the format, of the packets sent out.
+    packet_mode: PacketMode,
+
+    /// Maximum number of retransmissions of a packet before it's considered lost.
+    /// If not set, the packets are retransmitted until they get acknowledged.
+    maximum_retransmissions: Option<u32>,
+
+    /// Channel used for notifying about the packets that were considered lost.
+    failed_transmission_sender: Option<FailedTransmissionSender>,
 }
 
 impl Config {
@@ -69,7 +93,7 @@ impl Config {
         ack_key: Arc<AckKey>,
         ack_wait_multiplier: f64,
         ack_wait_addition: Duration,
-        average_ack_delay_duration: Duration,
+        ack_delay_distribution: AckDelayDistribution,
         average_message_sending_delay: Duration,
         average_packet_delay_duration: Duration,
         disable_main_poisson_packet_distribution: bool,
@@ -82,15 +106,38 @@ impl Config {
             self_recipient,
             average_message_sending_delay,
             average_packet_delay_duration,
-            average_ack_delay_duration,
+            ack_delay_distribution,
             disable_main_poisson_packet_distribution,
             packet_size: Default::default(),
+            packet_mode: Default::default(),
+            maximum_retransmissions: None,
+            failed_transmission_sender: None,
         }
     }
 
     pub fn set_custom_packet_size(&mut self, packet_size: PacketSize) {
         self.packet_size = packet_size;
     }
+
+    pub fn set_custom_packet_mode(&mut self, packet_mode: PacketMode) {
+        self.packet_mode = packet_mode;
+    }
+
+    /// Sets the maximum number of retransmissions of a packet, where 0 means no limit.
+    pub fn set_maximum_retransmissions(&mut self, maximum_retransmissions: u32) {
+        self.maximum_retransmissions = if maximum_retransmissions == 0 {
+            None
+        } else {
+            Some(maximum_retransmissions)
+        };
+    }
+
+    pub fn set_failed_transmission_sender(
+        &mut self,
+        failed_transmission_sender: FailedTransmissionSender,
+    ) {
+        self.failed_transmission_sender = Some(failed_transmission_sender);
+    }
 }
 
 pub struct RealMessagesController<R>
@@ -123,15 +170,18 @@ impl RealMessagesController<OsRng> {
             input_receiver,
             sent_notifier_rx,
             ack_receiver,
+            config.failed_transmission_sender,
         );
 
         let ack_control_config = acknowledgement_control::Config::new(
             config.ack_wait_addition,
             config.ack_wait_multiplier,
-            config.average_ack_delay_duration,
+            config.ack_delay_distribution,
             config.average_packet_delay_duration,
         )
-        .with_custom_packet_size(config.packet_size);
+        .with_custom_packet_size(config.packet_size)
+        .with_custom_packet_mode(config.packet_mode)
+        .with_maximum_retransmissions(config.maximum_retransmissions);
 
         let ack_control = AcknowledgementController::new(
             ack_control_config,
@@ -145,12 +195,13 @@ impl RealMessagesController<OsRng> {
         );
 
         let out_queue_config = real_traffic_stream::Config::new(
-            config.average_ack_delay_duration,
+            config.ack_delay_distribution,
             config.average_packet_delay_duration,
             config.average_message_sending_delay,
             config.disable_main_poisson_packet_distribution,
         )
-        .with_custom_cover_packet_size(config.packet_size);
+        .with_custom_cover_packet_size(config.packet_size)
+        .with_custom_cover_packet_mode(config.packet_mode);
 
         let out_queue_control = OutQueueControl::new(
             out_queue_config,
@@ -9,12 +9,12 @@ use futures::channel::mpsc;
 use futures::task::{Context, Poll};
 use futures::{Future, Stream, StreamExt};
 use log::*;
-use nymsphinx::acknowledgements::AckKey;
+use nymsphinx::acknowledgements::{AckDelayDistribution, AckKey};
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::chunking::fragment::FragmentIdentifier;
 use nymsphinx::cover::generate_loop_cover_packet;
 use nymsphinx::forwarding::packet::MixPacket;
-use nymsphinx::params::PacketSize;
+use nymsphinx::params::{PacketMode, PacketSize};
 use nymsphinx::utils::sample_poisson_duration;
 use rand::{CryptoRng, Rng};
 use std::pin::Pin;
@@ -46,8 +46,8 @@ fn get_time_now() -> wasm_timer::Instant {
 
 /// Configurable parameters of the `OutQueueControl`
 pub(crate) struct Config {
-    /// Average delay an acknowledgement packet is going to get delay at a single mixnode.
-    average_ack_delay: Duration,
+    /// Distribution of the delays an acknowledgement packet is going to get at each mixnode.
+    ack_delay_distribution: AckDelayDistribution,
 
     /// Average delay a data packet is going to get delay at a single mixnode.
     average_packet_delay: Duration,
@@ -61,21 +61,25 @@ pub(crate) struct Config {
 
     /// Predefined packet size used for the loop cover messages.
     cover_packet_size: PacketSize,
+
+    /// Mode of the loop cover messages, which has to be the same as of the real ones.
+    cover_packet_mode: PacketMode,
 }
 
 impl Config {
     pub(crate) fn new(
-        average_ack_delay: Duration,
+        ack_delay_distribution: AckDelayDistribution,
         average_packet_delay: Duration,
         average_message_sending_delay: Duration,
         disable_poisson_packet_distribution: bool,
     ) -> Self {
         Config {
-            average_ack_delay,
+            ack_delay_distribution,
             average_packet_delay,
             average_message_sending_delay,
             disable_poisson_packet_distribution,
             cover_packet_size: Default::default(),
+            cover_packet_mode: Default::default(),
         }
     }
 
@@ -83,6 +87,11 @@ impl Config {
         self.cover_packet_size = packet_size;
         self
     }
+
+    pub fn with_custom_cover_packet_mode(mut self, packet_mode: PacketMode) -> Self {
+        self.cover_packet_mode = packet_mode;
+        self
+    }
 }
 
 pub(crate) struct OutQueueControl<R>
@@ -231,9 +240,10 @@ where
                         topology_ref,
                         &self.ack_key,
                         &self.our_full_destination,
-                        self.config.average_ack_delay,
+                        self.config.ack_delay_distribution,
                         self.config.average_packet_delay,
                         self.config.cover_packet_size,
+                        self.config.cover_packet_mode,
                     )
                     .expect(
                         "Somehow failed to generate a loop cover message with a valid topology",
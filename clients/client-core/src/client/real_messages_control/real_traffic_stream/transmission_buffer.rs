@@ -66,7 +66,7 @@ impl TransmissionBuffer {
                 lane_buffer_entry
                     .real_messages
                     .iter()
-                    .map(|real_message| real_message.mix_packet.sphinx_packet().len())
+                    .map(|real_message| real_message.mix_packet.packet().len())
                     .sum::<usize>()
             })
             .sum()
@@ -0,0 +1,151 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::client::reply_key_storage::ReplyKeyStorage;
+use crate::client::topology_control::TopologyAccessor;
+use crate::error::ClientCoreError;
+use client_connections::ConnectionId;
+use log::*;
+use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::anonymous_replies::encryption_key::EncryptionKeyDigest;
+use nymsphinx::anonymous_replies::ReplySurb;
+use nymsphinx::params::PacketMode;
+use rand::rngs::OsRng;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Creates reply SURBs addressed to this client, so that they could be handed over to other
+/// parties wishing to send us messages without learning our address. Note that, unlike the
+/// regular messages, the replies sent with those SURBs are not acknowledged nor retransmitted.
+///
+/// The SURBs are always generated for a particular connection and the keys of the ones that were
+/// never used are removed from the storage once the connection is closed.
+/// Note that cloning the generator produces a handle sharing the same set of connections.
+#[derive(Clone)]
+pub struct ReplySurbGenerator {
+    self_recipient: Recipient,
+    average_packet_delay: Duration,
+    packet_mode: PacketMode,
+    topology_access: TopologyAccessor,
+    reply_key_storage: ReplyKeyStorage,
+
+    /// Digests of the keys of all reply SURBs handed out for each open connection.
+    issued_keys: Arc<Mutex<HashMap<ConnectionId, Vec<EncryptionKeyDigest>>>>,
+}
+
+impl ReplySurbGenerator {
+    pub fn new(
+        self_recipient: Recipient,
+        average_packet_delay: Duration,
+        topology_access: TopologyAccessor,
+        reply_key_storage: ReplyKeyStorage,
+    ) -> Self {
+        ReplySurbGenerator {
+            self_recipient,
+            average_packet_delay,
+            packet_mode: PacketMode::Mix,
+            topology_access,
+            reply_key_storage,
+            issued_keys: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn with_packet_mode(mut self, packet_mode: PacketMode) -> Self {
+        self.packet_mode = packet_mode;
+        self
+    }
+
+    /// Generates the reply SURBs for a newly opened connection.
+    pub async fn generate_initial_reply_surbs(
+        &mut self,
+        connection_id: ConnectionId,
+        amount: usize,
+    ) -> Result<Vec<ReplySurb>, ClientCoreError> {
+        // the unwrap is fine as new connections are always accepted
+        Ok(self
+            .generate_reply_surbs(connection_id, amount, true)
+            .await?
+            .unwrap())
+    }
+
+    /// Generates more reply SURBs for an existing connection. Returns `None` if the connection
+    /// has already been closed, as nobody would be able to receive the replies anymore.
+    pub async fn generate_more_reply_surbs(
+        &mut self,
+        connection_id: ConnectionId,
+        amount: usize,
+    ) -> Result<Option<Vec<ReplySurb>>, ClientCoreError> {
+        self.generate_reply_surbs(connection_id, amount, false)
+            .await
+    }
+
+    /// Removes the keys of all the reply SURBs of the closed connection that were never used.
+    pub fn close_connection(&self, connection_id: ConnectionId) {
+        let issued = self
+            .issued_keys
+            .lock()
+            .expect("reply SURB generator mutex got poisoned")
+            .remove(&connection_id)
+            .unwrap_or_default();
+
+        for digest in issued {
+            // the keys of the used SURBs are already gone
+            if let Err(err) = self.reply_key_storage.get_and_remove_encryption_key(digest) {
+                warn!("Failed to remove unused reply SURB key of connection {connection_id} - {err:?}");
+            }
+        }
+    }
+
+    /// Generates the specified number of reply SURBs, storing their encryption keys so that
+    /// the replies could be decrypted upon being received.
+    async fn generate_reply_surbs(
+        &mut self,
+        connection_id: ConnectionId,
+        amount: usize,
+        new_connection: bool,
+    ) -> Result<Option<Vec<ReplySurb>>, ClientCoreError> {
+        let topology_permit = self.topology_access.get_read_permit().await;
+        let topology = topology_permit
+            .try_get_valid_topology_ref(&self.self_recipient, None)
+            .ok_or(ClientCoreError::InsufficientNetworkTopology)?;
+
+        let mut reply_surbs = Vec::with_capacity(amount);
+        for _ in 0..amount {
+            let reply_surb = ReplySurb::construct(
+                &mut OsRng,
+                &self.self_recipient,
+                self.average_packet_delay,
+                topology,
+                self.packet_mode,
+            )
+            .map_err(|_| ClientCoreError::InsufficientNetworkTopology)?;
+            reply_surbs.push(reply_surb);
+        }
+
+        // the keys are only stored once we know the connection is still open,
+        // as otherwise nothing would ever remove them
+        let mut issued_keys = self
+            .issued_keys
+            .lock()
+            .expect("reply SURB generator mutex got poisoned");
+        let issued = if new_connection {
+            issued_keys.entry(connection_id).or_default()
+        } else {
+            match issued_keys.get_mut(&connection_id) {
+                Some(issued) => issued,
+                None => return Ok(None),
+            }
+        };
+
+        for reply_surb in &reply_surbs {
+            let encryption_key = reply_surb.encryption_key();
+            issued.push(encryption_key.compute_digest());
+            self.reply_key_storage
+                .insert_encryption_key(encryption_key.clone())
+                .expect("Failed to insert surb reply key to the store!");
+        }
+
+        Ok(Some(reply_surbs))
+    }
+}
@@ -12,7 +12,7 @@ use std::sync::Arc;
 use std::time;
 use std::time::Duration;
 use tokio::sync::{RwLock, RwLockReadGuard};
-use topology::{nym_topology_from_detailed, NymTopology};
+use topology::{nym_topology_from_detailed, sphinx_key_epoch, NymTopology, SphinxKeyEpoch};
 use url::Url;
 
 // I'm extremely curious why compiler NEVER complained about lack of Debug here before
@@ -117,6 +117,16 @@ impl Default for TopologyAccessor {
     }
 }
 
+fn current_sphinx_key_epoch() -> SphinxKeyEpoch {
+    #[cfg(not(target_arch = "wasm32"))]
+    let since_unix_epoch = time::SystemTime::now().duration_since(time::UNIX_EPOCH);
+
+    #[cfg(target_arch = "wasm32")]
+    let since_unix_epoch = wasm_timer::SystemTime::now().duration_since(wasm_timer::UNIX_EPOCH);
+
+    sphinx_key_epoch(since_unix_epoch.unwrap_or_default().as_secs())
+}
+
 pub struct TopologyRefresherConfig {
     validator_api_urls: Vec<Url>,
     refresh_rate: time::Duration,
@@ -255,9 +265,30 @@ impl TopologyRefresher {
             Ok(gateways) => gateways,
         };
 
+        // nodes always accept packets encrypted with their static keys, so if the rotated ones
+        // are not available, we can still carry on
+        let sphinx_key_announcements = match self
+            .validator_client
+            .get_cached_sphinx_key_announcements()
+            .await
+        {
+            Err(err) => {
+                warn!("failed to get the rotated sphinx keys - {}", err);
+                Default::default()
+            }
+            Ok(announcements) => announcements,
+        };
+
         let mixnodes_count = mixnodes.len();
+        // note: the epoch is only updated on refresh, but the nodes keep on accepting the keys
+        // of the previous epoch for a while, which is longer than any sane refresh rate
         let topology = nym_topology_from_detailed(mixnodes, gateways)
-            .filter_system_version(&self.client_version);
+            .filter_system_version(&self.client_version)
+            .with_sphinx_key_announcements(
+                &sphinx_key_announcements.mixnodes,
+                &sphinx_key_announcements.gateways,
+            )
+            .with_sphinx_key_epoch(current_sphinx_key_epoch());
 
         if !self.check_layer_distribution(&topology, mixnodes_count) {
             warn!("The current filtered active topology has extremely skewed layer distribution. It cannot be used.");
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use config::NymConfig;
+use nymsphinx::acknowledgements::AckDelayDistribution;
 use nymsphinx::params::PacketSize;
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
@@ -20,6 +21,7 @@ pub const MISSING_VALUE: &str = "MISSING VALUE";
 const DEFAULT_ACK_WAIT_MULTIPLIER: f64 = 1.5;
 
 const DEFAULT_ACK_WAIT_ADDITION: Duration = Duration::from_millis(1_500);
+const DEFAULT_MAXIMUM_RETRANSMISSIONS: u32 = 0;
 const DEFAULT_LOOP_COVER_STREAM_AVERAGE_DELAY: Duration = Duration::from_millis(200);
 const DEFAULT_MESSAGE_STREAM_AVERAGE_DELAY: Duration = Duration::from_millis(20);
 const DEFAULT_AVERAGE_PACKET_DELAY: Duration = Duration::from_millis(50);
@@ -34,6 +36,10 @@ pub fn missing_string_value() -> String {
     MISSING_VALUE.to_string()
 }
 
+fn default_recipient_hrp() -> String {
+    network_defaults::mainnet::RECIPIENT_HRP.to_string()
+}
+
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config<T> {
@@ -129,6 +135,10 @@ impl<T: NymConfig> Config<T> {
         self.client.validator_api_urls = validator_api_urls;
     }
 
+    pub fn set_recipient_hrp<S: Into<String>>(&mut self, recipient_hrp: S) {
+        self.client.recipient_hrp = recipient_hrp.into();
+    }
+
     pub fn set_high_default_traffic_volume(&mut self) {
         self.debug.average_packet_delay = Duration::from_millis(10);
         self.debug.loop_cover_traffic_average_delay = Duration::from_millis(2_000_000); // basically don't really send cover messages
@@ -183,6 +193,10 @@ impl<T: NymConfig> Config<T> {
         self.client.validator_api_urls.clone()
     }
 
+    pub fn get_recipient_hrp(&self) -> &str {
+        &self.client.recipient_hrp
+    }
+
     pub fn get_gateway_id(&self) -> String {
         self.client.gateway_endpoint.gateway_id.clone()
     }
@@ -212,6 +226,12 @@ impl<T: NymConfig> Config<T> {
         self.debug.average_ack_delay
     }
 
+    pub fn get_ack_delay_distribution(&self) -> AckDelayDistribution {
+        self.debug
+            .ack_delay_distribution
+            .with_average(self.debug.average_ack_delay)
+    }
+
     pub fn get_ack_wait_multiplier(&self) -> f64 {
         self.debug.ack_wait_multiplier
     }
@@ -220,6 +240,10 @@ impl<T: NymConfig> Config<T> {
         self.debug.ack_wait_addition
     }
 
+    pub fn get_maximum_retransmissions(&self) -> u32 {
+        self.debug.maximum_retransmissions
+    }
+
     pub fn get_loop_cover_traffic_average_delay(&self) -> Duration {
         self.debug.loop_cover_traffic_average_delay
     }
@@ -252,6 +276,14 @@ impl<T: NymConfig> Config<T> {
         self.debug.use_extended_packet_size.clone()
     }
 
+    pub fn get_use_outfox_packets(&self) -> bool {
+        self.debug.use_outfox_packets
+    }
+
+    pub fn get_use_legacy_gateway_handshake(&self) -> bool {
+        self.debug.use_legacy_gateway_handshake
+    }
+
     pub fn get_version(&self) -> &str {
         &self.client.version
     }
@@ -283,7 +315,9 @@ pub struct GatewayEndpoint {
 
 impl From<topology::gateway::Node> for GatewayEndpoint {
     fn from(node: topology::gateway::Node) -> GatewayEndpoint {
-        let gateway_listener = node.clients_address();
+        // browser-based clients are frequently served over https and thus can't open plain
+        // websocket connections, so they should use the TLS endpoint whenever it's available
+        let gateway_listener = node.clients_address(cfg!(target_arch = "wasm32"));
         GatewayEndpoint {
             gateway_id: node.identity_key.to_base58_string(),
             gateway_owner: node.owner,
@@ -309,6 +343,10 @@ pub struct Client<T> {
     /// Addresses to APIs running on validator from which the client gets the view of the network.
     validator_api_urls: Vec<Url>,
 
+    /// Human readable part of the checksummed addresses of the clients on the network.
+    #[serde(default = "default_recipient_hrp")]
+    recipient_hrp: String,
+
     /// Path to file containing private identity key.
     private_identity_key_file: PathBuf,
 
@@ -355,6 +393,7 @@ impl<T: NymConfig> Default for Client<T> {
             id: "".to_string(),
             disabled_credentials_mode: true,
             validator_api_urls: vec![],
+            recipient_hrp: default_recipient_hrp(),
             private_identity_key_file: Default::default(),
             public_identity_key_file: Default::default(),
             private_encryption_key_file: Default::default(),
@@ -424,17 +463,34 @@ pub struct Debug {
     #[serde(with = "humantime_serde")]
     pub average_ack_delay: Duration,
 
+    /// Distribution from which the delays of acknowledgement packets at each mix node are drawn,
+    /// with the mean of [Self::average_ack_delay]. Unlike the default `exponential` one,
+    /// the `uniform` distribution bounds the delays, allowing for lower latency
+    /// of the interactive traffic at the cost of the acks being easier to correlate.
+    pub ack_delay_distribution: AckDelayDistributionKind,
+
     /// Value multiplied with the expected round trip time of an acknowledgement packet before
     /// it is assumed it was lost and retransmission of the data packet happens.
     /// In an ideal network with 0 latency, this value would have been 1.
+    /// It's only used until enough acknowledgements were received to estimate it from
+    /// the observed round trip times.
     pub ack_wait_multiplier: f64,
 
     /// Value added to the expected round trip time of an acknowledgement packet before
     /// it is assumed it was lost and retransmission of the data packet happens.
     /// In an ideal network with 0 latency, this value would have been 0.
+    /// It's only used until enough acknowledgements were received to estimate it from
+    /// the observed round trip times.
     #[serde(with = "humantime_serde")]
     pub ack_wait_addition: Duration,
 
+    /// Maximum number of times a packet is going to get retransmitted before it is given up on
+    /// and considered lost. Each retransmission doubles the time waited for the acknowledgement.
+    /// If set to 0, packets are retransmitted until they get acknowledged. Note that giving up
+    /// on a packet is only reported in the logs and the message it belonged to will never
+    /// get reconstructed by its recipient.
+    pub maximum_retransmissions: u32,
+
     /// The parameter of Poisson distribution determining how long, on average,
     /// it is going to take for another loop cover traffic message to be sent.
     #[serde(with = "humantime_serde")]
@@ -473,6 +529,16 @@ pub struct Debug {
 
     /// Controls whether the sent sphinx packet use a NON-DEFAULT bigger size.
     pub use_extended_packet_size: Option<ExtendedPacketSize>,
+
+    /// Controls whether the sent packets use the outfox format rather than sphinx, which is
+    /// cheaper to process by the mix nodes. Note that they're going to be dropped by nodes
+    /// that don't support it yet.
+    pub use_outfox_packets: bool,
+
+    /// Controls whether the client should use the legacy registration handshake with its gateway
+    /// rather than the Noise-based one that establishes fresh session keys for every connection.
+    // TODO: remember to change it in one of future releases, once most gateways support the new handshake
+    pub use_legacy_gateway_handshake: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -483,13 +549,37 @@ pub enum ExtendedPacketSize {
     Extended32,
 }
 
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AckDelayDistributionKind {
+    Exponential,
+    Uniform,
+}
+
+impl AckDelayDistributionKind {
+    pub fn with_average(self, average: Duration) -> AckDelayDistribution {
+        match self {
+            AckDelayDistributionKind::Exponential => AckDelayDistribution::Exponential(average),
+            AckDelayDistributionKind::Uniform => AckDelayDistribution::Uniform(average),
+        }
+    }
+}
+
+impl Default for AckDelayDistributionKind {
+    fn default() -> Self {
+        AckDelayDistributionKind::Exponential
+    }
+}
+
 impl Default for Debug {
     fn default() -> Self {
         Debug {
             average_packet_delay: DEFAULT_AVERAGE_PACKET_DELAY,
             average_ack_delay: DEFAULT_AVERAGE_PACKET_DELAY,
+            ack_delay_distribution: Default::default(),
             ack_wait_multiplier: DEFAULT_ACK_WAIT_MULTIPLIER,
             ack_wait_addition: DEFAULT_ACK_WAIT_ADDITION,
+            maximum_retransmissions: DEFAULT_MAXIMUM_RETRANSMISSIONS,
             loop_cover_traffic_average_delay: DEFAULT_LOOP_COVER_STREAM_AVERAGE_DELAY,
             message_sending_average_delay: DEFAULT_MESSAGE_STREAM_AVERAGE_DELAY,
             gateway_response_timeout: DEFAULT_GATEWAY_RESPONSE_TIMEOUT,
@@ -498,6 +588,8 @@ impl Default for Debug {
             disable_loop_cover_traffic_stream: false,
             disable_main_poisson_packet_distribution: false,
             use_extended_packet_size: None,
+            use_outfox_packets: false,
+            use_legacy_gateway_handshake: true,
         }
     }
 }
@@ -69,8 +69,12 @@ where
     let mut rng = OsRng;
     let mut key_manager = KeyManager::new(&mut rng);
 
-    let shared_keys =
-        register_with_gateway(&gateway_details, key_manager.identity_keypair()).await?;
+    let shared_keys = register_with_gateway(
+        &gateway_details,
+        key_manager.identity_keypair(),
+        config.get_use_legacy_gateway_handshake(),
+    )
+    .await?;
     key_manager.insert_gateway_shared_key(shared_keys);
 
     let pathfinder = ClientKeyPathfinder::new_from_config(config);
@@ -82,10 +86,11 @@ where
 async fn register_with_gateway(
     gateway: &gateway::Node,
     our_identity: Arc<identity::KeyPair>,
+    use_legacy_handshake: bool,
 ) -> Result<Arc<SharedKeys>, ClientCoreError> {
     let timeout = Duration::from_millis(1500);
     let mut gateway_client = GatewayClient::new_init(
-        gateway.clients_address(),
+        gateway.clients_address(false),
         gateway.identity_key,
         gateway.owner.clone(),
         our_identity.clone(),
@@ -93,6 +98,8 @@ async fn register_with_gateway(
         #[cfg(not(target_arch = "wasm32"))]
         None,
     );
+    gateway_client.with_legacy_handshake(use_legacy_handshake);
+    gateway_client.with_gateway_sphinx_key(gateway.sphinx_key);
     gateway_client
         .establish_connection()
         .await
@@ -145,5 +152,9 @@ where
     );
 
     println!("\nThe address of this client is: {}", client_recipient);
+    println!(
+        "Its checksummed equivalent is: {}",
+        client_recipient.to_bech32m_string(config.get_recipient_hrp())
+    );
     Ok(())
 }
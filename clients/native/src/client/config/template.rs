@@ -30,6 +30,9 @@ validator_api_urls = [
     {{/each}}
 ]
 
+# Human readable part of the checksummed addresses of the clients on the network.
+recipient_hrp = '{{ client.recipient_hrp }}'
+
 # Path to file containing private identity key.
 private_identity_key_file = '{{ client.private_identity_key_file }}'
 
@@ -103,6 +106,8 @@ listening_port = {{ socket.listening_port }}
 
 average_packet_delay = '{{ debug.average_packet_delay }}'
 average_ack_delay = '{{ debug.average_ack_delay }}'
+ack_delay_distribution = '{{ debug.ack_delay_distribution }}'
+maximum_retransmissions = {{ debug.maximum_retransmissions }}
 loop_cover_traffic_average_delay = '{{ debug.loop_cover_traffic_average_delay }}'
 message_sending_average_delay = '{{ debug.message_sending_average_delay }}'
 
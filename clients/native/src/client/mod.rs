@@ -9,7 +9,9 @@ use client_core::client::inbound_messages::{
 use client_core::client::key_manager::KeyManager;
 use client_core::client::mix_traffic::{BatchMixMessageSender, MixTrafficController};
 use client_core::client::real_messages_control;
-use client_core::client::real_messages_control::RealMessagesController;
+use client_core::client::real_messages_control::{
+    FailedTransmissionReceiver, FailedTransmissionSender, RealMessagesController,
+};
 use client_core::client::received_buffer::{
     ReceivedBufferMessage, ReceivedBufferRequestReceiver, ReceivedBufferRequestSender,
     ReceivedMessagesBufferController, ReconstructedMessagesReceiver,
@@ -31,6 +33,7 @@ use log::*;
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::addressing::nodes::NodeIdentity;
 use nymsphinx::anonymous_replies::ReplySurb;
+use nymsphinx::params::PacketMode;
 use nymsphinx::receiver::ReconstructedMessage;
 use task::{wait_for_signal, ShutdownListener, ShutdownNotifier};
 
@@ -93,7 +96,7 @@ impl NymClient {
 
         let mut stream = LoopCoverTrafficStream::new(
             self.key_manager.ack_key(),
-            self.config.get_base().get_average_ack_delay(),
+            self.config.get_base().get_ack_delay_distribution(),
             self.config.get_base().get_average_packet_delay(),
             self.config
                 .get_base()
@@ -108,6 +111,11 @@ impl NymClient {
             stream.set_custom_packet_size(size.into());
         }
 
+        if self.config.get_base().get_use_outfox_packets() {
+            log::debug!("Using outfox packets");
+            stream.set_custom_packet_mode(PacketMode::Outfox);
+        }
+
         stream.start_with_shutdown(shutdown);
     }
 
@@ -120,13 +128,14 @@ impl NymClient {
         input_receiver: InputMessageReceiver,
         mix_sender: BatchMixMessageSender,
         closed_connection_rx: ClosedConnectionReceiver,
+        failed_transmission_tx: FailedTransmissionSender,
         shutdown: ShutdownListener,
     ) {
         let mut controller_config = real_messages_control::Config::new(
             self.key_manager.ack_key(),
             self.config.get_base().get_ack_wait_multiplier(),
             self.config.get_base().get_ack_wait_addition(),
-            self.config.get_base().get_average_ack_delay(),
+            self.config.get_base().get_ack_delay_distribution(),
             self.config.get_base().get_message_sending_average_delay(),
             self.config.get_base().get_average_packet_delay(),
             self.config
@@ -140,6 +149,15 @@ impl NymClient {
             controller_config.set_custom_packet_size(size.into());
         }
 
+        if self.config.get_base().get_use_outfox_packets() {
+            log::debug!("Using outfox packets");
+            controller_config.set_custom_packet_mode(PacketMode::Outfox);
+        }
+
+        controller_config
+            .set_maximum_retransmissions(self.config.get_base().get_maximum_retransmissions());
+        controller_config.set_failed_transmission_sender(failed_transmission_tx);
+
         info!("Starting real traffic stream...");
 
         RealMessagesController::new(
@@ -223,6 +241,8 @@ impl NymClient {
 
         gateway_client
             .set_disabled_credentials_mode(self.config.get_base().get_disabled_credentials_mode());
+        gateway_client
+            .with_legacy_handshake(self.config.get_base().get_use_legacy_gateway_handshake());
 
         gateway_client
             .authenticate_and_start()
@@ -284,6 +304,7 @@ impl NymClient {
         buffer_requester: ReceivedBufferRequestSender,
         msg_input: InputMessageSender,
         closed_connection_tx: ClosedConnectionSender,
+        failed_transmission_rx: FailedTransmissionReceiver,
     ) {
         info!("Starting websocket listener...");
 
@@ -291,7 +312,9 @@ impl NymClient {
             msg_input,
             closed_connection_tx,
             buffer_requester,
+            failed_transmission_rx,
             self.as_mix_recipient(),
+            self.config.get_base().get_recipient_hrp().to_owned(),
         );
 
         websocket::Listener::new(self.config.get_listening_port()).start(websocket_handler);
@@ -417,6 +440,10 @@ impl NymClient {
         // controller that connections are closed.
         let (closed_connection_tx, closed_connection_rx) = mpsc::unbounded();
 
+        // Channel used by the real traffic controller to tell the websocket listener about
+        // the messages it gave up on delivering.
+        let (failed_transmission_tx, failed_transmission_rx) = mpsc::unbounded();
+
         self.start_real_traffic_controller(
             shared_topology_accessor.clone(),
             reply_key_storage,
@@ -424,6 +451,7 @@ impl NymClient {
             input_receiver,
             sphinx_message_sender.clone(),
             closed_connection_rx,
+            failed_transmission_tx,
             shutdown.subscribe(),
         );
 
@@ -444,6 +472,7 @@ impl NymClient {
                 received_buffer_request_sender,
                 input_sender,
                 closed_connection_tx,
+                failed_transmission_rx,
             ),
             SocketType::None => {
                 // if we did not start the socket, it means we're running (supposedly) in the native mode
@@ -110,6 +110,10 @@ pub(crate) fn override_config(mut config: Config, args: OverrideConfig) -> Confi
             .set_custom_validator_apis(config::parse_validators(&raw_validators));
     }
 
+    if let Ok(recipient_hrp) = std::env::var(network_defaults::var_names::RECIPIENT_HRP) {
+        config.get_base_mut().set_recipient_hrp(recipient_hrp);
+    }
+
     if args.disable_socket {
         config = config.with_socket(SocketType::None);
     }
@@ -4,6 +4,7 @@
 use client_connections::{ClosedConnectionSender, TransmissionLane};
 use client_core::client::{
     inbound_messages::{InputMessage, InputMessageSender},
+    real_messages_control::{FailedTransmission, FailedTransmissionReceiver},
     received_buffer::{
         ReceivedBufferMessage, ReceivedBufferRequestSender, ReconstructedMessagesReceiver,
     },
@@ -14,13 +15,19 @@ use log::*;
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::anonymous_replies::ReplySurb;
 use nymsphinx::receiver::ReconstructedMessage;
+use std::sync::Arc;
 use tokio::net::TcpStream;
+use tokio::sync::Mutex;
 use tokio_tungstenite::{
     accept_async,
     tungstenite::{protocol::Message as WsMessage, Error as WsError},
     WebSocketStream,
 };
-use websocket_requests::{requests::ClientRequest, responses::ServerResponse};
+use websocket_requests::{
+    error::{Error as ResponseError, ErrorKind},
+    requests::ClientRequest,
+    responses::ServerResponse,
+};
 
 enum ReceivedResponseType {
     Binary,
@@ -37,7 +44,11 @@ pub(crate) struct Handler {
     msg_input: InputMessageSender,
     closed_connection_tx: ClosedConnectionSender,
     buffer_requester: ReceivedBufferRequestSender,
+    // only a single connection is ever handled at a time, so the receiver is shared between
+    // the clones rather than being recreated for each of them
+    failed_transmissions: Arc<Mutex<FailedTransmissionReceiver>>,
     self_full_address: Recipient,
+    recipient_hrp: String,
     socket: Option<WebSocketStream<TcpStream>>,
     received_response_type: ReceivedResponseType,
 }
@@ -49,7 +60,9 @@ impl Clone for Handler {
             msg_input: self.msg_input.clone(),
             closed_connection_tx: self.closed_connection_tx.clone(),
             buffer_requester: self.buffer_requester.clone(),
+            failed_transmissions: Arc::clone(&self.failed_transmissions),
             self_full_address: self.self_full_address,
+            recipient_hrp: self.recipient_hrp.clone(),
             socket: None,
             received_response_type: Default::default(),
         }
@@ -69,13 +82,17 @@ impl Handler {
         msg_input: InputMessageSender,
         closed_connection_tx: ClosedConnectionSender,
         buffer_requester: ReceivedBufferRequestSender,
+        failed_transmissions: FailedTransmissionReceiver,
         self_full_address: Recipient,
+        recipient_hrp: String,
     ) -> Self {
         Handler {
             msg_input,
             closed_connection_tx,
             buffer_requester,
+            failed_transmissions: Arc::new(Mutex::new(failed_transmissions)),
             self_full_address,
+            recipient_hrp,
             socket: None,
             received_response_type: Default::default(),
         }
@@ -140,7 +157,7 @@ impl Handler {
         trace!("Content: {:?}", msg);
 
         self.received_response_type = ReceivedResponseType::Text;
-        let client_request = ClientRequest::try_from_text(msg);
+        let client_request = ClientRequest::try_from_text(msg, &self.recipient_hrp);
 
         let response = match client_request {
             Err(err) => Some(ServerResponse::Error(err)),
@@ -164,6 +181,27 @@ impl Handler {
         response.map(|resp| WsMessage::Binary(resp.into_binary()))
     }
 
+    fn handle_failed_transmission(&self, failed: FailedTransmission) -> WsMessage {
+        let connection = match failed.lane {
+            TransmissionLane::ConnectionId(connection_id) => {
+                format!(" on connection {}", connection_id)
+            }
+            _ => String::new(),
+        };
+        let response = ServerResponse::Error(ResponseError::new(
+            ErrorKind::MessageUndelivered,
+            format!(
+                "fragment {} of the message sent to {}{} was not acknowledged after {} retransmissions",
+                failed.fragment_id, failed.recipient, connection, failed.retransmissions
+            ),
+        ));
+
+        match self.received_response_type {
+            ReceivedResponseType::Binary => WsMessage::Binary(response.into_binary()),
+            ReceivedResponseType::Text => WsMessage::text(response.into_text()),
+        }
+    }
+
     fn handle_ws_request(&mut self, raw_request: WsMessage) -> Option<WsMessage> {
         // apparently tungstenite auto-handles ping/pong/close messages so for now let's ignore
         // them and let's test that claim. If that's not the case, just copy code from
@@ -241,6 +279,9 @@ impl Handler {
     }
 
     async fn listen_for_requests(&mut self, mut msg_receiver: ReconstructedMessagesReceiver) {
+        let failed_transmissions = Arc::clone(&self.failed_transmissions);
+        let mut failed_transmissions = failed_transmissions.lock().await;
+
         loop {
             tokio::select! {
                 // we can either get a client request from the websocket
@@ -280,6 +321,17 @@ impl Handler {
                         break;
                     }
                 }
+                // or a notification about a message that we gave up on delivering
+                Some(failed) = failed_transmissions.next() => {
+                    let response = self.handle_failed_transmission(failed);
+                    if let Err(err) = self.send_websocket_response(response).await {
+                        warn!(
+                            "Failed to send message over websocket: {}. Assuming the connection is dead.",
+                            err
+                        );
+                        break;
+                    }
+                }
             }
         }
     }
@@ -299,13 +299,13 @@ impl ClientRequest {
         Self::deserialize(&raw_req)
     }
 
-    pub fn try_from_text(raw_req: String) -> Result<Self, error::Error> {
+    pub fn try_from_text(raw_req: String, recipient_hrp: &str) -> Result<Self, error::Error> {
         // use the intermediate string structure and let serde do bunch of work for us
         let text_req = ClientRequestText::try_from(raw_req).map_err(|json_err| {
             error::Error::new(ErrorKind::MalformedRequest, json_err.to_string())
         })?;
 
-        text_req.try_into()
+        text_req.try_into_client_request(recipient_hrp)
     }
 }
 
@@ -220,6 +220,7 @@ impl ServerResponse {
             _ if b[1] == (ErrorKind::TooShortRequest as u8) => ErrorKind::TooShortRequest,
             _ if b[1] == (ErrorKind::UnknownRequest as u8) => ErrorKind::UnknownRequest,
             _ if b[1] == (ErrorKind::MalformedRequest as u8) => ErrorKind::MalformedRequest,
+            _ if b[1] == (ErrorKind::MessageUndelivered as u8) => ErrorKind::MessageUndelivered,
 
             _ if b[1] == (ErrorKind::EmptyResponse as u8) => ErrorKind::EmptyResponse,
             _ if b[1] == (ErrorKind::TooShortResponse as u8) => ErrorKind::TooShortResponse,
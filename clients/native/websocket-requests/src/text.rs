@@ -7,7 +7,7 @@ use crate::responses::ServerResponse;
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::anonymous_replies::ReplySurb;
 use serde::{Deserialize, Serialize};
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryFrom;
 
 // local text equivalent of `ClientRequest` for easier serialization + deserialization with serde
 // TODO: figure out if there's an easy way to avoid defining it
@@ -38,10 +38,13 @@ impl TryFrom<String> for ClientRequestText {
     }
 }
 
-impl TryInto<ClientRequest> for ClientRequestText {
-    type Error = crate::error::Error;
-
-    fn try_into(self) -> Result<ClientRequest, Self::Error> {
+impl ClientRequestText {
+    /// Converts the request into its binary equivalent, parsing the checksummed recipient
+    /// addresses with the provided human readable part of the network.
+    pub(super) fn try_into_client_request(
+        self,
+        recipient_hrp: &str,
+    ) -> Result<ClientRequest, crate::error::Error> {
         match self {
             ClientRequestText::Send {
                 message,
@@ -50,9 +53,10 @@ impl TryInto<ClientRequest> for ClientRequestText {
                 connection_id,
             } => {
                 let message_bytes = message.into_bytes();
-                let recipient = Recipient::try_from_base58_string(recipient).map_err(|err| {
-                    Self::Error::new(ErrorKind::MalformedRequest, err.to_string())
-                })?;
+                let recipient =
+                    Recipient::try_from_string(recipient, recipient_hrp).map_err(|err| {
+                        crate::error::Error::new(ErrorKind::MalformedRequest, err.to_string())
+                    })?;
 
                 Ok(ClientRequest::Send {
                     message: message_bytes,
@@ -68,7 +72,7 @@ impl TryInto<ClientRequest> for ClientRequestText {
             } => {
                 let message_bytes = message.into_bytes();
                 let reply_surb = ReplySurb::from_base58_string(reply_surb).map_err(|err| {
-                    Self::Error::new(ErrorKind::MalformedRequest, err.to_string())
+                    crate::error::Error::new(ErrorKind::MalformedRequest, err.to_string())
                 })?;
 
                 Ok(ClientRequest::Reply {
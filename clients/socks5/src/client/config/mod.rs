@@ -70,14 +70,22 @@ impl Config {
         self
     }
 
+    pub fn with_anonymous_replies(mut self, anonymous_replies: bool) -> Self {
+        self.socks5.anonymous_replies = anonymous_replies;
+        self
+    }
+
     // getters
     pub fn get_config_file_save_location(&self) -> PathBuf {
         self.config_directory().join(Self::config_file_name())
     }
 
     pub fn get_provider_mix_address(&self) -> Recipient {
-        Recipient::try_from_base58_string(&self.socks5.provider_mix_address)
-            .expect("malformed provider address")
+        Recipient::try_from_string(
+            &self.socks5.provider_mix_address,
+            self.base.get_recipient_hrp(),
+        )
+        .expect("malformed provider address")
     }
 
     pub fn get_base(&self) -> &BaseConfig<Self> {
@@ -91,6 +99,10 @@ impl Config {
     pub fn get_listening_port(&self) -> u16 {
         self.socks5.listening_port
     }
+
+    pub fn get_anonymous_replies(&self) -> bool {
+        self.socks5.anonymous_replies
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
@@ -101,6 +113,12 @@ pub struct Socks5 {
 
     /// The mix address of the provider to which all requests are going to be sent.
     provider_mix_address: String,
+
+    /// Specifies whether this client is going to hide its address from the provider by only
+    /// giving it reply SURBs to send the responses with. Note that, unlike the regular messages,
+    /// the responses sent that way are not retransmitted if they get lost.
+    #[serde(default)]
+    anonymous_replies: bool,
 }
 
 impl Socks5 {
@@ -108,6 +126,7 @@ impl Socks5 {
         Socks5 {
             listening_port: DEFAULT_SOCKS5_LISTENING_PORT,
             provider_mix_address: provider_mix_address.into(),
+            anonymous_replies: false,
         }
     }
 }
@@ -117,6 +136,7 @@ impl Default for Socks5 {
         Socks5 {
             listening_port: DEFAULT_SOCKS5_LISTENING_PORT,
             provider_mix_address: "".into(),
+            anonymous_replies: false,
         }
     }
 }
@@ -30,6 +30,9 @@ validator_api_urls = [
     {{/each}}
 ]
 
+# Human readable part of the checksummed addresses of the clients on the network.
+recipient_hrp = '{{ client.recipient_hrp }}'
+
 # Path to file containing private identity key.
 private_identity_key_file = '{{ client.private_identity_key_file }}'
 
@@ -85,6 +88,11 @@ provider_mix_address = '{{ socks5.provider_mix_address }}'
 # The port on which the client will be listening for incoming requests
 listening_port = {{ socks5.listening_port }}
 
+# Specifies whether this client is going to hide its address from the provider by only
+# giving it reply SURBs to send the responses with. Note that, unlike the regular messages,
+# the responses sent that way are not retransmitted if they get lost.
+anonymous_replies = {{ socks5.anonymous_replies }}
+
 
 ##### logging configuration options #####
 
@@ -101,6 +109,8 @@ listening_port = {{ socks5.listening_port }}
 
 average_packet_delay = '{{ debug.average_packet_delay }}'
 average_ack_delay = '{{ debug.average_ack_delay }}'
+ack_delay_distribution = '{{ debug.ack_delay_distribution }}'
+maximum_retransmissions = {{ debug.maximum_retransmissions }}
 loop_cover_traffic_average_delay = '{{ debug.loop_cover_traffic_average_delay }}'
 message_sending_average_delay = '{{ debug.message_sending_average_delay }}'
 
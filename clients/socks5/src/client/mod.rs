@@ -16,11 +16,14 @@ use client_core::client::inbound_messages::{
 };
 use client_core::client::key_manager::KeyManager;
 use client_core::client::mix_traffic::{BatchMixMessageSender, MixTrafficController};
-use client_core::client::real_messages_control::RealMessagesController;
+use client_core::client::real_messages_control::{
+    FailedTransmissionReceiver, FailedTransmissionSender, RealMessagesController,
+};
 use client_core::client::received_buffer::{
     ReceivedBufferRequestReceiver, ReceivedBufferRequestSender, ReceivedMessagesBufferController,
 };
 use client_core::client::reply_key_storage::ReplyKeyStorage;
+use client_core::client::reply_surb_generator::ReplySurbGenerator;
 use client_core::client::topology_control::{
     TopologyAccessor, TopologyRefresher, TopologyRefresherConfig,
 };
@@ -37,6 +40,7 @@ use gateway_client::{
 use log::*;
 use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::addressing::nodes::NodeIdentity;
+use nymsphinx::params::PacketMode;
 use task::{wait_for_signal, ShutdownListener, ShutdownNotifier};
 
 pub mod config;
@@ -93,7 +97,7 @@ impl NymClient {
 
         let mut stream = LoopCoverTrafficStream::new(
             self.key_manager.ack_key(),
-            self.config.get_base().get_average_ack_delay(),
+            self.config.get_base().get_ack_delay_distribution(),
             self.config.get_base().get_average_packet_delay(),
             self.config
                 .get_base()
@@ -108,6 +112,11 @@ impl NymClient {
             stream.set_custom_packet_size(size.into());
         }
 
+        if self.config.get_base().get_use_outfox_packets() {
+            log::debug!("Using outfox packets");
+            stream.set_custom_packet_mode(PacketMode::Outfox);
+        }
+
         stream.start_with_shutdown(shutdown);
     }
 
@@ -120,13 +129,14 @@ impl NymClient {
         input_receiver: InputMessageReceiver,
         mix_sender: BatchMixMessageSender,
         closed_connection_rx: ClosedConnectionReceiver,
+        failed_transmission_tx: FailedTransmissionSender,
         shutdown: ShutdownListener,
     ) {
         let mut controller_config = client_core::client::real_messages_control::Config::new(
             self.key_manager.ack_key(),
             self.config.get_base().get_ack_wait_multiplier(),
             self.config.get_base().get_ack_wait_addition(),
-            self.config.get_base().get_average_ack_delay(),
+            self.config.get_base().get_ack_delay_distribution(),
             self.config.get_base().get_message_sending_average_delay(),
             self.config.get_base().get_average_packet_delay(),
             self.config
@@ -140,6 +150,15 @@ impl NymClient {
             controller_config.set_custom_packet_size(size.into());
         }
 
+        if self.config.get_base().get_use_outfox_packets() {
+            log::debug!("Using outfox packets");
+            controller_config.set_custom_packet_mode(PacketMode::Outfox);
+        }
+
+        controller_config
+            .set_maximum_retransmissions(self.config.get_base().get_maximum_retransmissions());
+        controller_config.set_failed_transmission_sender(failed_transmission_tx);
+
         info!("Starting real traffic stream...");
 
         RealMessagesController::new(
@@ -223,6 +242,8 @@ impl NymClient {
 
         gateway_client
             .set_disabled_credentials_mode(self.config.get_base().get_disabled_credentials_mode());
+        gateway_client
+            .with_legacy_handshake(self.config.get_base().get_use_legacy_gateway_handshake());
 
         gateway_client
             .authenticate_and_start()
@@ -279,11 +300,38 @@ impl NymClient {
         mix_tx
     }
 
+    // generator of reply SURBs handed over to the provider, if we don't want it to learn our address
+    fn reply_surb_generator(
+        &self,
+        topology_accessor: TopologyAccessor,
+        reply_key_storage: ReplyKeyStorage,
+    ) -> Option<ReplySurbGenerator> {
+        if !self.config.get_anonymous_replies() {
+            return None;
+        }
+
+        info!("The provider is going to send the responses using reply SURBs");
+        let mut generator = ReplySurbGenerator::new(
+            self.as_mix_recipient(),
+            self.config.get_base().get_average_packet_delay(),
+            topology_accessor,
+            reply_key_storage,
+        );
+
+        if self.config.get_base().get_use_outfox_packets() {
+            generator = generator.with_packet_mode(PacketMode::Outfox);
+        }
+
+        Some(generator)
+    }
+
     fn start_socks5_listener(
         &self,
         buffer_requester: ReceivedBufferRequestSender,
         msg_input: InputMessageSender,
         closed_connection_tx: ClosedConnectionSender,
+        failed_transmission_rx: FailedTransmissionReceiver,
+        reply_surb_generator: Option<ReplySurbGenerator>,
         shutdown: ShutdownListener,
     ) {
         info!("Starting socks5 listener...");
@@ -296,11 +344,17 @@ impl NymClient {
             authenticator,
             self.config.get_provider_mix_address(),
             self.as_mix_recipient(),
+            reply_surb_generator,
             shutdown,
         );
         tokio::spawn(async move {
             sphinx_socks
-                .serve(msg_input, buffer_requester, closed_connection_tx)
+                .serve(
+                    msg_input,
+                    buffer_requester,
+                    closed_connection_tx,
+                    failed_transmission_rx,
+                )
                 .await
         });
     }
@@ -409,6 +463,13 @@ impl NymClient {
         // This will be forwarded to `OutQueueControl`
         let (closed_connection_tx, closed_connection_rx) = mpsc::unbounded();
 
+        // Channel for announcing the data that was given up on by the real traffic controller.
+        // This will be used for tearing down the affected (socks5) connections
+        let (failed_transmission_tx, failed_transmission_rx) = mpsc::unbounded();
+
+        let reply_surb_generator =
+            self.reply_surb_generator(shared_topology_accessor.clone(), reply_key_storage.clone());
+
         self.start_real_traffic_controller(
             shared_topology_accessor.clone(),
             reply_key_storage,
@@ -416,6 +477,7 @@ impl NymClient {
             input_receiver,
             sphinx_message_sender.clone(),
             closed_connection_rx,
+            failed_transmission_tx,
             shutdown.subscribe(),
         );
 
@@ -435,6 +497,8 @@ impl NymClient {
             received_buffer_request_sender,
             input_sender,
             closed_connection_tx,
+            failed_transmission_rx,
+            reply_surb_generator,
             shutdown.subscribe(),
         );
 
@@ -16,7 +16,8 @@ pub(crate) struct Init {
     #[clap(long)]
     id: String,
 
-    /// Address of the socks5 provider to send messages to.
+    /// Address of the socks5 provider to send messages to, either in the base58
+    /// or the checksummed format.
     #[clap(long)]
     provider: String,
 
@@ -37,6 +38,11 @@ pub(crate) struct Init {
     #[clap(short, long)]
     port: Option<u16>,
 
+    /// Hide the address of this client from the provider by only giving it reply SURBs
+    /// to send the responses with.
+    #[clap(long)]
+    anonymous_replies: bool,
+
     /// Mostly debug-related option to increase default traffic rate so that you would not need to
     /// modify config post init
     #[clap(long, hidden = true)]
@@ -55,6 +61,7 @@ impl From<Init> for OverrideConfig {
             validators: init_config.validators,
             port: init_config.port,
             fastmode: init_config.fastmode,
+            anonymous_replies: init_config.anonymous_replies,
             #[cfg(feature = "coconut")]
             enabled_credentials_mode: init_config.enabled_credentials_mode,
         }
@@ -81,6 +81,7 @@ pub(crate) struct OverrideConfig {
     validators: Option<String>,
     port: Option<u16>,
     fastmode: bool,
+    anonymous_replies: bool,
 
     #[cfg(feature = "coconut")]
     enabled_credentials_mode: bool,
@@ -110,6 +111,10 @@ pub(crate) fn override_config(mut config: Config, args: OverrideConfig) -> Confi
             .set_custom_validator_apis(parse_validators(&raw_validators));
     }
 
+    if let Ok(recipient_hrp) = std::env::var(network_defaults::var_names::RECIPIENT_HRP) {
+        config.get_base_mut().set_recipient_hrp(recipient_hrp);
+    }
+
     if let Some(port) = args.port {
         config = config.with_port(port);
     }
@@ -121,6 +126,10 @@ pub(crate) fn override_config(mut config: Config, args: OverrideConfig) -> Confi
         }
     }
 
+    if args.anonymous_replies {
+        config = config.with_anonymous_replies(true);
+    }
+
     if args.fastmode {
         config.get_base_mut().set_high_default_traffic_volume();
     }
@@ -22,7 +22,8 @@ pub(crate) struct Run {
     #[clap(long)]
     config: Option<String>,
 
-    /// Address of the socks5 provider to send messages to.
+    /// Address of the socks5 provider to send messages to, either in the base58
+    /// or the checksummed format.
     #[clap(long)]
     provider: Option<String>,
 
@@ -39,6 +40,11 @@ pub(crate) struct Run {
     #[clap(short, long)]
     port: Option<u16>,
 
+    /// Hide the address of this client from the provider by only giving it reply SURBs
+    /// to send the responses with.
+    #[clap(long)]
+    anonymous_replies: bool,
+
     /// Set this client to work in a enabled credentials mode that would attempt to use gateway
     /// with bandwidth credential requirement.
     #[cfg(feature = "coconut")]
@@ -52,6 +58,7 @@ impl From<Run> for OverrideConfig {
             validators: run_config.validators,
             port: run_config.port,
             fastmode: false,
+            anonymous_replies: run_config.anonymous_replies,
 
             #[cfg(feature = "coconut")]
             enabled_credentials_mode: run_config.enabled_credentials_mode,
@@ -6,10 +6,12 @@ use super::types::{ResponseCode, SocksProxyError};
 use super::{RESERVED, SOCKS_VERSION};
 use client_connections::TransmissionLane;
 use client_core::client::inbound_messages::{InputMessage, InputMessageSender};
+use client_core::client::reply_surb_generator::ReplySurbGenerator;
 use futures::channel::mpsc;
 use futures::task::{Context, Poll};
 use log::*;
 use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::anonymous_replies::ReplySurb;
 use pin_project::pin_project;
 use proxy_helpers::connection_controller::{
     ConnectionReceiver, ControllerCommand, ControllerSender,
@@ -24,6 +26,10 @@ use task::ShutdownListener;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tokio::{self, net::TcpStream};
 
+// Number of reply SURBs sent to the provider alongside the request opening an anonymous connection.
+// Any further ones are only sent once the provider asks for them.
+const INITIAL_REPLY_SURBS: usize = 20;
+
 #[pin_project(project = StateProject)]
 enum StreamState {
     Available(TcpStream),
@@ -140,6 +146,7 @@ pub(crate) struct SocksClient {
     connection_id: ConnectionId,
     service_provider: Recipient,
     self_address: Recipient,
+    reply_surb_generator: Option<ReplySurbGenerator>,
     started_proxy: bool,
     shutdown_listener: ShutdownListener,
 }
@@ -147,6 +154,9 @@ pub(crate) struct SocksClient {
 impl Drop for SocksClient {
     fn drop(&mut self) {
         debug!("Connection {} is getting closed", self.connection_id);
+        if let Some(reply_surb_generator) = &self.reply_surb_generator {
+            reply_surb_generator.close_connection(self.connection_id);
+        }
         // if we never managed to start a proxy, the entry will not exist in the controller
         if self.started_proxy {
             self.controller_sender
@@ -165,6 +175,7 @@ impl SocksClient {
         service_provider: Recipient,
         controller_sender: ControllerSender,
         self_address: Recipient,
+        reply_surb_generator: Option<ReplySurbGenerator>,
         shutdown_listener: ShutdownListener,
     ) -> Self {
         let connection_id = Self::generate_random();
@@ -178,6 +189,7 @@ impl SocksClient {
             input_sender,
             service_provider,
             self_address,
+            reply_surb_generator,
             started_proxy: false,
             shutdown_listener,
         }
@@ -226,8 +238,30 @@ impl SocksClient {
         }
     }
 
-    fn send_connect_to_mixnet(&mut self, remote_address: RemoteAddress) {
-        let req = Request::new_connect(self.connection_id, remote_address, self.self_address);
+    /// Generates the reply SURBs sent alongside the connect request, if our address is meant
+    /// to be hidden from the provider.
+    async fn initial_reply_surbs(&mut self) -> Result<Option<Vec<ReplySurb>>, SocksProxyError> {
+        match self.reply_surb_generator.as_mut() {
+            Some(generator) => Ok(Some(
+                generator
+                    .generate_initial_reply_surbs(self.connection_id, INITIAL_REPLY_SURBS)
+                    .await?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn send_connect_to_mixnet(
+        &mut self,
+        remote_address: RemoteAddress,
+        reply_surbs: Option<Vec<ReplySurb>>,
+    ) {
+        let req = match reply_surbs {
+            Some(reply_surbs) => {
+                Request::new_connect_anonymous(self.connection_id, remote_address, reply_surbs)
+            }
+            None => Request::new_connect(self.connection_id, remote_address, self.self_address),
+        };
         let msg = Message::Request(req);
 
         let input_message = InputMessage::new_fresh(
@@ -239,8 +273,13 @@ impl SocksClient {
         self.input_sender.unbounded_send(input_message).unwrap();
     }
 
-    async fn run_proxy(&mut self, conn_receiver: ConnectionReceiver, remote_proxy_target: String) {
-        self.send_connect_to_mixnet(remote_proxy_target.clone());
+    async fn run_proxy(
+        &mut self,
+        conn_receiver: ConnectionReceiver,
+        remote_proxy_target: String,
+        reply_surbs: Option<Vec<ReplySurb>>,
+    ) {
+        self.send_connect_to_mixnet(remote_proxy_target.clone(), reply_surbs);
 
         let stream = self.stream.run_proxy();
         let local_stream_remote = stream
@@ -286,6 +325,7 @@ impl SocksClient {
             // Use the Proxy to connect to the specified addr/port
             SocksCommand::Connect => {
                 trace!("Connecting to: {:?}", remote_address.clone());
+                let reply_surbs = self.initial_reply_surbs().await?;
                 self.acknowledge_socks5().await;
 
                 self.started_proxy = true;
@@ -298,7 +338,8 @@ impl SocksClient {
                     remote_address.clone(),
                     self.connection_id
                 );
-                self.run_proxy(mix_receiver, remote_address.clone()).await;
+                self.run_proxy(mix_receiver, remote_address.clone(), reply_surbs)
+                    .await;
                 info!(
                     "Proxy for {} is finished (id: {})",
                     remote_address, self.connection_id
@@ -2,17 +2,27 @@ use futures::channel::mpsc;
 use futures::StreamExt;
 use log::*;
 
+use client_connections::TransmissionLane;
+use client_core::client::inbound_messages::{InputMessage, InputMessageSender};
 use client_core::client::received_buffer::ReconstructedMessagesReceiver;
 use client_core::client::received_buffer::{ReceivedBufferMessage, ReceivedBufferRequestSender};
+use client_core::client::reply_surb_generator::ReplySurbGenerator;
+use nymsphinx::addressing::clients::Recipient;
 use nymsphinx::receiver::ReconstructedMessage;
 use proxy_helpers::connection_controller::{ControllerCommand, ControllerSender};
-use socks5_requests::Message;
+use socks5_requests::{Message, ReplySurbsRequest, Request};
 use task::ShutdownListener;
 
+// Upper bound on the number of reply SURBs sent back in response to a single request of the provider.
+const MAX_REPLY_SURBS_PER_REQUEST: u32 = 100;
+
 pub(crate) struct MixnetResponseListener {
     buffer_requester: ReceivedBufferRequestSender,
     mix_response_receiver: ReconstructedMessagesReceiver,
     controller_sender: ControllerSender,
+    input_sender: InputMessageSender,
+    service_provider: Recipient,
+    reply_surb_generator: Option<ReplySurbGenerator>,
     shutdown: ShutdownListener,
 }
 
@@ -28,6 +38,9 @@ impl MixnetResponseListener {
     pub(crate) fn new(
         buffer_requester: ReceivedBufferRequestSender,
         controller_sender: ControllerSender,
+        input_sender: InputMessageSender,
+        service_provider: Recipient,
+        reply_surb_generator: Option<ReplySurbGenerator>,
         shutdown: ShutdownListener,
     ) -> Self {
         let (mix_response_sender, mix_response_receiver) = mpsc::unbounded();
@@ -39,11 +52,58 @@ impl MixnetResponseListener {
             buffer_requester,
             mix_response_receiver,
             controller_sender,
+            input_sender,
+            service_provider,
+            reply_surb_generator,
             shutdown,
         }
     }
 
-    async fn on_message(&self, reconstructed_message: ReconstructedMessage) {
+    async fn on_reply_surbs_request(&mut self, request: ReplySurbsRequest) {
+        let generator = match self.reply_surb_generator.as_mut() {
+            Some(generator) => generator,
+            None => {
+                warn!(
+                    "the provider asked for reply SURBs for connection {} even though we never gave it any",
+                    request.connection_id
+                );
+                return;
+            }
+        };
+
+        let amount = request.amount.min(MAX_REPLY_SURBS_PER_REQUEST) as usize;
+        let reply_surbs = match generator
+            .generate_more_reply_surbs(request.connection_id, amount)
+            .await
+        {
+            Ok(Some(reply_surbs)) => reply_surbs,
+            Ok(None) => {
+                debug!(
+                    "the provider asked for reply SURBs for already closed connection {}",
+                    request.connection_id
+                );
+                return;
+            }
+            Err(err) => {
+                warn!(
+                    "failed to generate reply SURBs for connection {} - {}",
+                    request.connection_id, err
+                );
+                return;
+            }
+        };
+
+        let msg = Message::Request(Request::new_reply_surbs(request.connection_id, reply_surbs));
+        let input_message = InputMessage::new_fresh(
+            self.service_provider,
+            msg.into_bytes(),
+            false,
+            TransmissionLane::ConnectionId(request.connection_id),
+        );
+        self.input_sender.unbounded_send(input_message).unwrap();
+    }
+
+    async fn on_message(&mut self, reconstructed_message: ReconstructedMessage) {
         let raw_message = reconstructed_message.message;
         if reconstructed_message.reply_surb.is_some() {
             warn!("this message had a surb - we didn't do anything with it");
@@ -66,6 +126,10 @@ impl MixnetResponseListener {
                 );
                 return;
             }
+            Ok(Message::ReplySurbsRequest(request)) => {
+                self.on_reply_surbs_request(request).await;
+                return;
+            }
         };
 
         self.controller_sender
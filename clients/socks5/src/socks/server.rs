@@ -4,13 +4,15 @@ use super::{
     mixnet_responses::MixnetResponseListener,
     types::{ResponseCode, SocksProxyError},
 };
-use client_connections::ClosedConnectionSender;
+use client_connections::{ClosedConnectionSender, TransmissionLane};
 use client_core::client::{
-    inbound_messages::InputMessageSender, received_buffer::ReceivedBufferRequestSender,
+    inbound_messages::InputMessageSender, real_messages_control::FailedTransmissionReceiver,
+    received_buffer::ReceivedBufferRequestSender, reply_surb_generator::ReplySurbGenerator,
 };
+use futures::StreamExt;
 use log::*;
 use nymsphinx::addressing::clients::Recipient;
-use proxy_helpers::connection_controller::Controller;
+use proxy_helpers::connection_controller::{Controller, ControllerCommand, ControllerSender};
 use std::net::SocketAddr;
 use task::ShutdownListener;
 use tokio::net::TcpListener;
@@ -21,6 +23,7 @@ pub struct SphinxSocksServer {
     listening_address: SocketAddr,
     service_provider: Recipient,
     self_address: Recipient,
+    reply_surb_generator: Option<ReplySurbGenerator>,
     shutdown: ShutdownListener,
 }
 
@@ -31,6 +34,7 @@ impl SphinxSocksServer {
         authenticator: Authenticator,
         service_provider: Recipient,
         self_address: Recipient,
+        reply_surb_generator: Option<ReplySurbGenerator>,
         shutdown: ShutdownListener,
     ) -> Self {
         // hardcode ip as we (presumably) ONLY want to listen locally. If we change it, we can
@@ -42,10 +46,33 @@ impl SphinxSocksServer {
             listening_address: format!("{}:{}", ip, port).parse().unwrap(),
             service_provider,
             self_address,
+            reply_surb_generator,
             shutdown,
         }
     }
 
+    /// Tears down the connections whose data was given up on, as the remote would never
+    /// receive the full stream anyway.
+    async fn abort_failed_connections(
+        mut failed_transmission_rx: FailedTransmissionReceiver,
+        controller_sender: ControllerSender,
+    ) {
+        while let Some(failed) = failed_transmission_rx.next().await {
+            if let TransmissionLane::ConnectionId(connection_id) = failed.lane {
+                warn!(
+                    "Data sent on connection {} was not delivered after {} retransmissions - closing the connection",
+                    connection_id, failed.retransmissions
+                );
+                if controller_sender
+                    .unbounded_send(ControllerCommand::Abort(connection_id))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+    }
+
     /// Set up the listener and initiate connection handling when something
     /// connects to the server.
     pub(crate) async fn serve(
@@ -53,6 +80,7 @@ impl SphinxSocksServer {
         input_sender: InputMessageSender,
         buffer_requester: ReceivedBufferRequestSender,
         closed_connection_tx: ClosedConnectionSender,
+        failed_transmission_rx: FailedTransmissionReceiver,
     ) -> Result<(), SocksProxyError> {
         let listener = TcpListener::bind(self.listening_address).await.unwrap();
         info!("Serving Connections...");
@@ -68,12 +96,20 @@ impl SphinxSocksServer {
         let mut mixnet_response_listener = MixnetResponseListener::new(
             buffer_requester,
             controller_sender.clone(),
+            input_sender.clone(),
+            self.service_provider,
+            self.reply_surb_generator.clone(),
             self.shutdown.clone(),
         );
         tokio::spawn(async move {
             mixnet_response_listener.run().await;
         });
 
+        tokio::spawn(Self::abort_failed_connections(
+            failed_transmission_rx,
+            controller_sender.clone(),
+        ));
+
         loop {
             tokio::select! {
                 Ok((stream, _remote)) = listener.accept() => {
@@ -85,6 +121,7 @@ impl SphinxSocksServer {
                         self.service_provider,
                         controller_sender.clone(),
                         self.self_address,
+                        self.reply_surb_generator.clone(),
                         self.shutdown.clone(),
                     );
 
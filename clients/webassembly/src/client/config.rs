@@ -5,6 +5,7 @@
 #![allow(clippy::drop_non_drop)]
 
 use client_core::config::{Debug as ConfigDebug, ExtendedPacketSize, GatewayEndpoint};
+use network_defaults::mainnet;
 use std::time::Duration;
 use url::Url;
 use wasm_bindgen::prelude::*;
@@ -16,6 +17,9 @@ pub struct Config {
 
     pub(crate) validator_api_url: Url,
 
+    /// Human readable part of the checksummed recipient addresses used by the network.
+    pub(crate) recipient_hrp: String,
+
     pub(crate) disabled_credentials_mode: bool,
 
     /// Information regarding how the client should send data to gateway.
@@ -38,11 +42,17 @@ impl Config {
             validator_api_url: validator_server
                 .parse()
                 .expect("provided url was malformed"),
+            recipient_hrp: mainnet::RECIPIENT_HRP.to_owned(),
             disabled_credentials_mode: true,
             gateway_endpoint,
             debug: debug.map(Into::into).unwrap_or_default(),
         }
     }
+
+    /// Overrides the human readable part of recipient addresses, for networks other than mainnet.
+    pub fn set_recipient_hrp(&mut self, recipient_hrp: String) {
+        self.recipient_hrp = recipient_hrp;
+    }
 }
 
 // just a helper structure to more easily pass through the JS boundary
@@ -70,6 +80,11 @@ pub struct Debug {
     /// In an ideal network with 0 latency, this value would have been 0.
     pub ack_wait_addition_ms: u64,
 
+    /// Maximum number of times a packet is going to get retransmitted before it is given up on
+    /// and considered lost. Each retransmission doubles the time waited for the acknowledgement.
+    /// If set to 0, packets are retransmitted until they get acknowledged.
+    pub maximum_retransmissions: u32,
+
     /// The parameter of Poisson distribution determining how long, on average,
     /// it is going to take for another loop cover traffic message to be sent.
     pub loop_cover_traffic_average_delay_ms: u64,
@@ -103,6 +118,13 @@ pub struct Debug {
 
     /// Controls whether the sent sphinx packet use the NON-DEFAULT bigger size.
     pub use_extended_packet_size: bool,
+
+    /// Controls whether the sent packets use the outfox format rather than sphinx.
+    pub use_outfox_packets: bool,
+
+    /// Controls whether the client should use the legacy registration handshake with its gateway
+    /// rather than the Noise-based one that establishes fresh session keys for every connection.
+    pub use_legacy_gateway_handshake: bool,
 }
 
 impl From<Debug> for ConfigDebug {
@@ -115,8 +137,10 @@ impl From<Debug> for ConfigDebug {
         ConfigDebug {
             average_packet_delay: Duration::from_millis(debug.average_packet_delay_ms),
             average_ack_delay: Duration::from_millis(debug.average_ack_delay_ms),
+            ack_delay_distribution: Default::default(),
             ack_wait_multiplier: debug.ack_wait_multiplier,
             ack_wait_addition: Duration::from_millis(debug.ack_wait_addition_ms),
+            maximum_retransmissions: debug.maximum_retransmissions,
             loop_cover_traffic_average_delay: Duration::from_millis(
                 debug.loop_cover_traffic_average_delay_ms,
             ),
@@ -132,6 +156,8 @@ impl From<Debug> for ConfigDebug {
             disable_main_poisson_packet_distribution: debug
                 .disable_main_poisson_packet_distribution,
             use_extended_packet_size,
+            use_outfox_packets: debug.use_outfox_packets,
+            use_legacy_gateway_handshake: debug.use_legacy_gateway_handshake,
         }
     }
 }
@@ -143,6 +169,7 @@ impl From<ConfigDebug> for Debug {
             average_ack_delay_ms: debug.average_ack_delay.as_millis() as u64,
             ack_wait_multiplier: debug.ack_wait_multiplier,
             ack_wait_addition_ms: debug.ack_wait_addition.as_millis() as u64,
+            maximum_retransmissions: debug.maximum_retransmissions,
             loop_cover_traffic_average_delay_ms: debug.loop_cover_traffic_average_delay.as_millis()
                 as u64,
             message_sending_average_delay_ms: debug.message_sending_average_delay.as_millis()
@@ -154,6 +181,8 @@ impl From<ConfigDebug> for Debug {
             disable_main_poisson_packet_distribution: debug
                 .disable_main_poisson_packet_distribution,
             use_extended_packet_size: debug.use_extended_packet_size.is_some(),
+            use_outfox_packets: debug.use_outfox_packets,
+            use_legacy_gateway_handshake: debug.use_legacy_gateway_handshake,
         }
     }
 }
@@ -23,6 +23,7 @@ use gateway_client::{
     MixnetMessageSender,
 };
 use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::params::PacketMode;
 use rand::rngs::OsRng;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::spawn_local;
@@ -107,7 +108,10 @@ impl NymClient {
 
         let mut stream = LoopCoverTrafficStream::new(
             self.key_manager.ack_key(),
-            self.config.debug.average_ack_delay,
+            self.config
+                .debug
+                .ack_delay_distribution
+                .with_average(self.config.debug.average_ack_delay),
             self.config.debug.average_packet_delay,
             self.config.debug.loop_cover_traffic_average_delay,
             mix_tx,
@@ -119,6 +123,10 @@ impl NymClient {
             stream.set_custom_packet_size(size.clone().into());
         }
 
+        if self.config.debug.use_outfox_packets {
+            stream.set_custom_packet_mode(PacketMode::Outfox);
+        }
+
         stream.start();
     }
 
@@ -134,7 +142,10 @@ impl NymClient {
             self.key_manager.ack_key(),
             self.config.debug.ack_wait_multiplier,
             self.config.debug.ack_wait_addition,
-            self.config.debug.average_ack_delay,
+            self.config
+                .debug
+                .ack_delay_distribution
+                .with_average(self.config.debug.average_ack_delay),
             self.config.debug.message_sending_average_delay,
             self.config.debug.average_packet_delay,
             self.config.debug.disable_main_poisson_packet_distribution,
@@ -145,6 +156,12 @@ impl NymClient {
             controller_config.set_custom_packet_size(size.clone().into());
         }
 
+        if self.config.debug.use_outfox_packets {
+            controller_config.set_custom_packet_mode(PacketMode::Outfox);
+        }
+
+        controller_config.set_maximum_retransmissions(self.config.debug.maximum_retransmissions);
+
         console_log!("Starting real traffic stream...");
 
         RealMessagesController::new(
@@ -208,6 +225,7 @@ impl NymClient {
         );
 
         gateway_client.set_disabled_credentials_mode(self.config.disabled_credentials_mode);
+        gateway_client.with_legacy_handshake(self.config.debug.use_legacy_gateway_handshake);
 
         let shared_keys = gateway_client
             .authenticate_and_start()
@@ -373,17 +391,22 @@ impl NymClient {
 
     // Right now it's impossible to have async exported functions to take `&mut self` rather than mut self
     // TODO: try Rc<RefCell<Self>> approach?
-    pub async fn send_message(self, message: String, recipient: String) -> Self {
+    pub async fn send_message(self, message: String, recipient: String) -> Result<Self, JsValue> {
         console_log!("Sending {} to {}", message, recipient);
 
         let message_bytes = message.into_bytes();
         self.send_binary_message(message_bytes, recipient).await
     }
 
-    pub async fn send_binary_message(self, message: Vec<u8>, recipient: String) -> Self {
+    pub async fn send_binary_message(
+        self,
+        message: Vec<u8>,
+        recipient: String,
+    ) -> Result<Self, JsValue> {
         console_log!("Sending {} bytes to {}", message.len(), recipient);
 
-        let recipient = Recipient::try_from_base58_string(recipient).unwrap();
+        let recipient = Recipient::try_from_string(recipient, &self.config.recipient_hrp)
+            .map_err(|err| JsValue::from_str(&format!("invalid recipient - {}", err)))?;
         let lane = TransmissionLane::General;
 
         let input_msg = InputMessage::new_fresh(recipient, message, false, lane);
@@ -394,6 +417,6 @@ impl NymClient {
             .unbounded_send(input_msg)
             .unwrap();
 
-        self
+        Ok(self)
     }
 }
@@ -2,30 +2,37 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use client_core::config::GatewayEndpoint;
+use topology::gateway;
 use wasm_bindgen::prelude::*;
 
+// pages served over https are not allowed to open plain websocket connections,
+// so always prefer the TLS endpoint if the gateway has announced one
+fn gateway_endpoint(gateway: &gateway::Node) -> GatewayEndpoint {
+    GatewayEndpoint {
+        gateway_id: gateway.identity_key.to_base58_string(),
+        gateway_owner: gateway.owner.clone(),
+        gateway_listener: gateway.clients_address(true),
+    }
+}
+
 #[wasm_bindgen]
 pub async fn get_gateway(api_server: String, preferred: Option<String>) -> GatewayEndpoint {
     let validator_client = validator_client::ApiClient::new(api_server.parse().unwrap());
 
     let gateways = match validator_client.get_cached_gateways().await {
         Err(err) => panic!("failed to obtain list of all gateways - {}", err),
-        Ok(gateways) => gateways,
+        Ok(gateways) => gateways
+            .iter()
+            .filter_map(|bond| gateway::Node::try_from(bond).ok())
+            .collect::<Vec<_>>(),
     };
 
     if let Some(preferred) = preferred {
         if let Some(details) = gateways
             .iter()
-            .find(|g| g.gateway.identity_key == preferred)
+            .find(|g| g.identity_key.to_base58_string() == preferred)
         {
-            return GatewayEndpoint {
-                gateway_id: details.gateway.identity_key.clone(),
-                gateway_owner: details.owner.to_string(),
-                gateway_listener: format!(
-                    "ws://{}:{}",
-                    details.gateway.host, details.gateway.clients_port
-                ),
-            };
+            return gateway_endpoint(details);
         }
     }
 
@@ -33,12 +40,5 @@ pub async fn get_gateway(api_server: String, preferred: Option<String>) -> Gatew
         .first()
         .expect("current topology holds no gateways");
 
-    GatewayEndpoint {
-        gateway_id: details.gateway.identity_key.clone(),
-        gateway_owner: details.owner.to_string(),
-        gateway_listener: format!(
-            "ws://{}:{}",
-            details.gateway.host, details.gateway.clients_port
-        ),
-    }
+    gateway_endpoint(details)
 }
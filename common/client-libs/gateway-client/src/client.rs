@@ -15,11 +15,13 @@ use crate::wasm_storage::PersistentStorage;
 use coconut_interface::Credential;
 #[cfg(not(target_arch = "wasm32"))]
 use credential_storage::PersistentStorage;
-use crypto::asymmetric::identity;
+use crypto::asymmetric::{encryption, identity};
 use futures::{FutureExt, SinkExt, StreamExt};
 use gateway_requests::authentication::encrypted_address::EncryptedAddressBytes;
 use gateway_requests::iv::IV;
-use gateway_requests::registration::handshake::{client_handshake, SharedKeys};
+use gateway_requests::registration::handshake::{
+    client_handshake, client_noise_handshake, ConnectionKeys, SharedKeys,
+};
 use gateway_requests::{BinaryRequest, ClientControlRequest, ServerResponse};
 use log::*;
 use network_defaults::{REMAINING_BANDWIDTH_THRESHOLD, TOKENS_TO_BURN};
@@ -49,9 +51,15 @@ pub struct GatewayClient {
     bandwidth_remaining: i64,
     gateway_address: String,
     gateway_identity: identity::PublicKey,
+    /// Sphinx key the gateway is expected to present during the Noise handshake, if known.
+    gateway_sphinx_key: Option<encryption::PublicKey>,
     gateway_owner: String,
     local_identity: Arc<identity::KeyPair>,
     shared_key: Option<Arc<SharedKeys>>,
+    connection_keys: Option<ConnectionKeys>,
+    /// Specifies whether the client should use the legacy registration handshake and the static
+    /// shared keys rather than establishing fresh session keys on every connection.
+    use_legacy_handshake: bool,
     connection: SocketState,
     packet_router: PacketRouter,
     response_timeout_duration: Duration,
@@ -92,9 +100,12 @@ impl GatewayClient {
             bandwidth_remaining: 0,
             gateway_address,
             gateway_identity,
+            gateway_sphinx_key: None,
             gateway_owner,
             local_identity,
             shared_key,
+            connection_keys: None,
+            use_legacy_handshake: true,
             connection: SocketState::NotConnected,
             packet_router: PacketRouter::new(
                 ack_sender,
@@ -129,6 +140,14 @@ impl GatewayClient {
         self.reconnection_backoff = backoff
     }
 
+    pub fn with_legacy_handshake(&mut self, use_legacy_handshake: bool) {
+        self.use_legacy_handshake = use_legacy_handshake
+    }
+
+    pub fn with_gateway_sphinx_key(&mut self, gateway_sphinx_key: encryption::PublicKey) {
+        self.gateway_sphinx_key = Some(gateway_sphinx_key)
+    }
+
     pub fn new_init(
         gateway_address: String,
         gateway_identity: identity::PublicKey,
@@ -156,9 +175,12 @@ impl GatewayClient {
             bandwidth_remaining: 0,
             gateway_address,
             gateway_identity,
+            gateway_sphinx_key: None,
             gateway_owner,
             local_identity,
             shared_key: None,
+            connection_keys: None,
+            use_legacy_handshake: true,
             connection: SocketState::NotConnected,
             packet_router,
             response_timeout_duration,
@@ -465,6 +487,58 @@ impl GatewayClient {
         }?;
         if self.authenticated {
             self.shared_key = Some(Arc::new(shared_key));
+            self.connection_keys = Some(shared_key.into());
+        }
+        Ok(())
+    }
+
+    async fn register_with_noise_handshake(&mut self) -> Result<(), GatewayClientError> {
+        if !self.connection.is_established() {
+            return Err(GatewayClientError::ConnectionNotEstablished);
+        }
+
+        debug_assert!(self.connection.is_available());
+
+        // it's fine to instantiate it here as it's only used once (during authentication or registration)
+        // and putting it into the GatewayClient struct would be a hassle
+        let mut rng = OsRng;
+
+        let handshake_keys = match &mut self.connection {
+            SocketState::Available(ws_stream) => client_noise_handshake(
+                &mut rng,
+                ws_stream,
+                self.local_identity.as_ref(),
+                self.gateway_identity,
+                self.gateway_sphinx_key,
+            )
+            .await
+            .map_err(GatewayClientError::RegistrationFailure),
+            _ => unreachable!(),
+        }?;
+
+        // as the handshake is performed on every connection, the gateway responds in the same way
+        // as if we have authenticated with the existing keys
+        match self.read_control_response().await? {
+            ServerResponse::Authenticate {
+                status,
+                bandwidth_remaining,
+            } => {
+                self.authenticated = status;
+                self.bandwidth_remaining = bandwidth_remaining;
+            }
+            ServerResponse::Error { message } => {
+                return Err(GatewayClientError::GatewayError(message))
+            }
+            _ => return Err(GatewayClientError::UnexpectedResponse),
+        }
+
+        if self.authenticated {
+            // the gateway never replaces the keys we have registered with before,
+            // so neither should we
+            if self.shared_key.is_none() {
+                self.shared_key = Some(Arc::new(handshake_keys.shared_keys));
+            }
+            self.connection_keys = Some(handshake_keys.session_keys.into());
         }
         Ok(())
     }
@@ -495,6 +569,7 @@ impl GatewayClient {
             .public_key()
             .derive_destination_address();
         let encrypted_address = EncryptedAddressBytes::new(&self_address, shared_key, &iv);
+        let connection_keys = ConnectionKeys::from(*shared_key);
 
         let msg =
             ClientControlRequest::new_authenticate(self_address, encrypted_address, iv).into();
@@ -506,6 +581,9 @@ impl GatewayClient {
             } => {
                 self.authenticated = status;
                 self.bandwidth_remaining = bandwidth_remaining;
+                if self.authenticated {
+                    self.connection_keys = Some(connection_keys);
+                }
                 Ok(())
             }
             ServerResponse::Error { message } => Err(GatewayClientError::GatewayError(message)),
@@ -513,11 +591,14 @@ impl GatewayClient {
         }
     }
 
-    /// Helper method to either call register or authenticate based on self.shared_key value
+    /// Helper method to either call register or authenticate based on self.shared_key value,
+    /// unless the Noise handshake is used, in which case new keys are established every time.
     pub async fn perform_initial_authentication(
         &mut self,
     ) -> Result<Arc<SharedKeys>, GatewayClientError> {
-        if self.shared_key.is_some() {
+        if !self.use_legacy_handshake {
+            self.register_with_noise_handshake().await?;
+        } else if self.shared_key.is_some() {
             self.authenticate(None).await?;
         } else {
             self.register().await?;
@@ -540,7 +621,7 @@ impl GatewayClient {
 
         let msg = ClientControlRequest::new_enc_coconut_bandwidth_credential(
             &credential,
-            self.shared_key.as_ref().unwrap(),
+            self.connection_keys.as_ref().unwrap(),
             iv,
         )
         .into();
@@ -567,7 +648,7 @@ impl GatewayClient {
         if !self.authenticated {
             return Err(GatewayClientError::NotAuthenticated);
         }
-        if self.shared_key.is_none() {
+        if self.connection_keys.is_none() {
             return Err(GatewayClientError::NoSharedKeyAvailable);
         }
         if self.bandwidth_controller.is_none() && !self.disabled_credentials_mode {
@@ -599,7 +680,7 @@ impl GatewayClient {
     fn estimate_required_bandwidth(&self, packets: &[MixPacket]) -> i64 {
         packets
             .iter()
-            .map(|packet| packet.sphinx_packet().len())
+            .map(|packet| packet.packet().len())
             .sum::<usize>() as i64
     }
 
@@ -624,9 +705,9 @@ impl GatewayClient {
             .into_iter()
             .map(|mix_packet| {
                 BinaryRequest::new_forward_request(mix_packet).into_ws_message(
-                    self.shared_key
+                    self.connection_keys
                         .as_ref()
-                        .expect("no shared key present even though we're authenticated!"),
+                        .expect("no connection keys present even though we're authenticated!"),
                 )
             })
             .collect();
@@ -680,9 +761,9 @@ impl GatewayClient {
         if !self.authenticated {
             return Err(GatewayClientError::NotAuthenticated);
         }
-        if (mix_packet.sphinx_packet().len() as i64) > self.bandwidth_remaining {
+        if (mix_packet.packet().len() as i64) > self.bandwidth_remaining {
             return Err(GatewayClientError::NotEnoughBandwidth(
-                mix_packet.sphinx_packet().len() as i64,
+                mix_packet.packet().len() as i64,
                 self.bandwidth_remaining,
             ));
         }
@@ -692,9 +773,9 @@ impl GatewayClient {
         // note: into_ws_message encrypts the requests and adds a MAC on it. Perhaps it should
         // be more explicit in the naming?
         let msg = BinaryRequest::new_forward_request(mix_packet).into_ws_message(
-            self.shared_key
+            self.connection_keys
                 .as_ref()
-                .expect("no shared key present even though we're authenticated!"),
+                .expect("no connection keys present even though we're authenticated!"),
         );
         self.send_with_reconnection_on_failure(msg).await
     }
@@ -734,11 +815,9 @@ impl GatewayClient {
                     PartiallyDelegated::split_and_listen_for_mixnet_messages(
                         *conn,
                         self.packet_router.clone(),
-                        Arc::clone(
-                            self.shared_key
-                                .as_ref()
-                                .expect("no shared key present even though we're authenticated!"),
-                        ),
+                        self.connection_keys
+                            .clone()
+                            .expect("no connection keys present even though we're authenticated!"),
                         #[cfg(not(target_arch = "wasm32"))]
                         self.shutdown.clone(),
                     )
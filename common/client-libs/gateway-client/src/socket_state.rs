@@ -7,10 +7,9 @@ use crate::packet_router::PacketRouter;
 use futures::channel::oneshot;
 use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
-use gateway_requests::registration::handshake::SharedKeys;
+use gateway_requests::registration::handshake::ConnectionKeys;
 use gateway_requests::BinaryResponse;
 use log::*;
-use std::sync::Arc;
 #[cfg(not(target_arch = "wasm32"))]
 use task::ShutdownListener;
 use tungstenite::Message;
@@ -44,14 +43,18 @@ pub(crate) struct PartiallyDelegated {
 }
 
 impl PartiallyDelegated {
-    fn recover_received_plaintexts(ws_msgs: Vec<Message>, shared_key: &SharedKeys) -> Vec<Vec<u8>> {
+    fn recover_received_plaintexts(
+        ws_msgs: Vec<Message>,
+        connection_keys: &ConnectionKeys,
+    ) -> Vec<Vec<u8>> {
         let mut plaintexts = Vec::with_capacity(ws_msgs.len());
         for ws_msg in ws_msgs {
             match ws_msg {
                 Message::Binary(bin_msg) => {
                     // this function decrypts the request and checks the MAC
                     let plaintext = match BinaryResponse::try_from_encrypted_tagged_bytes(
-                        bin_msg, shared_key,
+                        bin_msg,
+                        connection_keys,
                     ) {
                         Ok(bin_response) => match bin_response {
                             BinaryResponse::PushedMixMessage(plaintext) => plaintext,
@@ -88,16 +91,16 @@ impl PartiallyDelegated {
     fn route_socket_messages(
         ws_msgs: Vec<Message>,
         packet_router: &mut PacketRouter,
-        shared_key: &SharedKeys,
+        connection_keys: &ConnectionKeys,
     ) -> Result<(), GatewayClientError> {
-        let plaintexts = Self::recover_received_plaintexts(ws_msgs, shared_key);
+        let plaintexts = Self::recover_received_plaintexts(ws_msgs, connection_keys);
         packet_router.route_received(plaintexts)
     }
 
     pub(crate) fn split_and_listen_for_mixnet_messages(
         conn: WsConn,
         packet_router: PacketRouter,
-        shared_key: Arc<SharedKeys>,
+        connection_keys: ConnectionKeys,
         #[cfg(not(target_arch = "wasm32"))] shutdown: Option<ShutdownListener>,
     ) -> Self {
         // when called for, it NEEDS TO yield back the stream so that we could merge it and
@@ -146,7 +149,7 @@ impl PartiallyDelegated {
                             Ok(msgs) => msgs
                         };
 
-                        if let Err(err) = Self::route_socket_messages(ws_msgs, &mut packet_router, shared_key.as_ref()) {
+                        if let Err(err) = Self::route_socket_messages(ws_msgs, &mut packet_router, &connection_keys) {
                             log::warn!("Route socket messages failed: {:?}", err);
                         }
                     }
@@ -1,13 +1,18 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::hello::{self, HelloError, HelloPeers, DEFAULT_HELLO_TIMEOUT};
+use crate::link::codec::LinkCodec;
+use crate::link::{self, LinkConfig};
 use futures::channel::mpsc;
-use futures::StreamExt;
+use futures::{future, StreamExt};
 use log::*;
-use nymsphinx::framing::codec::SphinxCodec;
+use nymsphinx::framing::codec::MAX_BATCH_SIZE;
+use nymsphinx::framing::hello::{Features, Hello, NegotiatedParameters};
 use nymsphinx::framing::packet::FramedSphinxPacket;
+use nymsphinx::params::packet_version::PacketVersion;
 use nymsphinx::params::PacketMode;
-use nymsphinx::{addressing::nodes::NymNodeRoutingAddress, SphinxPacket};
+use nymsphinx::{addressing::nodes::NymNodeRoutingAddress, NymPacket};
 use std::collections::HashMap;
 use std::io;
 use std::net::SocketAddr;
@@ -23,6 +28,9 @@ pub struct Config {
     maximum_reconnection_backoff: Duration,
     initial_connection_timeout: Duration,
     maximum_connection_buffer_size: usize,
+
+    /// Specifies whether legacy packet version should be used with nodes that don't
+    /// understand the hello. Otherwise the packet version is negotiated per connection.
     use_legacy_version: bool,
 }
 
@@ -50,7 +58,7 @@ pub trait SendWithoutResponse {
     fn send_without_response(
         &mut self,
         address: NymNodeRoutingAddress,
-        packet: SphinxPacket,
+        packet: NymPacket,
         packet_mode: PacketMode,
     ) -> io::Result<()>;
 }
@@ -58,6 +66,8 @@ pub trait SendWithoutResponse {
 pub struct Client {
     conn_new: HashMap<NymNodeRoutingAddress, ConnectionSender>,
     config: Config,
+    link_config: Option<LinkConfig>,
+    hello_peers: HelloPeers,
 }
 
 struct ConnectionSender {
@@ -79,31 +89,41 @@ impl Client {
         Client {
             conn_new: HashMap::new(),
             config,
+            link_config: None,
+            hello_peers: HelloPeers::new(),
         }
     }
 
-    async fn manage_connection(
+    /// Makes the client attempt to establish encrypted links with all the nodes whose
+    /// identities are known.
+    #[must_use]
+    pub fn with_link_encryption(mut self, link_config: LinkConfig) -> Self {
+        self.link_config = Some(link_config);
+        self
+    }
+
+    async fn connect(
         address: SocketAddr,
-        receiver: mpsc::Receiver<FramedSphinxPacket>,
         connection_timeout: Duration,
         current_reconnection: &AtomicU32,
-    ) {
+    ) -> Option<TcpStream> {
         let connection_fut = TcpStream::connect(address);
 
-        let conn = match tokio::time::timeout(connection_timeout, connection_fut).await {
+        match tokio::time::timeout(connection_timeout, connection_fut).await {
             Ok(stream_res) => match stream_res {
                 Ok(stream) => {
                     debug!("Managed to establish connection to {}", address);
-                    // if we managed to connect, reset the reconnection count (whatever it might have been)
-                    current_reconnection.store(0, Ordering::Release);
-                    Framed::new(stream, SphinxCodec)
+                    Some(stream)
                 }
                 Err(err) => {
                     debug!(
                         "failed to establish connection to {} (err: {})",
                         address, err
                     );
-                    return;
+
+                    // we failed to connect - increase reconnection attempt
+                    current_reconnection.fetch_add(1, Ordering::SeqCst);
+                    None
                 }
             },
             Err(_) => {
@@ -114,14 +134,231 @@ impl Client {
 
                 // we failed to connect - increase reconnection attempt
                 current_reconnection.fetch_add(1, Ordering::SeqCst);
-                return;
+                None
+            }
+        }
+    }
+
+    /// Exchanges hellos over the fresh connection in order to negotiate the packet version and
+    /// features to use with the remote. If the remote does not understand the hello,
+    /// a new connection is established instead and `None` is returned alongside it.
+    async fn negotiate_parameters(
+        mut conn: TcpStream,
+        address: SocketAddr,
+        local_hello: &Hello,
+        hello_peers: &HelloPeers,
+        connection_timeout: Duration,
+        current_reconnection: &AtomicU32,
+    ) -> Option<(TcpStream, Option<NegotiatedParameters>)> {
+        if hello_peers.is_legacy(&address) {
+            return Some((conn, None));
+        }
+
+        match hello::initiate_hello(&mut conn, local_hello, DEFAULT_HELLO_TIMEOUT).await {
+            Ok(remote_hello) => match local_hello.negotiate(&remote_hello) {
+                Some(negotiated) => {
+                    debug!(
+                        "Negotiated packet version {:?} with {}",
+                        negotiated.packet_version, address
+                    );
+                    Some((conn, Some(negotiated)))
+                }
+                None => {
+                    warn!("{} does not support any of our packet versions", address);
+                    current_reconnection.fetch_add(1, Ordering::SeqCst);
+                    None
+                }
+            },
+            Err(HelloError::ConnectionClosed) => {
+                // it's a legacy node that dropped the connection upon receiving the hello
+                // - remember it, so that we wouldn't attempt the exchange every time
+                debug!(
+                    "{} does not understand the hello. Falling back to the default packet version",
+                    address
+                );
+                hello_peers.mark_legacy(address);
+                Self::connect(address, connection_timeout, current_reconnection)
+                    .await
+                    .map(|conn| (conn, None))
+            }
+            Err(err) => {
+                warn!("failed to exchange hello with {} - {}", address, err);
+                current_reconnection.fetch_add(1, Ordering::SeqCst);
+                None
+            }
+        }
+    }
+
+    /// Attempts to establish an encrypted link over the fresh connection if the identity of
+    /// the remote is known and it supports the links, according to either its announced version
+    /// or its hello.
+    /// If the remote is known not to support the links (or its identity is unknown), the plaintext
+    /// connection is used instead, if the configuration allows it.
+    /// Note that a failed handshake never results in a plaintext connection, as otherwise anyone
+    /// able to interfere with it could downgrade the link.
+    async fn establish_link(
+        conn: TcpStream,
+        address: SocketAddr,
+        link_config: &LinkConfig,
+        remote_supports_link: bool,
+        current_reconnection: &AtomicU32,
+    ) -> Option<Framed<TcpStream, LinkCodec>> {
+        let remote_identity = match link_config.peers.identity_of(&address) {
+            Some(remote_identity) if remote_supports_link => remote_identity,
+            _ if link_config.allow_plaintext_fallback => {
+                return Some(Framed::new(conn, LinkCodec::plaintext()))
+            }
+            Some(_) => {
+                warn!(
+                    "{} does not support encrypted links and plaintext connections are not allowed",
+                    address
+                );
+                current_reconnection.fetch_add(1, Ordering::SeqCst);
+                return None;
+            }
+            None => {
+                warn!(
+                    "the identity of {} is unknown and plaintext connections are not allowed",
+                    address
+                );
+                current_reconnection.fetch_add(1, Ordering::SeqCst);
+                return None;
+            }
+        };
+
+        let mut conn = conn;
+        match link::initiate_link(
+            &mut conn,
+            &link_config.local_identity,
+            remote_identity,
+            link_config.handshake_timeout,
+        )
+        .await
+        {
+            Ok(codec) => {
+                debug!("Established encrypted link with {}", address);
+                Some(Framed::new(conn, codec))
+            }
+            Err(err) => {
+                warn!(
+                    "failed to establish encrypted link with {} - {}",
+                    address, err
+                );
+                current_reconnection.fetch_add(1, Ordering::SeqCst);
+                None
+            }
+        }
+    }
+
+    async fn manage_connection(
+        address: SocketAddr,
+        receiver: mpsc::Receiver<FramedSphinxPacket>,
+        connection_timeout: Duration,
+        current_reconnection: &AtomicU32,
+        link_config: Option<LinkConfig>,
+        hello_peers: HelloPeers,
+        fallback_version: PacketVersion,
+    ) {
+        let conn = match Self::connect(address, connection_timeout, current_reconnection).await {
+            Some(conn) => conn,
+            None => return,
+        };
+
+        let local_features = if link_config.is_some() {
+            Features::LINK_ENCRYPTION | Features::OUTFOX
+        } else {
+            Features::OUTFOX
+        };
+        let (conn, negotiated) = match Self::negotiate_parameters(
+            conn,
+            address,
+            &Hello::new(local_features),
+            &hello_peers,
+            connection_timeout,
+            current_reconnection,
+        )
+        .await
+        {
+            Some(negotiated) => negotiated,
+            None => return,
+        };
+
+        let conn = match &link_config {
+            Some(link_config) => {
+                // the hello is not authenticated, so its absence (or the lack of the feature)
+                // must never override the version the remote has announced in the topology
+                let remote_supports_link = link_config.peers.supports_links(&address)
+                    || negotiated
+                        .as_ref()
+                        .map(|negotiated| negotiated.features.contains(Features::LINK_ENCRYPTION))
+                        .unwrap_or_default();
+                match Self::establish_link(
+                    conn,
+                    address,
+                    link_config,
+                    remote_supports_link,
+                    current_reconnection,
+                )
+                .await
+                {
+                    Some(conn) => conn,
+                    None => return,
+                }
             }
+            None => Framed::new(conn, LinkCodec::plaintext()),
         };
 
+        // only now the connection is actually usable, so reset the reconnection count
+        // (whatever it might have been)
+        current_reconnection.store(0, Ordering::Release);
+
+        let packet_version = negotiated
+            .as_ref()
+            .map(|negotiated| negotiated.packet_version)
+            .unwrap_or(fallback_version);
+
+        // the packets got framed before we knew what the remote supports,
+        // so make sure they're sent in the format it understands
+        let packets = receiver.filter_map(move |mut framed_packet| {
+            // nodes that don't say otherwise, only understand sphinx packets
+            let supports_outfox = negotiated
+                .as_ref()
+                .map(|negotiated| negotiated.features.contains(Features::OUTFOX))
+                .unwrap_or_default();
+            if framed_packet.packet_mode().is_outfox() && !supports_outfox {
+                debug!(
+                    "{} does not support outfox packets - dropping the packet",
+                    address
+                );
+                return future::ready(None);
+            }
+            if let Some(negotiated) = &negotiated {
+                if !negotiated.supports_packet_size(framed_packet.packet_size()) {
+                    debug!(
+                        "{} does not support {:?} packets - dropping the packet",
+                        address,
+                        framed_packet.packet_size()
+                    );
+                    return future::ready(None);
+                }
+            }
+            framed_packet.set_packet_version(packet_version);
+            future::ready(Some(framed_packet))
+        });
+
         // Take whatever the receiver channel produces and put it on the connection.
-        // We could have as well used conn.send_all(receiver.map(Ok)), but considering we don't care
-        // about neither receiver nor the connection, it doesn't matter which one gets consumed
-        if let Err(err) = receiver.map(Ok).forward(conn).await {
+        // We could have as well used conn.send_all(packets), but considering we don't care
+        // about neither receiver nor the connection, it doesn't matter which one gets consumed.
+        // Whatever has already been queued up by the time we're ready to write goes out in
+        // a single write (and a single link frame, if the link is encrypted). The batch consists
+        // of consecutive self-delimiting frames, so every node is able to read it, while
+        // all nodes supporting the encrypted links also accept batched link frames.
+        if let Err(err) = packets
+            .ready_chunks(MAX_BATCH_SIZE)
+            .map(Ok)
+            .forward(conn)
+            .await
+        {
             warn!("Failed to forward packets to {} - {:?}", address, err);
         }
 
@@ -175,8 +412,11 @@ impl Client {
         let reconnection_attempt = current_reconnection_attempt.load(Ordering::Acquire);
         let backoff = self.determine_backoff(reconnection_attempt);
 
-        // copy the value before moving into another task
+        // copy the values before moving into another task
         let initial_connection_timeout = self.config.initial_connection_timeout;
+        let link_config = self.link_config.clone();
+        let hello_peers = self.hello_peers.clone();
+        let fallback_version = PacketVersion::new(self.config.use_legacy_version);
 
         tokio::spawn(async move {
             // before executing the manager, wait for what was specified, if anything
@@ -190,6 +430,9 @@ impl Client {
                 receiver,
                 initial_connection_timeout,
                 &current_reconnection_attempt,
+                link_config,
+                hello_peers,
+                fallback_version,
             )
             .await
         });
@@ -200,7 +443,7 @@ impl SendWithoutResponse for Client {
     fn send_without_response(
         &mut self,
         address: NymNodeRoutingAddress,
-        packet: SphinxPacket,
+        packet: NymPacket,
         packet_mode: PacketMode,
     ) -> io::Result<()> {
         trace!("Sending packet to {:?}", address);
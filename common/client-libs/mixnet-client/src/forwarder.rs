@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::client::{Client, Config, SendWithoutResponse};
+use crate::link::LinkConfig;
 use futures::channel::mpsc;
 use futures::StreamExt;
 use log::*;
@@ -45,19 +46,27 @@ impl PacketForwarder {
         )
     }
 
+    /// Makes the forwarder attempt to establish encrypted links with all the nodes whose
+    /// identities are known.
+    #[must_use]
+    pub fn with_link_encryption(mut self, link_config: LinkConfig) -> Self {
+        self.mixnet_client = self.mixnet_client.with_link_encryption(link_config);
+        self
+    }
+
     pub async fn run(&mut self) {
         while let Some(mix_packet) = self.packet_receiver.next().await {
             trace!("Going to forward packet to {:?}", mix_packet.next_hop());
 
             let next_hop = mix_packet.next_hop();
             let packet_mode = mix_packet.packet_mode();
-            let sphinx_packet = mix_packet.into_sphinx_packet();
+            let packet = mix_packet.into_packet();
             // we don't care about responses, we just want to fire packets
             // as quickly as possible
 
             if let Err(err) =
                 self.mixnet_client
-                    .send_without_response(next_hop, sphinx_packet, packet_mode)
+                    .send_without_response(next_hop, packet, packet_mode)
             {
                 debug!("failed to forward the packet - {}", err)
             }
@@ -0,0 +1,272 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Exchange of the connection-level hello, through which the nodes discover the packet versions
+//! and features supported by both of them.
+//!
+//! The initiator of a connection sends its hello before anything else, i.e. even before the link
+//! preamble, and waits for the hello of the remote. Nodes that don't understand it simply drop
+//! the connection without sending anything back, which is the signal for the initiator to
+//! reconnect and use its fallback packet version instead. Any other failure of the exchange
+//! is treated as a failure of the connection.
+
+use bytes::{BufMut, BytesMut};
+use nymsphinx::framing::hello::{Hello, HELLO_MAGIC, HELLO_PREFIX_SIZE};
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Default maximum duration of the hello exchange.
+pub const DEFAULT_HELLO_TIMEOUT: Duration = Duration::from_millis(1_500);
+
+/// How long a node that has dropped the connection upon receiving the hello is assumed not to
+/// understand it before we attempt the exchange again.
+const LEGACY_PEER_RECHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Error)]
+pub enum HelloError {
+    #[error("experienced io error during the hello exchange - {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("the hello exchange has not completed in time")]
+    Timeout,
+
+    #[error("the remote has closed the connection without responding to the hello")]
+    ConnectionClosed,
+
+    #[error("received malformed hello message")]
+    MalformedHello,
+}
+
+/// Set of nodes that don't understand the hello.
+///
+/// Note that cloning it produces a handle to the same underlying data.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HelloPeers {
+    legacy: Arc<RwLock<HashMap<SocketAddr, Instant>>>,
+}
+
+impl HelloPeers {
+    pub(crate) fn new() -> Self {
+        Default::default()
+    }
+
+    /// Remembers that the node at the provided address does not understand the hello,
+    /// so that we wouldn't attempt the exchange on every connection.
+    pub(crate) fn mark_legacy(&self, address: SocketAddr) {
+        self.legacy
+            .write()
+            .expect("hello peers lock got poisoned")
+            .insert(address, Instant::now());
+    }
+
+    /// Checks whether the node at the provided address has recently dropped the connection
+    /// upon receiving the hello.
+    pub(crate) fn is_legacy(&self, address: &SocketAddr) -> bool {
+        let mut guard = self.legacy.write().expect("hello peers lock got poisoned");
+        match guard.get(address) {
+            Some(marked) if marked.elapsed() < LEGACY_PEER_RECHECK_INTERVAL => true,
+            Some(_) => {
+                guard.remove(address);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+async fn read_hello(conn: &mut TcpStream) -> Result<Hello, HelloError> {
+    let mut prefix = [0u8; HELLO_PREFIX_SIZE];
+    conn.read_exact(&mut prefix).await?;
+    let hello_len = Hello::encoded_len(prefix).map_err(|_| HelloError::MalformedHello)?;
+
+    let mut hello_bytes = BytesMut::with_capacity(hello_len);
+    hello_bytes.put_slice(&prefix);
+    hello_bytes.resize(hello_len, 0);
+    conn.read_exact(&mut hello_bytes[HELLO_PREFIX_SIZE..])
+        .await?;
+
+    match Hello::decode(&mut hello_bytes) {
+        Ok(Some(hello)) => Ok(hello),
+        _ => Err(HelloError::MalformedHello),
+    }
+}
+
+async fn exchange_hello(conn: &mut TcpStream, local_hello: &Hello) -> Result<Hello, HelloError> {
+    conn.write_all(&local_hello.to_bytes()).await?;
+
+    // legacy nodes drop the connection upon failing to decode the hello
+    // without ever sending anything back
+    let mut first_byte = [0u8; 1];
+    match conn.peek(&mut first_byte).await {
+        Ok(0) => return Err(HelloError::ConnectionClosed),
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+            ) =>
+        {
+            return Err(HelloError::ConnectionClosed)
+        }
+        Err(err) => return Err(err.into()),
+        Ok(_) => (),
+    }
+
+    read_hello(conn).await
+}
+
+/// Sends our hello over the fresh connection and waits for the hello of the remote.
+pub async fn initiate_hello(
+    conn: &mut TcpStream,
+    local_hello: &Hello,
+    hello_timeout: Duration,
+) -> Result<Hello, HelloError> {
+    tokio::time::timeout(hello_timeout, exchange_hello(conn, local_hello))
+        .await
+        .map_err(|_| HelloError::Timeout)?
+}
+
+async fn respond_to_hello(
+    conn: &mut TcpStream,
+    local_hello: &Hello,
+) -> Result<Option<Hello>, HelloError> {
+    let mut first_byte = [0u8; 1];
+    if conn.peek(&mut first_byte).await? == 0 {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    if first_byte[0] != HELLO_MAGIC {
+        return Ok(None);
+    }
+
+    let remote_hello = read_hello(conn).await?;
+    conn.write_all(&local_hello.to_bytes()).await?;
+    Ok(Some(remote_hello))
+}
+
+/// Determines, based on the first byte received, whether the remote has started the connection
+/// with the hello and, if so, responds with our own. It returns the hello of the remote, if any.
+/// Afterwards the connection is ready for the link establishment.
+pub async fn accept_hello(
+    conn: &mut TcpStream,
+    local_hello: &Hello,
+    hello_timeout: Duration,
+) -> Result<Option<Hello>, HelloError> {
+    tokio::time::timeout(hello_timeout, respond_to_hello(conn, local_hello))
+        .await
+        .map_err(|_| HelloError::Timeout)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use nymsphinx::framing::codec::SphinxCodec;
+    use nymsphinx::framing::hello::Features;
+    use nymsphinx::framing::packet::FramedSphinxPacket;
+    use nymsphinx::params::packet_version::PacketVersion;
+    use nymsphinx::params::{PacketMode, PacketSize};
+    use nymsphinx::NymPacket;
+    use tokio::net::TcpListener;
+    use tokio_util::codec::Framed;
+
+    async fn connected_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (initiator, accepted) = tokio::join!(TcpStream::connect(address), listener.accept());
+        (initiator.unwrap(), accepted.unwrap().0)
+    }
+
+    #[tokio::test]
+    async fn hellos_are_exchanged_before_any_packets() {
+        let (mut initiator_conn, mut responder_conn) = connected_pair().await;
+        let initiator_hello = Hello::new(Features::empty());
+        let responder_hello = Hello::new(Features::LINK_ENCRYPTION);
+
+        let (received_by_initiator, received_by_responder) = tokio::join!(
+            initiate_hello(&mut initiator_conn, &initiator_hello, DEFAULT_HELLO_TIMEOUT),
+            accept_hello(&mut responder_conn, &responder_hello, DEFAULT_HELLO_TIMEOUT)
+        );
+        assert_eq!(received_by_initiator.unwrap(), responder_hello);
+        assert_eq!(received_by_responder.unwrap().unwrap(), initiator_hello);
+
+        let negotiated = initiator_hello.negotiate(&responder_hello).unwrap();
+        assert_eq!(negotiated.packet_version, PacketVersion::default());
+
+        // the connection can be used for sending packets afterwards
+        let packet_bytes = vec![42u8; PacketSize::AckPacket.size()];
+        let packet = NymPacket::sphinx_from_bytes(&packet_bytes).unwrap();
+        let mut initiator = Framed::new(initiator_conn, SphinxCodec);
+        initiator
+            .send(FramedSphinxPacket::new(packet, PacketMode::Mix, false))
+            .await
+            .unwrap();
+
+        assert!(
+            accept_hello(&mut responder_conn, &responder_hello, DEFAULT_HELLO_TIMEOUT)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let mut responder = Framed::new(responder_conn, SphinxCodec);
+        let received = responder.next().await.unwrap().unwrap();
+        assert_eq!(received.packet_size(), PacketSize::AckPacket);
+    }
+
+    #[tokio::test]
+    async fn hello_fails_with_legacy_nodes() {
+        let (mut initiator_conn, responder_conn) = connected_pair().await;
+
+        // legacy node treats everything it receives as sphinx frames and drops the connection
+        // upon failing to decode them
+        let responder = tokio::spawn(async move {
+            let mut responder = Framed::new(responder_conn, SphinxCodec);
+            assert!(responder.next().await.unwrap().is_err());
+        });
+
+        let result = initiate_hello(
+            &mut initiator_conn,
+            &Hello::new(Features::empty()),
+            DEFAULT_HELLO_TIMEOUT,
+        )
+        .await;
+        assert!(matches!(result, Err(HelloError::ConnectionClosed)));
+        responder.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unresponsive_nodes_are_not_mistaken_for_legacy_ones() {
+        let (mut initiator_conn, _responder_conn) = connected_pair().await;
+
+        let result = initiate_hello(
+            &mut initiator_conn,
+            &Hello::new(Features::empty()),
+            Duration::from_millis(100),
+        )
+        .await;
+        assert!(matches!(result, Err(HelloError::Timeout)));
+    }
+
+    #[test]
+    fn legacy_peers_are_remembered() {
+        let peers = HelloPeers::new();
+        let address = "127.0.0.1:1789".parse().unwrap();
+        assert!(!peers.is_legacy(&address));
+
+        peers.mark_legacy(address);
+        assert!(peers.is_legacy(&address));
+
+        // once the entry has expired, the hello is attempted again
+        // (unless the monotonic clock started too recently for us to check it)
+        if let Some(expired) = Instant::now().checked_sub(LEGACY_PEER_RECHECK_INTERVAL) {
+            peers.legacy.write().unwrap().insert(address, expired);
+            assert!(!peers.is_legacy(&address));
+            assert!(peers.legacy.read().unwrap().is_empty());
+        }
+    }
+}
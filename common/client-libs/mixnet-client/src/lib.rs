@@ -3,5 +3,7 @@
 
 pub mod client;
 pub mod forwarder;
+pub mod hello;
+pub mod link;
 
 pub use client::{Client, Config, SendWithoutResponse};
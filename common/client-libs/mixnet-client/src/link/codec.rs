@@ -0,0 +1,291 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::link::error::LinkCodecError;
+use crate::link::noise::{counter_nonce, LinkKeys, AEAD_TAG_SIZE};
+use aes_gcm::aead::{AeadInPlace, NewAead};
+use aes_gcm::{Aes256Gcm, Key, Nonce, Tag};
+use bytes::{Buf, BufMut, BytesMut};
+use nymsphinx::framing::codec::{SphinxCodec, SphinxCodecError, MAX_BATCH_SIZE};
+use nymsphinx::framing::packet::FramedSphinxPacket;
+use nymsphinx::params::PacketSize;
+use tokio_util::codec::{Decoder, Encoder};
+
+const FRAME_LENGTH_PREFIX_SIZE: usize = 4;
+
+// generous allowance for the size of the sphinx framing header
+const MAX_SPHINX_FRAMING_HEADER_SIZE: usize = 16;
+
+/// Codec used on an established link. Depending on what got negotiated with the remote,
+/// the sphinx frames are either sent as they are or encrypted with the link keys.
+pub enum LinkCodec {
+    Plaintext(SphinxCodec),
+    Encrypted(Box<EncryptedLinkCodec>),
+}
+
+impl LinkCodec {
+    pub fn plaintext() -> Self {
+        LinkCodec::Plaintext(SphinxCodec)
+    }
+
+    pub(crate) fn encrypted(keys: &LinkKeys) -> Self {
+        LinkCodec::Encrypted(Box::new(EncryptedLinkCodec::new(keys)))
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        matches!(self, LinkCodec::Encrypted(..))
+    }
+}
+
+impl Encoder<FramedSphinxPacket> for LinkCodec {
+    type Error = LinkCodecError;
+
+    fn encode(&mut self, item: FramedSphinxPacket, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        match self {
+            LinkCodec::Plaintext(codec) => Ok(codec.encode(item, dst)?),
+            LinkCodec::Encrypted(codec) => codec.encode(item, dst),
+        }
+    }
+}
+
+impl Encoder<Vec<FramedSphinxPacket>> for LinkCodec {
+    type Error = LinkCodecError;
+
+    fn encode(
+        &mut self,
+        items: Vec<FramedSphinxPacket>,
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        match self {
+            LinkCodec::Plaintext(codec) => Ok(codec.encode(items, dst)?),
+            LinkCodec::Encrypted(codec) => codec.encode(items, dst),
+        }
+    }
+}
+
+impl Decoder for LinkCodec {
+    type Item = FramedSphinxPacket;
+    type Error = LinkCodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self {
+            LinkCodec::Plaintext(codec) => Ok(codec.decode(src)?),
+            LinkCodec::Encrypted(codec) => codec.decode(src),
+        }
+    }
+}
+
+/// Each sphinx frame, or a batch of them, is encrypted separately and sent as
+/// `len (u32, big-endian) || AES-GCM(frame)`, where the nonce is the implicit counter of frames
+/// sent in the given direction. This hides both the content and the type of the exchanged packets
+/// while any modification, reordering or replay of the frames breaks the link.
+///
+/// The frames are encrypted and decrypted in place within the socket buffers, so the only copy
+/// of a received packet is made when the sphinx codec parses it out of the decrypted frame.
+pub struct EncryptedLinkCodec {
+    sphinx_codec: SphinxCodec,
+    sending_cipher: Aes256Gcm,
+    receiving_cipher: Aes256Gcm,
+    sending_nonce: u64,
+    receiving_nonce: u64,
+    max_frame_size: usize,
+
+    /// Decrypted content of the last link frame that hasn't been fully decoded yet,
+    /// i.e. the remainder of a batch.
+    pending_plaintext: BytesMut,
+}
+
+impl EncryptedLinkCodec {
+    fn new(keys: &LinkKeys) -> Self {
+        EncryptedLinkCodec {
+            sphinx_codec: SphinxCodec,
+            sending_cipher: Aes256Gcm::new(Key::from_slice(&keys.sending_key)),
+            receiving_cipher: Aes256Gcm::new(Key::from_slice(&keys.receiving_key)),
+            sending_nonce: 0,
+            receiving_nonce: 0,
+            // the largest batch of sphinx packets alongside their framing headers
+            // and the authentication tag
+            max_frame_size: MAX_BATCH_SIZE
+                * (PacketSize::ExtendedPacket32.size() + MAX_SPHINX_FRAMING_HEADER_SIZE)
+                + AEAD_TAG_SIZE,
+            pending_plaintext: BytesMut::new(),
+        }
+    }
+
+    fn encode<I>(&mut self, item: I, dst: &mut BytesMut) -> Result<(), LinkCodecError>
+    where
+        SphinxCodec: Encoder<I, Error = SphinxCodecError>,
+    {
+        // the length is filled in once we know the size of the encoded frame
+        let frame_start = dst.len();
+        let plaintext_start = frame_start + FRAME_LENGTH_PREFIX_SIZE;
+        dst.reserve(FRAME_LENGTH_PREFIX_SIZE);
+        dst.put_u32(0);
+
+        if let Err(err) = self.sphinx_codec.encode(item, dst) {
+            dst.truncate(frame_start);
+            return Err(err.into());
+        }
+
+        // the encryption can only fail if the plaintext is unreasonably long
+        let tag = self
+            .sending_cipher
+            .encrypt_in_place_detached(
+                Nonce::from_slice(&counter_nonce(self.sending_nonce)),
+                &[],
+                &mut dst[plaintext_start..],
+            )
+            .expect("failed to encrypt the link frame");
+        self.sending_nonce += 1;
+        dst.put_slice(&tag);
+
+        let frame_len = (dst.len() - plaintext_start) as u32;
+        dst[frame_start..plaintext_start].copy_from_slice(&frame_len.to_be_bytes());
+        Ok(())
+    }
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<FramedSphinxPacket>, LinkCodecError> {
+        // finish the current batch before touching the next link frame
+        if !self.pending_plaintext.is_empty() {
+            return self.decode_pending().map(Some);
+        }
+
+        if src.len() < FRAME_LENGTH_PREFIX_SIZE {
+            src.reserve(FRAME_LENGTH_PREFIX_SIZE);
+            return Ok(None);
+        }
+
+        let mut length_bytes = [0u8; FRAME_LENGTH_PREFIX_SIZE];
+        length_bytes.copy_from_slice(&src[..FRAME_LENGTH_PREFIX_SIZE]);
+        let frame_len = u32::from_be_bytes(length_bytes) as usize;
+        if frame_len > self.max_frame_size {
+            return Err(LinkCodecError::FrameTooLarge {
+                received: frame_len,
+                max: self.max_frame_size,
+            });
+        }
+        if frame_len < AEAD_TAG_SIZE {
+            return Err(LinkCodecError::DecryptionFailure);
+        }
+
+        if src.len() < FRAME_LENGTH_PREFIX_SIZE + frame_len {
+            src.reserve(FRAME_LENGTH_PREFIX_SIZE + frame_len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(FRAME_LENGTH_PREFIX_SIZE);
+        let mut frame = src.split_to(frame_len);
+        let tag = frame.split_off(frame_len - AEAD_TAG_SIZE);
+        self.receiving_cipher
+            .decrypt_in_place_detached(
+                Nonce::from_slice(&counter_nonce(self.receiving_nonce)),
+                &[],
+                &mut frame,
+                Tag::from_slice(&tag),
+            )
+            .map_err(|_| LinkCodecError::DecryptionFailure)?;
+        self.receiving_nonce += 1;
+
+        self.pending_plaintext = frame;
+        self.decode_pending().map(Some)
+    }
+
+    // every link frame contains either a single sphinx frame or a batch of complete ones
+    fn decode_pending(&mut self) -> Result<FramedSphinxPacket, LinkCodecError> {
+        self.sphinx_codec
+            .decode(&mut self.pending_plaintext)?
+            .ok_or(LinkCodecError::MalformedFrame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::asymmetric::identity;
+    use nymsphinx::params::PacketMode;
+    use nymsphinx::NymPacket;
+
+    fn dummy_keys() -> (LinkKeys, LinkKeys) {
+        let remote_identity = *identity::KeyPair::new(&mut rand::rngs::OsRng).public_key();
+        (
+            LinkKeys {
+                remote_identity,
+                sending_key: [1; 32],
+                receiving_key: [2; 32],
+            },
+            LinkKeys {
+                remote_identity,
+                sending_key: [2; 32],
+                receiving_key: [1; 32],
+            },
+        )
+    }
+
+    fn dummy_framed_packet() -> FramedSphinxPacket {
+        // the content doesn't matter as long as the packet has valid length
+        let packet_bytes = vec![42u8; PacketSize::AckPacket.size()];
+        let packet = NymPacket::sphinx_from_bytes(&packet_bytes).unwrap();
+        FramedSphinxPacket::new(packet, PacketMode::Mix, false)
+    }
+
+    #[test]
+    fn encrypted_frames_can_be_decoded_by_the_remote() {
+        let (local_keys, remote_keys) = dummy_keys();
+        let mut local = LinkCodec::encrypted(&local_keys);
+        let mut remote = LinkCodec::encrypted(&remote_keys);
+
+        let mut buf = BytesMut::new();
+        for _ in 0..3 {
+            local.encode(dummy_framed_packet(), &mut buf).unwrap();
+        }
+
+        // link frames are not valid sphinx frames
+        assert!(LinkCodec::plaintext().decode(&mut buf.clone()).is_err());
+
+        // while partial frames are simply awaiting more data
+        let mut partial = BytesMut::from(&buf[..10]);
+        assert!(remote.decode(&mut partial).unwrap().is_none());
+
+        for _ in 0..3 {
+            let decoded = remote.decode(&mut buf).unwrap().unwrap();
+            assert_eq!(decoded.packet_size(), PacketSize::AckPacket);
+        }
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn batched_frames_are_encrypted_together() {
+        let (local_keys, remote_keys) = dummy_keys();
+        let mut local = LinkCodec::encrypted(&local_keys);
+        let mut remote = LinkCodec::encrypted(&remote_keys);
+
+        let mut buf = BytesMut::new();
+        let batch = (0..3).map(|_| dummy_framed_packet()).collect::<Vec<_>>();
+        local.encode(batch, &mut buf).unwrap();
+        local.encode(dummy_framed_packet(), &mut buf).unwrap();
+
+        for _ in 0..4 {
+            let decoded = remote.decode(&mut buf).unwrap().unwrap();
+            assert_eq!(decoded.packet_size(), PacketSize::AckPacket);
+        }
+        assert!(buf.is_empty());
+        assert!(remote.decode(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn replayed_frames_are_rejected() {
+        let (local_keys, remote_keys) = dummy_keys();
+        let mut local = LinkCodec::encrypted(&local_keys);
+        let mut remote = LinkCodec::encrypted(&remote_keys);
+
+        let mut buf = BytesMut::new();
+        local.encode(dummy_framed_packet(), &mut buf).unwrap();
+        let mut replayed = buf.clone();
+
+        assert!(remote.decode(&mut buf).unwrap().is_some());
+        assert!(matches!(
+            remote.decode(&mut replayed),
+            Err(LinkCodecError::DecryptionFailure)
+        ));
+    }
+}
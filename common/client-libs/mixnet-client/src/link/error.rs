@@ -0,0 +1,60 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use nymsphinx::framing::codec::SphinxCodecError;
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LinkError {
+    #[error("experienced io error during link establishment - {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("the link handshake has not completed in time")]
+    HandshakeTimeout,
+
+    #[error("the remote used unsupported link version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("received malformed handshake message")]
+    MalformedHandshakeMessage,
+
+    #[error("failed to decrypt the handshake message")]
+    HandshakeDecryptionFailure,
+
+    #[error("the remote does not own the identity key it claims to have")]
+    IdentityMismatch,
+
+    #[error("the remote attempted to establish an unauthenticated connection")]
+    PlaintextRejected,
+
+    #[error("the remote {0} is not a known node of the network")]
+    UnknownPeer(String),
+}
+
+#[derive(Debug, Error)]
+pub enum LinkCodecError {
+    #[error("experienced io error - {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("failed to handle the sphinx frame - {0:?}")]
+    SphinxCodecError(SphinxCodecError),
+
+    #[error("received link frame of {received} bytes, while at most {max} are allowed")]
+    FrameTooLarge { received: usize, max: usize },
+
+    #[error("failed to decrypt received link frame")]
+    DecryptionFailure,
+
+    #[error("received link frame did not contain a complete sphinx packet")]
+    MalformedFrame,
+}
+
+impl From<SphinxCodecError> for LinkCodecError {
+    fn from(err: SphinxCodecError) -> Self {
+        match err {
+            SphinxCodecError::IoError(err) => LinkCodecError::IoError(err),
+            err => LinkCodecError::SphinxCodecError(err),
+        }
+    }
+}
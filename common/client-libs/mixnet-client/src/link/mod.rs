@@ -0,0 +1,345 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Optional authenticated and encrypted link layer between the nodes of the mixnet.
+//!
+//! The initiator of a connection that knows the identity of the remote node (from the network
+//! topology) begins it with a preamble, `LINK_PREAMBLE_MAGIC || LINK_VERSION`, followed by the
+//! Noise IK handshake, where the static keys are the x25519 forms of the nodes' identity keys.
+//! Afterwards all the sphinx frames are encrypted with the established keys.
+//!
+//! The magic byte can never be the first byte of a valid sphinx frame, so that the receiver can
+//! tell the two kinds of connections apart. Whether the remote supports the links at all is
+//! learned from its hello and the initiator only ever falls back to plaintext, if it's allowed
+//! to do so, for the nodes that are known not to support them. A failed handshake is never
+//! a reason for a downgrade.
+
+use crate::link::codec::LinkCodec;
+use crate::link::error::LinkError;
+use crate::link::noise::{LinkHandshake, INIT_MESSAGE_LEN, RESPONSE_MESSAGE_LEN};
+use crate::link::peers::LinkPeers;
+use crypto::asymmetric::identity;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+pub mod codec;
+pub mod error;
+mod noise;
+pub mod peers;
+
+/// First byte sent by the initiator of an encrypted link. It can't be confused with neither
+/// legacy packet size nor any of the packet versions.
+pub const LINK_PREAMBLE_MAGIC: u8 = 0xFF;
+
+/// Current version of the link protocol.
+pub const LINK_VERSION: u8 = 1;
+
+/// Default maximum duration of the link handshake.
+pub const DEFAULT_LINK_HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(1_500);
+
+/// Specifies which inbound connections a node is willing to accept.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InboundLinkPolicy {
+    /// Accept both the encrypted links and the legacy plaintext connections.
+    AllowPlaintext,
+
+    /// Only accept links that completed the handshake, regardless of the identity of the remote.
+    RequireEncrypted,
+
+    /// Only accept links that completed the handshake with nodes present in the network topology.
+    RequireKnownPeer,
+}
+
+impl Default for InboundLinkPolicy {
+    fn default() -> Self {
+        // TODO: change it once majority of the network supports encrypted links
+        InboundLinkPolicy::AllowPlaintext
+    }
+}
+
+/// Configuration of the outbound links established by the `Client`.
+#[derive(Clone)]
+pub struct LinkConfig {
+    /// Identity of this node used for authenticating itself to the remotes.
+    pub(crate) local_identity: Arc<identity::KeyPair>,
+
+    /// Directory of nodes used to determine identities of the remotes.
+    pub(crate) peers: LinkPeers,
+
+    /// Specifies whether plaintext connection should be used if the remote is unknown
+    /// or is known not to support the encrypted links.
+    pub(crate) allow_plaintext_fallback: bool,
+
+    pub(crate) handshake_timeout: Duration,
+}
+
+impl LinkConfig {
+    pub fn new(
+        local_identity: Arc<identity::KeyPair>,
+        peers: LinkPeers,
+        allow_plaintext_fallback: bool,
+    ) -> Self {
+        LinkConfig {
+            local_identity,
+            peers,
+            allow_plaintext_fallback,
+            handshake_timeout: DEFAULT_LINK_HANDSHAKE_TIMEOUT,
+        }
+    }
+
+    pub fn with_handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+        self.handshake_timeout = handshake_timeout;
+        self
+    }
+}
+
+async fn write_handshake_message(conn: &mut TcpStream, message: &[u8]) -> io::Result<()> {
+    // the handshake messages are tiny so the length will always fit in u16
+    conn.write_all(&(message.len() as u16).to_be_bytes())
+        .await?;
+    conn.write_all(message).await
+}
+
+async fn read_handshake_message(
+    conn: &mut TcpStream,
+    expected_len: usize,
+) -> Result<Vec<u8>, LinkError> {
+    let mut len_bytes = [0u8; 2];
+    conn.read_exact(&mut len_bytes).await?;
+    if u16::from_be_bytes(len_bytes) as usize != expected_len {
+        return Err(LinkError::MalformedHandshakeMessage);
+    }
+
+    let mut message = vec![0u8; expected_len];
+    conn.read_exact(&mut message).await?;
+    Ok(message)
+}
+
+async fn initiate_handshake(
+    conn: &mut TcpStream,
+    local_identity: &identity::KeyPair,
+    remote_identity: identity::PublicKey,
+) -> Result<LinkCodec, LinkError> {
+    let mut handshake =
+        LinkHandshake::new_initiator(&mut rand::rngs::OsRng, local_identity, remote_identity);
+
+    conn.write_all(&[LINK_PREAMBLE_MAGIC, LINK_VERSION]).await?;
+
+    // -> e, es, s, ss, ENC(local identity)
+    let init_message = handshake.write_init_message(local_identity.public_key());
+    write_handshake_message(conn, &init_message).await?;
+
+    // <- e, ee, se
+    let response = read_handshake_message(conn, RESPONSE_MESSAGE_LEN).await?;
+    handshake.read_response_message(&response)?;
+
+    Ok(LinkCodec::encrypted(&handshake.finalize()))
+}
+
+/// Attempts to establish an encrypted link with the node owning the provided identity over the
+/// fresh connection.
+pub async fn initiate_link(
+    conn: &mut TcpStream,
+    local_identity: &identity::KeyPair,
+    remote_identity: identity::PublicKey,
+    handshake_timeout: Duration,
+) -> Result<LinkCodec, LinkError> {
+    tokio::time::timeout(
+        handshake_timeout,
+        initiate_handshake(conn, local_identity, remote_identity),
+    )
+    .await
+    .map_err(|_| LinkError::HandshakeTimeout)?
+}
+
+async fn respond_to_handshake(
+    conn: &mut TcpStream,
+    local_identity: &identity::KeyPair,
+    policy: InboundLinkPolicy,
+) -> Result<(LinkCodec, Option<identity::PublicKey>), LinkError> {
+    let mut first_byte = [0u8; 1];
+    if conn.peek(&mut first_byte).await? == 0 {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    if first_byte[0] != LINK_PREAMBLE_MAGIC {
+        return match policy {
+            InboundLinkPolicy::AllowPlaintext => Ok((LinkCodec::plaintext(), None)),
+            InboundLinkPolicy::RequireEncrypted | InboundLinkPolicy::RequireKnownPeer => {
+                Err(LinkError::PlaintextRejected)
+            }
+        };
+    }
+
+    let mut preamble = [0u8; 2];
+    conn.read_exact(&mut preamble).await?;
+    if preamble[1] != LINK_VERSION {
+        return Err(LinkError::UnsupportedVersion(preamble[1]));
+    }
+
+    let mut handshake = LinkHandshake::new_responder(&mut rand::rngs::OsRng, local_identity);
+
+    // <- e, es, s, ss, ENC(remote identity)
+    let init_message = read_handshake_message(conn, INIT_MESSAGE_LEN).await?;
+    handshake.read_init_message(&init_message)?;
+
+    // -> e, ee, se
+    let response = handshake.write_response_message();
+    write_handshake_message(conn, &response).await?;
+
+    let keys = handshake.finalize();
+    Ok((LinkCodec::encrypted(&keys), Some(keys.remote_identity)))
+}
+
+/// Determines, based on the first bytes received, whether the remote wants to establish an
+/// encrypted link or use a legacy plaintext connection and, if permitted by the policy, completes
+/// the link establishment. It returns the codec to be used on the connection alongside
+/// the authenticated identity of the remote, if any.
+pub async fn accept_link(
+    conn: &mut TcpStream,
+    local_identity: &identity::KeyPair,
+    peers: &LinkPeers,
+    policy: InboundLinkPolicy,
+    handshake_timeout: Duration,
+) -> Result<(LinkCodec, Option<identity::PublicKey>), LinkError> {
+    let (codec, remote_identity) = tokio::time::timeout(
+        handshake_timeout,
+        respond_to_handshake(conn, local_identity, policy),
+    )
+    .await
+    .map_err(|_| LinkError::HandshakeTimeout)??;
+
+    if policy == InboundLinkPolicy::RequireKnownPeer {
+        // if we got here, the remote identity must have been established
+        if let Some(remote_identity) = remote_identity {
+            if !peers.is_known(&remote_identity) {
+                return Err(LinkError::UnknownPeer(remote_identity.to_base58_string()));
+            }
+        }
+    }
+
+    Ok((codec, remote_identity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link::peers::LinkPeer;
+    use futures::{SinkExt, StreamExt};
+    use nymsphinx::framing::packet::FramedSphinxPacket;
+    use nymsphinx::params::{PacketMode, PacketSize};
+    use nymsphinx::NymPacket;
+    use tokio::net::TcpListener;
+    use tokio_util::codec::Framed;
+
+    fn dummy_framed_packet() -> FramedSphinxPacket {
+        let packet_bytes = vec![42u8; PacketSize::AckPacket.size()];
+        let packet = NymPacket::sphinx_from_bytes(&packet_bytes).unwrap();
+        FramedSphinxPacket::new(packet, PacketMode::Mix, false)
+    }
+
+    async fn connected_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (initiator, accepted) = tokio::join!(TcpStream::connect(address), listener.accept());
+        (initiator.unwrap(), accepted.unwrap().0)
+    }
+
+    #[tokio::test]
+    async fn encrypted_link_is_established_between_known_peers() {
+        let initiator_identity = identity::KeyPair::new(&mut rand::rngs::OsRng);
+        let responder_identity = identity::KeyPair::new(&mut rand::rngs::OsRng);
+        let (mut initiator_conn, mut responder_conn) = connected_pair().await;
+
+        let peers = LinkPeers::new();
+        peers.update(vec![(
+            initiator_conn.local_addr().unwrap(),
+            LinkPeer {
+                identity: *initiator_identity.public_key(),
+                supports_links: true,
+            },
+        )]);
+
+        let (initiator_codec, accepted) = tokio::join!(
+            initiate_link(
+                &mut initiator_conn,
+                &initiator_identity,
+                *responder_identity.public_key(),
+                DEFAULT_LINK_HANDSHAKE_TIMEOUT
+            ),
+            accept_link(
+                &mut responder_conn,
+                &responder_identity,
+                &peers,
+                InboundLinkPolicy::RequireKnownPeer,
+                DEFAULT_LINK_HANDSHAKE_TIMEOUT
+            )
+        );
+        let (responder_codec, remote_identity) = accepted.unwrap();
+        assert_eq!(
+            remote_identity.as_ref(),
+            Some(initiator_identity.public_key())
+        );
+
+        let mut initiator = Framed::new(initiator_conn, initiator_codec.unwrap());
+        let mut responder = Framed::new(responder_conn, responder_codec);
+        assert!(responder.codec().is_encrypted());
+
+        initiator.send(dummy_framed_packet()).await.unwrap();
+        let received = responder.next().await.unwrap().unwrap();
+        assert_eq!(received.packet_size(), PacketSize::AckPacket);
+    }
+
+    #[tokio::test]
+    async fn plaintext_connections_are_subject_to_policy() {
+        let responder_identity = identity::KeyPair::new(&mut rand::rngs::OsRng);
+
+        for (policy, accepted) in [
+            (InboundLinkPolicy::AllowPlaintext, true),
+            (InboundLinkPolicy::RequireEncrypted, false),
+        ] {
+            let (initiator_conn, mut responder_conn) = connected_pair().await;
+            let mut initiator = Framed::new(initiator_conn, LinkCodec::plaintext());
+            initiator.send(dummy_framed_packet()).await.unwrap();
+
+            let result = accept_link(
+                &mut responder_conn,
+                &responder_identity,
+                &LinkPeers::new(),
+                policy,
+                DEFAULT_LINK_HANDSHAKE_TIMEOUT,
+            )
+            .await;
+            assert_eq!(result.is_ok(), accepted);
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_peers_are_rejected_by_policy() {
+        let initiator_identity = identity::KeyPair::new(&mut rand::rngs::OsRng);
+        let responder_identity = identity::KeyPair::new(&mut rand::rngs::OsRng);
+        let (mut initiator_conn, mut responder_conn) = connected_pair().await;
+
+        let (_, accepted) = tokio::join!(
+            initiate_link(
+                &mut initiator_conn,
+                &initiator_identity,
+                *responder_identity.public_key(),
+                DEFAULT_LINK_HANDSHAKE_TIMEOUT
+            ),
+            accept_link(
+                &mut responder_conn,
+                &responder_identity,
+                &LinkPeers::new(),
+                InboundLinkPolicy::RequireKnownPeer,
+                DEFAULT_LINK_HANDSHAKE_TIMEOUT
+            )
+        );
+        assert!(matches!(accepted, Err(LinkError::UnknownPeer(..))));
+    }
+}
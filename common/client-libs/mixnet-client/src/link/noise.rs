@@ -0,0 +1,220 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::link::error::LinkError;
+use crypto::asymmetric::identity;
+use crypto::noise::{self, IkHandshake, NoiseError};
+use rand::{CryptoRng, RngCore};
+
+pub(crate) use crypto::noise::{counter_nonce, AEAD_TAG_SIZE};
+
+// Note: the construction is identical to the one used by the gateway handshake, however, the links
+// use a different prologue so that the messages of one protocol could never be accepted by the other.
+const PROLOGUE: &[u8] = b"nym-mix-link";
+
+/// Size of the symmetric keys used for encrypting the link traffic.
+pub(crate) const LINK_KEY_SIZE: usize = noise::TRANSPORT_KEY_SIZE;
+
+// e || ENC(s) || ENC(initiator identity)
+pub(crate) const INIT_MESSAGE_LEN: usize = noise::init_message_len(identity::PUBLIC_KEY_LENGTH);
+
+// e || ENC(empty payload)
+pub(crate) const RESPONSE_MESSAGE_LEN: usize = noise::response_message_len(0);
+
+impl From<NoiseError> for LinkError {
+    fn from(err: NoiseError) -> Self {
+        match err {
+            NoiseError::MalformedMessage => LinkError::MalformedHandshakeMessage,
+            NoiseError::DecryptionFailure => LinkError::HandshakeDecryptionFailure,
+        }
+    }
+}
+
+/// Keys established as the result of the link handshake.
+pub(crate) struct LinkKeys {
+    /// Identity of the remote node that has been authenticated during the handshake.
+    pub(crate) remote_identity: identity::PublicKey,
+
+    /// Key used for encrypting all the traffic sent over the link.
+    pub(crate) sending_key: [u8; LINK_KEY_SIZE],
+
+    /// Key used for decrypting all the traffic received over the link.
+    pub(crate) receiving_key: [u8; LINK_KEY_SIZE],
+}
+
+/// State of the Noise IK handshake performed when establishing a link between two nodes.
+/// The initiator is expected to know the identity of the responder in advance (from the topology).
+///
+/// The handshake consists of the following messages:
+///
+/// -> e, es, s, ss     (with initiator's identity key as the payload)
+/// <- e, ee, se
+pub(crate) struct LinkHandshake {
+    handshake: IkHandshake,
+    remote_identity: Option<identity::PublicKey>,
+}
+
+impl LinkHandshake {
+    pub(crate) fn new_initiator(
+        rng: &mut (impl RngCore + CryptoRng),
+        local_identity: &identity::KeyPair,
+        remote_identity: identity::PublicKey,
+    ) -> Self {
+        LinkHandshake {
+            handshake: IkHandshake::new_initiator(rng, PROLOGUE, local_identity, &remote_identity),
+            remote_identity: Some(remote_identity),
+        }
+    }
+
+    pub(crate) fn new_responder(
+        rng: &mut (impl RngCore + CryptoRng),
+        local_identity: &identity::KeyPair,
+    ) -> Self {
+        LinkHandshake {
+            handshake: IkHandshake::new_responder(rng, PROLOGUE, local_identity),
+            remote_identity: None,
+        }
+    }
+
+    // -> e, es, s, ss, ENC(initiator identity)
+    pub(crate) fn write_init_message(&mut self, local_identity: &identity::PublicKey) -> Vec<u8> {
+        self.handshake
+            .write_init_message(&local_identity.to_bytes())
+    }
+
+    pub(crate) fn read_init_message(&mut self, message: &[u8]) -> Result<(), LinkError> {
+        if message.len() != INIT_MESSAGE_LEN {
+            return Err(LinkError::MalformedHandshakeMessage);
+        }
+
+        let payload = self.handshake.read_init_message(message)?;
+        let remote_identity = identity::PublicKey::from_bytes(&payload)
+            .map_err(|_| LinkError::MalformedHandshakeMessage)?;
+
+        // make sure the initiator actually owns the identity it claims to have
+        if Some(&remote_identity.to_x25519()) != self.handshake.remote_static() {
+            return Err(LinkError::IdentityMismatch);
+        }
+
+        self.remote_identity = Some(remote_identity);
+        Ok(())
+    }
+
+    // <- e, ee, se
+    pub(crate) fn write_response_message(&mut self) -> Vec<u8> {
+        self.handshake.write_response_message(&[])
+    }
+
+    pub(crate) fn read_response_message(&mut self, message: &[u8]) -> Result<(), LinkError> {
+        if message.len() != RESPONSE_MESSAGE_LEN {
+            return Err(LinkError::MalformedHandshakeMessage);
+        }
+
+        self.handshake.read_response_message(message)?;
+        Ok(())
+    }
+
+    /// Finish the handshake, yielding the keys for both directions of the link.
+    pub(crate) fn finalize(self) -> LinkKeys {
+        let keys = self.handshake.finalize();
+
+        LinkKeys {
+            remote_identity: self
+                .remote_identity
+                .expect("the handshake has not been completed"),
+            sending_key: keys.sending_key,
+            receiving_key: keys.receiving_key,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete_handshake(
+        initiator_identity: &identity::KeyPair,
+        responder_identity: &identity::KeyPair,
+    ) -> (LinkKeys, LinkKeys) {
+        let mut rng = rand::rngs::OsRng;
+        let mut initiator = LinkHandshake::new_initiator(
+            &mut rng,
+            initiator_identity,
+            *responder_identity.public_key(),
+        );
+        let mut responder = LinkHandshake::new_responder(&mut rng, responder_identity);
+
+        let init_message = initiator.write_init_message(initiator_identity.public_key());
+        responder.read_init_message(&init_message).unwrap();
+        let response = responder.write_response_message();
+        initiator.read_response_message(&response).unwrap();
+
+        (initiator.finalize(), responder.finalize())
+    }
+
+    #[test]
+    fn both_parties_derive_matching_keys() {
+        let mut rng = rand::rngs::OsRng;
+        let initiator_identity = identity::KeyPair::new(&mut rng);
+        let responder_identity = identity::KeyPair::new(&mut rng);
+
+        let (initiator_keys, responder_keys) =
+            complete_handshake(&initiator_identity, &responder_identity);
+
+        assert_eq!(initiator_keys.sending_key, responder_keys.receiving_key);
+        assert_eq!(initiator_keys.receiving_key, responder_keys.sending_key);
+        assert_ne!(initiator_keys.sending_key, initiator_keys.receiving_key);
+
+        assert_eq!(
+            &initiator_keys.remote_identity,
+            responder_identity.public_key()
+        );
+        assert_eq!(
+            &responder_keys.remote_identity,
+            initiator_identity.public_key()
+        );
+
+        // and the next link uses completely fresh keys
+        let (next_initiator_keys, _) = complete_handshake(&initiator_identity, &responder_identity);
+        assert_ne!(initiator_keys.sending_key, next_initiator_keys.sending_key);
+    }
+
+    #[test]
+    fn handshake_fails_for_wrong_responder_key() {
+        let mut rng = rand::rngs::OsRng;
+        let initiator_identity = identity::KeyPair::new(&mut rng);
+        let responder_identity = identity::KeyPair::new(&mut rng);
+        let other_identity = identity::KeyPair::new(&mut rng);
+
+        let mut initiator = LinkHandshake::new_initiator(
+            &mut rng,
+            &initiator_identity,
+            *other_identity.public_key(),
+        );
+        let mut responder = LinkHandshake::new_responder(&mut rng, &responder_identity);
+
+        let init_message = initiator.write_init_message(initiator_identity.public_key());
+        assert!(responder.read_init_message(&init_message).is_err());
+    }
+
+    #[test]
+    fn initiator_cannot_claim_someone_elses_identity() {
+        let mut rng = rand::rngs::OsRng;
+        let initiator_identity = identity::KeyPair::new(&mut rng);
+        let responder_identity = identity::KeyPair::new(&mut rng);
+        let impersonated_identity = identity::KeyPair::new(&mut rng);
+
+        let mut initiator = LinkHandshake::new_initiator(
+            &mut rng,
+            &initiator_identity,
+            *responder_identity.public_key(),
+        );
+        let mut responder = LinkHandshake::new_responder(&mut rng, &responder_identity);
+
+        let init_message = initiator.write_init_message(impersonated_identity.public_key());
+        assert!(matches!(
+            responder.read_init_message(&init_message),
+            Err(LinkError::IdentityMismatch)
+        ));
+    }
+}
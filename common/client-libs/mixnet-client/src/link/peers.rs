@@ -0,0 +1,85 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::asymmetric::identity;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+/// Node of the network, as announced in the topology.
+#[derive(Clone, Copy, Debug)]
+pub struct LinkPeer {
+    pub identity: identity::PublicKey,
+
+    /// Specifies whether, according to the version it has announced, the node supports
+    /// the encrypted links.
+    pub supports_links: bool,
+}
+
+#[derive(Default)]
+struct LinkPeersInner {
+    peers_by_address: HashMap<SocketAddr, LinkPeer>,
+    known_identities: HashSet<[u8; identity::PUBLIC_KEY_LENGTH]>,
+}
+
+/// Directory of nodes of the network, based on the current topology, used for establishing
+/// and authenticating links between them.
+///
+/// Note that cloning the directory produces a handle to the same underlying data.
+#[derive(Clone, Default)]
+pub struct LinkPeers {
+    inner: Arc<RwLock<LinkPeersInner>>,
+}
+
+impl LinkPeers {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Replaces the known nodes with the provided (mix address, node) pairs.
+    pub fn update<I>(&self, peers: I)
+    where
+        I: IntoIterator<Item = (SocketAddr, LinkPeer)>,
+    {
+        let peers_by_address: HashMap<_, _> = peers.into_iter().collect();
+        let known_identities = peers_by_address
+            .values()
+            .map(|peer| peer.identity.to_bytes())
+            .collect();
+
+        let mut guard = self.inner.write().expect("link peers lock got poisoned");
+        guard.peers_by_address = peers_by_address;
+        guard.known_identities = known_identities;
+    }
+
+    /// Identity of the node listening for mix packets on the provided address, if known.
+    pub fn identity_of(&self, address: &SocketAddr) -> Option<identity::PublicKey> {
+        self.inner
+            .read()
+            .expect("link peers lock got poisoned")
+            .peers_by_address
+            .get(address)
+            .map(|peer| peer.identity)
+    }
+
+    /// Checks whether the node listening on the provided address has announced a version
+    /// that supports the encrypted links.
+    pub fn supports_links(&self, address: &SocketAddr) -> bool {
+        self.inner
+            .read()
+            .expect("link peers lock got poisoned")
+            .peers_by_address
+            .get(address)
+            .map(|peer| peer.supports_links)
+            .unwrap_or_default()
+    }
+
+    /// Checks whether the provided identity belongs to any node of the network.
+    pub fn is_known(&self, identity: &identity::PublicKey) -> bool {
+        self.inner
+            .read()
+            .expect("link peers lock got poisoned")
+            .known_identities
+            .contains(&identity.to_bytes())
+    }
+}
@@ -12,7 +12,7 @@ use validator_api_requests::coconut::{
 };
 use validator_api_requests::models::{
     GatewayCoreStatusResponse, MixnodeCoreStatusResponse, MixnodeStatusResponse,
-    RewardEstimationResponse, StakeSaturationResponse,
+    RewardEstimationResponse, SphinxKeyAnnouncements, StakeSaturationResponse,
 };
 
 #[cfg(feature = "nymd-client")]
@@ -566,6 +566,12 @@ impl<C> Client<C> {
         Ok(self.validator_api.get_gateways().await?)
     }
 
+    pub async fn get_cached_sphinx_key_announcements(
+        &self,
+    ) -> Result<SphinxKeyAnnouncements, ValidatorClientError> {
+        Ok(self.validator_api.get_sphinx_key_announcements().await?)
+    }
+
     pub async fn blind_sign(
         &self,
         request_body: &BlindSignRequestBody,
@@ -619,6 +625,12 @@ impl ApiClient {
         Ok(self.validator_api.get_gateways().await?)
     }
 
+    pub async fn get_cached_sphinx_key_announcements(
+        &self,
+    ) -> Result<SphinxKeyAnnouncements, ValidatorClientError> {
+        Ok(self.validator_api.get_sphinx_key_announcements().await?)
+    }
+
     pub async fn get_gateway_core_status_count(
         &self,
         identity: IdentityKeyRef<'_>,
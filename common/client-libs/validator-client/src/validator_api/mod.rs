@@ -16,7 +16,7 @@ use validator_api_requests::models::{
     GatewayCoreStatusResponse, GatewayStatusReportResponse, GatewayUptimeHistoryResponse,
     InclusionProbabilityResponse, MixNodeBondAnnotated, MixnodeCoreStatusResponse,
     MixnodeStatusReportResponse, MixnodeStatusResponse, MixnodeUptimeHistoryResponse, RequestError,
-    RewardEstimationResponse, StakeSaturationResponse, UptimeResponse,
+    RewardEstimationResponse, SphinxKeyAnnouncements, StakeSaturationResponse, UptimeResponse,
 };
 
 pub mod error;
@@ -178,6 +178,13 @@ impl Client {
         .await
     }
 
+    pub async fn get_sphinx_key_announcements(
+        &self,
+    ) -> Result<SphinxKeyAnnouncements, ValidatorAPIError> {
+        self.query_validator_api(&[routes::API_VERSION, routes::SPHINX_KEYS], NO_PARAMS)
+            .await
+    }
+
     pub async fn get_mixnode_report(
         &self,
         mix_id: MixId,
@@ -6,6 +6,7 @@ use network_defaults::VALIDATOR_API_VERSION;
 pub const API_VERSION: &str = VALIDATOR_API_VERSION;
 pub const MIXNODES: &str = "mixnodes";
 pub const GATEWAYS: &str = "gateways";
+pub const SPHINX_KEYS: &str = "sphinx-keys";
 
 pub const DETAILED: &str = "detailed";
 pub const ACTIVE: &str = "active";
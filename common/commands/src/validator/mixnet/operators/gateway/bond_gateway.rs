@@ -22,6 +22,12 @@ pub struct Args {
     #[clap(long)]
     pub clients_port: Option<u16>,
 
+    #[clap(long)]
+    pub clients_wss_port: Option<u16>,
+
+    #[clap(long)]
+    pub http_api_port: Option<u16>,
+
     #[clap(long)]
     pub location: Option<String>,
 
@@ -59,6 +65,8 @@ pub async fn bond_gateway(args: Args, client: SigningClient) {
         host: args.host,
         mix_port: args.mix_port.unwrap_or(DEFAULT_MIX_LISTENING_PORT),
         clients_port: args.clients_port.unwrap_or(DEFAULT_CLIENT_LISTENING_PORT),
+        clients_wss_port: args.clients_wss_port,
+        http_api_port: args.http_api_port,
         location: args
             .location
             .unwrap_or_else(|| "secret gateway location".to_owned()),
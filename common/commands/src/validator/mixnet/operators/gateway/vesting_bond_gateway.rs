@@ -22,6 +22,12 @@ pub struct Args {
     #[clap(long)]
     pub clients_port: Option<u16>,
 
+    #[clap(long)]
+    pub clients_wss_port: Option<u16>,
+
+    #[clap(long)]
+    pub http_api_port: Option<u16>,
+
     #[clap(long)]
     pub location: Option<String>,
 
@@ -57,6 +63,8 @@ pub async fn vesting_bond_gateway(client: SigningClient, args: Args, denom: &str
         host: args.host,
         mix_port: args.mix_port.unwrap_or(DEFAULT_MIX_LISTENING_PORT),
         clients_port: args.clients_port.unwrap_or(DEFAULT_CLIENT_LISTENING_PORT),
+        clients_wss_port: args.clients_wss_port,
+        http_api_port: args.http_api_port,
         location: args
             .location
             .unwrap_or_else(|| "secret gateway location".to_owned()),
@@ -10,6 +10,7 @@ use std::path::PathBuf;
 use std::{fs, io};
 
 pub mod defaults;
+pub mod reload;
 
 pub trait NymConfig: Default + Serialize + DeserializeOwned {
     fn template() -> &'static str;
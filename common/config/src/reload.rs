@@ -0,0 +1,257 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::net::IpAddr;
+use tokio::sync::{mpsc, oneshot};
+
+#[derive(Debug)]
+pub enum ReloadError {
+    /// The configuration file could not be read or parsed.
+    Load(io::Error),
+
+    /// The new configuration is malformed.
+    Invalid(String),
+
+    /// The new configuration changes fields that can't be applied without a restart.
+    RequiresRestart(Vec<String>),
+
+    /// The node is no longer able to process the reload request.
+    Unavailable,
+}
+
+impl Display for ReloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ReloadError::Load(err) => write!(f, "failed to load the configuration file - {}", err),
+            ReloadError::Invalid(reason) => {
+                write!(f, "the new configuration is invalid - {}", reason)
+            }
+            ReloadError::RequiresRestart(fields) => write!(
+                f,
+                "the following fields can only be changed by restarting the node: {}",
+                fields.join(", ")
+            ),
+            ReloadError::Unavailable => write!(f, "the node is not accepting reload requests"),
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {}
+
+/// Outcome of a config reload, i.e. either the fields that got changed or the reason
+/// why the new config got rejected.
+pub type ReloadResult = Result<Vec<String>, ReloadError>;
+
+pub type ReloadRequestReceiver = mpsc::UnboundedReceiver<oneshot::Sender<ReloadResult>>;
+
+/// Handle for requesting the node to re-read and apply its configuration file.
+#[derive(Clone)]
+pub struct ReloadRequester(mpsc::UnboundedSender<oneshot::Sender<ReloadResult>>);
+
+impl ReloadRequester {
+    pub fn new() -> (Self, ReloadRequestReceiver) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (ReloadRequester(sender), receiver)
+    }
+
+    pub async fn request_reload(&self) -> ReloadResult {
+        let (result_sender, result_receiver) = oneshot::channel();
+        self.0
+            .send(result_sender)
+            .map_err(|_| ReloadError::Unavailable)?;
+        result_receiver
+            .await
+            .map_err(|_| ReloadError::Unavailable)?
+    }
+}
+
+/// Requests the reload on behalf of a received signal and returns whether the node
+/// is still able to process any further requests.
+async fn handle_reload_signal(requester: &ReloadRequester) -> bool {
+    // the outcome is logged by the node itself, a rejected config must not stop us
+    // from reloading the fixed one later on
+    !matches!(
+        requester.request_reload().await,
+        Err(ReloadError::Unavailable)
+    )
+}
+
+/// Requests the config to get reloaded upon receiving SIGHUP.
+#[cfg(unix)]
+pub fn start_reload_signal_listener(requester: ReloadRequester) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sighup = signal(SignalKind::hangup()).expect("Failed to setup SIGHUP channel");
+    tokio::spawn(async move {
+        while sighup.recv().await.is_some() {
+            log::info!("Received SIGHUP - reloading the configuration");
+            if !handle_reload_signal(&requester).await {
+                return;
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn start_reload_signal_listener(_requester: ReloadRequester) {}
+
+/// Checks whether the request came from the machine the node is running on.
+pub fn is_local(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => address.is_loopback(),
+        // make sure to also handle ipv4-mapped addresses if we're listening on a dual-stack socket
+        IpAddr::V6(address) => {
+            address.is_loopback() || address.to_ipv4().map_or(false, |v4| v4.is_loopback())
+        }
+    }
+}
+
+fn field_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+fn collect_changed_fields(
+    path: &str,
+    current: &toml::Value,
+    updated: &toml::Value,
+    changed: &mut Vec<String>,
+) {
+    match (current, updated) {
+        (toml::Value::Table(current), toml::Value::Table(updated)) => {
+            for (key, current_value) in current {
+                let field = field_path(path, key);
+                match updated.get(key) {
+                    Some(updated_value) => {
+                        collect_changed_fields(&field, current_value, updated_value, changed)
+                    }
+                    None => changed.push(field),
+                }
+            }
+            for key in updated.keys().filter(|key| !current.contains_key(*key)) {
+                changed.push(field_path(path, key))
+            }
+        }
+        (current, updated) if current != updated => changed.push(path.to_owned()),
+        _ => (),
+    }
+}
+
+/// Returns dot-separated paths of all the fields whose values differ between the two configs,
+/// e.g. `mixnode.announce_address`.
+pub fn changed_fields<T: Serialize>(current: &T, updated: &T) -> Vec<String> {
+    // serializing our own config structs into toml can't fail
+    let current = toml::Value::try_from(current).expect("failed to serialize the config");
+    let updated = toml::Value::try_from(updated).expect("failed to serialize the config");
+
+    let mut changed = Vec::new();
+    collect_changed_fields("", &current, &updated, &mut changed);
+    changed
+}
+
+/// Checks whether all the changes between the two configs can be applied while the node
+/// is running, i.e. whether all of them are within the provided `live_reloadable` fields.
+/// If so, the changed fields are returned.
+pub fn check_live_changes<T: Serialize>(
+    current: &T,
+    updated: &T,
+    live_reloadable: &[&str],
+) -> Result<Vec<String>, ReloadError> {
+    let (live, requiring_restart): (Vec<_>, Vec<_>) = changed_fields(current, updated)
+        .into_iter()
+        .partition(|field| live_reloadable.contains(&field.as_str()));
+
+    if !requiring_restart.is_empty() {
+        return Err(ReloadError::RequiresRestart(requiring_restart));
+    }
+    Ok(live)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Clone)]
+    struct Section {
+        address: String,
+        port: u16,
+        urls: Vec<String>,
+    }
+
+    #[derive(Serialize, Clone)]
+    struct TestConfig {
+        node: Section,
+        level: Option<String>,
+    }
+
+    fn test_config() -> TestConfig {
+        TestConfig {
+            node: Section {
+                address: "1.2.3.4".to_string(),
+                port: 1789,
+                urls: vec!["https://foo.com".to_string()],
+            },
+            level: None,
+        }
+    }
+
+    #[test]
+    fn only_live_reloadable_changes_are_accepted() {
+        let live_reloadable = ["node.address", "node.urls", "level"];
+        let current = test_config();
+
+        let mut updated = current.clone();
+        updated.node.address = "5.6.7.8".to_string();
+        updated.node.urls.push("https://bar.com".to_string());
+        updated.level = Some("debug".to_string());
+        assert_eq!(
+            check_live_changes(&current, &updated, &live_reloadable).unwrap(),
+            vec!["node.address", "node.urls", "level"]
+        );
+
+        updated.node.port = 1790;
+        match check_live_changes(&current, &updated, &live_reloadable) {
+            Err(ReloadError::RequiresRestart(fields)) => assert_eq!(fields, vec!["node.port"]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(check_live_changes(&current, &current, &live_reloadable)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn signal_listener_only_stops_once_the_node_is_unavailable() {
+        let (requester, mut requests) = ReloadRequester::new();
+
+        let node = tokio::spawn(async move {
+            let result_sender = requests.recv().await.unwrap();
+            result_sender
+                .send(Err(ReloadError::Invalid("foomp".to_string())))
+                .unwrap();
+            let result_sender = requests.recv().await.unwrap();
+            result_sender.send(Ok(vec!["level".to_string()])).unwrap();
+            // the node stops processing the requests
+        });
+
+        assert!(handle_reload_signal(&requester).await);
+        assert!(handle_reload_signal(&requester).await);
+        node.await.unwrap();
+        assert!(!handle_reload_signal(&requester).await);
+    }
+
+    #[test]
+    fn only_loopback_addresses_are_local() {
+        assert!(is_local("127.0.0.1".parse().unwrap()));
+        assert!(is_local("::1".parse().unwrap()));
+        assert!(is_local("::ffff:127.0.0.1".parse().unwrap()));
+        assert!(!is_local("1.2.3.4".parse().unwrap()));
+        assert!(!is_local("::ffff:1.2.3.4".parse().unwrap()));
+    }
+}
@@ -16,6 +16,12 @@ pub struct Gateway {
     pub host: String,
     pub mix_port: u16,
     pub clients_port: u16,
+    /// Optional port on which the gateway accepts TLS-terminated (`wss://`) client connections.
+    #[serde(default)]
+    pub clients_wss_port: Option<u16>,
+    /// Optional port of the HTTP API of the gateway, on which it announces its rotated sphinx keys.
+    #[serde(default)]
+    pub http_api_port: Option<u16>,
     pub location: String,
     pub sphinx_key: SphinxKey,
     /// Base58 encoded ed25519 EdDSA public key of the gateway used to derive shared keys with clients
@@ -154,6 +160,8 @@ mod tests {
             host: "1.1.1.1".to_string(),
             mix_port: 123,
             clients_port: 456,
+            clients_wss_port: None,
+            http_api_port: None,
             location: "foomplandia".to_string(),
             sphinx_key: "sphinxkey".to_string(),
             identity_key: "identitykey".to_string(),
@@ -16,6 +16,7 @@ mod msg;
 pub mod pending_events;
 pub mod reward_params;
 pub mod rewarding;
+pub mod sphinx_keys;
 mod types;
 
 pub use contracts_common::types::*;
@@ -41,4 +42,5 @@ pub use pending_events::{
     PendingIntervalEventData, PendingIntervalEventKind,
 };
 pub use reward_params::{IntervalRewardParams, IntervalRewardingParamsUpdate, RewardingParams};
+pub use sphinx_keys::{SphinxKeyAnnouncement, SphinxKeyEpoch};
 pub use types::*;
@@ -0,0 +1,84 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::SphinxKey;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Identifier of the period during which particular rotated sphinx key is in use.
+///
+/// Sphinx key epochs are derived purely from the unix time so that the nodes and the clients
+/// could agree on the current epoch without having to query the chain.
+pub type SphinxKeyEpoch = u64;
+
+/// Duration of a single sphinx key epoch, in seconds. It matches the default length of the
+/// mixnet epoch.
+pub const SPHINX_KEY_EPOCH_LENGTH_SECS: u64 = 60 * 60;
+
+/// Duration, in seconds, at the beginning of each epoch during which the key of the previous
+/// epoch is still accepted, so that the packets created with slightly outdated topology
+/// (or by clients with skewed clocks) would not get dropped.
+pub const SPHINX_KEY_OVERLAP_SECS: u64 = 15 * 60;
+
+/// Returns the sphinx key epoch corresponding to the provided unix timestamp.
+pub fn sphinx_key_epoch(unix_timestamp: u64) -> SphinxKeyEpoch {
+    unix_timestamp / SPHINX_KEY_EPOCH_LENGTH_SECS
+}
+
+/// Returns the unix timestamp at which the provided sphinx key epoch begins.
+pub fn sphinx_key_epoch_start(epoch: SphinxKeyEpoch) -> u64 {
+    epoch.saturating_mul(SPHINX_KEY_EPOCH_LENGTH_SECS)
+}
+
+/// Sphinx key that is going to be used by a node during a particular epoch, signed with
+/// the identity key of that node.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct SphinxKeyAnnouncement {
+    /// The epoch during which the key is going to be used.
+    pub epoch: SphinxKeyEpoch,
+
+    /// Base58-encoded x25519 public key used for sphinx key derivation during the epoch.
+    pub sphinx_key: SphinxKey,
+
+    /// Base58-encoded ed25519 signature on the `signing_plaintext` of this announcement
+    /// created with the identity key of the node.
+    pub signature: String,
+}
+
+impl SphinxKeyAnnouncement {
+    /// Returns the plaintext that has to be signed by the identity key of the node announcing
+    /// the provided sphinx key for the specified epoch.
+    pub fn signing_plaintext(epoch: SphinxKeyEpoch, sphinx_key: &str) -> Vec<u8> {
+        b"nym-sphinx-key-announcement"
+            .iter()
+            .copied()
+            .chain(epoch.to_be_bytes())
+            .chain(sphinx_key.as_bytes().iter().copied())
+            .collect()
+    }
+
+    pub fn plaintext(&self) -> Vec<u8> {
+        Self::signing_plaintext(self.epoch, &self.sphinx_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epochs_are_derived_from_unix_time() {
+        assert_eq!(sphinx_key_epoch(0), 0);
+        assert_eq!(sphinx_key_epoch(SPHINX_KEY_EPOCH_LENGTH_SECS - 1), 0);
+        assert_eq!(sphinx_key_epoch(SPHINX_KEY_EPOCH_LENGTH_SECS), 1);
+        assert_eq!(sphinx_key_epoch(sphinx_key_epoch_start(42)), 42);
+    }
+
+    #[test]
+    fn plaintext_is_bound_to_the_epoch() {
+        assert_ne!(
+            SphinxKeyAnnouncement::signing_plaintext(1, "foomp"),
+            SphinxKeyAnnouncement::signing_plaintext(2, "foomp")
+        );
+    }
+}
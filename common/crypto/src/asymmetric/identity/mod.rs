@@ -4,6 +4,8 @@
 pub use ed25519_dalek::ed25519::signature::Signature as SignatureTrait;
 pub use ed25519_dalek::SignatureError;
 pub use ed25519_dalek::{Verifier, PUBLIC_KEY_LENGTH, SECRET_KEY_LENGTH, SIGNATURE_LENGTH};
+use super::encryption;
+use curve25519_dalek::edwards::CompressedEdwardsY;
 use nymsphinx_types::{DestinationAddressBytes, DESTINATION_ADDRESS_LENGTH};
 use pemstore::traits::{PemStorableKey, PemStorableKeyPair};
 #[cfg(feature = "rand")]
@@ -141,6 +143,19 @@ impl PublicKey {
     pub fn verify(&self, message: &[u8], signature: &Signature) -> Result<(), SignatureError> {
         self.0.verify(message, &signature.0)
     }
+
+    /// Converts this ed25519 public key into its birationally equivalent x25519 public key,
+    /// so that it could also be used for the Diffie-Hellman key exchange.
+    pub fn to_x25519(&self) -> encryption::PublicKey {
+        // the decompression can't fail as the point was already validated
+        // when this public key was constructed
+        let edwards_point = CompressedEdwardsY::from_slice(self.0.as_bytes())
+            .decompress()
+            .expect("failed to decompress an already validated ed25519 public key");
+
+        // the length is always correct here
+        encryption::PublicKey::from_bytes(edwards_point.to_montgomery().as_bytes()).unwrap()
+    }
 }
 
 #[cfg(feature = "serde")]
@@ -228,6 +243,17 @@ impl PrivateKey {
         let signature = bs58::encode(signature_bytes).into_string();
         signature
     }
+
+    /// Converts this ed25519 private key into the x25519 private key corresponding to
+    /// the public key returned by [`PublicKey::to_x25519`].
+    pub fn to_x25519(&self) -> encryption::PrivateKey {
+        // the first half of the expanded key is the already clamped secret scalar
+        let expanded_secret_key = ed25519_dalek::ExpandedSecretKey::from(&self.0);
+        let scalar_bytes = &expanded_secret_key.to_bytes()[..encryption::PRIVATE_KEY_SIZE];
+
+        // the length is always correct here
+        encryption::PrivateKey::from_bytes(scalar_bytes).unwrap()
+    }
 }
 
 #[cfg(feature = "serde")]
@@ -310,3 +336,45 @@ impl<'d> Deserialize<'d> for Signature {
         Signature::from_bytes(bytes.as_ref()).map_err(SerdeError::custom)
     }
 }
+
+#[cfg(test)]
+mod x25519_key_conversion {
+    use super::*;
+
+    const NUM_ITERATIONS: usize = 100;
+
+    #[test]
+    fn converted_keys_form_a_valid_keypair() {
+        let mut rng = rand::rngs::OsRng;
+
+        for _ in 0..NUM_ITERATIONS {
+            let keys = KeyPair::new(&mut rng);
+            let x25519_private = keys.private_key().to_x25519();
+            let x25519_public = keys.public_key().to_x25519();
+
+            assert_eq!(
+                encryption::PublicKey::from(&x25519_private).to_bytes(),
+                x25519_public.to_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn converted_keys_agree_on_shared_secret() {
+        let mut rng = rand::rngs::OsRng;
+
+        let keys1 = KeyPair::new(&mut rng);
+        let keys2 = KeyPair::new(&mut rng);
+
+        let secret1 = keys1
+            .private_key()
+            .to_x25519()
+            .diffie_hellman(&keys2.public_key().to_x25519());
+        let secret2 = keys2
+            .private_key()
+            .to_x25519()
+            .diffie_hellman(&keys1.public_key().to_x25519());
+
+        assert_eq!(secret1, secret2);
+    }
+}
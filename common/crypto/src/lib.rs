@@ -10,6 +10,8 @@ pub mod crypto_hash;
 pub mod hkdf;
 #[cfg(feature = "hashing")]
 pub mod hmac;
+#[cfg(feature = "noise")]
+pub mod noise;
 #[cfg(all(feature = "asymmetric", feature = "hashing", feature = "symmetric"))]
 pub mod shared_key;
 #[cfg(feature = "symmetric")]
@@ -0,0 +1,492 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Noise IK handshake shared by all the protocols of the network that need to establish
+//! fresh session keys between two parties, one of which knows the identity of the other
+//! in advance (i.e. the client registering with a gateway and the links between the nodes).
+//!
+//! The static keys of both parties are the x25519 forms of their ed25519 identity keys.
+//! Each protocol uses a distinct prologue, so that the messages of one of them could never
+//! be accepted by the other.
+
+use crate::asymmetric::{encryption, identity};
+use crate::{blake3, hkdf};
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use rand::{CryptoRng, RngCore};
+use std::fmt::{self, Display, Formatter};
+
+// Note: while BLAKE3 is not one of the hash functions defined by the Noise specification,
+// it is used everywhere else in our protocols and it satisfies all of the requirements
+// put on the hash function.
+pub const PROTOCOL_NAME: &[u8] = b"Noise_IK_25519_AESGCM_BLAKE3";
+
+pub const HASH_LEN: usize = 32;
+pub const AEAD_NONCE_SIZE: usize = 12;
+pub const AEAD_TAG_SIZE: usize = 16;
+
+/// Size of the transport keys produced by the handshake.
+pub const TRANSPORT_KEY_SIZE: usize = HASH_LEN;
+
+type NoiseHkdfAlgorithm = blake3::Hasher;
+
+/// Length of the `-> e, es, s, ss` message carrying payload of the specified length.
+pub const fn init_message_len(payload_len: usize) -> usize {
+    encryption::PUBLIC_KEY_SIZE
+        + encryption::PUBLIC_KEY_SIZE
+        + AEAD_TAG_SIZE
+        + payload_len
+        + AEAD_TAG_SIZE
+}
+
+/// Length of the `<- e, ee, se` message carrying payload of the specified length.
+pub const fn response_message_len(payload_len: usize) -> usize {
+    encryption::PUBLIC_KEY_SIZE + payload_len + AEAD_TAG_SIZE
+}
+
+/// 32 bits of zeroes followed by big-endian encoding of the counter.
+pub fn counter_nonce(counter: u64) -> [u8; AEAD_NONCE_SIZE] {
+    let mut nonce = [0u8; AEAD_NONCE_SIZE];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseError {
+    MalformedMessage,
+    DecryptionFailure,
+}
+
+impl Display for NoiseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NoiseError::MalformedMessage => write!(f, "received malformed handshake message"),
+            NoiseError::DecryptionFailure => {
+                write!(f, "failed to decrypt the received handshake message")
+            }
+        }
+    }
+}
+
+impl std::error::Error for NoiseError {}
+
+/// The `SymmetricState` object as defined by the Noise specification.
+struct SymmetricState {
+    chaining_key: [u8; HASH_LEN],
+    handshake_hash: [u8; HASH_LEN],
+    cipher: Option<Aes256Gcm>,
+    nonce: u64,
+}
+
+impl SymmetricState {
+    fn new(prologue: &[u8]) -> Self {
+        let mut handshake_hash = [0u8; HASH_LEN];
+        handshake_hash[..PROTOCOL_NAME.len()].copy_from_slice(PROTOCOL_NAME);
+
+        let mut state = SymmetricState {
+            chaining_key: handshake_hash,
+            handshake_hash,
+            cipher: None,
+            nonce: 0,
+        };
+        state.mix_hash(prologue);
+        state
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.handshake_hash);
+        hasher.update(data);
+        self.handshake_hash = *hasher.finalize().as_bytes();
+    }
+
+    // HKDF(chaining_key, input_key_material) with two outputs
+    fn hkdf(&self, input_key_material: &[u8]) -> ([u8; HASH_LEN], [u8; HASH_LEN]) {
+        // there is no reason for this to fail as our okm is expected to be only 64 bytes
+        let okm = hkdf::extract_then_expand::<NoiseHkdfAlgorithm>(
+            Some(&self.chaining_key),
+            input_key_material,
+            None,
+            2 * HASH_LEN,
+        )
+        .expect("somehow too long okm was provided");
+
+        let mut first = [0u8; HASH_LEN];
+        let mut second = [0u8; HASH_LEN];
+        first.copy_from_slice(&okm[..HASH_LEN]);
+        second.copy_from_slice(&okm[HASH_LEN..]);
+        (first, second)
+    }
+
+    fn mix_key(&mut self, input_key_material: &[u8]) {
+        let (chaining_key, temp_key) = self.hkdf(input_key_material);
+        self.chaining_key = chaining_key;
+        self.cipher = Some(Aes256Gcm::new(Key::from_slice(&temp_key)));
+        self.nonce = 0;
+    }
+
+    fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let ciphertext = match &self.cipher {
+            Some(cipher) => {
+                let payload = Payload {
+                    msg: plaintext,
+                    aad: &self.handshake_hash,
+                };
+                // the encryption can only fail if the plaintext is unreasonably long
+                let ciphertext = cipher
+                    .encrypt(Nonce::from_slice(&counter_nonce(self.nonce)), payload)
+                    .expect("failed to encrypt the handshake message");
+                self.nonce += 1;
+                ciphertext
+            }
+            None => plaintext.to_vec(),
+        };
+
+        self.mix_hash(&ciphertext);
+        ciphertext
+    }
+
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let plaintext = match &self.cipher {
+            Some(cipher) => {
+                let payload = Payload {
+                    msg: ciphertext,
+                    aad: &self.handshake_hash,
+                };
+                let plaintext = cipher
+                    .decrypt(Nonce::from_slice(&counter_nonce(self.nonce)), payload)
+                    .map_err(|_| NoiseError::DecryptionFailure)?;
+                self.nonce += 1;
+                plaintext
+            }
+            None => ciphertext.to_vec(),
+        };
+
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    // returns the (initiator -> responder, responder -> initiator) keys
+    fn split(&self) -> ([u8; TRANSPORT_KEY_SIZE], [u8; TRANSPORT_KEY_SIZE]) {
+        self.hkdf(&[])
+    }
+}
+
+/// Keys for both directions of the channel established by the handshake.
+pub struct TransportKeys {
+    /// Key used for encrypting all the traffic sent to the remote.
+    pub sending_key: [u8; TRANSPORT_KEY_SIZE],
+
+    /// Key used for decrypting all the traffic received from the remote.
+    pub receiving_key: [u8; TRANSPORT_KEY_SIZE],
+}
+
+/// State of the Noise IK handshake, where the initiator knows the static key of the responder
+/// in advance.
+///
+/// The handshake consists of the following messages:
+///
+/// -> e, es, s, ss
+/// <- e, ee, se
+pub struct IkHandshake {
+    symmetric_state: SymmetricState,
+    is_initiator: bool,
+    local_static: encryption::PrivateKey,
+    local_ephemeral: encryption::KeyPair,
+    remote_static: Option<encryption::PublicKey>,
+    remote_ephemeral: Option<encryption::PublicKey>,
+    static_shared_secret: Option<[u8; encryption::SHARED_SECRET_SIZE]>,
+}
+
+impl IkHandshake {
+    fn new(
+        rng: &mut (impl RngCore + CryptoRng),
+        prologue: &[u8],
+        local_identity: &identity::KeyPair,
+        is_initiator: bool,
+    ) -> Self {
+        IkHandshake {
+            symmetric_state: SymmetricState::new(prologue),
+            is_initiator,
+            local_static: local_identity.private_key().to_x25519(),
+            local_ephemeral: encryption::KeyPair::new(rng),
+            remote_static: None,
+            remote_ephemeral: None,
+            static_shared_secret: None,
+        }
+    }
+
+    pub fn new_initiator(
+        rng: &mut (impl RngCore + CryptoRng),
+        prologue: &[u8],
+        local_identity: &identity::KeyPair,
+        remote_identity: &identity::PublicKey,
+    ) -> Self {
+        let mut handshake = Self::new(rng, prologue, local_identity, true);
+
+        // pre-message: <- s
+        let remote_static = remote_identity.to_x25519();
+        handshake
+            .symmetric_state
+            .mix_hash(&remote_static.to_bytes());
+        handshake.remote_static = Some(remote_static);
+        handshake
+    }
+
+    pub fn new_responder(
+        rng: &mut (impl RngCore + CryptoRng),
+        prologue: &[u8],
+        local_identity: &identity::KeyPair,
+    ) -> Self {
+        let mut handshake = Self::new(rng, prologue, local_identity, false);
+
+        // pre-message: <- s
+        let local_static = local_identity.public_key().to_x25519();
+        handshake.symmetric_state.mix_hash(&local_static.to_bytes());
+        handshake
+    }
+
+    /// Static key of the remote. For the responder it's only known after reading
+    /// the init message.
+    pub fn remote_static(&self) -> Option<&encryption::PublicKey> {
+        self.remote_static.as_ref()
+    }
+
+    /// Result of the Diffie-Hellman between the static keys of both parties. It's the same for
+    /// every handshake between the same two parties.
+    pub fn static_shared_secret(&self) -> Option<&[u8; encryption::SHARED_SECRET_SIZE]> {
+        self.static_shared_secret.as_ref()
+    }
+
+    fn local_ephemeral_public_key(&self) -> [u8; encryption::PUBLIC_KEY_SIZE] {
+        self.local_ephemeral.public_key().to_bytes()
+    }
+
+    // -> e, es, s, ss
+    pub fn write_init_message(&mut self, payload: &[u8]) -> Vec<u8> {
+        let remote_static = self
+            .remote_static
+            .expect("the initiator must know the static key of the responder");
+
+        let ephemeral_key = self.local_ephemeral_public_key();
+        self.symmetric_state.mix_hash(&ephemeral_key);
+
+        let es = self
+            .local_ephemeral
+            .private_key()
+            .diffie_hellman(&remote_static);
+        self.symmetric_state.mix_key(&es);
+
+        let local_static = encryption::PublicKey::from(&self.local_static).to_bytes();
+        let encrypted_static = self.symmetric_state.encrypt_and_hash(&local_static);
+
+        let ss = self.local_static.diffie_hellman(&remote_static);
+        self.symmetric_state.mix_key(&ss);
+        self.static_shared_secret = Some(ss);
+
+        let encrypted_payload = self.symmetric_state.encrypt_and_hash(payload);
+
+        ephemeral_key
+            .iter()
+            .copied()
+            .chain(encrypted_static.into_iter())
+            .chain(encrypted_payload.into_iter())
+            .collect()
+    }
+
+    pub fn read_init_message(&mut self, message: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if message.len() < init_message_len(0) {
+            return Err(NoiseError::MalformedMessage);
+        }
+
+        let (ephemeral_key, rest) = message.split_at(encryption::PUBLIC_KEY_SIZE);
+        let (encrypted_static, encrypted_payload) =
+            rest.split_at(encryption::PUBLIC_KEY_SIZE + AEAD_TAG_SIZE);
+
+        // this can't fail as we have already checked the length
+        let remote_ephemeral = encryption::PublicKey::from_bytes(ephemeral_key).unwrap();
+        self.symmetric_state.mix_hash(ephemeral_key);
+
+        let es = self.local_static.diffie_hellman(&remote_ephemeral);
+        self.symmetric_state.mix_key(&es);
+
+        let remote_static_bytes = self.symmetric_state.decrypt_and_hash(encrypted_static)?;
+        let remote_static = encryption::PublicKey::from_bytes(&remote_static_bytes)
+            .map_err(|_| NoiseError::MalformedMessage)?;
+
+        let ss = self.local_static.diffie_hellman(&remote_static);
+        self.symmetric_state.mix_key(&ss);
+        self.static_shared_secret = Some(ss);
+
+        let payload = self.symmetric_state.decrypt_and_hash(encrypted_payload)?;
+
+        self.remote_ephemeral = Some(remote_ephemeral);
+        self.remote_static = Some(remote_static);
+        Ok(payload)
+    }
+
+    // <- e, ee, se
+    pub fn write_response_message(&mut self, payload: &[u8]) -> Vec<u8> {
+        let remote_ephemeral = self
+            .remote_ephemeral
+            .expect("the init message has not been read");
+        let remote_static = self
+            .remote_static
+            .expect("the init message has not been read");
+
+        let ephemeral_key = self.local_ephemeral_public_key();
+        self.symmetric_state.mix_hash(&ephemeral_key);
+
+        let ee = self
+            .local_ephemeral
+            .private_key()
+            .diffie_hellman(&remote_ephemeral);
+        self.symmetric_state.mix_key(&ee);
+
+        let se = self
+            .local_ephemeral
+            .private_key()
+            .diffie_hellman(&remote_static);
+        self.symmetric_state.mix_key(&se);
+
+        let encrypted_payload = self.symmetric_state.encrypt_and_hash(payload);
+
+        ephemeral_key
+            .iter()
+            .copied()
+            .chain(encrypted_payload.into_iter())
+            .collect()
+    }
+
+    pub fn read_response_message(&mut self, message: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if message.len() < response_message_len(0) {
+            return Err(NoiseError::MalformedMessage);
+        }
+
+        let (ephemeral_key, encrypted_payload) = message.split_at(encryption::PUBLIC_KEY_SIZE);
+
+        // this can't fail as we have already checked the length
+        let remote_ephemeral = encryption::PublicKey::from_bytes(ephemeral_key).unwrap();
+        self.symmetric_state.mix_hash(ephemeral_key);
+
+        let ee = self
+            .local_ephemeral
+            .private_key()
+            .diffie_hellman(&remote_ephemeral);
+        self.symmetric_state.mix_key(&ee);
+
+        let se = self.local_static.diffie_hellman(&remote_ephemeral);
+        self.symmetric_state.mix_key(&se);
+
+        self.remote_ephemeral = Some(remote_ephemeral);
+
+        self.symmetric_state.decrypt_and_hash(encrypted_payload)
+    }
+
+    /// Finish the handshake, yielding the keys for both directions of the channel.
+    pub fn finalize(self) -> TransportKeys {
+        let (initiator_key, responder_key) = self.symmetric_state.split();
+        let (sending_key, receiving_key) = if self.is_initiator {
+            (initiator_key, responder_key)
+        } else {
+            (responder_key, initiator_key)
+        };
+
+        TransportKeys {
+            sending_key,
+            receiving_key,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROLOGUE: &[u8] = b"test-prologue";
+
+    #[test]
+    fn both_parties_derive_matching_keys() {
+        let mut rng = rand::rngs::OsRng;
+        let initiator_identity = identity::KeyPair::new(&mut rng);
+        let responder_identity = identity::KeyPair::new(&mut rng);
+
+        let mut initiator = IkHandshake::new_initiator(
+            &mut rng,
+            PROLOGUE,
+            &initiator_identity,
+            responder_identity.public_key(),
+        );
+        let mut responder = IkHandshake::new_responder(&mut rng, PROLOGUE, &responder_identity);
+
+        let init_message = initiator.write_init_message(b"foomp");
+        assert_eq!(init_message.len(), init_message_len(5));
+        assert_eq!(
+            responder.read_init_message(&init_message).unwrap(),
+            b"foomp"
+        );
+        assert_eq!(
+            responder.remote_static(),
+            Some(&initiator_identity.public_key().to_x25519())
+        );
+
+        let response = responder.write_response_message(&[]);
+        assert_eq!(response.len(), response_message_len(0));
+        assert!(initiator
+            .read_response_message(&response)
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(
+            initiator.static_shared_secret(),
+            responder.static_shared_secret()
+        );
+
+        let initiator_keys = initiator.finalize();
+        let responder_keys = responder.finalize();
+        assert_eq!(initiator_keys.sending_key, responder_keys.receiving_key);
+        assert_eq!(initiator_keys.receiving_key, responder_keys.sending_key);
+        assert_ne!(initiator_keys.sending_key, initiator_keys.receiving_key);
+    }
+
+    #[test]
+    fn handshake_fails_for_wrong_responder_key() {
+        let mut rng = rand::rngs::OsRng;
+        let initiator_identity = identity::KeyPair::new(&mut rng);
+        let responder_identity = identity::KeyPair::new(&mut rng);
+        let other_identity = identity::KeyPair::new(&mut rng);
+
+        let mut initiator = IkHandshake::new_initiator(
+            &mut rng,
+            PROLOGUE,
+            &initiator_identity,
+            other_identity.public_key(),
+        );
+        let mut responder = IkHandshake::new_responder(&mut rng, PROLOGUE, &responder_identity);
+
+        let init_message = initiator.write_init_message(&[]);
+        assert_eq!(
+            responder.read_init_message(&init_message),
+            Err(NoiseError::DecryptionFailure)
+        );
+    }
+
+    #[test]
+    fn handshake_fails_for_different_prologues() {
+        let mut rng = rand::rngs::OsRng;
+        let initiator_identity = identity::KeyPair::new(&mut rng);
+        let responder_identity = identity::KeyPair::new(&mut rng);
+
+        let mut initiator = IkHandshake::new_initiator(
+            &mut rng,
+            PROLOGUE,
+            &initiator_identity,
+            responder_identity.public_key(),
+        );
+        let mut responder =
+            IkHandshake::new_responder(&mut rng, b"other-prologue", &responder_identity);
+
+        let init_message = initiator.write_init_message(&[]);
+        assert!(responder.read_init_message(&init_message).is_err());
+    }
+}
@@ -1,17 +1,47 @@
 // Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-// I'd argue we should start transitioning from `log` to `tracing`
-pub fn setup_logging() {
+use log::{LevelFilter, Log, Metadata, Record};
+use once_cell::sync::OnceCell;
+use std::sync::RwLock;
+
+static LOGGER: OnceCell<ReloadableLogger> = OnceCell::new();
+
+/// Logger whose filters can be replaced after it got installed.
+struct ReloadableLogger {
+    inner: RwLock<Box<dyn Log>>,
+}
+
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.inner
+            .read()
+            .expect("logger lock got poisoned")
+            .enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.inner
+            .read()
+            .expect("logger lock got poisoned")
+            .log(record)
+    }
+
+    fn flush(&self) {
+        self.inner.read().expect("logger lock got poisoned").flush()
+    }
+}
+
+fn build_logger(filters: Option<&str>) -> (Box<dyn Log>, LevelFilter) {
     let mut log_builder = pretty_env_logger::formatted_timed_builder();
-    if let Ok(s) = ::std::env::var("RUST_LOG") {
-        log_builder.parse_filters(&s);
+    if let Some(filters) = filters {
+        log_builder.parse_filters(filters);
     } else {
         // default to 'Info'
         log_builder.filter(None, log::LevelFilter::Info);
     }
 
-    log_builder
+    let logger = log_builder
         .filter_module("hyper", log::LevelFilter::Warn)
         .filter_module("tokio_reactor", log::LevelFilter::Warn)
         .filter_module("reqwest", log::LevelFilter::Warn)
@@ -21,5 +51,35 @@ pub fn setup_logging() {
         .filter_module("tokio_tungstenite", log::LevelFilter::Warn)
         .filter_module("handlebars", log::LevelFilter::Warn)
         .filter_module("sled", log::LevelFilter::Warn)
-        .init();
+        .build();
+
+    let max_level = logger.filter();
+    (Box::new(logger), max_level)
+}
+
+// I'd argue we should start transitioning from `log` to `tracing`
+pub fn setup_logging() {
+    let env_filters = std::env::var("RUST_LOG").ok();
+    let (logger, max_level) = build_logger(env_filters.as_deref());
+
+    let logger = LOGGER.get_or_init(|| ReloadableLogger {
+        inner: RwLock::new(logger),
+    });
+    log::set_logger(logger).expect("the logger has already been set up");
+    log::set_max_level(max_level);
+}
+
+/// Replaces the filters (in the same format as `RUST_LOG`) of the logger installed by `setup_logging`.
+/// If no filters are provided, the ones from `RUST_LOG` (or the defaults) are restored.
+pub fn set_logging_filters(filters: Option<&str>) {
+    let logger = match LOGGER.get() {
+        Some(logger) => logger,
+        None => return,
+    };
+
+    let env_filters = std::env::var("RUST_LOG").ok();
+    let (new_logger, max_level) = build_logger(filters.or(env_filters.as_deref()));
+
+    *logger.inner.write().expect("logger lock got poisoned") = new_logger;
+    log::set_max_level(max_level);
 }
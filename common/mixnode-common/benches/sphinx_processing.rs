@@ -0,0 +1,195 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use mixnode_common::packet_processor::processor::SphinxPacketProcessor;
+use mixnode_common::packet_processor::worker_pool::SphinxProcessingPool;
+use nymsphinx_addressing::nodes::NymNodeRoutingAddress;
+use nymsphinx_framing::packet::FramedSphinxPacket;
+use nymsphinx_params::{PacketMode, PacketSize};
+use nymsphinx_types::builder::SphinxPacketBuilder;
+use nymsphinx_types::outfox::OutfoxPacket;
+use nymsphinx_types::{
+    crypto, Delay as SphinxDelay, Destination, DestinationAddressBytes, Node, NodeAddressBytes,
+    DESTINATION_ADDRESS_LENGTH, IDENTIFIER_LENGTH,
+};
+use std::convert::TryInto;
+use std::net::SocketAddr;
+
+const BATCH_SIZES: [usize; 3] = [1, 16, 64];
+const POOL_THREADS: [usize; 2] = [2, 4];
+const PACKET_FORMATS: [PacketMode; 2] = [PacketMode::Mix, PacketMode::Outfox];
+const COMPARED_PACKET_SIZES: [PacketSize; 2] = [PacketSize::AckPacket, PacketSize::RegularPacket];
+
+fn node_address(port: u16) -> NodeAddressBytes {
+    let address: SocketAddr = format!("1.2.3.4:{}", port).parse().unwrap();
+    NymNodeRoutingAddress::from(address).try_into().unwrap()
+}
+
+fn make_route(first_hop_key: &crypto::PublicKey) -> Vec<Node> {
+    let (_, second_hop_key) = crypto::keygen();
+    let (_, third_hop_key) = crypto::keygen();
+    vec![
+        Node::new(node_address(1789), *first_hop_key),
+        Node::new(node_address(1790), second_hop_key),
+        Node::new(node_address(1791), third_hop_key),
+    ]
+}
+
+fn make_packet(
+    route: &[Node],
+    packet_mode: PacketMode,
+    packet_size: PacketSize,
+) -> FramedSphinxPacket {
+    let destination = Destination::new(
+        DestinationAddressBytes::from_bytes([3u8; DESTINATION_ADDRESS_LENGTH]),
+        [4u8; IDENTIFIER_LENGTH],
+    );
+    let delays = vec![SphinxDelay::new_from_nanos(42); route.len()];
+
+    let packet = if packet_mode.is_outfox() {
+        OutfoxPacket::new(
+            b"foomp",
+            packet_size.payload_size(),
+            route,
+            &destination,
+            &delays,
+        )
+        .unwrap()
+        .into()
+    } else {
+        SphinxPacketBuilder::new()
+            .with_payload_size(packet_size.payload_size())
+            .build_packet(b"foomp".to_vec(), route, &destination, &delays)
+            .unwrap()
+            .into()
+    };
+    FramedSphinxPacket::new(packet, packet_mode, false)
+}
+
+// every packet has to be fresh, otherwise they'd get rejected as replays
+fn make_packets(first_hop_key: &crypto::PublicKey, n: usize) -> Vec<FramedSphinxPacket> {
+    let route = make_route(first_hop_key);
+    (0..n)
+        .map(|_| make_packet(&route, PacketMode::default(), PacketSize::default()))
+        .collect()
+}
+
+// the current design: packets are unwrapped one by one on the connection's task
+fn inline_processing(c: &mut Criterion) {
+    let (private_key, public_key) = crypto::keygen();
+    let processor = SphinxPacketProcessor::new(private_key);
+
+    let mut group = c.benchmark_group("inline sphinx processing");
+    for batch_size in BATCH_SIZES {
+        group.throughput(Throughput::Elements(batch_size as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(batch_size),
+            &batch_size,
+            |b, &batch_size| {
+                b.iter_batched(
+                    || make_packets(&public_key, batch_size),
+                    |packets| {
+                        packets
+                            .into_iter()
+                            .map(|packet| processor.process_received(packet))
+                            .collect::<Vec<_>>()
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+// packets are submitted in batches to the dedicated worker pool
+fn pool_processing(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let (private_key, public_key) = crypto::keygen();
+    let processor = SphinxPacketProcessor::new(private_key);
+
+    for threads in POOL_THREADS {
+        let pool = SphinxProcessingPool::new(processor.clone(), threads).unwrap();
+
+        let mut group =
+            c.benchmark_group(format!("pooled sphinx processing ({} threads)", threads));
+        for batch_size in BATCH_SIZES {
+            group.throughput(Throughput::Elements(batch_size as u64));
+            group.bench_with_input(
+                BenchmarkId::from_parameter(batch_size),
+                &batch_size,
+                |b, &batch_size| {
+                    b.iter_batched(
+                        || make_packets(&public_key, batch_size),
+                        |packets| runtime.block_on(pool.process_batch(packets)),
+                        BatchSize::SmallInput,
+                    )
+                },
+            );
+        }
+        group.finish();
+    }
+}
+
+fn format_name(packet_mode: PacketMode) -> &'static str {
+    if packet_mode.is_outfox() {
+        "outfox"
+    } else {
+        "sphinx"
+    }
+}
+
+// cost of creating a single packet by the clients in either of the formats
+fn packet_format_creation(c: &mut Criterion) {
+    let (_, public_key) = crypto::keygen();
+    let route = make_route(&public_key);
+
+    let mut group = c.benchmark_group("packet creation");
+    for packet_size in COMPARED_PACKET_SIZES {
+        for packet_mode in PACKET_FORMATS {
+            group.bench_with_input(
+                BenchmarkId::new(format_name(packet_mode), format!("{:?}", packet_size)),
+                &(packet_mode, packet_size),
+                |b, &(packet_mode, packet_size)| {
+                    b.iter(|| make_packet(&route, packet_mode, packet_size))
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+// cost of unwrapping a single layer of a packet by the mixnodes in either of the formats
+fn packet_format_processing(c: &mut Criterion) {
+    let (private_key, public_key) = crypto::keygen();
+    let processor = SphinxPacketProcessor::new(private_key);
+    let route = make_route(&public_key);
+
+    let mut group = c.benchmark_group("packet processing");
+    for packet_size in COMPARED_PACKET_SIZES {
+        for packet_mode in PACKET_FORMATS {
+            group.bench_with_input(
+                BenchmarkId::new(format_name(packet_mode), format!("{:?}", packet_size)),
+                &(packet_mode, packet_size),
+                |b, &(packet_mode, packet_size)| {
+                    b.iter_batched(
+                        || make_packet(&route, packet_mode, packet_size),
+                        |packet| processor.process_received(packet),
+                        BatchSize::SmallInput,
+                    )
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    inline_processing,
+    pool_processing,
+    packet_format_creation,
+    packet_format_processing
+);
+criterion_main!(benches);
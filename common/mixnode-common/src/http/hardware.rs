@@ -1,10 +1,13 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
 use cupid::TopologyType;
 use rocket::serde::{json::Json, Serialize};
 use sysinfo::{System, SystemExt};
 
 #[derive(Serialize, Debug)]
 #[serde(crate = "rocket::serde")]
-pub(crate) struct Hardware {
+pub struct Hardware {
     ram: String,
     num_cores: usize,
     crypto_hardware: Option<CryptoHardware>,
@@ -13,7 +16,7 @@ pub(crate) struct Hardware {
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Serialize, Debug)]
 #[serde(crate = "rocket::serde")]
-pub(crate) struct CryptoHardware {
+pub struct CryptoHardware {
     aesni: bool,
     avx2: bool,
     brand_string: String,
@@ -24,8 +27,8 @@ pub(crate) struct CryptoHardware {
 }
 
 /// Provides hardware information which Nym can use to optimize mixnet speed over time (memory, crypto hardware, CPU, cores, etc).
-#[get("/hardware")]
-pub(crate) fn hardware() -> Json<Option<Hardware>> {
+#[rocket::get("/hardware")]
+pub fn hardware() -> Json<Option<Hardware>> {
     Json(hardware_info())
 }
 
@@ -0,0 +1,38 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use config::reload::{is_local, ReloadError, ReloadRequester};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use rocket::State;
+use std::net::SocketAddr;
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ConfigReloadResponse {
+    /// Fields of the config that got changed.
+    applied: Vec<String>,
+}
+
+/// Re-reads the configuration file and applies the changes that do not require a restart.
+/// If any of the changed fields can't be applied while the node is running, nothing is applied.
+/// Only requests made from the machine the node is running on are accepted.
+#[rocket::post("/config/reload")]
+pub async fn reload_config(
+    remote: SocketAddr,
+    requester: &State<ReloadRequester>,
+) -> Result<Json<ConfigReloadResponse>, status::Custom<String>> {
+    if !is_local(remote.ip()) {
+        return Err(status::Custom(Status::Forbidden, String::new()));
+    }
+
+    match requester.request_reload().await {
+        Ok(applied) => Ok(Json(ConfigReloadResponse { applied })),
+        Err(err @ ReloadError::Unavailable) => {
+            Err(status::Custom(Status::ServiceUnavailable, err.to_string()))
+        }
+        Err(err) => Err(status::Custom(Status::UnprocessableEntity, err.to_string())),
+    }
+}
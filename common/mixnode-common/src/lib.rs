@@ -1,5 +1,10 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod http;
+pub mod link_peers;
+pub mod node_description;
 pub mod packet_processor;
+pub mod sphinx_key_rotation;
+pub mod validator_api;
 pub mod verloc;
@@ -0,0 +1,139 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::validator_api::ValidatorApiEndpoints;
+use crypto::asymmetric::identity;
+use futures::{stream, StreamExt};
+use log::*;
+use mixnet_client::link::peers::{LinkPeer, LinkPeers};
+use std::net::SocketAddr;
+use std::time::Duration;
+use task::ShutdownListener;
+use tokio::time::sleep;
+use version_checker::parse_version;
+
+/// Default delay between subsequent refreshes of the known link peers.
+pub const DEFAULT_LINK_PEERS_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Minimum version of a node (gateway or mixnode) that is capable of establishing encrypted links,
+/// i.e. the first release of the `nym-mixnode` and `nym-gateway` crates shipping them.
+pub const MINIMUM_LINK_NODE_VERSION: &str = "1.1.1";
+
+const MAX_CONCURRENT_HOST_RESOLUTIONS: usize = 32;
+
+fn supports_links(version: &str) -> bool {
+    // the unwrap is fine as the minimum version is a valid semver
+    let minimum_version = parse_version(MINIMUM_LINK_NODE_VERSION).unwrap();
+    parse_version(version)
+        .map(|version| version >= minimum_version)
+        .unwrap_or_default()
+}
+
+async fn resolve_peer(
+    host: String,
+    port: u16,
+    identity_key: String,
+    version: String,
+) -> Option<(SocketAddr, LinkPeer)> {
+    let identity = identity::PublicKey::from_base58_string(identity_key).ok()?;
+    let address = tokio::net::lookup_host((&*host, port)).await.ok()?.next()?;
+    Some((
+        address,
+        LinkPeer {
+            identity,
+            supports_links: supports_links(&version),
+        },
+    ))
+}
+
+/// Periodically updates the directory of link peers with all the mixnodes and gateways
+/// that are present in the network.
+pub struct LinkPeersRefresher {
+    validator_api: ValidatorApiEndpoints,
+    peers: LinkPeers,
+    refresh_interval: Duration,
+}
+
+impl LinkPeersRefresher {
+    pub fn new(
+        validator_api: ValidatorApiEndpoints,
+        peers: LinkPeers,
+        refresh_interval: Duration,
+    ) -> Self {
+        LinkPeersRefresher {
+            validator_api,
+            peers,
+            refresh_interval,
+        }
+    }
+
+    pub async fn refresh(&self) {
+        let validator_client = self.validator_api.random_client();
+        let mixnodes = match validator_client.get_cached_mixnodes().await {
+            Ok(mixnodes) => mixnodes,
+            Err(err) => {
+                warn!(
+                    "failed to obtain list of mixnodes for the link peers - {}",
+                    err
+                );
+                return;
+            }
+        };
+        let gateways = match validator_client.get_cached_gateways().await {
+            Ok(gateways) => gateways,
+            Err(err) => {
+                warn!(
+                    "failed to obtain list of gateways for the link peers - {}",
+                    err
+                );
+                return;
+            }
+        };
+
+        let mix_hosts = mixnodes.into_iter().map(|mixnode| {
+            let mix_node = mixnode.bond_information.mix_node;
+            (
+                mix_node.host,
+                mix_node.mix_port,
+                mix_node.identity_key,
+                mix_node.version,
+            )
+        });
+        let gateway_hosts = gateways.into_iter().map(|bond| {
+            let gateway = bond.gateway;
+            (
+                gateway.host,
+                gateway.mix_port,
+                gateway.identity_key,
+                gateway.version,
+            )
+        });
+
+        let peers: Vec<_> = stream::iter(mix_hosts.chain(gateway_hosts))
+            .map(|(host, port, identity_key, version)| {
+                resolve_peer(host, port, identity_key, version)
+            })
+            .buffer_unordered(MAX_CONCURRENT_HOST_RESOLUTIONS)
+            .filter_map(|peer| async move { peer })
+            .collect()
+            .await;
+
+        debug!("updating the link peers with {} nodes", peers.len());
+        self.peers.update(peers);
+    }
+
+    pub async fn run(&mut self, mut shutdown: ShutdownListener) {
+        debug!("Started LinkPeersRefresher with graceful shutdown support");
+        while !shutdown.is_shutdown() {
+            self.refresh().await;
+
+            tokio::select! {
+                _ = sleep(self.refresh_interval) => {},
+                _ = shutdown.recv() => {
+                    trace!("LinkPeersRefresher: Received shutdown");
+                }
+            }
+        }
+        trace!("LinkPeersRefresher: Exiting");
+    }
+}
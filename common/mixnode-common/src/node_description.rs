@@ -0,0 +1,77 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use colored::Colorize;
+use serde::Deserialize;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::{fs, io};
+
+pub const DESCRIPTION_FILE: &str = "description.toml";
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct NodeDescription {
+    pub name: String,
+    pub description: String,
+    pub link: String,
+    pub location: String,
+}
+
+impl Default for NodeDescription {
+    fn default() -> Self {
+        NodeDescription {
+            name: "This node has not yet set a name".to_string(),
+            description: "This node has not yet set a description".to_string(),
+            link: "https://nymtech.net".to_string(),
+            location: "This node has not yet set a location".to_string(),
+        }
+    }
+}
+
+fn prompt(message: &str) -> String {
+    print!("{}: ", message);
+    io::stdout().flush().unwrap();
+    let mut buf = String::new();
+    io::stdin().read_line(&mut buf).unwrap();
+    buf.trim().to_string()
+}
+
+impl NodeDescription {
+    /// Interactively asks the operator for the description of the node.
+    ///
+    /// # Arguments
+    ///
+    /// * `example_url`: example link shown to the operator, e.g. `https://mixnode.yourdomain.com`.
+    pub fn from_user_input(example_url: &str) -> NodeDescription {
+        let name = prompt("name");
+        let description = prompt("description");
+        let link = prompt(&format!("link, e.g. {}", example_url.bright_cyan()));
+        let location = prompt("location, e.g. City: London, Country: UK");
+
+        NodeDescription {
+            name,
+            description,
+            link,
+            location,
+        }
+    }
+
+    pub fn load_from_file(config_path: PathBuf) -> io::Result<NodeDescription> {
+        let description_file_path: PathBuf = [config_path.to_str().unwrap(), DESCRIPTION_FILE]
+            .iter()
+            .collect();
+        let toml = fs::read_to_string(description_file_path)?;
+        toml::from_str(&toml).map_err(|toml_err| io::Error::new(io::ErrorKind::Other, toml_err))
+    }
+
+    pub fn save_to_file(description: &NodeDescription, config_path: PathBuf) -> io::Result<()> {
+        let description_file_path: PathBuf = [config_path.to_str().unwrap(), DESCRIPTION_FILE]
+            .iter()
+            .collect();
+        let description_toml =
+            toml::to_string(description).expect("could not encode description to toml");
+        fs::write(description_file_path, description_toml)?;
+        Ok(())
+    }
+}
@@ -3,17 +3,21 @@
 
 use nymsphinx_acknowledgements::surb_ack::SurbAckRecoveryError;
 use nymsphinx_addressing::nodes::NymNodeRoutingAddressError;
-use nymsphinx_types::Error as SphinxError;
+use nymsphinx_types::outfox::OutfoxError;
+use nymsphinx_types::{Error as SphinxError, NymPacketError};
 use std::fmt::{self, Display, Formatter};
 
 #[derive(Debug)]
 pub enum MixProcessingError {
     SphinxProcessingError(SphinxError),
+    OutfoxProcessingError(OutfoxError),
     InvalidHopAddress(NymNodeRoutingAddressError),
     NoSurbAckInFinalHop,
     MalformedSurbAck(SurbAckRecoveryError),
 
     ReceivedOldTypeVpnPacket,
+    ReplayedPacket,
+    ReplayCacheFull,
 }
 
 impl From<SphinxError> for MixProcessingError {
@@ -25,6 +29,23 @@ impl From<SphinxError> for MixProcessingError {
     }
 }
 
+impl From<OutfoxError> for MixProcessingError {
+    fn from(err: OutfoxError) -> Self {
+        use MixProcessingError::*;
+
+        OutfoxProcessingError(err)
+    }
+}
+
+impl From<NymPacketError> for MixProcessingError {
+    fn from(err: NymPacketError) -> Self {
+        match err {
+            NymPacketError::Sphinx(err) => err.into(),
+            NymPacketError::Outfox(err) => err.into(),
+        }
+    }
+}
+
 impl From<NymNodeRoutingAddressError> for MixProcessingError {
     fn from(err: NymNodeRoutingAddressError) -> Self {
         use MixProcessingError::*;
@@ -47,6 +68,9 @@ impl Display for MixProcessingError {
             MixProcessingError::SphinxProcessingError(sphinx_err) => {
                 write!(f, "Sphinx Processing Error - {}", sphinx_err)
             }
+            MixProcessingError::OutfoxProcessingError(outfox_err) => {
+                write!(f, "Outfox Processing Error - {}", outfox_err)
+            }
             MixProcessingError::InvalidHopAddress(address_err) => {
                 write!(f, "Invalid Hop Address - {:?}", address_err)
             }
@@ -59,6 +83,15 @@ impl Display for MixProcessingError {
             MixProcessingError::ReceivedOldTypeVpnPacket => {
                 write!(f, "Received an old-type unsafe 'VPN' mode packet")
             }
+            MixProcessingError::ReplayedPacket => {
+                write!(f, "Received a packet that has already been processed")
+            }
+            MixProcessingError::ReplayCacheFull => {
+                write!(
+                    f,
+                    "The replay cache of the packet key is full - can't tell whether it's a replay"
+                )
+            }
         }
     }
 }
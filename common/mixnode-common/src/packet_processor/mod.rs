@@ -3,3 +3,5 @@
 
 pub mod error;
 pub mod processor;
+pub mod replay_cache;
+pub mod worker_pool;
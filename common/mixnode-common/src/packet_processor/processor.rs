@@ -2,18 +2,20 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::packet_processor::error::MixProcessingError;
+use crate::packet_processor::replay_cache::{ReplayCache, ReplayCheck};
+use crate::sphinx_key_rotation::{ActiveKey, SphinxKeyRing};
 use log::*;
+use mixnet_contract_common::sphinx_keys::SphinxKeyEpoch;
 use nymsphinx_acknowledgements::surb_ack::SurbAck;
 use nymsphinx_addressing::nodes::NymNodeRoutingAddress;
 use nymsphinx_forwarding::packet::MixPacket;
 use nymsphinx_framing::packet::FramedSphinxPacket;
 use nymsphinx_params::{PacketMode, PacketSize};
 use nymsphinx_types::{
-    Delay as SphinxDelay, DestinationAddressBytes, NodeAddressBytes, Payload, PrivateKey,
-    ProcessedPacket, SphinxPacket,
+    Delay as SphinxDelay, DestinationAddressBytes, NodeAddressBytes, NymPacket, NymPacketError,
+    NymProcessedPacket, PrivateKey,
 };
 use std::convert::TryFrom;
-use std::sync::Arc;
 
 type ForwardAck = MixPacket;
 
@@ -33,49 +35,126 @@ pub enum MixProcessingResult {
 
 #[derive(Clone)]
 pub struct SphinxPacketProcessor {
-    /// Private sphinx key of this node required to unwrap received sphinx packet.
-    sphinx_key: Arc<PrivateKey>,
+    /// Private sphinx keys of this node required to unwrap received sphinx packet.
+    sphinx_keys: SphinxKeyRing,
+
+    /// Tags of recently processed packets used for detecting replays.
+    replay_cache: ReplayCache,
 }
 
 impl SphinxPacketProcessor {
     /// Creates new instance of `CachedPacketProcessor`
     pub fn new(sphinx_key: PrivateKey) -> Self {
+        Self::new_with_key_ring(SphinxKeyRing::new_static(sphinx_key))
+    }
+
+    /// Creates new instance of `CachedPacketProcessor` using keys from the provided key ring.
+    pub fn new_with_key_ring(sphinx_keys: SphinxKeyRing) -> Self {
         SphinxPacketProcessor {
-            sphinx_key: Arc::new(sphinx_key),
+            sphinx_keys,
+            replay_cache: ReplayCache::default(),
         }
     }
 
-    /// Performs a fresh sphinx unwrapping using no cache.
+    /// Replaces the replay cache used by this processor.
+    #[must_use]
+    pub fn with_replay_cache(mut self, replay_cache: ReplayCache) -> Self {
+        self.replay_cache = replay_cache;
+        self
+    }
+
+    /// Total number of replayed packets rejected by this processor (and all of its clones).
+    pub fn replayed_packets(&self) -> u64 {
+        self.replay_cache.replayed_packets()
+    }
+
+    /// Attempts to unwrap the packet with each of the currently active keys. Wrong keys
+    /// are rejected by the header integrity check, so at most one of them could ever succeed.
+    /// The static key is always attempted first, so the packets of clients unaware of
+    /// the rotation never pay for any failed attempts.
+    /// On success, it also returns the replay tag of the packet and the epoch of the key
+    /// that has been used (or `None` for the static key).
+    fn unwrap_with_active_keys(
+        &self,
+        packet: NymPacket,
+        keys: &[ActiveKey],
+    ) -> Result<(NymProcessedPacket, [u8; 32], Option<SphinxKeyEpoch>), NymPacketError> {
+        if keys.len() == 1 {
+            let (processed, replay_tag) = packet.process_with_replay_tag(&keys[0].private_key)?;
+            return Ok((processed, replay_tag, keys[0].epoch));
+        }
+
+        // processing consumes the packet, so we need to keep its bytes around for other attempts
+        let packet_bytes = packet.to_bytes();
+        let is_outfox = packet.is_outfox();
+        let mut packet = Some(packet);
+        let mut last_err = None;
+        for key in keys.iter() {
+            let attempt = match packet.take() {
+                Some(packet) => packet,
+                None if is_outfox => NymPacket::outfox_from_bytes(&packet_bytes)?,
+                None => NymPacket::sphinx_from_bytes(&packet_bytes)?,
+            };
+            match attempt.process_with_replay_tag(&key.private_key) {
+                Ok((processed, replay_tag)) => return Ok((processed, replay_tag, key.epoch)),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        // the key ring always contains at least the static key
+        Err(last_err.expect("the sphinx key ring was empty"))
+    }
+
+    /// Performs a fresh sphinx (or outfox) unwrapping using no cache.
     fn perform_initial_sphinx_packet_processing(
         &self,
-        packet: SphinxPacket,
-    ) -> Result<ProcessedPacket, MixProcessingError> {
-        packet.process(&self.sphinx_key).map_err(|err| {
-            debug!("Failed to unwrap Sphinx packet: {:?}", err);
-            MixProcessingError::SphinxProcessingError(err)
-        })
+        packet: NymPacket,
+    ) -> Result<NymProcessedPacket, MixProcessingError> {
+        let keys = self.sphinx_keys.active_keys();
+        // packets encrypted for the keys that are no longer active can't be unwrapped anymore,
+        // so there's no point in remembering their tags
+        if let Some(oldest_epoch) = keys.iter().filter_map(|key| key.epoch).min() {
+            self.replay_cache.forget_epochs_before(oldest_epoch);
+        }
+
+        let (processed, replay_tag, key_epoch) =
+            self.unwrap_with_active_keys(packet, &keys).map_err(|err| {
+                debug!("Failed to unwrap the packet: {:?}", err);
+                MixProcessingError::from(err)
+            })?;
+
+        // only check the tag after the header got successfully authenticated, otherwise anyone
+        // could poison the cache with a copied tag and garbage routing information
+        // so that the genuine packet would later get rejected
+        match self.replay_cache.check_and_insert(replay_tag, key_epoch) {
+            ReplayCheck::Fresh => (),
+            ReplayCheck::Replayed => return Err(MixProcessingError::ReplayedPacket),
+            ReplayCheck::CapacityExceeded => return Err(MixProcessingError::ReplayCacheFull),
+        }
+
+        Ok(processed)
     }
 
     /// Takes the received framed packet and tries to unwrap it from the sphinx encryption.
     fn perform_initial_unwrapping(
         &self,
         received: FramedSphinxPacket,
-    ) -> Result<ProcessedPacket, MixProcessingError> {
+    ) -> Result<NymProcessedPacket, MixProcessingError> {
         let packet_mode = received.packet_mode();
-        let sphinx_packet = received.into_inner();
+        let packet = received.into_inner();
 
         if packet_mode.is_old_vpn() {
             return Err(MixProcessingError::ReceivedOldTypeVpnPacket);
         }
 
-        self.perform_initial_sphinx_packet_processing(sphinx_packet)
+        self.perform_initial_sphinx_packet_processing(packet)
     }
 
     /// Processed received forward hop packet - tries to extract next hop address, sets delay
     /// and packs all the data in a way that can be easily sent to the next hop.
     fn process_forward_hop(
         &self,
-        packet: SphinxPacket,
+        packet: NymPacket,
         forward_address: NodeAddressBytes,
         delay: SphinxDelay,
         packet_mode: PacketMode,
@@ -122,7 +201,8 @@ impl SphinxPacketProcessor {
             | PacketSize::ExtendedPacket32 => {
                 trace!("received a normal packet!");
                 let (ack_data, message) = self.split_hop_data_into_ack_and_message(data)?;
-                let (ack_first_hop, ack_packet) = SurbAck::try_recover_first_hop_packet(&ack_data)?;
+                let (ack_first_hop, ack_packet) =
+                    SurbAck::try_recover_first_hop_packet(&ack_data, packet_mode)?;
                 let forward_ack = MixPacket::new(ack_first_hop, ack_packet, packet_mode);
                 Ok((Some(forward_ack), message))
             }
@@ -135,12 +215,10 @@ impl SphinxPacketProcessor {
     fn process_final_hop(
         &self,
         destination: DestinationAddressBytes,
-        payload: Payload,
+        packet_message: Vec<u8>,
         packet_size: PacketSize,
         packet_mode: PacketMode,
     ) -> Result<MixProcessingResult, MixProcessingError> {
-        let packet_message = payload.recover_plaintext()?;
-
         let (forward_ack, message) =
             self.split_into_ack_and_message(packet_message, packet_size, packet_mode)?;
 
@@ -155,18 +233,18 @@ impl SphinxPacketProcessor {
     /// or a final hop.
     fn perform_final_processing(
         &self,
-        packet: ProcessedPacket,
+        packet: NymProcessedPacket,
         packet_size: PacketSize,
         packet_mode: PacketMode,
     ) -> Result<MixProcessingResult, MixProcessingError> {
         match packet {
-            ProcessedPacket::ForwardHop(packet, address, delay) => {
-                self.process_forward_hop(packet, address, delay, packet_mode)
+            NymProcessedPacket::ForwardHop(packet, address, delay) => {
+                self.process_forward_hop(*packet, address, delay, packet_mode)
             }
-            // right now there's no use for the surb_id included in the header - probably it should get removed from the
-            // sphinx all together?
-            ProcessedPacket::FinalHop(destination, _, payload) => {
-                self.process_final_hop(destination, payload, packet_size, packet_mode)
+            // note: the surb_id included in the sphinx header has no use for us, so it's not even
+            // present in the processed packet (nor in the outfox header)
+            NymProcessedPacket::FinalHop(destination, message) => {
+                self.process_final_hop(destination, message, packet_size, packet_mode)
             }
         }
     }
@@ -192,7 +270,13 @@ impl SphinxPacketProcessor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use nymsphinx_types::builder::SphinxPacketBuilder;
     use nymsphinx_types::crypto::keygen;
+    use nymsphinx_types::outfox::OutfoxPacket;
+    use nymsphinx_types::{Destination, Node, DESTINATION_ADDRESS_LENGTH, IDENTIFIER_LENGTH};
+    use std::convert::TryInto;
+    use std::net::SocketAddr;
+    use std::time::Duration;
 
     fn fixture() -> SphinxPacketProcessor {
         let local_keys = keygen();
@@ -234,4 +318,109 @@ mod tests {
         assert!(ack.is_none());
         assert_eq!(data, message)
     }
+
+    #[tokio::test]
+    async fn outfox_packets_are_processed_and_replays_rejected() {
+        let (private_key, public_key) = keygen();
+        let processor = SphinxPacketProcessor::new(private_key);
+
+        let next_hop: SocketAddr = "1.2.3.4:1789".parse().unwrap();
+        let (_, next_hop_key) = keygen();
+        let route = [
+            Node::new(
+                NymNodeRoutingAddress::from(next_hop).try_into().unwrap(),
+                public_key,
+            ),
+            Node::new(
+                NymNodeRoutingAddress::from(next_hop).try_into().unwrap(),
+                next_hop_key,
+            ),
+        ];
+        let destination = Destination::new(
+            DestinationAddressBytes::from_bytes([3u8; DESTINATION_ADDRESS_LENGTH]),
+            [4u8; IDENTIFIER_LENGTH],
+        );
+        let delays = vec![SphinxDelay::new_from_nanos(42); 2];
+        let packet = OutfoxPacket::new(
+            b"foomp",
+            PacketSize::default().payload_size(),
+            &route,
+            &destination,
+            &delays,
+        )
+        .unwrap();
+        let packet_bytes = packet.to_bytes();
+
+        let framed = FramedSphinxPacket::new(packet.into(), PacketMode::Outfox, false);
+        match processor.process_received(framed).unwrap() {
+            MixProcessingResult::ForwardHop(mix_packet, delay) => {
+                assert_eq!(mix_packet.next_hop(), NymNodeRoutingAddress::from(next_hop));
+                assert_eq!(mix_packet.packet_mode(), PacketMode::Outfox);
+                assert!(mix_packet.packet().is_outfox());
+                assert_eq!(delay.unwrap().to_duration(), Duration::from_nanos(42));
+            }
+            _ => panic!("expected forward hop"),
+        }
+
+        let replayed = NymPacket::outfox_from_bytes(&packet_bytes).unwrap();
+        let framed = FramedSphinxPacket::new(replayed, PacketMode::Outfox, false);
+        assert!(matches!(
+            processor.process_received(framed),
+            Err(MixProcessingError::ReplayedPacket)
+        ));
+
+        // the top bit of the ephemeral key is ignored by the key exchange
+        let mut modified_bytes = packet_bytes;
+        modified_bytes[31] ^= 0x80;
+        let replayed = NymPacket::outfox_from_bytes(&modified_bytes).unwrap();
+        let framed = FramedSphinxPacket::new(replayed, PacketMode::Outfox, false);
+        assert!(matches!(
+            processor.process_received(framed),
+            Err(MixProcessingError::ReplayedPacket)
+        ));
+    }
+
+    #[tokio::test]
+    async fn sphinx_replays_with_modified_alpha_encoding_are_rejected() {
+        let (private_key, public_key) = keygen();
+        let processor = SphinxPacketProcessor::new(private_key);
+
+        let next_hop: SocketAddr = "1.2.3.4:1789".parse().unwrap();
+        let (_, next_hop_key) = keygen();
+        let route = [
+            Node::new(
+                NymNodeRoutingAddress::from(next_hop).try_into().unwrap(),
+                public_key,
+            ),
+            Node::new(
+                NymNodeRoutingAddress::from(next_hop).try_into().unwrap(),
+                next_hop_key,
+            ),
+        ];
+        let destination = Destination::new(
+            DestinationAddressBytes::from_bytes([3u8; DESTINATION_ADDRESS_LENGTH]),
+            [4u8; IDENTIFIER_LENGTH],
+        );
+        let delays = vec![SphinxDelay::new_from_nanos(42); 2];
+        let packet = SphinxPacketBuilder::new()
+            .with_payload_size(PacketSize::default().payload_size())
+            .build_packet(b"foomp".to_vec(), &route, &destination, &delays)
+            .unwrap();
+        let mut packet_bytes = packet.to_bytes();
+
+        let framed = FramedSphinxPacket::new(packet.into(), PacketMode::Mix, false);
+        assert!(matches!(
+            processor.process_received(framed),
+            Ok(MixProcessingResult::ForwardHop(..))
+        ));
+
+        // the top bit of the alpha is ignored by the key exchange
+        packet_bytes[31] ^= 0x80;
+        let replayed = NymPacket::sphinx_from_bytes(&packet_bytes).unwrap();
+        let framed = FramedSphinxPacket::new(replayed, PacketMode::Mix, false);
+        assert!(matches!(
+            processor.process_received(framed),
+            Err(MixProcessingError::ReplayedPacket)
+        ));
+    }
 }
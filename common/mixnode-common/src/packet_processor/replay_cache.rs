@@ -0,0 +1,341 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use mixnet_contract_common::sphinx_keys::SphinxKeyEpoch;
+use std::collections::{BTreeMap, HashSet};
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Default number of tags of packets unwrapped with the static key kept in a single generation
+/// of the cache. With two generations, each tag taking 32 bytes (plus the hashset overhead),
+/// they use roughly 20MB of memory.
+pub const DEFAULT_REPLAY_CACHE_GENERATION_SIZE: usize = 250_000;
+
+/// Default maximum number of tags of packets unwrapped with a single rotated key. Since at most
+/// two rotated keys are active at any given time, they use at most roughly 80MB of memory.
+pub const DEFAULT_REPLAY_CACHE_EPOCH_CAPACITY: usize = 1_000_000;
+
+/// Number of independently locked shards of the cache, so that the packets processed
+/// concurrently by different connections would rarely contend on the same lock.
+const REPLAY_CACHE_SHARDS: usize = 16;
+
+/// Tag uniquely identifying a sphinx packet at a given hop, i.e. its shared secret.
+pub type ReplayTag = [u8; 32];
+
+/// Outcome of checking a packet tag against the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayCheck {
+    /// The tag has not been seen before and got inserted into the cache.
+    Fresh,
+
+    /// The tag has already been seen.
+    Replayed,
+
+    /// The tags of the key epoch have reached their capacity, so it's impossible to tell whether
+    /// the packet is fresh. Such packets must be rejected, as otherwise flooding the node with
+    /// traffic would let an attacker replay packets that got evicted from the cache.
+    CapacityExceeded,
+}
+
+impl ReplayCheck {
+    pub fn is_fresh(&self) -> bool {
+        matches!(self, ReplayCheck::Fresh)
+    }
+}
+
+struct ReplayCacheShard {
+    /// Maximum number of tags in a single generation of the static key tags.
+    generation_size: usize,
+
+    /// Maximum number of tags of a single rotated key epoch.
+    epoch_capacity: usize,
+    current_static: HashSet<ReplayTag>,
+    previous_static: HashSet<ReplayTag>,
+
+    /// Tags of packets unwrapped with the rotated keys, grouped by the epochs of the keys.
+    rotated: BTreeMap<SphinxKeyEpoch, HashSet<ReplayTag>>,
+}
+
+impl ReplayCacheShard {
+    fn new(generation_size: usize, epoch_capacity: usize) -> Self {
+        ReplayCacheShard {
+            generation_size,
+            epoch_capacity,
+            current_static: HashSet::new(),
+            previous_static: HashSet::new(),
+            rotated: BTreeMap::new(),
+        }
+    }
+
+    /// Inserts the tag unless it was already present or its epoch is already full.
+    fn insert(&mut self, tag: ReplayTag, key_epoch: Option<SphinxKeyEpoch>) -> ReplayCheck {
+        match key_epoch {
+            None => {
+                if self.current_static.contains(&tag) || self.previous_static.contains(&tag) {
+                    return ReplayCheck::Replayed;
+                }
+                if self.current_static.len() >= self.generation_size {
+                    let fresh = HashSet::with_capacity(self.generation_size);
+                    self.previous_static = mem::replace(&mut self.current_static, fresh);
+                }
+                self.current_static.insert(tag);
+                ReplayCheck::Fresh
+            }
+            Some(epoch) => {
+                let tags = self.rotated.entry(epoch).or_default();
+                if tags.contains(&tag) {
+                    ReplayCheck::Replayed
+                } else if tags.len() >= self.epoch_capacity {
+                    // unlike the static key, we can't just forget the old tags as the key
+                    // is still active and thus the forgotten packets could be replayed
+                    ReplayCheck::CapacityExceeded
+                } else {
+                    tags.insert(tag);
+                    ReplayCheck::Fresh
+                }
+            }
+        }
+    }
+
+    fn forget_epochs_before(&mut self, epoch: SphinxKeyEpoch) {
+        self.rotated = self.rotated.split_off(&epoch);
+    }
+}
+
+/// Cache of tags of recently processed sphinx packets used for detecting replays.
+///
+/// A replayed packet can only be unwrapped for as long as the key it was encrypted for
+/// is active, so the tags of packets unwrapped with a rotated key are kept for exactly
+/// that long and are forgotten together with the key. To keep the memory bounded, each epoch
+/// can hold at most `epoch_capacity` tags (per shard) and once it's full, any new packet
+/// of that epoch is rejected until the key expires.
+///
+/// The static key never expires, thus the tags of its packets are kept in two bounded generations
+/// instead. Once the current generation gets full, it becomes the previous one and the old
+/// previous generation is discarded. This means the cache always remembers at least
+/// `generation_size` most recent static key packets (per shard) while never holding more than
+/// `2 * generation_size` of them.
+///
+/// Note that cloning the cache produces a handle to the same underlying data.
+#[derive(Clone)]
+pub struct ReplayCache {
+    shards: Arc<Vec<Mutex<ReplayCacheShard>>>,
+
+    /// All the tags of rotated keys older than this epoch have already been forgotten.
+    oldest_epoch: Arc<AtomicU64>,
+    replayed_packets: Arc<AtomicU64>,
+    over_capacity_packets: Arc<AtomicU64>,
+}
+
+impl Default for ReplayCache {
+    fn default() -> Self {
+        ReplayCache::new(
+            DEFAULT_REPLAY_CACHE_GENERATION_SIZE,
+            DEFAULT_REPLAY_CACHE_EPOCH_CAPACITY,
+        )
+    }
+}
+
+impl ReplayCache {
+    /// Creates a cache keeping at least `generation_size` most recent tags
+    /// of the static key packets and at most `epoch_capacity` tags of each rotated key.
+    pub fn new(generation_size: usize, epoch_capacity: usize) -> Self {
+        // a zero-sized generation would make every packet immediately forgotten
+        let shard_generation_size = (generation_size / REPLAY_CACHE_SHARDS).max(1);
+        let shard_epoch_capacity = (epoch_capacity / REPLAY_CACHE_SHARDS).max(1);
+
+        ReplayCache {
+            shards: Arc::new(
+                (0..REPLAY_CACHE_SHARDS)
+                    .map(|_| {
+                        Mutex::new(ReplayCacheShard::new(
+                            shard_generation_size,
+                            shard_epoch_capacity,
+                        ))
+                    })
+                    .collect(),
+            ),
+            oldest_epoch: Arc::new(AtomicU64::new(0)),
+            replayed_packets: Arc::new(AtomicU64::new(0)),
+            over_capacity_packets: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn shard(&self, tag: &ReplayTag) -> MutexGuard<'_, ReplayCacheShard> {
+        // the tags are uniformly distributed, so any of its bytes is good enough for picking
+        // the shard
+        self.shards[tag[0] as usize % REPLAY_CACHE_SHARDS]
+            .lock()
+            .expect("replay cache mutex got poisoned")
+    }
+
+    /// Checks whether the tag of the packet unwrapped with the key of the provided epoch (or the
+    /// static key) has already been seen and if not, inserts it into the cache.
+    pub fn check_and_insert(
+        &self,
+        tag: ReplayTag,
+        key_epoch: Option<SphinxKeyEpoch>,
+    ) -> ReplayCheck {
+        let outcome = self.shard(&tag).insert(tag, key_epoch);
+
+        // we only use the counters for reporting purposes
+        match outcome {
+            ReplayCheck::Fresh => (),
+            ReplayCheck::Replayed => {
+                self.replayed_packets.fetch_add(1, Ordering::Relaxed);
+            }
+            ReplayCheck::CapacityExceeded => {
+                self.over_capacity_packets.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        outcome
+    }
+
+    /// Forgets the tags of packets unwrapped with the rotated keys of epochs preceding
+    /// the provided one, as those keys are no longer used and thus the packets could never
+    /// be successfully replayed. It is cheap to call if there is nothing to forget.
+    pub fn forget_epochs_before(&self, epoch: SphinxKeyEpoch) {
+        if self.oldest_epoch.fetch_max(epoch, Ordering::AcqRel) >= epoch {
+            return;
+        }
+
+        for shard in self.shards.iter() {
+            shard
+                .lock()
+                .expect("replay cache mutex got poisoned")
+                .forget_epochs_before(epoch)
+        }
+    }
+
+    /// Total number of replayed packets detected by this cache.
+    pub fn replayed_packets(&self) -> u64 {
+        self.replayed_packets.load(Ordering::Relaxed)
+    }
+
+    /// Total number of packets rejected because the tags of their key epoch reached the capacity.
+    pub fn over_capacity_packets(&self) -> u64 {
+        self.over_capacity_packets.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // makes sure all the tags end up in the same shard
+    fn tag(i: u8) -> ReplayTag {
+        let mut tag = [i; 32];
+        tag[0] = 0;
+        tag
+    }
+
+    #[test]
+    fn replayed_tag_is_detected() {
+        let cache = ReplayCache::new(10 * REPLAY_CACHE_SHARDS, 10 * REPLAY_CACHE_SHARDS);
+        assert!(cache.check_and_insert([1u8; 32], None).is_fresh());
+        assert!(cache.check_and_insert([2u8; 32], Some(5)).is_fresh());
+        assert_eq!(
+            cache.check_and_insert([1u8; 32], None),
+            ReplayCheck::Replayed
+        );
+        assert_eq!(cache.replayed_packets(), 1);
+
+        // clones share the same underlying cache
+        let cloned = cache.clone();
+        assert_eq!(
+            cloned.check_and_insert([2u8; 32], Some(5)),
+            ReplayCheck::Replayed
+        );
+        assert_eq!(cache.replayed_packets(), 2);
+    }
+
+    #[test]
+    fn static_key_tags_are_bounded_by_two_generations() {
+        let cache = ReplayCache::new(4 * REPLAY_CACHE_SHARDS, 4 * REPLAY_CACHE_SHARDS);
+        for i in 0..9u8 {
+            assert!(cache.check_and_insert(tag(i), None).is_fresh());
+        }
+
+        // the most recent generation_size tags are always remembered
+        for i in 5..9u8 {
+            assert_eq!(cache.check_and_insert(tag(i), None), ReplayCheck::Replayed);
+        }
+        // while the oldest ones got discarded
+        assert!(cache.check_and_insert(tag(0), None).is_fresh());
+
+        let shard = cache.shard(&tag(0));
+        assert!(shard.current_static.len() + shard.previous_static.len() <= 8);
+    }
+
+    #[test]
+    fn rotated_key_tags_live_as_long_as_their_keys() {
+        let cache = ReplayCache::new(REPLAY_CACHE_SHARDS, 1000 * REPLAY_CACHE_SHARDS);
+
+        // way more packets than the static generation size
+        for i in 0..100u8 {
+            assert!(cache.check_and_insert(tag(i), Some(10)).is_fresh());
+        }
+        assert!(cache.check_and_insert(tag(0), Some(11)).is_fresh());
+        for i in 0..100u8 {
+            assert_eq!(
+                cache.check_and_insert(tag(i), Some(10)),
+                ReplayCheck::Replayed
+            );
+        }
+
+        // the key of epoch 10 expired
+        cache.forget_epochs_before(11);
+        assert!(cache.check_and_insert(tag(1), Some(10)).is_fresh());
+        assert_eq!(
+            cache.check_and_insert(tag(0), Some(11)),
+            ReplayCheck::Replayed
+        );
+
+        // forgetting is never undone by an older epoch
+        cache.forget_epochs_before(9);
+        assert_eq!(
+            cache.check_and_insert(tag(0), Some(11)),
+            ReplayCheck::Replayed
+        );
+    }
+
+    #[test]
+    fn rotated_key_tags_are_bounded_per_epoch() {
+        let cache = ReplayCache::new(REPLAY_CACHE_SHARDS, 4 * REPLAY_CACHE_SHARDS);
+        for i in 0..4u8 {
+            assert!(cache.check_and_insert(tag(i), Some(10)).is_fresh());
+        }
+
+        // the epoch is full, so new packets are rejected while the old ones are still remembered
+        assert_eq!(
+            cache.check_and_insert(tag(4), Some(10)),
+            ReplayCheck::CapacityExceeded
+        );
+        assert_eq!(
+            cache.check_and_insert(tag(0), Some(10)),
+            ReplayCheck::Replayed
+        );
+        assert_eq!(cache.over_capacity_packets(), 1);
+        assert_eq!(cache.replayed_packets(), 1);
+
+        // other epochs are not affected
+        assert!(cache.check_and_insert(tag(4), Some(11)).is_fresh());
+
+        // and the capacity is freed once the key expires
+        cache.forget_epochs_before(11);
+        assert!(cache.check_and_insert(tag(4), Some(10)).is_fresh());
+        assert_eq!(cache.shard(&tag(0)).rotated[&10].len(), 1);
+    }
+
+    #[test]
+    fn tags_are_spread_across_shards() {
+        let cache = ReplayCache::default();
+        for i in 0..=255u8 {
+            assert!(cache.check_and_insert([i; 32], None).is_fresh());
+        }
+        for shard in cache.shards.iter() {
+            assert_eq!(shard.lock().unwrap().current_static.len(), 16);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::packet_processor::error::MixProcessingError;
+use crate::packet_processor::processor::{MixProcessingResult, SphinxPacketProcessor};
+use nymsphinx_framing::packet::FramedSphinxPacket;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+pub use rayon::ThreadPoolBuildError;
+
+/// Result of processing a single packet in the worker pool.
+pub struct ProcessingOutcome {
+    pub result: Result<MixProcessingResult, MixProcessingError>,
+
+    /// Time it took to unwrap the packet (excluding any time spent waiting in the pool).
+    pub processing_time: Duration,
+}
+
+/// Dedicated pool of threads for the CPU-bound sphinx unwrapping, so that it would not compete
+/// with the I/O performed on the tokio runtime.
+///
+/// Packets are submitted in batches, which get unwrapped in parallel, but the outcomes are
+/// always returned in the same order as the packets were submitted in. Hence as long as the
+/// next batch of a connection is only submitted once the previous one got processed,
+/// the per-connection ordering is preserved.
+///
+/// Note that cloning it produces a handle to the same underlying pool.
+#[derive(Clone)]
+pub struct SphinxProcessingPool {
+    processor: SphinxPacketProcessor,
+    pool: Arc<ThreadPool>,
+}
+
+impl SphinxProcessingPool {
+    pub fn new(
+        processor: SphinxPacketProcessor,
+        threads: usize,
+    ) -> Result<Self, ThreadPoolBuildError> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("sphinx-worker-{}", i))
+            .build()?;
+
+        Ok(SphinxProcessingPool {
+            processor,
+            pool: Arc::new(pool),
+        })
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    fn process_timed(
+        processor: &SphinxPacketProcessor,
+        packet: FramedSphinxPacket,
+    ) -> ProcessingOutcome {
+        let start = Instant::now();
+        let result = processor.process_received(packet);
+        ProcessingOutcome {
+            result,
+            processing_time: start.elapsed(),
+        }
+    }
+
+    /// Unwraps the batch of packets in the pool, returning the outcomes in the submission order.
+    pub async fn process_batch(&self, packets: Vec<FramedSphinxPacket>) -> Vec<ProcessingOutcome> {
+        let (outcome_sender, outcome_receiver) = oneshot::channel();
+        let processor = self.processor.clone();
+
+        self.pool.spawn(move || {
+            let outcomes = packets
+                .into_par_iter()
+                .map(|packet| Self::process_timed(&processor, packet))
+                .collect();
+
+            // the receiver might have gone away if the connection got closed in the meantime,
+            // but in that case nobody cares about the outcome anyway
+            let _ = outcome_sender.send(outcomes);
+        });
+
+        // the sender is only ever dropped after sending the outcome, unless the closure panicked,
+        // in which case we have a bigger problem
+        outcome_receiver
+            .await
+            .expect("sphinx processing worker has panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nymsphinx_addressing::nodes::NymNodeRoutingAddress;
+    use nymsphinx_params::{PacketMode, PacketSize};
+    use nymsphinx_types::builder::SphinxPacketBuilder;
+    use nymsphinx_types::{
+        crypto, Delay as SphinxDelay, Destination, DestinationAddressBytes, Node, NodeAddressBytes,
+        DESTINATION_ADDRESS_LENGTH, IDENTIFIER_LENGTH,
+    };
+    use std::convert::TryInto;
+    use std::net::SocketAddr;
+
+    fn node_address(port: u16) -> NodeAddressBytes {
+        let address: SocketAddr = format!("1.2.3.4:{}", port).parse().unwrap();
+        NymNodeRoutingAddress::from(address).try_into().unwrap()
+    }
+
+    fn packet_for(first_hop_key: &crypto::PublicKey, next_hop_port: u16) -> FramedSphinxPacket {
+        let (_, second_hop_key) = crypto::keygen();
+        let route = [
+            Node::new(node_address(1789), *first_hop_key),
+            Node::new(node_address(next_hop_port), second_hop_key),
+        ];
+        let destination = Destination::new(
+            DestinationAddressBytes::from_bytes([3u8; DESTINATION_ADDRESS_LENGTH]),
+            [4u8; IDENTIFIER_LENGTH],
+        );
+        let delays = vec![SphinxDelay::new_from_nanos(42); 2];
+        let packet = SphinxPacketBuilder::new()
+            .with_payload_size(PacketSize::default().payload_size())
+            .build_packet(b"foomp".to_vec(), &route, &destination, &delays)
+            .unwrap();
+
+        FramedSphinxPacket::new(packet.into(), PacketMode::default(), false)
+    }
+
+    #[tokio::test]
+    async fn batch_outcomes_preserve_submission_order() {
+        let (private_key, public_key) = crypto::keygen();
+        let pool = SphinxProcessingPool::new(SphinxPacketProcessor::new(private_key), 4).unwrap();
+
+        let ports = (2000..2032).collect::<Vec<u16>>();
+        let packets = ports
+            .iter()
+            .map(|port| packet_for(&public_key, *port))
+            .collect();
+
+        let outcomes = pool.process_batch(packets).await;
+        assert_eq!(outcomes.len(), ports.len());
+        for (outcome, port) in outcomes.into_iter().zip(ports) {
+            match outcome.result.unwrap() {
+                MixProcessingResult::ForwardHop(packet, _) => {
+                    assert_eq!(SocketAddr::from(packet.next_hop()).port(), port)
+                }
+                MixProcessingResult::FinalHop(_) => panic!("expected a forward hop"),
+            }
+        }
+    }
+}
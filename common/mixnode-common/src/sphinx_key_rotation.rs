@@ -0,0 +1,418 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::asymmetric::{encryption, identity};
+use log::*;
+pub use mixnet_contract_common::sphinx_keys::SphinxKeyAnnouncement;
+use mixnet_contract_common::sphinx_keys::{
+    sphinx_key_epoch, sphinx_key_epoch_start, SphinxKeyEpoch, SPHINX_KEY_OVERLAP_SECS,
+};
+use nymsphinx_types::crypto::keygen;
+use nymsphinx_types::PrivateKey;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use task::ShutdownListener;
+
+/// How often the rotation task checks whether the set of active keys should change.
+pub const ROTATION_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Key that is currently used for unwrapping received packets.
+pub struct ActiveKey {
+    /// Epoch the key has been generated for or `None` for the static key, which never expires.
+    pub epoch: Option<SphinxKeyEpoch>,
+
+    pub private_key: Arc<PrivateKey>,
+}
+
+struct RotatedKey {
+    // kept around in this form so that the key could be persisted
+    stored_key: encryption::PrivateKey,
+    private_key: Arc<PrivateKey>,
+    public_key: encryption::PublicKey,
+}
+
+impl RotatedKey {
+    fn new() -> Self {
+        Self::from_stored(keygen().0.into())
+    }
+
+    fn from_stored(stored_key: encryption::PrivateKey) -> Self {
+        RotatedKey {
+            private_key: Arc::new((&stored_key).into()),
+            public_key: (&stored_key).into(),
+            stored_key,
+        }
+    }
+}
+
+fn load_rotated_keys(path: &Path) -> io::Result<BTreeMap<SphinxKeyEpoch, RotatedKey>> {
+    let stored: Vec<(SphinxKeyEpoch, encryption::PrivateKey)> = match File::open(path) {
+        Ok(file) => serde_json::from_reader(file)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err),
+    };
+
+    Ok(stored
+        .into_iter()
+        .map(|(epoch, private_key)| (epoch, RotatedKey::from_stored(private_key)))
+        .collect())
+}
+
+fn store_rotated_keys(
+    path: &Path,
+    rotated_keys: &BTreeMap<SphinxKeyEpoch, RotatedKey>,
+) -> io::Result<()> {
+    let stored = rotated_keys
+        .iter()
+        .map(|(epoch, key)| (epoch, &key.stored_key))
+        .collect::<Vec<_>>();
+
+    // write the keys to a temporary file first so that a crash would never leave us
+    // with a corrupted one
+    let temp_path = path.with_extension("tmp");
+    let file = File::create(&temp_path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    serde_json::to_writer(&file, &stored)?;
+    file.sync_all()?;
+    fs::rename(temp_path, path)
+}
+
+struct KeyRingInner {
+    /// The static key of the node used by clients that are not aware of the key rotation.
+    static_key: Arc<PrivateKey>,
+
+    /// Whether fresh keys should get generated for each epoch.
+    rotation_enabled: bool,
+
+    rotated_keys: BTreeMap<SphinxKeyEpoch, RotatedKey>,
+
+    /// File the rotated keys are persisted to, so that the keys announced to the network
+    /// would survive restarts of the node.
+    storage_path: Option<PathBuf>,
+
+    /// Keys that should currently be attempted for unwrapping received packets, in order.
+    active_keys: Arc<Vec<ActiveKey>>,
+}
+
+impl KeyRingInner {
+    fn rotate(&mut self, unix_timestamp: u64) {
+        let current_epoch = sphinx_key_epoch(unix_timestamp);
+        let next_epoch = current_epoch + 1;
+        let within_overlap =
+            unix_timestamp < sphinx_key_epoch_start(current_epoch) + SPHINX_KEY_OVERLAP_SECS;
+
+        // the previous key is only retained during the overlap window, afterwards it gets
+        // dropped so that the packets encrypted with it could never be unwrapped again
+        let held_keys = self.rotated_keys.len();
+        self.rotated_keys.retain(|&epoch, _| {
+            (epoch >= current_epoch && epoch <= next_epoch)
+                || (within_overlap && epoch + 1 == current_epoch)
+        });
+        let mut changed = held_keys != self.rotated_keys.len();
+
+        for epoch in [current_epoch, next_epoch] {
+            self.rotated_keys.entry(epoch).or_insert_with(|| {
+                debug!("generating fresh sphinx key for epoch {}", epoch);
+                changed = true;
+                RotatedKey::new()
+            });
+        }
+
+        if changed {
+            self.persist();
+        }
+
+        // while the network is migrating, the vast majority of the packets is still encrypted
+        // with the static key, so we try it first. Afterwards the current key is by far
+        // the most likely to be used, then the previous one (during the overlap) followed by
+        // the next one (for clients with skewed clocks)
+        let mut active_keys = Vec::with_capacity(self.rotated_keys.len() + 1);
+        active_keys.push(ActiveKey {
+            epoch: None,
+            private_key: Arc::clone(&self.static_key),
+        });
+        active_keys.extend(
+            [current_epoch, current_epoch.wrapping_sub(1), next_epoch]
+                .iter()
+                .filter_map(|epoch| {
+                    self.rotated_keys.get(epoch).map(|key| ActiveKey {
+                        epoch: Some(*epoch),
+                        private_key: Arc::clone(&key.private_key),
+                    })
+                }),
+        );
+        self.active_keys = Arc::new(active_keys);
+    }
+
+    fn persist(&self) {
+        if let Some(storage_path) = &self.storage_path {
+            // if we failed to persist the keys, we can still carry on, it's just that
+            // the packets sent with the currently announced keys will get dropped after a restart
+            if let Err(err) = store_rotated_keys(storage_path, &self.rotated_keys) {
+                error!(
+                    "failed to persist the rotated sphinx keys to {} - {}",
+                    storage_path.display(),
+                    err
+                )
+            }
+        }
+    }
+}
+
+/// Set of sphinx keys a node is currently willing to use for unwrapping received packets.
+///
+/// Apart from its static key, a node with rotation enabled holds a fresh key for the current
+/// and the upcoming epoch as well as, during the overlap window, the key of the previous epoch.
+/// The rotated keys are persisted, so that the keys announced to the network remain valid
+/// across restarts, but they are removed from the storage as soon as they expire, so that
+/// once an epoch is over, the packets sent during it could no longer be unwrapped even if
+/// the node got compromised.
+///
+/// Since a reply SURB is bound to the keys of the epoch during which it got constructed,
+/// rotation limits its lifetime, see `REPLY_SURB_MAX_AGE`.
+///
+/// Note that cloning the key ring produces a handle to the same underlying keys.
+#[derive(Clone)]
+pub struct SphinxKeyRing {
+    inner: Arc<RwLock<KeyRingInner>>,
+}
+
+impl SphinxKeyRing {
+    /// Creates a key ring only ever using the provided static key.
+    pub fn new_static(static_key: PrivateKey) -> Self {
+        let static_key = Arc::new(static_key);
+        SphinxKeyRing {
+            inner: Arc::new(RwLock::new(KeyRingInner {
+                active_keys: Arc::new(vec![ActiveKey {
+                    epoch: None,
+                    private_key: Arc::clone(&static_key),
+                }]),
+                static_key,
+                rotation_enabled: false,
+                rotated_keys: BTreeMap::new(),
+                storage_path: None,
+            })),
+        }
+    }
+
+    /// Creates a key ring that, apart from the provided static key, also uses fresh keys
+    /// generated for each epoch. The rotated keys are persisted to the provided file
+    /// and any keys that are still valid are loaded from it.
+    pub fn new_rotating<P: AsRef<Path>>(
+        static_key: PrivateKey,
+        storage_path: P,
+    ) -> io::Result<Self> {
+        let storage_path = storage_path.as_ref().to_path_buf();
+        let rotated_keys = load_rotated_keys(&storage_path)?;
+
+        let key_ring = Self::new_static(static_key);
+        {
+            let mut guard = key_ring.write();
+            guard.rotation_enabled = true;
+            guard.rotated_keys = rotated_keys;
+            guard.storage_path = Some(storage_path);
+            guard.rotate(current_unix_timestamp());
+        }
+        Ok(key_ring)
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, KeyRingInner> {
+        self.inner
+            .read()
+            .expect("sphinx key ring lock got poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, KeyRingInner> {
+        self.inner
+            .write()
+            .expect("sphinx key ring lock got poisoned")
+    }
+
+    pub fn rotation_enabled(&self) -> bool {
+        self.read().rotation_enabled
+    }
+
+    /// Keys that should be attempted, in order, for unwrapping received packets.
+    pub fn active_keys(&self) -> Arc<Vec<ActiveKey>> {
+        Arc::clone(&self.read().active_keys)
+    }
+
+    /// Updates the set of keys based on the provided time, generating keys for new epochs
+    /// and removing the expired ones.
+    pub fn rotate(&self, unix_timestamp: u64) {
+        let mut guard = self.write();
+        if guard.rotation_enabled {
+            guard.rotate(unix_timestamp)
+        }
+    }
+
+    /// Produces announcements of all currently held rotated keys signed with the provided
+    /// identity key.
+    pub fn announcements(&self, identity_key: &identity::PrivateKey) -> Vec<SphinxKeyAnnouncement> {
+        self.read()
+            .rotated_keys
+            .iter()
+            .map(|(&epoch, key)| {
+                let sphinx_key = key.public_key.to_base58_string();
+                let plaintext = SphinxKeyAnnouncement::signing_plaintext(epoch, &sphinx_key);
+                SphinxKeyAnnouncement {
+                    epoch,
+                    sphinx_key,
+                    signature: identity_key.sign(&plaintext).to_base58_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn current_unix_timestamp() -> u64 {
+    // the unwrap is fine as the current time is always after the unix epoch
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Task periodically rotating keys of the provided key ring.
+pub struct SphinxKeyRotator {
+    key_ring: SphinxKeyRing,
+    shutdown: ShutdownListener,
+}
+
+impl SphinxKeyRotator {
+    pub fn new(key_ring: SphinxKeyRing, shutdown: ShutdownListener) -> Self {
+        SphinxKeyRotator { key_ring, shutdown }
+    }
+
+    pub async fn run(&mut self) {
+        debug!("Started SphinxKeyRotator with graceful shutdown support");
+        while !self.shutdown.is_shutdown() {
+            tokio::select! {
+                _ = tokio::time::sleep(ROTATION_CHECK_INTERVAL) => {
+                    self.key_ring.rotate(current_unix_timestamp())
+                }
+                _ = self.shutdown.recv() => {
+                    trace!("SphinxKeyRotator: Received shutdown");
+                }
+            }
+        }
+        trace!("SphinxKeyRotator: Exiting");
+    }
+
+    pub fn start(mut self) {
+        tokio::spawn(async move { self.run().await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mixnet_contract_common::sphinx_keys::SPHINX_KEY_EPOCH_LENGTH_SECS;
+
+    fn rotating_ring_at(unix_timestamp: u64) -> SphinxKeyRing {
+        let ring = SphinxKeyRing::new_static(keygen().0);
+        {
+            let mut guard = ring.write();
+            guard.rotation_enabled = true;
+            guard.rotate(unix_timestamp);
+        }
+        ring
+    }
+
+    #[test]
+    fn static_ring_only_uses_the_static_key() {
+        let ring = SphinxKeyRing::new_static(keygen().0);
+        ring.rotate(current_unix_timestamp());
+        assert_eq!(ring.active_keys().len(), 1);
+        assert!(ring
+            .announcements(&identity::PrivateKey::from_bytes(&[1; 32]).unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    fn previous_key_is_only_kept_during_overlap() {
+        let epoch_start = sphinx_key_epoch_start(1000);
+        let ring = rotating_ring_at(epoch_start - 1);
+        // current, next and the static key
+        assert_eq!(ring.active_keys().len(), 3);
+
+        // the new epoch just started - the previous key is still accepted
+        ring.rotate(epoch_start);
+        assert_eq!(ring.active_keys().len(), 4);
+
+        // but not after the overlap window is over
+        ring.rotate(epoch_start + SPHINX_KEY_OVERLAP_SECS);
+        assert_eq!(ring.active_keys().len(), 3);
+
+        ring.rotate(epoch_start + 5 * SPHINX_KEY_EPOCH_LENGTH_SECS);
+        assert_eq!(ring.active_keys().len(), 3);
+    }
+
+    #[test]
+    fn static_key_is_attempted_first() {
+        let static_key = keygen().0;
+        let static_key_bytes = static_key.to_bytes();
+        let ring = SphinxKeyRing::new_static(static_key);
+        {
+            let mut guard = ring.write();
+            guard.rotation_enabled = true;
+            guard.rotate(sphinx_key_epoch_start(1000));
+        }
+
+        // static, current and next
+        let active_keys = ring.active_keys();
+        assert_eq!(active_keys.len(), 3);
+        assert_eq!(active_keys[0].epoch, None);
+        assert_eq!(active_keys[0].private_key.to_bytes(), static_key_bytes);
+    }
+
+    #[test]
+    fn rotated_keys_survive_restarts() {
+        let storage_path = std::env::temp_dir().join(format!(
+            "rotated-sphinx-keys-{}.json",
+            rand::random::<u64>()
+        ));
+        let identity = identity::PrivateKey::from_bytes(&[42; 32]).unwrap();
+
+        let ring = SphinxKeyRing::new_rotating(keygen().0, &storage_path).unwrap();
+        let announced = ring.announcements(&identity);
+        assert_eq!(announced.len(), 2);
+
+        let restarted = SphinxKeyRing::new_rotating(keygen().0, &storage_path).unwrap();
+        let reannounced = restarted.announcements(&identity);
+        for (before, after) in announced.iter().zip(reannounced.iter()) {
+            assert_eq!(before.epoch, after.epoch);
+            assert_eq!(before.sphinx_key, after.sphinx_key);
+        }
+
+        fs::remove_file(storage_path).unwrap();
+    }
+
+    #[test]
+    fn announcements_are_signed_with_identity_key() {
+        let identity_private = identity::PrivateKey::from_bytes(&[42; 32]).unwrap();
+        let identity_public = identity::PublicKey::from(&identity_private);
+
+        let ring = rotating_ring_at(sphinx_key_epoch_start(1000) + SPHINX_KEY_OVERLAP_SECS);
+        let announcements = ring.announcements(&identity_private);
+        assert_eq!(announcements.len(), 2);
+        assert_eq!(announcements[0].epoch, 1000);
+        assert_eq!(announcements[1].epoch, 1001);
+
+        for announcement in announcements {
+            let signature =
+                identity::Signature::from_base58_string(&announcement.signature).unwrap();
+            assert!(identity_public
+                .verify(&announcement.plaintext(), &signature)
+                .is_ok());
+        }
+    }
+}
@@ -0,0 +1,51 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::sync::{Arc, RwLock};
+use url::Url;
+
+/// Addresses of the validator APIs from which the node gets the view of the network,
+/// which can be changed while the node is running.
+///
+/// Note that cloning it produces a handle to the same underlying data.
+#[derive(Clone, Debug, Default)]
+pub struct ValidatorApiEndpoints {
+    urls: Arc<RwLock<Vec<Url>>>,
+}
+
+impl ValidatorApiEndpoints {
+    pub fn new(urls: Vec<Url>) -> Self {
+        ValidatorApiEndpoints {
+            urls: Arc::new(RwLock::new(urls)),
+        }
+    }
+
+    pub fn update(&self, urls: Vec<Url>) {
+        *self
+            .urls
+            .write()
+            .expect("validator api urls lock got poisoned") = urls;
+    }
+
+    pub fn urls(&self) -> Vec<Url> {
+        self.urls
+            .read()
+            .expect("validator api urls lock got poisoned")
+            .clone()
+    }
+
+    /// Creates a client for one of the validator APIs, chosen at random.
+    pub fn random_client(&self) -> validator_client::ApiClient {
+        let urls = self
+            .urls
+            .read()
+            .expect("validator api urls lock got poisoned");
+        let validator_api = urls
+            .choose(&mut thread_rng())
+            .expect("The list of validator apis is empty");
+
+        validator_client::ApiClient::new(validator_api.clone())
+    }
+}
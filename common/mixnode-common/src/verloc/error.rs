@@ -79,3 +79,32 @@ impl Display for RttError {
 }
 
 impl std::error::Error for RttError {}
+
+#[derive(Debug)]
+pub enum ReportError {
+    MalformedSignerIdentity,
+    MalformedSignature,
+    InvalidSignature,
+    MalformedReport(serde_json::Error),
+}
+
+impl Display for ReportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportError::MalformedSignerIdentity => {
+                write!(f, "The verloc report had malformed signer identity")
+            }
+            ReportError::MalformedSignature => {
+                write!(f, "The verloc report had malformed signature")
+            }
+            ReportError::InvalidSignature => {
+                write!(f, "The verloc report had invalid signature")
+            }
+            ReportError::MalformedReport(err) => {
+                write!(f, "The verloc report could not be parsed - {}", err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
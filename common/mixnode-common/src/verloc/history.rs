@@ -0,0 +1,361 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::verloc::error::ReportError;
+use crate::verloc::measurement::VerlocResult;
+use crypto::asymmetric::identity;
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::RwLock;
+
+/// Results of a single verloc measurement run signed with the identity key of the node
+/// that has performed it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedVerlocReport {
+    /// Base58 encoded identity key of the node that has performed the measurements.
+    pub identity: String,
+
+    /// JSON serialized `VerlocResult`. It is kept in its serialized form so that the signature
+    /// could be verified without having to reproduce the exact serialization.
+    pub report: String,
+
+    /// Base58 encoded signature on the bytes of the `report`.
+    pub signature: String,
+}
+
+impl SignedVerlocReport {
+    pub fn new(identity: &identity::KeyPair, result: &VerlocResult) -> Self {
+        // serializing a struct with string keys can't fail
+        let report = serde_json::to_string(result).unwrap();
+        let signature = identity.private_key().sign(report.as_bytes());
+
+        SignedVerlocReport {
+            identity: identity.public_key().to_base58_string(),
+            report,
+            signature: signature.to_base58_string(),
+        }
+    }
+
+    /// Verifies the signature on the report and, if valid, returns the measurement results it contains.
+    pub fn verify(&self) -> Result<VerlocResult, ReportError> {
+        let identity = identity::PublicKey::from_base58_string(&self.identity)
+            .map_err(|_| ReportError::MalformedSignerIdentity)?;
+        let signature = identity::Signature::from_base58_string(&self.signature)
+            .map_err(|_| ReportError::MalformedSignature)?;
+        identity
+            .verify(self.report.as_bytes(), &signature)
+            .map_err(|_| ReportError::InvalidSignature)?;
+
+        serde_json::from_str(&self.report).map_err(ReportError::MalformedReport)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Percentiles {
+    #[serde(with = "humantime_serde")]
+    pub p50: Duration,
+    #[serde(with = "humantime_serde")]
+    pub p90: Duration,
+    #[serde(with = "humantime_serde")]
+    pub p99: Duration,
+}
+
+impl Percentiles {
+    fn new(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+
+        // nearest-rank method
+        let percentile = |p: usize| {
+            let rank = (p * samples.len() + 99) / 100;
+            samples[rank.max(1) - 1]
+        };
+
+        Some(Percentiles {
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+        })
+    }
+}
+
+/// Percentiles of the mean round-trip times to all the nodes measured during a single run.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunTrend {
+    #[serde(with = "humantime_serde")]
+    pub run_finished: Option<SystemTime>,
+    pub total_tested: usize,
+    pub measured: usize,
+    pub mean_rtt: Option<Percentiles>,
+}
+
+/// Percentiles of the mean round-trip times to a particular node across all the stored runs.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeTrend {
+    pub measured_in_runs: usize,
+    pub mean_rtt: Option<Percentiles>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerlocTrends {
+    /// Trends of all the stored runs, from the oldest to the most recent one.
+    pub runs: Vec<RunTrend>,
+
+    /// Trends of all the nodes measured in any of the stored runs, by their base58 encoded identities.
+    pub nodes: BTreeMap<String, NodeTrend>,
+}
+
+struct HistoryEntry {
+    report: SignedVerlocReport,
+    result: VerlocResult,
+}
+
+struct HistoryInner {
+    max_runs: usize,
+    storage: Option<PathBuf>,
+    entries: VecDeque<HistoryEntry>,
+}
+
+impl HistoryInner {
+    fn load_entries(storage: &Path) -> io::Result<VecDeque<HistoryEntry>> {
+        let reports: Vec<SignedVerlocReport> = serde_json::from_slice(&fs::read(storage)?)?;
+
+        Ok(reports
+            .into_iter()
+            .filter_map(|report| match report.verify() {
+                Ok(result) => Some(HistoryEntry { report, result }),
+                Err(err) => {
+                    warn!("ignoring stored verloc report - {}", err);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    fn persist(&self) -> io::Result<()> {
+        let storage = match &self.storage {
+            Some(storage) => storage,
+            None => return Ok(()),
+        };
+
+        let reports = self
+            .entries
+            .iter()
+            .map(|entry| &entry.report)
+            .collect::<Vec<_>>();
+
+        // write to a temporary file first so that we'd never end up with a partially written history
+        let temporary = storage.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_vec(&reports)?)?;
+        fs::rename(temporary, storage)
+    }
+}
+
+/// Rolling history of the signed results of the most recent verloc measurement runs,
+/// optionally persisted on disk.
+pub struct VerlocHistory {
+    inner: Arc<RwLock<HistoryInner>>,
+}
+
+impl VerlocHistory {
+    /// Creates the history, loading any previously persisted runs from the `storage`, if provided.
+    pub fn load(storage: Option<PathBuf>, max_runs: usize) -> Self {
+        let mut entries = match &storage {
+            Some(path) if path.exists() => HistoryInner::load_entries(path).unwrap_or_else(|err| {
+                warn!(
+                    "failed to load verloc history from {} - {}. Starting with an empty one",
+                    path.display(),
+                    err
+                );
+                VecDeque::new()
+            }),
+            _ => VecDeque::new(),
+        };
+        while entries.len() > max_runs {
+            entries.pop_front();
+        }
+
+        VerlocHistory {
+            inner: Arc::new(RwLock::new(HistoryInner {
+                max_runs,
+                storage,
+                entries,
+            })),
+        }
+    }
+
+    // this could have also been achieved with a normal #[derive(Clone)] but I prefer to be explicit about it
+    pub(crate) fn clone_data_pointer(&self) -> Self {
+        VerlocHistory {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    pub(crate) async fn append(&self, report: SignedVerlocReport, result: VerlocResult) {
+        let mut write_permit = self.inner.write().await;
+        if write_permit.max_runs == 0 {
+            return;
+        }
+
+        write_permit
+            .entries
+            .push_back(HistoryEntry { report, result });
+        while write_permit.entries.len() > write_permit.max_runs {
+            write_permit.entries.pop_front();
+        }
+
+        if let Err(err) = write_permit.persist() {
+            warn!("failed to persist verloc history - {}", err)
+        }
+    }
+
+    /// Returns the signed reports of all the stored runs, from the oldest to the most recent one.
+    pub async fn signed_reports(&self) -> Vec<SignedVerlocReport> {
+        self.inner
+            .read()
+            .await
+            .entries
+            .iter()
+            .map(|entry| entry.report.clone())
+            .collect()
+    }
+
+    pub async fn trends(&self) -> VerlocTrends {
+        let read_permit = self.inner.read().await;
+
+        let mut runs = Vec::with_capacity(read_permit.entries.len());
+        let mut node_samples: BTreeMap<String, Vec<Duration>> = BTreeMap::new();
+        for entry in &read_permit.entries {
+            let mut run_samples = Vec::with_capacity(entry.result.results().len());
+            for verloc in entry.result.results() {
+                let samples = node_samples
+                    .entry(verloc.identity.to_base58_string())
+                    .or_default();
+                if let Some(measurement) = verloc.latest_measurement {
+                    samples.push(measurement.mean);
+                    run_samples.push(measurement.mean);
+                }
+            }
+
+            runs.push(RunTrend {
+                run_finished: entry.result.run_finished(),
+                total_tested: entry.result.total_tested(),
+                measured: run_samples.len(),
+                mean_rtt: Percentiles::new(run_samples),
+            })
+        }
+
+        let nodes = node_samples
+            .into_iter()
+            .map(|(identity, samples)| {
+                let trend = NodeTrend {
+                    measured_in_runs: samples.len(),
+                    mean_rtt: Percentiles::new(samples),
+                };
+                (identity, trend)
+            })
+            .collect();
+
+        VerlocTrends { runs, nodes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verloc::measurement::{Measurement, Verloc};
+
+    fn test_keys(seed: u8) -> identity::KeyPair {
+        let private_key = identity::PrivateKey::from_bytes(&[seed; 32]).unwrap();
+        let public_key = identity::PublicKey::from(&private_key);
+        identity::KeyPair::from_bytes(&private_key.to_bytes(), &public_key.to_bytes()).unwrap()
+    }
+
+    fn verloc_result(identity: identity::PublicKey, mean_millis: u64) -> VerlocResult {
+        let mean = Duration::from_millis(mean_millis);
+        VerlocResult::new_finished(vec![Verloc::new(
+            identity,
+            Some(Measurement {
+                minimum: mean,
+                mean,
+                maximum: mean,
+                standard_deviation: Duration::ZERO,
+            }),
+        )])
+    }
+
+    #[test]
+    fn tampered_reports_are_rejected() {
+        let keys = test_keys(1);
+        let result = verloc_result(*keys.public_key(), 42);
+
+        let report = SignedVerlocReport::new(&keys, &result);
+        assert_eq!(report.verify().unwrap(), result);
+
+        let mut tampered = report.clone();
+        tampered.report = tampered.report.replace("42ms", "1ms");
+        assert!(matches!(
+            tampered.verify(),
+            Err(ReportError::InvalidSignature)
+        ));
+
+        let other_keys = test_keys(2);
+        let mut wrong_signer = report;
+        wrong_signer.identity = other_keys.public_key().to_base58_string();
+        assert!(matches!(
+            wrong_signer.verify(),
+            Err(ReportError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let samples = (1..=100).map(Duration::from_millis).collect();
+        let percentiles = Percentiles::new(samples).unwrap();
+        assert_eq!(percentiles.p50, Duration::from_millis(50));
+        assert_eq!(percentiles.p90, Duration::from_millis(90));
+        assert_eq!(percentiles.p99, Duration::from_millis(99));
+
+        let single = Percentiles::new(vec![Duration::from_millis(7)]).unwrap();
+        assert_eq!(single.p50, Duration::from_millis(7));
+        assert_eq!(single.p99, Duration::from_millis(7));
+
+        assert!(Percentiles::new(Vec::new()).is_none());
+    }
+
+    #[tokio::test]
+    async fn history_is_rolling_and_persisted() {
+        let storage =
+            std::env::temp_dir().join(format!("verloc-history-test-{}.json", std::process::id()));
+        let keys = test_keys(1);
+
+        let history = VerlocHistory::load(Some(storage.clone()), 2);
+        for mean in [10, 20, 30] {
+            let result = verloc_result(*keys.public_key(), mean);
+            let report = SignedVerlocReport::new(&keys, &result);
+            history.append(report, result).await;
+        }
+
+        let trends = history.trends().await;
+        assert_eq!(trends.runs.len(), 2);
+        let node_trend = &trends.nodes[&keys.public_key().to_base58_string()];
+        assert_eq!(node_trend.measured_in_runs, 2);
+        assert_eq!(node_trend.mean_rtt.unwrap().p50, Duration::from_millis(20));
+
+        let reloaded = VerlocHistory::load(Some(storage.clone()), 2);
+        assert_eq!(
+            reloaded.signed_reports().await,
+            history.signed_reports().await
+        );
+
+        fs::remove_file(storage).unwrap();
+    }
+}
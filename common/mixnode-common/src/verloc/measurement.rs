@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crypto::asymmetric::identity;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::Ordering;
 use std::fmt::{self, Display, Formatter};
 use std::sync::Arc;
@@ -13,7 +13,7 @@ pub struct AtomicVerlocResult {
     inner: Arc<RwLock<VerlocResult>>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct VerlocResult {
     total_tested: usize,
     #[serde(with = "humantime_serde")]
@@ -23,6 +23,31 @@ pub struct VerlocResult {
     results: Vec<Verloc>,
 }
 
+impl VerlocResult {
+    pub fn total_tested(&self) -> usize {
+        self.total_tested
+    }
+
+    pub fn results(&self) -> &[Verloc] {
+        &self.results
+    }
+
+    pub fn run_finished(&self) -> Option<std::time::SystemTime> {
+        self.run_finished
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new_finished(results: Vec<Verloc>) -> Self {
+        let now = std::time::SystemTime::now();
+        VerlocResult {
+            total_tested: results.len(),
+            run_started: Some(now),
+            run_finished: Some(now),
+            results,
+        }
+    }
+}
+
 impl AtomicVerlocResult {
     pub(crate) fn new() -> Self {
         AtomicVerlocResult {
@@ -70,9 +95,12 @@ impl AtomicVerlocResult {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Verloc {
-    #[serde(serialize_with = "serialize_identity_as_string")]
+    #[serde(
+        serialize_with = "serialize_identity_as_string",
+        deserialize_with = "deserialize_identity_from_string"
+    )]
     pub identity: identity::PublicKey,
     pub latest_measurement: Option<Measurement>,
 }
@@ -87,6 +115,16 @@ where
     serializer.serialize_str(&identity.to_base58_string())
 }
 
+fn deserialize_identity_from_string<'de, D>(
+    deserializer: D,
+) -> Result<identity::PublicKey, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let encoded = String::deserialize(deserializer)?;
+    identity::PublicKey::from_base58_string(encoded).map_err(serde::de::Error::custom)
+}
+
 impl Display for Verloc {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         if let Some(measurement) = self.latest_measurement {
@@ -134,15 +172,15 @@ impl Verloc {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Measurement {
-    #[serde(serialize_with = "humantime_serde::serialize")]
+    #[serde(with = "humantime_serde")]
     pub minimum: Duration,
-    #[serde(serialize_with = "humantime_serde::serialize")]
+    #[serde(with = "humantime_serde")]
     pub mean: Duration,
-    #[serde(serialize_with = "humantime_serde::serialize")]
+    #[serde(with = "humantime_serde")]
     pub maximum: Duration,
-    #[serde(serialize_with = "humantime_serde::serialize")]
+    #[serde(with = "humantime_serde")]
     pub standard_deviation: Duration,
 }
 
@@ -1,26 +1,28 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::validator_api::ValidatorApiEndpoints;
 use crate::verloc::listener::PacketListener;
 use crate::verloc::sender::{PacketSender, TestedNode};
 use crypto::asymmetric::identity;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use log::*;
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::{thread_rng, Rng};
 use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use task::ShutdownListener;
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
-use url::Url;
 use version_checker::parse_version;
 
+pub use crate::verloc::history::{SignedVerlocReport, VerlocHistory, VerlocTrends};
 pub use crate::verloc::measurement::{AtomicVerlocResult, Verloc, VerlocResult};
 
 pub mod error;
+pub(crate) mod history;
 pub(crate) mod listener;
 pub(crate) mod measurement;
 pub(crate) mod packet;
@@ -38,6 +40,7 @@ const DEFAULT_DELAY_BETWEEN_PACKETS: Duration = Duration::from_millis(50);
 const DEFAULT_BATCH_SIZE: usize = 50;
 const DEFAULT_TESTING_INTERVAL: Duration = Duration::from_secs(60 * 60 * 12);
 const DEFAULT_RETRY_TIMEOUT: Duration = Duration::from_secs(60 * 30);
+const DEFAULT_HISTORY_LENGTH: usize = 28;
 
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -69,8 +72,15 @@ pub struct Config {
     /// due to being unable to get the list of nodes.
     retry_timeout: Duration,
 
-    /// URLs to the validator apis for obtaining network topology.
-    validator_api_urls: Vec<Url>,
+    /// Validator apis for obtaining network topology.
+    validator_api: ValidatorApiEndpoints,
+
+    /// Specifies number of the most recent measurement runs kept in the history.
+    history_length: usize,
+
+    /// Path to the file in which the measurement history is persisted. If not specified,
+    /// the history is only kept in memory.
+    history_file: Option<PathBuf>,
 }
 
 impl Config {
@@ -132,15 +142,25 @@ impl ConfigBuilder {
         self
     }
 
-    pub fn validator_api_urls(mut self, validator_api_urls: Vec<Url>) -> Self {
-        self.0.validator_api_urls = validator_api_urls;
+    pub fn validator_api(mut self, validator_api: ValidatorApiEndpoints) -> Self {
+        self.0.validator_api = validator_api;
+        self
+    }
+
+    pub fn history_length(mut self, history_length: usize) -> Self {
+        self.0.history_length = history_length;
+        self
+    }
+
+    pub fn history_file(mut self, history_file: PathBuf) -> Self {
+        self.0.history_file = Some(history_file);
         self
     }
 
     pub fn build(self) -> Config {
         // panics here are fine as those are only ever constructed at the initial setup
         assert!(
-            !self.0.validator_api_urls.is_empty(),
+            !self.0.validator_api.urls().is_empty(),
             "at least one validator endpoint must be provided",
         );
         self.0
@@ -159,34 +179,32 @@ impl Default for ConfigBuilder {
             tested_nodes_batch_size: DEFAULT_BATCH_SIZE,
             testing_interval: DEFAULT_TESTING_INTERVAL,
             retry_timeout: DEFAULT_RETRY_TIMEOUT,
-            validator_api_urls: vec![],
+            validator_api: ValidatorApiEndpoints::default(),
+            history_length: DEFAULT_HISTORY_LENGTH,
+            history_file: None,
         })
     }
 }
 
 pub struct VerlocMeasurer {
     config: Config,
+    identity: Arc<identity::KeyPair>,
     packet_sender: Arc<PacketSender>,
     packet_listener: Arc<PacketListener>,
     shutdown_listener: ShutdownListener,
 
     currently_used_api: usize,
-
-    // Note: this client is only fine here as it does not maintain constant connection to the validator.
-    // It only does bunch of REST queries. If we update it at some point to a more sophisticated (maybe signing) client,
-    // then it definitely cannot be constructed here and probably will need to be passed from outside,
-    // as mixnodes/gateways would already be using an instance of said client.
-    validator_client: validator_client::ApiClient,
     results: AtomicVerlocResult,
+    history: VerlocHistory,
 }
 
 impl VerlocMeasurer {
     pub fn new(
-        mut config: Config,
+        config: Config,
         identity: Arc<identity::KeyPair>,
         shutdown_listener: ShutdownListener,
     ) -> Self {
-        config.validator_api_urls.shuffle(&mut thread_rng());
+        let currently_used_api = thread_rng().gen_range(0..config.validator_api.urls().len());
 
         VerlocMeasurer {
             packet_sender: Arc::new(PacketSender::new(
@@ -203,31 +221,48 @@ impl VerlocMeasurer {
                 shutdown_listener.clone(),
             )),
             shutdown_listener,
-            currently_used_api: 0,
-            validator_client: validator_client::ApiClient::new(
-                config.validator_api_urls[0].clone(),
-            ),
+            currently_used_api,
+            history: VerlocHistory::load(config.history_file.clone(), config.history_length),
+            identity,
             config,
             results: AtomicVerlocResult::new(),
         }
     }
 
+    // Note: the client is only fine to be constructed here as it does not maintain constant connection to the validator.
+    // It only does bunch of REST queries. If we update it at some point to a more sophisticated (maybe signing) client,
+    // then it definitely cannot be constructed here and probably will need to be passed from outside,
+    // as mixnodes/gateways would already be using an instance of said client.
+    fn current_validator_client(&self) -> validator_client::ApiClient {
+        // the list of the apis might have changed since we last used it
+        let urls = self.config.validator_api.urls();
+        validator_client::ApiClient::new(urls[self.currently_used_api % urls.len()].clone())
+    }
+
     fn use_next_validator_api(&mut self) {
-        if self.config.validator_api_urls.len() == 1 {
+        let available_apis = self.config.validator_api.urls().len();
+        if available_apis == 1 {
             warn!("There's only a single validator API available - it won't be possible to use a different one");
             return;
         }
 
-        self.currently_used_api =
-            (self.currently_used_api + 1) % self.config.validator_api_urls.len();
-        self.validator_client
-            .change_validator_api(self.config.validator_api_urls[self.currently_used_api].clone())
+        self.currently_used_api = (self.currently_used_api + 1) % available_apis;
     }
 
     pub fn get_verloc_results_pointer(&self) -> AtomicVerlocResult {
         self.results.clone_data_pointer()
     }
 
+    pub fn get_verloc_history_pointer(&self) -> VerlocHistory {
+        self.history.clone_data_pointer()
+    }
+
+    async fn store_signed_results(&self) {
+        let result = self.results.clone_data().await;
+        let report = SignedVerlocReport::new(&self.identity, &result);
+        self.history.append(report, result).await;
+    }
+
     fn start_listening(&self) -> JoinHandle<()> {
         let packet_listener = Arc::clone(&self.packet_listener);
         tokio::spawn(packet_listener.run())
@@ -301,7 +336,7 @@ impl VerlocMeasurer {
             info!("Starting verloc measurements");
             // TODO: should we also measure gateways?
 
-            let all_mixes = match self.validator_client.get_cached_mixnodes().await {
+            let all_mixes = match self.current_validator_client().get_cached_mixnodes().await {
                 Ok(nodes) => nodes,
                 Err(err) => {
                     error!(
@@ -356,6 +391,7 @@ impl VerlocMeasurer {
 
             // write current time to "run finished" field
             self.results.finish_measurements().await;
+            self.store_signed_results().await;
 
             info!(
                 "Finished performing verloc measurements. The next one will happen in {:?}",
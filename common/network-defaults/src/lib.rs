@@ -276,6 +276,12 @@ impl ValidatorDetails {
     }
 }
 
+/// Human readable part of the checksummed client addresses of the network configured
+/// in the environment, or of the mainnet if none is configured.
+pub fn recipient_hrp() -> String {
+    var(var_names::RECIPIENT_HRP).unwrap_or_else(|_| mainnet::RECIPIENT_HRP.to_owned())
+}
+
 pub fn setup_env(config_env_file: Option<PathBuf>) {
     match std::env::var(var_names::CONFIGURED) {
         // if the configuration is not already set in the env vars
@@ -5,6 +5,8 @@ use crate::var_names;
 use crate::{DenomDetails, ValidatorDetails};
 
 pub(crate) const BECH32_PREFIX: &str = "n";
+/// Human readable part of the checksummed addresses of the clients.
+pub const RECIPIENT_HRP: &str = "nym";
 
 pub const MIX_DENOM: DenomDetails = DenomDetails::new("unym", "nym", 6);
 pub const STAKE_DENOM: DenomDetails = DenomDetails::new("unyx", "nyx", 6);
@@ -59,6 +61,7 @@ pub fn read_var_if_not_default(var: &str) -> Option<String> {
 pub fn export_to_env() {
     set_var_to_default(var_names::CONFIGURED, "true");
     set_var_to_default(var_names::BECH32_PREFIX, BECH32_PREFIX);
+    set_var_to_default(var_names::RECIPIENT_HRP, RECIPIENT_HRP);
     set_var_to_default(var_names::MIX_DENOM, MIX_DENOM.base);
     set_var_to_default(var_names::MIX_DENOM_DISPLAY, MIX_DENOM.display);
     set_var_to_default(var_names::STAKE_DENOM, STAKE_DENOM.base);
@@ -99,6 +102,7 @@ pub fn export_to_env() {
 pub fn export_to_env_if_not_set() {
     set_var_conditionally_to_default(var_names::CONFIGURED, "true");
     set_var_conditionally_to_default(var_names::BECH32_PREFIX, BECH32_PREFIX);
+    set_var_conditionally_to_default(var_names::RECIPIENT_HRP, RECIPIENT_HRP);
     set_var_conditionally_to_default(var_names::MIX_DENOM, MIX_DENOM.base);
     set_var_conditionally_to_default(var_names::MIX_DENOM_DISPLAY, MIX_DENOM.display);
     set_var_conditionally_to_default(var_names::STAKE_DENOM, STAKE_DENOM.base);
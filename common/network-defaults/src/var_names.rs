@@ -5,6 +5,7 @@
 pub const CONFIGURED: &str = "CONFIGURED";
 
 pub const BECH32_PREFIX: &str = "BECH32_PREFIX";
+pub const RECIPIENT_HRP: &str = "RECIPIENT_HRP";
 pub const MIX_DENOM: &str = "MIX_DENOM";
 pub const MIX_DENOM_DISPLAY: &str = "MIX_DENOM_DISPLAY";
 pub const STAKE_DENOM: &str = "STAKE_DENOM";
@@ -107,11 +107,14 @@ impl Theta {
         Theta::try_from(bytes)
     }
 
-    pub fn blinded_serial_number_bs58(&self) -> String {
-        let blinded_serial_nuumber = BlindedSerialNumber {
+    pub fn get_blinded_serial_number(&self) -> BlindedSerialNumber {
+        BlindedSerialNumber {
             inner: self.blinded_serial_number,
-        };
-        blinded_serial_nuumber.to_bs58()
+        }
+    }
+
+    pub fn blinded_serial_number_bs58(&self) -> String {
+        self.get_blinded_serial_number().to_bs58()
     }
 }
 
@@ -0,0 +1,56 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use nymsphinx_types::delays::{self, Delay};
+use rand::Rng;
+use std::time::Duration;
+
+/// Distribution from which the delays of SURB-acks at each mix node are drawn. Each variant
+/// is parameterised by the average delay at a single hop.
+///
+/// Note that there's deliberately no distribution producing fixed delays, as it would make
+/// the acks trivially distinguishable from all the other packets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AckDelayDistribution {
+    /// Exponentially distributed delays, i.e. the same distribution as used by the real
+    /// and the cover packets.
+    Exponential(Duration),
+
+    /// Delays uniformly distributed between zero and twice the average. Unlike the exponential
+    /// distribution it has no long tail, hence the acks can't get arbitrarily delayed.
+    Uniform(Duration),
+}
+
+impl AckDelayDistribution {
+    /// Generates delays for the specified number of hops.
+    pub fn generate_delays<R: Rng>(&self, rng: &mut R, number: usize) -> Vec<Delay> {
+        match *self {
+            AckDelayDistribution::Exponential(average) => {
+                delays::generate_from_average_duration(number, average)
+            }
+            AckDelayDistribution::Uniform(average) => {
+                let max = 2 * average.as_nanos() as u64;
+                (0..number)
+                    .map(|_| Delay::new_from_nanos(rng.gen_range(0, max + 1)))
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn uniform_distribution_respects_its_bounds() {
+        let average = Duration::from_millis(50);
+
+        let uniform = AckDelayDistribution::Uniform(average).generate_delays(&mut OsRng, 1000);
+        assert_eq!(uniform.len(), 1000);
+        assert!(uniform
+            .iter()
+            .all(|delay| delay.to_duration() <= 2 * average));
+    }
+}
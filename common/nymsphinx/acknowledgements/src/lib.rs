@@ -1,8 +1,10 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod delay_distribution;
 pub mod identifier;
 pub mod key;
 pub mod surb_ack;
 
+pub use delay_distribution::AckDelayDistribution;
 pub use key::AckKey;
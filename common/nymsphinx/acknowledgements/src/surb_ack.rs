@@ -2,27 +2,80 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::identifier::prepare_identifier;
-use crate::AckKey;
+use crate::{AckDelayDistribution, AckKey};
 use nymsphinx_addressing::clients::Recipient;
 use nymsphinx_addressing::nodes::{NymNodeRoutingAddress, MAX_NODE_ADDRESS_UNPADDED_LEN};
 use nymsphinx_params::packet_sizes::PacketSize;
-use nymsphinx_params::DEFAULT_NUM_MIX_HOPS;
+use nymsphinx_params::{PacketMode, DEFAULT_NUM_MIX_HOPS};
 use nymsphinx_types::builder::SphinxPacketBuilder;
-use nymsphinx_types::{
-    delays::{self, Delay},
-    SphinxPacket,
-};
+use nymsphinx_types::outfox::{OutfoxPacket, OutfoxSurb};
+use nymsphinx_types::{delays::Delay, NymPacket, SURBMaterial, SURB};
 use rand::{CryptoRng, RngCore};
 use std::convert::TryFrom;
-use std::time;
 use topology::{NymTopology, NymTopologyError};
 
 pub struct SurbAck {
-    surb_ack_packet: SphinxPacket,
+    surb_ack_packet: NymPacket,
     first_hop_address: NymNodeRoutingAddress,
     expected_total_delay: Delay,
 }
 
+/// Route, delays and header key material of a SURB-ack computed ahead of time, so that turning
+/// it into an actual [`SurbAck`] only requires encrypting the fragment identifier.
+pub struct PrecomputedSurbAck {
+    header: PrecomputedHeader,
+    expected_total_delay: Delay,
+}
+
+enum PrecomputedHeader {
+    Sphinx(SURB),
+    Outfox(OutfoxSurb),
+}
+
+impl PrecomputedSurbAck {
+    /// Precomputes the SURB-ack in the format corresponding to the provided packet mode, which
+    /// must be the same as of the packet it is going to be attached to.
+    pub fn new<R>(
+        rng: &mut R,
+        recipient: &Recipient,
+        delay_distribution: AckDelayDistribution,
+        topology: &NymTopology,
+        packet_mode: PacketMode,
+    ) -> Result<Self, NymTopologyError>
+    where
+        R: RngCore + CryptoRng,
+    {
+        let route =
+            topology.random_route_to_gateway(rng, DEFAULT_NUM_MIX_HOPS, recipient.gateway())?;
+        let delays = delay_distribution.generate_delays(rng, route.len());
+        let destination = recipient.as_sphinx_destination();
+
+        // in our case, the last hop is a gateway that does NOT do any delays
+        let expected_total_delay = delays.iter().take(delays.len() - 1).sum();
+
+        // this can't fail as we know we have a valid route to gateway and have correct number of delays
+        let header = if packet_mode.is_outfox() {
+            PrecomputedHeader::Outfox(OutfoxSurb::new(&route, &destination, &delays).unwrap())
+        } else {
+            let surb_material = SURBMaterial::new(route, delays, destination);
+            PrecomputedHeader::Sphinx(surb_material.construct_SURB().unwrap())
+        };
+
+        Ok(PrecomputedSurbAck {
+            header,
+            expected_total_delay,
+        })
+    }
+
+    /// Mode of the packet produced by this SURB-ack.
+    pub fn packet_mode(&self) -> PacketMode {
+        match self.header {
+            PrecomputedHeader::Sphinx(..) => PacketMode::Mix,
+            PrecomputedHeader::Outfox(..) => PacketMode::Outfox,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum SurbAckRecoveryError {
     InvalidPacketSize,
@@ -31,28 +84,44 @@ pub enum SurbAckRecoveryError {
 }
 
 impl SurbAck {
+    /// Constructs the SURB-ack in the format corresponding to the provided packet mode, which
+    /// must be the same as of the packet it is going to be attached to.
     pub fn construct<R>(
         rng: &mut R,
         recipient: &Recipient,
         ack_key: &AckKey,
         marshaled_fragment_id: [u8; 5],
-        average_delay: time::Duration,
+        delay_distribution: AckDelayDistribution,
         topology: &NymTopology,
+        packet_mode: PacketMode,
     ) -> Result<Self, NymTopologyError>
     where
         R: RngCore + CryptoRng,
     {
         let route =
             topology.random_route_to_gateway(rng, DEFAULT_NUM_MIX_HOPS, recipient.gateway())?;
-        let delays = delays::generate_from_average_duration(route.len(), average_delay);
+        let delays = delay_distribution.generate_delays(rng, route.len());
         let destination = recipient.as_sphinx_destination();
 
         let surb_ack_payload = prepare_identifier(rng, ack_key, marshaled_fragment_id);
 
-        let surb_ack_packet = SphinxPacketBuilder::new()
-            .with_payload_size(PacketSize::AckPacket.payload_size())
-            .build_packet(surb_ack_payload, &route, &destination, &delays)
-            .unwrap();
+        let surb_ack_packet = if packet_mode.is_outfox() {
+            OutfoxPacket::new(
+                &surb_ack_payload,
+                PacketSize::AckPacket.payload_size(),
+                &route,
+                &destination,
+                &delays,
+            )
+            .unwrap()
+            .into()
+        } else {
+            SphinxPacketBuilder::new()
+                .with_payload_size(PacketSize::AckPacket.payload_size())
+                .build_packet(surb_ack_payload, &route, &destination, &delays)
+                .unwrap()
+                .into()
+        };
 
         // in our case, the last hop is a gateway that does NOT do any delays
         let expected_total_delay = delays.iter().take(delays.len() - 1).sum();
@@ -66,6 +135,38 @@ impl SurbAck {
         })
     }
 
+    /// Finishes construction of the SURB-ack whose route and header got computed beforehand.
+    pub fn from_precomputed<R>(
+        rng: &mut R,
+        precomputed: PrecomputedSurbAck,
+        ack_key: &AckKey,
+        marshaled_fragment_id: [u8; 5],
+    ) -> Self
+    where
+        R: RngCore + CryptoRng,
+    {
+        let surb_ack_payload = prepare_identifier(rng, ack_key, marshaled_fragment_id);
+
+        // the identifier is always much shorter than the payload of an ack packet
+        let payload_size = PacketSize::AckPacket.payload_size();
+        let (surb_ack_packet, first_hop) = match precomputed.header {
+            PrecomputedHeader::Sphinx(surb) => surb
+                .use_surb(&surb_ack_payload, payload_size)
+                .map(|(packet, first_hop)| (packet.into(), first_hop))
+                .unwrap(),
+            PrecomputedHeader::Outfox(surb) => surb
+                .use_surb(&surb_ack_payload, payload_size)
+                .map(|(packet, first_hop)| (packet.into(), first_hop))
+                .unwrap(),
+        };
+
+        SurbAck {
+            surb_ack_packet,
+            first_hop_address: NymNodeRoutingAddress::try_from(first_hop).unwrap(),
+            expected_total_delay: precomputed.expected_total_delay,
+        }
+    }
+
     pub fn len() -> usize {
         // TODO: this will be variable once/if we decide to introduce optimization described
         // in common/nymsphinx/chunking/src/lib.rs:available_plaintext_size()
@@ -84,9 +185,11 @@ impl SurbAck {
     }
 
     // partial reciprocal of `prepare_for_sending` performed by the gateway
+    // note: the SURB-ack is always in the same format as the packet it was attached to
     pub fn try_recover_first_hop_packet(
         b: &[u8],
-    ) -> Result<(NymNodeRoutingAddress, SphinxPacket), SurbAckRecoveryError> {
+        packet_mode: PacketMode,
+    ) -> Result<(NymNodeRoutingAddress, NymPacket), SurbAckRecoveryError> {
         if b.len() != Self::len() {
             Err(SurbAckRecoveryError::InvalidPacketSize)
         } else {
@@ -98,7 +201,12 @@ impl SurbAck {
             // TODO: this will be variable once/if we decide to introduce optimization described
             // in common/nymsphinx/chunking/src/lib.rs:available_plaintext_size()
             let address_offset = MAX_NODE_ADDRESS_UNPADDED_LEN;
-            let packet = match SphinxPacket::from_bytes(&b[address_offset..]) {
+            let packet = if packet_mode.is_outfox() {
+                NymPacket::outfox_from_bytes(&b[address_offset..])
+            } else {
+                NymPacket::sphinx_from_bytes(&b[address_offset..])
+            };
+            let packet = match packet {
                 Ok(packet) => packet,
                 Err(_) => return Err(SurbAckRecoveryError::InvalidSphinxPacket),
             };
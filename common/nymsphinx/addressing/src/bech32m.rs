@@ -0,0 +1,202 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Minimal implementation of the bech32m encoding as defined in BIP-350.
+//!
+//! Unlike the original specification, the length of the encoded strings is not limited
+//! to 90 characters, as the addresses of the clients are much longer than that.
+//! Note that the checksum only guarantees detecting any error affecting up to 4 characters
+//! in strings of up to 89 characters. The addresses of the clients are over 160 characters
+//! long, so there's no such guarantee for them and any error is only detected with
+//! the probability of roughly 1 - 1/10^9.
+
+use std::fmt::{self, Display, Formatter};
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const SEPARATOR: char = '1';
+const CHECKSUM_LENGTH: usize = 6;
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+const GENERATOR: [u32; 5] = [
+    0x3b6a_57b2,
+    0x2650_8e6d,
+    0x1ea1_19fa,
+    0x3d42_33dd,
+    0x2a14_62b3,
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Bech32mError {
+    MixedCase,
+    MissingSeparator,
+    InvalidHrp,
+    InvalidCharacter(char),
+    InvalidChecksum,
+    InvalidPadding,
+}
+
+impl Display for Bech32mError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Bech32mError::MixedCase => write!(f, "the string contains mixed-case characters"),
+            Bech32mError::MissingSeparator => write!(f, "the separator '1' is missing"),
+            Bech32mError::InvalidHrp => write!(f, "the human readable part is invalid"),
+            Bech32mError::InvalidCharacter(c) => write!(f, "'{}' is not a valid character", c),
+            Bech32mError::InvalidChecksum => write!(f, "the checksum is invalid"),
+            Bech32mError::InvalidPadding => write!(f, "the data has invalid padding"),
+        }
+    }
+}
+
+impl std::error::Error for Bech32mError {}
+
+fn polymod(values: impl Iterator<Item = u8>) -> u32 {
+    values.fold(1u32, |chk, value| {
+        let top = chk >> 25;
+        GENERATOR
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| (top >> i) & 1 == 1)
+            .fold(((chk & 0x01ff_ffff) << 5) ^ value as u32, |chk, (_, g)| {
+                chk ^ g
+            })
+    })
+}
+
+fn expand_hrp(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+    hrp.bytes()
+        .map(|c| c >> 5)
+        .chain(std::iter::once(0))
+        .chain(hrp.bytes().map(|c| c & 0x1f))
+}
+
+fn is_valid_hrp(hrp: &str) -> bool {
+    !hrp.is_empty() && hrp.bytes().all(|c| (33..=126).contains(&c))
+}
+
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, Bech32mError> {
+    let mut acc = 0u32;
+    let mut bits = 0u32;
+    let max_value = (1u32 << to) - 1;
+    let mut out = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+
+    for value in data {
+        acc = (acc << from) | *value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((acc >> bits) & max_value) as u8);
+        }
+    }
+
+    if pad {
+        if bits > 0 {
+            out.push(((acc << (to - bits)) & max_value) as u8);
+        }
+    } else if bits >= from || (acc << (to - bits)) & max_value != 0 {
+        return Err(Bech32mError::InvalidPadding);
+    }
+    Ok(out)
+}
+
+/// Encodes the data with the provided human readable part, which must consist of
+/// lowercase ascii characters.
+pub fn encode(hrp: &str, data: &[u8]) -> String {
+    debug_assert!(is_valid_hrp(hrp) && hrp.to_lowercase() == hrp);
+
+    // converting to 5-bit groups with padding can't fail
+    let data = convert_bits(data, 8, 5, true).unwrap();
+    let checksum = polymod(
+        expand_hrp(hrp)
+            .chain(data.iter().copied())
+            .chain([0; CHECKSUM_LENGTH]),
+    ) ^ BECH32M_CONST;
+
+    let mut encoded = String::with_capacity(hrp.len() + 1 + data.len() + CHECKSUM_LENGTH);
+    encoded.push_str(hrp);
+    encoded.push(SEPARATOR);
+    for value in data {
+        encoded.push(CHARSET[value as usize] as char);
+    }
+    for i in 0..CHECKSUM_LENGTH {
+        let value = (checksum >> (5 * (CHECKSUM_LENGTH - 1 - i))) & 0x1f;
+        encoded.push(CHARSET[value as usize] as char);
+    }
+    encoded
+}
+
+/// Decodes the string into its (lowercase) human readable part and the underlying data.
+pub fn decode(encoded: &str) -> Result<(String, Vec<u8>), Bech32mError> {
+    if encoded.to_lowercase() != encoded && encoded.to_uppercase() != encoded {
+        return Err(Bech32mError::MixedCase);
+    }
+    let encoded = encoded.to_lowercase();
+
+    let (hrp, data) = encoded
+        .rsplit_once(SEPARATOR)
+        .ok_or(Bech32mError::MissingSeparator)?;
+    if !is_valid_hrp(hrp) {
+        return Err(Bech32mError::InvalidHrp);
+    }
+    if data.len() < CHECKSUM_LENGTH {
+        return Err(Bech32mError::InvalidChecksum);
+    }
+
+    let values = data
+        .chars()
+        .map(|c| {
+            CHARSET
+                .iter()
+                .position(|&charset_c| charset_c as char == c)
+                .map(|value| value as u8)
+                .ok_or(Bech32mError::InvalidCharacter(c))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if polymod(expand_hrp(hrp).chain(values.iter().copied())) != BECH32M_CONST {
+        return Err(Bech32mError::InvalidChecksum);
+    }
+
+    let data = convert_bits(&values[..values.len() - CHECKSUM_LENGTH], 5, 8, false)?;
+    Ok((hrp.to_owned(), data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_bip350_test_vectors_are_accepted() {
+        let vectors = [
+            "A1LQFN3A",
+            "a1lqfn3a",
+            "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+            "split1checkupstagehandshakeupstreamerranterredcaperredlc445v",
+            "?1v759aa",
+        ];
+        for vector in vectors {
+            assert!(decode(vector).is_ok(), "{}", vector);
+        }
+    }
+
+    #[test]
+    fn invalid_bip350_test_vectors_are_rejected() {
+        let vectors = [
+            "qyrz8wqd2c9m",
+            "1qyrz8wqd2c9m",
+            "M1VUXWEZ",
+            "16plkw9",
+            "1p2gdwpf",
+            "A1lqfn3a",
+        ];
+        for vector in vectors {
+            assert!(decode(vector).is_err(), "{}", vector);
+        }
+    }
+
+    #[test]
+    fn decoding_reverses_encoding() {
+        let data: Vec<_> = (0..=255).collect();
+        let encoded = encode("nym", &data);
+        assert_eq!(decode(&encoded).unwrap(), ("nym".to_owned(), data));
+    }
+}
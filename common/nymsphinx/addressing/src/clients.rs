@@ -4,11 +4,13 @@
 // of a helper/utils structure, because before it reaches the gateway
 // it's already destructed).
 
+use crate::bech32m::{self, Bech32mError};
 use crate::nodes::{NodeIdentity, NODE_IDENTITY_SIZE};
 use crypto::asymmetric::{encryption, identity};
 use nymsphinx_types::Destination;
 use serde::de::{Error as SerdeError, Unexpected, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryInto;
 use std::fmt::{self, Formatter};
 
 // Not entirely sure whether this is the correct place for those, but let's see how it's going
@@ -19,12 +21,18 @@ const CLIENT_ENCRYPTION_KEY_SIZE: usize = encryption::PUBLIC_KEY_SIZE;
 pub type ClientIdentity = identity::PublicKey;
 const CLIENT_IDENTITY_SIZE: usize = identity::PUBLIC_KEY_LENGTH;
 
+/// Version of the checksummed address format, put in front of the bytes of the recipient.
+const RECIPIENT_ENCODING_VERSION: u8 = 0;
+
 #[derive(Debug)]
 pub enum RecipientFormattingError {
     MalformedRecipientError,
     MalformedIdentityError(identity::Ed25519RecoveryError),
     MalformedEncryptionKeyError(encryption::KeyRecoveryError),
     MalformedGatewayError(identity::Ed25519RecoveryError),
+    MalformedBech32mError(Bech32mError),
+    UnexpectedHrp { expected: String, received: String },
+    UnsupportedEncodingVersion(u8),
 }
 
 impl fmt::Display for RecipientFormattingError {
@@ -44,6 +52,19 @@ impl fmt::Display for RecipientFormattingError {
                 "recipient gateway's identity key is malformed: {}",
                 id_err
             ),
+            RecipientFormattingError::MalformedBech32mError(err) => {
+                write!(f, "recipient's checksummed address is malformed: {}", err)
+            }
+            RecipientFormattingError::UnexpectedHrp { expected, received } => write!(
+                f,
+                "recipient's address is meant for the '{}' network, while '{}' was expected",
+                received, expected
+            ),
+            RecipientFormattingError::UnsupportedEncodingVersion(version) => write!(
+                f,
+                "recipient's address uses unsupported format version {}",
+                version
+            ),
         }
     }
 }
@@ -57,6 +78,12 @@ impl From<encryption::KeyRecoveryError> for RecipientFormattingError {
     }
 }
 
+impl From<Bech32mError> for RecipientFormattingError {
+    fn from(err: Bech32mError) -> Self {
+        RecipientFormattingError::MalformedBech32mError(err)
+    }
+}
+
 // TODO: this should a different home... somewhere, but where?
 #[derive(Clone, Copy, Debug)]
 pub struct Recipient {
@@ -224,6 +251,66 @@ impl Recipient {
             gateway,
         })
     }
+
+    /// Encodes the recipient as `hrp || 1 || bech32m(version || bytes)`, which, unlike the base58
+    /// representation, is protected by a checksum and identifies the network it's meant for.
+    pub fn to_bech32m_string(&self, hrp: &str) -> String {
+        let mut data = Vec::with_capacity(1 + Self::LEN);
+        data.push(RECIPIENT_ENCODING_VERSION);
+        data.extend_from_slice(&self.to_bytes());
+        bech32m::encode(hrp, &data)
+    }
+
+    pub fn try_from_bech32m_string(
+        address: &str,
+        expected_hrp: &str,
+    ) -> Result<Self, RecipientFormattingError> {
+        let (hrp, data) = bech32m::decode(address)?;
+        if hrp != expected_hrp {
+            return Err(RecipientFormattingError::UnexpectedHrp {
+                expected: expected_hrp.to_owned(),
+                received: hrp,
+            });
+        }
+
+        match data.split_first() {
+            Some((&RECIPIENT_ENCODING_VERSION, bytes)) => {
+                let bytes = bytes
+                    .try_into()
+                    .map_err(|_| RecipientFormattingError::MalformedRecipientError)?;
+                Self::try_from_bytes(bytes)
+            }
+            Some((&version, _)) => Err(RecipientFormattingError::UnsupportedEncodingVersion(
+                version,
+            )),
+            None => Err(RecipientFormattingError::MalformedRecipientError),
+        }
+    }
+
+    /// Attempts to parse the recipient from either of the supported formats, i.e. the base58
+    /// `identity.encryption@gateway` or the checksummed bech32m address of the network
+    /// identified by the provided human readable part.
+    pub fn try_from_string<S: Into<String>>(
+        full_address: S,
+        expected_hrp: &str,
+    ) -> Result<Self, RecipientFormattingError> {
+        let string_address = full_address.into();
+        if string_address.contains('@') {
+            Self::try_from_base58_string(string_address)
+        } else {
+            Self::try_from_bech32m_string(&string_address, expected_hrp)
+        }
+    }
+}
+
+/// Converts the base58 address of a client into its checksummed equivalent.
+pub fn base58_to_bech32m(address: &str, hrp: &str) -> Result<String, RecipientFormattingError> {
+    Ok(Recipient::try_from_base58_string(address)?.to_bech32m_string(hrp))
+}
+
+/// Converts the checksummed address of a client into its base58 equivalent.
+pub fn bech32m_to_base58(address: &str, hrp: &str) -> Result<String, RecipientFormattingError> {
+    Ok(Recipient::try_from_bech32m_string(address, hrp)?.to_string())
 }
 
 // ADDRESS . ENCRYPTION @ GATEWAY_ID
@@ -275,6 +362,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn bech32m_conversion_works() {
+        let mut rng = rand::thread_rng();
+
+        let recipient = Recipient::new(
+            *identity::KeyPair::new(&mut rng).public_key(),
+            *encryption::KeyPair::new(&mut rng).public_key(),
+            *identity::KeyPair::new(&mut rng).public_key(),
+        );
+
+        let checksummed = recipient.to_bech32m_string("nym");
+        let recovered_recipient = Recipient::try_from_string(&checksummed, "nym").unwrap();
+        assert_eq!(recipient.to_bytes(), recovered_recipient.to_bytes());
+
+        // both formats are interchangeable
+        let base58 = bech32m_to_base58(&checksummed, "nym").unwrap();
+        assert_eq!(base58, recipient.to_string());
+        assert_eq!(base58_to_bech32m(&base58, "nym").unwrap(), checksummed);
+        let recovered_recipient = Recipient::try_from_string(&base58, "nym").unwrap();
+        assert_eq!(recipient.to_bytes(), recovered_recipient.to_bytes());
+
+        // typos are detected
+        let mut typo = checksummed.clone().into_bytes();
+        let i = typo.len() / 2;
+        typo[i] = if typo[i] == b'q' { b'p' } else { b'q' };
+        assert!(Recipient::try_from_string(String::from_utf8(typo).unwrap(), "nym").is_err());
+
+        // as are addresses of other networks
+        let other_network = recipient.to_bech32m_string("nymt");
+        assert!(matches!(
+            Recipient::try_from_string(&other_network, "nym"),
+            Err(RecipientFormattingError::UnexpectedHrp { .. })
+        ));
+        assert!(Recipient::try_from_string(&other_network, "nymt").is_ok());
+    }
+
     #[test]
     fn bytes_conversion_works() {
         let mut rng = rand::thread_rng();
@@ -5,4 +5,4 @@ pub mod encryption_key;
 pub mod reply_surb;
 
 pub use encryption_key::{SurbEncryptionKey, SurbEncryptionKeySize};
-pub use reply_surb::{ReplySurb, ReplySurbError};
+pub use reply_surb::{ReplySurb, ReplySurbError, REPLY_SURB_MAX_AGE};
@@ -6,21 +6,33 @@ use crypto::{generic_array::typenum::Unsigned, Digest};
 use nymsphinx_addressing::clients::Recipient;
 use nymsphinx_addressing::nodes::{NymNodeRoutingAddress, MAX_NODE_ADDRESS_UNPADDED_LEN};
 use nymsphinx_params::packet_sizes::PacketSize;
-use nymsphinx_params::{ReplySurbKeyDigestAlgorithm, DEFAULT_NUM_MIX_HOPS};
-use nymsphinx_types::{delays, Error as SphinxError, SURBMaterial, SphinxPacket, SURB};
+use nymsphinx_params::{PacketMode, ReplySurbKeyDigestAlgorithm, DEFAULT_NUM_MIX_HOPS};
+use nymsphinx_types::outfox::{OutfoxError, OutfoxSurb};
+use nymsphinx_types::{delays, Error as SphinxError, NymPacket, SURBMaterial, SURB};
 use rand::{CryptoRng, RngCore};
 use serde::de::{Error as SerdeError, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::convert::TryFrom;
 use std::fmt::{self, Formatter};
 use std::time;
-use topology::{NymTopology, NymTopologyError};
+use topology::{NymTopology, NymTopologyError, SPHINX_KEY_OVERLAP_SECS};
+
+/// Minimum time for which a freshly constructed reply SURB is guaranteed to remain usable.
+///
+/// A SURB is bound to the sphinx keys the nodes on its route announced for the epoch during
+/// which it got constructed. Nodes with key rotation enabled only accept packets encrypted with
+/// such a key until the overlap window at the beginning of the following epoch is over, so a SURB
+/// constructed right before the end of an epoch stays usable only for the duration of that window.
+/// Holders of the SURB can't tell whether the nodes on its route rotate their keys, so they must
+/// not rely on it for any longer than that.
+pub const REPLY_SURB_MAX_AGE: time::Duration = time::Duration::from_secs(SPHINX_KEY_OVERLAP_SECS);
 
 #[derive(Debug)]
 pub enum ReplySurbError {
     UnpaddedMessageError,
     MalformedStringError(bs58::decode::Error),
     RecoveryError(SphinxError),
+    OutfoxRecoveryError(OutfoxError),
     InvalidEncryptionKeyData(SurbEncryptionKeyError),
 }
 
@@ -36,6 +48,13 @@ impl fmt::Display for ReplySurbError {
             ReplySurbError::RecoveryError(sphinx_err) => {
                 write!(f, "failed to recover reply SURB from bytes: {}", sphinx_err)
             }
+            ReplySurbError::OutfoxRecoveryError(outfox_err) => {
+                write!(
+                    f,
+                    "failed to recover outfox reply SURB from bytes: {}",
+                    outfox_err
+                )
+            }
             ReplySurbError::InvalidEncryptionKeyData(surb_key_err) => write!(
                 f,
                 "failed to recover reply SURB encryption key from bytes: {}",
@@ -54,9 +73,15 @@ impl From<SurbEncryptionKeyError> for ReplySurbError {
     }
 }
 
+#[derive(Debug)]
+enum SurbHeader {
+    Sphinx(SURB),
+    Outfox(OutfoxSurb),
+}
+
 #[derive(Debug)]
 pub struct ReplySurb {
-    surb: SURB,
+    surb: SurbHeader,
     encryption_key: SurbEncryptionKey,
 }
 
@@ -107,11 +132,14 @@ impl ReplySurb {
 
     // TODO: should this return `ReplySURBError` for consistency sake
     // or keep `NymTopologyError` because it's the only error it can actually return?
+    /// Constructs the reply SURB producing packets in the format corresponding to the provided
+    /// packet mode.
     pub fn construct<R>(
         rng: &mut R,
         recipient: &Recipient,
         average_delay: time::Duration,
         topology: &NymTopology,
+        packet_mode: PacketMode,
     ) -> Result<Self, NymTopologyError>
     where
         R: RngCore + CryptoRng,
@@ -121,20 +149,30 @@ impl ReplySurb {
         let delays = delays::generate_from_average_duration(route.len(), average_delay);
         let destination = recipient.as_sphinx_destination();
 
-        let surb_material = SURBMaterial::new(route, delays, destination);
-
         // this can't fail as we know we have a valid route to gateway and have correct number of delays
+        let surb = if packet_mode.is_outfox() {
+            SurbHeader::Outfox(OutfoxSurb::new(&route, &destination, &delays).unwrap())
+        } else {
+            let surb_material = SURBMaterial::new(route, delays, destination);
+            SurbHeader::Sphinx(surb_material.construct_SURB().unwrap())
+        };
+
         Ok(ReplySurb {
-            surb: surb_material.construct_SURB().unwrap(),
+            surb,
             encryption_key: SurbEncryptionKey::new(rng),
         })
     }
 
-    /// Returns the expected number of bytes the [`ReplySURB`] will take after serialization.
-    /// Useful for deserialization from a bytes stream.
-    pub fn serialized_len(mix_hops: u8) -> usize {
+    /// Returns the expected number of bytes the [`ReplySURB`] of the specified packet mode
+    /// will take after serialization. Useful for deserialization from a bytes stream.
+    pub fn serialized_len(mix_hops: u8, packet_mode: PacketMode) -> usize {
         use nymsphinx_types::{HEADER_SIZE, NODE_ADDRESS_LENGTH, PAYLOAD_KEY_SIZE};
 
+        if packet_mode.is_outfox() {
+            return SurbEncryptionKeySize::USIZE
+                + OutfoxSurb::serialized_len(1 + mix_hops as usize);
+        }
+
         // the SURB itself consists of SURB_header, first hop address and set of payload keys
         // (note extra 1 for the gateway)
         SurbEncryptionKeySize::USIZE
@@ -143,26 +181,50 @@ impl ReplySurb {
             + (1 + mix_hops as usize) * PAYLOAD_KEY_SIZE
     }
 
+    /// Mode of the packets produced by this reply SURB.
+    pub fn packet_mode(&self) -> PacketMode {
+        match self.surb {
+            SurbHeader::Sphinx(..) => PacketMode::Mix,
+            SurbHeader::Outfox(..) => PacketMode::Outfox,
+        }
+    }
+
     pub fn encryption_key(&self) -> &SurbEncryptionKey {
         &self.encryption_key
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
         // KEY || SURB_BYTES
+        let surb_bytes = match &self.surb {
+            SurbHeader::Sphinx(surb) => surb.to_bytes(),
+            SurbHeader::Outfox(surb) => surb.to_bytes(),
+        };
         self.encryption_key
             .to_bytes()
             .into_iter()
-            .chain(self.surb.to_bytes().into_iter())
+            .chain(surb_bytes.into_iter())
             .collect()
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplySurbError> {
+        if bytes.len() < SurbEncryptionKeySize::USIZE {
+            return Err(SurbEncryptionKeyError::BytesOfInvalidLengthError.into());
+        }
+
         let encryption_key =
             SurbEncryptionKey::try_from_bytes(&bytes[..SurbEncryptionKeySize::USIZE])?;
 
-        let surb = match SURB::from_bytes(&bytes[SurbEncryptionKeySize::USIZE..]) {
-            Err(err) => return Err(ReplySurbError::RecoveryError(err)),
-            Ok(surb) => surb,
+        // outfox SURBs are marked with their first byte and have lengths distinct from
+        // the sphinx ones for any number of hops
+        let surb_bytes = &bytes[SurbEncryptionKeySize::USIZE..];
+        let surb = if OutfoxSurb::is_outfox_surb(surb_bytes) {
+            OutfoxSurb::from_bytes(surb_bytes)
+                .map(SurbHeader::Outfox)
+                .map_err(ReplySurbError::OutfoxRecoveryError)?
+        } else {
+            SURB::from_bytes(surb_bytes)
+                .map(SurbHeader::Sphinx)
+                .map_err(ReplySurbError::RecoveryError)?
         };
 
         Ok(ReplySurb {
@@ -193,7 +255,7 @@ impl ReplySurb {
         self,
         message: &[u8],
         packet_size: Option<PacketSize>,
-    ) -> Result<(SphinxPacket, NymNodeRoutingAddress), ReplySurbError> {
+    ) -> Result<(NymPacket, NymNodeRoutingAddress), ReplySurbError> {
         let packet_size = packet_size.unwrap_or_default();
 
         if message.len() != packet_size.plaintext_size() {
@@ -201,13 +263,37 @@ impl ReplySurb {
         }
 
         // this can realistically only fail on too long messages and we just checked for that
-        let (packet, first_hop) = self
-            .surb
-            .use_surb(message, packet_size.payload_size())
-            .expect("this error indicates inconsistent message length checking - it shouldn't have happened!");
+        let (packet, first_hop) = match self.surb {
+            SurbHeader::Sphinx(surb) => surb
+                .use_surb(message, packet_size.payload_size())
+                .map(|(packet, first_hop)| (packet.into(), first_hop))
+                .ok(),
+            SurbHeader::Outfox(surb) => surb
+                .use_surb(message, packet_size.payload_size())
+                .map(|(packet, first_hop)| (packet.into(), first_hop))
+                .ok(),
+        }
+        .expect("this error indicates inconsistent message length checking - it shouldn't have happened!");
 
         let first_hop_address = NymNodeRoutingAddress::try_from(first_hop).unwrap();
 
         Ok((packet, first_hop_address))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outfox_and_sphinx_surbs_never_have_the_same_length() {
+        for sphinx_hops in 0..=10 {
+            for outfox_hops in 0..=10 {
+                assert_ne!(
+                    ReplySurb::serialized_len(sphinx_hops, PacketMode::Mix),
+                    ReplySurb::serialized_len(outfox_hops, PacketMode::Outfox)
+                );
+            }
+        }
+    }
+}
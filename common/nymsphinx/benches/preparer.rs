@@ -0,0 +1,208 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use crypto::asymmetric::{encryption, identity};
+use mixnet_contract_common::{Layer, MixId};
+use nymsphinx::acknowledgements::{AckDelayDistribution, AckKey};
+use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::chunking::fragment::Fragment;
+use nymsphinx::preparer::MessagePreparer;
+use rand::rngs::OsRng;
+use std::collections::HashMap;
+use std::time::Duration;
+use topology::{gateway, mix, NymTopology};
+
+const MESSAGE_SIZES: [usize; 3] = [16 * 1024, 256 * 1024, 1024 * 1024];
+
+// the mix keys double as the keys of the client, we just need them to be valid
+const CLIENT_ADDRESS: &str = "3ebjp1Fb9hdcS1AR6AZihgeJiMHkB5jjJUsvqNnfQwU7.B3GzG62aXAZNg14RoMCp3BhELNBrySLr2JqrwyfYFzRc@FioFa8nMmPpQnYi7JyojoTuwGLeyNS8BF4ChPr29zUML";
+
+fn mix_node(
+    mix_id: MixId,
+    host: &str,
+    identity_key: &str,
+    sphinx_key: &str,
+    layer: Layer,
+) -> mix::Node {
+    mix::Node {
+        mix_id,
+        owner: format!("foomp{}", mix_id),
+        host: host.parse().unwrap(),
+        mix_host: format!("{}:1789", host).parse().unwrap(),
+        identity_key: identity::PublicKey::from_base58_string(identity_key).unwrap(),
+        sphinx_key: encryption::PublicKey::from_base58_string(sphinx_key).unwrap(),
+        announced_sphinx_keys: Default::default(),
+        layer,
+        version: "0.8.0-dev".to_string(),
+    }
+}
+
+fn topology_fixture() -> NymTopology {
+    let mut mixes = HashMap::new();
+    mixes.insert(
+        1,
+        vec![mix_node(
+            123,
+            "10.20.30.40",
+            "3ebjp1Fb9hdcS1AR6AZihgeJiMHkB5jjJUsvqNnfQwU7",
+            "B3GzG62aXAZNg14RoMCp3BhELNBrySLr2JqrwyfYFzRc",
+            Layer::One,
+        )],
+    );
+    mixes.insert(
+        2,
+        vec![mix_node(
+            234,
+            "11.21.31.41",
+            "D6YaMzLSY7mANtSQRKXsmMZpqgqiVkeiagKM4V4oFPFr",
+            "5Z1VqYwM2xeKxd8H7fJpGWasNiDFijYBAee7MErkZ5QT",
+            Layer::Two,
+        )],
+    );
+    mixes.insert(
+        3,
+        vec![mix_node(
+            456,
+            "12.22.32.42",
+            "GkWDysw4AjESv1KiAiVn7JzzCMJeksxNSXVfr1PpX8wD",
+            "9EyjhCggr2QEA2nakR88YHmXgpy92DWxoe2draDRkYof",
+            Layer::Three,
+        )],
+    );
+
+    NymTopology::new(
+        mixes,
+        vec![gateway::Node {
+            owner: "foomp4".to_string(),
+            stake: 123,
+            location: "unknown".to_string(),
+            host: "1.2.3.4".parse().unwrap(),
+            mix_host: "1.2.3.4:1789".parse().unwrap(),
+            clients_port: 9000,
+            clients_wss_port: None,
+            http_api_port: None,
+            identity_key: identity::PublicKey::from_base58_string(
+                "FioFa8nMmPpQnYi7JyojoTuwGLeyNS8BF4ChPr29zUML",
+            )
+            .unwrap(),
+            sphinx_key: encryption::PublicKey::from_base58_string(
+                "EB42xvMFMD5rUCstE2CDazgQQJ22zLv8SPm1Luxni44c",
+            )
+            .unwrap(),
+            announced_sphinx_keys: Default::default(),
+            version: "0.8.0-dev".to_string(),
+        }],
+    )
+}
+
+fn message_preparer() -> MessagePreparer<OsRng> {
+    let address = Recipient::try_from_base58_string(CLIENT_ADDRESS).unwrap();
+    MessagePreparer::new(
+        OsRng,
+        address,
+        Duration::from_millis(50),
+        AckDelayDistribution::Exponential(Duration::from_millis(50)),
+    )
+}
+
+fn split_message(
+    preparer: &mut MessagePreparer<OsRng>,
+    topology: &NymTopology,
+    message_size: usize,
+) -> Vec<Fragment> {
+    preparer
+        .prepare_and_split_message(vec![42u8; message_size], false, topology)
+        .unwrap()
+        .0
+}
+
+// compares preparing fragments one by one, as done historically, with preparing all of them
+// on the rayon thread pool, with and without SURB-acks computed ahead of time
+fn fragment_preparation(c: &mut Criterion) {
+    let topology = topology_fixture();
+    let ack_key = AckKey::new(&mut OsRng);
+    let recipient = Recipient::try_from_base58_string(CLIENT_ADDRESS).unwrap();
+    let mut preparer = message_preparer();
+
+    let mut group = c.benchmark_group("fragment preparation");
+    group.sample_size(10);
+    for message_size in MESSAGE_SIZES {
+        let fragments = split_message(&mut preparer, &topology, message_size);
+        group.throughput(Throughput::Elements(fragments.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("sequential", message_size),
+            &fragments,
+            |b, fragments| {
+                b.iter_batched(
+                    || fragments.clone(),
+                    |fragments| {
+                        fragments
+                            .into_iter()
+                            .map(|fragment| {
+                                preparer
+                                    .prepare_chunk_for_sending(
+                                        fragment, &topology, &ack_key, &recipient,
+                                    )
+                                    .unwrap()
+                            })
+                            .collect::<Vec<_>>()
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("parallel", message_size),
+            &fragments,
+            |b, fragments| {
+                b.iter_batched(
+                    || fragments.clone(),
+                    |fragments| {
+                        preparer
+                            .prepare_chunks_for_sending(
+                                fragments,
+                                &topology,
+                                &ack_key,
+                                &recipient,
+                                &mut Vec::new(),
+                            )
+                            .unwrap()
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+
+        // the precomputation itself is not measured as it's meant to happen while the client is idle
+        let mut precomputing_preparer = message_preparer();
+        group.bench_with_input(
+            BenchmarkId::new("parallel with precomputed acks", message_size),
+            &fragments,
+            |b, fragments| {
+                b.iter_batched(
+                    || {
+                        let acks = precomputing_preparer
+                            .precompute_surb_acks(fragments.len(), &topology)
+                            .unwrap();
+                        (fragments.clone(), acks)
+                    },
+                    |(fragments, mut acks)| {
+                        preparer
+                            .prepare_chunks_for_sending(
+                                fragments, &topology, &ack_key, &recipient, &mut acks,
+                            )
+                            .unwrap()
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, fragment_preparation);
+criterion_main!(benches);
@@ -4,7 +4,7 @@
 use crypto::shared_key::new_ephemeral_shared_key;
 use crypto::symmetric::stream_cipher;
 use nymsphinx_acknowledgements::surb_ack::SurbAck;
-use nymsphinx_acknowledgements::AckKey;
+use nymsphinx_acknowledgements::{AckDelayDistribution, AckKey};
 use nymsphinx_addressing::clients::Recipient;
 use nymsphinx_addressing::nodes::{NymNodeRoutingAddress, NymNodeRoutingAddressError};
 use nymsphinx_chunking::fragment::COVER_FRAG_ID;
@@ -14,7 +14,8 @@ use nymsphinx_params::{
     PacketEncryptionAlgorithm, PacketHkdfAlgorithm, PacketMode, DEFAULT_NUM_MIX_HOPS,
 };
 use nymsphinx_types::builder::SphinxPacketBuilder;
-use nymsphinx_types::{delays, Error as SphinxError};
+use nymsphinx_types::outfox::OutfoxPacket;
+use nymsphinx_types::{delays, Error as SphinxError, NymPacket};
 use rand::{CryptoRng, RngCore};
 use std::convert::TryFrom;
 use std::time;
@@ -54,7 +55,8 @@ pub fn generate_loop_cover_surb_ack<R>(
     topology: &NymTopology,
     ack_key: &AckKey,
     full_address: &Recipient,
-    average_ack_delay: time::Duration,
+    ack_delay_distribution: AckDelayDistribution,
+    packet_mode: PacketMode,
 ) -> Result<SurbAck, CoverMessageError>
 where
     R: RngCore + CryptoRng,
@@ -64,8 +66,9 @@ where
         full_address,
         ack_key,
         COVER_FRAG_ID.to_bytes(),
-        average_ack_delay,
+        ack_delay_distribution,
         topology,
+        packet_mode,
     )?)
 }
 
@@ -74,17 +77,24 @@ pub fn generate_loop_cover_packet<R>(
     topology: &NymTopology,
     ack_key: &AckKey,
     full_address: &Recipient,
-    average_ack_delay: time::Duration,
+    ack_delay_distribution: AckDelayDistribution,
     average_packet_delay: time::Duration,
     packet_size: PacketSize,
+    packet_mode: PacketMode,
 ) -> Result<MixPacket, CoverMessageError>
 where
     R: RngCore + CryptoRng,
 {
     // we don't care about total ack delay - we will not be retransmitting it anyway
-    let (_, ack_bytes) =
-        generate_loop_cover_surb_ack(rng, topology, ack_key, full_address, average_ack_delay)?
-            .prepare_for_sending();
+    let (_, ack_bytes) = generate_loop_cover_surb_ack(
+        rng,
+        topology,
+        ack_key,
+        full_address,
+        ack_delay_distribution,
+        packet_mode,
+    )?
+    .prepare_for_sending();
 
     // cover message can't be distinguishable from a normal traffic so we have to go through
     // all the effort of key generation, encryption, etc. Note here we are generating shared key
@@ -127,16 +137,30 @@ where
     let delays = delays::generate_from_average_duration(route.len(), average_packet_delay);
     let destination = full_address.as_sphinx_destination();
 
-    // once merged, that's an easy rng injection point for sphinx packets : )
-    let packet = SphinxPacketBuilder::new()
-        .with_payload_size(packet_size.payload_size())
-        .build_packet(packet_payload, &route, &destination, &delays)
-        .unwrap();
+    // cover packets must use the same format as the real ones to remain indistinguishable
+    let packet: NymPacket = if packet_mode.is_outfox() {
+        OutfoxPacket::new(
+            &packet_payload,
+            packet_size.payload_size(),
+            &route,
+            &destination,
+            &delays,
+        )
+        .unwrap()
+        .into()
+    } else {
+        // once merged, that's an easy rng injection point for sphinx packets : )
+        SphinxPacketBuilder::new()
+            .with_payload_size(packet_size.payload_size())
+            .build_packet(packet_payload, &route, &destination, &delays)
+            .unwrap()
+            .into()
+    };
 
     let first_hop_address =
         NymNodeRoutingAddress::try_from(route.first().unwrap().address).unwrap();
 
-    Ok(MixPacket::new(first_hop_address, packet, PacketMode::Mix))
+    Ok(MixPacket::new(first_hop_address, packet, packet_mode))
 }
 
 /// Helper function used to determine if given message represents a loop cover message.
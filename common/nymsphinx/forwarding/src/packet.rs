@@ -3,7 +3,7 @@
 
 use nymsphinx_addressing::nodes::{NymNodeRoutingAddress, NymNodeRoutingAddressError};
 use nymsphinx_params::{PacketMode, PacketSize};
-use nymsphinx_types::SphinxPacket;
+use nymsphinx_types::NymPacket;
 use std::convert::TryFrom;
 use std::fmt::{self, Display, Formatter};
 
@@ -46,19 +46,19 @@ impl From<NymNodeRoutingAddressError> for MixPacketFormattingError {
 
 pub struct MixPacket {
     next_hop: NymNodeRoutingAddress,
-    sphinx_packet: SphinxPacket,
+    packet: NymPacket,
     packet_mode: PacketMode,
 }
 
 impl MixPacket {
     pub fn new(
         next_hop: NymNodeRoutingAddress,
-        sphinx_packet: SphinxPacket,
+        packet: NymPacket,
         packet_mode: PacketMode,
     ) -> Self {
         MixPacket {
             next_hop,
-            sphinx_packet,
+            packet,
             packet_mode,
         }
     }
@@ -67,12 +67,12 @@ impl MixPacket {
         self.next_hop
     }
 
-    pub fn sphinx_packet(&self) -> &SphinxPacket {
-        &self.sphinx_packet
+    pub fn packet(&self) -> &NymPacket {
+        &self.packet
     }
 
-    pub fn into_sphinx_packet(self) -> SphinxPacket {
-        self.sphinx_packet
+    pub fn into_packet(self) -> NymPacket {
+        self.packet
     }
 
     pub fn packet_mode(&self) -> PacketMode {
@@ -80,7 +80,8 @@ impl MixPacket {
     }
 
     // the message is formatted as follows:
-    // PACKET_MODE || FIRST_HOP || SPHINX_PACKET
+    // PACKET_MODE || FIRST_HOP || PACKET
+    // where the format of the packet is determined by the packet mode
     pub fn try_from_bytes(b: &[u8]) -> Result<Self, MixPacketFormattingError> {
         let packet_mode = match PacketMode::try_from(b[0]) {
             Ok(mode) => mode,
@@ -95,14 +96,19 @@ impl MixPacket {
         if PacketSize::get_type(packet_size).is_err() {
             Err(MixPacketFormattingError::InvalidPacketSize(packet_size))
         } else {
-            let sphinx_packet = match SphinxPacket::from_bytes(sphinx_packet_data) {
+            let packet = if packet_mode.is_outfox() {
+                NymPacket::outfox_from_bytes(sphinx_packet_data)
+            } else {
+                NymPacket::sphinx_from_bytes(sphinx_packet_data)
+            };
+            let packet = match packet {
                 Ok(packet) => packet,
                 Err(_) => return Err(MixPacketFormattingError::MalformedSphinxPacket),
             };
 
             Ok(MixPacket {
                 next_hop,
-                sphinx_packet,
+                packet,
                 packet_mode,
             })
         }
@@ -111,7 +117,7 @@ impl MixPacket {
     pub fn into_bytes(self) -> Vec<u8> {
         std::iter::once(self.packet_mode as u8)
             .chain(self.next_hop.as_bytes().into_iter())
-            .chain(self.sphinx_packet.to_bytes().into_iter())
+            .chain(self.packet.to_bytes().into_iter())
             .collect()
     }
 }
@@ -0,0 +1,90 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use nymsphinx_framing::codec::{SphinxCodec, MAX_BATCH_SIZE};
+use nymsphinx_framing::packet::FramedSphinxPacket;
+use nymsphinx_params::{PacketMode, PacketSize};
+use nymsphinx_types::NymPacket;
+use tokio_util::codec::{Decoder, Encoder};
+
+// number of packets put on the connection in every iteration
+const STREAM_LENGTH: usize = 256;
+const BATCH_SIZES: [usize; 3] = [1, 4, MAX_BATCH_SIZE];
+const COMPARED_PACKET_SIZES: [PacketSize; 2] = [PacketSize::AckPacket, PacketSize::RegularPacket];
+
+fn make_packets(packet_size: PacketSize) -> Vec<FramedSphinxPacket> {
+    // the content doesn't matter for the framing as long as the packet has valid length
+    let packet_bytes = vec![42u8; packet_size.size()];
+    (0..STREAM_LENGTH)
+        .map(|_| {
+            let packet = NymPacket::sphinx_from_bytes(&packet_bytes).unwrap();
+            FramedSphinxPacket::new(packet, PacketMode::Mix, false)
+        })
+        .collect()
+}
+
+// batch of size 1 is equivalent to the frame-per-packet behaviour of the older nodes
+fn encode_stream(packets: Vec<FramedSphinxPacket>, batch_size: usize) -> BytesMut {
+    let mut bytes = BytesMut::new();
+    let mut packets = packets.into_iter().peekable();
+    while packets.peek().is_some() {
+        let batch: Vec<_> = packets.by_ref().take(batch_size).collect();
+        SphinxCodec.encode(batch, &mut bytes).unwrap();
+    }
+    bytes
+}
+
+// cost of writing the packets on the sender side
+fn stream_encoding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame encoding");
+    group.throughput(Throughput::Elements(STREAM_LENGTH as u64));
+    for packet_size in COMPARED_PACKET_SIZES {
+        for batch_size in BATCH_SIZES {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{:?}", packet_size), batch_size),
+                &batch_size,
+                |b, &batch_size| {
+                    b.iter_batched(
+                        || make_packets(packet_size),
+                        |packets| encode_stream(packets, batch_size),
+                        BatchSize::SmallInput,
+                    )
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+// cost of reading the packets on the receiving mixnode or gateway before they get processed
+fn stream_decoding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame decoding");
+    group.throughput(Throughput::Elements(STREAM_LENGTH as u64));
+    for packet_size in COMPARED_PACKET_SIZES {
+        for batch_size in BATCH_SIZES {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{:?}", packet_size), batch_size),
+                &batch_size,
+                |b, &batch_size| {
+                    b.iter_batched(
+                        || encode_stream(make_packets(packet_size), batch_size),
+                        |mut bytes| {
+                            let mut decoded = 0;
+                            while SphinxCodec.decode(&mut bytes).unwrap().is_some() {
+                                decoded += 1;
+                            }
+                            assert_eq!(decoded, STREAM_LENGTH)
+                        },
+                        BatchSize::SmallInput,
+                    )
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, stream_encoding, stream_decoding);
+criterion_main!(benches);
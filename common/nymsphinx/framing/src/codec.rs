@@ -2,10 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::packet::{FramedSphinxPacket, Header};
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{BufMut, BytesMut};
 use nymsphinx_params::packet_modes::InvalidPacketMode;
 use nymsphinx_params::packet_sizes::{InvalidPacketSize, PacketSize};
-use nymsphinx_types::SphinxPacket;
+use nymsphinx_types::NymPacket;
 use std::io;
 use tokio_util::codec::{Decoder, Encoder};
 
@@ -14,6 +14,8 @@ pub enum SphinxCodecError {
     InvalidPacketSize,
     InvalidPacketMode,
     MalformedSphinxPacket,
+    MalformedHello,
+    BatchTooLarge,
     IoError(io::Error),
 }
 
@@ -35,6 +37,12 @@ impl From<SphinxCodecError> for io::Error {
             SphinxCodecError::MalformedSphinxPacket => {
                 io::Error::new(io::ErrorKind::InvalidData, "malformed packet")
             }
+            SphinxCodecError::MalformedHello => {
+                io::Error::new(io::ErrorKind::InvalidData, "malformed hello")
+            }
+            SphinxCodecError::BatchTooLarge => {
+                io::Error::new(io::ErrorKind::InvalidInput, "batch too large")
+            }
             SphinxCodecError::IoError(err) => err,
         }
     }
@@ -52,6 +60,9 @@ impl From<InvalidPacketMode> for SphinxCodecError {
     }
 }
 
+/// Maximum number of packets that can be put in a single batch.
+pub const MAX_BATCH_SIZE: usize = 16;
+
 // TODO: in the future it could be extended to have state containing symmetric encryption key
 // so that all data could be encrypted easily (alternatively we could just slap TLS)
 pub struct SphinxCodec;
@@ -66,6 +77,29 @@ impl Encoder<FramedSphinxPacket> for SphinxCodec {
     }
 }
 
+/// Encodes multiple packets at once, i.e. as their consecutive frames, so that they could be
+/// written to the connection (or sealed in a single encrypted link frame) together.
+/// The frames are self-delimiting, so the batch is not distinguishable from the packets being
+/// sent one by one and requires no special handling on the receiving side.
+impl Encoder<Vec<FramedSphinxPacket>> for SphinxCodec {
+    type Error = SphinxCodecError;
+
+    fn encode(
+        &mut self,
+        items: Vec<FramedSphinxPacket>,
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        if items.len() > MAX_BATCH_SIZE {
+            return Err(SphinxCodecError::BatchTooLarge);
+        }
+
+        for item in items {
+            self.encode(item, dst)?;
+        }
+        Ok(())
+    }
+}
+
 impl Decoder for SphinxCodec {
     type Item = FramedSphinxPacket;
     type Error = SphinxCodecError;
@@ -94,20 +128,23 @@ impl Decoder for SphinxCodec {
             return Ok(None);
         }
 
-        // advance buffer past the header - at this point we have enough bytes
-        src.advance(header.size());
-        let sphinx_packet_bytes = src.split_to(sphinx_packet_size);
-        let sphinx_packet = match SphinxPacket::from_bytes(&sphinx_packet_bytes) {
-            Ok(sphinx_packet) => sphinx_packet,
+        // split the frame off the read buffer without copying it and parse the packet
+        // directly out of it
+        let frame = src.split_to(frame_len).freeze();
+        let sphinx_packet_bytes = &frame[header.size()..];
+        let packet = if header.packet_mode.is_outfox() {
+            NymPacket::outfox_from_bytes(sphinx_packet_bytes)
+        } else {
+            NymPacket::sphinx_from_bytes(sphinx_packet_bytes)
+        };
+        let packet = match packet {
+            Ok(packet) => packet,
             // here it could be debatable whether stream is corrupt or not,
             // but let's go with the safer approach and assume it is.
             Err(_) => return Err(SphinxCodecError::MalformedSphinxPacket),
         };
 
-        let nymsphinx_packet = FramedSphinxPacket {
-            header,
-            packet: sphinx_packet,
-        };
+        let nymsphinx_packet = FramedSphinxPacket { header, packet };
 
         // As per docs:
         // Before returning from the function, implementations should ensure that the buffer
@@ -143,13 +180,15 @@ impl Decoder for SphinxCodec {
 #[cfg(test)]
 mod packet_encoding {
     use super::*;
+    use nymsphinx_params::PacketMode;
     use nymsphinx_types::builder::SphinxPacketBuilder;
+    use nymsphinx_types::outfox::OutfoxPacket;
     use nymsphinx_types::{
         crypto, Delay as SphinxDelay, Destination, DestinationAddressBytes, Node, NodeAddressBytes,
         DESTINATION_ADDRESS_LENGTH, IDENTIFIER_LENGTH, NODE_ADDRESS_LENGTH,
     };
 
-    fn make_valid_sphinx_packet(size: PacketSize) -> SphinxPacket {
+    fn make_valid_sphinx_packet(size: PacketSize) -> NymPacket {
         let (_, node1_pk) = crypto::keygen();
         let node1 = Node::new(
             NodeAddressBytes::from_bytes([5u8; NODE_ADDRESS_LENGTH]),
@@ -180,32 +219,69 @@ mod packet_encoding {
             .with_payload_size(size.payload_size())
             .build_packet(b"foomp".to_vec(), &route, &destination, &delays)
             .unwrap()
+            .into()
+    }
+
+    fn make_valid_outfox_packet(size: PacketSize) -> NymPacket {
+        let route: Vec<_> = (0..4u8)
+            .map(|i| {
+                let (_, pub_key) = crypto::keygen();
+                Node::new(
+                    NodeAddressBytes::from_bytes([i; NODE_ADDRESS_LENGTH]),
+                    pub_key,
+                )
+            })
+            .collect();
+        let destination = Destination::new(
+            DestinationAddressBytes::from_bytes([3u8; DESTINATION_ADDRESS_LENGTH]),
+            [4u8; IDENTIFIER_LENGTH],
+        );
+        let delays = vec![SphinxDelay::new_from_nanos(42); route.len()];
+        OutfoxPacket::new(b"foomp", size.payload_size(), &route, &destination, &delays)
+            .unwrap()
+            .into()
     }
 
     #[test]
     fn whole_packet_can_be_decoded_from_a_valid_encoded_instance() {
         let header = Default::default();
-        let sphinx_packet = make_valid_sphinx_packet(Default::default());
-        let sphinx_bytes = sphinx_packet.to_bytes();
+        let packet = make_valid_sphinx_packet(Default::default());
+        let packet_bytes = packet.to_bytes();
 
-        let packet = FramedSphinxPacket {
-            header,
-            packet: sphinx_packet,
-        };
+        let packet = FramedSphinxPacket { header, packet };
 
         let mut bytes = BytesMut::new();
         SphinxCodec.encode(packet, &mut bytes).unwrap();
         let decoded = SphinxCodec.decode(&mut bytes).unwrap().unwrap();
 
         assert_eq!(decoded.header, header);
-        assert_eq!(decoded.packet.to_bytes(), sphinx_bytes)
+        assert_eq!(decoded.packet.to_bytes(), packet_bytes)
+    }
+
+    #[test]
+    fn outfox_packet_can_be_decoded_from_a_valid_encoded_instance() {
+        let header = Header {
+            packet_mode: PacketMode::Outfox,
+            ..Default::default()
+        };
+        let packet = make_valid_outfox_packet(Default::default());
+        let packet_bytes = packet.to_bytes();
+
+        let mut bytes = BytesMut::new();
+        SphinxCodec
+            .encode(FramedSphinxPacket { header, packet }, &mut bytes)
+            .unwrap();
+        let decoded = SphinxCodec.decode(&mut bytes).unwrap().unwrap();
+
+        assert_eq!(decoded.header, header);
+        assert!(decoded.packet.is_outfox());
+        assert_eq!(decoded.packet.to_bytes(), packet_bytes)
     }
 
     #[cfg(test)]
     mod decode_will_allocate_enough_bytes_for_next_call {
         use super::*;
         use nymsphinx_params::packet_version::PacketVersion;
-        use nymsphinx_params::PacketMode;
 
         #[test]
         fn for_empty_bytes() {
@@ -284,10 +360,8 @@ mod packet_encoding {
             let mut bytes = BytesMut::new();
             SphinxCodec.encode(packet, &mut bytes).unwrap();
             assert!(SphinxCodec.decode(&mut bytes).unwrap().is_some());
-            assert_eq!(
-                bytes.capacity(),
-                Header::LEGACY_SIZE + PacketSize::AckPacket.size()
-            );
+            // (the space of the consumed frame might get reused, so there could be even more)
+            assert!(bytes.capacity() >= Header::LEGACY_SIZE + PacketSize::AckPacket.size());
         }
 
         #[test]
@@ -301,10 +375,8 @@ mod packet_encoding {
             let mut bytes = BytesMut::new();
             SphinxCodec.encode(packet, &mut bytes).unwrap();
             assert!(SphinxCodec.decode(&mut bytes).unwrap().is_some());
-            assert_eq!(
-                bytes.capacity(),
-                Header::VERSIONED_SIZE + PacketSize::AckPacket.size()
-            );
+            // (the space of the consumed frame might get reused, so there could be even more)
+            assert!(bytes.capacity() >= Header::VERSIONED_SIZE + PacketSize::AckPacket.size());
         }
 
         #[test]
@@ -389,6 +461,74 @@ mod packet_encoding {
         assert!(SphinxCodec.decode(&mut bytes).unwrap().is_none());
     }
 
+    #[test]
+    fn batched_packets_can_be_decoded_from_a_valid_encoded_instance() {
+        let packets: Vec<_> = (0..3)
+            .map(|_| FramedSphinxPacket {
+                header: Header::default(),
+                packet: make_valid_sphinx_packet(PacketSize::AckPacket),
+            })
+            .collect();
+        let packets_bytes: Vec<_> = packets.iter().map(|p| p.packet.to_bytes()).collect();
+
+        let mut bytes = BytesMut::new();
+        SphinxCodec.encode(packets, &mut bytes).unwrap();
+
+        // the batch might arrive in pieces
+        let mut tmp = bytes.split_off(200);
+        for packet_bytes in packets_bytes {
+            let decoded = loop {
+                if let Some(decoded) = SphinxCodec.decode(&mut bytes).unwrap() {
+                    break decoded;
+                }
+                bytes.put(tmp.split_to(std::cmp::min(200, tmp.len())));
+            };
+            assert_eq!(decoded.packet.to_bytes(), packet_bytes);
+        }
+        assert!(bytes.is_empty());
+        assert!(tmp.is_empty());
+    }
+
+    #[test]
+    fn batch_is_encoded_as_consecutive_frames() {
+        let packets: Vec<_> = (0..3)
+            .map(|_| FramedSphinxPacket {
+                header: Header::default(),
+                packet: make_valid_sphinx_packet(PacketSize::AckPacket),
+            })
+            .collect();
+
+        let mut individual = BytesMut::new();
+        for packet in &packets {
+            let packet = FramedSphinxPacket {
+                header: packet.header,
+                packet: NymPacket::sphinx_from_bytes(&packet.packet.to_bytes()).unwrap(),
+            };
+            SphinxCodec.encode(packet, &mut individual).unwrap();
+        }
+
+        let mut batched = BytesMut::new();
+        SphinxCodec.encode(packets, &mut batched).unwrap();
+        assert_eq!(batched, individual);
+    }
+
+    #[test]
+    fn too_big_batches_are_rejected() {
+        let packets: Vec<_> = (0..=MAX_BATCH_SIZE)
+            .map(|_| FramedSphinxPacket {
+                header: Header::default(),
+                packet: make_valid_sphinx_packet(PacketSize::AckPacket),
+            })
+            .collect();
+
+        let mut bytes = BytesMut::new();
+        assert!(matches!(
+            SphinxCodec.encode(packets, &mut bytes),
+            Err(SphinxCodecError::BatchTooLarge)
+        ));
+        assert!(bytes.is_empty());
+    }
+
     #[test]
     fn can_decode_two_packets_in_separate_calls() {
         let packet1 = FramedSphinxPacket {
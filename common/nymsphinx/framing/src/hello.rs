@@ -0,0 +1,330 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Connection-level hello used by the nodes to discover the packet versions and features
+//! supported by both ends of a connection.
+//!
+//! It's put on the wire as `HELLO_MAGIC || body length (u16) || body`, where the body consists of:
+//! - number of supported packet versions (u8) followed by the versions themselves
+//!   (one byte each, with `0` representing the legacy, unversioned, packets)
+//! - bitmask of the supported packet sizes (u8)
+//! - bitmask of the supported features (u8)
+//!
+//! Any bytes following the known fields of the body are ignored, so that it could be extended
+//! in the future without breaking the existing nodes.
+
+use crate::codec::SphinxCodecError;
+use bytes::{Buf, BufMut, BytesMut};
+use nymsphinx_params::packet_sizes::PacketSize;
+use nymsphinx_params::packet_version::PacketVersion;
+use std::ops::BitOr;
+
+/// First byte of the hello message. It can't be confused with neither legacy packet size,
+/// any of the packet versions nor the link preamble.
+/// Furthermore, the following length byte is always 0, which is not a valid packet size,
+/// so nodes that don't understand the hello are guaranteed to drop the connection.
+pub const HELLO_MAGIC: u8 = 0xFE;
+
+/// The size of the magic byte alongside the body length.
+pub const HELLO_PREFIX_SIZE: usize = 3;
+
+/// Upper bound on the body length so that the peer couldn't make us buffer arbitrary amount of data.
+const MAX_HELLO_BODY_SIZE: usize = 512;
+
+/// Wire representation of `PacketVersion::Legacy`.
+const LEGACY_VERSION_BYTE: u8 = 0;
+
+const ALL_PACKET_SIZES: [PacketSize; 5] = [
+    PacketSize::RegularPacket,
+    PacketSize::AckPacket,
+    PacketSize::ExtendedPacket32,
+    PacketSize::ExtendedPacket8,
+    PacketSize::ExtendedPacket16,
+];
+
+/// Optional features of the connection supported by a node.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Features(u8);
+
+impl Features {
+    /// The node is able to establish encrypted links.
+    pub const LINK_ENCRYPTION: Features = Features(0b0000_0001);
+
+    /// The node is able to process packets in the outfox format.
+    pub const OUTFOX: Features = Features(0b0000_0100);
+
+    pub const fn empty() -> Self {
+        Features(0)
+    }
+
+    pub fn contains(&self, other: Features) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Features supported by both sides.
+    pub fn common(&self, other: Features) -> Self {
+        Features(self.0 & other.0)
+    }
+}
+
+impl BitOr for Features {
+    type Output = Features;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Features(self.0 | rhs.0)
+    }
+}
+
+/// Parameters agreed on by both sides of the connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NegotiatedParameters {
+    /// The highest packet version understood by both sides.
+    pub packet_version: PacketVersion,
+
+    /// Packet sizes understood by both sides.
+    pub packet_sizes: Vec<PacketSize>,
+
+    /// Features supported by both sides.
+    pub features: Features,
+}
+
+impl NegotiatedParameters {
+    pub fn supports_packet_size(&self, packet_size: PacketSize) -> bool {
+        self.packet_sizes.contains(&packet_size)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hello {
+    /// Packet versions the node is able to understand.
+    pub packet_versions: Vec<PacketVersion>,
+
+    /// Packet sizes the node is able to understand.
+    pub packet_sizes: Vec<PacketSize>,
+
+    /// Optional features supported by the node.
+    pub features: Features,
+}
+
+impl Hello {
+    /// Creates the hello advertising all the packet versions and sizes supported by this build
+    /// alongside the provided features.
+    pub fn new(features: Features) -> Self {
+        Hello {
+            packet_versions: vec![PacketVersion::default(), PacketVersion::new_legacy()],
+            packet_sizes: ALL_PACKET_SIZES.to_vec(),
+            features,
+        }
+    }
+
+    /// Picks the best parameters supported by both nodes, i.e. the highest common packet version,
+    /// alongside all the common packet sizes and features. If the nodes have no common
+    /// packet version, `None` is returned.
+    pub fn negotiate(&self, remote: &Hello) -> Option<NegotiatedParameters> {
+        let packet_version = self
+            .packet_versions
+            .iter()
+            .filter(|version| remote.packet_versions.contains(version))
+            // legacy packets are always worse than any of the versioned ones
+            .max_by_key(|version| version.as_u8().map(|version| version as u16 + 1))?;
+
+        let packet_sizes = self
+            .packet_sizes
+            .iter()
+            .filter(|size| remote.packet_sizes.contains(size))
+            .copied()
+            .collect();
+
+        Some(NegotiatedParameters {
+            packet_version: *packet_version,
+            packet_sizes,
+            features: self.features.common(remote.features),
+        })
+    }
+
+    fn encode_body(&self, dst: &mut BytesMut) {
+        // we're never going to support anywhere near 255 versions at once
+        dst.put_u8(self.packet_versions.len() as u8);
+        for version in &self.packet_versions {
+            dst.put_u8(version.as_u8().unwrap_or(LEGACY_VERSION_BYTE));
+        }
+
+        let packet_sizes = self
+            .packet_sizes
+            .iter()
+            .fold(0u8, |mask, size| mask | (1 << *size as u8));
+        dst.put_u8(packet_sizes);
+        dst.put_u8(self.features.0);
+    }
+
+    pub fn encode(&self, dst: &mut BytesMut) {
+        let mut body = BytesMut::new();
+        self.encode_body(&mut body);
+
+        dst.reserve(HELLO_PREFIX_SIZE + body.len());
+        dst.put_u8(HELLO_MAGIC);
+        dst.put_u16(body.len() as u16);
+        dst.put_slice(&body);
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = BytesMut::new();
+        self.encode(&mut bytes);
+        bytes.to_vec()
+    }
+
+    fn decode_body(mut body: &[u8]) -> Result<Self, SphinxCodecError> {
+        if body.is_empty() {
+            return Err(SphinxCodecError::MalformedHello);
+        }
+        let versions = body.get_u8() as usize;
+        // versions followed by the packet sizes and features
+        if body.len() < versions + 2 {
+            return Err(SphinxCodecError::MalformedHello);
+        }
+
+        let packet_versions = (0..versions)
+            .map(|_| match body.get_u8() {
+                LEGACY_VERSION_BYTE => PacketVersion::new_legacy(),
+                version => PacketVersion::new_versioned(version),
+            })
+            .collect();
+
+        let packet_sizes_mask = body.get_u8();
+        let packet_sizes = ALL_PACKET_SIZES
+            .iter()
+            .filter(|size| packet_sizes_mask & (1 << **size as u8) != 0)
+            .copied()
+            .collect();
+
+        Ok(Hello {
+            packet_versions,
+            packet_sizes,
+            features: Features(body.get_u8()),
+        })
+    }
+
+    /// Attempts to decode the hello from the provided bytes. If there are not enough bytes
+    /// for the full message, `None` is returned instead.
+    pub fn decode(src: &mut BytesMut) -> Result<Option<Self>, SphinxCodecError> {
+        if src.len() < HELLO_PREFIX_SIZE {
+            src.reserve(HELLO_PREFIX_SIZE);
+            return Ok(None);
+        }
+
+        if src[0] != HELLO_MAGIC {
+            return Err(SphinxCodecError::MalformedHello);
+        }
+        let body_len = u16::from_be_bytes([src[1], src[2]]) as usize;
+        if body_len > MAX_HELLO_BODY_SIZE {
+            return Err(SphinxCodecError::MalformedHello);
+        }
+
+        if src.len() < HELLO_PREFIX_SIZE + body_len {
+            src.reserve(HELLO_PREFIX_SIZE + body_len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(HELLO_PREFIX_SIZE);
+        let body = src.split_to(body_len);
+        Self::decode_body(&body).map(Some)
+    }
+
+    /// Returns the total length of the hello message, including the magic byte, based on its
+    /// first three bytes.
+    pub fn encoded_len(prefix: [u8; HELLO_PREFIX_SIZE]) -> Result<usize, SphinxCodecError> {
+        if prefix[0] != HELLO_MAGIC {
+            return Err(SphinxCodecError::MalformedHello);
+        }
+        let body_len = u16::from_be_bytes([prefix[1], prefix[2]]) as usize;
+        if body_len > MAX_HELLO_BODY_SIZE {
+            return Err(SphinxCodecError::MalformedHello);
+        }
+        Ok(HELLO_PREFIX_SIZE + body_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hello_can_be_decoded_from_a_valid_encoded_instance() {
+        let hello = Hello::new(Features::LINK_ENCRYPTION | Features::OUTFOX);
+        let mut bytes = BytesMut::new();
+        hello.encode(&mut bytes);
+
+        // partial message
+        let mut partial = BytesMut::from(&bytes[..bytes.len() - 1]);
+        assert!(Hello::decode(&mut partial).unwrap().is_none());
+
+        let prefix = [bytes[0], bytes[1], bytes[2]];
+        assert_eq!(Hello::encoded_len(prefix).unwrap(), bytes.len());
+
+        let decoded = Hello::decode(&mut bytes).unwrap().unwrap();
+        assert_eq!(decoded, hello);
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn unknown_trailing_fields_are_ignored() {
+        let hello = Hello::new(Features::empty());
+        let mut body = BytesMut::new();
+        hello.encode_body(&mut body);
+        body.put_slice(&[1, 2, 3]);
+
+        let mut bytes = BytesMut::new();
+        bytes.put_u8(HELLO_MAGIC);
+        bytes.put_u16(body.len() as u16);
+        bytes.put_slice(&body);
+
+        assert_eq!(Hello::decode(&mut bytes).unwrap().unwrap(), hello);
+    }
+
+    #[test]
+    fn hello_is_never_a_valid_header() {
+        let mut bytes = BytesMut::new();
+        Hello::new(Features::empty()).encode(&mut bytes);
+        assert!(crate::packet::Header::decode(&mut bytes).is_err());
+    }
+
+    #[test]
+    fn highest_common_version_is_negotiated() {
+        let local = Hello {
+            packet_versions: vec![
+                PacketVersion::new_versioned(8),
+                PacketVersion::new_versioned(7),
+                PacketVersion::new_legacy(),
+            ],
+            packet_sizes: ALL_PACKET_SIZES.to_vec(),
+            features: Features::LINK_ENCRYPTION | Features::OUTFOX,
+        };
+
+        let remote = Hello {
+            packet_versions: vec![PacketVersion::new_legacy(), PacketVersion::new_versioned(7)],
+            packet_sizes: vec![PacketSize::RegularPacket, PacketSize::AckPacket],
+            features: Features::LINK_ENCRYPTION,
+        };
+        let negotiated = local.negotiate(&remote).unwrap();
+        assert_eq!(negotiated.packet_version, PacketVersion::new_versioned(7));
+        assert!(negotiated.supports_packet_size(PacketSize::AckPacket));
+        assert!(!negotiated.supports_packet_size(PacketSize::ExtendedPacket32));
+        assert!(negotiated.features.contains(Features::LINK_ENCRYPTION));
+        assert!(!negotiated.features.contains(Features::OUTFOX));
+
+        let legacy_remote = Hello {
+            packet_versions: vec![PacketVersion::new_legacy()],
+            ..remote.clone()
+        };
+        assert_eq!(
+            local.negotiate(&legacy_remote).unwrap().packet_version,
+            PacketVersion::new_legacy()
+        );
+
+        let incompatible_remote = Hello {
+            packet_versions: vec![PacketVersion::new_versioned(9)],
+            ..remote
+        };
+        assert!(local.negotiate(&incompatible_remote).is_none());
+    }
+}
@@ -6,19 +6,21 @@ use bytes::{BufMut, BytesMut};
 use nymsphinx_params::packet_sizes::PacketSize;
 use nymsphinx_params::packet_version::PacketVersion;
 use nymsphinx_params::PacketMode;
-use nymsphinx_types::SphinxPacket;
+use nymsphinx_types::NymPacket;
 use std::convert::TryFrom;
 
 pub struct FramedSphinxPacket {
     /// Contains any metadata helping receiver to handle the underlying packet.
     pub(crate) header: Header,
 
-    /// The actual SphinxPacket being sent.
-    pub(crate) packet: SphinxPacket,
+    /// The actual packet being sent, in the format indicated by the packet mode.
+    pub(crate) packet: NymPacket,
 }
 
 impl FramedSphinxPacket {
-    pub fn new(packet: SphinxPacket, packet_mode: PacketMode, use_legacy_version: bool) -> Self {
+    pub fn new(packet: NymPacket, packet_mode: PacketMode, use_legacy_version: bool) -> Self {
+        debug_assert_eq!(packet.is_outfox(), packet_mode.is_outfox());
+
         // If this fails somebody is using the library in a super incorrect way, because they
         // already managed to somehow create a sphinx packet
         let packet_size = PacketSize::get_type(packet.len()).unwrap();
@@ -33,6 +35,16 @@ impl FramedSphinxPacket {
         }
     }
 
+    pub fn packet_version(&self) -> PacketVersion {
+        self.header.packet_version
+    }
+
+    /// Changes the wire format version the packet is going to be sent with, for example
+    /// to the one negotiated with the receiver.
+    pub fn set_packet_version(&mut self, packet_version: PacketVersion) {
+        self.header.packet_version = packet_version
+    }
+
     pub fn packet_size(&self) -> PacketSize {
         self.header.packet_size
     }
@@ -41,7 +53,7 @@ impl FramedSphinxPacket {
         self.header.packet_mode
     }
 
-    pub fn into_inner(self) -> SphinxPacket {
+    pub fn into_inner(self) -> NymPacket {
         self.packet
     }
 }
@@ -54,7 +66,7 @@ pub struct Header {
     /// Represents the wire format version used to construct this packet.
     pub(crate) packet_version: PacketVersion,
 
-    /// Represents type and consequently size of the included SphinxPacket.
+    /// Represents type and consequently size of the included packet.
     pub(crate) packet_size: PacketSize,
 
     /// Represents whether this packet is sent in a `vpn_mode` meaning it should not get delayed
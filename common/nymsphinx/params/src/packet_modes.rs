@@ -1,6 +1,7 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use nymsphinx_types::NymPacket;
 use std::convert::TryFrom;
 
 #[derive(Debug)]
@@ -16,6 +17,10 @@ pub enum PacketMode {
     /// Represents a VPN packet that should not be delayed and ideally cached pre-computed keys
     /// should be used for unwrapping data. Note that it does not offer the same level of anonymity.
     Vpn = 1,
+
+    /// Represents 'normal' packet, just like `Mix`, that uses the outfox layered format rather than
+    /// sphinx, which is considerably cheaper to process by the mix nodes.
+    Outfox = 2,
 }
 
 impl PacketMode {
@@ -26,6 +31,19 @@ impl PacketMode {
     pub fn is_old_vpn(self) -> bool {
         self == PacketMode::Vpn
     }
+
+    pub fn is_outfox(self) -> bool {
+        self == PacketMode::Outfox
+    }
+
+    /// Mode of the mix (i.e. delayed) packets using the provided format.
+    pub fn for_packet(packet: &NymPacket) -> Self {
+        if packet.is_outfox() {
+            PacketMode::Outfox
+        } else {
+            PacketMode::Mix
+        }
+    }
 }
 
 impl TryFrom<u8> for PacketMode {
@@ -35,6 +53,7 @@ impl TryFrom<u8> for PacketMode {
         match value {
             _ if value == (PacketMode::Mix as u8) => Ok(Self::Mix),
             _ if value == (PacketMode::Vpn as u8) => Ok(Self::Vpn),
+            _ if value == (PacketMode::Outfox as u8) => Ok(Self::Outfox),
             _ => Err(InvalidPacketMode),
         }
     }
@@ -2,12 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::chunking;
+use crate::receiver::OUTFOX_REPLY_SURB_PREFIX;
 use crypto::asymmetric::encryption;
 use crypto::shared_key::new_ephemeral_shared_key;
 use crypto::symmetric::stream_cipher;
 use crypto::Digest;
-use nymsphinx_acknowledgements::surb_ack::SurbAck;
-use nymsphinx_acknowledgements::AckKey;
+use nymsphinx_acknowledgements::surb_ack::{PrecomputedSurbAck, SurbAck};
+use nymsphinx_acknowledgements::{AckDelayDistribution, AckKey};
 use nymsphinx_addressing::clients::Recipient;
 use nymsphinx_addressing::nodes::{NymNodeRoutingAddress, MAX_NODE_ADDRESS_UNPADDED_LEN};
 use nymsphinx_anonymous_replies::encryption_key::SurbEncryptionKey;
@@ -16,16 +17,20 @@ use nymsphinx_chunking::fragment::{Fragment, FragmentIdentifier};
 use nymsphinx_forwarding::packet::MixPacket;
 use nymsphinx_params::packet_sizes::PacketSize;
 use nymsphinx_params::{
-    PacketEncryptionAlgorithm, PacketHkdfAlgorithm, ReplySurbEncryptionAlgorithm,
+    PacketEncryptionAlgorithm, PacketHkdfAlgorithm, PacketMode, ReplySurbEncryptionAlgorithm,
     ReplySurbKeyDigestAlgorithm, DEFAULT_NUM_MIX_HOPS,
 };
 use nymsphinx_types::builder::SphinxPacketBuilder;
-use nymsphinx_types::{delays, Delay};
+use nymsphinx_types::outfox::OutfoxPacket;
+use nymsphinx_types::{delays, Delay, NymPacket};
 use rand::{CryptoRng, Rng};
 use std::convert::TryFrom;
 use std::time::Duration;
 use topology::{NymTopology, NymTopologyError};
 
+#[cfg(not(target_arch = "wasm32"))]
+mod parallel;
+
 /// Represents fully packed and prepared [`Fragment`] that can be sent through the mix network.
 pub struct PreparedFragment {
     /// Indicates the total expected round-trip time, i.e. delay from the sending of this message
@@ -70,12 +75,15 @@ pub struct MessagePreparer<R: CryptoRng + Rng> {
     /// Average delay a data packet is going to get delay at a single mixnode.
     average_packet_delay: Duration,
 
-    /// Average delay an acknowledgement packet is going to get delay at a single mixnode.
-    average_ack_delay: Duration,
+    /// Distribution of the delays an acknowledgement packet is going to get at each mixnode.
+    ack_delay_distribution: AckDelayDistribution,
 
     /// Number of mix hops each packet ('real' message, ack, reply) is expected to take.
     /// Note that it does not include gateway hops.
     num_mix_hops: u8,
+
+    /// Mode of the packets sent out, which determines their layered format.
+    packet_mode: PacketMode,
 }
 
 impl<R> MessagePreparer<R>
@@ -86,15 +94,16 @@ where
         rng: R,
         sender_address: Recipient,
         average_packet_delay: Duration,
-        average_ack_delay: Duration,
+        ack_delay_distribution: AckDelayDistribution,
     ) -> Self {
         MessagePreparer {
             rng,
             packet_size: Default::default(),
             sender_address,
             average_packet_delay,
-            average_ack_delay,
+            ack_delay_distribution,
             num_mix_hops: DEFAULT_NUM_MIX_HOPS,
+            packet_mode: Default::default(),
         }
     }
 
@@ -110,6 +119,13 @@ where
         self
     }
 
+    /// Allows setting non-default mode, and consequently the format, of the packets sent out.
+    /// Note that the same mode is used for the acknowledgements and reply SURBs.
+    pub fn with_packet_mode(mut self, packet_mode: PacketMode) -> Self {
+        self.packet_mode = packet_mode;
+        self
+    }
+
     /// Overwrites existing sender address with the provided value.
     pub fn set_sender_address(&mut self, sender_address: Recipient) {
         self.sender_address = sender_address;
@@ -154,6 +170,8 @@ where
     /// new_message = 0 || message
     /// OR
     /// new_message = 1 || REPLY_KEY || REPLY_SURB || message
+    /// OR, for outfox reply-SURBs
+    /// new_message = 2 || REPLY_KEY || REPLY_SURB || message
     fn optionally_attach_reply_surb(
         &mut self,
         message: Vec<u8>,
@@ -166,12 +184,19 @@ where
                 &self.sender_address,
                 self.average_packet_delay,
                 topology,
+                self.packet_mode,
             )?;
 
+            let surb_prefix = if self.packet_mode.is_outfox() {
+                OUTFOX_REPLY_SURB_PREFIX
+            } else {
+                true as u8
+            };
+
             let reply_key = reply_surb.encryption_key();
             // if there's a reply surb, the message takes form of `1 || REPLY_KEY || REPLY_SURB || MSG`
             Ok((
-                std::iter::once(true as u8)
+                std::iter::once(surb_prefix)
                     .chain(reply_surb.to_bytes().iter().cloned())
                     .chain(message.into_iter())
                     .collect(),
@@ -221,92 +246,46 @@ where
         ack_key: &AckKey,
         packet_recipient: &Recipient,
     ) -> Result<PreparedFragment, NymTopologyError> {
-        // create an ack
-        let (ack_delay, surb_ack_bytes) = self
-            .generate_surb_ack(fragment.fragment_identifier(), topology, ack_key)?
-            .prepare_for_sending();
-
-        // TODO:
-        // TODO:
-        // TODO:
-        // TODO:
-        // TODO: ASK @AP AND @DH WHETHER THOSE KEYS CAN/SHOULD ALSO BE REUSED IN VPN MODE!!
-        // TODO:
-        // TODO:
-        // TODO:
-        // TODO:
-
-        // create keys for 'payload' encryption
-        let (ephemeral_keypair, shared_key) =
-            new_ephemeral_shared_key::<PacketEncryptionAlgorithm, PacketHkdfAlgorithm, _>(
-                &mut self.rng,
-                packet_recipient.encryption_key(),
-            );
-
-        // serialize fragment and encrypt its content
-        let mut chunk_data = fragment.into_bytes();
-
-        let zero_iv = stream_cipher::zero_iv::<PacketEncryptionAlgorithm>();
-        stream_cipher::encrypt_in_place::<PacketEncryptionAlgorithm>(
-            &shared_key,
-            &zero_iv,
-            &mut chunk_data,
-        );
-
-        // combine it together as follows:
-        // SURB_ACK_FIRST_HOP || SURB_ACK_DATA || EPHEMERAL_KEY || CHUNK_DATA
-        // (note: surb_ack_bytes contains SURB_ACK_FIRST_HOP || SURB_ACK_DATA )
-        let packet_payload: Vec<_> = surb_ack_bytes
-            .into_iter()
-            .chain(ephemeral_keypair.public_key().to_bytes().iter().cloned())
-            .chain(chunk_data.into_iter())
-            .collect();
-
-        // generate pseudorandom route for the packet
-        let route = topology.random_route_to_gateway(
+        self.fragment_preparer().prepare(
             &mut self.rng,
-            self.num_mix_hops,
-            packet_recipient.gateway(),
-        )?;
-        let destination = packet_recipient.as_sphinx_destination();
-
-        // including set of delays
-        let delays = delays::generate_from_average_duration(route.len(), self.average_packet_delay);
-
-        // create the actual sphinx packet here. With valid route and correct payload size,
-        // there's absolutely no reason for this call to fail.
-        let sphinx_packet = SphinxPacketBuilder::new()
-            .with_payload_size(self.packet_size.payload_size())
-            .build_packet(packet_payload, &route, &destination, &delays)
-            .unwrap();
-
-        // from the previously constructed route extract the first hop
-        let first_hop_address =
-            NymNodeRoutingAddress::try_from(route.first().unwrap().address).unwrap();
+            fragment,
+            topology,
+            ack_key,
+            packet_recipient,
+            None,
+        )
+    }
 
-        Ok(PreparedFragment {
-            // the round-trip delay is the sum of delays of all hops on the forward route as
-            // well as the total delay of the ack packet.
-            // note that the last hop of the packet is a gateway that does not do any delays
-            total_delay: delays.iter().take(delays.len() - 1).sum::<Delay>() + ack_delay,
-            mix_packet: MixPacket::new(first_hop_address, sphinx_packet, Default::default()),
-        })
+    /// Extracts the parameters needed for preparing individual fragments, so that they could be
+    /// used independently of the rng of this preparer.
+    fn fragment_preparer(&self) -> FragmentPreparer {
+        FragmentPreparer {
+            sender_address: self.sender_address,
+            packet_size: self.packet_size,
+            average_packet_delay: self.average_packet_delay,
+            ack_delay_distribution: self.ack_delay_distribution,
+            num_mix_hops: self.num_mix_hops,
+            packet_mode: self.packet_mode,
+        }
     }
 
-    /// Construct an acknowledgement SURB for the given [`FragmentIdentifier`]
+    /// Construct an acknowledgement SURB for the given [`FragmentIdentifier`] to be attached
+    /// to a packet of the provided mode.
     fn generate_surb_ack(
         &mut self,
         fragment_id: FragmentIdentifier,
         topology: &NymTopology,
         ack_key: &AckKey,
+        packet_mode: PacketMode,
     ) -> Result<SurbAck, NymTopologyError> {
         SurbAck::construct(
             &mut self.rng,
             &self.sender_address,
             ack_key,
             fragment_id.to_bytes(),
-            self.average_ack_delay,
+            self.ack_delay_distribution,
             topology,
+            packet_mode,
         )
     }
 
@@ -356,8 +335,10 @@ where
         // even though it won't be used for retransmission, it must be present so that
         // gateways could not distinguish reply packets from normal messages due to lack of said acks
         // note: the ack delay is irrelevant since we do not know the delay of actual surb
+        // also the ack has to be in the same format as the packet produced by the surb
+        let packet_mode = reply_surb.packet_mode();
         let (_, surb_ack_bytes) = self
-            .generate_surb_ack(reply_id, topology, ack_key)?
+            .generate_surb_ack(reply_id, topology, ack_key, packet_mode)?
             .prepare_for_sending();
 
         let zero_pad_len = self.packet_size.plaintext_size()
@@ -397,10 +378,7 @@ where
             .apply_surb(&packet_payload, Some(self.packet_size))
             .unwrap();
 
-        Ok((
-            MixPacket::new(first_hop, packet, Default::default()),
-            reply_id,
-        ))
+        Ok((MixPacket::new(first_hop, packet, packet_mode), reply_id))
     }
 
     #[allow(dead_code)]
@@ -414,12 +392,136 @@ where
             packet_size: Default::default(),
             sender_address: dummy_address,
             average_packet_delay: Default::default(),
-            average_ack_delay: Default::default(),
+            ack_delay_distribution: AckDelayDistribution::Exponential(Default::default()),
             num_mix_hops: DEFAULT_NUM_MIX_HOPS,
+            packet_mode: Default::default(),
         }
     }
 }
 
+/// Parameters required for turning a [`Fragment`] into a [`PreparedFragment`]. They are
+/// decoupled from the [`MessagePreparer`] so that multiple fragments could be prepared at once,
+/// each using its own source of randomness.
+#[derive(Clone, Copy)]
+struct FragmentPreparer {
+    sender_address: Recipient,
+    packet_size: PacketSize,
+    average_packet_delay: Duration,
+    ack_delay_distribution: AckDelayDistribution,
+    num_mix_hops: u8,
+    packet_mode: PacketMode,
+}
+
+impl FragmentPreparer {
+    /// See [`MessagePreparer::prepare_chunk_for_sending`] for details. If a precomputed SURB-ack
+    /// is provided, it must have the same packet mode as the one used by this preparer.
+    fn prepare<R>(
+        &self,
+        rng: &mut R,
+        fragment: Fragment,
+        topology: &NymTopology,
+        ack_key: &AckKey,
+        packet_recipient: &Recipient,
+        precomputed_ack: Option<PrecomputedSurbAck>,
+    ) -> Result<PreparedFragment, NymTopologyError>
+    where
+        R: CryptoRng + Rng,
+    {
+        // create an ack
+        // (unless it has already been mostly computed beforehand)
+        let fragment_id = fragment.fragment_identifier().to_bytes();
+        let surb_ack = match precomputed_ack {
+            Some(precomputed) => SurbAck::from_precomputed(rng, precomputed, ack_key, fragment_id),
+            None => SurbAck::construct(
+                rng,
+                &self.sender_address,
+                ack_key,
+                fragment_id,
+                self.ack_delay_distribution,
+                topology,
+                self.packet_mode,
+            )?,
+        };
+        let (ack_delay, surb_ack_bytes) = surb_ack.prepare_for_sending();
+
+        // TODO:
+        // TODO:
+        // TODO:
+        // TODO:
+        // TODO: ASK @AP AND @DH WHETHER THOSE KEYS CAN/SHOULD ALSO BE REUSED IN VPN MODE!!
+        // TODO:
+        // TODO:
+        // TODO:
+        // TODO:
+
+        // create keys for 'payload' encryption
+        let (ephemeral_keypair, shared_key) = new_ephemeral_shared_key::<
+            PacketEncryptionAlgorithm,
+            PacketHkdfAlgorithm,
+            _,
+        >(rng, packet_recipient.encryption_key());
+
+        // serialize fragment and encrypt its content
+        let mut chunk_data = fragment.into_bytes();
+
+        let zero_iv = stream_cipher::zero_iv::<PacketEncryptionAlgorithm>();
+        stream_cipher::encrypt_in_place::<PacketEncryptionAlgorithm>(
+            &shared_key,
+            &zero_iv,
+            &mut chunk_data,
+        );
+
+        // combine it together as follows:
+        // SURB_ACK_FIRST_HOP || SURB_ACK_DATA || EPHEMERAL_KEY || CHUNK_DATA
+        // (note: surb_ack_bytes contains SURB_ACK_FIRST_HOP || SURB_ACK_DATA )
+        let packet_payload: Vec<_> = surb_ack_bytes
+            .into_iter()
+            .chain(ephemeral_keypair.public_key().to_bytes().iter().cloned())
+            .chain(chunk_data.into_iter())
+            .collect();
+
+        // generate pseudorandom route for the packet
+        let route =
+            topology.random_route_to_gateway(rng, self.num_mix_hops, packet_recipient.gateway())?;
+        let destination = packet_recipient.as_sphinx_destination();
+
+        // including set of delays
+        let delays = delays::generate_from_average_duration(route.len(), self.average_packet_delay);
+
+        // create the actual packet here. With valid route and correct payload size,
+        // there's absolutely no reason for this call to fail.
+        let packet: NymPacket = if self.packet_mode.is_outfox() {
+            OutfoxPacket::new(
+                &packet_payload,
+                self.packet_size.payload_size(),
+                &route,
+                &destination,
+                &delays,
+            )
+            .unwrap()
+            .into()
+        } else {
+            SphinxPacketBuilder::new()
+                .with_payload_size(self.packet_size.payload_size())
+                .build_packet(packet_payload, &route, &destination, &delays)
+                .unwrap()
+                .into()
+        };
+
+        // from the previously constructed route extract the first hop
+        let first_hop_address =
+            NymNodeRoutingAddress::try_from(route.first().unwrap().address).unwrap();
+
+        Ok(PreparedFragment {
+            // the round-trip delay is the sum of delays of all hops on the forward route as
+            // well as the total delay of the ack packet.
+            // note that the last hop of the packet is a gateway that does not do any delays
+            total_delay: delays.iter().take(delays.len() - 1).sum::<Delay>() + ack_delay,
+            mix_packet: MixPacket::new(first_hop_address, packet, self.packet_mode),
+        })
+    }
+}
+
 /*
    And for completion reconstruction:
    1. receive unwrapped sphinx packet: g^x || v_b
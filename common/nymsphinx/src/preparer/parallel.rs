@@ -0,0 +1,136 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{FragmentPreparer, MessagePreparer, PreparedFragment};
+use nymsphinx_acknowledgements::surb_ack::PrecomputedSurbAck;
+use nymsphinx_acknowledgements::AckKey;
+use nymsphinx_addressing::clients::Recipient;
+use nymsphinx_chunking::fragment::Fragment;
+use rand::rngs::StdRng;
+use rand::{CryptoRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use topology::{NymTopology, NymTopologyError};
+
+type Seed = <StdRng as SeedableRng>::Seed;
+
+impl<R> MessagePreparer<R>
+where
+    R: CryptoRng + Rng,
+{
+    /// Computes routes, delays and header key material of the specified number of SURB-acks
+    /// ahead of time, for example while the client is idle, so that they could be later used
+    /// for speeding up the preparation of fragments.
+    pub fn precompute_surb_acks(
+        &mut self,
+        count: usize,
+        topology: &NymTopology,
+    ) -> Result<Vec<PrecomputedSurbAck>, NymTopologyError> {
+        (0..count)
+            .map(|_| {
+                PrecomputedSurbAck::new(
+                    &mut self.rng,
+                    &self.sender_address,
+                    self.ack_delay_distribution,
+                    topology,
+                    self.packet_mode,
+                )
+            })
+            .collect()
+    }
+
+    /// Equivalent of calling [`MessagePreparer::prepare_chunk_for_sending`] for each of the
+    /// provided fragments, but the packets are built in parallel on the rayon thread pool.
+    /// Each fragment is going to use one of the provided precomputed SURB-acks, if there are
+    /// any available in the correct packet mode; the used ones are removed from the pool.
+    pub fn prepare_chunks_for_sending(
+        &mut self,
+        fragments: Vec<Fragment>,
+        topology: &NymTopology,
+        ack_key: &AckKey,
+        packet_recipient: &Recipient,
+        precomputed_acks: &mut Vec<PrecomputedSurbAck>,
+    ) -> Result<Vec<PreparedFragment>, NymTopologyError> {
+        let jobs = self.prepare_jobs(fragments, precomputed_acks);
+        prepare_in_parallel(
+            self.fragment_preparer(),
+            jobs,
+            topology,
+            ack_key,
+            packet_recipient,
+        )
+    }
+
+    /// Same as [`MessagePreparer::prepare_chunks_for_sending`] but the work is moved off the
+    /// current thread entirely, so that it could be awaited without blocking the async runtime.
+    pub fn prepare_chunks_in_background(
+        &mut self,
+        fragments: Vec<Fragment>,
+        topology: NymTopology,
+        ack_key: Arc<AckKey>,
+        packet_recipient: Recipient,
+        precomputed_acks: &mut Vec<PrecomputedSurbAck>,
+    ) -> impl Future<Output = Result<Vec<PreparedFragment>, NymTopologyError>> + Send + 'static
+    {
+        let jobs = self.prepare_jobs(fragments, precomputed_acks);
+        let fragment_preparer = self.fragment_preparer();
+
+        let (sender, receiver) = oneshot::channel();
+        rayon::spawn(move || {
+            let res = prepare_in_parallel(
+                fragment_preparer,
+                jobs,
+                &topology,
+                &ack_key,
+                &packet_recipient,
+            );
+            // the receiver might have been dropped if the caller is no longer interested
+            let _ = sender.send(res);
+        });
+
+        async move {
+            receiver
+                .await
+                .expect("the fragment preparation task has panicked")
+        }
+    }
+
+    /// Pairs each fragment with a seed for its own rng and, if available, a precomputed SURB-ack.
+    fn prepare_jobs(
+        &mut self,
+        fragments: Vec<Fragment>,
+        precomputed_acks: &mut Vec<PrecomputedSurbAck>,
+    ) -> Vec<(Fragment, Seed, Option<PrecomputedSurbAck>)> {
+        // acks created for a different packet mode are of no use to us anymore
+        precomputed_acks.retain(|ack| ack.packet_mode() == self.packet_mode);
+
+        fragments
+            .into_iter()
+            .map(|fragment| (fragment, self.rng.gen(), precomputed_acks.pop()))
+            .collect()
+    }
+}
+
+fn prepare_in_parallel(
+    fragment_preparer: FragmentPreparer,
+    jobs: Vec<(Fragment, Seed, Option<PrecomputedSurbAck>)>,
+    topology: &NymTopology,
+    ack_key: &AckKey,
+    packet_recipient: &Recipient,
+) -> Result<Vec<PreparedFragment>, NymTopologyError> {
+    jobs.into_par_iter()
+        .map(|(fragment, seed, precomputed_ack)| {
+            let mut rng = StdRng::from_seed(seed);
+            fragment_preparer.prepare(
+                &mut rng,
+                fragment,
+                topology,
+                ack_key,
+                packet_recipient,
+                precomputed_ack,
+            )
+        })
+        .collect()
+}
@@ -7,7 +7,12 @@ use crypto::symmetric::stream_cipher;
 use nymsphinx_anonymous_replies::reply_surb::{ReplySurb, ReplySurbError};
 use nymsphinx_chunking::fragment::Fragment;
 use nymsphinx_chunking::reconstruction::MessageReconstructor;
-use nymsphinx_params::{PacketEncryptionAlgorithm, PacketHkdfAlgorithm, DEFAULT_NUM_MIX_HOPS};
+use nymsphinx_params::{
+    PacketEncryptionAlgorithm, PacketHkdfAlgorithm, PacketMode, DEFAULT_NUM_MIX_HOPS,
+};
+
+/// Prefix of the messages carrying an outfox reply SURB, as opposed to `1` used for the sphinx ones.
+pub(crate) const OUTFOX_REPLY_SURB_PREFIX: u8 = 2;
 
 // TODO: should this live in this file?
 #[derive(Debug)]
@@ -74,8 +79,13 @@ impl MessageReceiver {
                 message.remove(0);
                 Ok(None)
             }
-            n if n == true as u8 => {
-                let surb_len: usize = ReplySurb::serialized_len(self.num_mix_hops);
+            n if n == true as u8 || n == OUTFOX_REPLY_SURB_PREFIX => {
+                let packet_mode = if n == OUTFOX_REPLY_SURB_PREFIX {
+                    PacketMode::Outfox
+                } else {
+                    PacketMode::Mix
+                };
+                let surb_len: usize = ReplySurb::serialized_len(self.num_mix_hops, packet_mode);
                 // note the extra +1 (due to 0/1 message prefix)
                 let surb_bytes = &message[1..1 + surb_len];
                 let reply_surb = ReplySurb::from_bytes(surb_bytes)?;
@@ -215,6 +225,7 @@ mod message_receiver {
                     "B3GzG62aXAZNg14RoMCp3BhELNBrySLr2JqrwyfYFzRc",
                 )
                 .unwrap(),
+                announced_sphinx_keys: Default::default(),
                 layer: Layer::One,
                 version: "0.8.0-dev".to_string(),
             }],
@@ -235,6 +246,7 @@ mod message_receiver {
                     "5Z1VqYwM2xeKxd8H7fJpGWasNiDFijYBAee7MErkZ5QT",
                 )
                 .unwrap(),
+                announced_sphinx_keys: Default::default(),
                 layer: Layer::Two,
                 version: "0.8.0-dev".to_string(),
             }],
@@ -255,6 +267,7 @@ mod message_receiver {
                     "9EyjhCggr2QEA2nakR88YHmXgpy92DWxoe2draDRkYof",
                 )
                 .unwrap(),
+                announced_sphinx_keys: Default::default(),
                 layer: Layer::Three,
                 version: "0.8.0-dev".to_string(),
             }],
@@ -270,6 +283,8 @@ mod message_receiver {
                 host: "1.2.3.4".parse().unwrap(),
                 mix_host: "1.2.3.4:1789".parse().unwrap(),
                 clients_port: 9000,
+                clients_wss_port: None,
+                http_api_port: None,
                 identity_key: identity::PublicKey::from_base58_string(
                     "FioFa8nMmPpQnYi7JyojoTuwGLeyNS8BF4ChPr29zUML",
                 )
@@ -278,6 +293,7 @@ mod message_receiver {
                     "EB42xvMFMD5rUCstE2CDazgQQJ22zLv8SPm1Luxni44c",
                 )
                 .unwrap(),
+                announced_sphinx_keys: Default::default(),
                 version: "0.8.0-dev".to_string(),
             }],
         )
@@ -293,8 +309,14 @@ mod message_receiver {
         let average_delay = Duration::from_millis(500);
         let topology = topology_fixture();
 
-        let reply_surb =
-            ReplySurb::construct(&mut OsRng, &dummy_recipient, average_delay, &topology).unwrap();
+        let reply_surb = ReplySurb::construct(
+            &mut OsRng,
+            &dummy_recipient,
+            average_delay,
+            &topology,
+            PacketMode::Mix,
+        )
+        .unwrap();
 
         let reply_surb_bytes = reply_surb.to_bytes();
 
@@ -318,5 +340,27 @@ mod message_receiver {
             .unwrap();
         assert_eq!(received_with_surb, message);
         assert_eq!(reply_surb_bytes, reply_surb.unwrap().to_bytes());
+
+        let outfox_reply_surb = ReplySurb::construct(
+            &mut OsRng,
+            &dummy_recipient,
+            average_delay,
+            &topology,
+            PacketMode::Outfox,
+        )
+        .unwrap();
+        let outfox_reply_surb_bytes = outfox_reply_surb.to_bytes();
+
+        let mut received_with_outfox_surb: Vec<_> = std::iter::once(OUTFOX_REPLY_SURB_PREFIX)
+            .chain(outfox_reply_surb_bytes.iter().cloned())
+            .chain(message.iter().cloned())
+            .collect();
+        let outfox_reply_surb = message_receiver
+            .recover_reply_surb_from_message(&mut received_with_outfox_surb)
+            .unwrap()
+            .unwrap();
+        assert_eq!(received_with_outfox_surb, message);
+        assert_eq!(outfox_reply_surb.packet_mode(), PacketMode::Outfox);
+        assert_eq!(outfox_reply_surb_bytes, outfox_reply_surb.to_bytes());
     }
 }
@@ -1,6 +1,11 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod nym_packet;
+pub mod outfox;
+
+pub use nym_packet::{NymPacket, NymPacketError, NymProcessedPacket};
+
 // re-exporting types and constants available in sphinx
 pub use sphinx::{
     constants::{
@@ -0,0 +1,159 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::outfox::{OutfoxError, OutfoxPacket, OutfoxProcessedPacket};
+use crate::{
+    Delay, DestinationAddressBytes, NodeAddressBytes, PrivateKey, ProcessedPacket, SphinxPacket,
+};
+use std::fmt::{self, Display, Formatter};
+use x25519_dalek::StaticSecret;
+
+const SPHINX_REPLAY_TAG_CONTEXT: &str = "nym sphinx 2022-11 replay tag";
+
+/// Packet in any of the supported layered formats.
+pub enum NymPacket {
+    Sphinx(SphinxPacket),
+    Outfox(OutfoxPacket),
+}
+
+pub enum NymProcessedPacket {
+    /// The packet has to be forwarded to the next hop after the specified delay.
+    ForwardHop(Box<NymPacket>, NodeAddressBytes, Delay),
+
+    /// The packet has reached its destination, alongside the recovered plaintext.
+    FinalHop(DestinationAddressBytes, Vec<u8>),
+}
+
+#[derive(Debug)]
+pub enum NymPacketError {
+    Sphinx(sphinx::Error),
+    Outfox(OutfoxError),
+}
+
+impl Display for NymPacketError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NymPacketError::Sphinx(err) => write!(f, "sphinx error - {}", err),
+            NymPacketError::Outfox(err) => write!(f, "outfox error - {}", err),
+        }
+    }
+}
+
+impl std::error::Error for NymPacketError {}
+
+impl From<sphinx::Error> for NymPacketError {
+    fn from(err: sphinx::Error) -> Self {
+        NymPacketError::Sphinx(err)
+    }
+}
+
+impl From<OutfoxError> for NymPacketError {
+    fn from(err: OutfoxError) -> Self {
+        NymPacketError::Outfox(err)
+    }
+}
+
+impl From<SphinxPacket> for NymPacket {
+    fn from(packet: SphinxPacket) -> Self {
+        NymPacket::Sphinx(packet)
+    }
+}
+
+impl From<OutfoxPacket> for NymPacket {
+    fn from(packet: OutfoxPacket) -> Self {
+        NymPacket::Outfox(packet)
+    }
+}
+
+impl NymPacket {
+    pub fn sphinx_from_bytes(bytes: &[u8]) -> Result<Self, NymPacketError> {
+        Ok(SphinxPacket::from_bytes(bytes)?.into())
+    }
+
+    pub fn outfox_from_bytes(bytes: &[u8]) -> Result<Self, NymPacketError> {
+        Ok(OutfoxPacket::from_bytes(bytes)?.into())
+    }
+
+    pub fn is_outfox(&self) -> bool {
+        matches!(self, NymPacket::Outfox(..))
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            NymPacket::Sphinx(packet) => packet.len(),
+            NymPacket::Outfox(packet) => packet.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            NymPacket::Sphinx(packet) => packet.to_bytes(),
+            NymPacket::Outfox(packet) => packet.to_bytes(),
+        }
+    }
+
+    /// Removes a single layer of encryption using the private key of the node and returns,
+    /// alongside the result, the value uniquely identifying the packet at this hop, to be used
+    /// for the replay detection.
+    pub fn process_with_replay_tag(
+        self,
+        private_key: &PrivateKey,
+    ) -> Result<(NymProcessedPacket, [u8; 32]), NymPacketError> {
+        match self {
+            NymPacket::Sphinx(packet) => {
+                let alpha = *packet.header.shared_secret.as_bytes();
+                let processed = NymPacket::Sphinx(packet).process(private_key)?;
+                Ok((processed, sphinx_replay_tag(private_key, alpha)))
+            }
+            NymPacket::Outfox(packet) => match packet.process_with_replay_tag(private_key)? {
+                (OutfoxProcessedPacket::ForwardHop(packet, next_hop, delay), replay_tag) => Ok((
+                    NymProcessedPacket::ForwardHop(Box::new((*packet).into()), next_hop, delay),
+                    replay_tag,
+                )),
+                (OutfoxProcessedPacket::FinalHop(destination, plaintext), replay_tag) => Ok((
+                    NymProcessedPacket::FinalHop(destination, plaintext),
+                    replay_tag,
+                )),
+            },
+        }
+    }
+
+    /// Removes a single layer of encryption using the private key of the node.
+    pub fn process(self, private_key: &PrivateKey) -> Result<NymProcessedPacket, NymPacketError> {
+        match self {
+            NymPacket::Sphinx(packet) => match packet.process(private_key)? {
+                ProcessedPacket::ForwardHop(packet, next_hop, delay) => Ok(
+                    NymProcessedPacket::ForwardHop(Box::new((*packet).into()), next_hop, delay),
+                ),
+                ProcessedPacket::FinalHop(destination, _, payload) => Ok(
+                    NymProcessedPacket::FinalHop(destination, payload.recover_plaintext()?),
+                ),
+            },
+            NymPacket::Outfox(packet) => match packet.process(private_key)? {
+                OutfoxProcessedPacket::ForwardHop(packet, next_hop, delay) => Ok(
+                    NymProcessedPacket::ForwardHop(Box::new((*packet).into()), next_hop, delay),
+                ),
+                OutfoxProcessedPacket::FinalHop(destination, plaintext) => {
+                    Ok(NymProcessedPacket::FinalHop(destination, plaintext))
+                }
+            },
+        }
+    }
+}
+
+/// Derives the replay tag of the sphinx packet from the shared secret of its current layer.
+/// The raw alpha can't be used directly, as it is malleable: multiple encodings
+/// (for example differing only in the ignored top bit) result in the very same shared secret,
+/// so the header would still get authenticated while the replay would go unnoticed.
+fn sphinx_replay_tag(private_key: &PrivateKey, alpha: [u8; 32]) -> [u8; 32] {
+    let mut secret_bytes = [0u8; 32];
+    secret_bytes.copy_from_slice(&private_key.to_bytes());
+    let secret = StaticSecret::from(secret_bytes);
+    let shared_secret = secret.diffie_hellman(&alpha.into());
+
+    blake3::derive_key(SPHINX_REPLAY_TAG_CONTEXT, shared_secret.as_bytes())
+}
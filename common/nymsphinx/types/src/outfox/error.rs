@@ -0,0 +1,51 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OutfoxError {
+    InvalidPacketLength(usize),
+    InvalidRouteLength(usize),
+    InvalidDelaysLength { expected: usize, received: usize },
+    TooLongPayload { max: usize, received: usize },
+    InvalidHeaderMac,
+    UnknownRoutingFlag(u8),
+    TamperedPayload,
+    MalformedSurb,
+}
+
+impl Display for OutfoxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OutfoxError::InvalidPacketLength(len) => {
+                write!(f, "{} is not a valid length of an outfox packet", len)
+            }
+            OutfoxError::InvalidRouteLength(len) => {
+                write!(
+                    f,
+                    "route consisting of {} nodes can't be used for outfox packets",
+                    len
+                )
+            }
+            OutfoxError::InvalidDelaysLength { expected, received } => write!(
+                f,
+                "expected {} delays for the route, but received {}",
+                expected, received
+            ),
+            OutfoxError::TooLongPayload { max, received } => write!(
+                f,
+                "the payload of {} bytes is longer than the maximum of {} bytes",
+                received, max
+            ),
+            OutfoxError::InvalidHeaderMac => write!(f, "the mac of the header is invalid"),
+            OutfoxError::UnknownRoutingFlag(flag) => {
+                write!(f, "received unknown routing flag {}", flag)
+            }
+            OutfoxError::TamperedPayload => write!(f, "the payload has been tampered with"),
+            OutfoxError::MalformedSurb => write!(f, "the outfox SURB is malformed"),
+        }
+    }
+}
+
+impl std::error::Error for OutfoxError {}
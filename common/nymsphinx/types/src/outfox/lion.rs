@@ -0,0 +1,87 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! LION-style wide-block cipher built out of blake3, used for the layered payload encryption.
+//! Flipping any bit of the ciphertext results in a completely garbled plaintext.
+
+use super::{apply_keystream, xor_in_place};
+
+/// Size of the left part of the block.
+const LEFT_SIZE: usize = 32;
+
+/// The smallest block the cipher operates on.
+pub(crate) const MIN_BLOCK_SIZE: usize = LEFT_SIZE + 1;
+
+struct RoundKeys([[u8; 32]; 3]);
+
+impl RoundKeys {
+    fn derive(key: &[u8; 32]) -> Self {
+        RoundKeys([
+            blake3::derive_key("nym outfox 2022-10 lion stream key 1", key),
+            blake3::derive_key("nym outfox 2022-10 lion hash key", key),
+            blake3::derive_key("nym outfox 2022-10 lion stream key 2", key),
+        ])
+    }
+}
+
+// R = R ^ S(L ^ K)
+fn stream_round(round_key: &[u8; 32], left: &[u8], right: &mut [u8]) {
+    let mut stream_key = *round_key;
+    xor_in_place(&mut stream_key, left);
+    apply_keystream(&stream_key, 0, right)
+}
+
+// L = L ^ H_K(R)
+fn hash_round(round_key: &[u8; 32], left: &mut [u8], right: &[u8]) {
+    xor_in_place(left, blake3::keyed_hash(round_key, right).as_bytes())
+}
+
+pub(crate) fn encrypt(key: &[u8; 32], block: &mut [u8]) {
+    debug_assert!(block.len() >= MIN_BLOCK_SIZE);
+    let keys = RoundKeys::derive(key);
+    let (left, right) = block.split_at_mut(LEFT_SIZE);
+
+    stream_round(&keys.0[0], left, right);
+    hash_round(&keys.0[1], left, right);
+    stream_round(&keys.0[2], left, right);
+}
+
+pub(crate) fn decrypt(key: &[u8; 32], block: &mut [u8]) {
+    debug_assert!(block.len() >= MIN_BLOCK_SIZE);
+    let keys = RoundKeys::derive(key);
+    let (left, right) = block.split_at_mut(LEFT_SIZE);
+
+    stream_round(&keys.0[2], left, right);
+    hash_round(&keys.0[1], left, right);
+    stream_round(&keys.0[0], left, right);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decryption_reverses_encryption() {
+        let key = [1u8; 32];
+        let plaintext: Vec<_> = (0..=255).collect();
+        let mut block = plaintext.clone();
+
+        encrypt(&key, &mut block);
+        assert_ne!(block, plaintext);
+        decrypt(&key, &mut block);
+        assert_eq!(block, plaintext);
+    }
+
+    #[test]
+    fn any_modification_garbles_the_whole_block() {
+        let key = [1u8; 32];
+        let plaintext = vec![0u8; 256];
+        let mut block = plaintext.clone();
+
+        encrypt(&key, &mut block);
+        block[200] ^= 1;
+        decrypt(&key, &mut block);
+        assert_ne!(&block[..LEFT_SIZE], &plaintext[..LEFT_SIZE]);
+        assert_ne!(&block[LEFT_SIZE..200], &plaintext[LEFT_SIZE..200]);
+    }
+}
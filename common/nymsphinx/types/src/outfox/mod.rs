@@ -0,0 +1,161 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+//! Outfox-style layered packet format.
+//!
+//! Compared to sphinx, every layer of the header is protected by a single x25519 key
+//! encapsulation and the symmetric operations are all based on blake3, which makes processing
+//! of a packet considerably cheaper for the mix nodes. The header occupies exactly
+//! `HEADER_SIZE` bytes, so that both formats share all the packet sizes.
+//!
+//! Every layer of the header consists of `ephemeral key || mac || encrypted routing info`.
+//! After the key exchange, the node decrypts the routing info (padded with zeroes) and finds its
+//! own routing information followed by the complete header for the next hop.
+//! The payload is encrypted with a wide-block cipher, similarly to sphinx, so that any tampering
+//! with it destroys the whole message.
+
+use crate::{PrivateKey, PublicKey, HEADER_SIZE, PAYLOAD_OVERHEAD_SIZE};
+use x25519_dalek::{EphemeralSecret, StaticSecret};
+
+pub mod error;
+mod lion;
+pub mod packet;
+pub mod surb;
+
+pub use error::OutfoxError;
+pub use packet::{OutfoxPacket, OutfoxProcessedPacket};
+pub use surb::OutfoxSurb;
+
+/// Size of the outfox header. It is the same as of the sphinx header.
+pub const OUTFOX_HEADER_SIZE: usize = HEADER_SIZE;
+
+pub(crate) const EPHEMERAL_KEY_SIZE: usize = 32;
+pub(crate) const MAC_SIZE: usize = 16;
+pub(crate) const LAYER_KEY_SIZE: usize = 32;
+
+/// Size of the encrypted routing information of each header.
+pub(crate) const ENCRYPTED_ROUTING_SIZE: usize = OUTFOX_HEADER_SIZE - EPHEMERAL_KEY_SIZE - MAC_SIZE;
+
+pub(crate) const FORWARD_HOP_FLAG: u8 = 1;
+pub(crate) const FINAL_HOP_FLAG: u8 = 2;
+
+/// flag || delay || next hop address
+pub(crate) const FORWARD_ROUTING_SIZE: usize = 1 + 8 + 32;
+
+/// flag || destination address
+pub(crate) const FINAL_ROUTING_SIZE: usize = 1 + 32;
+
+/// Number of header bytes used up by every forward hop.
+pub(crate) const FORWARD_LAYER_SIZE: usize = FORWARD_ROUTING_SIZE + EPHEMERAL_KEY_SIZE + MAC_SIZE;
+
+/// Maximum number of forward hops, i.e. excluding the destination, that fit in the header.
+pub const MAX_FORWARD_HOPS: usize =
+    (ENCRYPTED_ROUTING_SIZE - FINAL_ROUTING_SIZE) / FORWARD_LAYER_SIZE;
+
+/// Number of zero bytes prepended to the plaintext to let the destination detect any tampering.
+/// The remaining overhead byte is used for the padding marker.
+pub(crate) const PAYLOAD_INTEGRITY_SIZE: usize = PAYLOAD_OVERHEAD_SIZE - 1;
+
+const HEADER_KEY_CONTEXT: &str = "nym outfox 2022-10 header stream key";
+const MAC_KEY_CONTEXT: &str = "nym outfox 2022-10 header mac key";
+const PAYLOAD_KEY_CONTEXT: &str = "nym outfox 2022-10 payload key";
+const REPLAY_TAG_CONTEXT: &str = "nym outfox 2022-11 replay tag";
+
+/// Symmetric keys derived from the shared secret of a single layer.
+pub(crate) struct LayerKeys {
+    pub(crate) header: [u8; LAYER_KEY_SIZE],
+    pub(crate) mac: [u8; LAYER_KEY_SIZE],
+    pub(crate) payload: [u8; LAYER_KEY_SIZE],
+    pub(crate) replay_tag: [u8; LAYER_KEY_SIZE],
+}
+
+impl LayerKeys {
+    fn derive(shared_secret: &[u8; 32]) -> Self {
+        LayerKeys {
+            header: blake3::derive_key(HEADER_KEY_CONTEXT, shared_secret),
+            mac: blake3::derive_key(MAC_KEY_CONTEXT, shared_secret),
+            payload: blake3::derive_key(PAYLOAD_KEY_CONTEXT, shared_secret),
+            replay_tag: blake3::derive_key(REPLAY_TAG_CONTEXT, shared_secret),
+        }
+    }
+
+    /// Derives the keys of the layer on the node side.
+    pub(crate) fn derive_for_node(
+        private_key: &PrivateKey,
+        ephemeral_key: [u8; EPHEMERAL_KEY_SIZE],
+    ) -> Self {
+        let mut secret_bytes = [0u8; 32];
+        secret_bytes.copy_from_slice(&private_key.to_bytes());
+        let secret = StaticSecret::from(secret_bytes);
+        let shared_secret = secret.diffie_hellman(&ephemeral_key.into());
+
+        Self::derive(shared_secret.as_bytes())
+    }
+
+    /// Generates fresh ephemeral key for the layer and derives the keys on the sender side.
+    pub(crate) fn new_for_sender(node_key: &PublicKey) -> ([u8; EPHEMERAL_KEY_SIZE], LayerKeys) {
+        let ephemeral_secret = EphemeralSecret::new(rand::rngs::OsRng);
+        let ephemeral_key = x25519_dalek::PublicKey::from(&ephemeral_secret);
+        let shared_secret =
+            ephemeral_secret.diffie_hellman(&x25519_dalek::PublicKey::from(*node_key.as_bytes()));
+
+        (
+            ephemeral_key.to_bytes(),
+            Self::derive(shared_secret.as_bytes()),
+        )
+    }
+
+    pub(crate) fn header_mac(&self, encrypted_routing: &[u8]) -> [u8; MAC_SIZE] {
+        let mut mac = [0u8; MAC_SIZE];
+        mac.copy_from_slice(
+            &blake3::keyed_hash(&self.mac, encrypted_routing).as_bytes()[..MAC_SIZE],
+        );
+        mac
+    }
+}
+
+/// Xors the data with the keystream produced by the keyed blake3 in the XOF mode,
+/// starting at the provided offset.
+pub(crate) fn apply_keystream(key: &[u8; 32], offset: u64, data: &mut [u8]) {
+    let mut output = blake3::Hasher::new_keyed(key).finalize_xof();
+    output.set_position(offset);
+
+    let mut keystream = [0u8; 64];
+    for chunk in data.chunks_mut(keystream.len()) {
+        output.fill(&mut keystream[..chunk.len()]);
+        xor_in_place(chunk, &keystream);
+    }
+}
+
+/// Xors `dst` with the prefix of `src` of the same length.
+pub(crate) fn xor_in_place(dst: &mut [u8], src: &[u8]) {
+    dst.iter_mut().zip(src).for_each(|(a, b)| *a ^= b)
+}
+
+/// Compares the values without leaking the position of the first difference.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_fits_at_least_three_forward_hops() {
+        // three mix layers followed by the gateway
+        assert!(MAX_FORWARD_HOPS >= 3);
+    }
+
+    #[test]
+    fn keystream_can_be_applied_in_parts() {
+        let key = [42u8; 32];
+        let mut whole = vec![0u8; 200];
+        apply_keystream(&key, 0, &mut whole);
+
+        let mut parts = vec![0u8; 200];
+        apply_keystream(&key, 0, &mut parts[..70]);
+        apply_keystream(&key, 70, &mut parts[70..]);
+        assert_eq!(whole, parts);
+    }
+}
@@ -0,0 +1,401 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use super::error::OutfoxError;
+use super::{
+    apply_keystream, constant_time_eq, lion, LayerKeys, ENCRYPTED_ROUTING_SIZE, EPHEMERAL_KEY_SIZE,
+    FINAL_HOP_FLAG, FINAL_ROUTING_SIZE, FORWARD_HOP_FLAG, FORWARD_LAYER_SIZE, FORWARD_ROUTING_SIZE,
+    LAYER_KEY_SIZE, MAC_SIZE, MAX_FORWARD_HOPS, OUTFOX_HEADER_SIZE, PAYLOAD_INTEGRITY_SIZE,
+};
+use crate::{
+    Delay, Destination, DestinationAddressBytes, Node, NodeAddressBytes, PrivateKey,
+    PAYLOAD_OVERHEAD_SIZE,
+};
+use rand::RngCore;
+use std::convert::TryInto;
+
+/// Marks the end of the message within the padded plaintext.
+const PADDING_MARKER: u8 = 1;
+
+pub enum OutfoxProcessedPacket {
+    /// The packet has to be forwarded to the next hop after the specified delay.
+    ForwardHop(Box<OutfoxPacket>, NodeAddressBytes, Delay),
+
+    /// The packet has reached its destination, alongside the recovered plaintext.
+    FinalHop(DestinationAddressBytes, Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutfoxPacket {
+    header: Vec<u8>,
+    payload: Vec<u8>,
+}
+
+/// Builds the header for the provided route alongside the payload keys of all the layers,
+/// starting with the first hop.
+pub(crate) fn build_header(
+    route: &[Node],
+    destination: &Destination,
+    delays: &[Delay],
+) -> Result<(Vec<u8>, Vec<[u8; LAYER_KEY_SIZE]>), OutfoxError> {
+    if route.is_empty() || route.len() > MAX_FORWARD_HOPS + 1 {
+        return Err(OutfoxError::InvalidRouteLength(route.len()));
+    }
+    if delays.len() != route.len() {
+        return Err(OutfoxError::InvalidDelaysLength {
+            expected: route.len(),
+            received: delays.len(),
+        });
+    }
+
+    let layers: Vec<_> = route
+        .iter()
+        .map(|node| LayerKeys::new_for_sender(&node.pub_key))
+        .collect();
+
+    // filler ensuring that the headers decrypted by the nodes are consistent with
+    // what we have constructed, similarly to sphinx
+    let mut filler = Vec::new();
+    for (_, keys) in &layers[..layers.len() - 1] {
+        filler.resize(filler.len() + FORWARD_LAYER_SIZE, 0);
+        let offset = ENCRYPTED_ROUTING_SIZE + FORWARD_LAYER_SIZE - filler.len();
+        apply_keystream(&keys.header, offset as u64, &mut filler);
+    }
+
+    // the innermost layer meant for the destination
+    let (final_ephemeral_key, final_keys) = layers.last().unwrap();
+    let mut encrypted_routing = Vec::with_capacity(ENCRYPTED_ROUTING_SIZE);
+    encrypted_routing.push(FINAL_HOP_FLAG);
+    encrypted_routing.extend_from_slice(destination.address.as_bytes_ref());
+    let mut random_padding = vec![0u8; ENCRYPTED_ROUTING_SIZE - filler.len() - FINAL_ROUTING_SIZE];
+    rand::rngs::OsRng.fill_bytes(&mut random_padding);
+    encrypted_routing.extend_from_slice(&random_padding);
+    apply_keystream(&final_keys.header, 0, &mut encrypted_routing);
+    encrypted_routing.extend_from_slice(&filler);
+
+    let mut header = layer_header(final_ephemeral_key, final_keys, &encrypted_routing);
+
+    // and then wrap it for each of the forward hops
+    for (i, (ephemeral_key, keys)) in layers.iter().enumerate().rev().skip(1) {
+        let mut routing = Vec::with_capacity(ENCRYPTED_ROUTING_SIZE + FORWARD_LAYER_SIZE);
+        routing.push(FORWARD_HOP_FLAG);
+        routing.extend_from_slice(&(delays[i].to_duration().as_nanos() as u64).to_be_bytes());
+        routing.extend_from_slice(route[i + 1].address.as_bytes_ref());
+        routing.extend_from_slice(&header);
+        apply_keystream(&keys.header, 0, &mut routing);
+
+        // the node recovers the tail via the filler
+        debug_assert!(routing[ENCRYPTED_ROUTING_SIZE..].iter().all(|b| *b == 0));
+        header = layer_header(ephemeral_key, keys, &routing[..ENCRYPTED_ROUTING_SIZE]);
+    }
+
+    let payload_keys = layers.into_iter().map(|(_, keys)| keys.payload).collect();
+    Ok((header, payload_keys))
+}
+
+fn layer_header(
+    ephemeral_key: &[u8; EPHEMERAL_KEY_SIZE],
+    keys: &LayerKeys,
+    encrypted_routing: &[u8],
+) -> Vec<u8> {
+    let mut header = Vec::with_capacity(OUTFOX_HEADER_SIZE);
+    header.extend_from_slice(ephemeral_key);
+    header.extend_from_slice(&keys.header_mac(encrypted_routing));
+    header.extend_from_slice(encrypted_routing);
+    header
+}
+
+/// Pads the message to the payload size and applies the layered encryption using the provided
+/// keys, starting with the first hop.
+pub(crate) fn encrypt_payload(
+    message: &[u8],
+    payload_size: usize,
+    payload_keys: &[[u8; LAYER_KEY_SIZE]],
+) -> Result<Vec<u8>, OutfoxError> {
+    let max = OutfoxPacket::max_message_len(payload_size);
+    if payload_size < lion::MIN_BLOCK_SIZE || message.len() > max {
+        return Err(OutfoxError::TooLongPayload {
+            max,
+            received: message.len(),
+        });
+    }
+
+    let mut payload = Vec::with_capacity(payload_size);
+    payload.resize(PAYLOAD_INTEGRITY_SIZE, 0);
+    payload.extend_from_slice(message);
+    payload.push(PADDING_MARKER);
+    payload.resize(payload_size, 0);
+
+    for key in payload_keys.iter().rev() {
+        lion::encrypt(key, &mut payload);
+    }
+    Ok(payload)
+}
+
+fn recover_plaintext(mut payload: Vec<u8>) -> Result<Vec<u8>, OutfoxError> {
+    if payload[..PAYLOAD_INTEGRITY_SIZE].iter().any(|b| *b != 0) {
+        return Err(OutfoxError::TamperedPayload);
+    }
+
+    let marker = payload
+        .iter()
+        .rposition(|b| *b != 0)
+        .filter(|position| *position >= PAYLOAD_INTEGRITY_SIZE)
+        .ok_or(OutfoxError::TamperedPayload)?;
+    if payload[marker] != PADDING_MARKER {
+        return Err(OutfoxError::TamperedPayload);
+    }
+
+    payload.truncate(marker);
+    payload.drain(..PAYLOAD_INTEGRITY_SIZE);
+    Ok(payload)
+}
+
+impl OutfoxPacket {
+    /// Creates a new packet carrying the message through the provided route.
+    /// Delay at index `i` is applied by the node at index `i` of the route.
+    pub fn new(
+        message: &[u8],
+        payload_size: usize,
+        route: &[Node],
+        destination: &Destination,
+        delays: &[Delay],
+    ) -> Result<Self, OutfoxError> {
+        let (header, payload_keys) = build_header(route, destination, delays)?;
+        let payload = encrypt_payload(message, payload_size, &payload_keys)?;
+        Ok(OutfoxPacket { header, payload })
+    }
+
+    pub(crate) fn from_parts(header: Vec<u8>, payload: Vec<u8>) -> Self {
+        OutfoxPacket { header, payload }
+    }
+
+    /// Maximum length of the message that can be put in the payload of the provided size.
+    pub fn max_message_len(payload_size: usize) -> usize {
+        payload_size.saturating_sub(PAYLOAD_OVERHEAD_SIZE)
+    }
+
+    pub fn len(&self) -> usize {
+        self.header.len() + self.payload.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn ephemeral_key(&self) -> [u8; EPHEMERAL_KEY_SIZE] {
+        self.header[..EPHEMERAL_KEY_SIZE].try_into().unwrap()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len());
+        bytes.extend_from_slice(&self.header);
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OutfoxError> {
+        if bytes.len() < OUTFOX_HEADER_SIZE + lion::MIN_BLOCK_SIZE {
+            return Err(OutfoxError::InvalidPacketLength(bytes.len()));
+        }
+        let (header, payload) = bytes.split_at(OUTFOX_HEADER_SIZE);
+        Ok(OutfoxPacket {
+            header: header.to_vec(),
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Removes a single layer of encryption using the private key of the node.
+    pub fn process(self, private_key: &PrivateKey) -> Result<OutfoxProcessedPacket, OutfoxError> {
+        self.process_with_replay_tag(private_key)
+            .map(|(processed, _)| processed)
+    }
+
+    /// Removes a single layer of encryption using the private key of the node and returns,
+    /// alongside the result, the value uniquely identifying the packet at this hop, to be used
+    /// for the replay detection.
+    /// It is derived from the shared secret of the layer rather than being the raw ephemeral key,
+    /// as multiple encodings of the key (for example differing only in the ignored top bit)
+    /// result in the very same shared secret.
+    pub fn process_with_replay_tag(
+        self,
+        private_key: &PrivateKey,
+    ) -> Result<(OutfoxProcessedPacket, [u8; LAYER_KEY_SIZE]), OutfoxError> {
+        let keys = LayerKeys::derive_for_node(private_key, self.ephemeral_key());
+
+        let encrypted_routing = &self.header[EPHEMERAL_KEY_SIZE + MAC_SIZE..];
+        let mac = &self.header[EPHEMERAL_KEY_SIZE..EPHEMERAL_KEY_SIZE + MAC_SIZE];
+        if !constant_time_eq(mac, &keys.header_mac(encrypted_routing)) {
+            return Err(OutfoxError::InvalidHeaderMac);
+        }
+
+        let mut routing = Vec::with_capacity(ENCRYPTED_ROUTING_SIZE + FORWARD_LAYER_SIZE);
+        routing.extend_from_slice(encrypted_routing);
+        routing.resize(ENCRYPTED_ROUTING_SIZE + FORWARD_LAYER_SIZE, 0);
+        apply_keystream(&keys.header, 0, &mut routing);
+
+        let mut payload = self.payload;
+        lion::decrypt(&keys.payload, &mut payload);
+
+        match routing[0] {
+            FORWARD_HOP_FLAG => {
+                let delay = u64::from_be_bytes(routing[1..9].try_into().unwrap());
+                let next_hop = NodeAddressBytes::from_bytes(
+                    routing[9..FORWARD_ROUTING_SIZE].try_into().unwrap(),
+                );
+                let next_header = routing
+                    [FORWARD_ROUTING_SIZE..FORWARD_ROUTING_SIZE + OUTFOX_HEADER_SIZE]
+                    .to_vec();
+
+                Ok((
+                    OutfoxProcessedPacket::ForwardHop(
+                        Box::new(OutfoxPacket {
+                            header: next_header,
+                            payload,
+                        }),
+                        next_hop,
+                        Delay::new_from_nanos(delay),
+                    ),
+                    keys.replay_tag,
+                ))
+            }
+            FINAL_HOP_FLAG => {
+                let destination = DestinationAddressBytes::from_bytes(
+                    routing[1..FINAL_ROUTING_SIZE].try_into().unwrap(),
+                );
+                Ok((
+                    OutfoxProcessedPacket::FinalHop(destination, recover_plaintext(payload)?),
+                    keys.replay_tag,
+                ))
+            }
+            flag => Err(OutfoxError::UnknownRoutingFlag(flag)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::PublicKey;
+    use std::time::Duration;
+
+    pub(crate) fn random_node(seed: u8) -> (Node, PrivateKey) {
+        let private_key = PrivateKey::from([seed; 32]);
+        let public_key = PublicKey::from(&private_key);
+        (
+            Node::new(NodeAddressBytes::from_bytes([seed; 32]), public_key),
+            private_key,
+        )
+    }
+
+    pub(crate) fn test_destination() -> Destination {
+        Destination::new(DestinationAddressBytes::from_bytes([99; 32]), [0; 16])
+    }
+
+    #[test]
+    fn packet_goes_through_the_whole_route() {
+        let nodes: Vec<_> = (1..=4).map(random_node).collect();
+        let route: Vec<_> = nodes.iter().map(|(node, _)| node.clone()).collect();
+        let delays: Vec<_> = (1..=4)
+            .map(|i| Delay::new_from_nanos(i * 1_000_000))
+            .collect();
+        let message = b"hello outfox".to_vec();
+
+        let mut packet =
+            OutfoxPacket::new(&message, 1024, &route, &test_destination(), &delays).unwrap();
+        assert_eq!(packet.len(), OUTFOX_HEADER_SIZE + 1024);
+        assert_eq!(
+            OutfoxPacket::from_bytes(&packet.to_bytes()).unwrap(),
+            packet
+        );
+
+        for (i, (_, private_key)) in nodes[..3].iter().enumerate() {
+            match packet.process(private_key).unwrap() {
+                OutfoxProcessedPacket::ForwardHop(next, next_hop, delay) => {
+                    assert_eq!(next_hop, route[i + 1].address);
+                    assert_eq!(delay.to_duration(), Duration::from_millis(i as u64 + 1));
+                    assert_eq!(next.len(), OUTFOX_HEADER_SIZE + 1024);
+                    packet = *next;
+                }
+                _ => panic!("expected forward hop"),
+            }
+        }
+
+        match packet.process(&nodes[3].1).unwrap() {
+            OutfoxProcessedPacket::FinalHop(destination, plaintext) => {
+                assert_eq!(destination, test_destination().address);
+                assert_eq!(plaintext, message);
+            }
+            _ => panic!("expected final hop"),
+        }
+    }
+
+    #[test]
+    fn replay_tag_does_not_depend_on_the_ephemeral_key_encoding() {
+        let (node, private_key) = random_node(1);
+        let delays = [Delay::new_from_nanos(0)];
+        let packet =
+            OutfoxPacket::new(b"foomp", 1024, &[node], &test_destination(), &delays).unwrap();
+
+        let mut modified_bytes = packet.to_bytes();
+        modified_bytes[EPHEMERAL_KEY_SIZE - 1] ^= 0x80;
+        let modified = OutfoxPacket::from_bytes(&modified_bytes).unwrap();
+        assert_ne!(modified, packet);
+
+        let (_, tag) = packet.process_with_replay_tag(&private_key).unwrap();
+        let (_, modified_tag) = modified.process_with_replay_tag(&private_key).unwrap();
+        assert_eq!(tag, modified_tag);
+    }
+
+    #[test]
+    fn processing_with_wrong_key_fails() {
+        let (node, _) = random_node(1);
+        let (_, other_key) = random_node(2);
+        let packet = OutfoxPacket::new(
+            b"foomp",
+            256,
+            &[node],
+            &test_destination(),
+            &[Delay::new_from_nanos(0)],
+        )
+        .unwrap();
+
+        assert!(matches!(
+            packet.process(&other_key),
+            Err(OutfoxError::InvalidHeaderMac)
+        ));
+    }
+
+    #[test]
+    fn tampered_payload_is_detected() {
+        let (node, private_key) = random_node(1);
+        let packet = OutfoxPacket::new(
+            b"foomp",
+            256,
+            &[node],
+            &test_destination(),
+            &[Delay::new_from_nanos(0)],
+        )
+        .unwrap();
+
+        let mut bytes = packet.to_bytes();
+        bytes[OUTFOX_HEADER_SIZE + 100] ^= 1;
+        let tampered = OutfoxPacket::from_bytes(&bytes).unwrap();
+        assert!(matches!(
+            tampered.process(&private_key),
+            Err(OutfoxError::TamperedPayload)
+        ));
+    }
+
+    #[test]
+    fn too_long_routes_are_rejected() {
+        let route: Vec<_> = (1..=MAX_FORWARD_HOPS as u8 + 2)
+            .map(|i| random_node(i).0)
+            .collect();
+        let delays = vec![Delay::new_from_nanos(0); route.len()];
+        assert!(matches!(
+            OutfoxPacket::new(b"foomp", 256, &route, &test_destination(), &delays),
+            Err(OutfoxError::InvalidRouteLength(..))
+        ));
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use super::error::OutfoxError;
+use super::packet::{build_header, encrypt_payload, OutfoxPacket};
+use super::{LAYER_KEY_SIZE, OUTFOX_HEADER_SIZE};
+use crate::{Delay, Destination, Node, NodeAddressBytes, NODE_ADDRESS_LENGTH};
+use std::convert::TryInto;
+
+/// First byte of serialized outfox SURB, which makes it distinguishable from the sphinx ones.
+const OUTFOX_SURB_MARKER: u8 = 0x0F;
+
+/// Single use reply block allowing to send an outfox packet back to its creator,
+/// without the sender learning anything about the route.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutfoxSurb {
+    first_hop: NodeAddressBytes,
+    header: Vec<u8>,
+    payload_keys: Vec<[u8; LAYER_KEY_SIZE]>,
+}
+
+impl OutfoxSurb {
+    pub fn new(
+        route: &[Node],
+        destination: &Destination,
+        delays: &[Delay],
+    ) -> Result<Self, OutfoxError> {
+        let first_hop = route
+            .first()
+            .ok_or(OutfoxError::InvalidRouteLength(0))?
+            .address;
+        let (header, payload_keys) = build_header(route, destination, delays)?;
+
+        Ok(OutfoxSurb {
+            first_hop,
+            header,
+            payload_keys,
+        })
+    }
+
+    /// Serialized length of the SURB with the provided number of hops, including the destination.
+    pub fn serialized_len(hops: usize) -> usize {
+        1 + NODE_ADDRESS_LENGTH + OUTFOX_HEADER_SIZE + hops * LAYER_KEY_SIZE
+    }
+
+    /// Checks whether the bytes look like a serialized outfox SURB.
+    pub fn is_outfox_surb(bytes: &[u8]) -> bool {
+        bytes.len() > Self::serialized_len(0)
+            && bytes[0] == OUTFOX_SURB_MARKER
+            && (bytes.len() - Self::serialized_len(0)) % LAYER_KEY_SIZE == 0
+    }
+
+    /// Creates the packet carrying the message back to the creator of the SURB alongside
+    /// the address of its first hop.
+    pub fn use_surb(
+        self,
+        message: &[u8],
+        payload_size: usize,
+    ) -> Result<(OutfoxPacket, NodeAddressBytes), OutfoxError> {
+        let payload = encrypt_payload(message, payload_size, &self.payload_keys)?;
+        Ok((
+            OutfoxPacket::from_parts(self.header, payload),
+            self.first_hop,
+        ))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::serialized_len(self.payload_keys.len()));
+        bytes.push(OUTFOX_SURB_MARKER);
+        bytes.extend_from_slice(self.first_hop.as_bytes_ref());
+        bytes.extend_from_slice(&self.header);
+        for key in &self.payload_keys {
+            bytes.extend_from_slice(key);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OutfoxError> {
+        if !Self::is_outfox_surb(bytes) {
+            return Err(OutfoxError::MalformedSurb);
+        }
+
+        let header_start = 1 + NODE_ADDRESS_LENGTH;
+        let keys_start = header_start + OUTFOX_HEADER_SIZE;
+        Ok(OutfoxSurb {
+            first_hop: NodeAddressBytes::from_bytes(bytes[1..header_start].try_into().unwrap()),
+            header: bytes[header_start..keys_start].to_vec(),
+            payload_keys: bytes[keys_start..]
+                .chunks_exact(LAYER_KEY_SIZE)
+                .map(|key| key.try_into().unwrap())
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outfox::packet::tests::{random_node, test_destination};
+    use crate::outfox::OutfoxProcessedPacket;
+
+    #[test]
+    fn reply_reaches_the_creator_of_surb() {
+        let nodes: Vec<_> = (1..=4).map(random_node).collect();
+        let route: Vec<_> = nodes.iter().map(|(node, _)| node.clone()).collect();
+        let delays = vec![Delay::new_from_nanos(42); route.len()];
+
+        let surb = OutfoxSurb::new(&route, &test_destination(), &delays).unwrap();
+        let serialized = surb.to_bytes();
+        assert_eq!(serialized.len(), OutfoxSurb::serialized_len(4));
+        let surb = OutfoxSurb::from_bytes(&serialized).unwrap();
+
+        let (mut packet, first_hop) = surb.use_surb(b"reply", 512).unwrap();
+        assert_eq!(first_hop, route[0].address);
+
+        for (_, private_key) in &nodes[..3] {
+            match packet.process(private_key).unwrap() {
+                OutfoxProcessedPacket::ForwardHop(next, ..) => packet = *next,
+                _ => panic!("expected forward hop"),
+            }
+        }
+        match packet.process(&nodes[3].1).unwrap() {
+            OutfoxProcessedPacket::FinalHop(_, plaintext) => assert_eq!(plaintext, b"reply"),
+            _ => panic!("expected final hop"),
+        }
+    }
+}
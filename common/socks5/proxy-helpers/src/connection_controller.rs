@@ -35,6 +35,10 @@ pub enum ControllerCommand {
     Insert(ConnectionId, ConnectionSender),
     Remove(ConnectionId),
     Send(ConnectionId, Vec<u8>, bool),
+
+    /// Closes the local socket of the connection, for example because the data sent over it
+    /// could not be delivered.
+    Abort(ConnectionId),
 }
 
 struct ActiveConnection {
@@ -140,6 +144,21 @@ impl Controller {
         self.closed_connection_tx.unbounded_send(conn_id).unwrap();
     }
 
+    fn abort_connection(&mut self, conn_id: ConnectionId) {
+        if let Some(active_connection) = self.active_connections.get_mut(&conn_id) {
+            debug!("Aborting connection {}", conn_id);
+            active_connection.is_closed = true;
+            // the connection is going to be removed once its local socket is closed
+            if let Some(connection_sender) = &active_connection.connection_sender {
+                // the connection might have already finished on its own
+                let _ = connection_sender.unbounded_send(ConnectionMessage {
+                    payload: Vec::new(),
+                    socket_closed: true,
+                });
+            }
+        }
+    }
+
     fn send_to_connection(&mut self, conn_id: ConnectionId, payload: Vec<u8>, is_closed: bool) {
         if let Some(active_connection) = self.active_connections.get_mut(&conn_id) {
             if !payload.is_empty() {
@@ -206,6 +225,7 @@ impl Controller {
                         self.insert_connection(conn_id, sender)
                     }
                     Some(ControllerCommand::Remove(conn_id)) => self.remove_connection(conn_id),
+                    Some(ControllerCommand::Abort(conn_id)) => self.abort_connection(conn_id),
                     None => {
                         log::trace!("SOCKS5 Controller: Stopping since channel closed");
                         break;
@@ -4,3 +4,4 @@
 pub mod available_reader;
 pub mod connection_controller;
 pub mod proxy_runner;
+pub mod send_window;
@@ -4,6 +4,7 @@
 use super::MixProxySender;
 use super::SHUTDOWN_TIMEOUT;
 use crate::available_reader::AvailableReader;
+use crate::send_window::SendWindow;
 use bytes::Bytes;
 use futures::FutureExt;
 use futures::StreamExt;
@@ -29,11 +30,14 @@ fn send_empty_close<F, S>(
         .unwrap();
 }
 
+#[allow(clippy::too_many_arguments)]
 fn deal_with_data<F, S>(
     read_data: Option<io::Result<Bytes>>,
     local_destination_address: &str,
     remote_source_address: &str,
     connection_id: ConnectionId,
+    max_data_size: Option<usize>,
+    send_window: Option<&SendWindow>,
     message_sender: &mut OrderedMessageSender,
     mix_sender: &MixProxySender<S>,
     adapter_fn: F,
@@ -61,15 +65,34 @@ where
         is_finished
     );
 
-    // if we're sending through the mixnet increase the sequence number...
-    let ordered_msg = message_sender.wrap_message(read_data.to_vec()).into_bytes();
-    log::trace!(
-        "pushing data down the input sender: size: {}",
-        ordered_msg.len()
-    );
-    mix_sender
-        .unbounded_send(adapter_fn(connection_id, ordered_msg, is_finished))
-        .unwrap();
+    // if the data doesn't fit in a single message, split it into multiple ones, making sure
+    // only the last one is marked as closing the connection
+    let chunks: Vec<_> = match max_data_size {
+        Some(max_data_size) if read_data.len() > max_data_size => {
+            read_data.chunks(max_data_size).collect()
+        }
+        _ => vec![read_data.as_ref()],
+    };
+    let last_chunk = chunks.len() - 1;
+
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        // if we're sending through the mixnet increase the sequence number...
+        let ordered_msg = message_sender.wrap_message(chunk.to_vec()).into_bytes();
+        if let Some(send_window) = send_window {
+            send_window.consume(ordered_msg.len());
+        }
+        log::trace!(
+            "pushing data down the input sender: size: {}",
+            ordered_msg.len()
+        );
+        mix_sender
+            .unbounded_send(adapter_fn(
+                connection_id,
+                ordered_msg,
+                is_finished && i == last_chunk,
+            ))
+            .unwrap();
+    }
 
     if is_finished {
         // technically we already informed it when we sent the message to mixnet above
@@ -79,12 +102,28 @@ where
     is_finished
 }
 
+// if there's a send window, waits until there's room in it before reading more data.
+// `None` is returned if the window got closed and hence nothing more should be read.
+async fn read_within_window(
+    available_reader: &mut AvailableReader<'_, OwnedReadHalf>,
+    send_window: Option<&SendWindow>,
+) -> Option<Option<io::Result<Bytes>>> {
+    if let Some(send_window) = send_window {
+        if !send_window.wait_for_room().await {
+            return None;
+        }
+    }
+    Some(available_reader.next().await)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub(super) async fn run_inbound<F, S>(
     mut reader: OwnedReadHalf,
     local_destination_address: String, // addresses are provided for better logging
     remote_source_address: String,
     connection_id: ConnectionId,
+    max_data_size: Option<usize>,
+    send_window: Option<SendWindow>,
     mix_sender: MixProxySender<S>,
     adapter_fn: F,
     shutdown_notify: Arc<Notify>,
@@ -101,8 +140,14 @@ where
 
     loop {
         select! {
-            read_data = &mut available_reader.next() => {
-                if deal_with_data(read_data, &local_destination_address, &remote_source_address, connection_id, &mut message_sender, &mix_sender, &adapter_fn) {
+            read_data = read_within_window(&mut available_reader, send_window.as_ref()) => match read_data {
+                Some(read_data) => {
+                    if deal_with_data(read_data, &local_destination_address, &remote_source_address, connection_id, max_data_size, send_window.as_ref(), &mut message_sender, &mix_sender, &adapter_fn) {
+                        break
+                    }
+                }
+                None => {
+                    debug!(target: &*format!("({}) socks5 inbound", connection_id), "The send window got closed - won't read any more data");
                     break
                 }
             }
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::connection_controller::ConnectionReceiver;
+use crate::send_window::SendWindow;
 use futures::channel::mpsc;
 use socks5_requests::ConnectionId;
 use std::{sync::Arc, time::Duration};
@@ -46,6 +47,12 @@ pub struct ProxyRunner<S> {
     remote_source_address: String,
     connection_id: ConnectionId,
 
+    /// optional limit on the amount of data read from the socket put into a single message
+    max_data_size: Option<usize>,
+
+    /// optional window limiting the amount of data read from the socket that is yet to be sent on
+    send_window: Option<SendWindow>,
+
     // Listens to shutdown commands from higher up
     shutdown_listener: ShutdownListener,
 }
@@ -70,10 +77,27 @@ where
             local_destination_address,
             remote_source_address,
             connection_id,
+            max_data_size: None,
+            send_window: None,
             shutdown_listener,
         }
     }
 
+    /// Limits the amount of data read from the socket that is put into a single message,
+    /// splitting anything bigger into multiple consecutive messages. Useful when the messages
+    /// can't be fragmented further down the line, such as when they're sent using reply SURBs.
+    pub fn with_max_data_size(mut self, max_data_size: usize) -> Self {
+        self.max_data_size = Some(max_data_size);
+        self
+    }
+
+    /// Stops reading from the socket whenever the provided window is full, i.e. too much of
+    /// the already read data is yet to be sent on, and stops the proxy once the window is closed.
+    pub fn with_send_window(mut self, send_window: SendWindow) -> Self {
+        self.send_window = Some(send_window);
+        self
+    }
+
     // The `adapter_fn` is used to transform whatever was read into appropriate
     // request/response as required by entity running particular side of the proxy.
     pub async fn run<F>(mut self, adapter_fn: F) -> Self
@@ -89,6 +113,8 @@ where
             self.local_destination_address.clone(),
             self.remote_source_address.clone(),
             self.connection_id,
+            self.max_data_size,
+            self.send_window.clone(),
             self.mix_sender.clone(),
             adapter_fn,
             Arc::clone(&shutdown_notify),
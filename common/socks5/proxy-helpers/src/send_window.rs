@@ -0,0 +1,134 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Keeps track of the amount of data that was read from a socket but is yet to be sent on,
+/// so that the socket would not be read from while too much of it is still queued.
+/// The window is shared between the proxy reading the socket and whatever is sending the data on.
+#[derive(Clone, Debug)]
+pub struct SendWindow {
+    inner: Arc<SendWindowInner>,
+}
+
+#[derive(Debug)]
+struct SendWindowInner {
+    capacity: usize,
+    queued: AtomicUsize,
+    closed: AtomicBool,
+    changed: Notify,
+}
+
+impl SendWindow {
+    pub fn new(capacity: usize) -> Self {
+        SendWindow {
+            inner: Arc::new(SendWindowInner {
+                capacity,
+                queued: AtomicUsize::new(0),
+                closed: AtomicBool::new(false),
+                changed: Notify::new(),
+            }),
+        }
+    }
+
+    /// Records the data that was read from the socket.
+    pub fn consume(&self, bytes: usize) {
+        self.inner.queued.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    /// Records the data that is no longer queued, as it was either sent on or dropped.
+    pub fn release(&self, bytes: usize) {
+        let _ = self
+            .inner
+            .queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                Some(queued.saturating_sub(bytes))
+            });
+        self.inner.changed.notify_one();
+    }
+
+    /// Closes the window, indicating no more data is going to be sent on and hence the socket
+    /// should no longer be read from.
+    pub fn close(&self) {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        self.inner.changed.notify_one();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.closed.load(Ordering::SeqCst)
+    }
+
+    pub fn queued(&self) -> usize {
+        self.inner.queued.load(Ordering::SeqCst)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.queued() >= self.inner.capacity
+    }
+
+    /// Waits until more data can be read from the socket. Returns `false` if the window got
+    /// closed instead.
+    pub async fn wait_for_room(&self) -> bool {
+        // note: there's only ever a single task waiting on the window, so `notify_one` storing
+        // the permit if nobody is waiting at the time guarantees no change is ever missed
+        loop {
+            if self.is_closed() {
+                return false;
+            }
+            if !self.is_full() {
+                return true;
+            }
+            self.inner.changed.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    const WAIT: Duration = Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn reading_resumes_once_queued_data_is_released() {
+        let window = SendWindow::new(100);
+        assert!(window.wait_for_room().await);
+
+        window.consume(150);
+        assert!(window.is_full());
+        assert!(timeout(WAIT, window.wait_for_room()).await.is_err());
+
+        let waiting_window = window.clone();
+        let waiting = tokio::spawn(async move { waiting_window.wait_for_room().await });
+        window.release(100);
+        assert!(timeout(WAIT, waiting).await.unwrap().unwrap());
+        assert_eq!(window.queued(), 50);
+    }
+
+    #[tokio::test]
+    async fn closing_stops_the_reading() {
+        let window = SendWindow::new(100);
+        window.consume(100);
+
+        let waiting_window = window.clone();
+        let waiting = tokio::spawn(async move { waiting_window.wait_for_room().await });
+        window.close();
+        assert!(!timeout(WAIT, waiting).await.unwrap().unwrap());
+
+        // even if there's room again
+        window.release(100);
+        assert!(!window.wait_for_room().await);
+    }
+
+    #[test]
+    fn releasing_never_underflows() {
+        let window = SendWindow::new(100);
+        window.consume(10);
+        window.release(20);
+        assert_eq!(window.queued(), 0);
+    }
+}
@@ -3,10 +3,12 @@
 
 pub mod msg;
 pub mod network_requester_response;
+pub mod reply_surbs_request;
 pub mod request;
 pub mod response;
 
 pub use msg::*;
 pub use network_requester_response::*;
+pub use reply_surbs_request::ReplySurbsRequest;
 pub use request::*;
 pub use response::*;
@@ -4,6 +4,7 @@
 use thiserror::Error;
 
 use crate::network_requester_response::{Error as NrError, NetworkRequesterResponse};
+use crate::reply_surbs_request::{Error as ReplySurbsRequestError, ReplySurbsRequest};
 use crate::request::{Request, RequestError};
 use crate::response::{Response, ResponseError};
 
@@ -18,6 +19,9 @@ pub enum MessageError {
     #[error("{0}")]
     NetworkRequesterResponseError(NrError),
 
+    #[error("{0}")]
+    ReplySurbsRequest(ReplySurbsRequestError),
+
     #[error("no data")]
     NoData,
 
@@ -29,32 +33,37 @@ pub enum Message {
     Request(Request),
     Response(Response),
     NetworkRequesterResponse(NetworkRequesterResponse),
+    ReplySurbsRequest(ReplySurbsRequest),
 }
 
 impl Message {
     const REQUEST_FLAG: u8 = 0;
     const RESPONSE_FLAG: u8 = 1;
     const NR_RESPONSE_FLAG: u8 = 2;
+    const REPLY_SURBS_REQUEST_FLAG: u8 = 3;
 
     pub fn conn_id(&self) -> u64 {
         match self {
             Message::Request(req) => match req {
                 Request::Connect(c) => c.conn_id,
                 Request::Send(conn_id, _, _) => *conn_id,
+                Request::ConnectAnonymous(c) => c.conn_id,
+                Request::ReplySurbs(conn_id, _) => *conn_id,
             },
             Message::Response(resp) => resp.connection_id,
             Message::NetworkRequesterResponse(resp) => resp.connection_id,
+            Message::ReplySurbsRequest(req) => req.connection_id,
         }
     }
 
     pub fn size(&self) -> usize {
         match self {
             Message::Request(req) => match req {
-                Request::Connect(_) => 0,
+                Request::Connect(_) | Request::ConnectAnonymous(_) | Request::ReplySurbs(..) => 0,
                 Request::Send(_, data, _) => data.len(),
             },
             Message::Response(resp) => resp.data.len(),
-            Message::NetworkRequesterResponse(_) => 0,
+            Message::NetworkRequesterResponse(_) | Message::ReplySurbsRequest(_) => 0,
         }
     }
 
@@ -75,6 +84,10 @@ impl Message {
             NetworkRequesterResponse::try_from_bytes(&b[1..])
                 .map(Message::NetworkRequesterResponse)
                 .map_err(MessageError::NetworkRequesterResponseError)
+        } else if b[0] == Self::REPLY_SURBS_REQUEST_FLAG {
+            ReplySurbsRequest::try_from_bytes(&b[1..])
+                .map(Message::ReplySurbsRequest)
+                .map_err(MessageError::ReplySurbsRequest)
         } else {
             Err(MessageError::UnknownMessageType)
         }
@@ -91,6 +104,9 @@ impl Message {
             Self::NetworkRequesterResponse(r) => std::iter::once(Self::NR_RESPONSE_FLAG)
                 .chain(r.into_bytes().iter().cloned())
                 .collect(),
+            Self::ReplySurbsRequest(r) => std::iter::once(Self::REPLY_SURBS_REQUEST_FLAG)
+                .chain(r.into_bytes().iter().cloned())
+                .collect(),
         }
     }
 }
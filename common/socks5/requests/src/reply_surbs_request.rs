@@ -0,0 +1,82 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::ConnectionId;
+
+/// Request sent by the service provider, through one of the reply SURBs of an anonymous
+/// connection, asking the requester for more of them as it is about to run out.
+#[derive(Debug)]
+pub struct ReplySurbsRequest {
+    pub connection_id: ConnectionId,
+    pub amount: u32,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum Error {
+    #[error("no data provided")]
+    NoData,
+
+    #[error("the request has invalid length")]
+    InvalidLength,
+}
+
+impl ReplySurbsRequest {
+    pub fn new(connection_id: ConnectionId, amount: u32) -> Self {
+        ReplySurbsRequest {
+            connection_id,
+            amount,
+        }
+    }
+
+    pub fn try_from_bytes(b: &[u8]) -> Result<ReplySurbsRequest, Error> {
+        if b.is_empty() {
+            return Err(Error::NoData);
+        }
+
+        if b.len() != 12 {
+            return Err(Error::InvalidLength);
+        }
+
+        let connection_id = u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]);
+        let amount = u32::from_be_bytes([b[8], b[9], b[10], b[11]]);
+
+        Ok(ReplySurbsRequest {
+            connection_id,
+            amount,
+        })
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.connection_id
+            .to_be_bytes()
+            .iter()
+            .cloned()
+            .chain(self.amount.to_be_bytes().iter().cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialization_reverses_serialization() {
+        let request = ReplySurbsRequest::new(42, 100);
+        let recovered = ReplySurbsRequest::try_from_bytes(&request.into_bytes()).unwrap();
+        assert_eq!(recovered.connection_id, 42);
+        assert_eq!(recovered.amount, 100);
+    }
+
+    #[test]
+    fn fails_when_bytes_have_invalid_length() {
+        assert_eq!(
+            Error::NoData,
+            ReplySurbsRequest::try_from_bytes(&[]).unwrap_err()
+        );
+        assert_eq!(
+            Error::InvalidLength,
+            ReplySurbsRequest::try_from_bytes(&[0, 1, 2, 3, 4, 5, 6, 7, 0, 0]).unwrap_err()
+        );
+    }
+}
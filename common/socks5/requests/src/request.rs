@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use nymsphinx_addressing::clients::{Recipient, RecipientFormattingError};
+use nymsphinx_anonymous_replies::{ReplySurb, ReplySurbError};
 use std::convert::TryFrom;
 use thiserror::Error;
 
@@ -13,6 +14,8 @@ pub type RemoteAddress = String;
 pub enum RequestFlag {
     Connect = 0,
     Send = 1,
+    ConnectAnonymous = 2,
+    ReplySurbs = 3,
 }
 
 #[derive(Debug, Error)]
@@ -37,6 +40,12 @@ pub enum RequestError {
 
     #[error("malformed return address - {0}")]
     MalformedReturnAddress(RecipientFormattingError),
+
+    #[error("not enough bytes to recover the reply SURBs")]
+    ReplySurbsTooShort,
+
+    #[error("malformed reply SURB - {0}")]
+    MalformedReplySurb(ReplySurbError),
 }
 
 impl RequestError {
@@ -52,6 +61,8 @@ impl TryFrom<u8> for RequestFlag {
         match value {
             _ if value == (RequestFlag::Connect as u8) => Ok(Self::Connect),
             _ if value == (RequestFlag::Send as u8) => Ok(Self::Send),
+            _ if value == (RequestFlag::ConnectAnonymous as u8) => Ok(Self::ConnectAnonymous),
+            _ if value == (RequestFlag::ReplySurbs as u8) => Ok(Self::ReplySurbs),
             _ => Err(RequestError::UnknownRequestFlag),
         }
     }
@@ -64,6 +75,13 @@ pub struct ConnectRequest {
     pub return_address: Recipient,
 }
 
+#[derive(Debug)]
+pub struct AnonymousConnectRequest {
+    pub conn_id: ConnectionId,
+    pub remote_addr: RemoteAddress,
+    pub reply_surbs: Vec<ReplySurb>,
+}
+
 /// A request from a SOCKS5 client that a Nym Socks5 service provider should
 /// take an action for an application using a (probably local) Nym Socks5 proxy.
 #[derive(Debug)]
@@ -75,6 +93,14 @@ pub enum Request {
 
     /// Re-use an existing TCP connection, sending more request data up it.
     Send(ConnectionId, Vec<u8>, bool),
+
+    /// Same as `Connect`, but the requester does not reveal its address. Instead, all responses
+    /// produced on this `ConnectionId` should be sent back using the provided reply SURBs.
+    ConnectAnonymous(Box<AnonymousConnectRequest>),
+
+    /// Provides additional reply SURBs for an anonymous connection, usually after being
+    /// asked for them by the service provider.
+    ReplySurbs(ConnectionId, Vec<ReplySurb>),
 }
 
 impl Request {
@@ -96,6 +122,93 @@ impl Request {
         Request::Send(conn_id, data, local_closed)
     }
 
+    /// Construct a new Request::ConnectAnonymous instance
+    pub fn new_connect_anonymous(
+        conn_id: ConnectionId,
+        remote_addr: RemoteAddress,
+        reply_surbs: Vec<ReplySurb>,
+    ) -> Request {
+        Request::ConnectAnonymous(Box::new(AnonymousConnectRequest {
+            conn_id,
+            remote_addr,
+            reply_surbs,
+        }))
+    }
+
+    /// Construct a new Request::ReplySurbs instance
+    pub fn new_reply_surbs(conn_id: ConnectionId, reply_surbs: Vec<ReplySurb>) -> Request {
+        Request::ReplySurbs(conn_id, reply_surbs)
+    }
+
+    /// Recovers the remote address prefixed with its length, returning it alongside
+    /// the remaining bytes.
+    fn parse_remote_address(b: &[u8]) -> Result<(RemoteAddress, &[u8]), RequestError> {
+        // we need to be able to read at least 2 bytes that specify address length
+        if b.len() < 2 {
+            return Err(RequestError::AddressLengthTooShort);
+        }
+
+        let address_length = u16::from_be_bytes([b[0], b[1]]) as usize;
+
+        if b.len() < 2 + address_length {
+            return Err(RequestError::AddressTooShort);
+        }
+
+        let address_start = 2;
+        let address_end = address_start + address_length;
+        let address_bytes = &b[address_start..address_end];
+        let remote_address = String::from_utf8_lossy(address_bytes).to_string();
+
+        Ok((remote_address, &b[address_end..]))
+    }
+
+    /// Recovers the reply SURBs serialized with [`Request::serialize_reply_surbs`].
+    /// All of the provided bytes must be consumed.
+    fn parse_reply_surbs(b: &[u8]) -> Result<Vec<ReplySurb>, RequestError> {
+        if b.len() < 2 {
+            return Err(RequestError::ReplySurbsTooShort);
+        }
+        let num_surbs = u16::from_be_bytes([b[0], b[1]]) as usize;
+
+        let mut remaining = &b[2..];
+        let mut reply_surbs = Vec::with_capacity(num_surbs);
+        for _ in 0..num_surbs {
+            if remaining.len() < 2 {
+                return Err(RequestError::ReplySurbsTooShort);
+            }
+            let surb_len = u16::from_be_bytes([remaining[0], remaining[1]]) as usize;
+            if remaining.len() < 2 + surb_len {
+                return Err(RequestError::ReplySurbsTooShort);
+            }
+
+            let reply_surb = ReplySurb::from_bytes(&remaining[2..2 + surb_len])
+                .map_err(RequestError::MalformedReplySurb)?;
+            reply_surbs.push(reply_surb);
+            remaining = &remaining[2 + surb_len..];
+        }
+
+        if !remaining.is_empty() {
+            return Err(RequestError::ReplySurbsTooShort);
+        }
+        Ok(reply_surbs)
+    }
+
+    /// Serializes the reply SURBs as NUM_SURBS || (SURB_LEN || SURB)*
+    fn serialize_reply_surbs(reply_surbs: Vec<ReplySurb>) -> Vec<u8> {
+        let num_surbs = reply_surbs.len() as u16;
+        num_surbs
+            .to_be_bytes()
+            .into_iter()
+            .chain(reply_surbs.into_iter().flat_map(|reply_surb| {
+                let surb_bytes = reply_surb.to_bytes();
+                (surb_bytes.len() as u16)
+                    .to_be_bytes()
+                    .into_iter()
+                    .chain(surb_bytes.into_iter())
+            }))
+            .collect()
+    }
+
     /// Deserialize the request type, connection id, destination address and port,
     /// and the request body from bytes.
     ///
@@ -109,6 +222,10 @@ impl Request {
     /// The request_flag tells us whether this is a new connection request (`new_connect`),
     /// an already-established connection we should send up (`new_send`), or
     /// a request to close an established connection (`new_close`).
+    ///
+    /// Anonymous connection requests (`new_connect_anonymous`) replace the return address
+    /// with a batch of reply SURBs, while `new_reply_surbs` carries just the SURBs
+    /// after the connection id.
     pub fn try_from_bytes(b: &[u8]) -> Result<Request, RequestError> {
         // each request needs to at least contain flag and ConnectionId
         if b.is_empty() {
@@ -121,28 +238,8 @@ impl Request {
         let connection_id = u64::from_be_bytes([b[1], b[2], b[3], b[4], b[5], b[6], b[7], b[8]]);
         match RequestFlag::try_from(b[0])? {
             RequestFlag::Connect => {
-                let connect_request_bytes = &b[9..];
-
-                // we need to be able to read at least 2 bytes that specify address length
-                if connect_request_bytes.len() < 2 {
-                    return Err(RequestError::AddressLengthTooShort);
-                }
-
-                let address_length =
-                    u16::from_be_bytes([connect_request_bytes[0], connect_request_bytes[1]])
-                        as usize;
-
-                if connect_request_bytes.len() < 2 + address_length {
-                    return Err(RequestError::AddressTooShort);
-                }
-
-                let address_start = 2;
-                let address_end = address_start + address_length;
-                let address_bytes = &connect_request_bytes[address_start..address_end];
-                let remote_address = String::from_utf8_lossy(address_bytes).to_string();
-
                 // just a temporary reference to mid-slice for ease of use
-                let recipient_data_bytes = &connect_request_bytes[address_end..];
+                let (remote_address, recipient_data_bytes) = Self::parse_remote_address(&b[9..])?;
 
                 if recipient_data_bytes.len() != Recipient::LEN {
                     return Err(RequestError::ReturnAddressTooShort);
@@ -165,6 +262,21 @@ impl Request {
 
                 Ok(Request::Send(connection_id, data, local_closed))
             }
+            RequestFlag::ConnectAnonymous => {
+                let (remote_address, reply_surbs_bytes) = Self::parse_remote_address(&b[9..])?;
+                let reply_surbs = Self::parse_reply_surbs(reply_surbs_bytes)?;
+
+                Ok(Request::new_connect_anonymous(
+                    connection_id,
+                    remote_address,
+                    reply_surbs,
+                ))
+            }
+            RequestFlag::ReplySurbs => {
+                let reply_surbs = Self::parse_reply_surbs(&b[9..])?;
+
+                Ok(Request::ReplySurbs(connection_id, reply_surbs))
+            }
         }
     }
 
@@ -190,6 +302,24 @@ impl Request {
                 .chain(std::iter::once(local_closed as u8))
                 .chain(data.into_iter())
                 .collect(),
+            // anonymous connect is: CONN_ANON_FLAG || CONN_ID || REMOTE_LEN || REMOTE || REPLY_SURBS
+            Request::ConnectAnonymous(req) => {
+                let remote_address_bytes = req.remote_addr.into_bytes();
+                let remote_address_bytes_len = remote_address_bytes.len() as u16;
+
+                std::iter::once(RequestFlag::ConnectAnonymous as u8)
+                    .chain(req.conn_id.to_be_bytes().iter().cloned())
+                    .chain(remote_address_bytes_len.to_be_bytes().iter().cloned())
+                    .chain(remote_address_bytes.into_iter())
+                    .chain(Self::serialize_reply_surbs(req.reply_surbs).into_iter())
+                    .collect()
+            }
+            Request::ReplySurbs(conn_id, reply_surbs) => {
+                std::iter::once(RequestFlag::ReplySurbs as u8)
+                    .chain(conn_id.to_be_bytes().iter().cloned())
+                    .chain(Self::serialize_reply_surbs(reply_surbs).into_iter())
+                    .collect()
+            }
         }
     }
 }
@@ -476,4 +606,68 @@ mod request_deserialization_tests {
             }
         }
     }
+
+    #[cfg(test)]
+    mod anonymous_connections {
+        use super::*;
+
+        #[test]
+        fn connect_request_without_surbs_is_recovered() {
+            let request = Request::new_connect_anonymous(42, "foo.com".to_string(), Vec::new());
+            match Request::try_from_bytes(&request.into_bytes()).unwrap() {
+                Request::ConnectAnonymous(req) => {
+                    assert_eq!("foo.com".to_string(), req.remote_addr);
+                    assert_eq!(42, req.conn_id);
+                    assert!(req.reply_surbs.is_empty());
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn returns_error_when_surbs_are_missing() {
+            // this one has "foo.com" remote address, correct 8 bytes of connection_id
+            // and claims to have a single reply SURB of 100 bytes
+            let request_bytes = [
+                RequestFlag::ConnectAnonymous as u8,
+                1,
+                2,
+                3,
+                4,
+                5,
+                6,
+                7,
+                8,
+                0,
+                7,
+                102,
+                111,
+                111,
+                46,
+                99,
+                111,
+                109,
+                0,
+                1,
+                0,
+                100,
+            ];
+            match Request::try_from_bytes(&request_bytes).unwrap_err() {
+                RequestError::ReplySurbsTooShort => {}
+                _ => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn returns_error_when_surb_is_malformed() {
+            let request_bytes: Vec<_> = [RequestFlag::ReplySurbs as u8, 1, 2, 3, 4, 5, 6, 7, 8]
+                .into_iter()
+                .chain([0, 1, 0, 3, 255, 255, 255])
+                .collect();
+            match Request::try_from_bytes(&request_bytes).unwrap_err() {
+                RequestError::MalformedReplySurb(_) => {}
+                _ => unreachable!(),
+            }
+        }
+    }
 }
@@ -1,9 +1,10 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::sphinx_keys::{self, AnnouncedSphinxKeys};
 use crate::{filter, NetworkAddress};
 use crypto::asymmetric::{encryption, identity};
-use mixnet_contract_common::GatewayBond;
+use mixnet_contract_common::{GatewayBond, SphinxKeyAnnouncement, SphinxKeyEpoch};
 use nymsphinx_addressing::nodes::{NodeIdentity, NymNodeRoutingAddress};
 use nymsphinx_types::Node as SphinxNode;
 use std::convert::{TryFrom, TryInto};
@@ -78,8 +79,11 @@ pub struct Node {
     // hostname every time we want to construct a path via this node
     pub mix_host: SocketAddr,
     pub clients_port: u16,
+    pub clients_wss_port: Option<u16>,
     pub identity_key: identity::PublicKey,
     pub sphinx_key: encryption::PublicKey, // TODO: or nymsphinx::PublicKey? both are x25519
+    // verified rotated sphinx keys the gateway announced for particular epochs
+    pub announced_sphinx_keys: AnnouncedSphinxKeys,
     pub version: String,
 }
 
@@ -88,8 +92,40 @@ impl Node {
         &self.identity_key
     }
 
-    pub fn clients_address(&self) -> String {
-        format!("ws://{}:{}", self.host, self.clients_port)
+    /// Returns the websocket address clients should connect to. If `prefer_tls` is set and
+    /// the gateway announced a TLS endpoint, the `wss://` address is returned instead
+    /// of the plain one.
+    pub fn clients_address(&self, prefer_tls: bool) -> String {
+        match self.clients_wss_port {
+            Some(wss_port) if prefer_tls => format!("wss://{}:{}", self.host, wss_port),
+            _ => format!("ws://{}:{}", self.host, self.clients_port),
+        }
+    }
+
+    pub fn supports_tls(&self) -> bool {
+        self.clients_wss_port.is_some()
+    }
+
+    /// Returns the sphinx key that should be used for packets created during the provided epoch.
+    /// If the gateway has not announced a key for it (or the epoch is unknown), its static key
+    /// is used instead.
+    pub fn sphinx_key_for_epoch(&self, epoch: Option<SphinxKeyEpoch>) -> &encryption::PublicKey {
+        sphinx_keys::sphinx_key_for_epoch(&self.sphinx_key, &self.announced_sphinx_keys, epoch)
+    }
+
+    /// Attaches the rotated sphinx keys announced by this gateway, ignoring the ones that were
+    /// not signed with its identity key.
+    pub fn set_announced_sphinx_keys(&mut self, announcements: &[SphinxKeyAnnouncement]) {
+        self.announced_sphinx_keys =
+            sphinx_keys::verify_announced_sphinx_keys(&self.identity_key, announcements);
+    }
+
+    pub fn to_sphinx_node(&self, epoch: Option<SphinxKeyEpoch>) -> SphinxNode {
+        let node_address_bytes = NymNodeRoutingAddress::from(self.mix_host)
+            .try_into()
+            .unwrap();
+
+        SphinxNode::new(node_address_bytes, self.sphinx_key_for_epoch(epoch).into())
     }
 }
 
@@ -111,11 +147,7 @@ impl filter::Versioned for Node {
 
 impl<'a> From<&'a Node> for SphinxNode {
     fn from(node: &'a Node) -> Self {
-        let node_address_bytes = NymNodeRoutingAddress::from(node.mix_host)
-            .try_into()
-            .unwrap();
-
-        SphinxNode::new(node_address_bytes, (&node.sphinx_key).into())
+        node.to_sphinx_node(None)
     }
 }
 
@@ -140,8 +172,10 @@ impl<'a> TryFrom<&'a GatewayBond> for Node {
             host,
             mix_host,
             clients_port: bond.gateway.clients_port,
+            clients_wss_port: bond.gateway.clients_wss_port,
             identity_key: identity::PublicKey::from_base58_string(&bond.gateway.identity_key)?,
             sphinx_key: encryption::PublicKey::from_base58_string(&bond.gateway.sphinx_key)?,
+            announced_sphinx_keys: Default::default(),
             version: bond.gateway.version.clone(),
         })
     }
@@ -154,3 +188,41 @@ impl TryFrom<GatewayBond> for Node {
         Node::try_from(&bond)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gateway(clients_wss_port: Option<u16>) -> Node {
+        Node {
+            owner: "N/A".to_string(),
+            stake: 0,
+            location: "N/A".to_string(),
+            host: "3.3.3.3".parse().unwrap(),
+            mix_host: "3.3.3.3:1789".parse().unwrap(),
+            clients_port: 9000,
+            clients_wss_port,
+            identity_key: identity::PublicKey::from_base58_string(
+                "3ebjp1Fb9hdcS1AR6AZihgeJiMHkB5jjJUsvqNnfQwU7",
+            )
+            .unwrap(),
+            sphinx_key: encryption::PublicKey::from_base58_string(
+                "C7cown6dYCLZpLiMFC1PaBmhvLvmJmLDJGeRTbPD45bX",
+            )
+            .unwrap(),
+            announced_sphinx_keys: Default::default(),
+            version: "0.x.0".to_string(),
+        }
+    }
+
+    #[test]
+    fn tls_address_is_used_only_if_preferred_and_announced() {
+        let with_tls = gateway(Some(9001));
+        assert_eq!(with_tls.clients_address(true), "wss://3.3.3.3:9001");
+        assert_eq!(with_tls.clients_address(false), "ws://3.3.3.3:9000");
+
+        let without_tls = gateway(None);
+        assert_eq!(without_tls.clients_address(true), "ws://3.3.3.3:9000");
+        assert_eq!(without_tls.clients_address(false), "ws://3.3.3.3:9000");
+    }
+}
@@ -4,7 +4,9 @@
 use crate::filter::VersionFilterable;
 use log::warn;
 use mixnet_contract_common::mixnode::MixNodeDetails;
-use mixnet_contract_common::GatewayBond;
+pub use mixnet_contract_common::sphinx_keys::{sphinx_key_epoch, SPHINX_KEY_OVERLAP_SECS};
+pub use mixnet_contract_common::SphinxKeyEpoch;
+use mixnet_contract_common::{GatewayBond, IdentityKey, MixId, SphinxKeyAnnouncement};
 use nymsphinx_addressing::nodes::NodeIdentity;
 use nymsphinx_types::Node as SphinxNode;
 use rand::Rng;
@@ -18,6 +20,7 @@ use std::str::FromStr;
 pub mod filter;
 pub mod gateway;
 pub mod mix;
+pub mod sphinx_keys;
 
 #[derive(Debug)]
 pub enum NymTopologyError {
@@ -73,11 +76,51 @@ pub type MixLayer = u8;
 pub struct NymTopology {
     mixes: HashMap<MixLayer, Vec<mix::Node>>,
     gateways: Vec<gateway::Node>,
+
+    /// Epoch used for choosing rotated sphinx keys of the mixnodes when constructing routes.
+    /// If not set, the static keys are used.
+    sphinx_key_epoch: Option<SphinxKeyEpoch>,
 }
 
 impl NymTopology {
     pub fn new(mixes: HashMap<MixLayer, Vec<mix::Node>>, gateways: Vec<gateway::Node>) -> Self {
-        NymTopology { mixes, gateways }
+        NymTopology {
+            mixes,
+            gateways,
+            sphinx_key_epoch: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_sphinx_key_epoch(mut self, epoch: SphinxKeyEpoch) -> Self {
+        self.sphinx_key_epoch = Some(epoch);
+        self
+    }
+
+    /// Attaches the rotated sphinx keys announced by the nodes, as obtained from the validator API.
+    /// Announcements that were not signed with the identity key of the respective node
+    /// are ignored.
+    #[must_use]
+    pub fn with_sphinx_key_announcements(
+        mut self,
+        mixnodes: &HashMap<MixId, Vec<SphinxKeyAnnouncement>>,
+        gateways: &HashMap<IdentityKey, Vec<SphinxKeyAnnouncement>>,
+    ) -> Self {
+        for mix in self.mixes.values_mut().flatten() {
+            if let Some(announcements) = mixnodes.get(&mix.mix_id) {
+                mix.set_announced_sphinx_keys(announcements)
+            }
+        }
+        for gateway in &mut self.gateways {
+            if let Some(announcements) = gateways.get(&gateway.identity_key.to_base58_string()) {
+                gateway.set_announced_sphinx_keys(announcements)
+            }
+        }
+        self
+    }
+
+    pub fn sphinx_key_epoch(&self) -> Option<SphinxKeyEpoch> {
+        self.sphinx_key_epoch
     }
 
     pub fn mixes(&self) -> &HashMap<MixLayer, Vec<mix::Node>> {
@@ -147,7 +190,7 @@ impl NymTopology {
             let random_mix = layer_mixes
                 .choose(rng)
                 .ok_or(NymTopologyError::NoMixesOnLayerAvailable(layer))?;
-            route.push(random_mix.into());
+            route.push(random_mix.to_sphinx_node(self.sphinx_key_epoch));
         }
 
         Ok(route)
@@ -172,7 +215,9 @@ impl NymTopology {
         Ok(self
             .random_mix_route(rng, num_mix_hops)?
             .into_iter()
-            .chain(std::iter::once(gateway.into()))
+            .chain(std::iter::once(
+                gateway.to_sphinx_node(self.sphinx_key_epoch),
+            ))
             .collect())
     }
 
@@ -221,6 +266,7 @@ impl NymTopology {
         NymTopology {
             mixes: self.mixes.filter_by_version(expected_mix_version),
             gateways: self.gateways.filter_by_version(expected_gateway_version),
+            sphinx_key_epoch: self.sphinx_key_epoch,
         }
     }
 }
@@ -296,6 +342,7 @@ mod converting_mixes_to_vec {
                     "C7cown6dYCLZpLiMFC1PaBmhvLvmJmLDJGeRTbPD45bX",
                 )
                 .unwrap(),
+                announced_sphinx_keys: Default::default(),
                 layer: Layer::One,
                 version: "0.x.0".to_string(),
             };
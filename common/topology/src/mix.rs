@@ -1,9 +1,10 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::sphinx_keys::{self, AnnouncedSphinxKeys};
 use crate::{filter, NetworkAddress};
 use crypto::asymmetric::{encryption, identity};
-use mixnet_contract_common::{Layer, MixId, MixNodeBond};
+use mixnet_contract_common::{Layer, MixId, MixNodeBond, SphinxKeyAnnouncement, SphinxKeyEpoch};
 use nymsphinx_addressing::nodes::NymNodeRoutingAddress;
 use nymsphinx_types::Node as SphinxNode;
 use std::convert::{TryFrom, TryInto};
@@ -76,10 +77,36 @@ pub struct Node {
     pub mix_host: SocketAddr,
     pub identity_key: identity::PublicKey,
     pub sphinx_key: encryption::PublicKey, // TODO: or nymsphinx::PublicKey? both are x25519
+    // verified rotated sphinx keys the node announced for particular epochs
+    pub announced_sphinx_keys: AnnouncedSphinxKeys,
     pub layer: Layer,
     pub version: String,
 }
 
+impl Node {
+    /// Returns the sphinx key that should be used for packets created during the provided epoch.
+    /// If the node has not announced a key for it (or the epoch is unknown), its static key
+    /// is used instead.
+    pub fn sphinx_key_for_epoch(&self, epoch: Option<SphinxKeyEpoch>) -> &encryption::PublicKey {
+        sphinx_keys::sphinx_key_for_epoch(&self.sphinx_key, &self.announced_sphinx_keys, epoch)
+    }
+
+    /// Attaches the rotated sphinx keys announced by this node, ignoring the ones that were
+    /// not signed with its identity key.
+    pub fn set_announced_sphinx_keys(&mut self, announcements: &[SphinxKeyAnnouncement]) {
+        self.announced_sphinx_keys =
+            sphinx_keys::verify_announced_sphinx_keys(&self.identity_key, announcements);
+    }
+
+    pub fn to_sphinx_node(&self, epoch: Option<SphinxKeyEpoch>) -> SphinxNode {
+        let node_address_bytes = NymNodeRoutingAddress::from(self.mix_host)
+            .try_into()
+            .unwrap();
+
+        SphinxNode::new(node_address_bytes, self.sphinx_key_for_epoch(epoch).into())
+    }
+}
+
 impl filter::Versioned for Node {
     fn version(&self) -> String {
         self.version.clone()
@@ -88,11 +115,7 @@ impl filter::Versioned for Node {
 
 impl<'a> From<&'a Node> for SphinxNode {
     fn from(node: &'a Node) -> Self {
-        let node_address_bytes = NymNodeRoutingAddress::from(node.mix_host)
-            .try_into()
-            .unwrap();
-
-        SphinxNode::new(node_address_bytes, (&node.sphinx_key).into())
+        node.to_sphinx_node(None)
     }
 }
 
@@ -119,6 +142,7 @@ impl<'a> TryFrom<&'a MixNodeBond> for Node {
             mix_host,
             identity_key: identity::PublicKey::from_base58_string(&bond.mix_node.identity_key)?,
             sphinx_key: encryption::PublicKey::from_base58_string(&bond.mix_node.sphinx_key)?,
+            announced_sphinx_keys: Default::default(),
             layer: bond.layer,
             version: bond.mix_node.version.clone(),
         })
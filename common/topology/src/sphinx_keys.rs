@@ -0,0 +1,128 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::asymmetric::{encryption, identity};
+use log::warn;
+use mixnet_contract_common::{SphinxKeyAnnouncement, SphinxKeyEpoch};
+use std::collections::BTreeMap;
+
+/// Verified rotated sphinx keys a node has announced for particular epochs.
+pub type AnnouncedSphinxKeys = BTreeMap<SphinxKeyEpoch, encryption::PublicKey>;
+
+/// Returns the sphinx key that should be used for packets created during the provided epoch.
+/// If the node has not announced a key for it (or the epoch is unknown), its static key
+/// is used instead.
+pub(crate) fn sphinx_key_for_epoch<'a>(
+    static_key: &'a encryption::PublicKey,
+    announced: &'a AnnouncedSphinxKeys,
+    epoch: Option<SphinxKeyEpoch>,
+) -> &'a encryption::PublicKey {
+    epoch
+        .and_then(|epoch| announced.get(&epoch))
+        .unwrap_or(static_key)
+}
+
+/// Filters out the announcements that were not signed with the provided identity key
+/// or that contain malformed keys.
+pub fn verify_announced_sphinx_keys(
+    identity_key: &identity::PublicKey,
+    announcements: &[SphinxKeyAnnouncement],
+) -> AnnouncedSphinxKeys {
+    let mut verified = BTreeMap::new();
+    for announcement in announcements {
+        let signature = match identity::Signature::from_base58_string(&announcement.signature) {
+            Ok(signature) => signature,
+            Err(_) => {
+                warn!(
+                    "{} has announced a malformed sphinx key signature",
+                    identity_key
+                );
+                continue;
+            }
+        };
+        if identity_key
+            .verify(&announcement.plaintext(), &signature)
+            .is_err()
+        {
+            warn!(
+                "{} has announced an incorrectly signed sphinx key",
+                identity_key
+            );
+            continue;
+        }
+        match encryption::PublicKey::from_base58_string(&announcement.sphinx_key) {
+            Ok(key) => {
+                verified.insert(announcement.epoch, key);
+            }
+            Err(err) => warn!(
+                "{} has announced a malformed sphinx key - {}",
+                identity_key, err
+            ),
+        }
+    }
+    verified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announcement(
+        identity: &identity::PrivateKey,
+        epoch: SphinxKeyEpoch,
+        sphinx_key: &encryption::PublicKey,
+    ) -> SphinxKeyAnnouncement {
+        let sphinx_key = sphinx_key.to_base58_string();
+        let plaintext = SphinxKeyAnnouncement::signing_plaintext(epoch, &sphinx_key);
+        SphinxKeyAnnouncement {
+            epoch,
+            sphinx_key,
+            signature: identity.sign(&plaintext).to_base58_string(),
+        }
+    }
+
+    #[test]
+    fn only_correctly_signed_sphinx_keys_are_accepted() {
+        let identity_private = identity::PrivateKey::from_bytes(&[1; 32]).unwrap();
+        let identity_public = identity::PublicKey::from(&identity_private);
+        let other_identity = identity::PrivateKey::from_bytes(&[2; 32]).unwrap();
+
+        let key1 = encryption::PublicKey::from_bytes(&[3; 32]).unwrap();
+        let key2 = encryption::PublicKey::from_bytes(&[4; 32]).unwrap();
+
+        let mut tampered = announcement(&identity_private, 3, &key1);
+        tampered.epoch = 4;
+
+        let announcements = vec![
+            announcement(&identity_private, 1, &key1),
+            announcement(&identity_private, 2, &key2),
+            announcement(&other_identity, 3, &key1),
+            tampered,
+        ];
+
+        let verified = verify_announced_sphinx_keys(&identity_public, &announcements);
+        assert_eq!(verified.len(), 2);
+        assert_eq!(verified.get(&1), Some(&key1));
+        assert_eq!(verified.get(&2), Some(&key2));
+    }
+
+    #[test]
+    fn static_key_is_used_for_unannounced_epochs() {
+        let static_key = encryption::PublicKey::from_bytes(&[5; 32]).unwrap();
+        let rotated_key = encryption::PublicKey::from_bytes(&[6; 32]).unwrap();
+        let announced = [(10, rotated_key)].into_iter().collect();
+
+        assert_eq!(
+            sphinx_key_for_epoch(&static_key, &announced, Some(10)),
+            &rotated_key
+        );
+        assert_eq!(
+            sphinx_key_for_epoch(&static_key, &announced, Some(11)),
+            &static_key
+        );
+        assert_eq!(
+            sphinx_key_for_epoch(&static_key, &announced, None),
+            &static_key
+        );
+    }
+}
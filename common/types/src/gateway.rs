@@ -16,6 +16,8 @@ pub struct Gateway {
     pub host: String,
     pub mix_port: u16,
     pub clients_port: u16,
+    pub clients_wss_port: Option<u16>,
+    pub http_api_port: Option<u16>,
     pub location: String,
     pub sphinx_key: String,
     /// Base58 encoded ed25519 EdDSA public key of the gateway used to derive shared keys with clients
@@ -29,6 +31,8 @@ impl From<MixnetContractGateway> for Gateway {
             host,
             mix_port,
             clients_port,
+            clients_wss_port,
+            http_api_port,
             location,
             sphinx_key,
             identity_key,
@@ -39,6 +43,8 @@ impl From<MixnetContractGateway> for Gateway {
             host,
             mix_port,
             clients_port,
+            clients_wss_port,
+            http_api_port,
             location,
             sphinx_key,
             identity_key,
@@ -0,0 +1,59 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::registration::handshake::session_keys::SessionKeys;
+use crate::registration::handshake::shared_key::SharedKeys;
+use crate::GatewayRequestsError;
+use crypto::symmetric::stream_cipher::IV;
+use nymsphinx::params::GatewayEncryptionAlgorithm;
+use std::sync::Arc;
+
+/// Keys used for protecting the traffic exchanged over a single client-gateway connection.
+#[derive(Clone)]
+pub enum ConnectionKeys {
+    /// Static keys established with the legacy handshake that are reused between connections.
+    Legacy(SharedKeys),
+
+    /// Fresh keys established with the Noise-based handshake for this particular connection.
+    Session(Arc<SessionKeys>),
+}
+
+impl From<SharedKeys> for ConnectionKeys {
+    fn from(keys: SharedKeys) -> Self {
+        ConnectionKeys::Legacy(keys)
+    }
+}
+
+impl From<SessionKeys> for ConnectionKeys {
+    fn from(keys: SessionKeys) -> Self {
+        ConnectionKeys::Session(Arc::new(keys))
+    }
+}
+
+impl ConnectionKeys {
+    pub fn is_legacy(&self) -> bool {
+        matches!(self, ConnectionKeys::Legacy(_))
+    }
+
+    /// Encrypts and authenticates the provided data. Note that the initialisation vector is only
+    /// used by the legacy keys as the session keys derive their nonces from message counters.
+    pub fn encrypt(&self, data: &[u8], iv: Option<&IV<GatewayEncryptionAlgorithm>>) -> Vec<u8> {
+        match self {
+            ConnectionKeys::Legacy(shared_keys) => shared_keys.encrypt_and_tag(data, iv),
+            ConnectionKeys::Session(session_keys) => session_keys.encrypt(data),
+        }
+    }
+
+    /// Verifies integrity of and decrypts the provided data. Note that the initialisation vector
+    /// is only used by the legacy keys.
+    pub fn decrypt(
+        &self,
+        enc_data: &[u8],
+        iv: Option<&IV<GatewayEncryptionAlgorithm>>,
+    ) -> Result<Vec<u8>, GatewayRequestsError> {
+        match self {
+            ConnectionKeys::Legacy(shared_keys) => shared_keys.decrypt_tagged(enc_data, iv),
+            ConnectionKeys::Session(session_keys) => session_keys.decrypt(enc_data),
+        }
+    }
+}
@@ -25,4 +25,10 @@ pub enum HandshakeError {
     MalformedRequest,
     #[error("sent request was malformed")]
     HandshakeFailure,
+    #[error("failed to decrypt the received handshake message")]
+    DecryptionFailure,
+    #[error("the received identity does not match the static key used in the handshake")]
+    IdentityMismatch,
+    #[error("the gateway uses a different sphinx key than the one it has announced")]
+    SphinxKeyMismatch,
 }
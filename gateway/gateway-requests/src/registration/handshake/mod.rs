@@ -2,11 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use self::client::ClientHandshake;
+pub use self::connection_keys::ConnectionKeys;
 use self::error::HandshakeError;
 #[cfg(not(target_arch = "wasm32"))]
 use self::gateway::GatewayHandshake;
+pub use self::noise::NoiseHandshakeKeys;
+pub use self::session_keys::SessionKeys;
 pub use self::shared_key::{SharedKeySize, SharedKeys};
-use crypto::asymmetric::identity;
+use crypto::asymmetric::{encryption, identity};
 use futures::{Sink, Stream};
 use rand::{CryptoRng, RngCore};
 use tungstenite::{Error as WsError, Message as WsMessage};
@@ -14,12 +17,23 @@ use tungstenite::{Error as WsError, Message as WsMessage};
 pub(crate) type WsItem = Result<WsMessage, WsError>;
 
 mod client;
+pub mod connection_keys;
 pub mod error;
 #[cfg(not(target_arch = "wasm32"))]
 mod gateway;
+mod noise;
+pub mod session_keys;
 pub mod shared_key;
 mod state;
 
+/// Protocol version of the original, STS-based, registration handshake resulting in static
+/// shared keys. It is assumed whenever the client did not specify any version.
+pub const LEGACY_HANDSHAKE_VERSION: u8 = 1;
+
+/// Protocol version of the Noise IK based handshake resulting in fresh session keys
+/// for each connection.
+pub const NOISE_HANDSHAKE_VERSION: u8 = 2;
+
 // Note: the handshake is built on top of WebSocket, but in principle it shouldn't be too difficult
 // to remove that restriction, by just changing Sink<WsMessage> and Stream<Item = WsMessage> into
 // AsyncWrite and AsyncRead and slightly adjusting the implementation. But right now
@@ -50,9 +64,38 @@ where
     GatewayHandshake::new(rng, ws_stream, identity, received_init_payload).await
 }
 
+pub async fn client_noise_handshake<'a, S>(
+    rng: &mut (impl RngCore + CryptoRng),
+    ws_stream: &'a mut S,
+    identity: &'a identity::KeyPair,
+    gateway_pubkey: identity::PublicKey,
+    gateway_sphinx_key: Option<encryption::PublicKey>,
+) -> Result<NoiseHandshakeKeys, HandshakeError>
+where
+    S: Stream<Item = WsItem> + Sink<WsMessage> + Unpin + Send + 'a,
+{
+    noise::perform_client_handshake(rng, ws_stream, identity, gateway_pubkey, gateway_sphinx_key)
+        .await
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn gateway_noise_handshake<'a, S>(
+    rng: &mut (impl RngCore + CryptoRng),
+    ws_stream: &'a mut S,
+    identity: &'a identity::KeyPair,
+    sphinx_key: &'a encryption::PublicKey,
+    received_init_payload: Vec<u8>,
+) -> Result<NoiseHandshakeKeys, HandshakeError>
+where
+    S: Stream<Item = WsItem> + Sink<WsMessage> + Unpin + Send + 'a,
+{
+    noise::perform_gateway_handshake(rng, ws_stream, identity, sphinx_key, received_init_payload)
+        .await
+}
+
 /*
 
-Messages exchanged:
+Messages exchanged (legacy handshake):
 
 CLIENT -> GATEWAY:
 CLIENT_ID_KEY || G^x
@@ -67,3 +110,18 @@ GATEWAY -> CLIENT
 DONE(status)
 
 */
+
+/*
+
+Messages exchanged (Noise handshake, static keys are x25519 forms of the identity keys):
+
+CLIENT -> GATEWAY:
+REGISTER_INIT(version = 2, E_C || AEAD(k1, CLIENT_STATIC) || AEAD(k2, CLIENT_ID_KEY))
+
+GATEWAY -> CLIENT
+E_G || AEAD(k3, EMPTY)
+
+GATEWAY -> CLIENT
+AUTHENTICATE(status, bandwidth_remaining)
+
+*/
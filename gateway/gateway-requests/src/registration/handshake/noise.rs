@@ -0,0 +1,362 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::registration::handshake::error::HandshakeError;
+use crate::registration::handshake::session_keys::SessionKeys;
+use crate::registration::handshake::shared_key::{SharedKeySize, SharedKeys};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::registration::handshake::state::send_handshake_data;
+use crate::registration::handshake::state::{receive_handshake_message, send_handshake_error};
+use crate::registration::handshake::{WsItem, NOISE_HANDSHAKE_VERSION};
+use crate::types::ClientControlRequest;
+use crypto::asymmetric::{encryption, identity};
+use crypto::generic_array::typenum::Unsigned;
+use crypto::hkdf;
+use crypto::noise::{self, IkHandshake, NoiseError};
+use futures::{Sink, SinkExt, Stream};
+use nymsphinx::params::GatewaySharedKeyHkdfAlgorithm;
+use rand::{CryptoRng, RngCore};
+use tungstenite::Message as WsMessage;
+
+const PROLOGUE: &[u8] = b"nym-gateway-handshake";
+const SHARED_KEYS_INFO: &[u8] = b"nym-gateway-shared-keys";
+
+// e || ENC(s) || ENC(client identity)
+#[cfg(not(target_arch = "wasm32"))]
+const INIT_MESSAGE_LEN: usize = noise::init_message_len(identity::PUBLIC_KEY_LENGTH);
+
+// e || ENC(gateway sphinx key)
+const RESPONSE_MESSAGE_LEN: usize = noise::response_message_len(encryption::PUBLIC_KEY_SIZE);
+
+/// Keys established as the result of the Noise-based handshake.
+pub struct NoiseHandshakeKeys {
+    /// Identity of the remote party that has been authenticated during the handshake.
+    pub remote_identity: identity::PublicKey,
+
+    /// Keys used for encrypting all traffic exchanged over this particular connection.
+    pub session_keys: SessionKeys,
+
+    /// Long-term keys derived out of the static keys of both parties. They're the same for every
+    /// connection and are only kept around for compatibility with the legacy authentication.
+    pub shared_keys: SharedKeys,
+
+    /// Sphinx key of the gateway, authenticated with its identity during the handshake.
+    /// It is only known to the client.
+    pub gateway_sphinx_key: Option<encryption::PublicKey>,
+}
+
+/// Noise IK handshake, where the client is the initiator that knows the static key
+/// of the gateway in advance.
+///
+/// The handshake consists of the following messages:
+///
+/// -> e, es, s, ss     (with client's identity key as the payload)
+/// <- e, ee, se        (with gateway's sphinx key as the payload)
+struct NoiseHandshake {
+    handshake: IkHandshake,
+}
+
+impl NoiseHandshake {
+    fn new_initiator(
+        rng: &mut (impl RngCore + CryptoRng),
+        local_identity: &identity::KeyPair,
+        remote_identity: &identity::PublicKey,
+    ) -> Self {
+        NoiseHandshake {
+            handshake: IkHandshake::new_initiator(rng, PROLOGUE, local_identity, remote_identity),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new_responder(
+        rng: &mut (impl RngCore + CryptoRng),
+        local_identity: &identity::KeyPair,
+    ) -> Self {
+        NoiseHandshake {
+            handshake: IkHandshake::new_responder(rng, PROLOGUE, local_identity),
+        }
+    }
+
+    // -> e, es, s, ss
+    fn write_init_message(&mut self, payload: &[u8]) -> Vec<u8> {
+        self.handshake.write_init_message(payload)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_init_message(&mut self, message: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        if message.len() != INIT_MESSAGE_LEN {
+            return Err(HandshakeError::MalformedRequest);
+        }
+
+        self.handshake
+            .read_init_message(message)
+            .map_err(|err| match err {
+                NoiseError::MalformedMessage => HandshakeError::MalformedRequest,
+                NoiseError::DecryptionFailure => HandshakeError::DecryptionFailure,
+            })
+    }
+
+    // <- e, ee, se
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write_response_message(&mut self, payload: &[u8]) -> Vec<u8> {
+        self.handshake.write_response_message(payload)
+    }
+
+    fn read_response_message(&mut self, message: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        if message.len() != RESPONSE_MESSAGE_LEN {
+            return Err(HandshakeError::MalformedResponse);
+        }
+
+        self.handshake
+            .read_response_message(message)
+            .map_err(|err| match err {
+                NoiseError::MalformedMessage => HandshakeError::MalformedResponse,
+                NoiseError::DecryptionFailure => HandshakeError::DecryptionFailure,
+            })
+    }
+
+    /// Checks whether the provided identity corresponds to the static key the remote
+    /// has used during the handshake.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn is_remote_static(&self, identity: &identity::PublicKey) -> bool {
+        self.handshake.remote_static() == Some(&identity.to_x25519())
+    }
+
+    fn derive_shared_keys(&self) -> SharedKeys {
+        let static_shared_secret = self
+            .handshake
+            .static_shared_secret()
+            .expect("the static keys were not mixed in");
+
+        // there is no reason for this to fail as our okm is expected to be only 32 bytes
+        let okm = hkdf::extract_then_expand::<GatewaySharedKeyHkdfAlgorithm>(
+            None,
+            static_shared_secret,
+            Some(SHARED_KEYS_INFO),
+            SharedKeySize::to_usize(),
+        )
+        .expect("somehow too long okm was provided");
+
+        SharedKeys::try_from_bytes(&okm).expect("okm was expanded to incorrect length!")
+    }
+
+    /// Finish the handshake, yielding all the established keys.
+    fn finalize(self, remote_identity: identity::PublicKey) -> NoiseHandshakeKeys {
+        let shared_keys = self.derive_shared_keys();
+        let keys = self.handshake.finalize();
+
+        NoiseHandshakeKeys {
+            remote_identity,
+            session_keys: SessionKeys::new(&keys.sending_key, &keys.receiving_key),
+            shared_keys,
+            gateway_sphinx_key: None,
+        }
+    }
+}
+
+// If any step along the way failed (that are non-network related),
+// try to send 'error' message to the remote
+// party to indicate handshake should be terminated
+async fn check_processing_error<T, S>(
+    result: Result<T, HandshakeError>,
+    ws_stream: &mut S,
+) -> Result<T, HandshakeError>
+where
+    S: Sink<WsMessage> + Unpin,
+{
+    match result {
+        Ok(ok) => Ok(ok),
+        Err(err) => {
+            send_handshake_error(ws_stream, err.to_string()).await?;
+            Err(err)
+        }
+    }
+}
+
+pub(crate) async fn perform_client_handshake<S>(
+    rng: &mut (impl RngCore + CryptoRng),
+    ws_stream: &mut S,
+    identity: &identity::KeyPair,
+    gateway_pubkey: identity::PublicKey,
+    expected_sphinx_key: Option<encryption::PublicKey>,
+) -> Result<NoiseHandshakeKeys, HandshakeError>
+where
+    S: Stream<Item = WsItem> + Sink<WsMessage> + Unpin,
+{
+    let mut handshake = NoiseHandshake::new_initiator(rng, identity, &gateway_pubkey);
+
+    // -> e, es, s, ss, ENC(client_id)
+    let init_message = handshake.write_init_message(&identity.public_key().to_bytes());
+    let init_request =
+        ClientControlRequest::new_register_handshake_init(init_message, NOISE_HANDSHAKE_VERSION);
+    ws_stream
+        .send(init_request.into())
+        .await
+        .map_err(|_| HandshakeError::ClosedStream)?;
+
+    // <- e, ee, se, ENC(gateway_sphinx_key)
+    let response = receive_handshake_message(ws_stream).await?;
+    let read_result = handshake
+        .read_response_message(&response)
+        .and_then(|payload| {
+            encryption::PublicKey::from_bytes(&payload)
+                .map_err(|_| HandshakeError::MalformedResponse)
+        })
+        .and_then(|sphinx_key| {
+            // make sure the gateway uses the same sphinx key as the one announced in the topology
+            match expected_sphinx_key {
+                Some(expected) if expected != sphinx_key => Err(HandshakeError::SphinxKeyMismatch),
+                _ => Ok(sphinx_key),
+            }
+        });
+    let sphinx_key = check_processing_error(read_result, ws_stream).await?;
+
+    let mut keys = handshake.finalize(gateway_pubkey);
+    keys.gateway_sphinx_key = Some(sphinx_key);
+    Ok(keys)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn perform_gateway_handshake<S>(
+    rng: &mut (impl RngCore + CryptoRng),
+    ws_stream: &mut S,
+    identity: &identity::KeyPair,
+    sphinx_key: &encryption::PublicKey,
+    received_init_payload: Vec<u8>,
+) -> Result<NoiseHandshakeKeys, HandshakeError>
+where
+    S: Stream<Item = WsItem> + Sink<WsMessage> + Unpin,
+{
+    let mut handshake = NoiseHandshake::new_responder(rng, identity);
+
+    // <- e, es, s, ss, ENC(client_id)
+    let read_result = handshake
+        .read_init_message(&received_init_payload)
+        .and_then(|payload| {
+            identity::PublicKey::from_bytes(&payload).map_err(|_| HandshakeError::MalformedRequest)
+        })
+        .and_then(|remote_identity| {
+            // make sure the client actually owns the identity it claims to have
+            if handshake.is_remote_static(&remote_identity) {
+                Ok(remote_identity)
+            } else {
+                Err(HandshakeError::IdentityMismatch)
+            }
+        });
+    let remote_identity = check_processing_error(read_result, ws_stream).await?;
+
+    // -> e, ee, se, ENC(gateway_sphinx_key)
+    let response = handshake.write_response_message(&sphinx_key.to_bytes());
+    send_handshake_data(ws_stream, response).await?;
+
+    Ok(handshake.finalize(remote_identity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_parties_derive_matching_keys() {
+        let mut rng = rand::rngs::OsRng;
+        let client_identity = identity::KeyPair::new(&mut rng);
+        let gateway_identity = identity::KeyPair::new(&mut rng);
+
+        let mut client = NoiseHandshake::new_initiator(
+            &mut rng,
+            &client_identity,
+            gateway_identity.public_key(),
+        );
+        let mut gateway = NoiseHandshake::new_responder(&mut rng, &gateway_identity);
+
+        let init_message = client.write_init_message(&client_identity.public_key().to_bytes());
+        let received_payload = gateway.read_init_message(&init_message).unwrap();
+        assert_eq!(received_payload, client_identity.public_key().to_bytes());
+
+        let sphinx_key = encryption::KeyPair::new(&mut rng);
+        let response = gateway.write_response_message(&sphinx_key.public_key().to_bytes());
+        assert_eq!(
+            client.read_response_message(&response).unwrap(),
+            sphinx_key.public_key().to_bytes()
+        );
+
+        let client_keys = client.finalize(*gateway_identity.public_key());
+        let gateway_keys = gateway.finalize(*client_identity.public_key());
+
+        assert_eq!(
+            client_keys.shared_keys.to_bytes(),
+            gateway_keys.shared_keys.to_bytes()
+        );
+
+        let request = b"foomp";
+        let response = b"bar";
+        let decrypted_request = gateway_keys
+            .session_keys
+            .decrypt(&client_keys.session_keys.encrypt(request))
+            .unwrap();
+        let decrypted_response = client_keys
+            .session_keys
+            .decrypt(&gateway_keys.session_keys.encrypt(response))
+            .unwrap();
+        assert_eq!(decrypted_request, request);
+        assert_eq!(decrypted_response, response);
+
+        // each direction uses different key
+        assert!(client_keys
+            .session_keys
+            .decrypt(&client_keys.session_keys.encrypt(request))
+            .is_err());
+    }
+
+    #[test]
+    fn handshake_fails_for_wrong_gateway_key() {
+        let mut rng = rand::rngs::OsRng;
+        let client_identity = identity::KeyPair::new(&mut rng);
+        let gateway_identity = identity::KeyPair::new(&mut rng);
+        let other_identity = identity::KeyPair::new(&mut rng);
+
+        let mut client =
+            NoiseHandshake::new_initiator(&mut rng, &client_identity, other_identity.public_key());
+        let mut gateway = NoiseHandshake::new_responder(&mut rng, &gateway_identity);
+
+        let init_message = client.write_init_message(&client_identity.public_key().to_bytes());
+        assert!(gateway.read_init_message(&init_message).is_err());
+    }
+
+    #[test]
+    fn sessions_use_fresh_keys() {
+        let mut rng = rand::rngs::OsRng;
+        let client_identity = identity::KeyPair::new(&mut rng);
+        let gateway_identity = identity::KeyPair::new(&mut rng);
+
+        let mut establish_keys = || {
+            let mut client = NoiseHandshake::new_initiator(
+                &mut rng,
+                &client_identity,
+                gateway_identity.public_key(),
+            );
+            let mut gateway = NoiseHandshake::new_responder(&mut rng, &gateway_identity);
+            let init_message = client.write_init_message(&client_identity.public_key().to_bytes());
+            gateway.read_init_message(&init_message).unwrap();
+            let response = gateway.write_response_message(&[0u8; encryption::PUBLIC_KEY_SIZE]);
+            client.read_response_message(&response).unwrap();
+            (
+                client.finalize(*gateway_identity.public_key()),
+                gateway.finalize(*client_identity.public_key()),
+            )
+        };
+
+        let (first_client, _) = establish_keys();
+        let (second_client, second_gateway) = establish_keys();
+
+        // keys are the same across sessions
+        assert_eq!(
+            first_client.shared_keys.to_bytes(),
+            second_client.shared_keys.to_bytes()
+        );
+
+        // but session traffic from one session can't be decrypted in another
+        let ciphertext = first_client.session_keys.encrypt(b"foomp");
+        assert!(second_gateway.session_keys.decrypt(&ciphertext).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::GatewayRequestsError;
+use aes_gcm::aead::{Aead, NewAead};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use crypto::noise::counter_nonce;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Size of the AES256-GCM session key.
+pub const SESSION_KEY_SIZE: usize = 32;
+
+// the message counter is encoded as big-endian u64.
+const COUNTER_SIZE: usize = 8;
+
+// AES256-GCM authentication tag is 128 bit long.
+const TAG_SIZE: usize = 16;
+
+/// Per-connection keys established with the Noise-based handshake.
+///
+/// Each direction of the connection uses a separate AES256-GCM key. Every message is encrypted
+/// under a nonce derived from a strictly increasing counter that is prepended to the ciphertext.
+/// The receiver only accepts counters higher than any it has seen before, so that any replayed
+/// or reordered message gets rejected, while messages that never reach the decryption
+/// (for example if they were received while waiting for a control response) do not
+/// break the subsequent ones.
+///
+/// Note that the keys are deliberately not `Clone`, as two copies of the sending counter
+/// would result in a nonce reuse.
+pub struct SessionKeys {
+    sending_cipher: Aes256Gcm,
+    receiving_cipher: Aes256Gcm,
+
+    /// Counter of the next message to be sent.
+    sending_counter: AtomicU64,
+
+    /// The lowest counter the next received message could use.
+    receiving_counter: AtomicU64,
+}
+
+impl SessionKeys {
+    pub(crate) fn new(
+        sending_key: &[u8; SESSION_KEY_SIZE],
+        receiving_key: &[u8; SESSION_KEY_SIZE],
+    ) -> Self {
+        SessionKeys {
+            sending_cipher: Aes256Gcm::new(Key::from_slice(sending_key)),
+            receiving_cipher: Aes256Gcm::new(Key::from_slice(receiving_key)),
+            sending_counter: AtomicU64::new(0),
+            receiving_counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Encrypts the provided data with the sending key, producing COUNTER || CIPHERTEXT || TAG.
+    pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+        // even at a million messages per second, it would take half a million years to overflow
+        let counter = self.sending_counter.fetch_add(1, Ordering::Relaxed);
+
+        // the encryption can only fail if the plaintext is longer than what AES-GCM supports
+        // (~64GB), which is way above any message we could possibly send
+        let ciphertext = self
+            .sending_cipher
+            .encrypt(Nonce::from_slice(&counter_nonce(counter)), data)
+            .expect("failed to encrypt the message with the session key");
+
+        counter
+            .to_be_bytes()
+            .iter()
+            .copied()
+            .chain(ciphertext.into_iter())
+            .collect()
+    }
+
+    /// Attempts to decrypt the data produced by the remote's `encrypt`. It fails if the message
+    /// does not use a higher counter than all of the previously decrypted ones.
+    pub fn decrypt(&self, enc_data: &[u8]) -> Result<Vec<u8>, GatewayRequestsError> {
+        if enc_data.len() < COUNTER_SIZE + TAG_SIZE {
+            return Err(GatewayRequestsError::TooShortRequest);
+        }
+
+        let (counter_bytes, ciphertext) = enc_data.split_at(COUNTER_SIZE);
+        // the unwrap is fine as we have just split off exactly 8 bytes
+        let counter = u64::from_be_bytes(counter_bytes.try_into().unwrap());
+        if counter < self.receiving_counter.load(Ordering::Acquire) {
+            return Err(GatewayRequestsError::ReplayedMessage);
+        }
+
+        let plaintext = self
+            .receiving_cipher
+            .decrypt(Nonce::from_slice(&counter_nonce(counter)), ciphertext)
+            .map_err(|_| GatewayRequestsError::InvalidMac)?;
+
+        // only move the window after the message got authenticated, so that garbage couldn't
+        // be used to make us reject the genuine messages. Also make sure that nobody has
+        // concurrently accepted the same message in the meantime
+        let next = counter
+            .checked_add(1)
+            .ok_or(GatewayRequestsError::ReplayedMessage)?;
+        if self.receiving_counter.fetch_max(next, Ordering::AcqRel) > counter {
+            return Err(GatewayRequestsError::ReplayedMessage);
+        }
+
+        Ok(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_pair() -> (SessionKeys, SessionKeys) {
+        let first_key = [1u8; SESSION_KEY_SIZE];
+        let second_key = [2u8; SESSION_KEY_SIZE];
+        (
+            SessionKeys::new(&first_key, &second_key),
+            SessionKeys::new(&second_key, &first_key),
+        )
+    }
+
+    #[test]
+    fn replayed_messages_are_rejected() {
+        let (sender, receiver) = key_pair();
+
+        let message = sender.encrypt(b"foomp");
+        assert_eq!(receiver.decrypt(&message).unwrap(), b"foomp");
+        assert!(matches!(
+            receiver.decrypt(&message),
+            Err(GatewayRequestsError::ReplayedMessage)
+        ));
+    }
+
+    #[test]
+    fn reordered_messages_are_rejected_but_gaps_are_fine() {
+        let (sender, receiver) = key_pair();
+
+        let first = sender.encrypt(b"first");
+        let second = sender.encrypt(b"second");
+        let third = sender.encrypt(b"third");
+        let fourth = sender.encrypt(b"fourth");
+
+        // the first message never reached the decryption
+        assert_eq!(receiver.decrypt(&third).unwrap(), b"third");
+        assert!(receiver.decrypt(&first).is_err());
+        assert!(receiver.decrypt(&second).is_err());
+        assert_eq!(receiver.decrypt(&fourth).unwrap(), b"fourth");
+    }
+
+    #[test]
+    fn forged_counters_do_not_move_the_window() {
+        let (sender, receiver) = key_pair();
+
+        let message = sender.encrypt(b"foomp");
+        let mut forged = message.clone();
+        forged[..COUNTER_SIZE].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(matches!(
+            receiver.decrypt(&forged),
+            Err(GatewayRequestsError::InvalidMac)
+        ));
+
+        assert_eq!(receiver.decrypt(&message).unwrap(), b"foomp");
+    }
+}
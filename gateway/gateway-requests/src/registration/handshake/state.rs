@@ -204,26 +204,7 @@ impl<'a, S> State<'a, S> {
     where
         S: Stream<Item = WsItem> + Unpin,
     {
-        loop {
-            if let Some(msg) = self.ws_stream.next().await {
-                if let Ok(msg) = msg {
-                    match msg {
-                        WsMessage::Text(ws_msg) => match types::RegistrationHandshake::try_from(ws_msg) {
-                            Ok(reg_handshake_msg) => return match reg_handshake_msg {
-                                types::RegistrationHandshake::HandshakePayload { data } => Ok(data),
-                                types::RegistrationHandshake::HandshakeError { message } => Err(HandshakeError::RemoteError(message)),
-                            },
-                            Err(_) => error!("Received a non-handshake message during the registration handshake! It's getting dropped."),
-                        },
-                        _ => error!("Received non-text message during registration handshake"),
-                    }
-                } else {
-                    return Err(HandshakeError::NetworkError);
-                }
-            } else {
-                return Err(HandshakeError::ClosedStream);
-            }
-        }
+        receive_handshake_message(&mut *self.ws_stream).await
     }
 
     // upon receiving this, the receiver should terminate the handshake
@@ -234,11 +215,7 @@ impl<'a, S> State<'a, S> {
     where
         S: Sink<WsMessage> + Unpin,
     {
-        let handshake_message = types::RegistrationHandshake::new_error(message);
-        self.ws_stream
-            .send(WsMessage::Text(handshake_message.try_into().unwrap()))
-            .await
-            .map_err(|_| HandshakeError::ClosedStream)
+        send_handshake_error(&mut *self.ws_stream, message).await
     }
 
     pub(crate) async fn send_handshake_data(
@@ -248,11 +225,7 @@ impl<'a, S> State<'a, S> {
     where
         S: Sink<WsMessage> + Unpin,
     {
-        let handshake_message = types::RegistrationHandshake::new_payload(payload);
-        self.ws_stream
-            .send(WsMessage::Text(handshake_message.try_into().unwrap()))
-            .await
-            .map_err(|_| HandshakeError::ClosedStream)
+        send_handshake_data(&mut *self.ws_stream, payload).await
     }
 
     /// Finish the handshake, yielding the derived shared key and implicitly dropping all borrowed
@@ -261,3 +234,63 @@ impl<'a, S> State<'a, S> {
         self.derived_shared_keys.unwrap()
     }
 }
+
+// the below are also used by the Noise-based handshake which does not rely on the `State`
+
+pub(crate) async fn receive_handshake_message<S>(
+    ws_stream: &mut S,
+) -> Result<Vec<u8>, HandshakeError>
+where
+    S: Stream<Item = WsItem> + Unpin,
+{
+    loop {
+        if let Some(msg) = ws_stream.next().await {
+            if let Ok(msg) = msg {
+                match msg {
+                    WsMessage::Text(ws_msg) => match types::RegistrationHandshake::try_from(ws_msg) {
+                        Ok(reg_handshake_msg) => return match reg_handshake_msg {
+                            types::RegistrationHandshake::HandshakePayload { data } => Ok(data),
+                            types::RegistrationHandshake::HandshakeError { message } => Err(HandshakeError::RemoteError(message)),
+                        },
+                        Err(_) => error!("Received a non-handshake message during the registration handshake! It's getting dropped."),
+                    },
+                    _ => error!("Received non-text message during registration handshake"),
+                }
+            } else {
+                return Err(HandshakeError::NetworkError);
+            }
+        } else {
+            return Err(HandshakeError::ClosedStream);
+        }
+    }
+}
+
+// upon receiving this, the receiver should terminate the handshake
+pub(crate) async fn send_handshake_error<S, M>(
+    ws_stream: &mut S,
+    message: M,
+) -> Result<(), HandshakeError>
+where
+    S: Sink<WsMessage> + Unpin,
+    M: Into<String>,
+{
+    let handshake_message = types::RegistrationHandshake::new_error(message);
+    ws_stream
+        .send(WsMessage::Text(handshake_message.try_into().unwrap()))
+        .await
+        .map_err(|_| HandshakeError::ClosedStream)
+}
+
+pub(crate) async fn send_handshake_data<S>(
+    ws_stream: &mut S,
+    payload: Vec<u8>,
+) -> Result<(), HandshakeError>
+where
+    S: Sink<WsMessage> + Unpin,
+{
+    let handshake_message = types::RegistrationHandshake::new_payload(payload);
+    ws_stream
+        .send(WsMessage::Text(handshake_message.try_into().unwrap()))
+        .await
+        .map_err(|_| HandshakeError::ClosedStream)
+}
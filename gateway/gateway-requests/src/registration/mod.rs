@@ -3,7 +3,5 @@
 
 pub mod handshake;
 
-// TODO: is it perhaps possible to replace the 'custom' handshake with an existing
-// implementation like with one of the variants on the Noise framework?
-
-// Right now it's based on the STS (Station-to-Station) Protocol.
+// The legacy handshake is based on the STS (Station-to-Station) Protocol, while its replacement
+// follows the IK pattern of the Noise framework, providing forward secrecy for each connection.
@@ -3,15 +3,10 @@
 
 use crate::authentication::encrypted_address::EncryptedAddressBytes;
 use crate::iv::IV;
-use crate::registration::handshake::SharedKeys;
-use crate::GatewayMacSize;
-use crypto::generic_array::typenum::Unsigned;
-use crypto::hmac::recompute_keyed_hmac_and_verify_tag;
-use crypto::symmetric::stream_cipher;
+use crate::registration::handshake::ConnectionKeys;
 use nymsphinx::addressing::nodes::NymNodeRoutingAddressError;
 use nymsphinx::forwarding::packet::{MixPacket, MixPacketFormattingError};
 use nymsphinx::params::packet_sizes::PacketSize;
-use nymsphinx::params::{GatewayEncryptionAlgorithm, GatewayIntegrityHmacAlgorithm};
 use nymsphinx::DestinationAddressBytes;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -64,6 +59,7 @@ impl TryInto<String> for RegistrationHandshake {
 pub enum GatewayRequestsError {
     TooShortRequest,
     InvalidMac,
+    ReplayedMessage,
     IncorrectlyEncodedAddress,
     RequestOfInvalidSize(usize),
     MalformedSphinxPacket,
@@ -78,6 +74,7 @@ impl fmt::Display for GatewayRequestsError {
         match self {
             TooShortRequest => write!(f, "the request is too short"),
             InvalidMac => write!(f, "provided MAC is invalid"),
+            ReplayedMessage => write!(f, "the message has been replayed or reordered"),
             IncorrectlyEncodedAddress => write!(f, "address field was incorrectly encoded"),
             RequestOfInvalidSize(actual) =>
                 write!(
@@ -122,6 +119,10 @@ pub enum ClientControlRequest {
     #[serde(alias = "handshakePayload")]
     RegisterHandshakeInitRequest {
         data: Vec<u8>,
+        /// Version of the handshake the client wishes to perform.
+        /// If not provided, the legacy handshake is assumed.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        protocol_version: Option<u8>,
     },
     BandwidthCredential {
         enc_credential: Vec<u8>,
@@ -143,14 +144,21 @@ impl ClientControlRequest {
         }
     }
 
+    pub fn new_register_handshake_init(data: Vec<u8>, protocol_version: u8) -> Self {
+        ClientControlRequest::RegisterHandshakeInitRequest {
+            data,
+            protocol_version: Some(protocol_version),
+        }
+    }
+
     #[cfg(feature = "coconut")]
     pub fn new_enc_coconut_bandwidth_credential(
         credential: &Credential,
-        shared_key: &SharedKeys,
+        keys: &ConnectionKeys,
         iv: IV,
     ) -> Self {
         let serialized_credential = credential.as_bytes();
-        let enc_credential = shared_key.encrypt_and_tag(&serialized_credential, Some(iv.inner()));
+        let enc_credential = keys.encrypt(&serialized_credential, Some(iv.inner()));
 
         ClientControlRequest::BandwidthCredential {
             enc_credential,
@@ -161,10 +169,10 @@ impl ClientControlRequest {
     #[cfg(feature = "coconut")]
     pub fn try_from_enc_coconut_bandwidth_credential(
         enc_credential: Vec<u8>,
-        shared_key: &SharedKeys,
+        keys: &ConnectionKeys,
         iv: IV,
     ) -> Result<Credential, GatewayRequestsError> {
-        let credential_bytes = shared_key.decrypt_tagged(&enc_credential, Some(iv.inner()))?;
+        let credential_bytes = keys.decrypt(&enc_credential, Some(iv.inner()))?;
         Credential::from_bytes(&credential_bytes)
             .map_err(|_| GatewayRequestsError::MalformedEncryption)
     }
@@ -172,10 +180,10 @@ impl ClientControlRequest {
     #[cfg(not(feature = "coconut"))]
     pub fn new_enc_token_bandwidth_credential(
         credential: &TokenCredential,
-        shared_key: &SharedKeys,
+        keys: &ConnectionKeys,
         iv: IV,
     ) -> Self {
-        let enc_credential = shared_key.encrypt_and_tag(&credential.to_bytes(), Some(iv.inner()));
+        let enc_credential = keys.encrypt(&credential.to_bytes(), Some(iv.inner()));
         ClientControlRequest::BandwidthCredential {
             enc_credential,
             iv: iv.to_bytes(),
@@ -185,10 +193,10 @@ impl ClientControlRequest {
     #[cfg(not(feature = "coconut"))]
     pub fn try_from_enc_token_bandwidth_credential(
         enc_credential: Vec<u8>,
-        shared_key: &SharedKeys,
+        keys: &ConnectionKeys,
         iv: IV,
     ) -> Result<TokenCredential, GatewayRequestsError> {
-        let credential = shared_key.decrypt_tagged(&enc_credential, Some(iv.inner()))?;
+        let credential = keys.decrypt(&enc_credential, Some(iv.inner()))?;
         TokenCredential::from_bytes(&credential)
             .map_err(|_| GatewayRequestsError::MalformedEncryption)
     }
@@ -282,16 +290,17 @@ pub enum BinaryRequest {
 }
 
 // Right now the only valid `BinaryRequest` is a request to forward a sphinx packet.
-// It is encrypted using the derived shared key between client and the gateway. Thanks to
-// randomness inside the sphinx packet themselves (even via the same route), the 0s IV can be used here.
+// It is encrypted using the keys established between client and the gateway. In the case of the
+// legacy shared keys, thanks to randomness inside the sphinx packet themselves (even via the same route),
+// the 0s IV can be used here.
 // HOWEVER, NOTE: If we introduced another 'BinaryRequest', we must carefully examine if a 0s IV
 // would work there.
 impl BinaryRequest {
     pub fn try_from_encrypted_tagged_bytes(
         raw_req: Vec<u8>,
-        shared_keys: &SharedKeys,
+        keys: &ConnectionKeys,
     ) -> Result<Self, GatewayRequestsError> {
-        let message_bytes = &shared_keys.decrypt_tagged(&raw_req, None)?;
+        let message_bytes = &keys.decrypt(&raw_req, None)?;
 
         // right now there's only a single option possible which significantly simplifies the logic
         // if we decided to allow for more 'binary' messages, the API wouldn't need to change.
@@ -299,14 +308,14 @@ impl BinaryRequest {
         Ok(BinaryRequest::ForwardSphinx(mix_packet))
     }
 
-    pub fn into_encrypted_tagged_bytes(self, shared_key: &SharedKeys) -> Vec<u8> {
+    pub fn into_encrypted_tagged_bytes(self, keys: &ConnectionKeys) -> Vec<u8> {
         match self {
             BinaryRequest::ForwardSphinx(mix_packet) => {
                 let forwarding_data = mix_packet.into_bytes();
 
                 // TODO: it could be theoretically slightly more efficient if the data wasn't taken
                 // by reference because then it makes a copy for encryption rather than do it in place
-                shared_key.encrypt_and_tag(&forwarding_data, None)
+                keys.encrypt(&forwarding_data, None)
             }
         }
     }
@@ -316,8 +325,8 @@ impl BinaryRequest {
         BinaryRequest::ForwardSphinx(mix_packet)
     }
 
-    pub fn into_ws_message(self, shared_key: &SharedKeys) -> Message {
-        Message::Binary(self.into_encrypted_tagged_bytes(shared_key))
+    pub fn into_ws_message(self, keys: &ConnectionKeys) -> Message {
+        Message::Binary(self.into_encrypted_tagged_bytes(keys))
     }
 }
 
@@ -329,39 +338,17 @@ pub enum BinaryResponse {
 impl BinaryResponse {
     pub fn try_from_encrypted_tagged_bytes(
         raw_req: Vec<u8>,
-        shared_keys: &SharedKeys,
+        keys: &ConnectionKeys,
     ) -> Result<Self, GatewayRequestsError> {
-        let mac_size = GatewayMacSize::to_usize();
-        if raw_req.len() < mac_size {
-            return Err(GatewayRequestsError::TooShortRequest);
-        }
-
-        let mac_tag = &raw_req[..mac_size];
-        let message_bytes = &raw_req[mac_size..];
-
-        if !recompute_keyed_hmac_and_verify_tag::<GatewayIntegrityHmacAlgorithm>(
-            shared_keys.mac_key(),
-            message_bytes,
-            mac_tag,
-        ) {
-            return Err(GatewayRequestsError::InvalidMac);
-        }
-
-        let zero_iv = stream_cipher::zero_iv::<GatewayEncryptionAlgorithm>();
-        let plaintext = stream_cipher::decrypt::<GatewayEncryptionAlgorithm>(
-            shared_keys.encryption_key(),
-            &zero_iv,
-            message_bytes,
-        );
-
+        let plaintext = keys.decrypt(&raw_req, None)?;
         Ok(BinaryResponse::PushedMixMessage(plaintext))
     }
 
-    pub fn into_encrypted_tagged_bytes(self, shared_key: &SharedKeys) -> Vec<u8> {
+    pub fn into_encrypted_tagged_bytes(self, keys: &ConnectionKeys) -> Vec<u8> {
         match self {
             // TODO: it could be theoretically slightly more efficient if the data wasn't taken
             // by reference because then it makes a copy for encryption rather than do it in place
-            BinaryResponse::PushedMixMessage(message) => shared_key.encrypt_and_tag(&message, None),
+            BinaryResponse::PushedMixMessage(message) => keys.encrypt(&message, None),
         }
     }
 
@@ -369,8 +356,8 @@ impl BinaryResponse {
         BinaryResponse::PushedMixMessage(msg)
     }
 
-    pub fn into_ws_message(self, shared_key: &SharedKeys) -> Message {
-        Message::Binary(self.into_encrypted_tagged_bytes(shared_key))
+    pub fn into_ws_message(self, keys: &ConnectionKeys) -> Message {
+        Message::Binary(self.into_encrypted_tagged_bytes(keys))
     }
 }
 
@@ -388,10 +375,28 @@ mod tests {
         let deserialized = ClientControlRequest::try_from(serialized).unwrap();
 
         match deserialized {
-            ClientControlRequest::RegisterHandshakeInitRequest { data } => {
-                assert_eq!(data, handshake_data)
+            ClientControlRequest::RegisterHandshakeInitRequest {
+                data,
+                protocol_version,
+            } => {
+                assert_eq!(data, handshake_data);
+                assert!(protocol_version.is_none())
             }
             _ => unreachable!("this branch shouldn't have been reached!"),
         }
     }
+
+    #[test]
+    fn register_handshake_init_request_preserves_protocol_version() {
+        let request = ClientControlRequest::new_register_handshake_init(vec![1, 2, 3], 2);
+        let serialized: String = request.try_into().unwrap();
+        let deserialized = ClientControlRequest::try_from(serialized).unwrap();
+
+        match deserialized {
+            ClientControlRequest::RegisterHandshakeInitRequest {
+                protocol_version, ..
+            } => assert_eq!(protocol_version, Some(2)),
+            _ => unreachable!("this branch shouldn't have been reached!"),
+        }
+    }
 }
@@ -0,0 +1,16 @@
+/*
+ * Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+-- messages are now encrypted at rest for a per-client inbox key, whose private part is only ever
+-- stored wrapped under the shared keys of the client. any previously stored plaintext messages
+-- are encrypted in place on startup
+ALTER TABLE message_store ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE inbox_keys
+(
+    client_address_bs58 TEXT NOT NULL PRIMARY KEY,
+    public_key_bs58     TEXT NOT NULL,
+    wrapped_private_key BLOB NOT NULL
+);
@@ -0,0 +1,36 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::Config;
+use clap::Args;
+use config::NymConfig;
+use log::error;
+use mixnode_common::node_description::NodeDescription;
+
+#[derive(Args)]
+pub(crate) struct Describe {
+    /// The id of the gateway you want to describe
+    #[clap(long)]
+    id: String,
+}
+
+pub(crate) fn execute(args: &Describe) {
+    // ensure that the gateway has in fact been initialized
+    match Config::load_from_file(Some(&args.id)) {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            error!("Failed to load config for {}. Are you sure you have run `init` before? (Error was: {})", &args.id, err);
+            return;
+        }
+    };
+
+    // get input from the user
+    let node_description = NodeDescription::from_user_input("https://gateway.yourdomain.com");
+
+    // save the struct
+    NodeDescription::save_to_file(
+        &node_description,
+        Config::default_config_directory(Some(&args.id)),
+    )
+    .unwrap()
+}
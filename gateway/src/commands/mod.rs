@@ -15,6 +15,7 @@ use network_defaults::var_names::{
     API_VALIDATOR, BECH32_PREFIX, CONFIGURED, NYMD_VALIDATOR, STATISTICS_SERVICE_DOMAIN_ADDRESS,
 };
 
+pub(crate) mod describe;
 pub(crate) mod init;
 pub(crate) mod node_details;
 pub(crate) mod run;
@@ -23,6 +24,9 @@ pub(crate) mod upgrade;
 
 #[derive(Subcommand)]
 pub(crate) enum Commands {
+    /// Describe your gateway and tell people why they should use it
+    Describe(describe::Describe),
+
     /// Initialise the gateway
     Init(init::Init),
 
@@ -51,6 +55,10 @@ pub(crate) struct OverrideConfig {
     wallet_address: Option<String>,
     mix_port: Option<u16>,
     clients_port: Option<u16>,
+    clients_wss_port: Option<u16>,
+    tls_certificate: Option<String>,
+    tls_private_key: Option<String>,
+    http_api_port: Option<u16>,
     datastore: Option<String>,
     announce_host: Option<String>,
     enabled_statistics: Option<bool>,
@@ -67,6 +75,7 @@ pub(crate) async fn execute(args: Cli) {
     let bin_name = "nym-gateway";
 
     match &args.command {
+        Commands::Describe(m) => describe::execute(m),
         Commands::Init(m) => init::execute(m).await,
         Commands::NodeDetails(m) => node_details::execute(m).await,
         Commands::Run(m) => run::execute(m).await,
@@ -92,6 +101,22 @@ pub(crate) fn override_config(mut config: Config, args: OverrideConfig) -> Confi
         config = config.with_clients_port(clients_port);
     }
 
+    if let Some(clients_wss_port) = args.clients_wss_port {
+        config = config.with_clients_wss_port(clients_wss_port);
+    }
+
+    if let Some(http_api_port) = args.http_api_port {
+        config = config.with_http_api_port(http_api_port);
+    }
+
+    if let Some(tls_certificate) = args.tls_certificate {
+        config = config.with_tls_certificate_file(tls_certificate);
+    }
+
+    if let Some(tls_private_key) = args.tls_private_key {
+        config = config.with_tls_private_key_file(tls_private_key);
+    }
+
     if let Some(announce_host) = args.announce_host {
         config = config.with_announce_address(announce_host);
     } else if was_host_overridden {
@@ -31,6 +31,22 @@ pub struct Run {
     #[clap(long)]
     clients_port: Option<u16>,
 
+    /// The port on which the gateway will be listening for TLS-terminated (wss) clients gateway-requests
+    #[clap(long)]
+    clients_wss_port: Option<u16>,
+
+    /// Path to PEM file containing the certificate chain used for the wss listener
+    #[clap(long)]
+    tls_certificate: Option<String>,
+
+    /// Path to PEM file containing the private key used for the wss listener
+    #[clap(long)]
+    tls_private_key: Option<String>,
+
+    /// The port on which the gateway will be serving its HTTP API
+    #[clap(long)]
+    http_api_port: Option<u16>,
+
     /// The host that will be reported to the directory server
     #[clap(long)]
     announce_host: Option<String>,
@@ -72,6 +88,10 @@ impl From<Run> for OverrideConfig {
             wallet_address: run_config.wallet_address,
             mix_port: run_config.mix_port,
             clients_port: run_config.clients_port,
+            clients_wss_port: run_config.clients_wss_port,
+            tls_certificate: run_config.tls_certificate,
+            tls_private_key: run_config.tls_private_key,
+            http_api_port: run_config.http_api_port,
             datastore: run_config.datastore,
             announce_host: run_config.announce_host,
             validator_apis: run_config.validator_apis,
@@ -2,9 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::config::template::config_template;
-use config::defaults::{DEFAULT_CLIENT_LISTENING_PORT, DEFAULT_MIX_LISTENING_PORT};
+use config::defaults::{
+    DEFAULT_CLIENT_LISTENING_PORT, DEFAULT_HTTP_API_LISTENING_PORT, DEFAULT_MIX_LISTENING_PORT,
+};
+use config::reload::{check_live_changes, ReloadError};
 use config::NymConfig;
 use log::error;
+use mixnet_client::link::InboundLinkPolicy;
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
 use std::path::PathBuf;
@@ -25,6 +29,13 @@ const DEFAULT_PACKET_FORWARDING_MAXIMUM_BACKOFF: Duration = Duration::from_milli
 const DEFAULT_INITIAL_CONNECTION_TIMEOUT: Duration = Duration::from_millis(1_500);
 const DEFAULT_MAXIMUM_CONNECTION_BUFFER_SIZE: usize = 128;
 
+/// Fields that can be changed without having to restart the gateway.
+const LIVE_RELOADABLE_FIELDS: &[&str] = &[
+    "logging.filters",
+    "gateway.announce_address",
+    "gateway.validator_api_urls",
+];
+
 const DEFAULT_STORED_MESSAGE_FILENAME_LENGTH: u16 = 16;
 const DEFAULT_MESSAGE_RETRIEVAL_LIMIT: i64 = 100;
 
@@ -44,6 +55,10 @@ fn default_clients_port() -> u16 {
     DEFAULT_CLIENT_LISTENING_PORT
 }
 
+fn default_http_api_port() -> u16 {
+    DEFAULT_HTTP_API_LISTENING_PORT
+}
+
 #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct Config {
     gateway: Gateway,
@@ -186,6 +201,26 @@ impl Config {
         self
     }
 
+    pub fn with_clients_wss_port(mut self, port: u16) -> Self {
+        self.gateway.clients_wss_port = Some(port);
+        self
+    }
+
+    pub fn with_http_api_port(mut self, port: u16) -> Self {
+        self.gateway.http_api_port = port;
+        self
+    }
+
+    pub fn with_tls_certificate_file<P: Into<PathBuf>>(mut self, certificate_file: P) -> Self {
+        self.gateway.tls_certificate_file = Some(certificate_file.into());
+        self
+    }
+
+    pub fn with_tls_private_key_file<P: Into<PathBuf>>(mut self, private_key_file: P) -> Self {
+        self.gateway.tls_private_key_file = Some(private_key_file.into());
+        self
+    }
+
     pub fn announce_host_from_listening_host(mut self) -> Self {
         self.gateway.announce_address = self.gateway.listening_address.to_string();
         self
@@ -269,6 +304,22 @@ impl Config {
         self.gateway.clients_port
     }
 
+    pub fn get_clients_wss_port(&self) -> Option<u16> {
+        self.gateway.clients_wss_port
+    }
+
+    pub fn get_http_api_port(&self) -> u16 {
+        self.gateway.http_api_port
+    }
+
+    pub fn get_tls_certificate_file(&self) -> Option<PathBuf> {
+        self.gateway.tls_certificate_file.clone()
+    }
+
+    pub fn get_tls_private_key_file(&self) -> Option<PathBuf> {
+        self.gateway.tls_private_key_file.clone()
+    }
+
     pub fn get_persistent_store_path(&self) -> PathBuf {
         self.gateway.persistent_storage.clone()
     }
@@ -293,6 +344,26 @@ impl Config {
         self.debug.use_legacy_framed_packet_version
     }
 
+    pub fn get_use_encrypted_links(&self) -> bool {
+        self.debug.use_encrypted_links
+    }
+
+    pub fn get_require_encrypted_links(&self) -> bool {
+        self.debug.require_encrypted_links
+    }
+
+    pub fn get_inbound_link_policy(&self) -> InboundLinkPolicy {
+        self.debug.inbound_link_policy
+    }
+
+    pub fn get_enable_sphinx_key_rotation(&self) -> bool {
+        self.debug.enable_sphinx_key_rotation
+    }
+
+    pub fn get_rotated_sphinx_keys_file(&self) -> PathBuf {
+        self.data_directory().join("rotated_sphinx_keys.json")
+    }
+
     pub fn get_message_retrieval_limit(&self) -> i64 {
         self.debug.message_retrieval_limit
     }
@@ -304,6 +375,22 @@ impl Config {
     pub fn get_wallet_address(&self) -> &str {
         &self.gateway.wallet_address
     }
+
+    pub fn get_logging_filters(&self) -> Option<&str> {
+        Some(&self.logging.filters).filter(|filters| !filters.is_empty())
+    }
+
+    /// Checks whether the `updated` config is valid and can be applied while the gateway is running.
+    /// If so, returns the fields that have changed.
+    pub fn check_live_reload(&self, updated: &Config) -> Result<Vec<String>, ReloadError> {
+        if updated.gateway.validator_api_urls.is_empty() {
+            return Err(ReloadError::Invalid(
+                "at least one validator api url must be provided".to_string(),
+            ));
+        }
+
+        check_live_changes(self, updated, LIVE_RELOADABLE_FIELDS)
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
@@ -340,6 +427,24 @@ pub struct Gateway {
     #[serde(default = "default_clients_port")]
     clients_port: u16,
 
+    /// Optional port used for listening for TLS-terminated (`wss://`) client traffic.
+    /// If set, both `tls_certificate_file` and `tls_private_key_file` must also be provided.
+    #[serde(default)]
+    clients_wss_port: Option<u16>,
+
+    /// Path to PEM file containing the certificate chain used by the TLS client listener.
+    #[serde(default)]
+    tls_certificate_file: Option<PathBuf>,
+
+    /// Path to PEM file containing the private key used by the TLS client listener.
+    #[serde(default)]
+    tls_private_key_file: Option<PathBuf>,
+
+    /// Port used for serving the HTTP API with the gateway's self-reported information.
+    /// (default: 8000)
+    #[serde(default = "default_http_api_port")]
+    http_api_port: u16,
+
     /// Path to file containing private identity key.
     private_identity_key_file: PathBuf,
 
@@ -411,6 +516,10 @@ impl Default for Gateway {
             announce_address: "127.0.0.1".to_string(),
             mix_port: DEFAULT_MIX_LISTENING_PORT,
             clients_port: DEFAULT_CLIENT_LISTENING_PORT,
+            clients_wss_port: None,
+            tls_certificate_file: None,
+            tls_private_key_file: None,
+            http_api_port: DEFAULT_HTTP_API_LISTENING_PORT,
             private_identity_key_file: Default::default(),
             public_identity_key_file: Default::default(),
             private_sphinx_key_file: Default::default(),
@@ -429,7 +538,12 @@ impl Default for Gateway {
 
 #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
-struct Logging {}
+struct Logging {
+    /// Logging filters in the same format as the `RUST_LOG` environment variable, e.g. `info,nym_gateway=debug`.
+    /// If empty, the value of `RUST_LOG` is used instead.
+    #[serde(default)]
+    filters: String,
+}
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 #[serde(default)]
@@ -466,6 +580,26 @@ struct Debug {
     // existing nodes whilst everyone else is upgrading and getting the code for handling the new field.
     // It shall be disabled in the subsequent releases.
     use_legacy_framed_packet_version: bool,
+
+    /// Specifies whether encrypted links should be established when forwarding packets to nodes
+    /// whose identities are known.
+    use_encrypted_links: bool,
+
+    /// Specifies whether packets should only ever be forwarded over encrypted links, i.e. whether
+    /// no connection should be made to nodes that are known not to support them
+    /// (or whose identities are unknown) instead of falling back to plaintext.
+    /// Note that a failed handshake never results in a plaintext connection.
+    // it's opt-in, as with it enabled, the nodes that haven't upgraded yet become unreachable.
+    require_encrypted_links: bool,
+
+    /// Specifies which inbound mix connections should be accepted.
+    inbound_link_policy: InboundLinkPolicy,
+
+    /// Specifies whether the gateway should generate, and announce, fresh sphinx keys for each
+    /// sphinx key epoch on top of its static key.
+    // note that the rotation limits the lifetime of the reply SURBs addressed to clients
+    // of this gateway, so it's disabled by default until the clients are aware of it.
+    enable_sphinx_key_rotation: bool,
 }
 
 impl Default for Debug {
@@ -480,6 +614,10 @@ impl Default for Debug {
             message_retrieval_limit: DEFAULT_MESSAGE_RETRIEVAL_LIMIT,
             // TODO: remember to change it in one of future releases!!
             use_legacy_framed_packet_version: true,
+            use_encrypted_links: true,
+            require_encrypted_links: false,
+            inbound_link_policy: InboundLinkPolicy::default(),
+            enable_sphinx_key_rotation: false,
         }
     }
 }
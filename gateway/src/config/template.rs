@@ -53,6 +53,25 @@ mix_port = {{ gateway.mix_port }}
 # (default: 9000)
 clients_port = {{ gateway.clients_port }}
 
+# Optional port used for listening for TLS-terminated (wss) client websocket traffic.
+# If set, both `tls_certificate_file` and `tls_private_key_file` must also be provided.
+{{#if gateway.clients_wss_port }}
+clients_wss_port = {{ gateway.clients_wss_port }}
+{{/if}}
+
+# Paths to PEM files containing the certificate chain and the private key
+# used by the TLS client listener.
+{{#if gateway.tls_certificate_file }}
+tls_certificate_file = '{{ gateway.tls_certificate_file }}'
+{{/if}}
+{{#if gateway.tls_private_key_file }}
+tls_private_key_file = '{{ gateway.tls_private_key_file }}'
+{{/if}}
+
+# Port used for serving the HTTP API with the gateway's self-reported information.
+# (default: 8000)
+http_api_port = {{ gateway.http_api_port }}
+
 # Wheather gateway collects and sends anonymized statistics
 enabled_statistics = {{ gateway.enabled_statistics }}
 
@@ -92,7 +111,10 @@ persistent_storage = '{{ gateway.persistent_storage }}'
 
 [logging]
 
-# TODO
+# Logging filters in the same format as the `RUST_LOG` environment variable, e.g. 'info,nym_gateway=debug'.
+# If empty, the value of `RUST_LOG` is used instead.
+# It can be changed without restarting the gateway.
+filters = '{{ logging.filters }}'
 
 "#
 }
@@ -1,3 +1,6 @@
+#[macro_use]
+extern crate rocket;
+
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
@@ -5,12 +5,16 @@ use crate::node::client_handling::websocket::connection_handler::{ClientDetails,
 use crate::node::client_handling::websocket::message_receiver::MixMessageReceiver;
 use crate::node::storage::error::StorageError;
 use crate::node::storage::Storage;
+#[cfg(feature = "coconut")]
+use futures::Future;
 use futures::StreamExt;
 use gateway_requests::iv::IVConversionError;
 use gateway_requests::types::{BinaryRequest, ServerResponse};
 use gateway_requests::{ClientControlRequest, GatewayRequestsError};
 use log::*;
 use nymsphinx::forwarding::packet::MixPacket;
+#[cfg(feature = "coconut")]
+use nymsphinx::DestinationAddressBytes;
 use rand::{CryptoRng, Rng};
 use std::convert::TryFrom;
 use std::process;
@@ -63,6 +67,10 @@ pub(crate) enum RequestHandlingError {
     #[error("There was a problem with the proposal id: {reason}")]
     ProposalIdError { reason: String },
 
+    #[cfg(feature = "coconut")]
+    #[error("Provided bandwidth credential has already been spent")]
+    CredentialAlreadySpent,
+
     #[cfg(feature = "coconut")]
     #[error("Coconut interface error - {0}")]
     CoconutInterfaceError(#[from] coconut_interface::error::CoconutInterfaceError),
@@ -89,6 +97,49 @@ impl IntoWSMessage for Result<ServerResponse, RequestHandlingError> {
     }
 }
 
+#[cfg(feature = "coconut")]
+/// Marks the credential with the provided blinded serial number as spent, so that any concurrent
+/// attempts of reusing it would get rejected immediately, and then redeems it. If the redemption
+/// fails for any reason other than the credential having already been spent, the mark is removed
+/// so that the client could retry.
+///
+/// # Arguments
+///
+/// * `storage`: storage holding the spent credentials.
+/// * `blinded_serial_number`: blinded serial number of the credential being spent.
+/// * `client_address`: address of the client spending the credential.
+/// * `redeem`: redemption of the credential with the coconut bandwidth contract.
+async fn spend_credential<St, F>(
+    storage: &St,
+    blinded_serial_number: &coconut_interface::BlindedSerialNumber,
+    client_address: DestinationAddressBytes,
+    redeem: F,
+) -> Result<(), RequestHandlingError>
+where
+    St: Storage,
+    F: Future<Output = Result<(), RequestHandlingError>>,
+{
+    if !storage
+        .insert_spent_credential(blinded_serial_number, client_address)
+        .await?
+    {
+        return Err(RequestHandlingError::CredentialAlreadySpent);
+    }
+
+    if let Err(err) = redeem.await {
+        // if the credential has already been seen by the contract, it must never be accepted
+        // again, otherwise we failed to redeem it, so the client should be able to retry
+        if !matches!(err, RequestHandlingError::CredentialAlreadySpent) {
+            storage
+                .remove_spent_credential(blinded_serial_number)
+                .await?;
+        }
+        return Err(err);
+    }
+
+    Ok(())
+}
+
 pub(crate) struct AuthenticatedHandler<R, S, St> {
     inner: FreshHandler<R, S, St>,
     client: ClientDetails,
@@ -117,13 +168,14 @@ where
     /// # Arguments
     ///
     /// * `fresh`: fresh, unauthenticated, connection handler.
-    /// * `client`: details (i.e. address and shared keys) of the registered client
+    /// * `client`: details (i.e. address and connection keys) of the registered client
     /// * `mix_receiver`: channel used for receiving messages from the mixnet destined for this client.
     pub(crate) fn upgrade(
         fresh: FreshHandler<R, S, St>,
         client: ClientDetails,
         mix_receiver: MixMessageReceiver,
     ) -> Self {
+        fresh.stats.new_client_session();
         AuthenticatedHandler {
             inner: fresh,
             client,
@@ -185,6 +237,30 @@ where
             error!("We failed to forward requested mix packet - {}. Presumably our mix forwarder has crashed. We cannot continue.", err);
             process::exit(1);
         }
+        self.inner.stats.forwarded_client_packet();
+    }
+
+    #[cfg(feature = "coconut")]
+    /// Reconciles the local view of spent credentials with the coconut bandwidth contract and, if
+    /// the credential has not been spent anywhere else, attempts to release the associated funds.
+    ///
+    /// # Arguments
+    ///
+    /// * `credential`: verified bandwidth credential to redeem.
+    async fn redeem_coconut_credential(
+        &self,
+        credential: &coconut_interface::Credential,
+    ) -> Result<(), RequestHandlingError> {
+        if self
+            .inner
+            .coconut_verifier
+            .is_spent_on_chain(credential.blinded_serial_number())
+            .await?
+        {
+            return Err(RequestHandlingError::CredentialAlreadySpent);
+        }
+
+        self.inner.coconut_verifier.release_funds(credential).await
     }
 
     #[cfg(feature = "coconut")]
@@ -203,7 +279,7 @@ where
         let iv = IV::try_from_bytes(&iv)?;
         let credential = ClientControlRequest::try_from_enc_coconut_bandwidth_credential(
             enc_credential,
-            &self.client.shared_keys,
+            &self.client.connection_keys,
             iv,
         )?;
 
@@ -218,10 +294,13 @@ where
             ));
         }
 
-        self.inner
-            .coconut_verifier
-            .release_funds(&credential)
-            .await?;
+        spend_credential(
+            &self.inner.storage,
+            &credential.theta().get_blinded_serial_number(),
+            self.client.address,
+            self.redeem_coconut_credential(&credential),
+        )
+        .await?;
 
         let bandwidth = Bandwidth::from(credential);
         let bandwidth_value = bandwidth.value();
@@ -258,7 +337,7 @@ where
         let iv = IV::try_from_bytes(&iv)?;
         let credential = ClientControlRequest::try_from_enc_token_bandwidth_credential(
             enc_credential,
-            &self.client.shared_keys,
+            &self.client.connection_keys,
             iv,
         )?;
         if !self
@@ -332,7 +411,7 @@ where
         &self,
         mix_packet: MixPacket,
     ) -> Result<ServerResponse, RequestHandlingError> {
-        let consumed_bandwidth = mix_packet.sphinx_packet().len() as i64;
+        let consumed_bandwidth = mix_packet.packet().len() as i64;
 
         let available_bandwidth = self.get_available_bandwidth().await?;
 
@@ -357,7 +436,8 @@ where
     /// * `bin_msg`: raw message to handle.
     async fn handle_binary(&self, bin_msg: Vec<u8>) -> Message {
         // this function decrypts the request and checks the MAC
-        match BinaryRequest::try_from_encrypted_tagged_bytes(bin_msg, &self.client.shared_keys) {
+        match BinaryRequest::try_from_encrypted_tagged_bytes(bin_msg, &self.client.connection_keys)
+        {
             Err(e) => RequestHandlingError::InvalidBinaryRequest(e).into_error_message(),
             Ok(request) => match request {
                 // currently only a single type exists
@@ -447,7 +527,7 @@ where
                 },
                 mix_messages = self.mix_receiver.next() => {
                     let mix_messages = mix_messages.expect("sender was unexpectedly closed! this shouldn't have ever happened!");
-                    if let Err(e) = self.inner.push_packets_to_client(self.client.shared_keys, mix_messages).await {
+                    if let Err(e) = self.inner.push_packets_to_client(&self.client.connection_keys, mix_messages).await {
                         warn!("failed to send the unwrapped sphinx packets back to the client - {:?}, assuming the connection is dead", e);
                         break;
                     }
@@ -459,3 +539,134 @@ where
         trace!("The stream was closed!");
     }
 }
+
+#[cfg(all(test, feature = "coconut"))]
+mod tests {
+    use super::*;
+    use crate::node::storage::PersistentStorage;
+    use coconut_interface::tests::helpers::theta_from_keys_and_attributes;
+    use coconut_interface::{hash_to_scalar, ttp_keygen, BlindedSerialNumber, Parameters};
+    use rand::rngs::OsRng;
+    use rand::RngCore;
+    use std::path::PathBuf;
+
+    struct TestStorage {
+        storage: PersistentStorage,
+        path: PathBuf,
+    }
+
+    impl TestStorage {
+        async fn new() -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("gateway-credentials-{}.sqlite", OsRng.next_u64()));
+            TestStorage {
+                storage: PersistentStorage::init(&path, 100).await.unwrap(),
+                path,
+            }
+        }
+    }
+
+    impl Drop for TestStorage {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    fn blinded_serial_number() -> BlindedSerialNumber {
+        let params = Parameters::new(4).unwrap();
+        let keypairs = ttp_keygen(&params, 1, 1).unwrap();
+        let public_attributes = vec![hash_to_scalar("1000"), hash_to_scalar("BandwidthVoucher")];
+        theta_from_keys_and_attributes(&params, &keypairs, &public_attributes)
+            .unwrap()
+            .get_blinded_serial_number()
+    }
+
+    fn client_address() -> DestinationAddressBytes {
+        DestinationAddressBytes::from_bytes([1; 32])
+    }
+
+    fn transient_failure() -> RequestHandlingError {
+        RequestHandlingError::ProposalIdError {
+            reason: "the chain is unavailable".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn credential_cannot_be_spent_twice() {
+        let test_storage = TestStorage::new().await;
+        let serial_number = blinded_serial_number();
+
+        spend_credential(
+            &test_storage.storage,
+            &serial_number,
+            client_address(),
+            async { Ok(()) },
+        )
+        .await
+        .unwrap();
+
+        let res = spend_credential(
+            &test_storage.storage,
+            &serial_number,
+            client_address(),
+            async { panic!("an already spent credential must not be redeemed again") },
+        )
+        .await;
+        assert!(matches!(
+            res,
+            Err(RequestHandlingError::CredentialAlreadySpent)
+        ));
+    }
+
+    #[tokio::test]
+    async fn credential_spent_on_chain_is_kept() {
+        let test_storage = TestStorage::new().await;
+        let serial_number = blinded_serial_number();
+
+        let res = spend_credential(
+            &test_storage.storage,
+            &serial_number,
+            client_address(),
+            async { Err(RequestHandlingError::CredentialAlreadySpent) },
+        )
+        .await;
+        assert!(matches!(
+            res,
+            Err(RequestHandlingError::CredentialAlreadySpent)
+        ));
+
+        assert!(!test_storage
+            .storage
+            .insert_spent_credential(&serial_number, client_address())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn credential_is_released_after_transient_failure() {
+        let test_storage = TestStorage::new().await;
+        let serial_number = blinded_serial_number();
+
+        let res = spend_credential(
+            &test_storage.storage,
+            &serial_number,
+            client_address(),
+            async { Err(transient_failure()) },
+        )
+        .await;
+        assert!(matches!(
+            res,
+            Err(RequestHandlingError::ProposalIdError { .. })
+        ));
+
+        // the client can retry
+        spend_credential(
+            &test_storage.storage,
+            &serial_number,
+            client_address(),
+            async { Ok(()) },
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -9,7 +9,10 @@ use coconut_interface::{Credential, VerificationKey};
 use validator_client::{
     nymd::{
         cosmwasm_client::logs::find_attribute,
-        traits::{CoconutBandwidthSigningClient, MultisigQueryClient, MultisigSigningClient},
+        traits::{
+            CoconutBandwidthQueryClient, CoconutBandwidthSigningClient, MultisigQueryClient,
+            MultisigSigningClient,
+        },
         Coin, Fee, NymdClient, SigningNymdClient,
     },
     ApiClient,
@@ -52,6 +55,20 @@ impl CoconutVerifier {
         &self.aggregated_verification_key
     }
 
+    /// Checks whether the coconut bandwidth contract already knows about a credential with the
+    /// provided blinded serial number, i.e. whether it has been spent (or is in the process of
+    /// being spent) at any gateway.
+    pub async fn is_spent_on_chain(
+        &self,
+        blinded_serial_number_bs58: String,
+    ) -> Result<bool, RequestHandlingError> {
+        let res = self
+            .nymd_client
+            .get_spent_credential(blinded_serial_number_bs58)
+            .await?;
+        Ok(res.spend_credential.is_some())
+    }
+
     pub async fn release_funds(&self, credential: &Credential) -> Result<(), RequestHandlingError> {
         // Use a custom multiplier for revoke, as the default one (1.3)
         // isn't enough
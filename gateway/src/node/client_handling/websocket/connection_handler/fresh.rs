@@ -7,16 +7,20 @@ use crate::node::client_handling::websocket::connection_handler::coconut::Coconu
 use crate::node::client_handling::websocket::connection_handler::{
     AuthenticatedHandler, ClientDetails, InitialAuthResult, SocketStream,
 };
+use crate::node::node_statistics::SharedGatewayStats;
 use crate::node::storage::error::StorageError;
 use crate::node::storage::Storage;
-use crypto::asymmetric::identity;
+use crypto::asymmetric::{encryption, identity};
 use futures::{channel::mpsc, SinkExt, StreamExt};
 use gateway_requests::authentication::encrypted_address::{
     EncryptedAddressBytes, EncryptedAddressConversionError,
 };
 use gateway_requests::iv::{IVConversionError, IV};
 use gateway_requests::registration::handshake::error::HandshakeError;
-use gateway_requests::registration::handshake::{gateway_handshake, SharedKeys};
+use gateway_requests::registration::handshake::{
+    gateway_handshake, gateway_noise_handshake, ConnectionKeys, NoiseHandshakeKeys, SharedKeys,
+    LEGACY_HANDSHAKE_VERSION, NOISE_HANDSHAKE_VERSION,
+};
 use gateway_requests::types::{ClientControlRequest, ServerResponse};
 use gateway_requests::BinaryResponse;
 use log::*;
@@ -53,6 +57,9 @@ enum InitialAuthenticationError {
     #[error("Only 'Register' or 'Authenticate' requests are allowed")]
     InvalidRequest,
 
+    #[error("Handshake version {0} is not supported")]
+    UnsupportedHandshakeVersion(u8),
+
     #[error("Experienced connection error - {0}")]
     ConnectionError(#[from] WsError),
 }
@@ -67,11 +74,13 @@ impl InitialAuthenticationError {
 pub(crate) struct FreshHandler<R, S, St> {
     rng: R,
     local_identity: Arc<identity::KeyPair>,
+    sphinx_key: encryption::PublicKey,
     pub(crate) disabled_credentials_mode: bool,
     pub(crate) active_clients_store: ActiveClientsStore,
     pub(crate) outbound_mix_sender: MixForwardingSender,
     pub(crate) socket_connection: SocketStream<S>,
     pub(crate) storage: St,
+    pub(crate) stats: SharedGatewayStats,
 
     #[cfg(feature = "coconut")]
     pub(crate) coconut_verifier: Arc<CoconutVerifier>,
@@ -93,8 +102,10 @@ where
         disabled_credentials_mode: bool,
         outbound_mix_sender: MixForwardingSender,
         local_identity: Arc<identity::KeyPair>,
+        sphinx_key: encryption::PublicKey,
         storage: St,
         active_clients_store: ActiveClientsStore,
+        stats: SharedGatewayStats,
         #[cfg(feature = "coconut")] coconut_verifier: Arc<CoconutVerifier>,
     ) -> Self {
         FreshHandler {
@@ -104,7 +115,9 @@ where
             outbound_mix_sender,
             socket_connection: SocketStream::RawTcp(conn),
             local_identity,
+            sphinx_key,
             storage,
+            stats,
             #[cfg(feature = "coconut")]
             coconut_verifier,
         }
@@ -166,6 +179,35 @@ where
         }
     }
 
+    /// Using received `init_msg` tries to complete the Noise-based handshake with the connected
+    /// client to establish fresh session keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `init_msg`: a client handshake init message which should contain its ephemeral key as well as its encrypted static key.
+    async fn perform_noise_handshake(
+        &mut self,
+        init_msg: Vec<u8>,
+    ) -> Result<NoiseHandshakeKeys, HandshakeError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        debug_assert!(self.socket_connection.is_websocket());
+        match &mut self.socket_connection {
+            SocketStream::UpgradedWebSocket(ws_stream) => {
+                gateway_noise_handshake(
+                    &mut self.rng,
+                    ws_stream,
+                    self.local_identity.as_ref(),
+                    &self.sphinx_key,
+                    init_msg,
+                )
+                .await
+            }
+            _ => unreachable!(),
+        }
+    }
+
     /// Attempts to read websocket message from the associated socket.
     pub(crate) async fn read_websocket_message(&mut self) -> Option<Result<Message, WsError>>
     where
@@ -196,15 +238,15 @@ where
     }
 
     /// Sends unwrapped sphinx packets (payloads) back to the client. Note that each message is encrypted and tagged with
-    /// the previously derived shared keys.
+    /// the previously established connection keys.
     ///
     /// # Arguments
     ///
-    /// * `shared_keys`: keys derived between the client and gateway.
+    /// * `connection_keys`: keys established between the client and gateway.
     /// * `packets`: unwrapped packets that are to be pushed back to the client.
     pub(crate) async fn push_packets_to_client(
         &mut self,
-        shared_keys: SharedKeys,
+        connection_keys: &ConnectionKeys,
         packets: Vec<Vec<u8>>,
     ) -> Result<(), WsError>
     where
@@ -216,7 +258,7 @@ where
             .into_iter()
             .map(|received_message| {
                 Ok(BinaryResponse::new_pushed_mix_message(received_message)
-                    .into_ws_message(&shared_keys))
+                    .into_ws_message(connection_keys))
             })
             .collect();
         let mut send_stream = futures::stream::iter(messages);
@@ -255,11 +297,13 @@ where
     /// # Arguments
     ///
     /// * `client_address`: address of the client that is going to receive the messages.
-    /// * `shared_keys`: shared keys derived between the client and the gateway used to encrypt and tag the messages.
+    /// * `shared_keys`: long-term keys of the client used for recovering the messages stored in its inbox.
+    /// * `connection_keys`: keys established between the client and the gateway used to encrypt and tag the messages.
     async fn push_stored_messages_to_client(
         &mut self,
         client_address: DestinationAddressBytes,
-        shared_keys: SharedKeys,
+        shared_keys: &SharedKeys,
+        connection_keys: &ConnectionKeys,
     ) -> Result<(), InitialAuthenticationError>
     where
         S: AsyncRead + AsyncWrite + Unpin,
@@ -269,7 +313,7 @@ where
             // retrieve some messages
             let (messages, new_start_next_after) = self
                 .storage
-                .retrieve_messages(client_address, start_next_after)
+                .retrieve_messages(client_address, shared_keys, start_next_after)
                 .await?;
 
             let (messages, ids) = messages
@@ -278,7 +322,7 @@ where
                 .unzip();
 
             // push them to the client
-            if let Err(err) = self.push_packets_to_client(shared_keys, messages).await {
+            if let Err(err) = self.push_packets_to_client(connection_keys, messages).await {
                 warn!(
                     "We failed to send stored messages to fresh client - {}",
                     err
@@ -372,7 +416,7 @@ where
             .await?;
 
         if let Some(shared_keys) = shared_keys {
-            self.push_stored_messages_to_client(client_address, shared_keys)
+            self.push_stored_messages_to_client(client_address, &shared_keys, &shared_keys.into())
                 .await?;
             Ok(Some(shared_keys))
         } else {
@@ -415,7 +459,7 @@ where
             .await?
             .unwrap_or(0);
         let client_details =
-            shared_keys.map(|shared_keys| ClientDetails::new(address, shared_keys));
+            shared_keys.map(|shared_keys| ClientDetails::new(address, shared_keys.into()));
 
         Ok(InitialAuthResult::new(
             client_details,
@@ -433,10 +477,14 @@ where
     ///
     /// # Arguments
     ///
-    /// * `client`: details (i.e. address and shared keys) of the registered client
+    /// * `client`: details (i.e. address and connection keys) of the registered client
+    /// * `shared_keys`: long-term keys derived during the handshake that are going to be persisted
+    /// * `overwrite_keys`: whether the keys should replace the ones already stored for the client
     async fn register_client(
         &mut self,
-        client: ClientDetails,
+        client: &ClientDetails,
+        shared_keys: SharedKeys,
+        overwrite_keys: bool,
     ) -> Result<bool, InitialAuthenticationError>
     where
         S: AsyncRead + AsyncWrite + Unpin,
@@ -446,9 +494,19 @@ where
             client.address.as_base58_string()
         );
 
-        self.storage
-            .insert_shared_keys(client.address, client.shared_keys)
-            .await?;
+        let shared_keys = match self.storage.get_shared_keys(client.address).await? {
+            // the unwrap here is fine as we only ever construct persisted shared keys ourselves
+            Some(stored_keys) if !overwrite_keys => SharedKeys::try_from_base58_string(
+                stored_keys.derived_aes128_ctr_blake3_hmac_keys_bs58,
+            )
+            .unwrap(),
+            _ => {
+                self.storage
+                    .insert_shared_keys(client.address, shared_keys)
+                    .await?;
+                shared_keys
+            }
+        };
 
         // see if we have bandwidth entry for the client already, if not, create one with zero value
         if self
@@ -460,7 +518,7 @@ where
             self.storage.create_bandwidth_entry(client.address).await?;
         }
 
-        self.push_stored_messages_to_client(client.address, client.shared_keys)
+        self.push_stored_messages_to_client(client.address, &shared_keys, &client.connection_keys)
             .await?;
 
         Ok(true)
@@ -487,9 +545,11 @@ where
         }
 
         let shared_keys = self.perform_registration_handshake(init_data).await?;
-        let client_details = ClientDetails::new(remote_address, shared_keys);
+        let client_details = ClientDetails::new(remote_address, shared_keys.into());
 
-        let status = self.register_client(client_details).await?;
+        let status = self
+            .register_client(&client_details, shared_keys, true)
+            .await?;
 
         Ok(InitialAuthResult::new(
             Some(client_details),
@@ -497,6 +557,51 @@ where
         ))
     }
 
+    /// Tries to handle the received register request by attempting to complete the Noise-based
+    /// handshake using the received data. Unlike the legacy registration, it is performed on
+    /// every connection and thus the response includes the currently available bandwidth.
+    ///
+    /// # Arguments
+    ///
+    /// * `init_data`: init payload of the Noise handshake.
+    async fn handle_noise_register(
+        &mut self,
+        init_data: Vec<u8>,
+    ) -> Result<InitialAuthResult, InitialAuthenticationError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        // the identity of the client is only revealed (and authenticated) during the handshake itself
+        let handshake_keys = self.perform_noise_handshake(init_data).await?;
+        let remote_address = handshake_keys.remote_identity.derive_destination_address();
+
+        if self.active_clients_store.get(remote_address).is_some() {
+            return Err(InitialAuthenticationError::DuplicateConnection);
+        }
+
+        let client_details = ClientDetails::new(remote_address, handshake_keys.session_keys.into());
+
+        // the client might have previously registered with the legacy handshake and still rely on
+        // the keys derived back then (for example for retrieving its stored messages with an
+        // older version of the software), so they must never get replaced
+        let status = self
+            .register_client(&client_details, handshake_keys.shared_keys, false)
+            .await?;
+        let bandwidth_remaining = self
+            .storage
+            .get_available_bandwidth(remote_address)
+            .await?
+            .unwrap_or(0);
+
+        Ok(InitialAuthResult::new(
+            Some(client_details),
+            ServerResponse::Authenticate {
+                status,
+                bandwidth_remaining,
+            },
+        ))
+    }
+
     /// Handles data that resembles request to either start registration handshake or perform
     /// authentication.
     ///
@@ -517,9 +622,16 @@ where
                     enc_address,
                     iv,
                 } => self.handle_authenticate(address, enc_address, iv).await,
-                ClientControlRequest::RegisterHandshakeInitRequest { data } => {
-                    self.handle_register(data).await
-                }
+                ClientControlRequest::RegisterHandshakeInitRequest {
+                    data,
+                    protocol_version,
+                } => match protocol_version.unwrap_or(LEGACY_HANDSHAKE_VERSION) {
+                    LEGACY_HANDSHAKE_VERSION => self.handle_register(data).await,
+                    NOISE_HANDSHAKE_VERSION => self.handle_noise_register(data).await,
+                    other => Err(InitialAuthenticationError::UnsupportedHandshakeVersion(
+                        other,
+                    )),
+                },
                 // won't accept anything else (like bandwidth) without prior authentication
                 _ => Err(InitialAuthenticationError::InvalidRequest),
             }
@@ -1,7 +1,7 @@
 // Copyright 2021 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use gateway_requests::registration::handshake::SharedKeys;
+use gateway_requests::registration::handshake::ConnectionKeys;
 use gateway_requests::ServerResponse;
 use log::{trace, warn};
 use nymsphinx::DestinationAddressBytes;
@@ -37,17 +37,17 @@ impl<S> SocketStream<S> {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub(crate) struct ClientDetails {
     pub(crate) address: DestinationAddressBytes,
-    pub(crate) shared_keys: SharedKeys,
+    pub(crate) connection_keys: ConnectionKeys,
 }
 
 impl ClientDetails {
-    pub(crate) fn new(address: DestinationAddressBytes, shared_keys: SharedKeys) -> Self {
+    pub(crate) fn new(address: DestinationAddressBytes, connection_keys: ConnectionKeys) -> Self {
         ClientDetails {
             address,
-            shared_keys,
+            connection_keys,
         }
     }
 }
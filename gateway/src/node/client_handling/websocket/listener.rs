@@ -3,8 +3,9 @@
 
 use crate::node::client_handling::active_clients::ActiveClientsStore;
 use crate::node::client_handling::websocket::connection_handler::FreshHandler;
+use crate::node::node_statistics::SharedGatewayStats;
 use crate::node::storage::Storage;
-use crypto::asymmetric::identity;
+use crypto::asymmetric::{encryption, identity};
 use log::*;
 use mixnet_client::forwarder::MixForwardingSender;
 use rand::rngs::OsRng;
@@ -12,14 +13,19 @@ use std::net::SocketAddr;
 use std::process;
 use std::sync::Arc;
 use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
 
 #[cfg(feature = "coconut")]
 use crate::node::client_handling::websocket::connection_handler::coconut::CoconutVerifier;
 
+#[derive(Clone)]
 pub(crate) struct Listener {
     address: SocketAddr,
     local_identity: Arc<identity::KeyPair>,
+    sphinx_key: encryption::PublicKey,
     disabled_credentials_mode: bool,
+    tls_acceptor: Option<TlsAcceptor>,
+    stats: SharedGatewayStats,
 
     #[cfg(feature = "coconut")]
     pub(crate) coconut_verifier: Arc<CoconutVerifier>,
@@ -29,18 +35,55 @@ impl Listener {
     pub(crate) fn new(
         address: SocketAddr,
         local_identity: Arc<identity::KeyPair>,
+        sphinx_key: encryption::PublicKey,
         disabled_credentials_mode: bool,
+        stats: SharedGatewayStats,
         #[cfg(feature = "coconut")] coconut_verifier: Arc<CoconutVerifier>,
     ) -> Self {
         Listener {
             address,
             local_identity,
+            sphinx_key,
             disabled_credentials_mode,
+            tls_acceptor: None,
+            stats,
             #[cfg(feature = "coconut")]
             coconut_verifier,
         }
     }
 
+    /// Makes the listener terminate TLS on all accepted connections before
+    /// performing the websocket handshake.
+    pub(crate) fn with_tls_acceptor(mut self, tls_acceptor: TlsAcceptor) -> Self {
+        self.tls_acceptor = Some(tls_acceptor);
+        self
+    }
+
+    fn new_handler<S, St>(
+        &self,
+        conn: S,
+        outbound_mix_sender: MixForwardingSender,
+        storage: St,
+        active_clients_store: ActiveClientsStore,
+    ) -> FreshHandler<OsRng, S, St>
+    where
+        St: Storage,
+    {
+        FreshHandler::new(
+            OsRng,
+            conn,
+            self.disabled_credentials_mode,
+            outbound_mix_sender,
+            Arc::clone(&self.local_identity),
+            self.sphinx_key,
+            storage,
+            active_clients_store,
+            self.stats.clone(),
+            #[cfg(feature = "coconut")]
+            Arc::clone(&self.coconut_verifier),
+        )
+    }
+
     // TODO: change the signature to pub(crate) async fn run(&self, handler: Handler)
 
     pub(crate) async fn run<St>(
@@ -51,7 +94,11 @@ impl Listener {
     ) where
         St: Storage + Clone + 'static,
     {
-        info!("Starting websocket listener at {}", self.address);
+        if self.tls_acceptor.is_some() {
+            info!("Starting TLS websocket listener at {}", self.address);
+        } else {
+            info!("Starting websocket listener at {}", self.address);
+        }
         let tcp_listener = match tokio::net::TcpListener::bind(self.address).await {
             Ok(listener) => listener,
             Err(err) => {
@@ -66,18 +113,43 @@ impl Listener {
                     trace!("received a socket connection from {}", remote_addr);
                     // TODO: I think we *REALLY* need a mechanism for having a maximum number of connected
                     // clients or spawned tokio tasks -> perhaps a worker system?
-                    let handle = FreshHandler::new(
-                        OsRng,
-                        socket,
-                        self.disabled_credentials_mode,
-                        outbound_mix_sender.clone(),
-                        Arc::clone(&self.local_identity),
-                        storage.clone(),
-                        active_clients_store.clone(),
-                        #[cfg(feature = "coconut")]
-                        Arc::clone(&self.coconut_verifier),
-                    );
-                    tokio::spawn(async move { handle.start_handling().await });
+                    match &self.tls_acceptor {
+                        None => {
+                            let handle = self.new_handler(
+                                socket,
+                                outbound_mix_sender.clone(),
+                                storage.clone(),
+                                active_clients_store.clone(),
+                            );
+                            tokio::spawn(async move { handle.start_handling().await });
+                        }
+                        Some(tls_acceptor) => {
+                            let tls_acceptor = tls_acceptor.clone();
+                            let listener = self.clone();
+                            let outbound_mix_sender = outbound_mix_sender.clone();
+                            let storage = storage.clone();
+                            let active_clients_store = active_clients_store.clone();
+                            // the TLS handshake is performed on the handler task so that a slow
+                            // client could not stall the accept loop
+                            tokio::spawn(async move {
+                                match tls_acceptor.accept(socket).await {
+                                    Ok(tls_stream) => {
+                                        let handle = listener.new_handler(
+                                            tls_stream,
+                                            outbound_mix_sender,
+                                            storage,
+                                            active_clients_store,
+                                        );
+                                        handle.start_handling().await
+                                    }
+                                    Err(err) => warn!(
+                                        "failed to complete TLS handshake with {} - {}",
+                                        remote_addr, err
+                                    ),
+                                }
+                            });
+                        }
+                    }
                 }
                 Err(e) => warn!("failed to get client: {:?}", e),
             }
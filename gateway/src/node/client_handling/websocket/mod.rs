@@ -6,3 +6,4 @@ pub(crate) use listener::Listener;
 pub(crate) mod connection_handler;
 pub(crate) mod listener;
 pub(crate) mod message_receiver;
+pub(crate) mod tls;
@@ -0,0 +1,80 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio_rustls::rustls::{self, Certificate, PrivateKey, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+#[derive(Debug, Error)]
+pub(crate) enum TlsSetupError {
+    #[error("failed to read the TLS certificate file {path:?} - {source}")]
+    CertificateReadFailure { path: PathBuf, source: io::Error },
+
+    #[error("the TLS certificate file {0:?} does not contain any certificates")]
+    NoCertificates(PathBuf),
+
+    #[error("failed to read the TLS private key file {path:?} - {source}")]
+    PrivateKeyReadFailure { path: PathBuf, source: io::Error },
+
+    #[error("the TLS private key file {0:?} does not contain any supported private key")]
+    NoPrivateKey(PathBuf),
+
+    #[error("the provided TLS certificate and private key are invalid - {0}")]
+    InvalidConfiguration(#[from] rustls::Error),
+}
+
+fn load_certificates(path: &Path) -> Result<Vec<Certificate>, TlsSetupError> {
+    let read_err = |source| TlsSetupError::CertificateReadFailure {
+        path: path.to_owned(),
+        source,
+    };
+
+    let mut reader = BufReader::new(File::open(path).map_err(read_err)?);
+    let certificates = rustls_pemfile::certs(&mut reader).map_err(read_err)?;
+    if certificates.is_empty() {
+        return Err(TlsSetupError::NoCertificates(path.to_owned()));
+    }
+
+    Ok(certificates.into_iter().map(Certificate).collect())
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKey, TlsSetupError> {
+    let read_err = |source| TlsSetupError::PrivateKeyReadFailure {
+        path: path.to_owned(),
+        source,
+    };
+
+    let mut reader = BufReader::new(File::open(path).map_err(read_err)?);
+    // use the first key found in the file, regardless of its encoding
+    while let Some(item) = rustls_pemfile::read_one(&mut reader).map_err(read_err)? {
+        match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(PrivateKey(key)),
+            _ => continue,
+        }
+    }
+
+    Err(TlsSetupError::NoPrivateKey(path.to_owned()))
+}
+
+/// Creates the acceptor used for terminating TLS on the client websocket listener
+/// out of the PEM-encoded certificate chain and the private key.
+pub(crate) fn load_tls_acceptor(
+    certificate_file: &Path,
+    private_key_file: &Path,
+) -> Result<TlsAcceptor, TlsSetupError> {
+    let certificates = load_certificates(certificate_file)?;
+    let private_key = load_private_key(private_key_file)?;
+
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certificates, private_key)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
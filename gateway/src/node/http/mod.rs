@@ -0,0 +1,14 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+pub(crate) mod description;
+pub(crate) mod sphinx_keys;
+pub(crate) mod stats;
+pub(crate) mod version;
+
+use rocket::Request;
+
+#[catch(404)]
+pub(crate) fn not_found(req: &Request<'_>) -> String {
+    format!("I couldn't find '{}'. Try something else?", req.uri())
+}
@@ -0,0 +1,33 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::asymmetric::identity;
+use mixnode_common::sphinx_key_rotation::{SphinxKeyAnnouncement, SphinxKeyRing};
+use rocket::serde::json::Json;
+use rocket::State;
+use std::sync::Arc;
+
+pub(crate) struct SphinxKeysState {
+    key_ring: SphinxKeyRing,
+    identity_keypair: Arc<identity::KeyPair>,
+}
+
+impl SphinxKeysState {
+    pub fn new(key_ring: SphinxKeyRing, identity_keypair: Arc<identity::KeyPair>) -> Self {
+        SphinxKeysState {
+            key_ring,
+            identity_keypair,
+        }
+    }
+}
+
+/// Provides the sphinx keys this gateway is going to use for the current and upcoming epochs,
+/// each signed with its identity key.
+#[get("/sphinx-keys")]
+pub(crate) fn sphinx_keys(state: &State<SphinxKeysState>) -> Json<Vec<SphinxKeyAnnouncement>> {
+    Json(
+        state
+            .key_ring
+            .announcements(state.identity_keypair.private_key()),
+    )
+}
@@ -0,0 +1,16 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::client_handling::active_clients::ActiveClientsStore;
+use crate::node::node_statistics::{GatewayStats, SharedGatewayStats};
+use rocket::serde::json::Json;
+use rocket::State;
+
+/// Returns the packet and client counters of the gateway.
+#[get("/stats")]
+pub(crate) fn stats(
+    stats: &State<SharedGatewayStats>,
+    active_clients: &State<ActiveClientsStore>,
+) -> Json<GatewayStats> {
+    Json(stats.snapshot(active_clients.size()))
+}
@@ -0,0 +1,52 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use gateway_requests::registration::handshake::{
+    LEGACY_HANDSHAKE_VERSION, NOISE_HANDSHAKE_VERSION,
+};
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct GatewayVersion {
+    build_version: String,
+    commit_sha: String,
+    features: GatewayFeatures,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct GatewayFeatures {
+    /// Versions of the client registration handshake understood by the gateway.
+    handshake_versions: Vec<u8>,
+
+    /// Indicates whether the gateway accepts TLS-terminated (`wss://`) client connections.
+    clients_wss: bool,
+
+    /// Indicates whether the gateway was built with support for coconut bandwidth credentials.
+    coconut_credentials: bool,
+
+    /// Indicates whether clients are allowed to claim bandwidth without presenting credentials.
+    disabled_credentials_mode: bool,
+}
+
+impl GatewayVersion {
+    pub(crate) fn new(clients_wss: bool, disabled_credentials_mode: bool) -> Self {
+        GatewayVersion {
+            build_version: env!("CARGO_PKG_VERSION").to_string(),
+            commit_sha: env!("VERGEN_GIT_SHA").to_string(),
+            features: GatewayFeatures {
+                handshake_versions: vec![LEGACY_HANDSHAKE_VERSION, NOISE_HANDSHAKE_VERSION],
+                clients_wss,
+                coconut_credentials: cfg!(feature = "coconut"),
+                disabled_credentials_mode,
+            },
+        }
+    }
+}
+
+/// Returns the version of the gateway alongside the protocol features it supports.
+#[get("/version")]
+pub(crate) fn version(version: &State<GatewayVersion>) -> Json<GatewayVersion> {
+    Json(version.inner().clone())
+}
@@ -4,18 +4,24 @@
 use crate::node::client_handling::active_clients::ActiveClientsStore;
 use crate::node::client_handling::websocket::message_receiver::MixMessageSender;
 use crate::node::mixnet_handling::receiver::packet_processing::PacketProcessor;
+use crate::node::node_statistics::SharedGatewayStats;
 use crate::node::storage::error::StorageError;
 use crate::node::storage::Storage;
+use crypto::asymmetric::identity;
 use futures::StreamExt;
 use log::*;
 use mixnet_client::forwarder::MixForwardingSender;
+use mixnet_client::hello::{self, DEFAULT_HELLO_TIMEOUT};
+use mixnet_client::link::peers::LinkPeers;
+use mixnet_client::link::{self, InboundLinkPolicy, DEFAULT_LINK_HANDSHAKE_TIMEOUT};
 use mixnode_common::packet_processor::processor::ProcessedFinalHop;
 use nymsphinx::forwarding::packet::MixPacket;
-use nymsphinx::framing::codec::SphinxCodec;
+use nymsphinx::framing::hello::{Features, Hello};
 use nymsphinx::framing::packet::FramedSphinxPacket;
 use nymsphinx::DestinationAddressBytes;
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::TcpStream;
 use tokio_util::codec::Framed;
 
@@ -30,6 +36,10 @@ pub(crate) struct ConnectionHandler<St: Storage> {
     active_clients_store: ActiveClientsStore,
     storage: St,
     ack_sender: MixForwardingSender,
+    stats: SharedGatewayStats,
+    identity: Arc<identity::KeyPair>,
+    link_peers: LinkPeers,
+    inbound_link_policy: InboundLinkPolicy,
 }
 
 impl<St: Storage + Clone> Clone for ConnectionHandler<St> {
@@ -48,6 +58,10 @@ impl<St: Storage + Clone> Clone for ConnectionHandler<St> {
             active_clients_store: self.active_clients_store.clone(),
             storage: self.storage.clone(),
             ack_sender: self.ack_sender.clone(),
+            stats: self.stats.clone(),
+            identity: Arc::clone(&self.identity),
+            link_peers: self.link_peers.clone(),
+            inbound_link_policy: self.inbound_link_policy,
         }
     }
 }
@@ -58,6 +72,10 @@ impl<St: Storage> ConnectionHandler<St> {
         storage: St,
         ack_sender: MixForwardingSender,
         active_clients_store: ActiveClientsStore,
+        stats: SharedGatewayStats,
+        identity: Arc<identity::KeyPair>,
+        link_peers: LinkPeers,
+        inbound_link_policy: InboundLinkPolicy,
     ) -> Self {
         ConnectionHandler {
             packet_processor,
@@ -65,6 +83,10 @@ impl<St: Storage> ConnectionHandler<St> {
             storage,
             active_clients_store,
             ack_sender,
+            stats,
+            identity,
+            link_peers,
+            inbound_link_policy,
         }
     }
 
@@ -142,10 +164,26 @@ impl<St: Storage> ConnectionHandler<St> {
                 .store_processed_packet_payload(client_address, unsent_plaintext)
                 .await
             {
-                Err(err) => error!("Failed to store client data - {}", err),
-                Ok(_) => trace!("Stored packet for {}", client_address),
+                Err(err) => {
+                    // the message is gone, so make sure the sender would learn about it
+                    // (and retransmit it) by not acknowledging it
+                    if matches!(err, StorageError::UnregisteredClient(..)) {
+                        debug!("Received a packet for an unknown client - {}", err)
+                    } else {
+                        error!("Failed to store client data - {}", err)
+                    }
+                    self.stats.dropped_mix_packet();
+                    return;
+                }
+                Ok(_) => {
+                    self.stats.stored_client_packet();
+                    trace!("Stored packet for {}", client_address)
+                }
             },
-            Ok(_) => trace!("Pushed received packet to {}", client_address),
+            Ok(_) => {
+                self.stats.pushed_client_packet();
+                trace!("Pushed received packet to {}", client_address)
+            }
         }
 
         // if we managed to either push message directly to the [online] client or store it at
@@ -155,16 +193,18 @@ impl<St: Storage> ConnectionHandler<St> {
     }
 
     async fn handle_received_packet(&mut self, framed_sphinx_packet: FramedSphinxPacket) {
-        //
-        // TODO: here be replay attack detection - it will require similar key cache to the one in
-        // packet processor for vpn packets,
-        // question: can it also be per connection vs global?
-        //
+        // note: replay detection is performed by the packet processor, whose cache is shared
+        // between all connections
 
+        self.stats.received_mix_packet();
         let processed_final_hop = match self.packet_processor.process_received(framed_sphinx_packet)
         {
             Err(e) => {
                 debug!("We failed to process received sphinx packet - {:?}", e);
+                if e.is_replay() {
+                    self.stats.replayed_mix_packet();
+                }
+                self.stats.dropped_mix_packet();
                 return;
             }
             Ok(processed_final_hop) => processed_final_hop,
@@ -173,9 +213,46 @@ impl<St: Storage> ConnectionHandler<St> {
         self.handle_processed_packet(processed_final_hop).await
     }
 
-    pub(crate) async fn handle_connection(mut self, conn: TcpStream, remote: SocketAddr) {
+    pub(crate) async fn handle_connection(mut self, mut conn: TcpStream, remote: SocketAddr) {
         debug!("Starting connection handler for {:?}", remote);
-        let mut framed_conn = Framed::new(conn, SphinxCodec);
+        // we're always able to respond to the link handshake, even if the policy
+        // ends up rejecting it
+        let local_hello = Hello::new(Features::LINK_ENCRYPTION | Features::OUTFOX);
+        match hello::accept_hello(&mut conn, &local_hello, DEFAULT_HELLO_TIMEOUT).await {
+            Ok(Some(remote_hello)) => {
+                trace!("{:?} has sent us its hello - {:?}", remote, remote_hello)
+            }
+            Ok(None) => (),
+            Err(err) => {
+                debug!("Failed to exchange hello with {:?} - {}", remote, err);
+                return;
+            }
+        }
+
+        let codec = match link::accept_link(
+            &mut conn,
+            &self.identity,
+            &self.link_peers,
+            self.inbound_link_policy,
+            DEFAULT_LINK_HANDSHAKE_TIMEOUT,
+        )
+        .await
+        {
+            Ok((codec, Some(remote_identity))) => {
+                debug!(
+                    "Established encrypted link with {} ({})",
+                    remote_identity, remote
+                );
+                codec
+            }
+            Ok((codec, None)) => codec,
+            Err(err) => {
+                debug!("Rejected connection from {:?} - {}", remote, err);
+                return;
+            }
+        };
+
+        let mut framed_conn = Framed::new(conn, codec);
         while let Some(framed_sphinx_packet) = framed_conn.next().await {
             match framed_sphinx_packet {
                 Ok(framed_sphinx_packet) => {
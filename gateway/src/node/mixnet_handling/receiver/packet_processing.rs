@@ -1,10 +1,10 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use crypto::asymmetric::encryption;
 use mixnode_common::packet_processor::error::MixProcessingError;
 pub use mixnode_common::packet_processor::processor::MixProcessingResult;
 use mixnode_common::packet_processor::processor::{ProcessedFinalHop, SphinxPacketProcessor};
+use mixnode_common::sphinx_key_rotation::SphinxKeyRing;
 use nymsphinx::framing::packet::FramedSphinxPacket;
 
 #[derive(Debug)]
@@ -13,6 +13,15 @@ pub enum GatewayProcessingError {
     ForwardHopReceivedError,
 }
 
+impl GatewayProcessingError {
+    pub(crate) fn is_replay(&self) -> bool {
+        matches!(
+            self,
+            GatewayProcessingError::PacketProcessingError(MixProcessingError::ReplayedPacket)
+        )
+    }
+}
+
 impl From<MixProcessingError> for GatewayProcessingError {
     fn from(e: MixProcessingError) -> Self {
         use GatewayProcessingError::*;
@@ -28,9 +37,9 @@ pub struct PacketProcessor {
 }
 
 impl PacketProcessor {
-    pub(crate) fn new(encryption_key: &encryption::PrivateKey) -> Self {
+    pub(crate) fn new(sphinx_keys: SphinxKeyRing) -> Self {
         PacketProcessor {
-            inner_processor: SphinxPacketProcessor::new(encryption_key.into()),
+            inner_processor: SphinxPacketProcessor::new_with_key_ring(sphinx_keys),
         }
     }
 
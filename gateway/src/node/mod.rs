@@ -6,20 +6,46 @@ use crate::commands::validate_bech32_address_or_exit;
 use crate::config::Config;
 use crate::node::client_handling::active_clients::ActiveClientsStore;
 use crate::node::client_handling::websocket;
+use crate::node::http::{
+    description::description,
+    not_found,
+    sphinx_keys::{sphinx_keys, SphinxKeysState},
+    stats::stats,
+    version::version,
+    version::GatewayVersion,
+};
 use crate::node::mixnet_handling::receiver::connection_handler::ConnectionHandler;
+use crate::node::node_statistics::SharedGatewayStats;
 use crate::node::statistics::collector::GatewayStatisticsCollector;
 use crate::node::storage::Storage;
+use config::reload::{
+    start_reload_signal_listener, ReloadError, ReloadRequestReceiver, ReloadRequester, ReloadResult,
+};
+use config::NymConfig;
 use crypto::asymmetric::{encryption, identity};
 use log::*;
 use mixnet_client::forwarder::{MixForwardingSender, PacketForwarder};
+use mixnet_client::link::peers::LinkPeers;
+use mixnet_client::link::LinkConfig;
+use mixnode_common::http::hardware::hardware;
+use mixnode_common::http::reload::reload_config;
+use mixnode_common::link_peers::{LinkPeersRefresher, DEFAULT_LINK_PEERS_REFRESH_INTERVAL};
+use mixnode_common::node_description::NodeDescription;
+use mixnode_common::sphinx_key_rotation::{
+    current_unix_timestamp, SphinxKeyRing, ROTATION_CHECK_INTERVAL,
+};
+use mixnode_common::validator_api::ValidatorApiEndpoints;
 #[cfg(feature = "coconut")]
 use network_defaults::NymNetworkDetails;
+#[cfg(feature = "coconut")]
 use rand::seq::SliceRandom;
+#[cfg(feature = "coconut")]
 use rand::thread_rng;
 use statistics_common::collector::StatisticsSender;
 use std::net::SocketAddr;
 use std::process;
 use std::sync::Arc;
+use tokio_rustls::TlsAcceptor;
 
 use crate::config::persistence::pathfinder::GatewayPathfinder;
 #[cfg(feature = "coconut")]
@@ -32,7 +58,9 @@ use validator_client::nymd;
 use self::storage::PersistentStorage;
 
 pub(crate) mod client_handling;
+mod http;
 pub(crate) mod mixnet_handling;
+pub(crate) mod node_statistics;
 pub(crate) mod statistics;
 pub(crate) mod storage;
 
@@ -53,10 +81,18 @@ async fn initialise_storage(config: &Config) -> PersistentStorage {
 
 pub(crate) struct Gateway<St: Storage> {
     config: Config,
+    descriptor: NodeDescription,
+    stats: SharedGatewayStats,
     /// ed25519 keypair used to assert one's identity.
     identity_keypair: Arc<identity::KeyPair>,
     /// x25519 keypair used for Diffie-Hellman. Currently only used for sphinx key derivation.
     sphinx_keypair: Arc<encryption::KeyPair>,
+    /// Sphinx keys currently used for unwrapping received packets, including the rotated ones.
+    sphinx_key_ring: SphinxKeyRing,
+    /// Directory of nodes of the network used for establishing and authenticating mix links.
+    link_peers: LinkPeers,
+    /// Validator APIs used for obtaining the view of the network.
+    validator_api: ValidatorApiEndpoints,
     storage: St,
 }
 
@@ -68,11 +104,17 @@ where
     pub async fn new(config: Config, storage: St) -> Self {
         let pathfinder = GatewayPathfinder::new_from_config(&config);
         // let storage = Self::initialise_storage(&config).await;
+        let sphinx_keypair = Self::load_sphinx_keys(&pathfinder);
 
         Gateway {
+            descriptor: Self::load_node_description(&config),
+            stats: SharedGatewayStats::new(),
+            validator_api: ValidatorApiEndpoints::new(config.get_validator_api_endpoints()),
+            sphinx_key_ring: Self::load_sphinx_key_ring(&config, &sphinx_keypair),
             config,
             identity_keypair: Arc::new(Self::load_identity_keys(&pathfinder)),
-            sphinx_keypair: Arc::new(Self::load_sphinx_keys(&pathfinder)),
+            sphinx_keypair: Arc::new(sphinx_keypair),
+            link_peers: LinkPeers::new(),
             storage,
         }
     }
@@ -85,13 +127,23 @@ where
         storage: St,
     ) -> Self {
         Gateway {
+            descriptor: Self::load_node_description(&config),
+            stats: SharedGatewayStats::new(),
+            validator_api: ValidatorApiEndpoints::new(config.get_validator_api_endpoints()),
             config,
             identity_keypair: Arc::new(identity_keypair),
+            sphinx_key_ring: SphinxKeyRing::new_static(sphinx_keypair.private_key().into()),
             sphinx_keypair: Arc::new(sphinx_keypair),
+            link_peers: LinkPeers::new(),
             storage,
         }
     }
 
+    fn load_node_description(config: &Config) -> NodeDescription {
+        NodeDescription::load_from_file(Config::default_config_directory(Some(&config.get_id())))
+            .unwrap_or_default()
+    }
+
     fn load_identity_keys(pathfinder: &GatewayPathfinder) -> identity::KeyPair {
         let identity_keypair: identity::KeyPair =
             pemstore::load_keypair(&pemstore::KeyPairPath::new(
@@ -112,6 +164,24 @@ where
         sphinx_keypair
     }
 
+    fn load_sphinx_key_ring(
+        config: &Config,
+        sphinx_keypair: &encryption::KeyPair,
+    ) -> SphinxKeyRing {
+        let static_key = sphinx_keypair.private_key().into();
+        if !config.get_enable_sphinx_key_rotation() {
+            return SphinxKeyRing::new_static(static_key);
+        }
+
+        match SphinxKeyRing::new_rotating(static_key, config.get_rotated_sphinx_keys_file()) {
+            Ok(key_ring) => key_ring,
+            Err(err) => {
+                error!("failed to load the rotated sphinx keys - {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
     /// Signs the node config's bech32 address to produce a verification code for use in the wallet.
     /// Exits if the address isn't valid (which should protect against manual edits).
     fn generate_owner_signature(&self) -> String {
@@ -140,10 +210,14 @@ where
         );
         println!("Version: {}", self.config.get_version());
         println!(
-            "Mix Port: {}, Clients port: {}",
+            "Mix Port: {}, Clients port: {}, Http Port: {}",
             self.config.get_mix_port(),
-            self.config.get_clients_port()
+            self.config.get_clients_port(),
+            self.config.get_http_api_port()
         );
+        if let Some(wss_port) = self.config.get_clients_wss_port() {
+            println!("Clients wss port: {}", wss_port);
+        }
 
         println!(
             "Data store is at: {:?}",
@@ -158,14 +232,17 @@ where
     ) {
         info!("Starting mix socket listener...");
 
-        let packet_processor =
-            mixnet_handling::PacketProcessor::new(self.sphinx_keypair.private_key());
+        let packet_processor = mixnet_handling::PacketProcessor::new(self.sphinx_key_ring.clone());
 
         let connection_handler = ConnectionHandler::new(
             packet_processor,
             self.storage.clone(),
             ack_sender,
             active_clients_store,
+            self.stats.clone(),
+            Arc::clone(&self.identity_keypair),
+            self.link_peers.clone(),
+            self.config.get_inbound_link_policy(),
         );
 
         let listening_address = SocketAddr::new(
@@ -189,10 +266,36 @@ where
             self.config.get_clients_port(),
         );
 
+        if let Some(tls_acceptor) = self.load_client_tls_acceptor() {
+            let tls_listening_address = SocketAddr::new(
+                self.config.get_listening_address(),
+                // we wouldn't have created an acceptor if the port wasn't set
+                self.config.get_clients_wss_port().unwrap(),
+            );
+
+            websocket::Listener::new(
+                tls_listening_address,
+                Arc::clone(&self.identity_keypair),
+                *self.sphinx_keypair.public_key(),
+                self.config.get_disabled_credentials_mode(),
+                self.stats.clone(),
+                #[cfg(feature = "coconut")]
+                Arc::clone(&coconut_verifier),
+            )
+            .with_tls_acceptor(tls_acceptor)
+            .start(
+                forwarding_channel.clone(),
+                self.storage.clone(),
+                active_clients_store.clone(),
+            );
+        }
+
         websocket::Listener::new(
             listening_address,
             Arc::clone(&self.identity_keypair),
+            *self.sphinx_keypair.public_key(),
             self.config.get_disabled_credentials_mode(),
+            self.stats.clone(),
             #[cfg(feature = "coconut")]
             coconut_verifier,
         )
@@ -203,6 +306,87 @@ where
         );
     }
 
+    fn start_http_api(
+        &self,
+        active_clients_store: ActiveClientsStore,
+        reload_requester: ReloadRequester,
+    ) {
+        info!(
+            "Starting HTTP API on http://{}:{}",
+            self.config.get_listening_address(),
+            self.config.get_http_api_port()
+        );
+
+        let mut config = rocket::config::Config::release_default();
+
+        // bind to the same address as we are using for the mix and client traffic
+        config.address = self.config.get_listening_address();
+        config.port = self.config.get_http_api_port();
+
+        let descriptor = self.descriptor.clone();
+        let node_stats = self.stats.clone();
+        let sphinx_keys_state = SphinxKeysState::new(
+            self.sphinx_key_ring.clone(),
+            Arc::clone(&self.identity_keypair),
+        );
+        let gateway_version = GatewayVersion::new(
+            self.config.get_clients_wss_port().is_some(),
+            self.config.get_disabled_credentials_mode(),
+        );
+
+        tokio::spawn(async move {
+            rocket::build()
+                .configure(config)
+                .mount(
+                    "/",
+                    routes![
+                        description,
+                        hardware,
+                        stats,
+                        version,
+                        reload_config,
+                        sphinx_keys
+                    ],
+                )
+                .register("/", catchers![not_found])
+                .manage(descriptor)
+                .manage(node_stats)
+                .manage(active_clients_store)
+                .manage(gateway_version)
+                .manage(reload_requester)
+                .manage(sphinx_keys_state)
+                .launch()
+                .await
+        });
+    }
+
+    fn load_client_tls_acceptor(&self) -> Option<TlsAcceptor> {
+        let wss_port = self.config.get_clients_wss_port()?;
+        let (certificate_file, private_key_file) = match (
+            self.config.get_tls_certificate_file(),
+            self.config.get_tls_private_key_file(),
+        ) {
+            (Some(certificate_file), Some(private_key_file)) => {
+                (certificate_file, private_key_file)
+            }
+            _ => {
+                error!(
+                    "The TLS client listener was requested on port {}, but the TLS certificate or private key file is not specified",
+                    wss_port
+                );
+                process::exit(1);
+            }
+        };
+
+        match websocket::tls::load_tls_acceptor(&certificate_file, &private_key_file) {
+            Ok(tls_acceptor) => Some(tls_acceptor),
+            Err(err) => {
+                error!("Failed to set up the TLS client listener - {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
     fn start_packet_forwarder(&self) -> MixForwardingSender {
         info!("Starting mix packet forwarder...");
 
@@ -213,30 +397,100 @@ where
             self.config.get_maximum_connection_buffer_size(),
             self.config.get_use_legacy_sphinx_framing(),
         );
+        if self.config.get_use_encrypted_links() {
+            packet_forwarder = packet_forwarder.with_link_encryption(LinkConfig::new(
+                Arc::clone(&self.identity_keypair),
+                self.link_peers.clone(),
+                !self.config.get_require_encrypted_links(),
+            ));
+        }
 
         tokio::spawn(async move { packet_forwarder.run().await });
         packet_sender
     }
 
-    async fn wait_for_interrupt(&self) {
-        if let Err(e) = tokio::signal::ctrl_c().await {
-            error!(
-                "There was an error while capturing SIGINT - {:?}. We will terminate regardless",
-                e
-            );
+    fn start_sphinx_key_rotator(&self) {
+        if !self.sphinx_key_ring.rotation_enabled() {
+            return;
         }
-        println!(
-            "Received SIGINT - the gateway will terminate now (threads are not yet nicely stopped, if you see stack traces that's alright)."
+        info!("Starting sphinx key rotator...");
+        let key_ring = self.sphinx_key_ring.clone();
+
+        // the gateway does not support graceful shutdown yet, so just keep rotating forever
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(ROTATION_CHECK_INTERVAL).await;
+                key_ring.rotate(current_unix_timestamp());
+            }
+        });
+    }
+
+    fn start_link_peers_refresher(&self) {
+        info!("Starting link peers refresher...");
+        let refresher = LinkPeersRefresher::new(
+            self.validator_api.clone(),
+            self.link_peers.clone(),
+            DEFAULT_LINK_PEERS_REFRESH_INTERVAL,
         );
+
+        // the gateway does not support graceful shutdown yet, so just keep refreshing forever
+        tokio::spawn(async move {
+            loop {
+                refresher.refresh().await;
+                tokio::time::sleep(DEFAULT_LINK_PEERS_REFRESH_INTERVAL).await;
+            }
+        });
     }
 
-    fn random_api_client(&self) -> validator_client::ApiClient {
-        let endpoints = self.config.get_validator_api_endpoints();
-        let validator_api = endpoints
-            .choose(&mut thread_rng())
-            .expect("The list of validator apis is empty");
+    /// Re-reads the configuration file and applies all the changes, as long as none of them
+    /// requires restarting the gateway.
+    fn reload_config(&mut self) -> ReloadResult {
+        let updated =
+            Config::load_from_file(Some(&self.config.get_id())).map_err(ReloadError::Load)?;
+        let changes = self.config.check_live_reload(&updated)?;
+
+        if self.config.get_logging_filters() != updated.get_logging_filters() {
+            logging::set_logging_filters(updated.get_logging_filters());
+        }
+        self.validator_api
+            .update(updated.get_validator_api_endpoints());
+
+        self.config = updated;
+        Ok(changes)
+    }
 
-        validator_client::ApiClient::new(validator_api.clone())
+    async fn wait_for_interrupt(&mut self, mut reload_requests: ReloadRequestReceiver) {
+        loop {
+            tokio::select! {
+                interrupt = tokio::signal::ctrl_c() => {
+                    if let Err(e) = interrupt {
+                        error!(
+                            "There was an error while capturing SIGINT - {:?}. We will terminate regardless",
+                            e
+                        );
+                    }
+                    break;
+                }
+                Some(result_sender) = reload_requests.recv() => {
+                    let result = self.reload_config();
+                    match &result {
+                        Ok(changes) if changes.is_empty() => {
+                            info!("The configuration file has not changed")
+                        }
+                        Ok(changes) => {
+                            info!("Applied the changes of: {}", changes.join(", "))
+                        }
+                        Err(err) => warn!("Rejected the configuration reload - {}", err),
+                    }
+                    // the requester might have gone away in the meantime,
+                    // but the changes are applied regardless
+                    let _ = result_sender.send(result);
+                }
+            }
+        }
+        println!(
+            "Received SIGINT - the gateway will terminate now (threads are not yet nicely stopped, if you see stack traces that's alright)."
+        );
     }
 
     #[cfg(feature = "coconut")]
@@ -272,7 +526,7 @@ where
 
     // TODO: ask DH whether this function still makes sense in ^0.10
     async fn check_if_same_ip_gateway_exists(&self) -> Option<String> {
-        let validator_client = self.random_api_client();
+        let validator_client = self.validator_api.random_client();
 
         let existing_gateways = match validator_client.get_cached_gateways().await {
             Ok(gateways) => gateways,
@@ -293,6 +547,11 @@ where
     pub async fn run(&mut self) {
         info!("Starting nym gateway!");
 
+        // the filters from the config take precedence over `RUST_LOG`
+        if let Some(filters) = self.config.get_logging_filters() {
+            logging::set_logging_filters(Some(filters));
+        }
+
         if let Some(duplicate_node_key) = self.check_if_same_ip_gateway_exists().await {
             if duplicate_node_key == self.identity_keypair.public_key().to_base58_string() {
                 warn!("We seem to have not unregistered after going offline - there's a node with identical identity and announce-host as us registered.")
@@ -321,6 +580,8 @@ where
         )
         .expect("Could not create coconut verifier");
 
+        self.start_sphinx_key_rotator();
+        self.start_link_peers_refresher();
         let mix_forwarding_channel = self.start_packet_forwarder();
 
         let active_clients_store = ActiveClientsStore::new();
@@ -342,6 +603,10 @@ where
             });
         }
 
+        let (reload_requester, reload_requests) = ReloadRequester::new();
+        self.start_http_api(active_clients_store.clone(), reload_requester.clone());
+        start_reload_signal_listener(reload_requester);
+
         self.start_client_websocket_listener(
             mix_forwarding_channel,
             active_clients_store,
@@ -351,6 +616,6 @@ where
 
         info!("Finished nym gateway startup procedure - it should now be able to receive mix and client traffic!");
 
-        self.wait_for_interrupt().await
+        self.wait_for_interrupt(reload_requests).await
     }
 }
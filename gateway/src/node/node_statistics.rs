@@ -0,0 +1,137 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Running counters of the traffic handled by the gateway since it has started.
+// note: the counters are only ever used for reporting purposes so relaxed ordering is more than enough
+#[derive(Clone)]
+pub(crate) struct SharedGatewayStats {
+    inner: Arc<GatewayStatsInner>,
+}
+
+struct GatewayStatsInner {
+    startup_time: SystemTime,
+
+    received_mix_packets: AtomicU64,
+    dropped_mix_packets: AtomicU64,
+    replayed_mix_packets: AtomicU64,
+    pushed_client_packets: AtomicU64,
+    stored_client_packets: AtomicU64,
+    forwarded_client_packets: AtomicU64,
+    client_sessions: AtomicU64,
+}
+
+impl SharedGatewayStats {
+    pub(crate) fn new() -> Self {
+        SharedGatewayStats {
+            inner: Arc::new(GatewayStatsInner {
+                startup_time: SystemTime::now(),
+                received_mix_packets: AtomicU64::new(0),
+                dropped_mix_packets: AtomicU64::new(0),
+                replayed_mix_packets: AtomicU64::new(0),
+                pushed_client_packets: AtomicU64::new(0),
+                stored_client_packets: AtomicU64::new(0),
+                forwarded_client_packets: AtomicU64::new(0),
+                client_sessions: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub(crate) fn received_mix_packet(&self) {
+        self.inner
+            .received_mix_packets
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dropped_mix_packet(&self) {
+        self.inner
+            .dropped_mix_packets
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn replayed_mix_packet(&self) {
+        self.inner
+            .replayed_mix_packets
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn pushed_client_packet(&self) {
+        self.inner
+            .pushed_client_packets
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stored_client_packet(&self) {
+        self.inner
+            .stored_client_packets
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn forwarded_client_packet(&self) {
+        self.inner
+            .forwarded_client_packets
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn new_client_session(&self) {
+        self.inner.client_sessions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Creates a snapshot of the current values of all the counters.
+    ///
+    /// # Arguments
+    ///
+    /// * `active_clients`: number of clients currently connected to the gateway.
+    pub(crate) fn snapshot(&self, active_clients: usize) -> GatewayStats {
+        GatewayStats {
+            startup_time: self.inner.startup_time,
+            update_time: SystemTime::now(),
+            received_mix_packets: self.inner.received_mix_packets.load(Ordering::Relaxed),
+            dropped_mix_packets: self.inner.dropped_mix_packets.load(Ordering::Relaxed),
+            replayed_mix_packets: self.inner.replayed_mix_packets.load(Ordering::Relaxed),
+            pushed_client_packets: self.inner.pushed_client_packets.load(Ordering::Relaxed),
+            stored_client_packets: self.inner.stored_client_packets.load(Ordering::Relaxed),
+            forwarded_client_packets: self.inner.forwarded_client_packets.load(Ordering::Relaxed),
+            client_sessions: self.inner.client_sessions.load(Ordering::Relaxed),
+            active_clients,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct GatewayStats {
+    #[serde(serialize_with = "humantime_serde::serialize")]
+    startup_time: SystemTime,
+
+    #[serde(serialize_with = "humantime_serde::serialize")]
+    update_time: SystemTime,
+
+    /// Number of sphinx packets received from the mix network.
+    received_mix_packets: u64,
+
+    /// Number of received sphinx packets that failed to get processed.
+    dropped_mix_packets: u64,
+
+    /// Number of received sphinx packets that got rejected as replays of already processed ones.
+    /// Note that those are also included in `dropped_mix_packets`.
+    replayed_mix_packets: u64,
+
+    /// Number of processed packets pushed directly to connected clients.
+    pushed_client_packets: u64,
+
+    /// Number of processed packets stored for offline clients.
+    stored_client_packets: u64,
+
+    /// Number of packets received from clients and forwarded into the mix network.
+    forwarded_client_packets: u64,
+
+    /// Number of successfully authenticated (or registered) client connections.
+    client_sessions: u64,
+
+    /// Number of clients currently connected to the gateway.
+    active_clients: usize,
+}
@@ -10,4 +10,10 @@ pub(crate) enum StorageError {
 
     #[error("Failed to perform database migration - {0}")]
     MigrationError(#[from] sqlx::migrate::MigrateError),
+
+    #[error("Client {0} has not registered with this gateway")]
+    UnregisteredClient(String),
+
+    #[error("The stored inbox key of {0} is malformed")]
+    MalformedInboxKey(String),
 }
@@ -0,0 +1,308 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use crypto::asymmetric::encryption;
+use crypto::hkdf;
+use gateway_requests::registration::handshake::SharedKeys;
+use nymsphinx::params::{GatewaySharedKeyHkdfAlgorithm, PacketSize};
+use nymsphinx::DestinationAddressBytes;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::convert::TryInto;
+use thiserror::Error;
+
+/// Domain separator used when deriving the key wrapping the inbox key from the client's shared keys.
+const INBOX_KEY_WRAPPING_INFO: &[u8] = b"nym-gateway-inbox-key-wrapping";
+
+/// Domain separator used when deriving the key of a particular stored message.
+const INBOX_MESSAGE_INFO: &[u8] = b"nym-gateway-inbox-message-encryption";
+
+const SYMMETRIC_KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+
+// every message is encrypted under a fresh ephemeral key, so the nonce never repeats for a given key
+const MESSAGE_NONCE: [u8; NONCE_SIZE] = [0u8; NONCE_SIZE];
+
+// the plaintext is prefixed with its actual length so that the padding could be removed
+const LENGTH_PREFIX_SIZE: usize = 4;
+
+const PADDING_SIZES: [PacketSize; 5] = [
+    PacketSize::AckPacket,
+    PacketSize::RegularPacket,
+    PacketSize::ExtendedPacket8,
+    PacketSize::ExtendedPacket16,
+    PacketSize::ExtendedPacket32,
+];
+
+#[derive(Debug, Error)]
+pub(crate) enum InboxDecryptionError {
+    #[error("the stored data is too short to have been produced by the inbox cipher")]
+    TooShortCiphertext,
+
+    #[error("the stored data could not be authenticated with the provided keys")]
+    AuthenticationFailure,
+
+    #[error("the stored message has malformed padding")]
+    MalformedPadding,
+
+    #[error("the stored inbox key is malformed - {0}")]
+    MalformedInboxKey(#[from] encryption::KeyRecoveryError),
+}
+
+fn derive_key(salt: &[u8], ikm: &[u8], info: &[u8]) -> Aes256Gcm {
+    // the unwrap is fine as the requested key size is way below the hkdf limit
+    let key = hkdf::extract_then_expand::<GatewaySharedKeyHkdfAlgorithm>(
+        Some(salt),
+        ikm,
+        Some(info),
+        SYMMETRIC_KEY_SIZE,
+    )
+    .unwrap();
+
+    Aes256Gcm::new(Key::from_slice(&key))
+}
+
+fn wrapping_cipher(
+    shared_keys: &SharedKeys,
+    client_address: &DestinationAddressBytes,
+) -> Aes256Gcm {
+    derive_key(
+        client_address.as_bytes_ref(),
+        &shared_keys.to_bytes(),
+        INBOX_KEY_WRAPPING_INFO,
+    )
+}
+
+fn message_cipher(shared_secret: &[u8], ephemeral_key: &encryption::PublicKey) -> Aes256Gcm {
+    derive_key(&ephemeral_key.to_bytes(), shared_secret, INBOX_MESSAGE_INFO)
+}
+
+fn padded_length(message_len: usize) -> usize {
+    let required = message_len + LENGTH_PREFIX_SIZE;
+    PADDING_SIZES
+        .iter()
+        .map(|packet_size| packet_size.size())
+        .find(|&size| size >= required)
+        .unwrap_or_else(|| {
+            // this should never happen as we're only ever storing unwrapped sphinx payloads,
+            // but if it did, just round up to the multiple of the largest packet
+            let largest = PacketSize::ExtendedPacket32.size();
+            ((required + largest - 1) / largest) * largest
+        })
+}
+
+/// Pads the provided message to the size of the smallest sphinx packet that can fit it and
+/// encrypts it for the inbox with the provided public key, producing EPHEMERAL_KEY || CIPHERTEXT || TAG.
+///
+/// The message key is derived from a fresh ephemeral x25519 key, so storing messages does not
+/// require any secrets of either the gateway or the client.
+/// The address of the client is used as the associated data, so that a message could not be
+/// moved between different inboxes without being detected.
+pub(crate) fn encrypt_message(
+    inbox_key: &encryption::PublicKey,
+    client_address: &DestinationAddressBytes,
+    message: &[u8],
+) -> Vec<u8> {
+    let mut padded = Vec::with_capacity(padded_length(message.len()));
+    padded.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    padded.extend_from_slice(message);
+    padded.resize(padded_length(message.len()), 0);
+
+    let ephemeral_keys = encryption::KeyPair::new(&mut OsRng);
+    let shared_secret = ephemeral_keys.private_key().diffie_hellman(inbox_key);
+
+    let payload = Payload {
+        msg: &padded,
+        aad: client_address.as_bytes_ref(),
+    };
+
+    // the encryption can only fail if the plaintext is longer than what AES-GCM supports
+    let ciphertext = message_cipher(&shared_secret, ephemeral_keys.public_key())
+        .encrypt(Nonce::from_slice(&MESSAGE_NONCE), payload)
+        .expect("failed to encrypt the inbox message");
+
+    ephemeral_keys
+        .public_key()
+        .to_bytes()
+        .into_iter()
+        .chain(ciphertext)
+        .collect()
+}
+
+/// Keys protecting the inbox of a particular client.
+///
+/// The messages are stored encrypted for the public key, while the private key is only ever
+/// persisted wrapped under the long-term shared keys of the client. It is re-wrapped whenever
+/// the client derives new shared keys, so that the stored messages survive the re-registration.
+///
+/// Note that the shared keys themselves have to be persisted in the very same database,
+/// as the gateway needs them for authenticating the client. The wrapping does therefore NOT
+/// protect the messages against anyone who obtains the whole database (or controls the gateway).
+/// It only ensures that the messages can't be read out of the inbox tables alone, that they
+/// can't be moved between inboxes and that they become unrecoverable once the shared keys of
+/// the client are removed.
+pub(crate) struct InboxKeys {
+    keys: encryption::KeyPair,
+}
+
+impl InboxKeys {
+    pub(crate) fn new() -> Self {
+        InboxKeys {
+            keys: encryption::KeyPair::new(&mut OsRng),
+        }
+    }
+
+    pub(crate) fn public_key(&self) -> &encryption::PublicKey {
+        self.keys.public_key()
+    }
+
+    /// Encrypts the private inbox key under the shared keys of the client, producing NONCE || CIPHERTEXT || TAG.
+    pub(crate) fn wrap(
+        &self,
+        shared_keys: &SharedKeys,
+        client_address: &DestinationAddressBytes,
+    ) -> Vec<u8> {
+        let mut nonce = [0u8; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+
+        let private_key = self.keys.private_key().to_bytes();
+        let payload = Payload {
+            msg: &private_key,
+            aad: client_address.as_bytes_ref(),
+        };
+
+        let ciphertext = wrapping_cipher(shared_keys, client_address)
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .expect("failed to wrap the inbox key");
+
+        nonce.into_iter().chain(ciphertext).collect()
+    }
+
+    /// Recovers the inbox keys wrapped under the provided shared keys of the client.
+    pub(crate) fn unwrap(
+        wrapped: &[u8],
+        shared_keys: &SharedKeys,
+        client_address: &DestinationAddressBytes,
+    ) -> Result<Self, InboxDecryptionError> {
+        if wrapped.len() < NONCE_SIZE + TAG_SIZE {
+            return Err(InboxDecryptionError::TooShortCiphertext);
+        }
+
+        let (nonce, ciphertext) = wrapped.split_at(NONCE_SIZE);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: client_address.as_bytes_ref(),
+        };
+
+        let private_key = wrapping_cipher(shared_keys, client_address)
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| InboxDecryptionError::AuthenticationFailure)?;
+        let private_key = encryption::PrivateKey::from_bytes(&private_key)?;
+        let public_key = encryption::PublicKey::from(&private_key);
+
+        Ok(InboxKeys {
+            keys: encryption::KeyPair::from_bytes(&private_key.to_bytes(), &public_key.to_bytes())?,
+        })
+    }
+
+    /// Decrypts the stored message and removes its padding.
+    pub(crate) fn decrypt(
+        &self,
+        client_address: &DestinationAddressBytes,
+        stored: &[u8],
+    ) -> Result<Vec<u8>, InboxDecryptionError> {
+        if stored.len() < encryption::PUBLIC_KEY_SIZE + LENGTH_PREFIX_SIZE + TAG_SIZE {
+            return Err(InboxDecryptionError::TooShortCiphertext);
+        }
+
+        let (ephemeral_key, ciphertext) = stored.split_at(encryption::PUBLIC_KEY_SIZE);
+        let ephemeral_key = encryption::PublicKey::from_bytes(ephemeral_key)?;
+        let shared_secret = self.keys.private_key().diffie_hellman(&ephemeral_key);
+
+        let payload = Payload {
+            msg: ciphertext,
+            aad: client_address.as_bytes_ref(),
+        };
+
+        let mut padded = message_cipher(&shared_secret, &ephemeral_key)
+            .decrypt(Nonce::from_slice(&MESSAGE_NONCE), payload)
+            .map_err(|_| InboxDecryptionError::AuthenticationFailure)?;
+
+        // the unwrap is fine as we've checked the length of the ciphertext before
+        let message_len =
+            u32::from_be_bytes(padded[..LENGTH_PREFIX_SIZE].try_into().unwrap()) as usize;
+        if message_len > padded.len() - LENGTH_PREFIX_SIZE {
+            return Err(InboxDecryptionError::MalformedPadding);
+        }
+
+        padded.truncate(LENGTH_PREFIX_SIZE + message_len);
+        Ok(padded.split_off(LENGTH_PREFIX_SIZE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dummy_shared_keys(byte: u8) -> SharedKeys {
+        SharedKeys::try_from_bytes(&[byte; 32]).unwrap()
+    }
+
+    fn dummy_address(byte: u8) -> DestinationAddressBytes {
+        DestinationAddressBytes::from_bytes([byte; 32])
+    }
+
+    #[test]
+    fn encrypted_message_can_be_recovered() {
+        let keys = InboxKeys::new();
+        let message = b"hello there, this is a stored message".to_vec();
+
+        let stored = encrypt_message(keys.public_key(), &dummy_address(1), &message);
+        assert_eq!(keys.decrypt(&dummy_address(1), &stored).unwrap(), message);
+    }
+
+    #[test]
+    fn messages_of_different_lengths_are_padded_to_the_same_size() {
+        let keys = InboxKeys::new();
+
+        let short = encrypt_message(keys.public_key(), &dummy_address(1), &[42u8; 1000]);
+        let long = encrypt_message(keys.public_key(), &dummy_address(1), &[42u8; 2000]);
+        assert_eq!(short.len(), long.len());
+        assert_eq!(
+            short.len(),
+            encryption::PUBLIC_KEY_SIZE + PacketSize::RegularPacket.size() + TAG_SIZE
+        );
+    }
+
+    #[test]
+    fn message_cannot_be_recovered_with_different_keys() {
+        let keys = InboxKeys::new();
+        let stored = encrypt_message(keys.public_key(), &dummy_address(1), b"foomp");
+
+        assert!(InboxKeys::new()
+            .decrypt(&dummy_address(1), &stored)
+            .is_err());
+        assert!(keys.decrypt(&dummy_address(2), &stored).is_err());
+    }
+
+    #[test]
+    fn inbox_key_can_only_be_unwrapped_with_the_same_shared_keys() {
+        let keys = InboxKeys::new();
+        let stored = encrypt_message(keys.public_key(), &dummy_address(1), b"foomp");
+        let wrapped = keys.wrap(&dummy_shared_keys(1), &dummy_address(1));
+
+        let unwrapped =
+            InboxKeys::unwrap(&wrapped, &dummy_shared_keys(1), &dummy_address(1)).unwrap();
+        assert_eq!(unwrapped.public_key(), keys.public_key());
+        assert_eq!(
+            unwrapped.decrypt(&dummy_address(1), &stored).unwrap(),
+            b"foomp"
+        );
+
+        assert!(InboxKeys::unwrap(&wrapped, &dummy_shared_keys(2), &dummy_address(1)).is_err());
+        assert!(InboxKeys::unwrap(&wrapped, &dummy_shared_keys(1), &dummy_address(2)).is_err());
+    }
+}
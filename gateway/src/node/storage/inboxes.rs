@@ -1,7 +1,7 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::node::storage::models::StoredMessage;
+use crate::node::storage::models::{PersistedInboxKey, StoredMessage};
 
 #[derive(Clone)]
 pub(crate) struct InboxManager {
@@ -30,14 +30,14 @@ impl InboxManager {
     /// # Arguments
     ///
     /// * `client_address_bs58`: base58-encoded address of the client
-    /// * `content`: raw content of the message to store.
+    /// * `content`: encrypted content of the message to store.
     pub(crate) async fn insert_message(
         &self,
         client_address_bs58: &str,
         content: Vec<u8>,
     ) -> Result<(), sqlx::Error> {
         sqlx::query!(
-            "INSERT INTO message_store(client_address_bs58, content) VALUES (?, ?)",
+            "INSERT INTO message_store(client_address_bs58, content, encrypted) VALUES (?, ?, TRUE)",
             client_address_bs58,
             content,
         )
@@ -46,6 +46,113 @@ impl InboxManager {
         Ok(())
     }
 
+    /// Retrieves up to `limit` messages that were stored in plaintext by the previous versions
+    /// of the gateway.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_after`: id of the last message retrieved in the previous query, if any.
+    /// * `limit`: maximum number of messages to retrieve.
+    pub(crate) async fn get_unencrypted_messages(
+        &self,
+        start_after: i64,
+        limit: i64,
+    ) -> Result<Vec<StoredMessage>, sqlx::Error> {
+        sqlx::query_as!(
+            StoredMessage,
+            r#"
+                SELECT id, client_address_bs58, content FROM message_store
+                WHERE encrypted = FALSE AND id > ?
+                ORDER BY id ASC
+                LIMIT ?;
+            "#,
+            start_after,
+            limit
+        )
+        .fetch_all(&self.connection_pool)
+        .await
+    }
+
+    /// Replaces content of the plaintext message with the specified id with its encrypted variant.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: id of the message to update
+    /// * `content`: encrypted content of the message.
+    pub(crate) async fn set_encrypted_content(
+        &self,
+        id: i64,
+        content: Vec<u8>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE message_store SET content = ?, encrypted = TRUE WHERE id = ?",
+            content,
+            id
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Inserts the provided inbox key of the particular client.
+    /// If a key previously existed for the client, it is overwritten with the new data.
+    ///
+    /// # Arguments
+    ///
+    /// * `inbox_key`: public and wrapped private inbox keys of the client.
+    pub(crate) async fn set_inbox_key(
+        &self,
+        inbox_key: PersistedInboxKey,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT OR REPLACE INTO inbox_keys(client_address_bs58, public_key_bs58, wrapped_private_key) VALUES (?, ?, ?)",
+            inbox_key.client_address_bs58,
+            inbox_key.public_key_bs58,
+            inbox_key.wrapped_private_key,
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Inserts the provided inbox key of the particular client, unless one already exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `inbox_key`: public and wrapped private inbox keys of the client.
+    pub(crate) async fn insert_new_inbox_key(
+        &self,
+        inbox_key: PersistedInboxKey,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT OR IGNORE INTO inbox_keys(client_address_bs58, public_key_bs58, wrapped_private_key) VALUES (?, ?, ?)",
+            inbox_key.client_address_bs58,
+            inbox_key.public_key_bs58,
+            inbox_key.wrapped_private_key,
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Tries to retrieve the inbox key of the particular client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_address_bs58`: base58-encoded address of the client
+    pub(crate) async fn get_inbox_key(
+        &self,
+        client_address_bs58: &str,
+    ) -> Result<Option<PersistedInboxKey>, sqlx::Error> {
+        sqlx::query_as!(
+            PersistedInboxKey,
+            "SELECT * FROM inbox_keys WHERE client_address_bs58 = ?",
+            client_address_bs58
+        )
+        .fetch_optional(&self.connection_pool)
+        .await
+    }
+
     /// Retrieves messages stored for the particular client specified by the provided address.
     ///
     /// It also respects the specified retrieval limit. If there are more messages stored than allowed
@@ -70,7 +177,7 @@ impl InboxManager {
             sqlx::query_as!(
                 StoredMessage,
                 r#"
-                    SELECT * FROM message_store 
+                    SELECT id, client_address_bs58, content FROM message_store
                     WHERE client_address_bs58 = ? AND id > ?
                     ORDER BY id ASC
                     LIMIT ?;
@@ -85,7 +192,7 @@ impl InboxManager {
             sqlx::query_as!(
                 StoredMessage,
                 r#"
-                    SELECT * FROM message_store 
+                    SELECT id, client_address_bs58, content FROM message_store
                     WHERE client_address_bs58 = ?
                     ORDER BY id ASC
                     LIMIT ?;
@@ -3,26 +3,37 @@
 
 use crate::node::storage::bandwidth::BandwidthManager;
 use crate::node::storage::error::StorageError;
+use crate::node::storage::inbox_encryption::InboxKeys;
 use crate::node::storage::inboxes::InboxManager;
-use crate::node::storage::models::{PersistedSharedKeys, StoredMessage};
+use crate::node::storage::models::{PersistedInboxKey, PersistedSharedKeys, StoredMessage};
 use crate::node::storage::shared_keys::SharedKeysManager;
+#[cfg(feature = "coconut")]
+use crate::node::storage::spent_credentials::SpentCredentialsManager;
 use async_trait::async_trait;
+#[cfg(feature = "coconut")]
+use coconut_interface::{Base58, BlindedSerialNumber};
+use crypto::asymmetric::encryption;
 use gateway_requests::registration::handshake::SharedKeys;
-use log::{debug, error};
+use log::{debug, error, info, warn};
 use nymsphinx::DestinationAddressBytes;
 use sqlx::ConnectOptions;
 use std::path::Path;
 
 mod bandwidth;
 pub(crate) mod error;
+mod inbox_encryption;
 mod inboxes;
 mod models;
 mod shared_keys;
+#[cfg(feature = "coconut")]
+mod spent_credentials;
 
 #[async_trait]
 pub(crate) trait Storage: Send + Sync {
     /// Inserts provided derived shared keys into the database.
     /// If keys previously existed for the provided client, they are overwritten with the new data.
+    /// The inbox key of the client gets re-wrapped under the new keys, so that any messages stored
+    /// for the client remain recoverable.
     ///
     /// # Arguments
     ///
@@ -58,6 +69,8 @@ pub(crate) trait Storage: Send + Sync {
     ) -> Result<(), StorageError>;
 
     /// Inserts new message to the storage for an offline client for future retrieval.
+    /// The message is padded and encrypted for the inbox key of the client, which requires
+    /// the client to have registered with the gateway.
     ///
     /// # Arguments
     ///
@@ -69,11 +82,13 @@ pub(crate) trait Storage: Send + Sync {
         message: Vec<u8>,
     ) -> Result<(), StorageError>;
 
-    /// Retrieves messages stored for the particular client specified by the provided address.
+    /// Retrieves and decrypts messages stored for the particular client specified by the provided address.
+    /// Any messages that could not be decrypted are skipped, but are kept in the storage.
     ///
     /// # Arguments
     ///
     /// * `client_address`: address of the client
+    /// * `shared_keys`: long-term shared keys of the client, used for recovering its inbox key.
     /// * `start_after`: optional starting id of the messages to grab
     ///
     /// returns the retrieved messages alongside optional id of the last message retrieved if
@@ -81,6 +96,7 @@ pub(crate) trait Storage: Send + Sync {
     async fn retrieve_messages(
         &self,
         client_address: DestinationAddressBytes,
+        shared_keys: &SharedKeys,
         start_after: Option<i64>,
     ) -> Result<(Vec<StoredMessage>, Option<i64>), StorageError>;
 
@@ -134,6 +150,34 @@ pub(crate) trait Storage: Send + Sync {
         client_address: DestinationAddressBytes,
         amount: i64,
     ) -> Result<(), StorageError>;
+
+    /// Attempts to mark the credential with the provided blinded serial number as spent.
+    ///
+    /// Returns `false` if the credential has already been marked as spent before, in which case
+    /// it must not be accepted again.
+    ///
+    /// # Arguments
+    ///
+    /// * `blinded_serial_number`: blinded serial number of the credential being spent.
+    /// * `client_address`: address of the client spending the credential.
+    #[cfg(feature = "coconut")]
+    async fn insert_spent_credential(
+        &self,
+        blinded_serial_number: &BlindedSerialNumber,
+        client_address: DestinationAddressBytes,
+    ) -> Result<bool, StorageError>;
+
+    /// Removes the credential with the provided blinded serial number from the set of spent credentials,
+    /// for example if it turned out we failed to redeem it.
+    ///
+    /// # Arguments
+    ///
+    /// * `blinded_serial_number`: blinded serial number of the credential.
+    #[cfg(feature = "coconut")]
+    async fn remove_spent_credential(
+        &self,
+        blinded_serial_number: &BlindedSerialNumber,
+    ) -> Result<(), StorageError>;
 }
 
 // note that clone here is fine as upon cloning the same underlying pool will be used
@@ -142,6 +186,8 @@ pub(crate) struct PersistentStorage {
     shared_key_manager: SharedKeysManager,
     inbox_manager: InboxManager,
     bandwidth_manager: BandwidthManager,
+    #[cfg(feature = "coconut")]
+    spent_credentials_manager: SpentCredentialsManager,
 }
 
 impl PersistentStorage {
@@ -184,11 +230,163 @@ impl PersistentStorage {
         }
 
         // the cloning here are cheap as connection pool is stored behind an Arc
-        Ok(PersistentStorage {
+        let storage = PersistentStorage {
             shared_key_manager: SharedKeysManager::new(connection_pool.clone()),
             inbox_manager: InboxManager::new(connection_pool.clone(), message_retrieval_limit),
+            #[cfg(feature = "coconut")]
+            spent_credentials_manager: SpentCredentialsManager::new(connection_pool.clone()),
             bandwidth_manager: BandwidthManager::new(connection_pool),
-        })
+        };
+        storage.encrypt_legacy_messages().await?;
+
+        Ok(storage)
+    }
+
+    async fn stored_shared_keys(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<Option<SharedKeys>, StorageError> {
+        let shared_keys = match self.get_shared_keys(client_address).await? {
+            Some(shared_keys) => shared_keys,
+            None => return Ok(None),
+        };
+
+        match SharedKeys::try_from_base58_string(
+            shared_keys.derived_aes128_ctr_blake3_hmac_keys_bs58,
+        ) {
+            Ok(shared_keys) => Ok(Some(shared_keys)),
+            Err(err) => {
+                error!(
+                    "The stored shared keys of {} are malformed - {}",
+                    client_address, err
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// Retrieves the public inbox key of the particular client. If the client does not have one
+    /// yet (for example because it has registered with a previous version of the gateway),
+    /// a fresh key is created and wrapped under its stored shared keys.
+    ///
+    /// Returns `None` if the client has never registered with this gateway.
+    async fn inbox_public_key(
+        &self,
+        client_address: DestinationAddressBytes,
+    ) -> Result<Option<encryption::PublicKey>, StorageError> {
+        let client_address_bs58 = client_address.as_base58_string();
+        let mut inbox_key = self
+            .inbox_manager
+            .get_inbox_key(&client_address_bs58)
+            .await?;
+
+        if inbox_key.is_none() {
+            let shared_keys = match self.stored_shared_keys(client_address).await? {
+                Some(shared_keys) => shared_keys,
+                None => return Ok(None),
+            };
+
+            // if somebody else has just created the key for this client, theirs is kept instead
+            let inbox_keys = InboxKeys::new();
+            self.inbox_manager
+                .insert_new_inbox_key(PersistedInboxKey {
+                    client_address_bs58: client_address_bs58.clone(),
+                    public_key_bs58: inbox_keys.public_key().to_base58_string(),
+                    wrapped_private_key: inbox_keys.wrap(&shared_keys, &client_address),
+                })
+                .await?;
+            inbox_key = self
+                .inbox_manager
+                .get_inbox_key(&client_address_bs58)
+                .await?;
+        }
+
+        // the key has been produced by us, so its recovery could only fail if somebody has
+        // tampered with the database, in which case there's no point in storing anything
+        match inbox_key {
+            Some(inbox_key) => {
+                encryption::PublicKey::from_base58_string(&inbox_key.public_key_bs58)
+                    .map(Some)
+                    .map_err(|_| StorageError::MalformedInboxKey(client_address_bs58))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Encrypts, in place, any messages that were stored in plaintext by the previous versions
+    /// of the gateway. Messages that cannot be encrypted are left untouched.
+    async fn encrypt_legacy_messages(&self) -> Result<(), StorageError> {
+        const BATCH_SIZE: i64 = 1000;
+
+        let mut encrypted = 0;
+        let mut skipped = 0;
+        let mut last_seen_id = 0;
+        loop {
+            let messages = self
+                .inbox_manager
+                .get_unencrypted_messages(last_seen_id, BATCH_SIZE)
+                .await?;
+            let last_message_id = match messages.last() {
+                Some(message) => message.id,
+                None => break,
+            };
+
+            for message in messages {
+                // the stored address has been produced by us, but don't fail the whole startup
+                // just because somebody has tampered with a single row
+                let client_address = match DestinationAddressBytes::try_from_base58_string(
+                    message.client_address_bs58.clone(),
+                ) {
+                    Ok(address) => address,
+                    Err(err) => {
+                        warn!(
+                            "Stored message {} has malformed client address - {}. It is going to be left in plaintext",
+                            message.id, err
+                        );
+                        skipped += 1;
+                        continue;
+                    }
+                };
+
+                let inbox_key = match self.inbox_public_key(client_address).await? {
+                    Some(inbox_key) => inbox_key,
+                    None => {
+                        warn!(
+                            "Stored message {} belongs to {}, which has never registered with this gateway. It is going to be left in plaintext",
+                            message.id, client_address
+                        );
+                        skipped += 1;
+                        continue;
+                    }
+                };
+
+                let content = inbox_encryption::encrypt_message(
+                    &inbox_key,
+                    &client_address,
+                    &message.content,
+                );
+                self.inbox_manager
+                    .set_encrypted_content(message.id, content)
+                    .await?;
+                encrypted += 1;
+            }
+
+            last_seen_id = last_message_id;
+        }
+
+        if encrypted > 0 {
+            info!(
+                "Encrypted {} messages stored by the previous version of the gateway",
+                encrypted
+            );
+        }
+        if skipped > 0 {
+            warn!(
+                "{} messages stored by the previous version of the gateway could not be encrypted",
+                skipped
+            );
+        }
+        Ok(())
     }
 }
 
@@ -199,6 +397,34 @@ impl Storage for PersistentStorage {
         client_address: DestinationAddressBytes,
         shared_keys: SharedKeys,
     ) -> Result<(), StorageError> {
+        let client_address_bs58 = client_address.as_base58_string();
+
+        // recover the existing inbox key with the previous keys of the client, so that it could
+        // be re-wrapped under the new ones
+        let previous_inbox_key = self
+            .inbox_manager
+            .get_inbox_key(&client_address_bs58)
+            .await?;
+        let inbox_keys = match (
+            &previous_inbox_key,
+            self.stored_shared_keys(client_address).await?,
+        ) {
+            (Some(inbox_key), Some(previous_keys)) => InboxKeys::unwrap(
+                &inbox_key.wrapped_private_key,
+                &previous_keys,
+                &client_address,
+            )
+            .map_err(|err| {
+                warn!(
+                    "Failed to recover the inbox key of {} - {}. Any messages stored for it are no longer going to be recoverable",
+                    client_address, err
+                )
+            })
+            .ok(),
+            _ => None,
+        }
+        .unwrap_or_else(InboxKeys::new);
+
         let persisted_shared_keys = PersistedSharedKeys {
             client_address_bs58: client_address.as_base58_string(),
             derived_aes128_ctr_blake3_hmac_keys_bs58: shared_keys.to_base58_string(),
@@ -206,6 +432,13 @@ impl Storage for PersistentStorage {
         self.shared_key_manager
             .insert_shared_keys(persisted_shared_keys)
             .await?;
+        self.inbox_manager
+            .set_inbox_key(PersistedInboxKey {
+                client_address_bs58,
+                public_key_bs58: inbox_keys.public_key().to_base58_string(),
+                wrapped_private_key: inbox_keys.wrap(&shared_keys, &client_address),
+            })
+            .await?;
         Ok(())
     }
 
@@ -236,8 +469,14 @@ impl Storage for PersistentStorage {
         client_address: DestinationAddressBytes,
         message: Vec<u8>,
     ) -> Result<(), StorageError> {
+        let inbox_key = self
+            .inbox_public_key(client_address)
+            .await?
+            .ok_or_else(|| StorageError::UnregisteredClient(client_address.as_base58_string()))?;
+
+        let encrypted = inbox_encryption::encrypt_message(&inbox_key, &client_address, &message);
         self.inbox_manager
-            .insert_message(&client_address.as_base58_string(), message)
+            .insert_message(&client_address.as_base58_string(), encrypted)
             .await?;
         Ok(())
     }
@@ -245,13 +484,68 @@ impl Storage for PersistentStorage {
     async fn retrieve_messages(
         &self,
         client_address: DestinationAddressBytes,
+        shared_keys: &SharedKeys,
         start_after: Option<i64>,
     ) -> Result<(Vec<StoredMessage>, Option<i64>), StorageError> {
-        let messages = self
+        let client_address_bs58 = client_address.as_base58_string();
+        let (messages, start_next_after) = self
             .inbox_manager
-            .get_messages(&client_address.as_base58_string(), start_after)
+            .get_messages(&client_address_bs58, start_after)
             .await?;
-        Ok(messages)
+        if messages.is_empty() {
+            return Ok((messages, start_next_after));
+        }
+
+        // don't destroy the data if we can't recover the key, it might still be recoverable, say,
+        // once the client authenticates with its previous keys
+        let inbox_keys = match self
+            .inbox_manager
+            .get_inbox_key(&client_address_bs58)
+            .await?
+        {
+            Some(inbox_key) => {
+                match InboxKeys::unwrap(
+                    &inbox_key.wrapped_private_key,
+                    shared_keys,
+                    &client_address,
+                ) {
+                    Ok(inbox_keys) => inbox_keys,
+                    Err(err) => {
+                        error!(
+                            "Failed to recover the inbox key of {} - {}. The stored messages are going to be kept in the storage",
+                            client_address, err
+                        );
+                        return Ok((Vec::new(), start_next_after));
+                    }
+                }
+            }
+            None => {
+                error!(
+                    "{} does not have an inbox key. The stored messages are going to be kept in the storage",
+                    client_address
+                );
+                return Ok((Vec::new(), start_next_after));
+            }
+        };
+
+        let mut decrypted = Vec::with_capacity(messages.len());
+        for mut message in messages {
+            match inbox_keys.decrypt(&client_address, &message.content) {
+                Ok(content) => {
+                    message.content = content;
+                    decrypted.push(message)
+                }
+                Err(err) => {
+                    // don't destroy the data, it might still be recoverable, say, with the right key
+                    error!(
+                        "Failed to recover stored message {} of {} - {}. It is going to be kept in the storage",
+                        message.id, client_address, err
+                    );
+                }
+            }
+        }
+
+        Ok((decrypted, start_next_after))
     }
 
     async fn remove_messages(&self, ids: Vec<i64>) -> Result<(), StorageError> {
@@ -304,6 +598,33 @@ impl Storage for PersistentStorage {
             .await?;
         Ok(())
     }
+
+    #[cfg(feature = "coconut")]
+    async fn insert_spent_credential(
+        &self,
+        blinded_serial_number: &BlindedSerialNumber,
+        client_address: DestinationAddressBytes,
+    ) -> Result<bool, StorageError> {
+        let inserted = self
+            .spent_credentials_manager
+            .insert_spent_credential(
+                &blinded_serial_number.to_bs58(),
+                &client_address.as_base58_string(),
+            )
+            .await?;
+        Ok(inserted)
+    }
+
+    #[cfg(feature = "coconut")]
+    async fn remove_spent_credential(
+        &self,
+        blinded_serial_number: &BlindedSerialNumber,
+    ) -> Result<(), StorageError> {
+        self.spent_credentials_manager
+            .remove_spent_credential(&blinded_serial_number.to_bs58())
+            .await?;
+        Ok(())
+    }
 }
 
 /// In-memory implementation of `Storage`. The intention is primarily in testing environments.
@@ -355,6 +676,7 @@ impl Storage for InMemStorage {
     async fn retrieve_messages(
         &self,
         _client_address: DestinationAddressBytes,
+        _shared_keys: &SharedKeys,
         _start_after: Option<i64>,
     ) -> Result<(Vec<StoredMessage>, Option<i64>), StorageError> {
         todo!()
@@ -393,4 +715,263 @@ impl Storage for InMemStorage {
     ) -> Result<(), StorageError> {
         todo!()
     }
+
+    #[cfg(feature = "coconut")]
+    async fn insert_spent_credential(
+        &self,
+        _blinded_serial_number: &BlindedSerialNumber,
+        _client_address: DestinationAddressBytes,
+    ) -> Result<bool, StorageError> {
+        todo!()
+    }
+
+    #[cfg(feature = "coconut")]
+    async fn remove_spent_credential(
+        &self,
+        _blinded_serial_number: &BlindedSerialNumber,
+    ) -> Result<(), StorageError> {
+        todo!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use rand::RngCore;
+    use std::path::PathBuf;
+
+    struct TempDatabase(PathBuf);
+
+    impl TempDatabase {
+        fn new() -> Self {
+            TempDatabase(
+                std::env::temp_dir().join(format!("gateway-storage-{}.sqlite", OsRng.next_u64())),
+            )
+        }
+    }
+
+    impl Drop for TempDatabase {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn client_address(byte: u8) -> DestinationAddressBytes {
+        DestinationAddressBytes::from_bytes([byte; 32])
+    }
+
+    fn shared_keys(byte: u8) -> SharedKeys {
+        SharedKeys::try_from_bytes(&[byte; 32]).unwrap()
+    }
+
+    async fn init_storage(database: &TempDatabase) -> PersistentStorage {
+        PersistentStorage::init(&database.0, 100).await.unwrap()
+    }
+
+    async fn raw_messages(storage: &PersistentStorage, address: DestinationAddressBytes) -> usize {
+        storage
+            .inbox_manager
+            .get_messages(&address.as_base58_string(), None)
+            .await
+            .unwrap()
+            .0
+            .len()
+    }
+
+    async fn insert_plaintext_message(database: &TempDatabase, address: &str, content: &[u8]) {
+        let pool = sqlx::SqlitePool::connect(&format!("sqlite://{}", database.0.display()))
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO message_store(client_address_bs58, content) VALUES (?, ?)")
+            .bind(address)
+            .bind(content.to_vec())
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+    }
+
+    #[tokio::test]
+    async fn stored_messages_are_encrypted_at_rest() {
+        let database = TempDatabase::new();
+        let storage = init_storage(&database).await;
+        let address = client_address(1);
+
+        storage
+            .insert_shared_keys(address, shared_keys(1))
+            .await
+            .unwrap();
+        storage
+            .store_message(address, b"foomp".to_vec())
+            .await
+            .unwrap();
+
+        let raw = storage
+            .inbox_manager
+            .get_messages(&address.as_base58_string(), None)
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(raw.len(), 1);
+        assert_ne!(raw[0].content, b"foomp");
+
+        let (retrieved, _) = storage
+            .retrieve_messages(address, &shared_keys(1), None)
+            .await
+            .unwrap();
+        assert_eq!(retrieved[0].content, b"foomp");
+    }
+
+    #[tokio::test]
+    async fn messages_for_unregistered_clients_are_not_stored() {
+        let database = TempDatabase::new();
+        let storage = init_storage(&database).await;
+        let address = client_address(1);
+
+        assert!(storage
+            .store_message(address, b"foomp".to_vec())
+            .await
+            .is_err());
+        assert_eq!(raw_messages(&storage, address).await, 0);
+    }
+
+    #[tokio::test]
+    async fn messages_cannot_be_recovered_without_the_shared_keys() {
+        let database = TempDatabase::new();
+        let storage = init_storage(&database).await;
+        let address = client_address(1);
+
+        storage
+            .insert_shared_keys(address, shared_keys(1))
+            .await
+            .unwrap();
+        storage
+            .store_message(address, b"foomp".to_vec())
+            .await
+            .unwrap();
+
+        let (retrieved, _) = storage
+            .retrieve_messages(address, &shared_keys(2), None)
+            .await
+            .unwrap();
+        assert!(retrieved.is_empty());
+
+        // but they are kept in the storage
+        assert_eq!(raw_messages(&storage, address).await, 1);
+        let (retrieved, _) = storage
+            .retrieve_messages(address, &shared_keys(1), None)
+            .await
+            .unwrap();
+        assert_eq!(retrieved[0].content, b"foomp");
+    }
+
+    #[tokio::test]
+    async fn messages_survive_reregistration() {
+        let database = TempDatabase::new();
+        let storage = init_storage(&database).await;
+        let address = client_address(1);
+
+        storage
+            .insert_shared_keys(address, shared_keys(1))
+            .await
+            .unwrap();
+        storage
+            .store_message(address, b"foomp".to_vec())
+            .await
+            .unwrap();
+
+        storage
+            .insert_shared_keys(address, shared_keys(2))
+            .await
+            .unwrap();
+        storage
+            .store_message(address, b"bar".to_vec())
+            .await
+            .unwrap();
+
+        let (retrieved, _) = storage
+            .retrieve_messages(address, &shared_keys(2), None)
+            .await
+            .unwrap();
+        assert_eq!(retrieved.len(), 2);
+        assert_eq!(retrieved[0].content, b"foomp");
+        assert_eq!(retrieved[1].content, b"bar");
+
+        let (retrieved, _) = storage
+            .retrieve_messages(address, &shared_keys(1), None)
+            .await
+            .unwrap();
+        assert!(retrieved.is_empty());
+    }
+
+    #[tokio::test]
+    async fn plaintext_messages_are_encrypted_in_place_on_startup() {
+        let database = TempDatabase::new();
+        let address = client_address(1);
+
+        // run the migrations and insert a message the way the previous versions did
+        let storage = init_storage(&database).await;
+        storage
+            .shared_key_manager
+            .insert_shared_keys(PersistedSharedKeys {
+                client_address_bs58: address.as_base58_string(),
+                derived_aes128_ctr_blake3_hmac_keys_bs58: shared_keys(1).to_base58_string(),
+            })
+            .await
+            .unwrap();
+        drop(storage);
+        insert_plaintext_message(&database, &address.as_base58_string(), b"legacy message").await;
+
+        let storage = init_storage(&database).await;
+        assert!(storage
+            .inbox_manager
+            .get_unencrypted_messages(0, 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let (retrieved, _) = storage
+            .retrieve_messages(address, &shared_keys(1), None)
+            .await
+            .unwrap();
+        assert_eq!(retrieved.len(), 1);
+        assert_eq!(retrieved[0].content, b"legacy message");
+    }
+
+    #[tokio::test]
+    async fn messages_that_cannot_be_encrypted_do_not_block_the_startup() {
+        let database = TempDatabase::new();
+        let address = client_address(1);
+
+        let storage = init_storage(&database).await;
+        storage
+            .shared_key_manager
+            .insert_shared_keys(PersistedSharedKeys {
+                client_address_bs58: address.as_base58_string(),
+                derived_aes128_ctr_blake3_hmac_keys_bs58: shared_keys(1).to_base58_string(),
+            })
+            .await
+            .unwrap();
+        drop(storage);
+        insert_plaintext_message(&database, "definitely not an address", b"malformed").await;
+        insert_plaintext_message(&database, &client_address(2).as_base58_string(), b"unknown")
+            .await;
+        insert_plaintext_message(&database, &address.as_base58_string(), b"legacy message").await;
+
+        let storage = init_storage(&database).await;
+        let unencrypted = storage
+            .inbox_manager
+            .get_unencrypted_messages(0, 10)
+            .await
+            .unwrap();
+        assert_eq!(unencrypted.len(), 2);
+
+        let (retrieved, _) = storage
+            .retrieve_messages(address, &shared_keys(1), None)
+            .await
+            .unwrap();
+        assert_eq!(retrieved.len(), 1);
+        assert_eq!(retrieved[0].content, b"legacy message");
+    }
 }
@@ -13,6 +13,13 @@ pub(crate) struct StoredMessage {
     pub(crate) content: Vec<u8>,
 }
 
+pub(crate) struct PersistedInboxKey {
+    #[allow(dead_code)]
+    pub(crate) client_address_bs58: String,
+    pub(crate) public_key_bs58: String,
+    pub(crate) wrapped_private_key: Vec<u8>,
+}
+
 pub(crate) struct PersistedBandwidth {
     #[allow(dead_code)]
     pub(crate) client_address_bs58: String,
@@ -0,0 +1,137 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+#[derive(Clone)]
+pub(crate) struct SpentCredentialsManager {
+    connection_pool: sqlx::SqlitePool,
+}
+
+impl SpentCredentialsManager {
+    /// Creates new instance of the `SpentCredentialsManager` with the provided sqlite connection pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection_pool`: database connection pool to use.
+    pub(crate) fn new(connection_pool: sqlx::SqlitePool) -> Self {
+        SpentCredentialsManager { connection_pool }
+    }
+
+    /// Attempts to insert the blinded serial number of a credential that is being spent.
+    ///
+    /// Returns `false` if the serial number already existed in the database, i.e. if the credential
+    /// has already been spent (or is being spent) at this gateway.
+    ///
+    /// # Arguments
+    ///
+    /// * `blinded_serial_number_bs58`: base58-encoded blinded serial number of the credential.
+    /// * `client_address_bs58`: base58-encoded address of the client spending the credential.
+    pub(crate) async fn insert_spent_credential(
+        &self,
+        blinded_serial_number_bs58: &str,
+        client_address_bs58: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+                INSERT OR IGNORE INTO spent_credential(blinded_serial_number_bs58, client_address_bs58)
+                VALUES (?, ?)
+            "#,
+            blinded_serial_number_bs58,
+            client_address_bs58
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(res.rows_affected() == 1)
+    }
+
+    /// Removes the blinded serial number of the credential from the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `blinded_serial_number_bs58`: base58-encoded blinded serial number of the credential.
+    pub(crate) async fn remove_spent_credential(
+        &self,
+        blinded_serial_number_bs58: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM spent_credential WHERE blinded_serial_number_bs58 = ?",
+            blinded_serial_number_bs58
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use rand::RngCore;
+    use sqlx::ConnectOptions;
+    use std::path::PathBuf;
+
+    struct TempDatabase(PathBuf);
+
+    impl Drop for TempDatabase {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    async fn init_manager() -> (SpentCredentialsManager, TempDatabase) {
+        let database = TempDatabase(
+            std::env::temp_dir().join(format!("gateway-spent-{}.sqlite", OsRng.next_u64())),
+        );
+        let mut opts = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(&database.0)
+            .create_if_missing(true);
+        opts.disable_statement_logging();
+
+        let connection_pool = sqlx::SqlitePool::connect_with(opts).await.unwrap();
+        sqlx::migrate!("./migrations")
+            .run(&connection_pool)
+            .await
+            .unwrap();
+
+        (SpentCredentialsManager::new(connection_pool), database)
+    }
+
+    #[tokio::test]
+    async fn duplicate_serial_number_is_not_inserted() {
+        let (manager, _database) = init_manager().await;
+
+        assert!(manager
+            .insert_spent_credential("serial1", "client1")
+            .await
+            .unwrap());
+        assert!(!manager
+            .insert_spent_credential("serial1", "client1")
+            .await
+            .unwrap());
+        // regardless of who is trying to spend it
+        assert!(!manager
+            .insert_spent_credential("serial1", "client2")
+            .await
+            .unwrap());
+
+        assert!(manager
+            .insert_spent_credential("serial2", "client1")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn removed_serial_number_can_be_inserted_again() {
+        let (manager, _database) = init_manager().await;
+
+        assert!(manager
+            .insert_spent_credential("serial1", "client1")
+            .await
+            .unwrap());
+        manager.remove_spent_credential("serial1").await.unwrap();
+        assert!(manager
+            .insert_spent_credential("serial1", "client1")
+            .await
+            .unwrap());
+    }
+}
@@ -1,10 +1,7 @@
 use crate::config::Config;
-use crate::node::node_description::NodeDescription;
 use clap::Args;
-use colored::Colorize;
 use config::NymConfig;
-use std::io;
-use std::io::Write;
+use mixnode_common::node_description::NodeDescription;
 
 #[derive(Args)]
 pub(crate) struct Describe {
@@ -24,39 +21,7 @@ pub(crate) fn execute(args: &Describe) {
     };
 
     // get input from the user
-    print!("name: ");
-    io::stdout().flush().unwrap();
-    let mut name_buf = String::new();
-    io::stdin().read_line(&mut name_buf).unwrap();
-    let name = name_buf.trim().to_string();
-
-    print!("description: ");
-    io::stdout().flush().unwrap();
-    let mut desc_buf = String::new();
-    io::stdin().read_line(&mut desc_buf).unwrap();
-    let description = desc_buf.trim().to_string();
-
-    let example_url = "https://mixnode.yourdomain.com".bright_cyan();
-    let example_location = "City: London, Country: UK";
-
-    print!("link, e.g. {}: ", example_url);
-    io::stdout().flush().unwrap();
-    let mut link_buf = String::new();
-    io::stdin().read_line(&mut link_buf).unwrap();
-    let link = link_buf.trim().to_string();
-
-    print!("location, e.g. {}: ", example_location);
-    io::stdout().flush().unwrap();
-    let mut location_buf = String::new();
-    io::stdin().read_line(&mut location_buf).unwrap();
-    let location = location_buf.trim().to_string();
-
-    let node_description = NodeDescription {
-        name,
-        description,
-        link,
-        location,
-    };
+    let node_description = NodeDescription::from_user_input("https://mixnode.yourdomain.com");
 
     // save the struct
     NodeDescription::save_to_file(
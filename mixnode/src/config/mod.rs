@@ -5,7 +5,9 @@ use crate::config::template::config_template;
 use config::defaults::{
     DEFAULT_HTTP_API_LISTENING_PORT, DEFAULT_MIX_LISTENING_PORT, DEFAULT_VERLOC_LISTENING_PORT,
 };
+use config::reload::{check_live_changes, ReloadError};
 use config::NymConfig;
+use mixnet_client::link::InboundLinkPolicy;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
@@ -26,6 +28,7 @@ const DEFAULT_DELAY_BETWEEN_PACKETS: Duration = Duration::from_millis(50);
 const DEFAULT_BATCH_SIZE: usize = 50;
 const DEFAULT_TESTING_INTERVAL: Duration = Duration::from_secs(60 * 60 * 12);
 const DEFAULT_RETRY_TIMEOUT: Duration = Duration::from_secs(60 * 30);
+const DEFAULT_VERLOC_HISTORY_LENGTH: usize = 28;
 
 // 'DEBUG'
 const DEFAULT_NODE_STATS_LOGGING_DELAY: Duration = Duration::from_millis(60_000);
@@ -34,6 +37,42 @@ const DEFAULT_PACKET_FORWARDING_INITIAL_BACKOFF: Duration = Duration::from_milli
 const DEFAULT_PACKET_FORWARDING_MAXIMUM_BACKOFF: Duration = Duration::from_millis(300_000);
 const DEFAULT_INITIAL_CONNECTION_TIMEOUT: Duration = Duration::from_millis(1_500);
 const DEFAULT_MAXIMUM_CONNECTION_BUFFER_SIZE: usize = 128;
+const DEFAULT_LOOP_COVER_STREAM_AVERAGE_DELAY: Duration = Duration::from_secs(10);
+const DEFAULT_LOOP_COVER_PACKET_AVERAGE_DELAY: Duration = Duration::from_millis(50);
+const DEFAULT_LOOP_COVER_PACKET_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAXIMUM_DELAY_QUEUE_PACKETS: usize = 200_000;
+const DEFAULT_MAXIMUM_DELAY_QUEUE_BYTES: usize = 512 * 1024 * 1024;
+const DEFAULT_SPHINX_PROCESSING_BATCH_SIZE: usize = 32;
+const DEFAULT_MAXIMUM_INBOUND_CONNECTIONS: usize = 4096;
+const DEFAULT_MAXIMUM_INBOUND_CONNECTIONS_PER_ADDRESS: usize = 32;
+const DEFAULT_INBOUND_CONNECTION_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_MAINTENANCE_DRAIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Fields that can be changed without having to restart the node.
+const LIVE_RELOADABLE_FIELDS: &[&str] = &[
+    "logging.filters",
+    "mixnode.announce_address",
+    "mixnode.validator_api_urls",
+    "debug.maximum_inbound_connections",
+    "debug.maximum_inbound_connections_per_address",
+];
+
+/// Specifies how the mixnode should behave once its delay queue reaches its capacity.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DelayQueueOverflowPolicy {
+    /// Drop any new packets until the queue drains.
+    #[default]
+    DropNewest,
+
+    /// Drop the queued packets that would have been forwarded last in order to make space
+    /// for the new ones.
+    DropLongestDelay,
+
+    /// Stop reading from the connections that have the largest share of the queued packets
+    /// and drop any new packets that still arrive while the queue is full.
+    Backpressure,
+}
 
 pub fn missing_string_value<T: From<String>>() -> T {
     MISSING_VALUE.to_string().into()
@@ -51,6 +90,10 @@ fn default_verloc_port() -> u16 {
     DEFAULT_VERLOC_LISTENING_PORT
 }
 
+fn default_verloc_history_length() -> usize {
+    DEFAULT_VERLOC_HISTORY_LENGTH
+}
+
 fn default_http_api_port() -> u16 {
     DEFAULT_HTTP_API_LISTENING_PORT
 }
@@ -284,6 +327,82 @@ impl Config {
         self.debug.use_legacy_framed_packet_version
     }
 
+    pub fn get_use_encrypted_links(&self) -> bool {
+        self.debug.use_encrypted_links
+    }
+
+    pub fn get_require_encrypted_links(&self) -> bool {
+        self.debug.require_encrypted_links
+    }
+
+    pub fn get_enable_sphinx_key_rotation(&self) -> bool {
+        self.debug.enable_sphinx_key_rotation
+    }
+
+    pub fn get_inbound_link_policy(&self) -> InboundLinkPolicy {
+        self.debug.inbound_link_policy
+    }
+
+    pub fn get_disable_loop_cover_traffic(&self) -> bool {
+        self.debug.disable_loop_cover_traffic
+    }
+
+    pub fn get_loop_cover_traffic_average_delay(&self) -> Duration {
+        self.debug.loop_cover_traffic_average_delay
+    }
+
+    pub fn get_loop_cover_packet_average_delay(&self) -> Duration {
+        self.debug.loop_cover_packet_average_delay
+    }
+
+    pub fn get_loop_cover_packet_timeout(&self) -> Duration {
+        self.debug.loop_cover_packet_timeout
+    }
+
+    pub fn get_maximum_delay_queue_packets(&self) -> usize {
+        self.debug.maximum_delay_queue_packets
+    }
+
+    pub fn get_maximum_delay_queue_bytes(&self) -> usize {
+        self.debug.maximum_delay_queue_bytes
+    }
+
+    pub fn get_delay_queue_overflow_policy(&self) -> DelayQueueOverflowPolicy {
+        self.debug.delay_queue_overflow_policy
+    }
+
+    pub fn get_sphinx_processing_threads(&self) -> usize {
+        self.debug.sphinx_processing_threads
+    }
+
+    pub fn get_sphinx_processing_batch_size(&self) -> usize {
+        self.debug.sphinx_processing_batch_size
+    }
+
+    pub fn get_maximum_inbound_connections(&self) -> usize {
+        self.debug.maximum_inbound_connections
+    }
+
+    pub fn get_maximum_inbound_connections_per_address(&self) -> usize {
+        self.debug.maximum_inbound_connections_per_address
+    }
+
+    pub fn get_inbound_connection_idle_timeout(&self) -> Duration {
+        self.debug.inbound_connection_idle_timeout
+    }
+
+    pub fn get_only_accept_known_peers(&self) -> bool {
+        self.debug.only_accept_known_peers
+    }
+
+    pub fn get_enforce_layer_routing(&self) -> bool {
+        self.debug.enforce_layer_routing
+    }
+
+    pub fn get_maintenance_drain_timeout(&self) -> Duration {
+        self.debug.maintenance_drain_timeout
+    }
+
     pub fn get_version(&self) -> &str {
         &self.mixnode.version
     }
@@ -316,9 +435,44 @@ impl Config {
         self.verloc.retry_timeout
     }
 
+    pub fn get_measurement_history_length(&self) -> usize {
+        self.verloc.history_length
+    }
+
+    pub fn get_verloc_history_file(&self) -> PathBuf {
+        self.data_directory().join("verloc_history.json")
+    }
+
+    pub fn get_rotated_sphinx_keys_file(&self) -> PathBuf {
+        self.data_directory().join("rotated_sphinx_keys.json")
+    }
+
     pub fn get_wallet_address(&self) -> &str {
         &self.mixnode.wallet_address
     }
+
+    pub fn get_logging_filters(&self) -> Option<&str> {
+        Some(&self.logging.filters).filter(|filters| !filters.is_empty())
+    }
+
+    /// Checks whether the `updated` config is valid and can be applied while the node is running.
+    /// If so, returns the fields that have changed.
+    pub fn check_live_reload(&self, updated: &Config) -> Result<Vec<String>, ReloadError> {
+        if updated.mixnode.validator_api_urls.is_empty() {
+            return Err(ReloadError::Invalid(
+                "at least one validator api url must be provided".to_string(),
+            ));
+        }
+        if updated.debug.maximum_inbound_connections == 0
+            || updated.debug.maximum_inbound_connections_per_address == 0
+        {
+            return Err(ReloadError::Invalid(
+                "the inbound connection limits must be non-zero".to_string(),
+            ));
+        }
+
+        check_live_changes(self, updated, LIVE_RELOADABLE_FIELDS)
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
@@ -420,7 +574,12 @@ impl Default for MixNode {
 
 #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
-struct Logging {}
+struct Logging {
+    /// Logging filters in the same format as the `RUST_LOG` environment variable, e.g. `info,nym_mixnode=debug`.
+    /// If empty, the value of `RUST_LOG` is used instead.
+    #[serde(default)]
+    filters: String,
+}
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -446,6 +605,11 @@ struct Verloc {
     /// Specifies delay between attempting to run the measurement again if the previous run failed
     /// due to being unable to get the list of nodes.
     retry_timeout: Duration,
+
+    /// Specifies number of the most recent measurement runs whose signed results are kept
+    /// in the persisted history.
+    #[serde(default = "default_verloc_history_length")]
+    history_length: usize,
 }
 
 impl Default for Verloc {
@@ -458,6 +622,7 @@ impl Default for Verloc {
             tested_nodes_batch_size: DEFAULT_BATCH_SIZE,
             testing_interval: DEFAULT_TESTING_INTERVAL,
             retry_timeout: DEFAULT_RETRY_TIMEOUT,
+            history_length: DEFAULT_VERLOC_HISTORY_LENGTH,
         }
     }
 }
@@ -495,6 +660,83 @@ struct Debug {
     // existing nodes whilst everyone else is upgrading and getting the code for handling the new field.
     // It shall be disabled in the subsequent releases.
     use_legacy_framed_packet_version: bool,
+
+    /// Specifies whether encrypted links should be established when forwarding packets to nodes
+    /// whose identities are known.
+    use_encrypted_links: bool,
+
+    /// Specifies whether packets should only ever be forwarded over encrypted links, i.e. whether
+    /// no connection should be made to nodes that are known not to support them
+    /// (or whose identities are unknown) instead of falling back to plaintext.
+    /// Note that a failed handshake never results in a plaintext connection.
+    // it's opt-in, as with it enabled, the nodes that haven't upgraded yet become unreachable.
+    require_encrypted_links: bool,
+
+    /// Specifies which inbound mix connections should be accepted.
+    inbound_link_policy: InboundLinkPolicy,
+
+    /// Specifies whether the node should generate, and announce, fresh sphinx keys for each
+    /// sphinx key epoch on top of its static key.
+    // note that the rotation limits the lifetime of the reply SURBs going through this node,
+    // so it's disabled by default until the clients are aware of it.
+    enable_sphinx_key_rotation: bool,
+
+    /// Specifies whether the mixnode should stop sending its own loop cover packets through
+    /// the network.
+    disable_loop_cover_traffic: bool,
+
+    /// The parameter of Poisson distribution determining how long, on average, the mixnode
+    /// is going to wait between sending subsequent loop cover packets.
+    #[serde(with = "humantime_serde")]
+    loop_cover_traffic_average_delay: Duration,
+
+    /// The average delay, at each hop, of the loop cover packets sent by the mixnode.
+    #[serde(with = "humantime_serde")]
+    loop_cover_packet_average_delay: Duration,
+
+    /// Maximum amount of time to wait for the loop cover packet to return before considering it lost.
+    #[serde(with = "humantime_serde")]
+    loop_cover_packet_timeout: Duration,
+
+    /// Maximum number of packets that can be waiting in the delay queue at any given time.
+    maximum_delay_queue_packets: usize,
+
+    /// Maximum total size, in bytes, of packets that can be waiting in the delay queue at any given time.
+    maximum_delay_queue_bytes: usize,
+
+    /// Specifies what should happen to packets that would exceed the delay queue capacity.
+    delay_queue_overflow_policy: DelayQueueOverflowPolicy,
+
+    /// Number of threads in the dedicated pool used for unwrapping sphinx packets.
+    /// If set to 0, the packets are unwrapped directly on the tasks handling the connections.
+    sphinx_processing_threads: usize,
+
+    /// Maximum number of already received packets from a single connection that are submitted
+    /// to the sphinx processing pool at once.
+    sphinx_processing_batch_size: usize,
+
+    /// Maximum number of inbound connections that can be handled at the same time.
+    maximum_inbound_connections: usize,
+
+    /// Maximum number of inbound connections from a single ip address that can be handled at the same time.
+    maximum_inbound_connections_per_address: usize,
+
+    /// Duration after which an inbound connection that hasn't sent us any packets gets closed.
+    #[serde(with = "humantime_serde")]
+    inbound_connection_idle_timeout: Duration,
+
+    /// If enabled, only connections from gateways and mixnodes on the previous layer
+    /// of the current network topology are going to be accepted.
+    only_accept_known_peers: bool,
+
+    /// If enabled, packets are only forwarded to mixnodes on the next layer (or gateways,
+    /// if we're on the last layer) of the current network topology and dropped otherwise.
+    enforce_layer_routing: bool,
+
+    /// Maximum duration the node is going to wait for its delay queue to get drained
+    /// after entering the maintenance mode before shutting down regardless.
+    #[serde(with = "humantime_serde")]
+    maintenance_drain_timeout: Duration,
 }
 
 impl Default for Debug {
@@ -508,6 +750,26 @@ impl Default for Debug {
             maximum_connection_buffer_size: DEFAULT_MAXIMUM_CONNECTION_BUFFER_SIZE,
             // TODO: remember to change it in one of future releases!!
             use_legacy_framed_packet_version: true,
+            use_encrypted_links: true,
+            require_encrypted_links: false,
+            inbound_link_policy: InboundLinkPolicy::default(),
+            enable_sphinx_key_rotation: false,
+            disable_loop_cover_traffic: false,
+            loop_cover_traffic_average_delay: DEFAULT_LOOP_COVER_STREAM_AVERAGE_DELAY,
+            loop_cover_packet_average_delay: DEFAULT_LOOP_COVER_PACKET_AVERAGE_DELAY,
+            loop_cover_packet_timeout: DEFAULT_LOOP_COVER_PACKET_TIMEOUT,
+            maximum_delay_queue_packets: DEFAULT_MAXIMUM_DELAY_QUEUE_PACKETS,
+            maximum_delay_queue_bytes: DEFAULT_MAXIMUM_DELAY_QUEUE_BYTES,
+            delay_queue_overflow_policy: DelayQueueOverflowPolicy::default(),
+            sphinx_processing_threads: 0,
+            sphinx_processing_batch_size: DEFAULT_SPHINX_PROCESSING_BATCH_SIZE,
+            maximum_inbound_connections: DEFAULT_MAXIMUM_INBOUND_CONNECTIONS,
+            maximum_inbound_connections_per_address:
+                DEFAULT_MAXIMUM_INBOUND_CONNECTIONS_PER_ADDRESS,
+            inbound_connection_idle_timeout: DEFAULT_INBOUND_CONNECTION_IDLE_TIMEOUT,
+            only_accept_known_peers: false,
+            enforce_layer_routing: true,
+            maintenance_drain_timeout: DEFAULT_MAINTENANCE_DRAIN_TIMEOUT,
         }
     }
 }
@@ -73,7 +73,10 @@ nym_root_directory = '{{ mixnode.nym_root_directory }}'
 
 [logging]
 
-# TODO
+# Logging filters in the same format as the `RUST_LOG` environment variable, e.g. 'info,nym_mixnode=debug'.
+# If empty, the value of `RUST_LOG` is used instead.
+# It can be changed without restarting the node.
+filters = '{{ logging.filters }}'
 
 "#
 }
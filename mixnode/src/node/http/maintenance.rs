@@ -0,0 +1,49 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::maintenance::{MaintenanceMode, SignedNodeStatus};
+use config::reload::is_local;
+use crypto::asymmetric::identity;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+pub(crate) struct MaintenanceState {
+    maintenance: MaintenanceMode,
+    identity_keypair: Arc<identity::KeyPair>,
+}
+
+impl MaintenanceState {
+    pub fn new(maintenance: MaintenanceMode, identity_keypair: Arc<identity::KeyPair>) -> Self {
+        MaintenanceState {
+            maintenance,
+            identity_keypair,
+        }
+    }
+}
+
+/// Provides the current availability of this mixnode signed with its identity key.
+#[get("/maintenance")]
+pub(crate) fn maintenance_status(state: &State<MaintenanceState>) -> Json<SignedNodeStatus> {
+    Json(state.maintenance.signed_status(&state.identity_keypair))
+}
+
+/// Puts this mixnode into maintenance mode, in which it stops accepting new traffic
+/// and shuts down once all the already received packets got forwarded.
+/// Only requests made from the machine the node is running on are accepted.
+#[post("/maintenance/drain")]
+pub(crate) fn drain(
+    remote: SocketAddr,
+    state: &State<MaintenanceState>,
+) -> Result<Json<SignedNodeStatus>, Status> {
+    if !is_local(remote.ip()) {
+        return Err(Status::Forbidden);
+    }
+
+    state.maintenance.start_draining();
+    Ok(Json(
+        state.maintenance.signed_status(&state.identity_keypair),
+    ))
+}
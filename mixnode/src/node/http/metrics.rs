@@ -0,0 +1,22 @@
+use crate::node::http::verloc::VerlocState;
+use crate::node::metrics::{encode_verloc, MetricsEncoder, NodeMetrics};
+use crate::node::node_statistics::SharedNodeStats;
+use rocket::http::ContentType;
+use rocket::State;
+
+/// Returns the node metrics in the Prometheus text exposition format.
+/// Note that the packet counters are only refreshed every node stats update interval.
+#[get("/metrics")]
+pub(crate) async fn metrics(
+    node_metrics: &State<NodeMetrics>,
+    stats: &State<SharedNodeStats>,
+    verloc: &State<VerlocState>,
+) -> (ContentType, String) {
+    let mut encoder = MetricsEncoder::new();
+
+    stats.clone_data().await.encode_metrics(&mut encoder);
+    node_metrics.encode(&mut encoder);
+    encode_verloc(&mut encoder, &verloc.clone_data().await);
+
+    (ContentType::Plain, encoder.finish())
+}
@@ -1,17 +1,25 @@
-use mixnode_common::verloc::{AtomicVerlocResult, VerlocResult};
+use mixnode_common::verloc::{
+    AtomicVerlocResult, SignedVerlocReport, VerlocHistory, VerlocResult, VerlocTrends,
+};
 use rocket::serde::json::Json;
 use rocket::State;
 
 pub(crate) struct VerlocState {
     shared: AtomicVerlocResult,
+    history: VerlocHistory,
 }
 
 impl VerlocState {
-    pub fn new(atomic_verloc_result: AtomicVerlocResult) -> Self {
+    pub fn new(atomic_verloc_result: AtomicVerlocResult, verloc_history: VerlocHistory) -> Self {
         VerlocState {
             shared: atomic_verloc_result,
+            history: verloc_history,
         }
     }
+
+    pub(crate) async fn clone_data(&self) -> VerlocResult {
+        self.shared.clone_data().await
+    }
 }
 
 /// Provides verifiable location (verloc) measurements for this mixnode - a list of the
@@ -19,5 +27,19 @@ impl VerlocState {
 #[get("/verloc")]
 pub(crate) async fn verloc(state: &State<VerlocState>) -> Json<VerlocResult> {
     // since it's impossible to get a mutable reference to the state, we can't cache any results outside the lock : (
-    Json(state.shared.clone_data().await)
+    Json(state.clone_data().await)
+}
+
+/// Provides percentiles of the round-trip times measured during the recent verloc runs,
+/// both across the whole network for each run and for each node across all the runs.
+#[get("/verloc/history")]
+pub(crate) async fn verloc_history(state: &State<VerlocState>) -> Json<VerlocTrends> {
+    Json(state.history.trends().await)
+}
+
+/// Provides results of the recent verloc runs signed with the identity key of this mixnode,
+/// so that they could be verified and aggregated by third parties.
+#[get("/verloc/reports")]
+pub(crate) async fn verloc_reports(state: &State<VerlocState>) -> Json<Vec<SignedVerlocReport>> {
+    Json(state.history.signed_reports().await)
 }
@@ -0,0 +1,195 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::network_view::NetworkView;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RejectionReason {
+    /// We were already handling the maximum number of connections.
+    TooManyConnections,
+
+    /// We were already handling the maximum number of connections from this particular address.
+    TooManyConnectionsFromAddress,
+
+    /// The connection did not come from a node that is expected to be sending us packets.
+    UnknownPeer,
+}
+
+impl RejectionReason {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            RejectionReason::TooManyConnections => "too_many_connections",
+            RejectionReason::TooManyConnectionsFromAddress => "too_many_connections_from_address",
+            RejectionReason::UnknownPeer => "unknown_peer",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ConnectionLimits {
+    /// Maximum number of inbound connections that can be handled at the same time.
+    pub(crate) maximum_connections: usize,
+
+    /// Maximum number of inbound connections from a single ip address that can be handled at the same time.
+    pub(crate) maximum_connections_per_address: usize,
+}
+
+#[derive(Debug)]
+struct ActiveConnections {
+    limits: ConnectionLimits,
+    total: usize,
+    per_address: HashMap<IpAddr, usize>,
+}
+
+/// Decides whether new inbound connections should be accepted.
+///
+/// Note that cloning it produces a handle to the same underlying data.
+#[derive(Clone)]
+pub(crate) struct AdmissionControl {
+    /// If specified, only connections coming from nodes that are expected to be sending us
+    /// packets are accepted.
+    allowed_peers: Option<NetworkView>,
+
+    active: Arc<Mutex<ActiveConnections>>,
+}
+
+impl AdmissionControl {
+    pub(crate) fn new(limits: ConnectionLimits, allowed_peers: Option<NetworkView>) -> Self {
+        AdmissionControl {
+            allowed_peers,
+            active: Arc::new(Mutex::new(ActiveConnections {
+                limits,
+                total: 0,
+                per_address: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Changes the connection limits. Any already admitted connections above the new limits
+    /// are not closed, but no new ones are going to be admitted until enough of them are.
+    pub(crate) fn update_limits(&self, limits: ConnectionLimits) {
+        self.active
+            .lock()
+            .expect("active connections mutex got poisoned")
+            .limits = limits;
+    }
+
+    /// Attempts to admit a new connection from the provided address. The returned permit must
+    /// be kept alive for as long as the connection is being handled.
+    pub(crate) fn try_admit(&self, address: IpAddr) -> Result<ConnectionPermit, RejectionReason> {
+        if let Some(allowed_peers) = &self.allowed_peers {
+            if !allowed_peers.is_allowed_inbound(address) {
+                return Err(RejectionReason::UnknownPeer);
+            }
+        }
+
+        let mut active = self
+            .active
+            .lock()
+            .expect("active connections mutex got poisoned");
+        if active.total >= active.limits.maximum_connections {
+            return Err(RejectionReason::TooManyConnections);
+        }
+
+        let from_address = active
+            .per_address
+            .get(&address)
+            .copied()
+            .unwrap_or_default();
+        if from_address >= active.limits.maximum_connections_per_address {
+            return Err(RejectionReason::TooManyConnectionsFromAddress);
+        }
+        active.per_address.insert(address, from_address + 1);
+        active.total += 1;
+
+        Ok(ConnectionPermit {
+            address,
+            active: Arc::clone(&self.active),
+        })
+    }
+}
+
+/// Slot of an admitted connection, released on drop.
+pub(crate) struct ConnectionPermit {
+    address: IpAddr,
+    active: Arc<Mutex<ActiveConnections>>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let mut active = self
+            .active
+            .lock()
+            .expect("active connections mutex got poisoned");
+        active.total -= 1;
+        if let Some(from_address) = active.per_address.get_mut(&self.address) {
+            *from_address -= 1;
+            if *from_address == 0 {
+                active.per_address.remove(&self.address);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn admission_control(maximum_connections: usize, per_address: usize) -> AdmissionControl {
+        AdmissionControl::new(
+            ConnectionLimits {
+                maximum_connections,
+                maximum_connections_per_address: per_address,
+            },
+            None,
+        )
+    }
+
+    #[test]
+    fn connections_from_single_address_are_limited() {
+        let admission = admission_control(10, 2);
+        let address: IpAddr = "1.2.3.4".parse().unwrap();
+        let other_address: IpAddr = "5.6.7.8".parse().unwrap();
+
+        let first = admission.try_admit(address).unwrap();
+        let _second = admission.try_admit(address).unwrap();
+        assert_eq!(
+            admission.try_admit(address).err(),
+            Some(RejectionReason::TooManyConnectionsFromAddress)
+        );
+        assert!(admission.try_admit(other_address).is_ok());
+
+        // once a connection is closed, its slot is released
+        drop(first);
+        assert!(admission.try_admit(address).is_ok());
+    }
+
+    #[test]
+    fn total_connections_are_limited() {
+        let admission = admission_control(2, 2);
+
+        let _first = admission.try_admit("1.1.1.1".parse().unwrap()).unwrap();
+        let second = admission.try_admit("2.2.2.2".parse().unwrap()).unwrap();
+        assert_eq!(
+            admission.try_admit("3.3.3.3".parse().unwrap()).err(),
+            Some(RejectionReason::TooManyConnections)
+        );
+
+        drop(second);
+        let third = admission.try_admit("3.3.3.3".parse().unwrap()).unwrap();
+
+        // lowering the limits does not affect the existing connections
+        admission.update_limits(ConnectionLimits {
+            maximum_connections: 1,
+            maximum_connections_per_address: 2,
+        });
+        drop(third);
+        assert_eq!(
+            admission.try_admit("3.3.3.3".parse().unwrap()).err(),
+            Some(RejectionReason::TooManyConnections)
+        );
+    }
+}
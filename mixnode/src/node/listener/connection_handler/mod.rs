@@ -1,18 +1,31 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::node::listener::admission::ConnectionPermit;
 use crate::node::listener::connection_handler::packet_processing::{
     MixProcessingResult, PacketProcessor,
 };
-use crate::node::packet_delayforwarder::PacketDelayForwardSender;
+use crate::node::loop_cover::PendingLoops;
+use crate::node::maintenance::MaintenanceMode;
+use crate::node::metrics::NodeMetrics;
+use crate::node::network_view::NetworkView;
+use crate::node::node_statistics::{DropReason, UpdateSender};
+use crate::node::packet_delayforwarder::{PacketDelayForwardSender, PacketSenderHandle};
 use crate::node::ShutdownListener;
-use futures::StreamExt;
+use crypto::asymmetric::identity;
+use futures::{FutureExt, StreamExt};
 use log::{error, info};
+use mixnet_client::hello::{self, DEFAULT_HELLO_TIMEOUT};
+use mixnet_client::link::peers::LinkPeers;
+use mixnet_client::link::{self, InboundLinkPolicy, DEFAULT_LINK_HANDSHAKE_TIMEOUT};
+use mixnode_common::packet_processor::error::MixProcessingError;
 use nymsphinx::forwarding::packet::MixPacket;
-use nymsphinx::framing::codec::SphinxCodec;
+use nymsphinx::framing::hello::{Features, Hello};
 use nymsphinx::framing::packet::FramedSphinxPacket;
 use nymsphinx::Delay as SphinxDelay;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio::time::Instant;
 use tokio_util::codec::Framed;
@@ -23,66 +36,208 @@ pub(crate) mod packet_processing;
 pub(crate) struct ConnectionHandler {
     packet_processor: PacketProcessor,
     delay_forwarding_channel: PacketDelayForwardSender,
+    identity: Arc<identity::KeyPair>,
+    link_peers: LinkPeers,
+    inbound_link_policy: InboundLinkPolicy,
+    pending_loops: PendingLoops,
+    node_stats_update_sender: UpdateSender,
+    metrics: NodeMetrics,
+    idle_timeout: Duration,
+
+    /// If specified, packets are only forwarded to nodes on the next layer of the network.
+    routing_view: Option<NetworkView>,
 }
 
 impl ConnectionHandler {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         packet_processor: PacketProcessor,
         delay_forwarding_channel: PacketDelayForwardSender,
+        identity: Arc<identity::KeyPair>,
+        link_peers: LinkPeers,
+        inbound_link_policy: InboundLinkPolicy,
+        pending_loops: PendingLoops,
+        node_stats_update_sender: UpdateSender,
+        idle_timeout: Duration,
     ) -> Self {
         ConnectionHandler {
+            metrics: packet_processor.metrics().clone(),
             packet_processor,
             delay_forwarding_channel,
+            identity,
+            link_peers,
+            inbound_link_policy,
+            pending_loops,
+            node_stats_update_sender,
+            idle_timeout,
+            routing_view: None,
+        }
+    }
+
+    #[must_use]
+    pub(crate) fn with_layer_routing_enforcement(mut self, network_view: NetworkView) -> Self {
+        self.routing_view = Some(network_view);
+        self
+    }
+
+    fn is_valid_next_hop(&self, mix_packet: &MixPacket) -> bool {
+        match &self.routing_view {
+            Some(network_view) => network_view.is_valid_next_hop(mix_packet.next_hop().into()),
+            None => true,
         }
     }
 
-    fn delay_and_forward_packet(&self, mix_packet: MixPacket, delay: Option<SphinxDelay>) {
+    fn delay_and_forward_packet(
+        &self,
+        packet_sender: &PacketSenderHandle,
+        mix_packet: MixPacket,
+        delay: Option<SphinxDelay>,
+    ) {
         // determine instant at which packet should get forwarded. this way we minimise effect of
         // being stuck in the queue [of the channel] to get inserted into the delay queue
         let forward_instant = delay.map(|delay| Instant::now() + delay.to_duration());
 
-        // if unbounded_send() failed it means that the receiver channel was disconnected
-        // and hence something weird must have happened without a way of recovering
-        self.delay_forwarding_channel
-            .unbounded_send((mix_packet, forward_instant))
-            .expect("the delay-forwarder has died!");
+        packet_sender.forward_packet(mix_packet, forward_instant);
     }
 
-    fn handle_received_packet(&self, framed_sphinx_packet: FramedSphinxPacket) {
-        //
-        // TODO: here be replay attack detection - it will require similar key cache to the one in
-        // packet processor for vpn packets,
-        // question: can it also be per connection vs global?
-        //
-
+    fn handle_processing_result(
+        &self,
+        packet_sender: &PacketSenderHandle,
+        processing_result: Result<MixProcessingResult, MixProcessingError>,
+    ) {
         // all processing such, key caching, etc. was done.
         // however, if it was a forward hop, we still need to delay it
-        match self.packet_processor.process_received(framed_sphinx_packet) {
+        match processing_result {
             Err(e) => debug!("We failed to process received sphinx packet - {:?}", e),
             Ok(res) => match res {
                 MixProcessingResult::ForwardHop(forward_packet, delay) => {
-                    self.delay_and_forward_packet(forward_packet, delay)
+                    // don't let anyone use us as a relay to arbitrary hosts
+                    if self.is_valid_next_hop(&forward_packet) {
+                        self.delay_and_forward_packet(packet_sender, forward_packet, delay)
+                    } else {
+                        debug!(
+                            "Received a packet with an invalid next hop ({})",
+                            forward_packet.next_hop()
+                        );
+                        self.node_stats_update_sender
+                            .report_dropped_with_reason(DropReason::InvalidNextHop);
+                        self.metrics.report_dropped(DropReason::InvalidNextHop);
+                    }
                 }
-                MixProcessingResult::FinalHop(..) => {
-                    warn!("Somehow processed a loop cover message that we haven't implemented yet!")
+                MixProcessingResult::FinalHop(final_hop) => {
+                    // the only packets for which we are the final hop are our own loop cover packets
+                    match self.pending_loops.complete(&final_hop.message) {
+                        Some(rtt) => self
+                            .node_stats_update_sender
+                            .report_loop_cover_returned(rtt),
+                        None => {
+                            debug!("Received an unexpected (or expired) final hop packet");
+                            self.node_stats_update_sender
+                                .report_dropped_with_reason(DropReason::UnexpectedFinalHop);
+                            self.metrics.report_dropped(DropReason::UnexpectedFinalHop);
+                        }
+                    }
                 }
             },
         }
     }
 
+    fn handle_received_packet(
+        &self,
+        packet_sender: &PacketSenderHandle,
+        framed_sphinx_packet: FramedSphinxPacket,
+    ) {
+        // note: replay detection is performed by the packet processor, whose cache is shared
+        // between all connections
+        let processing_result = self.packet_processor.process_received(framed_sphinx_packet);
+        self.handle_processing_result(packet_sender, processing_result)
+    }
+
+    async fn handle_received_batch(
+        &self,
+        packet_sender: &PacketSenderHandle,
+        framed_sphinx_packets: Vec<FramedSphinxPacket>,
+    ) {
+        // the results are returned in the same order as the packets were received in
+        // and the next batch is only submitted once we're done with this one
+        for processing_result in self
+            .packet_processor
+            .process_received_batch(framed_sphinx_packets)
+            .await
+        {
+            self.handle_processing_result(packet_sender, processing_result)
+        }
+    }
+
     pub(crate) async fn handle_connection(
         self,
-        conn: TcpStream,
+        mut conn: TcpStream,
         remote: SocketAddr,
+        // released once the connection is closed
+        _permit: ConnectionPermit,
+        mut maintenance: MaintenanceMode,
         mut shutdown: ShutdownListener,
     ) {
         debug!("Starting connection handler for {:?}", remote);
-        let mut framed_conn = Framed::new(conn, SphinxCodec);
+        // we're always able to respond to the link handshake, even if the policy
+        // ends up rejecting it
+        let local_hello = Hello::new(Features::LINK_ENCRYPTION | Features::OUTFOX);
+        match hello::accept_hello(&mut conn, &local_hello, DEFAULT_HELLO_TIMEOUT).await {
+            Ok(Some(remote_hello)) => {
+                log::trace!("{:?} has sent us its hello - {:?}", remote, remote_hello)
+            }
+            Ok(None) => (),
+            Err(err) => {
+                debug!("Failed to exchange hello with {:?} - {}", remote, err);
+                return;
+            }
+        }
+
+        let codec = match link::accept_link(
+            &mut conn,
+            &self.identity,
+            &self.link_peers,
+            self.inbound_link_policy,
+            DEFAULT_LINK_HANDSHAKE_TIMEOUT,
+        )
+        .await
+        {
+            Ok((codec, Some(remote_identity))) => {
+                debug!(
+                    "Established encrypted link with {} ({})",
+                    remote_identity, remote
+                );
+                codec
+            }
+            Ok((codec, None)) => codec,
+            Err(err) => {
+                debug!("Rejected connection from {:?} - {}", remote, err);
+                return;
+            }
+        };
+
+        let _connection_guard = self.metrics.inbound_connection(remote.ip());
+        let packet_sender = self.delay_forwarding_channel.register_sender();
+        let mut framed_conn = Framed::new(conn, codec);
         while !shutdown.is_shutdown() {
             tokio::select! {
-                Some(framed_sphinx_packet) = framed_conn.next() => {
+                // if our delay queue is getting congested and this connection is responsible for
+                // a big chunk of it, stop reading from the socket for a while to apply backpressure
+                biased;
+                _ = shutdown.recv() => {
+                    log::trace!("ConnectionHandler: received shutdown");
+                    continue;
+                }
+                _ = packet_sender.wait_for_capacity() => (),
+            }
+
+            tokio::select! {
+                // note: the end of the stream has to be matched explicitly, as otherwise the branch
+                // would just get disabled and the closed connection would hold its permit
+                // until the idle timeout
+                framed_sphinx_packet = framed_conn.next() => {
                     match framed_sphinx_packet {
-                        Ok(framed_sphinx_packet) => {
+                        Some(Ok(framed_sphinx_packet)) => {
                             // TODO: benchmark spawning tokio task with full processing vs just processing it
                             // synchronously (without delaying inside of course,
                             // delay is moved to a global DelayQueue)
@@ -91,17 +246,59 @@ impl ConnectionHandler {
                             // in theory we could process multiple sphinx packet from the same connection in parallel,
                             // but we already handle multiple concurrent connections so if anything, making
                             // that change would only slow things down
-                            self.handle_received_packet(framed_sphinx_packet);
+                            // (unless the processing is moved off the runtime onto the dedicated pool)
+                            if !self.packet_processor.uses_processing_pool() {
+                                self.handle_received_packet(&packet_sender, framed_sphinx_packet);
+                                continue;
+                            }
+
+                            // grab whatever else is already available without waiting for more data
+                            let mut batch = vec![framed_sphinx_packet];
+                            let mut connection_error = None;
+                            while batch.len() < self.packet_processor.max_batch_size() {
+                                match framed_conn.next().now_or_never() {
+                                    Some(Some(Ok(framed_sphinx_packet))) => {
+                                        batch.push(framed_sphinx_packet)
+                                    }
+                                    Some(Some(Err(err))) => {
+                                        connection_error = Some(err);
+                                        break;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            self.handle_received_batch(&packet_sender, batch).await;
+
+                            if let Some(err) = connection_error {
+                                error!(
+                                    "The socket connection got corrupted with error: {:?}. Closing the socket",
+                                    err
+                                );
+                                return;
+                            }
                         }
-                        Err(err) => {
+                        Some(Err(err)) => {
                             error!(
                                 "The socket connection got corrupted with error: {:?}. Closing the socket",
                                 err
                             );
                             return;
                         }
+                        None => {
+                            debug!("Connection from {:?} got closed by the remote", remote);
+                            return;
+                        }
                     }
                 },
+                _ = tokio::time::sleep(self.idle_timeout) => {
+                    debug!("Closing idle connection from {:?}", remote);
+                    return;
+                }
+                // stop receiving any new packets so that the delay queue could get drained
+                _ = maintenance.wait_for_drain() => {
+                    debug!("Closing connection from {:?} due to maintenance", remote);
+                    return;
+                }
                 _ = shutdown.recv() => {
                     log::trace!("ConnectionHandler: received shutdown");
                 }
@@ -115,3 +312,109 @@ impl ConnectionHandler {
         log::trace!("ConnectionHandler: Exiting");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DelayQueueOverflowPolicy;
+    use crate::node::listener::admission::{AdmissionControl, ConnectionLimits};
+    use crate::node::node_statistics::DropCounters;
+    use crate::node::packet_delayforwarder::{DelayForwarder, DelayQueueLimits};
+    use futures::channel::mpsc;
+    use futures::SinkExt;
+    use mixnet_client::link::codec::LinkCodec;
+    use mixnode_common::sphinx_key_rotation::SphinxKeyRing;
+    use nymsphinx::addressing::nodes::NymNodeRoutingAddress;
+    use nymsphinx::params::{PacketMode, PacketSize};
+    use nymsphinx::NymPacket;
+    use std::io;
+    use task::ShutdownNotifier;
+    use tokio::net::TcpListener;
+
+    struct NoopClient;
+
+    impl mixnet_client::SendWithoutResponse for NoopClient {
+        fn send_without_response(
+            &mut self,
+            _address: NymNodeRoutingAddress,
+            _packet: NymPacket,
+            _packet_mode: PacketMode,
+        ) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn closed_connection_releases_its_permit() {
+        let (stats_sender, _stats_receiver) = mpsc::unbounded();
+        let stats_sender = UpdateSender::new(stats_sender, DropCounters::default());
+        let metrics = NodeMetrics::new();
+        let shutdown = ShutdownNotifier::default();
+
+        let forwarder = DelayForwarder::new(
+            NoopClient,
+            DelayQueueLimits {
+                maximum_packets: 10,
+                maximum_bytes: usize::MAX,
+                overflow_policy: DelayQueueOverflowPolicy::DropNewest,
+            },
+            stats_sender.clone(),
+            metrics.clone(),
+            shutdown.subscribe(),
+        );
+        let packet_processor = PacketProcessor::new(
+            SphinxKeyRing::new_static(nymsphinx::crypto::keygen().0),
+            stats_sender.clone(),
+            metrics,
+        );
+        let handler = ConnectionHandler::new(
+            packet_processor,
+            forwarder.sender(),
+            Arc::new(identity::KeyPair::new(&mut rand::rngs::OsRng)),
+            LinkPeers::new(),
+            InboundLinkPolicy::AllowPlaintext,
+            PendingLoops::default(),
+            stats_sender,
+            // long enough for the test to time out if we were relying on it
+            Duration::from_secs(3600),
+        );
+
+        let admission = AdmissionControl::new(
+            ConnectionLimits {
+                maximum_connections: 1,
+                maximum_connections_per_address: 1,
+            },
+            None,
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (initiator, accepted) = tokio::join!(TcpStream::connect(address), listener.accept());
+        let (accepted, remote) = accepted.unwrap();
+
+        let permit = admission.try_admit(remote.ip()).unwrap();
+        let handler_task = tokio::spawn(handler.handle_connection(
+            accepted,
+            remote,
+            permit,
+            MaintenanceMode::new(),
+            shutdown.subscribe(),
+        ));
+
+        // make sure the handler gets past the connection setup before the connection is closed
+        let mut initiator = Framed::new(initiator.unwrap(), LinkCodec::plaintext());
+        let packet_bytes = vec![42u8; PacketSize::AckPacket.size()];
+        let packet = NymPacket::sphinx_from_bytes(&packet_bytes).unwrap();
+        initiator
+            .send(FramedSphinxPacket::new(packet, PacketMode::Mix, false))
+            .await
+            .unwrap();
+        drop(initiator);
+
+        tokio::time::timeout(Duration::from_secs(5), handler_task)
+            .await
+            .expect("the handler did not finish after the connection got closed")
+            .unwrap();
+        assert!(admission.try_admit(remote.ip()).is_ok());
+    }
+}
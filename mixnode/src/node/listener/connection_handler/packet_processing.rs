@@ -1,12 +1,15 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::node::node_statistics;
-use crypto::asymmetric::encryption;
+use crate::node::metrics::NodeMetrics;
+use crate::node::node_statistics::{self, DropReason};
 use mixnode_common::packet_processor::error::MixProcessingError;
 pub use mixnode_common::packet_processor::processor::MixProcessingResult;
 use mixnode_common::packet_processor::processor::SphinxPacketProcessor;
+use mixnode_common::packet_processor::worker_pool::{SphinxProcessingPool, ThreadPoolBuildError};
+use mixnode_common::sphinx_key_rotation::SphinxKeyRing;
 use nymsphinx::framing::packet::FramedSphinxPacket;
+use std::time::{Duration, Instant};
 
 // PacketProcessor contains all data required to correctly unwrap and forward sphinx packets
 #[derive(Clone)]
@@ -14,18 +17,78 @@ pub struct PacketProcessor {
     /// Responsible for performing unwrapping
     inner_processor: SphinxPacketProcessor,
 
+    /// Optional dedicated pool of threads performing the unwrapping off the tokio runtime
+    processing_pool: Option<SphinxProcessingPool>,
+
+    /// Maximum number of packets submitted to the processing pool at once
+    max_batch_size: usize,
+
     /// Responsible for updating metrics data
     node_stats_update_sender: node_statistics::UpdateSender,
+
+    /// Responsible for updating real-time metrics data
+    metrics: NodeMetrics,
 }
 
 impl PacketProcessor {
     pub(crate) fn new(
-        encryption_key: &encryption::PrivateKey,
+        sphinx_keys: SphinxKeyRing,
         node_stats_update_sender: node_statistics::UpdateSender,
+        metrics: NodeMetrics,
     ) -> Self {
         PacketProcessor {
-            inner_processor: SphinxPacketProcessor::new(encryption_key.into()),
+            inner_processor: SphinxPacketProcessor::new_with_key_ring(sphinx_keys),
+            processing_pool: None,
+            max_batch_size: 1,
             node_stats_update_sender,
+            metrics,
+        }
+    }
+
+    /// Moves the sphinx unwrapping onto a dedicated pool of `threads` threads, to which
+    /// packets are submitted in batches of at most `max_batch_size` packets.
+    pub(crate) fn with_processing_pool(
+        mut self,
+        threads: usize,
+        max_batch_size: usize,
+    ) -> Result<Self, ThreadPoolBuildError> {
+        self.processing_pool = Some(SphinxProcessingPool::new(
+            self.inner_processor.clone(),
+            threads,
+        )?);
+        self.max_batch_size = max_batch_size.max(1);
+        Ok(self)
+    }
+
+    pub(crate) fn metrics(&self) -> &NodeMetrics {
+        &self.metrics
+    }
+
+    /// Maximum number of packets that should be passed to `process_received_batch` at once.
+    pub(crate) fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    pub(crate) fn uses_processing_pool(&self) -> bool {
+        self.processing_pool.is_some()
+    }
+
+    fn report_outcome(
+        &self,
+        processing_result: &Result<MixProcessingResult, MixProcessingError>,
+        processing_time: Duration,
+    ) {
+        self.node_stats_update_sender.report_received();
+        self.metrics.observe_sphinx_processing_time(processing_time);
+
+        if let Err(err) = processing_result {
+            if matches!(err, MixProcessingError::ReplayedPacket) {
+                self.node_stats_update_sender.report_replayed();
+            }
+            let reason = DropReason::from(err);
+            self.node_stats_update_sender
+                .report_dropped_with_reason(reason);
+            self.metrics.report_dropped(reason);
         }
     }
 
@@ -33,7 +96,32 @@ impl PacketProcessor {
         &self,
         received: FramedSphinxPacket,
     ) -> Result<MixProcessingResult, MixProcessingError> {
-        self.node_stats_update_sender.report_received();
-        self.inner_processor.process_received(received)
+        let processing_start = Instant::now();
+        let processing_result = self.inner_processor.process_received(received);
+        self.report_outcome(&processing_result, processing_start.elapsed());
+        processing_result
+    }
+
+    /// Processes the batch of packets, using the processing pool if available, returning
+    /// the results in the same order as the packets were provided.
+    pub(crate) async fn process_received_batch(
+        &self,
+        received: Vec<FramedSphinxPacket>,
+    ) -> Vec<Result<MixProcessingResult, MixProcessingError>> {
+        match &self.processing_pool {
+            Some(pool) => pool
+                .process_batch(received)
+                .await
+                .into_iter()
+                .map(|outcome| {
+                    self.report_outcome(&outcome.result, outcome.processing_time);
+                    outcome.result
+                })
+                .collect(),
+            None => received
+                .into_iter()
+                .map(|packet| self.process_received(packet))
+                .collect(),
+        }
     }
 }
@@ -1,7 +1,10 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::node::listener::admission::AdmissionControl;
 use crate::node::listener::connection_handler::ConnectionHandler;
+use crate::node::maintenance::MaintenanceMode;
+use crate::node::node_statistics::UpdateSender;
 use log::error;
 use std::net::SocketAddr;
 use std::process;
@@ -10,16 +13,32 @@ use tokio::task::JoinHandle;
 
 use super::ShutdownListener;
 
+pub(crate) mod admission;
 pub(crate) mod connection_handler;
 
 pub(crate) struct Listener {
     address: SocketAddr,
+    admission_control: AdmissionControl,
+    node_stats_update_sender: UpdateSender,
+    maintenance: MaintenanceMode,
     shutdown: ShutdownListener,
 }
 
 impl Listener {
-    pub(crate) fn new(address: SocketAddr, shutdown: ShutdownListener) -> Self {
-        Listener { address, shutdown }
+    pub(crate) fn new(
+        address: SocketAddr,
+        admission_control: AdmissionControl,
+        node_stats_update_sender: UpdateSender,
+        maintenance: MaintenanceMode,
+        shutdown: ShutdownListener,
+    ) -> Self {
+        Listener {
+            address,
+            admission_control,
+            node_stats_update_sender,
+            maintenance,
+            shutdown,
+        }
     }
 
     async fn run(&mut self, connection_handler: ConnectionHandler) {
@@ -37,12 +56,35 @@ impl Listener {
                 connection = listener.accept() => {
                     match connection {
                         Ok((socket, remote_addr)) => {
-                            let handler = connection_handler.clone();
-                            tokio::spawn(handler.handle_connection(socket, remote_addr, self.shutdown.clone()));
+                            match self.admission_control.try_admit(remote_addr.ip()) {
+                                Ok(permit) => {
+                                    let handler = connection_handler.clone();
+                                    tokio::spawn(handler.handle_connection(
+                                        socket,
+                                        remote_addr,
+                                        permit,
+                                        self.maintenance.clone(),
+                                        self.shutdown.clone(),
+                                    ));
+                                }
+                                Err(reason) => {
+                                    debug!(
+                                        "Rejecting connection from {} - {}",
+                                        remote_addr,
+                                        reason.as_str()
+                                    );
+                                    self.node_stats_update_sender
+                                        .report_connection_rejected(reason);
+                                }
+                            }
                         }
                         Err(err) => warn!("Failed to accept incoming connection - {:?}", err),
                     }
                 },
+                _ = self.maintenance.wait_for_drain() => {
+                    info!("Entered maintenance mode - no longer accepting new connections");
+                    break;
+                }
                 _ = self.shutdown.recv() => {
                     log::trace!("Listener: Received shutdown");
                 }
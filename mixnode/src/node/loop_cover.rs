@@ -0,0 +1,326 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::maintenance::MaintenanceMode;
+use crate::node::node_statistics::UpdateSender;
+use crate::node::packet_delayforwarder::PacketDelayForwardSender;
+use crypto::asymmetric::identity;
+use log::*;
+use mixnet_contract_common::sphinx_keys::sphinx_key_epoch;
+use mixnode_common::sphinx_key_rotation::current_unix_timestamp;
+use mixnode_common::validator_api::ValidatorApiEndpoints;
+use nymsphinx::addressing::nodes::NymNodeRoutingAddress;
+use nymsphinx::builder::SphinxPacketBuilder;
+use nymsphinx::forwarding::packet::MixPacket;
+use nymsphinx::params::{PacketMode, PacketSize};
+use nymsphinx::utils::sample_poisson_duration;
+use nymsphinx::{delays, Destination, Node as SphinxNode};
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use task::ShutdownListener;
+use tokio::time::Instant;
+use topology::{nym_topology_from_detailed, MixLayer, NymTopology};
+
+/// How often the view of the network used for constructing the loops is refreshed.
+const TOPOLOGY_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const LOOP_ID_LENGTH: usize = 16;
+
+// loop cover packets carry no data apart from their identifier, so they're sent as the smallest
+// packets available
+const LOOP_COVER_PACKET_SIZE: PacketSize = PacketSize::AckPacket;
+
+type LoopId = [u8; LOOP_ID_LENGTH];
+
+pub(crate) struct LoopCoverConfig {
+    /// Average delay between subsequent loop cover packets being sent.
+    pub(crate) average_loop_delay: Duration,
+
+    /// Average delay of the loop cover packets at each hop.
+    pub(crate) average_packet_delay: Duration,
+
+    /// Maximum amount of time to wait for the loop cover packet to return before considering it lost.
+    pub(crate) loop_timeout: Duration,
+}
+
+struct PendingLoop {
+    sent_at: Instant,
+    first_hop: NymNodeRoutingAddress,
+}
+
+/// Loop cover packets sent by this node that have not yet returned.
+///
+/// Note that cloning it produces a handle to the same underlying data.
+#[derive(Clone, Default)]
+pub(crate) struct PendingLoops {
+    inner: Arc<Mutex<HashMap<LoopId, PendingLoop>>>,
+}
+
+impl PendingLoops {
+    fn insert(&self, loop_id: LoopId, first_hop: NymNodeRoutingAddress) {
+        self.inner
+            .lock()
+            .expect("pending loops mutex got poisoned")
+            .insert(
+                loop_id,
+                PendingLoop {
+                    sent_at: Instant::now(),
+                    first_hop,
+                },
+            );
+    }
+
+    /// Checks whether the received final hop message is one of our pending loops and if so,
+    /// returns its round-trip time.
+    pub(crate) fn complete(&self, message: &[u8]) -> Option<Duration> {
+        let loop_id = LoopId::try_from(message).ok()?;
+        self.inner
+            .lock()
+            .expect("pending loops mutex got poisoned")
+            .remove(&loop_id)
+            .map(|pending| pending.sent_at.elapsed())
+    }
+
+    /// Removes all the loops that have been pending for longer than the timeout, returning the first
+    /// hops they were sent to.
+    fn remove_expired(&self, timeout: Duration) -> Vec<NymNodeRoutingAddress> {
+        let mut expired = Vec::new();
+        self.inner
+            .lock()
+            .expect("pending loops mutex got poisoned")
+            .retain(|_, pending| {
+                if pending.sent_at.elapsed() > timeout {
+                    expired.push(pending.first_hop);
+                    false
+                } else {
+                    true
+                }
+            });
+        expired
+    }
+}
+
+/// Creates a route going through the remaining layers of the network and back to the node itself.
+fn loop_route<R: RngCore>(
+    rng: &mut R,
+    topology: &NymTopology,
+    own_identity: &identity::PublicKey,
+) -> Option<Vec<SphinxNode>> {
+    let epoch = topology.sphinx_key_epoch();
+    let (own_layer, own_node) = topology.mixes().iter().find_map(|(layer, mixes)| {
+        mixes
+            .iter()
+            .find(|mix| &mix.identity_key == own_identity)
+            .map(|mix| (*layer, mix))
+    })?;
+
+    let mut route = Vec::with_capacity(3);
+    for offset in 1..3 {
+        let layer: MixLayer = (own_layer + offset - 1) % 3 + 1;
+        let mix = topology.mixes().get(&layer)?.choose(rng)?;
+        route.push(mix.to_sphinx_node(epoch));
+    }
+    route.push(own_node.to_sphinx_node(epoch));
+    Some(route)
+}
+
+/// Stream of Poisson-distributed loop cover packets sent by the mixnode through the remaining
+/// layers of the network and back to itself. Loops that fail to return in time might indicate
+/// that packets are being dropped or delayed on our links, for example as part of an (n-1) attack.
+pub(crate) struct LoopCoverTrafficStream {
+    config: LoopCoverConfig,
+    identity: identity::PublicKey,
+    topology: Option<NymTopology>,
+    validator_api: ValidatorApiEndpoints,
+    pending_loops: PendingLoops,
+    delay_forwarding_channel: PacketDelayForwardSender,
+    node_stats_update_sender: UpdateSender,
+    maintenance: MaintenanceMode,
+    shutdown: ShutdownListener,
+}
+
+impl LoopCoverTrafficStream {
+    pub(crate) fn new(
+        config: LoopCoverConfig,
+        identity: identity::PublicKey,
+        validator_api: ValidatorApiEndpoints,
+        delay_forwarding_channel: PacketDelayForwardSender,
+        node_stats_update_sender: UpdateSender,
+        maintenance: MaintenanceMode,
+        shutdown: ShutdownListener,
+    ) -> Self {
+        LoopCoverTrafficStream {
+            config,
+            identity,
+            topology: None,
+            validator_api,
+            pending_loops: PendingLoops::default(),
+            delay_forwarding_channel,
+            node_stats_update_sender,
+            maintenance,
+            shutdown,
+        }
+    }
+
+    pub(crate) fn pending_loops(&self) -> PendingLoops {
+        self.pending_loops.clone()
+    }
+
+    async fn refresh_topology(&mut self) {
+        let validator_client = self.validator_api.random_client();
+        match validator_client.get_cached_active_mixnodes().await {
+            Ok(mixnodes) => {
+                // if the rotated keys are unavailable, the static ones are still accepted
+                let announcements = validator_client
+                    .get_cached_sphinx_key_announcements()
+                    .await
+                    .unwrap_or_default();
+                let epoch = sphinx_key_epoch(current_unix_timestamp());
+                self.topology = Some(
+                    nym_topology_from_detailed(mixnodes, Vec::new())
+                        .with_sphinx_key_announcements(
+                            &announcements.mixnodes,
+                            &announcements.gateways,
+                        )
+                        .with_sphinx_key_epoch(epoch),
+                );
+            }
+            Err(err) => warn!(
+                "failed to obtain the network topology for the loop cover traffic - {}",
+                err
+            ),
+        }
+    }
+
+    fn report_expired_loops(&self) {
+        for first_hop in self.pending_loops.remove_expired(self.config.loop_timeout) {
+            debug!(
+                "loop cover packet sent via {} did not return in time",
+                first_hop
+            );
+            self.node_stats_update_sender
+                .report_loop_cover_lost(first_hop.to_string());
+        }
+    }
+
+    fn send_loop_cover_packet(&self) {
+        // don't add anything new to the delay queue while it's being drained
+        if self.maintenance.draining_since().is_some() {
+            return;
+        }
+
+        let topology = match &self.topology {
+            Some(topology) => topology,
+            None => return,
+        };
+
+        let mut rng = OsRng;
+        let route = match loop_route(&mut rng, topology, &self.identity) {
+            Some(route) => route,
+            None => {
+                trace!("we are not part of the active set - not sending loop cover traffic");
+                return;
+            }
+        };
+
+        let mut loop_id = LoopId::default();
+        rng.fill_bytes(&mut loop_id);
+
+        let destination = Destination::new(
+            self.identity.derive_destination_address(),
+            Default::default(),
+        );
+        let delays =
+            delays::generate_from_average_duration(route.len(), self.config.average_packet_delay);
+        let packet = match SphinxPacketBuilder::new()
+            .with_payload_size(LOOP_COVER_PACKET_SIZE.payload_size())
+            .build_packet(loop_id.to_vec(), &route, &destination, &delays)
+        {
+            Ok(packet) => packet,
+            Err(err) => {
+                warn!("failed to create loop cover packet - {}", err);
+                return;
+            }
+        };
+
+        // the route is never empty and it consists of valid mixnode addresses
+        let first_hop = NymNodeRoutingAddress::try_from(route[0].address).unwrap();
+        self.pending_loops.insert(loop_id, first_hop);
+        self.node_stats_update_sender.report_loop_cover_sent();
+
+        self.delay_forwarding_channel.forward_packet(
+            MixPacket::new(first_hop, packet.into(), PacketMode::Mix),
+            None,
+        );
+    }
+
+    pub(crate) async fn run(&mut self) {
+        debug!("Started LoopCoverTrafficStream with graceful shutdown support");
+
+        let mut topology_refresh = tokio::time::interval(TOPOLOGY_REFRESH_INTERVAL);
+        let next_loop = tokio::time::sleep(sample_poisson_duration(
+            &mut OsRng,
+            self.config.average_loop_delay,
+        ));
+        tokio::pin!(next_loop);
+
+        while !self.shutdown.is_shutdown() {
+            tokio::select! {
+                _ = topology_refresh.tick() => self.refresh_topology().await,
+                _ = &mut next_loop => {
+                    self.report_expired_loops();
+                    self.send_loop_cover_packet();
+
+                    let delay = sample_poisson_duration(&mut OsRng, self.config.average_loop_delay);
+                    next_loop.as_mut().reset(Instant::now() + delay);
+                }
+                _ = self.shutdown.recv() => {
+                    trace!("LoopCoverTrafficStream: Received shutdown");
+                }
+            }
+        }
+        trace!("LoopCoverTrafficStream: Exiting");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_pending_loops_are_completed() {
+        let pending_loops = PendingLoops::default();
+        let first_hop =
+            NymNodeRoutingAddress::from("1.2.3.4:1789".parse::<std::net::SocketAddr>().unwrap());
+        pending_loops.insert([1; LOOP_ID_LENGTH], first_hop);
+
+        assert!(pending_loops.complete(&[2; LOOP_ID_LENGTH]).is_none());
+        assert!(pending_loops.complete(b"foomp").is_none());
+        assert!(pending_loops.complete(&[1; LOOP_ID_LENGTH]).is_some());
+
+        // each loop can only be completed once
+        assert!(pending_loops.complete(&[1; LOOP_ID_LENGTH]).is_none());
+    }
+
+    #[test]
+    fn expired_loops_are_removed() {
+        let pending_loops = PendingLoops::default();
+        let first_hop =
+            NymNodeRoutingAddress::from("1.2.3.4:1789".parse::<std::net::SocketAddr>().unwrap());
+        pending_loops.insert([1; LOOP_ID_LENGTH], first_hop);
+
+        assert!(pending_loops
+            .remove_expired(Duration::from_secs(60))
+            .is_empty());
+        assert_eq!(
+            pending_loops.remove_expired(Duration::ZERO),
+            vec![first_hop]
+        );
+        assert!(pending_loops.complete(&[1; LOOP_ID_LENGTH]).is_none());
+    }
+}
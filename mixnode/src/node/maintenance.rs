@@ -0,0 +1,151 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::asymmetric::identity;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::watch;
+
+/// Maintenance mode of the node. Once the node starts draining, it stops accepting any new
+/// traffic and exits as soon as the packets it has already received got forwarded.
+///
+/// Note that cloning it produces a handle to the same underlying state.
+#[derive(Clone)]
+pub(crate) struct MaintenanceMode {
+    draining_since: Arc<watch::Sender<Option<SystemTime>>>,
+    receiver: watch::Receiver<Option<SystemTime>>,
+}
+
+impl MaintenanceMode {
+    pub(crate) fn new() -> Self {
+        let (sender, receiver) = watch::channel(None);
+        MaintenanceMode {
+            draining_since: Arc::new(sender),
+            receiver,
+        }
+    }
+
+    /// Puts the node into the draining state, returning whether it wasn't already draining.
+    pub(crate) fn start_draining(&self) -> bool {
+        self.draining_since.send_if_modified(|draining_since| {
+            if draining_since.is_some() {
+                return false;
+            }
+            *draining_since = Some(SystemTime::now());
+            true
+        })
+    }
+
+    pub(crate) fn draining_since(&self) -> Option<SystemTime> {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves once the node has started draining.
+    pub(crate) async fn wait_for_drain(&mut self) {
+        while self.receiver.borrow().is_none() {
+            // the sender lives for as long as any of the handles, so this can't fail
+            // while we're holding one
+            if self.receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    pub(crate) fn signed_status(&self, identity: &identity::KeyPair) -> SignedNodeStatus {
+        let draining_since = self.draining_since();
+        SignedNodeStatus::new(
+            identity,
+            &NodeStatus {
+                available: draining_since.is_none(),
+                draining_since,
+                timestamp: SystemTime::now(),
+            },
+        )
+    }
+}
+
+impl Default for MaintenanceMode {
+    fn default() -> Self {
+        MaintenanceMode::new()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct NodeStatus {
+    /// Indicates whether the node is willing to accept any new traffic.
+    available: bool,
+
+    #[serde(with = "humantime_serde")]
+    draining_since: Option<SystemTime>,
+
+    /// Time at which the status was produced, so that stale statuses could not be replayed.
+    #[serde(with = "humantime_serde")]
+    timestamp: SystemTime,
+}
+
+/// Status of the node signed with its identity key, so that validator-api could stop
+/// routing traffic through it without having to trust whoever relayed the status.
+#[derive(Debug, Serialize)]
+pub(crate) struct SignedNodeStatus {
+    identity: String,
+
+    /// JSON serialized `NodeStatus`.
+    status: String,
+
+    /// Base58 encoded signature on the bytes of the `status`.
+    signature: String,
+}
+
+impl SignedNodeStatus {
+    fn new(identity: &identity::KeyPair, status: &NodeStatus) -> Self {
+        // serializing a struct with string keys can't fail
+        let status = serde_json::to_string(status).unwrap();
+        let signature = identity.private_key().sign(status.as_bytes());
+
+        SignedNodeStatus {
+            identity: identity.public_key().to_base58_string(),
+            status,
+            signature: signature.to_base58_string(),
+        }
+    }
+}
+
+/// Puts the node into maintenance mode upon receiving SIGUSR1.
+#[cfg(unix)]
+pub(crate) fn start_drain_signal_listener(maintenance: MaintenanceMode) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigusr1 = signal(SignalKind::user_defined1()).expect("Failed to setup SIGUSR1 channel");
+    tokio::spawn(async move {
+        if sigusr1.recv().await.is_some() {
+            log::info!("Received SIGUSR1");
+            maintenance.start_draining();
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub(crate) fn start_drain_signal_listener(_maintenance: MaintenanceMode) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn draining_is_only_started_once() {
+        let maintenance = MaintenanceMode::new();
+        let mut waiting = maintenance.clone();
+        assert!(maintenance.draining_since().is_none());
+
+        let waiter = tokio::spawn(async move { waiting.wait_for_drain().await });
+        assert!(maintenance.start_draining());
+        let draining_since = maintenance.draining_since();
+        assert!(draining_since.is_some());
+
+        assert!(!maintenance.start_draining());
+        assert_eq!(maintenance.draining_since(), draining_since);
+
+        waiter.await.unwrap();
+    }
+}
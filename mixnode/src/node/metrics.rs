@@ -0,0 +1,403 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::node::node_statistics::DropReason;
+use mixnode_common::verloc::VerlocResult;
+use std::collections::HashMap;
+use std::fmt::{Display, Write};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const METRICS_PREFIX: &str = "nym_mixnode";
+
+// upper bounds of the sphinx processing time histogram buckets
+const SPHINX_PROCESSING_TIME_BUCKETS: [Duration; 12] = [
+    Duration::from_micros(10),
+    Duration::from_micros(25),
+    Duration::from_micros(50),
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(100),
+];
+
+#[derive(Clone, Copy)]
+pub(crate) enum MetricType {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Histogram => "histogram",
+        }
+    }
+}
+
+/// Encoder of the Prometheus text exposition format.
+#[derive(Default)]
+pub(crate) struct MetricsEncoder {
+    output: String,
+}
+
+impl MetricsEncoder {
+    pub(crate) fn new() -> Self {
+        Default::default()
+    }
+
+    /// Writes the `HELP` and `TYPE` lines of a metric family. It must be called exactly once,
+    /// before any samples of the family get written.
+    pub(crate) fn describe(&mut self, name: &str, metric_type: MetricType, help: &str) {
+        // writing to a String can't fail
+        let _ = writeln!(self.output, "# HELP {}_{} {}", METRICS_PREFIX, name, help);
+        let _ = writeln!(
+            self.output,
+            "# TYPE {}_{} {}",
+            METRICS_PREFIX,
+            name,
+            metric_type.as_str()
+        );
+    }
+
+    pub(crate) fn sample<V: Display>(&mut self, name: &str, labels: &[(&str, &str)], value: V) {
+        let _ = write!(self.output, "{}_{}", METRICS_PREFIX, name);
+        if !labels.is_empty() {
+            self.output.push('{');
+            for (i, (label, label_value)) in labels.iter().enumerate() {
+                if i != 0 {
+                    self.output.push(',');
+                }
+                let _ = write!(
+                    self.output,
+                    "{}=\"{}\"",
+                    label,
+                    escape_label_value(label_value)
+                );
+            }
+            self.output.push('}');
+        }
+        let _ = writeln!(self.output, " {}", value);
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.output
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Histogram with fixed buckets of durations, exposed in seconds.
+struct DurationHistogram {
+    bounds: &'static [Duration],
+    // note: those are not cumulative, the accumulation happens during encoding
+    buckets: Vec<AtomicU64>,
+    sum_nanos: AtomicU64,
+}
+
+impl DurationHistogram {
+    fn new(bounds: &'static [Duration]) -> Self {
+        DurationHistogram {
+            bounds,
+            // the extra bucket is for the `+Inf` upper bound
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, value: Duration) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| &value <= bound)
+            .unwrap_or(self.bounds.len());
+
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos
+            .fetch_add(value.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn encode(&self, encoder: &mut MetricsEncoder, name: &str, help: &str) {
+        encoder.describe(name, MetricType::Histogram, help);
+
+        let bucket_name = format!("{}_bucket", name);
+        let mut cumulative = 0;
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = bound.as_secs_f64().to_string();
+            encoder.sample(&bucket_name, &[("le", &le)], cumulative);
+        }
+        cumulative += self.buckets[self.bounds.len()].load(Ordering::Relaxed);
+        encoder.sample(&bucket_name, &[("le", "+Inf")], cumulative);
+
+        let sum = Duration::from_nanos(self.sum_nanos.load(Ordering::Relaxed));
+        encoder.sample(&format!("{}_sum", name), &[], sum.as_secs_f64());
+        encoder.sample(&format!("{}_count", name), &[], cumulative);
+    }
+}
+
+struct NodeMetricsInner {
+    delay_queue_depth: AtomicUsize,
+    sphinx_processing_time: DurationHistogram,
+    dropped_packets: [AtomicU64; DropReason::ALL.len()],
+    inbound_connections: Mutex<HashMap<IpAddr, u64>>,
+}
+
+/// Real-time metrics of the mixnode that are not part of the periodically updated `NodeStats`.
+///
+/// Note that cloning it produces a handle to the same underlying data.
+#[derive(Clone)]
+pub(crate) struct NodeMetrics {
+    inner: Arc<NodeMetricsInner>,
+}
+
+impl NodeMetrics {
+    pub(crate) fn new() -> Self {
+        NodeMetrics {
+            inner: Arc::new(NodeMetricsInner {
+                delay_queue_depth: AtomicUsize::new(0),
+                sphinx_processing_time: DurationHistogram::new(&SPHINX_PROCESSING_TIME_BUCKETS),
+                dropped_packets: Default::default(),
+                inbound_connections: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    pub(crate) fn set_delay_queue_depth(&self, depth: usize) {
+        self.inner.delay_queue_depth.store(depth, Ordering::Relaxed)
+    }
+
+    pub(crate) fn observe_sphinx_processing_time(&self, processing_time: Duration) {
+        self.inner.sphinx_processing_time.observe(processing_time)
+    }
+
+    pub(crate) fn report_dropped(&self, reason: DropReason) {
+        self.inner.dropped_packets[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Registers new inbound connection from the specified peer. It is considered active
+    /// until the returned guard is dropped.
+    pub(crate) fn inbound_connection(&self, peer: IpAddr) -> InboundConnectionGuard {
+        *self
+            .inner
+            .inbound_connections
+            .lock()
+            .expect("metrics mutex got poisoned")
+            .entry(peer)
+            .or_insert(0) += 1;
+
+        InboundConnectionGuard {
+            metrics: self.clone(),
+            peer,
+        }
+    }
+
+    fn close_inbound_connection(&self, peer: IpAddr) {
+        let mut connections = self
+            .inner
+            .inbound_connections
+            .lock()
+            .expect("metrics mutex got poisoned");
+        if let Some(count) = connections.get_mut(&peer) {
+            *count -= 1;
+            if *count == 0 {
+                connections.remove(&peer);
+            }
+        }
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut MetricsEncoder) {
+        encoder.describe(
+            "delay_queue_depth",
+            MetricType::Gauge,
+            "Number of packets currently being delayed before getting forwarded.",
+        );
+        encoder.sample(
+            "delay_queue_depth",
+            &[],
+            self.inner.delay_queue_depth.load(Ordering::Relaxed),
+        );
+
+        self.inner.sphinx_processing_time.encode(
+            encoder,
+            "sphinx_processing_time_seconds",
+            "Time taken to unwrap received sphinx packets.",
+        );
+
+        encoder.describe(
+            "packets_dropped_total",
+            MetricType::Counter,
+            "Number of packets dropped by the node, by the reason of dropping them.",
+        );
+        for reason in DropReason::ALL {
+            encoder.sample(
+                "packets_dropped_total",
+                &[("reason", reason.as_str())],
+                self.inner.dropped_packets[reason as usize].load(Ordering::Relaxed),
+            );
+        }
+
+        encoder.describe(
+            "inbound_connections",
+            MetricType::Gauge,
+            "Number of currently open inbound connections, by the remote peer.",
+        );
+        let connections = self
+            .inner
+            .inbound_connections
+            .lock()
+            .expect("metrics mutex got poisoned");
+        for (peer, count) in connections.iter() {
+            encoder.sample("inbound_connections", &[("peer", &peer.to_string())], count);
+        }
+    }
+}
+
+pub(crate) struct InboundConnectionGuard {
+    metrics: NodeMetrics,
+    peer: IpAddr,
+}
+
+impl Drop for InboundConnectionGuard {
+    fn drop(&mut self) {
+        self.metrics.close_inbound_connection(self.peer)
+    }
+}
+
+pub(crate) fn encode_verloc(encoder: &mut MetricsEncoder, verloc: &VerlocResult) {
+    encoder.describe(
+        "verloc_nodes_tested",
+        MetricType::Gauge,
+        "Number of nodes tested during the current verloc run.",
+    );
+    encoder.sample("verloc_nodes_tested", &[], verloc.total_tested());
+
+    // (identity, [minimum, mean, maximum, standard deviation])
+    let measured = verloc
+        .results()
+        .iter()
+        .filter_map(|result| {
+            result.latest_measurement.map(|measurement| {
+                (
+                    result.identity.to_base58_string(),
+                    [
+                        measurement.minimum,
+                        measurement.mean,
+                        measurement.maximum,
+                        measurement.standard_deviation,
+                    ],
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    encoder.describe(
+        "verloc_nodes_unreachable",
+        MetricType::Gauge,
+        "Number of nodes that could not be measured during the current verloc run.",
+    );
+    encoder.sample(
+        "verloc_nodes_unreachable",
+        &[],
+        verloc.results().len() - measured.len(),
+    );
+
+    let families = [
+        (
+            "verloc_rtt_minimum_seconds",
+            "Minimum measured round-trip time to the node.",
+        ),
+        (
+            "verloc_rtt_mean_seconds",
+            "Mean measured round-trip time to the node.",
+        ),
+        (
+            "verloc_rtt_maximum_seconds",
+            "Maximum measured round-trip time to the node.",
+        ),
+        (
+            "verloc_rtt_standard_deviation_seconds",
+            "Standard deviation of the measured round-trip times to the node.",
+        ),
+    ];
+
+    for (i, (name, help)) in families.iter().enumerate() {
+        encoder.describe(name, MetricType::Gauge, help);
+        for (identity, measurement) in &measured {
+            encoder.sample(
+                name,
+                &[("identity", identity)],
+                measurement[i].as_secs_f64(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let histogram = DurationHistogram::new(&SPHINX_PROCESSING_TIME_BUCKETS);
+        histogram.observe(Duration::from_micros(5));
+        histogram.observe(Duration::from_micros(40));
+        histogram.observe(Duration::from_secs(1));
+
+        let mut encoder = MetricsEncoder::new();
+        histogram.encode(&mut encoder, "foo_seconds", "foomp");
+        let encoded = encoder.finish();
+
+        assert!(encoded.contains("# TYPE nym_mixnode_foo_seconds histogram\n"));
+        assert!(encoded.contains("nym_mixnode_foo_seconds_bucket{le=\"0.00001\"} 1\n"));
+        assert!(encoded.contains("nym_mixnode_foo_seconds_bucket{le=\"0.00005\"} 2\n"));
+        assert!(encoded.contains("nym_mixnode_foo_seconds_bucket{le=\"0.1\"} 2\n"));
+        assert!(encoded.contains("nym_mixnode_foo_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(encoded.contains("nym_mixnode_foo_seconds_count 3\n"));
+    }
+
+    #[test]
+    fn inbound_connections_are_removed_once_closed() {
+        let metrics = NodeMetrics::new();
+        let peer: IpAddr = "1.2.3.4".parse().unwrap();
+
+        let guard1 = metrics.inbound_connection(peer);
+        let guard2 = metrics.inbound_connection(peer);
+        let encode = |metrics: &NodeMetrics| {
+            let mut encoder = MetricsEncoder::new();
+            metrics.encode(&mut encoder);
+            encoder.finish()
+        };
+
+        assert!(encode(&metrics).contains("nym_mixnode_inbound_connections{peer=\"1.2.3.4\"} 2\n"));
+        drop(guard1);
+        assert!(encode(&metrics).contains("nym_mixnode_inbound_connections{peer=\"1.2.3.4\"} 1\n"));
+        drop(guard2);
+        assert!(!encode(&metrics).contains("peer=\"1.2.3.4\""));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let mut encoder = MetricsEncoder::new();
+        encoder.sample("foo", &[("bar", "a\"b\\c\nd")], 42);
+        assert_eq!(
+            encoder.finish(),
+            "nym_mixnode_foo{bar=\"a\\\"b\\\\c\\nd\"} 42\n"
+        );
+    }
+}
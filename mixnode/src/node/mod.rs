@@ -6,32 +6,55 @@ use crate::config::persistence::pathfinder::MixNodePathfinder;
 use crate::config::Config;
 use crate::node::http::{
     description::description,
-    hardware::hardware,
+    maintenance::{drain, maintenance_status, MaintenanceState},
+    metrics::metrics as metricsRoute,
     not_found,
+    sphinx_keys::{sphinx_keys, SphinxKeysState},
     stats::stats,
-    verloc::{verloc as verlocRoute, VerlocState},
+    verloc::{verloc as verlocRoute, verloc_history, verloc_reports, VerlocState},
 };
+use crate::node::listener::admission::{AdmissionControl, ConnectionLimits};
 use crate::node::listener::connection_handler::packet_processing::PacketProcessor;
 use crate::node::listener::connection_handler::ConnectionHandler;
 use crate::node::listener::Listener;
-use crate::node::node_description::NodeDescription;
+use crate::node::loop_cover::{LoopCoverConfig, LoopCoverTrafficStream, PendingLoops};
+use crate::node::maintenance::{start_drain_signal_listener, MaintenanceMode};
+use crate::node::metrics::NodeMetrics;
+use crate::node::network_view::{
+    NetworkView, NetworkViewRefresher, DEFAULT_NETWORK_VIEW_REFRESH_INTERVAL,
+};
 use crate::node::node_statistics::SharedNodeStats;
-use crate::node::packet_delayforwarder::{DelayForwarder, PacketDelayForwardSender};
+use crate::node::packet_delayforwarder::{
+    DelayForwarder, DelayQueueLimits, PacketDelayForwardSender,
+};
 use ::crypto::asymmetric::{encryption, identity};
+use config::reload::{
+    start_reload_signal_listener, ReloadError, ReloadRequestReceiver, ReloadRequester, ReloadResult,
+};
 use config::NymConfig;
 use log::{error, info, warn};
-use mixnode_common::verloc::{self, AtomicVerlocResult, VerlocMeasurer};
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use mixnet_client::link::peers::LinkPeers;
+use mixnet_client::link::LinkConfig;
+use mixnode_common::http::hardware::hardware;
+use mixnode_common::http::reload::reload_config;
+use mixnode_common::link_peers::{LinkPeersRefresher, DEFAULT_LINK_PEERS_REFRESH_INTERVAL};
+use mixnode_common::node_description::NodeDescription;
+use mixnode_common::sphinx_key_rotation::{SphinxKeyRing, SphinxKeyRotator};
+use mixnode_common::validator_api::ValidatorApiEndpoints;
+use mixnode_common::verloc::{self, AtomicVerlocResult, VerlocHistory, VerlocMeasurer};
 use std::net::SocketAddr;
 use std::process;
 use std::sync::Arc;
+use std::time::Duration;
 use task::{wait_for_signal, ShutdownListener, ShutdownNotifier};
 use version_checker::parse_version;
 
 mod http;
 mod listener;
-pub(crate) mod node_description;
+mod loop_cover;
+mod maintenance;
+mod metrics;
+mod network_view;
 mod node_statistics;
 mod packet_delayforwarder;
 
@@ -41,20 +64,56 @@ pub struct MixNode {
     descriptor: NodeDescription,
     identity_keypair: Arc<identity::KeyPair>,
     sphinx_keypair: Arc<encryption::KeyPair>,
+    sphinx_key_ring: SphinxKeyRing,
+    link_peers: LinkPeers,
+    validator_api: ValidatorApiEndpoints,
+    metrics: NodeMetrics,
+    maintenance: MaintenanceMode,
+}
+
+fn connection_limits(config: &Config) -> ConnectionLimits {
+    ConnectionLimits {
+        maximum_connections: config.get_maximum_inbound_connections(),
+        maximum_connections_per_address: config.get_maximum_inbound_connections_per_address(),
+    }
 }
 
 impl MixNode {
     pub fn new(config: Config) -> Self {
         let pathfinder = MixNodePathfinder::new_from_config(&config);
+        let sphinx_keypair = Self::load_sphinx_keys(&pathfinder);
 
         MixNode {
             descriptor: Self::load_node_description(&config),
             identity_keypair: Arc::new(Self::load_identity_keys(&pathfinder)),
-            sphinx_keypair: Arc::new(Self::load_sphinx_keys(&pathfinder)),
+            sphinx_key_ring: Self::load_sphinx_key_ring(&config, &sphinx_keypair),
+            sphinx_keypair: Arc::new(sphinx_keypair),
+            link_peers: LinkPeers::new(),
+            validator_api: ValidatorApiEndpoints::new(config.get_validator_api_endpoints()),
+            metrics: NodeMetrics::new(),
+            maintenance: MaintenanceMode::new(),
             config,
         }
     }
 
+    fn load_sphinx_key_ring(
+        config: &Config,
+        sphinx_keypair: &encryption::KeyPair,
+    ) -> SphinxKeyRing {
+        let static_key = sphinx_keypair.private_key().into();
+        if !config.get_enable_sphinx_key_rotation() {
+            return SphinxKeyRing::new_static(static_key);
+        }
+
+        match SphinxKeyRing::new_rotating(static_key, config.get_rotated_sphinx_keys_file()) {
+            Ok(key_ring) => key_ring,
+            Err(err) => {
+                error!("failed to load the rotated sphinx keys - {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
     fn load_node_description(config: &Config) -> NodeDescription {
         NodeDescription::load_from_file(Config::default_config_directory(Some(&config.get_id())))
             .unwrap_or_default()
@@ -125,7 +184,9 @@ impl MixNode {
     fn start_http_api(
         &self,
         atomic_verloc_result: AtomicVerlocResult,
+        verloc_history: VerlocHistory,
         node_stats_pointer: SharedNodeStats,
+        reload_requester: ReloadRequester,
     ) {
         info!("Starting HTTP API on http://localhost:8000");
 
@@ -135,17 +196,43 @@ impl MixNode {
         config.address = self.config.get_listening_address();
         config.port = self.config.get_http_api_port();
 
-        let verloc_state = VerlocState::new(atomic_verloc_result);
+        let verloc_state = VerlocState::new(atomic_verloc_result, verloc_history);
+        let sphinx_keys_state = SphinxKeysState::new(
+            self.sphinx_key_ring.clone(),
+            Arc::clone(&self.identity_keypair),
+        );
         let descriptor = self.descriptor.clone();
+        let metrics_state = self.metrics.clone();
+        let maintenance_state =
+            MaintenanceState::new(self.maintenance.clone(), Arc::clone(&self.identity_keypair));
 
         tokio::spawn(async move {
             rocket::build()
                 .configure(config)
-                .mount("/", routes![verlocRoute, description, stats, hardware])
+                .mount(
+                    "/",
+                    routes![
+                        verlocRoute,
+                        verloc_history,
+                        verloc_reports,
+                        description,
+                        stats,
+                        hardware,
+                        sphinx_keys,
+                        metricsRoute,
+                        maintenance_status,
+                        drain,
+                        reload_config
+                    ],
+                )
                 .register("/", catchers![not_found])
                 .manage(verloc_state)
+                .manage(sphinx_keys_state)
                 .manage(descriptor)
                 .manage(node_stats_pointer)
+                .manage(metrics_state)
+                .manage(maintenance_state)
+                .manage(reload_requester)
                 .launch()
                 .await
         });
@@ -171,21 +258,102 @@ impl MixNode {
         &self,
         node_stats_update_sender: node_statistics::UpdateSender,
         delay_forwarding_channel: PacketDelayForwardSender,
+        pending_loops: PendingLoops,
+        network_view: Option<NetworkView>,
         shutdown: ShutdownListener,
-    ) {
+    ) -> AdmissionControl {
         info!("Starting socket listener...");
 
-        let packet_processor =
-            PacketProcessor::new(self.sphinx_keypair.private_key(), node_stats_update_sender);
+        let mut packet_processor = PacketProcessor::new(
+            self.sphinx_key_ring.clone(),
+            node_stats_update_sender.clone(),
+            self.metrics.clone(),
+        );
+
+        let processing_threads = self.config.get_sphinx_processing_threads();
+        if processing_threads > 0 {
+            packet_processor = match packet_processor.with_processing_pool(
+                processing_threads,
+                self.config.get_sphinx_processing_batch_size(),
+            ) {
+                Ok(packet_processor) => packet_processor,
+                Err(err) => {
+                    error!("failed to create the sphinx processing pool - {}", err);
+                    process::exit(1)
+                }
+            };
+            info!(
+                "Sphinx packets are going to be processed by {} dedicated threads",
+                processing_threads
+            );
+        }
+
+        let mut connection_handler = ConnectionHandler::new(
+            packet_processor,
+            delay_forwarding_channel,
+            Arc::clone(&self.identity_keypair),
+            self.link_peers.clone(),
+            self.config.get_inbound_link_policy(),
+            pending_loops,
+            node_stats_update_sender.clone(),
+            self.config.get_inbound_connection_idle_timeout(),
+        );
+        if self.config.get_enforce_layer_routing() {
+            if let Some(network_view) = &network_view {
+                connection_handler =
+                    connection_handler.with_layer_routing_enforcement(network_view.clone());
+            }
+        }
 
-        let connection_handler = ConnectionHandler::new(packet_processor, delay_forwarding_channel);
+        let allowed_peers = network_view.filter(|_| self.config.get_only_accept_known_peers());
+        let admission_control =
+            AdmissionControl::new(connection_limits(&self.config), allowed_peers);
 
         let listening_address = SocketAddr::new(
             self.config.get_listening_address(),
             self.config.get_mix_port(),
         );
 
-        Listener::new(listening_address, shutdown).start(connection_handler);
+        Listener::new(
+            listening_address,
+            admission_control.clone(),
+            node_stats_update_sender,
+            self.maintenance.clone(),
+            shutdown,
+        )
+        .start(connection_handler);
+        admission_control
+    }
+
+    fn start_network_view_refresher(&self, shutdown: ShutdownListener) -> NetworkView {
+        info!("Starting network view refresher...");
+        let network_view = NetworkView::default();
+        let mut refresher = NetworkViewRefresher::new(
+            *self.identity_keypair.public_key(),
+            self.validator_api.clone(),
+            network_view.clone(),
+            DEFAULT_NETWORK_VIEW_REFRESH_INTERVAL,
+        );
+        tokio::spawn(async move { refresher.run(shutdown).await });
+        network_view
+    }
+
+    fn start_link_peers_refresher(&self, shutdown: ShutdownListener) {
+        info!("Starting link peers refresher...");
+        let mut refresher = LinkPeersRefresher::new(
+            self.validator_api.clone(),
+            self.link_peers.clone(),
+            DEFAULT_LINK_PEERS_REFRESH_INTERVAL,
+        );
+        tokio::spawn(async move { refresher.run(shutdown).await });
+    }
+
+    fn start_sphinx_key_rotator(&self, shutdown: ShutdownListener) {
+        if !self.sphinx_key_ring.rotation_enabled() {
+            return;
+        }
+        info!("Starting sphinx key rotator...");
+        SphinxKeyRotator::new(self.sphinx_key_ring.clone(), shutdown).start();
     }
 
     fn start_packet_delay_forwarder(
@@ -203,9 +371,26 @@ impl MixNode {
             self.config.get_use_legacy_sphinx_framing(),
         );
 
+        let mut mixnet_client = mixnet_client::Client::new(client_config);
+        if self.config.get_use_encrypted_links() {
+            mixnet_client = mixnet_client.with_link_encryption(LinkConfig::new(
+                Arc::clone(&self.identity_keypair),
+                self.link_peers.clone(),
+                !self.config.get_require_encrypted_links(),
+            ));
+        }
+
+        let limits = DelayQueueLimits {
+            maximum_packets: self.config.get_maximum_delay_queue_packets(),
+            maximum_bytes: self.config.get_maximum_delay_queue_bytes(),
+            overflow_policy: self.config.get_delay_queue_overflow_policy(),
+        };
+
         let mut packet_forwarder = DelayForwarder::new(
-            mixnet_client::Client::new(client_config),
+            mixnet_client,
+            limits,
             node_stats_update_sender,
+            self.metrics.clone(),
             shutdown,
         );
 
@@ -215,7 +400,39 @@ impl MixNode {
         packet_sender
     }
 
-    fn start_verloc_measurements(&self, shutdown: ShutdownListener) -> AtomicVerlocResult {
+    fn start_loop_cover_traffic_stream(
+        &self,
+        node_stats_update_sender: node_statistics::UpdateSender,
+        delay_forwarding_channel: PacketDelayForwardSender,
+        shutdown: ShutdownListener,
+    ) -> PendingLoops {
+        info!("Starting loop cover traffic stream...");
+
+        let config = LoopCoverConfig {
+            average_loop_delay: self.config.get_loop_cover_traffic_average_delay(),
+            average_packet_delay: self.config.get_loop_cover_packet_average_delay(),
+            loop_timeout: self.config.get_loop_cover_packet_timeout(),
+        };
+
+        let mut stream = LoopCoverTrafficStream::new(
+            config,
+            *self.identity_keypair.public_key(),
+            self.validator_api.clone(),
+            delay_forwarding_channel,
+            node_stats_update_sender,
+            self.maintenance.clone(),
+            shutdown,
+        );
+        let pending_loops = stream.pending_loops();
+
+        tokio::spawn(async move { stream.run().await });
+        pending_loops
+    }
+
+    fn start_verloc_measurements(
+        &self,
+        shutdown: ShutdownListener,
+    ) -> (AtomicVerlocResult, VerlocHistory) {
         info!("Starting the round-trip-time measurer...");
 
         // this is a sanity check to make sure we didn't mess up with the minimum version at some point
@@ -245,30 +462,24 @@ impl MixNode {
             .tested_nodes_batch_size(self.config.get_measurement_tested_nodes_batch_size())
             .testing_interval(self.config.get_measurement_testing_interval())
             .retry_timeout(self.config.get_measurement_retry_timeout())
-            .validator_api_urls(self.config.get_validator_api_endpoints())
+            .validator_api(self.validator_api.clone())
+            .history_length(self.config.get_measurement_history_length())
+            .history_file(self.config.get_verloc_history_file())
             .build();
 
         let mut verloc_measurer =
             VerlocMeasurer::new(config, Arc::clone(&self.identity_keypair), shutdown);
         let atomic_verloc_results = verloc_measurer.get_verloc_results_pointer();
+        let verloc_history = verloc_measurer.get_verloc_history_pointer();
         tokio::spawn(async move { verloc_measurer.run().await });
-        atomic_verloc_results
-    }
-
-    fn random_api_client(&self) -> validator_client::ApiClient {
-        let endpoints = self.config.get_validator_api_endpoints();
-        let validator_api = endpoints
-            .choose(&mut thread_rng())
-            .expect("The list of validator apis is empty");
-
-        validator_client::ApiClient::new(validator_api.clone())
+        (atomic_verloc_results, verloc_history)
     }
 
     // TODO: ask DH whether this function still makes sense in ^0.10
     async fn check_if_same_ip_node_exists(&mut self) -> Option<String> {
         // TODO: if anything, this should be getting data directly from the contract
         // as opposed to the validator API
-        let validator_client = self.random_api_client();
+        let validator_client = self.validator_api.random_client();
         let existing_nodes = match validator_client.get_cached_mixnodes().await {
             Ok(nodes) => nodes,
             Err(err) => {
@@ -288,8 +499,87 @@ impl MixNode {
             .map(|node| node.bond_information.mix_node.identity_key.clone())
     }
 
-    async fn wait_for_interrupt(&self, mut shutdown: ShutdownNotifier) {
-        wait_for_signal().await;
+    /// Waits for all the packets that are still in the delay queue to get forwarded,
+    /// for at most the configured drain timeout.
+    async fn drain(&self, delay_forwarding_channel: &PacketDelayForwardSender) {
+        let queued_packets = delay_forwarding_channel.queued_packets();
+        info!(
+            "Draining the delay queue of {} packets before shutting down...",
+            queued_packets
+        );
+
+        let drain_timeout = tokio::time::sleep(self.config.get_maintenance_drain_timeout());
+        tokio::pin!(drain_timeout);
+
+        while delay_forwarding_channel.queued_packets() > 0 {
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+                _ = &mut drain_timeout => {
+                    warn!(
+                        "Failed to drain the delay queue in time - {} packets are going to be dropped",
+                        delay_forwarding_channel.queued_packets()
+                    );
+                    return;
+                }
+                _ = wait_for_signal() => {
+                    warn!("Received a signal while draining - not waiting for the delay queue anymore");
+                    return;
+                }
+            }
+        }
+        info!("The delay queue got drained");
+    }
+
+    /// Re-reads the configuration file and applies all the changes, as long as none of them
+    /// requires restarting the node.
+    fn reload_config(&mut self, admission_control: &AdmissionControl) -> ReloadResult {
+        let updated =
+            Config::load_from_file(Some(&self.config.get_id())).map_err(ReloadError::Load)?;
+        let changes = self.config.check_live_reload(&updated)?;
+
+        if self.config.get_logging_filters() != updated.get_logging_filters() {
+            logging::set_logging_filters(updated.get_logging_filters());
+        }
+        self.validator_api
+            .update(updated.get_validator_api_endpoints());
+        admission_control.update_limits(connection_limits(&updated));
+
+        self.config = updated;
+        Ok(changes)
+    }
+
+    async fn wait_for_interrupt(
+        &mut self,
+        mut shutdown: ShutdownNotifier,
+        delay_forwarding_channel: PacketDelayForwardSender,
+        admission_control: AdmissionControl,
+        mut reload_requests: ReloadRequestReceiver,
+    ) {
+        let mut maintenance = self.maintenance.clone();
+        loop {
+            tokio::select! {
+                _ = wait_for_signal() => break,
+                _ = maintenance.wait_for_drain() => {
+                    self.drain(&delay_forwarding_channel).await;
+                    break;
+                }
+                Some(result_sender) = reload_requests.recv() => {
+                    let result = self.reload_config(&admission_control);
+                    match &result {
+                        Ok(changes) if changes.is_empty() => {
+                            info!("The configuration file has not changed")
+                        }
+                        Ok(changes) => {
+                            info!("Applied the changes of: {}", changes.join(", "))
+                        }
+                        Err(err) => warn!("Rejected the configuration reload - {}", err),
+                    }
+                    // the requester might have gone away in the meantime,
+                    // but the changes are applied regardless
+                    let _ = result_sender.send(result);
+                }
+            }
+        }
 
         log::info!("Sending shutdown");
         shutdown.signal_shutdown().ok();
@@ -315,25 +605,66 @@ impl MixNode {
             }
         }
 
+        // the filters from the config take precedence over `RUST_LOG`
+        if let Some(filters) = self.config.get_logging_filters() {
+            logging::set_logging_filters(Some(filters));
+        }
+
         let shutdown = ShutdownNotifier::default();
 
+        self.start_sphinx_key_rotator(shutdown.subscribe());
+        self.start_link_peers_refresher(shutdown.subscribe());
         let (node_stats_pointer, node_stats_update_sender) =
             self.start_node_stats_controller(shutdown.subscribe());
         let delay_forwarding_channel = self
             .start_packet_delay_forwarder(node_stats_update_sender.clone(), shutdown.subscribe());
-        self.start_socket_listener(
+        let pending_loops = if self.config.get_disable_loop_cover_traffic() {
+            PendingLoops::default()
+        } else {
+            self.start_loop_cover_traffic_stream(
+                node_stats_update_sender.clone(),
+                delay_forwarding_channel.clone(),
+                shutdown.subscribe(),
+            )
+        };
+        let network_view = if self.config.get_only_accept_known_peers()
+            || self.config.get_enforce_layer_routing()
+        {
+            Some(self.start_network_view_refresher(shutdown.subscribe()))
+        } else {
+            None
+        };
+        let admission_control = self.start_socket_listener(
             node_stats_update_sender,
-            delay_forwarding_channel,
+            delay_forwarding_channel.clone(),
+            pending_loops,
+            network_view,
             shutdown.subscribe(),
         );
-        let atomic_verloc_results = self.start_verloc_measurements(shutdown.subscribe());
+        let (atomic_verloc_results, verloc_history) =
+            self.start_verloc_measurements(shutdown.subscribe());
 
         // Rocket handles shutdown on it's own, but its shutdown handling should be incorporated
         // with that of the rest of the tasks.
         // Currently it's runtime is forcefully terminated once the mixnode exits.
-        self.start_http_api(atomic_verloc_results, node_stats_pointer);
+        let (reload_requester, reload_requests) = ReloadRequester::new();
+        self.start_http_api(
+            atomic_verloc_results,
+            verloc_history,
+            node_stats_pointer,
+            reload_requester.clone(),
+        );
+
+        start_drain_signal_listener(self.maintenance.clone());
+        start_reload_signal_listener(reload_requester);
 
         info!("Finished nym mixnode startup procedure - it should now be able to receive mix traffic!");
-        self.wait_for_interrupt(shutdown).await
+        self.wait_for_interrupt(
+            shutdown,
+            delay_forwarding_channel,
+            admission_control,
+            reload_requests,
+        )
+        .await
     }
 }
@@ -0,0 +1,356 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::asymmetric::identity;
+use log::*;
+use mixnode_common::validator_api::ValidatorApiEndpoints;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use task::ShutdownListener;
+use tokio::time::sleep;
+use topology::{nym_topology_from_detailed, MixLayer, NymTopology};
+
+/// Default delay between subsequent refreshes of the network view.
+pub(crate) const DEFAULT_NETWORK_VIEW_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Layer whose nodes are expected to be sending packets to nodes on the provided layer.
+///
+/// Note that for layer 1 it's layer 3 rather than nothing, as this is where our own
+/// loop cover packets are going to be coming back from.
+fn previous_layer(layer: MixLayer) -> MixLayer {
+    (layer + 1) % 3 + 1
+}
+
+/// Layer whose nodes are expected to be receiving packets from nodes on the provided layer.
+///
+/// Note that for layer 3 it's layer 1 rather than nothing (apart from the gateways),
+/// as this is where the loop cover packets of the layer 1 and 2 nodes are going through.
+fn next_layer(layer: MixLayer) -> MixLayer {
+    layer % 3 + 1
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct KnownNodes {
+    own_layer: Option<MixLayer>,
+    mixes: HashMap<MixLayer, HashSet<SocketAddr>>,
+    gateways: HashSet<SocketAddr>,
+}
+
+fn contains_ip(addresses: &HashSet<SocketAddr>, ip: IpAddr) -> bool {
+    addresses.iter().any(|address| address.ip() == ip)
+}
+
+impl KnownNodes {
+    fn new(topology: &NymTopology, own_identity: &identity::PublicKey) -> Self {
+        let own_layer = topology.mixes().iter().find_map(|(layer, mixes)| {
+            mixes
+                .iter()
+                .any(|mix| &mix.identity_key == own_identity)
+                .then(|| *layer)
+        });
+
+        let mixes = topology
+            .mixes()
+            .iter()
+            .map(|(layer, mixes)| (*layer, mixes.iter().map(|mix| mix.mix_host).collect()))
+            .collect();
+
+        let gateways = topology
+            .gateways()
+            .iter()
+            .map(|gateway| gateway.mix_host)
+            .collect();
+
+        KnownNodes {
+            own_layer,
+            mixes,
+            gateways,
+        }
+    }
+
+    fn is_allowed_inbound(&self, ip: IpAddr) -> bool {
+        if contains_ip(&self.gateways, ip) {
+            return true;
+        }
+
+        match self.own_layer {
+            Some(layer) => self
+                .mixes
+                .get(&previous_layer(layer))
+                .map(|mixes| contains_ip(mixes, ip))
+                .unwrap_or_default(),
+            // if we're not part of the active set, we don't know which layer we should expect
+            // the traffic from, so accept it from any node
+            None => self.mixes.values().any(|mixes| contains_ip(mixes, ip)),
+        }
+    }
+
+    fn is_valid_next_hop(&self, next_hop: SocketAddr) -> bool {
+        match self.own_layer {
+            Some(layer) => {
+                let on_next_layer = self
+                    .mixes
+                    .get(&next_layer(layer))
+                    .map(|mixes| mixes.contains(&next_hop))
+                    .unwrap_or_default();
+                on_next_layer || (layer == 3 && self.gateways.contains(&next_hop))
+            }
+            // if we're not part of the active set, we can't tell which layer the packet
+            // should be going to, but it should at least be going to some node
+            None => {
+                self.gateways.contains(&next_hop)
+                    || self.mixes.values().any(|mixes| mixes.contains(&next_hop))
+            }
+        }
+    }
+}
+
+/// The current topology alongside the one preceding its last change.
+#[derive(Debug, Default)]
+struct TopologySnapshots {
+    current: Option<KnownNodes>,
+    previous: Option<KnownNodes>,
+}
+
+impl TopologySnapshots {
+    /// Checks the condition against both snapshots, so that the nodes that have just changed
+    /// their layer (or left the network) are still tolerated for a while after the change,
+    /// as the packets sent before it might still be on their way.
+    /// If we haven't yet managed to obtain the network topology, the condition is assumed to hold.
+    fn any(&self, condition: impl Fn(&KnownNodes) -> bool) -> bool {
+        match &self.current {
+            Some(current) => {
+                condition(current) || self.previous.as_ref().map(condition).unwrap_or_default()
+            }
+            None => true,
+        }
+    }
+}
+
+/// View of the network, from the point of view of this node, that is used for deciding
+/// which nodes we should be exchanging packets with.
+///
+/// Note that cloning it produces a handle to the same underlying data.
+#[derive(Clone, Default)]
+pub(crate) struct NetworkView {
+    inner: Arc<RwLock<TopologySnapshots>>,
+}
+
+impl NetworkView {
+    fn set_known_nodes(&self, known_nodes: KnownNodes) {
+        let mut snapshots = self.inner.write().expect("network view lock got poisoned");
+        // only replace the previous snapshot if the topology has actually changed,
+        // otherwise it would be gone after a single refresh
+        if snapshots.current.as_ref() != Some(&known_nodes) {
+            snapshots.previous = snapshots.current.replace(known_nodes);
+        }
+    }
+
+    fn update(&self, topology: &NymTopology, own_identity: &identity::PublicKey) {
+        self.set_known_nodes(KnownNodes::new(topology, own_identity))
+    }
+
+    /// Checks whether we should be accepting connections from the provided address, i.e. whether
+    /// it belongs to either a gateway or a mixnode on the previous layer in either the current
+    /// or the preceding topology.
+    /// If we haven't yet managed to obtain the network topology, all connections are allowed.
+    pub(crate) fn is_allowed_inbound(&self, ip: IpAddr) -> bool {
+        self.inner
+            .read()
+            .expect("network view lock got poisoned")
+            .any(|known_nodes| known_nodes.is_allowed_inbound(ip))
+    }
+
+    /// Checks whether the packet should be forwarded to the provided address, i.e. whether it
+    /// belongs to a mixnode on the next layer or, if we're on the last layer, to a gateway
+    /// in either the current or the preceding topology.
+    /// If we haven't yet managed to obtain the network topology, all addresses are considered valid.
+    pub(crate) fn is_valid_next_hop(&self, next_hop: SocketAddr) -> bool {
+        self.inner
+            .read()
+            .expect("network view lock got poisoned")
+            .any(|known_nodes| known_nodes.is_valid_next_hop(next_hop))
+    }
+}
+
+/// Periodically updates the network view with the current topology.
+pub(crate) struct NetworkViewRefresher {
+    identity: identity::PublicKey,
+    validator_api: ValidatorApiEndpoints,
+    view: NetworkView,
+    refresh_interval: Duration,
+}
+
+impl NetworkViewRefresher {
+    pub(crate) fn new(
+        identity: identity::PublicKey,
+        validator_api: ValidatorApiEndpoints,
+        view: NetworkView,
+        refresh_interval: Duration,
+    ) -> Self {
+        NetworkViewRefresher {
+            identity,
+            validator_api,
+            view,
+            refresh_interval,
+        }
+    }
+
+    async fn refresh(&self) {
+        let validator_client = self.validator_api.random_client();
+        let mixnodes = match validator_client.get_cached_active_mixnodes().await {
+            Ok(mixnodes) => mixnodes,
+            Err(err) => {
+                warn!(
+                    "failed to obtain list of active mixnodes for the network view - {}",
+                    err
+                );
+                return;
+            }
+        };
+        let gateways = match validator_client.get_cached_gateways().await {
+            Ok(gateways) => gateways,
+            Err(err) => {
+                warn!(
+                    "failed to obtain list of gateways for the network view - {}",
+                    err
+                );
+                return;
+            }
+        };
+
+        let topology = nym_topology_from_detailed(mixnodes, gateways);
+        self.view.update(&topology, &self.identity);
+    }
+
+    pub(crate) async fn run(&mut self, mut shutdown: ShutdownListener) {
+        debug!("Started NetworkViewRefresher with graceful shutdown support");
+        while !shutdown.is_shutdown() {
+            self.refresh().await;
+
+            tokio::select! {
+                _ = sleep(self.refresh_interval) => {},
+                _ = shutdown.recv() => {
+                    trace!("NetworkViewRefresher: Received shutdown");
+                }
+            }
+        }
+        trace!("NetworkViewRefresher: Exiting");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(last_octet: u8) -> IpAddr {
+        IpAddr::from([10, 0, 0, last_octet])
+    }
+
+    fn address(last_octet: u8) -> SocketAddr {
+        SocketAddr::new(ip(last_octet), 1789)
+    }
+
+    fn known_nodes(own_layer: Option<MixLayer>) -> KnownNodes {
+        KnownNodes {
+            own_layer,
+            mixes: [
+                (1, [address(1)].into_iter().collect()),
+                (2, [address(2)].into_iter().collect()),
+                (3, [address(3)].into_iter().collect()),
+            ]
+            .into_iter()
+            .collect(),
+            gateways: [address(4)].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn only_previous_layer_and_gateways_are_allowed_inbound() {
+        let layer2 = known_nodes(Some(2));
+        assert!(layer2.is_allowed_inbound(ip(1)));
+        assert!(!layer2.is_allowed_inbound(ip(2)));
+        assert!(!layer2.is_allowed_inbound(ip(3)));
+        assert!(layer2.is_allowed_inbound(ip(4)));
+        assert!(!layer2.is_allowed_inbound(ip(5)));
+
+        // layer 1 has to also accept our loop cover packets returning from layer 3
+        let layer1 = known_nodes(Some(1));
+        assert!(!layer1.is_allowed_inbound(ip(1)));
+        assert!(!layer1.is_allowed_inbound(ip(2)));
+        assert!(layer1.is_allowed_inbound(ip(3)));
+        assert!(layer1.is_allowed_inbound(ip(4)));
+
+        let inactive = known_nodes(None);
+        assert!((1..=4).all(|i| inactive.is_allowed_inbound(ip(i))));
+        assert!(!inactive.is_allowed_inbound(ip(5)));
+    }
+
+    #[test]
+    fn only_next_layer_is_valid_next_hop() {
+        let layer1 = known_nodes(Some(1));
+        assert!(!layer1.is_valid_next_hop(address(1)));
+        assert!(layer1.is_valid_next_hop(address(2)));
+        assert!(!layer1.is_valid_next_hop(address(3)));
+        assert!(!layer1.is_valid_next_hop(address(4)));
+
+        // layer 3 forwards to gateways, but also to layer 1 for the loop cover packets
+        let layer3 = known_nodes(Some(3));
+        assert!(layer3.is_valid_next_hop(address(1)));
+        assert!(!layer3.is_valid_next_hop(address(2)));
+        assert!(!layer3.is_valid_next_hop(address(3)));
+        assert!(layer3.is_valid_next_hop(address(4)));
+
+        // the port has to match as well, we're not a generic relay
+        assert!(!layer3.is_valid_next_hop(SocketAddr::new(ip(4), 22)));
+
+        let inactive = known_nodes(None);
+        assert!((1..=4).all(|i| inactive.is_valid_next_hop(address(i))));
+        assert!(!inactive.is_valid_next_hop(address(5)));
+    }
+
+    #[test]
+    fn everything_is_allowed_before_the_first_refresh() {
+        let view = NetworkView::default();
+        assert!(view.is_allowed_inbound(ip(5)));
+        assert!(view.is_valid_next_hop(address(5)));
+
+        view.set_known_nodes(known_nodes(Some(2)));
+        assert!(!view.is_allowed_inbound(ip(5)));
+        assert!(!view.is_valid_next_hop(address(5)));
+    }
+
+    #[test]
+    fn previous_topology_is_tolerated_after_a_change() {
+        let view = NetworkView::default();
+        view.set_known_nodes(known_nodes(Some(2)));
+
+        // the node on layer 1 has moved to layer 3 and a new node has joined layer 1
+        let mut changed = known_nodes(Some(2));
+        changed.mixes.get_mut(&1).unwrap().remove(&address(1));
+        changed.mixes.get_mut(&1).unwrap().insert(address(5));
+        changed.mixes.get_mut(&3).unwrap().insert(address(1));
+        view.set_known_nodes(changed);
+
+        assert!(view.is_allowed_inbound(ip(1)));
+        assert!(view.is_allowed_inbound(ip(5)));
+
+        // refreshing with the same topology keeps the previous one around
+        let mut unchanged = known_nodes(Some(2));
+        unchanged.mixes.get_mut(&1).unwrap().remove(&address(1));
+        unchanged.mixes.get_mut(&1).unwrap().insert(address(5));
+        unchanged.mixes.get_mut(&3).unwrap().insert(address(1));
+        view.set_known_nodes(unchanged);
+        assert!(view.is_allowed_inbound(ip(1)));
+
+        // but it's forgotten after the next change
+        view.set_known_nodes(known_nodes(Some(1)));
+        assert!(view.is_valid_next_hop(address(2)));
+        assert!(!view.is_allowed_inbound(ip(2)));
+        view.set_known_nodes(known_nodes(Some(2)));
+        assert!(!view.is_allowed_inbound(ip(5)));
+        assert!(view.is_valid_next_hop(address(3)));
+    }
+}
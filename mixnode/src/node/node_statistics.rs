@@ -1,6 +1,7 @@
 use futures::channel::mpsc;
 use futures::lock::Mutex;
 use futures::StreamExt;
+use mixnode_common::packet_processor::error::MixProcessingError;
 use serde::Serialize;
 use std::collections::HashMap;
 use std::ops::DerefMut;
@@ -9,6 +10,8 @@ use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use tokio::sync::{RwLock, RwLockReadGuard};
 
+use super::listener::admission::RejectionReason;
+use super::metrics::{MetricType, MetricsEncoder};
 use super::ShutdownListener;
 
 // convenience aliases
@@ -31,18 +34,36 @@ impl SharedNodeStats {
                 packets_received_since_startup: 0,
                 packets_sent_since_startup: HashMap::new(),
                 packets_explicitly_dropped_since_startup: HashMap::new(),
+                packets_replayed_since_startup: 0,
                 packets_received_since_last_update: 0,
                 packets_sent_since_last_update: HashMap::new(),
                 packets_explicitly_dropped_since_last_update: HashMap::new(),
+                packets_replayed_since_last_update: 0,
+                packets_dropped_by_reason_since_startup: HashMap::new(),
+                packets_dropped_by_reason_since_last_update: HashMap::new(),
+                loop_cover_packets_sent_since_startup: 0,
+                loop_cover_packets_returned_since_startup: 0,
+                loop_cover_packets_lost_since_startup: HashMap::new(),
+                loop_cover_packets_sent_since_last_update: 0,
+                loop_cover_packets_returned_since_last_update: 0,
+                loop_cover_packets_lost_since_last_update: HashMap::new(),
+                loop_cover_average_rtt_since_last_update: None,
+                connections_rejected_since_startup: HashMap::new(),
+                connections_rejected_since_last_update: HashMap::new(),
             })),
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn update(
         &self,
         new_received: u64,
         new_sent: PacketsMap,
         new_dropped: PacketsMap,
+        new_replayed: u64,
+        new_dropped_by_reason: PacketsMap,
+        new_loop_cover: LoopCoverUpdate,
+        new_connections_rejected: PacketsMap,
     ) {
         let mut guard = self.inner.write().await;
         let snapshot_time = SystemTime::now();
@@ -51,6 +72,7 @@ impl SharedNodeStats {
         guard.update_time = snapshot_time;
 
         guard.packets_received_since_startup += new_received;
+        guard.packets_replayed_since_startup += new_replayed;
         for (mix, count) in &new_sent {
             *guard
                 .packets_sent_since_startup
@@ -60,14 +82,45 @@ impl SharedNodeStats {
 
         for (mix, count) in &new_dropped {
             *guard
-                .packets_explicitly_dropped_since_last_update
+                .packets_explicitly_dropped_since_startup
                 .entry(mix.clone())
                 .or_insert(0) += *count;
         }
 
+        for (reason, count) in &new_dropped_by_reason {
+            *guard
+                .packets_dropped_by_reason_since_startup
+                .entry(reason.clone())
+                .or_insert(0) += *count;
+        }
+
         guard.packets_received_since_last_update = new_received;
         guard.packets_sent_since_last_update = new_sent;
         guard.packets_explicitly_dropped_since_last_update = new_dropped;
+        guard.packets_replayed_since_last_update = new_replayed;
+        guard.packets_dropped_by_reason_since_last_update = new_dropped_by_reason;
+
+        guard.loop_cover_packets_sent_since_startup += new_loop_cover.sent;
+        guard.loop_cover_packets_returned_since_startup += new_loop_cover.returned;
+        for (mix, count) in &new_loop_cover.lost {
+            *guard
+                .loop_cover_packets_lost_since_startup
+                .entry(mix.clone())
+                .or_insert(0) += *count;
+        }
+
+        guard.loop_cover_average_rtt_since_last_update = new_loop_cover.average_rtt();
+        guard.loop_cover_packets_sent_since_last_update = new_loop_cover.sent;
+        guard.loop_cover_packets_returned_since_last_update = new_loop_cover.returned;
+        guard.loop_cover_packets_lost_since_last_update = new_loop_cover.lost;
+
+        for (reason, count) in &new_connections_rejected {
+            *guard
+                .connections_rejected_since_startup
+                .entry(reason.clone())
+                .or_insert(0) += *count;
+        }
+        guard.connections_rejected_since_last_update = new_connections_rejected;
     }
 
     pub(crate) async fn clone_data(&self) -> NodeStats {
@@ -95,6 +148,9 @@ pub(crate) struct NodeStats {
     // we know for sure we dropped packets to those destinations
     packets_explicitly_dropped_since_startup: PacketsMap,
 
+    // packets rejected since we have already processed them before
+    packets_replayed_since_startup: u64,
+
     packets_received_since_last_update: u64,
 
     // note: sent does not imply forwarded. We don't know if it was delivered successfully
@@ -102,9 +158,119 @@ pub(crate) struct NodeStats {
 
     // we know for sure we dropped packets to those destinations
     packets_explicitly_dropped_since_last_update: PacketsMap,
+
+    // packets rejected since we have already processed them before
+    packets_replayed_since_last_update: u64,
+
+    // all packets we have dropped, by the reason of dropping them
+    packets_dropped_by_reason_since_startup: PacketsMap,
+
+    packets_dropped_by_reason_since_last_update: PacketsMap,
+
+    // our own loop cover packets sent through the network and back to us
+    loop_cover_packets_sent_since_startup: u64,
+
+    loop_cover_packets_returned_since_startup: u64,
+
+    // loop cover packets that have not returned in time, by the first hop they were sent to
+    loop_cover_packets_lost_since_startup: PacketsMap,
+
+    loop_cover_packets_sent_since_last_update: u64,
+
+    loop_cover_packets_returned_since_last_update: u64,
+
+    // loop cover packets that have not returned in time, by the first hop they were sent to
+    loop_cover_packets_lost_since_last_update: PacketsMap,
+
+    #[serde(serialize_with = "humantime_serde::serialize")]
+    loop_cover_average_rtt_since_last_update: Option<Duration>,
+
+    // inbound connections we have refused to handle, by the reason of rejecting them
+    connections_rejected_since_startup: PacketsMap,
+
+    connections_rejected_since_last_update: PacketsMap,
 }
 
 impl NodeStats {
+    pub(crate) fn encode_metrics(&self, encoder: &mut MetricsEncoder) {
+        encoder.describe(
+            "packets_received_total",
+            MetricType::Counter,
+            "Number of sphinx packets received by the node.",
+        );
+        encoder.sample(
+            "packets_received_total",
+            &[],
+            self.packets_received_since_startup,
+        );
+
+        encoder.describe(
+            "packets_sent_total",
+            MetricType::Counter,
+            "Number of sphinx packets sent by the node, by their destination.",
+        );
+        for (destination, count) in &self.packets_sent_since_startup {
+            encoder.sample("packets_sent_total", &[("destination", destination)], count);
+        }
+
+        encoder.describe(
+            "packets_forward_dropped_total",
+            MetricType::Counter,
+            "Number of sphinx packets dropped due to their destination's sending queue being full.",
+        );
+        for (destination, count) in &self.packets_explicitly_dropped_since_startup {
+            encoder.sample(
+                "packets_forward_dropped_total",
+                &[("destination", destination)],
+                count,
+            );
+        }
+
+        encoder.describe(
+            "loop_cover_packets_sent_total",
+            MetricType::Counter,
+            "Number of loop cover packets sent by the node.",
+        );
+        encoder.sample(
+            "loop_cover_packets_sent_total",
+            &[],
+            self.loop_cover_packets_sent_since_startup,
+        );
+
+        encoder.describe(
+            "loop_cover_packets_returned_total",
+            MetricType::Counter,
+            "Number of loop cover packets that returned to the node.",
+        );
+        encoder.sample(
+            "loop_cover_packets_returned_total",
+            &[],
+            self.loop_cover_packets_returned_since_startup,
+        );
+
+        encoder.describe(
+            "loop_cover_packets_lost_total",
+            MetricType::Counter,
+            "Number of loop cover packets that failed to return in time, by their first hop.",
+        );
+        for (first_hop, count) in &self.loop_cover_packets_lost_since_startup {
+            encoder.sample(
+                "loop_cover_packets_lost_total",
+                &[("first_hop", first_hop)],
+                count,
+            );
+        }
+
+        encoder.describe(
+            "connections_rejected_total",
+            MetricType::Counter,
+            "Number of inbound connections rejected by the node, by the reason of rejecting them.",
+        );
+        for (reason, count) in &self.connections_rejected_since_startup {
+            encoder.sample("connections_rejected_total", &[("reason", reason)], count);
+        }
+    }
+
     pub(crate) fn simplify(&self) -> NodeStatsSimple {
         NodeStatsSimple {
             update_time: self.update_time,
@@ -115,12 +281,40 @@ impl NodeStats {
                 .packets_explicitly_dropped_since_startup
                 .values()
                 .sum(),
+            packets_replayed_since_startup: self.packets_replayed_since_startup,
             packets_received_since_last_update: self.packets_received_since_last_update,
             packets_sent_since_last_update: self.packets_sent_since_last_update.values().sum(),
             packets_explicitly_dropped_since_last_update: self
                 .packets_explicitly_dropped_since_last_update
                 .values()
                 .sum(),
+            packets_replayed_since_last_update: self.packets_replayed_since_last_update,
+            packets_dropped_by_reason_since_startup: self
+                .packets_dropped_by_reason_since_startup
+                .clone(),
+            packets_dropped_by_reason_since_last_update: self
+                .packets_dropped_by_reason_since_last_update
+                .clone(),
+            loop_cover_packets_sent_since_startup: self.loop_cover_packets_sent_since_startup,
+            loop_cover_packets_returned_since_startup: self
+                .loop_cover_packets_returned_since_startup,
+            loop_cover_packets_lost_since_startup: self
+                .loop_cover_packets_lost_since_startup
+                .values()
+                .sum(),
+            loop_cover_packets_sent_since_last_update: self
+                .loop_cover_packets_sent_since_last_update,
+            loop_cover_packets_returned_since_last_update: self
+                .loop_cover_packets_returned_since_last_update,
+            loop_cover_packets_lost_since_last_update: self
+                .loop_cover_packets_lost_since_last_update
+                .values()
+                .sum(),
+            loop_cover_average_rtt_since_last_update: self.loop_cover_average_rtt_since_last_update,
+            connections_rejected_since_startup: self.connections_rejected_since_startup.clone(),
+            connections_rejected_since_last_update: self
+                .connections_rejected_since_last_update
+                .clone(),
         }
     }
 }
@@ -141,6 +335,9 @@ pub(crate) struct NodeStatsSimple {
     // we know for sure we dropped those packets
     packets_explicitly_dropped_since_startup: u64,
 
+    // packets rejected since we have already processed them before
+    packets_replayed_since_startup: u64,
+
     packets_received_since_last_update: u64,
 
     // note: sent does not imply forwarded. We don't know if it was delivered successfully
@@ -148,12 +345,164 @@ pub(crate) struct NodeStatsSimple {
 
     // we know for sure we dropped those packets
     packets_explicitly_dropped_since_last_update: u64,
+
+    // packets rejected since we have already processed them before
+    packets_replayed_since_last_update: u64,
+
+    // all packets we have dropped, by the reason of dropping them
+    packets_dropped_by_reason_since_startup: PacketsMap,
+
+    packets_dropped_by_reason_since_last_update: PacketsMap,
+
+    // our own loop cover packets sent through the network and back to us
+    loop_cover_packets_sent_since_startup: u64,
+
+    loop_cover_packets_returned_since_startup: u64,
+
+    // loop cover packets that have not returned in time
+    loop_cover_packets_lost_since_startup: u64,
+
+    loop_cover_packets_sent_since_last_update: u64,
+
+    loop_cover_packets_returned_since_last_update: u64,
+
+    // loop cover packets that have not returned in time
+    loop_cover_packets_lost_since_last_update: u64,
+
+    #[serde(serialize_with = "humantime_serde::serialize")]
+    loop_cover_average_rtt_since_last_update: Option<Duration>,
+
+    // inbound connections we have refused to handle, by the reason of rejecting them
+    connections_rejected_since_startup: PacketsMap,
+
+    connections_rejected_since_last_update: PacketsMap,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DropReason {
+    /// The packet has already been processed before.
+    Replayed,
+
+    /// The replay cache of the packet key was full, so it could not be told apart from a replay.
+    ReplayCacheFull,
+
+    /// The packet could not be unwrapped or contained invalid routing information.
+    Malformed,
+
+    /// The sending queue to the next hop was full.
+    ForwardQueueFull,
+
+    /// We were the final hop of a packet that wasn't one of our pending loop cover packets.
+    UnexpectedFinalHop,
+
+    /// The delay queue was full when the packet arrived.
+    DelayQueueFull,
+
+    /// The packet got evicted from the delay queue to make space for one with a shorter delay.
+    DelayQueueEvicted,
+
+    /// The next hop of the packet was not a node on the next layer of the network.
+    InvalidNextHop,
+
+    /// The delay forwarder could not keep up with the incoming packets.
+    ForwarderBacklogged,
+}
+
+impl DropReason {
+    pub(crate) const ALL: [DropReason; 9] = [
+        DropReason::Replayed,
+        DropReason::ReplayCacheFull,
+        DropReason::Malformed,
+        DropReason::ForwardQueueFull,
+        DropReason::UnexpectedFinalHop,
+        DropReason::DelayQueueFull,
+        DropReason::DelayQueueEvicted,
+        DropReason::InvalidNextHop,
+        DropReason::ForwarderBacklogged,
+    ];
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            DropReason::Replayed => "replayed",
+            DropReason::ReplayCacheFull => "replay_cache_full",
+            DropReason::Malformed => "malformed",
+            DropReason::ForwardQueueFull => "forward_queue_full",
+            DropReason::UnexpectedFinalHop => "unexpected_final_hop",
+            DropReason::DelayQueueFull => "delay_queue_full",
+            DropReason::DelayQueueEvicted => "delay_queue_evicted",
+            DropReason::InvalidNextHop => "invalid_next_hop",
+            DropReason::ForwarderBacklogged => "forwarder_backlogged",
+        }
+    }
+}
+
+impl From<&MixProcessingError> for DropReason {
+    fn from(err: &MixProcessingError) -> Self {
+        match err {
+            MixProcessingError::ReplayedPacket => DropReason::Replayed,
+            MixProcessingError::ReplayCacheFull => DropReason::ReplayCacheFull,
+            _ => DropReason::Malformed,
+        }
+    }
+}
+
+/// Numbers of packets dropped since the last stats update, by the reason of dropping them.
+///
+/// The drops are counted directly rather than reported through the stats channel, as they're
+/// the most frequent under a flood, when sending an event for every packet would only make
+/// the channel grow without bound.
+///
+/// Note that cloning it produces a handle to the same underlying counters.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DropCounters {
+    inner: Arc<[AtomicU64; DropReason::ALL.len()]>,
+}
+
+impl DropCounters {
+    fn increment(&self, reason: DropReason) {
+        self.inner[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the non-zero counts accumulated since the last call and resets them.
+    pub(crate) fn take(&self) -> PacketsMap {
+        DropReason::ALL
+            .iter()
+            .filter_map(|reason| {
+                let count = self.inner[*reason as usize].swap(0, Ordering::Relaxed);
+                (count > 0).then(|| (reason.as_str().to_string(), count))
+            })
+            .collect()
+    }
 }
 
 pub(crate) enum PacketEvent {
     Sent(String),
     Received,
     Dropped(String),
+    Replayed,
+    LoopCoverSent,
+    LoopCoverReturned(Duration),
+    LoopCoverLost(String),
+    ConnectionRejected(RejectionReason),
+}
+
+/// Loop cover traffic data gathered since the last stats update.
+#[derive(Debug, Default)]
+pub(crate) struct LoopCoverUpdate {
+    sent: u64,
+    returned: u64,
+    lost: PacketsMap,
+    total_rtt: Duration,
+}
+
+impl LoopCoverUpdate {
+    fn average_rtt(&self) -> Option<Duration> {
+        if self.returned == 0 {
+            None
+        } else {
+            Some(self.total_rtt / self.returned as u32)
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -164,17 +513,25 @@ struct CurrentPacketData {
 #[derive(Debug)]
 struct PacketDataInner {
     received: AtomicU64,
+    replayed: AtomicU64,
     sent: Mutex<PacketsMap>,
     dropped: Mutex<PacketsMap>,
+    dropped_by_reason: DropCounters,
+    loop_cover: Mutex<LoopCoverUpdate>,
+    connections_rejected: Mutex<PacketsMap>,
 }
 
 impl CurrentPacketData {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(dropped_by_reason: DropCounters) -> Self {
         CurrentPacketData {
             inner: Arc::new(PacketDataInner {
                 received: AtomicU64::new(0),
+                replayed: AtomicU64::new(0),
                 sent: Mutex::new(HashMap::new()),
                 dropped: Mutex::new(HashMap::new()),
+                dropped_by_reason,
+                loop_cover: Mutex::new(LoopCoverUpdate::default()),
+                connections_rejected: Mutex::new(HashMap::new()),
             }),
         }
     }
@@ -183,6 +540,10 @@ impl CurrentPacketData {
         self.inner.received.fetch_add(1, Ordering::SeqCst);
     }
 
+    fn increment_replayed(&self) {
+        self.inner.replayed.fetch_add(1, Ordering::SeqCst);
+    }
+
     async fn increment_sent(&self, destination: String) {
         let mut unlocked = self.inner.sent.lock().await;
         let receiver_count = unlocked.entry(destination).or_insert(0);
@@ -195,15 +556,48 @@ impl CurrentPacketData {
         *dropped_count += 1;
     }
 
-    async fn acquire_and_reset(&self) -> (u64, PacketsMap, PacketsMap) {
+    async fn increment_loop_cover_sent(&self) {
+        self.inner.loop_cover.lock().await.sent += 1;
+    }
+
+    async fn increment_loop_cover_returned(&self, rtt: Duration) {
+        let mut unlocked = self.inner.loop_cover.lock().await;
+        unlocked.returned += 1;
+        unlocked.total_rtt += rtt;
+    }
+
+    async fn increment_loop_cover_lost(&self, first_hop: String) {
+        let mut unlocked = self.inner.loop_cover.lock().await;
+        *unlocked.lost.entry(first_hop).or_insert(0) += 1;
+    }
+
+    async fn increment_connections_rejected(&self, reason: RejectionReason) {
+        let mut unlocked = self.inner.connections_rejected.lock().await;
+        *unlocked.entry(reason.as_str().to_string()).or_insert(0) += 1;
+    }
+
+    fn acquire_and_reset_dropped_by_reason(&self) -> PacketsMap {
+        self.inner.dropped_by_reason.take()
+    }
+
+    async fn acquire_and_reset_loop_cover(&self) -> LoopCoverUpdate {
+        std::mem::take(self.inner.loop_cover.lock().await.deref_mut())
+    }
+
+    async fn acquire_and_reset_connections_rejected(&self) -> PacketsMap {
+        std::mem::take(self.inner.connections_rejected.lock().await.deref_mut())
+    }
+
+    async fn acquire_and_reset(&self) -> (u64, PacketsMap, PacketsMap, u64) {
         let mut unlocked_sent = self.inner.sent.lock().await;
         let mut unlocked_dropped = self.inner.dropped.lock().await;
         let received = self.inner.received.swap(0, Ordering::SeqCst);
+        let replayed = self.inner.replayed.swap(0, Ordering::SeqCst);
 
         let sent = std::mem::take(unlocked_sent.deref_mut());
         let dropped = std::mem::take(unlocked_dropped.deref_mut());
 
-        (received, sent, dropped)
+        (received, sent, dropped, replayed)
     }
 }
 
@@ -240,6 +634,19 @@ impl UpdateHandler {
                         PacketEvent::Dropped(destination) => {
                             self.current_data.increment_dropped(destination).await
                         }
+                        PacketEvent::Replayed => self.current_data.increment_replayed(),
+                        PacketEvent::LoopCoverSent => {
+                            self.current_data.increment_loop_cover_sent().await
+                        }
+                        PacketEvent::LoopCoverReturned(rtt) => {
+                            self.current_data.increment_loop_cover_returned(rtt).await
+                        }
+                        PacketEvent::LoopCoverLost(first_hop) => {
+                            self.current_data.increment_loop_cover_lost(first_hop).await
+                        }
+                        PacketEvent::ConnectionRejected(reason) => {
+                            self.current_data.increment_connections_rejected(reason).await
+                        }
                     }
                 }
                 _ = self.shutdown.recv() => {
@@ -255,17 +662,23 @@ impl UpdateHandler {
 
 // Channel to report statistics
 #[derive(Clone)]
-pub struct UpdateSender(PacketDataSender);
+pub struct UpdateSender {
+    update_sender: PacketDataSender,
+    dropped_by_reason: DropCounters,
+}
 
 impl UpdateSender {
-    pub(crate) fn new(update_sender: PacketDataSender) -> Self {
-        UpdateSender(update_sender)
+    pub(crate) fn new(update_sender: PacketDataSender, dropped_by_reason: DropCounters) -> Self {
+        UpdateSender {
+            update_sender,
+            dropped_by_reason,
+        }
     }
 
     pub(crate) fn report_sent(&self, destination: String) {
         // in unbounded_send() failed it means that the receiver channel was disconnected
         // and hence something weird must have happened without a way of recovering
-        self.0
+        self.update_sender
             .unbounded_send(PacketEvent::Sent(destination))
             .unwrap()
     }
@@ -275,16 +688,62 @@ impl UpdateSender {
     pub(crate) fn report_received(&self) {
         // in unbounded_send() failed it means that the receiver channel was disconnected
         // and hence something weird must have happened without a way of recovering
-        self.0.unbounded_send(PacketEvent::Received).unwrap()
+        self.update_sender
+            .unbounded_send(PacketEvent::Received)
+            .unwrap()
     }
 
     pub(crate) fn report_dropped(&self, destination: String) {
         // in unbounded_send() failed it means that the receiver channel was disconnected
         // and hence something weird must have happened without a way of recovering
-        self.0
+        self.update_sender
             .unbounded_send(PacketEvent::Dropped(destination))
             .unwrap()
     }
+
+    pub(crate) fn report_dropped_with_reason(&self, reason: DropReason) {
+        self.dropped_by_reason.increment(reason)
+    }
+
+    pub(crate) fn report_replayed(&self) {
+        // in unbounded_send() failed it means that the receiver channel was disconnected
+        // and hence something weird must have happened without a way of recovering
+        self.update_sender
+            .unbounded_send(PacketEvent::Replayed)
+            .unwrap()
+    }
+
+    pub(crate) fn report_loop_cover_sent(&self) {
+        // in unbounded_send() failed it means that the receiver channel was disconnected
+        // and hence something weird must have happened without a way of recovering
+        self.update_sender
+            .unbounded_send(PacketEvent::LoopCoverSent)
+            .unwrap()
+    }
+
+    pub(crate) fn report_loop_cover_returned(&self, rtt: Duration) {
+        // in unbounded_send() failed it means that the receiver channel was disconnected
+        // and hence something weird must have happened without a way of recovering
+        self.update_sender
+            .unbounded_send(PacketEvent::LoopCoverReturned(rtt))
+            .unwrap()
+    }
+
+    pub(crate) fn report_loop_cover_lost(&self, first_hop: String) {
+        // in unbounded_send() failed it means that the receiver channel was disconnected
+        // and hence something weird must have happened without a way of recovering
+        self.update_sender
+            .unbounded_send(PacketEvent::LoopCoverLost(first_hop))
+            .unwrap()
+    }
+
+    pub(crate) fn report_connection_rejected(&self, reason: RejectionReason) {
+        // in unbounded_send() failed it means that the receiver channel was disconnected
+        // and hence something weird must have happened without a way of recovering
+        self.update_sender
+            .unbounded_send(PacketEvent::ConnectionRejected(reason))
+            .unwrap()
+    }
 }
 
 // Worker that periodically updates the shared node stats from the current packet data buffer that
@@ -313,8 +772,30 @@ impl StatsUpdater {
 
     async fn update_stats(&self) {
         // grab new data since last update
-        let (received, sent, dropped) = self.current_packet_data.acquire_and_reset().await;
-        self.current_stats.update(received, sent, dropped).await;
+        let (received, sent, dropped, replayed) =
+            self.current_packet_data.acquire_and_reset().await;
+        let dropped_by_reason = self
+            .current_packet_data
+            .acquire_and_reset_dropped_by_reason();
+        let loop_cover = self
+            .current_packet_data
+            .acquire_and_reset_loop_cover()
+            .await;
+        let connections_rejected = self
+            .current_packet_data
+            .acquire_and_reset_connections_rejected()
+            .await;
+        self.current_stats
+            .update(
+                received,
+                sent,
+                dropped,
+                replayed,
+                dropped_by_reason,
+                loop_cover,
+                connections_rejected,
+            )
+            .await;
     }
 
     async fn run(&mut self) {
@@ -377,6 +858,54 @@ impl PacketStatsConsoleLogger {
                 );
             }
 
+            if stats.packets_replayed_since_startup > 0 {
+                info!(
+                    "Since startup rejected {} replayed packets! ({} in last {} seconds)",
+                    stats.packets_replayed_since_startup,
+                    stats.packets_replayed_since_last_update,
+                    difference_secs,
+                );
+            }
+
+            for (reason, count) in &stats.packets_dropped_by_reason_since_startup {
+                info!(
+                    "Since startup dropped {} packets due to '{}' ({} in last {} seconds)",
+                    count,
+                    reason,
+                    stats
+                        .packets_dropped_by_reason_since_last_update
+                        .get(reason)
+                        .unwrap_or(&0),
+                    difference_secs,
+                );
+            }
+
+            if stats.loop_cover_packets_sent_since_startup > 0 {
+                info!(
+                    "Since startup {} out of {} loop cover packets returned ({} lost). ({} out of {} in last {} seconds, average RTT: {:?})",
+                    stats.loop_cover_packets_returned_since_startup,
+                    stats.loop_cover_packets_sent_since_startup,
+                    stats.loop_cover_packets_lost_since_startup.values().sum::<u64>(),
+                    stats.loop_cover_packets_returned_since_last_update,
+                    stats.loop_cover_packets_sent_since_last_update,
+                    difference_secs,
+                    stats.loop_cover_average_rtt_since_last_update,
+                );
+            }
+
+            for (reason, count) in &stats.connections_rejected_since_startup {
+                info!(
+                    "Since startup rejected {} connections due to '{}' ({} in last {} seconds)",
+                    count,
+                    reason,
+                    stats
+                        .connections_rejected_since_last_update
+                        .get(reason)
+                        .unwrap_or(&0),
+                    difference_secs,
+                );
+            }
+
             debug!(
                 "Since startup received {} packets ({} in last {} seconds)",
                 stats.packets_received_since_startup,
@@ -454,7 +983,8 @@ impl Controller {
         shutdown: ShutdownListener,
     ) -> Self {
         let (sender, receiver) = mpsc::unbounded();
-        let shared_packet_data = CurrentPacketData::new();
+        let dropped_by_reason = DropCounters::default();
+        let shared_packet_data = CurrentPacketData::new(dropped_by_reason.clone());
         let shared_node_stats = SharedNodeStats::new();
 
         Controller {
@@ -463,7 +993,7 @@ impl Controller {
                 receiver,
                 shutdown.clone(),
             ),
-            update_sender: UpdateSender::new(sender),
+            update_sender: UpdateSender::new(sender, dropped_by_reason),
             console_logger: PacketStatsConsoleLogger::new(
                 logging_delay,
                 shared_node_stats.clone(),
@@ -536,5 +1066,172 @@ mod tests {
         assert_eq!(&stats.packets_sent_since_last_update.len(), &1);
         assert_eq!(&stats.packets_received_since_startup, &0u64);
         assert!(&stats.packets_explicitly_dropped_since_startup.is_empty());
+        assert_eq!(&stats.packets_replayed_since_startup, &0u64);
+    }
+
+    #[tokio::test]
+    async fn replayed_packets_are_reported() {
+        let logging_delay = Duration::from_millis(20);
+        let stats_updating_delay = Duration::from_millis(10);
+        let shutdown = ShutdownNotifier::default();
+        let node_stats_controller =
+            Controller::new(logging_delay, stats_updating_delay, shutdown.subscribe());
+
+        let node_stats_pointer = node_stats_controller.get_node_stats_data_pointer();
+        let update_sender = node_stats_controller.start();
+        tokio::time::pause();
+
+        update_sender.report_received();
+        update_sender.report_received();
+        update_sender.report_replayed();
+        tokio::task::yield_now().await;
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+
+        let stats = node_stats_pointer.read().await;
+        assert_eq!(&stats.packets_received_since_startup, &2u64);
+        assert_eq!(&stats.packets_replayed_since_startup, &1u64);
+        assert_eq!(&stats.packets_replayed_since_last_update, &1u64);
+    }
+
+    #[tokio::test]
+    async fn dropped_packets_are_accumulated_since_startup() {
+        let logging_delay = Duration::from_millis(20);
+        let stats_updating_delay = Duration::from_millis(10);
+        let shutdown = ShutdownNotifier::default();
+        let node_stats_controller =
+            Controller::new(logging_delay, stats_updating_delay, shutdown.subscribe());
+
+        let node_stats_pointer = node_stats_controller.get_node_stats_data_pointer();
+        let update_sender = node_stats_controller.start();
+        tokio::time::pause();
+
+        update_sender.report_dropped("foo".to_string());
+        tokio::task::yield_now().await;
+        tokio::time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+
+        update_sender.report_dropped("foo".to_string());
+        tokio::task::yield_now().await;
+        tokio::time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+
+        let stats = node_stats_pointer.read().await;
+        assert_eq!(
+            stats.packets_explicitly_dropped_since_startup.get("foo"),
+            Some(&2)
+        );
+        assert_eq!(
+            stats
+                .packets_explicitly_dropped_since_last_update
+                .get("foo"),
+            Some(&1)
+        );
+    }
+
+    #[tokio::test]
+    async fn drop_reasons_are_reported() {
+        let logging_delay = Duration::from_millis(20);
+        let stats_updating_delay = Duration::from_millis(10);
+        let shutdown = ShutdownNotifier::default();
+        let node_stats_controller =
+            Controller::new(logging_delay, stats_updating_delay, shutdown.subscribe());
+
+        let node_stats_pointer = node_stats_controller.get_node_stats_data_pointer();
+        let update_sender = node_stats_controller.start();
+        tokio::time::pause();
+
+        update_sender.report_dropped_with_reason(DropReason::DelayQueueFull);
+        update_sender.report_dropped_with_reason(DropReason::DelayQueueFull);
+        update_sender.report_dropped_with_reason(DropReason::DelayQueueEvicted);
+        tokio::task::yield_now().await;
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+
+        let stats = node_stats_pointer.read().await;
+        assert_eq!(
+            stats
+                .packets_dropped_by_reason_since_startup
+                .get("delay_queue_full"),
+            Some(&2)
+        );
+        assert_eq!(
+            stats
+                .packets_dropped_by_reason_since_startup
+                .get("delay_queue_evicted"),
+            Some(&1)
+        );
+        assert_eq!(stats.packets_dropped_by_reason_since_startup.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn loop_cover_results_are_reported() {
+        let logging_delay = Duration::from_millis(20);
+        let stats_updating_delay = Duration::from_millis(10);
+        let shutdown = ShutdownNotifier::default();
+        let node_stats_controller =
+            Controller::new(logging_delay, stats_updating_delay, shutdown.subscribe());
+
+        let node_stats_pointer = node_stats_controller.get_node_stats_data_pointer();
+        let update_sender = node_stats_controller.start();
+        tokio::time::pause();
+
+        for _ in 0..3 {
+            update_sender.report_loop_cover_sent();
+        }
+        update_sender.report_loop_cover_returned(Duration::from_millis(100));
+        update_sender.report_loop_cover_returned(Duration::from_millis(300));
+        update_sender.report_loop_cover_lost("foo".to_string());
+        tokio::task::yield_now().await;
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+
+        let stats = node_stats_pointer.read().await;
+        assert_eq!(stats.loop_cover_packets_sent_since_startup, 3);
+        assert_eq!(stats.loop_cover_packets_returned_since_startup, 2);
+        assert_eq!(
+            stats.loop_cover_packets_lost_since_startup.get("foo"),
+            Some(&1)
+        );
+        assert_eq!(
+            stats.loop_cover_average_rtt_since_last_update,
+            Some(Duration::from_millis(200))
+        );
+    }
+
+    #[tokio::test]
+    async fn connection_rejections_are_reported() {
+        let logging_delay = Duration::from_millis(20);
+        let stats_updating_delay = Duration::from_millis(10);
+        let shutdown = ShutdownNotifier::default();
+        let node_stats_controller =
+            Controller::new(logging_delay, stats_updating_delay, shutdown.subscribe());
+
+        let node_stats_pointer = node_stats_controller.get_node_stats_data_pointer();
+        let update_sender = node_stats_controller.start();
+        tokio::time::pause();
+
+        update_sender.report_connection_rejected(RejectionReason::UnknownPeer);
+        update_sender.report_connection_rejected(RejectionReason::UnknownPeer);
+        update_sender.report_connection_rejected(RejectionReason::TooManyConnections);
+        tokio::task::yield_now().await;
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+
+        let stats = node_stats_pointer.read().await;
+        assert_eq!(
+            stats.connections_rejected_since_startup.get("unknown_peer"),
+            Some(&2)
+        );
+        assert_eq!(
+            stats
+                .connections_rejected_since_startup
+                .get("too_many_connections"),
+            Some(&1)
+        );
     }
 }
@@ -1,33 +1,240 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::node::node_statistics::UpdateSender;
-use futures::channel::mpsc;
+use crate::config::DelayQueueOverflowPolicy;
+use crate::node::metrics::NodeMetrics;
+use crate::node::node_statistics::{DropReason, UpdateSender};
 use futures::StreamExt;
-use nonexhaustive_delayqueue::{Expired, NonExhaustiveDelayQueue};
+use nonexhaustive_delayqueue::{Expired, NonExhaustiveDelayQueue, QueueKey};
 use nymsphinx::forwarding::packet::MixPacket;
+use std::collections::BTreeMap;
 use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, Notify};
 use tokio::time::Instant;
 
 use super::ShutdownListener;
 
+// fraction of the delay queue capacity at which the heaviest senders start getting throttled
+// when using the backpressure overflow policy
+const BACKPRESSURE_THRESHOLD: f64 = 0.9;
+
+// upper bound on the number of packets waiting for the `DelayForwarder` to pick them up.
+// The channel never has to hold more packets than the delay queue itself could
+const MAXIMUM_FORWARDER_CHANNEL_CAPACITY: usize = 10_000;
+
 // Delay + MixPacket vs Instant + MixPacket
 
 // rather than using Duration directly, we use an Instant, this way we minimise skew due to
 // time packet spent waiting in the queue to get delayed
-pub(crate) type PacketDelayForwardSender = mpsc::UnboundedSender<(MixPacket, Option<Instant>)>;
-type PacketDelayForwardReceiver = mpsc::UnboundedReceiver<(MixPacket, Option<Instant>)>;
+struct DelayedPacket {
+    packet: MixPacket,
+    forward_at: Option<Instant>,
+
+    /// Number of packets of the sender of this packet that are currently in the delay queue.
+    sender_share: Option<Arc<AtomicUsize>>,
+}
+
+type PacketDelayForwardReceiver = mpsc::Receiver<DelayedPacket>;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DelayQueueLimits {
+    pub(crate) maximum_packets: usize,
+    pub(crate) maximum_bytes: usize,
+    pub(crate) overflow_policy: DelayQueueOverflowPolicy,
+}
+
+/// Utilisation of the delay queue shared between the `DelayForwarder` and the packet senders.
+struct DelayQueueUsage {
+    limits: DelayQueueLimits,
+    packets: AtomicUsize,
+    bytes: AtomicUsize,
+    active_senders: AtomicUsize,
+    capacity_freed: Notify,
+}
+
+impl DelayQueueUsage {
+    fn new(limits: DelayQueueLimits) -> Self {
+        DelayQueueUsage {
+            limits,
+            packets: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+            active_senders: AtomicUsize::new(0),
+            capacity_freed: Notify::new(),
+        }
+    }
+
+    fn would_overflow(&self, packet_size: usize) -> bool {
+        self.packets.load(Ordering::Relaxed) >= self.limits.maximum_packets
+            || self.bytes.load(Ordering::Relaxed) + packet_size > self.limits.maximum_bytes
+    }
+
+    fn is_congested(&self) -> bool {
+        let packets = self.packets.load(Ordering::Relaxed) as f64;
+        let bytes = self.bytes.load(Ordering::Relaxed) as f64;
+
+        packets >= self.limits.maximum_packets as f64 * BACKPRESSURE_THRESHOLD
+            || bytes >= self.limits.maximum_bytes as f64 * BACKPRESSURE_THRESHOLD
+    }
+
+    fn fair_share(&self) -> usize {
+        let senders = self.active_senders.load(Ordering::Relaxed).max(1);
+        self.packets.load(Ordering::Relaxed) / senders
+    }
+
+    fn added(&self, packet_size: usize) -> usize {
+        self.bytes.fetch_add(packet_size, Ordering::Relaxed);
+        self.packets.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn removed(&self, packet_size: usize) -> usize {
+        self.bytes.fetch_sub(packet_size, Ordering::Relaxed);
+        let remaining = self.packets.fetch_sub(1, Ordering::Relaxed) - 1;
+
+        if self.limits.overflow_policy == DelayQueueOverflowPolicy::Backpressure {
+            self.capacity_freed.notify_waiters();
+        }
+        remaining
+    }
+}
+
+/// Bounded channel for submitting packets to the `DelayForwarder`.
+#[derive(Clone)]
+pub(crate) struct PacketDelayForwardSender {
+    channel: mpsc::Sender<DelayedPacket>,
+    usage: Arc<DelayQueueUsage>,
+    node_stats_update_sender: UpdateSender,
+    metrics: NodeMetrics,
+}
+
+impl PacketDelayForwardSender {
+    fn report_dropped(&self, reason: DropReason) {
+        self.node_stats_update_sender
+            .report_dropped_with_reason(reason);
+        self.metrics.report_dropped(reason);
+    }
+
+    fn send(&self, delayed_packet: DelayedPacket) {
+        // unless the forwarder could make space by evicting other packets, there's no point
+        // in even submitting a delayed packet that wouldn't fit in the queue
+        if delayed_packet.forward_at.is_some()
+            && self.usage.limits.overflow_policy != DelayQueueOverflowPolicy::DropLongestDelay
+            && self
+                .usage
+                .would_overflow(delayed_packet.packet.packet().len())
+        {
+            self.report_dropped(DropReason::DelayQueueFull);
+            return;
+        }
+
+        match self.channel.try_send(delayed_packet) {
+            Ok(_) => (),
+            Err(TrySendError::Full(_)) => self.report_dropped(DropReason::ForwarderBacklogged),
+            // if the channel got closed it means that the receiver was dropped
+            // and hence something weird must have happened without a way of recovering
+            Err(TrySendError::Closed(_)) => panic!("the delay-forwarder has died!"),
+        }
+    }
+
+    /// Forwards the packet at the specified instant or immediately if none was provided.
+    pub(crate) fn forward_packet(&self, packet: MixPacket, forward_at: Option<Instant>) {
+        self.send(DelayedPacket {
+            packet,
+            forward_at,
+            sender_share: None,
+        })
+    }
+
+    /// Number of packets that are currently waiting in the delay queue.
+    pub(crate) fn queued_packets(&self) -> usize {
+        self.usage.packets.load(Ordering::Relaxed)
+    }
+
+    /// Registers a new source of packets, such as a connection, whose share of the delay queue
+    /// is going to be tracked in order to apply backpressure if it gets full.
+    pub(crate) fn register_sender(&self) -> PacketSenderHandle {
+        self.usage.active_senders.fetch_add(1, Ordering::Relaxed);
+        PacketSenderHandle {
+            sender: self.clone(),
+            share: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+/// Handle of a single source of packets submitted to the `DelayForwarder`.
+pub(crate) struct PacketSenderHandle {
+    sender: PacketDelayForwardSender,
+    share: Arc<AtomicUsize>,
+}
+
+impl PacketSenderHandle {
+    /// Forwards the packet at the specified instant or immediately if none was provided.
+    pub(crate) fn forward_packet(&self, packet: MixPacket, forward_at: Option<Instant>) {
+        self.sender.send(DelayedPacket {
+            packet,
+            forward_at,
+            sender_share: Some(Arc::clone(&self.share)),
+        })
+    }
+
+    fn is_throttled(&self) -> bool {
+        let usage = &self.sender.usage;
+        usage.limits.overflow_policy == DelayQueueOverflowPolicy::Backpressure
+            && usage.is_congested()
+            && self.share.load(Ordering::Relaxed) > usage.fair_share()
+    }
+
+    /// If the backpressure overflow policy is used, waits for as long as the delay queue is congested
+    /// and this sender holds more than its fair share of it.
+    pub(crate) async fn wait_for_capacity(&self) {
+        loop {
+            // make sure to start listening for the notification before checking the condition
+            // so that we wouldn't miss it
+            let capacity_freed = self.sender.usage.capacity_freed.notified();
+            if !self.is_throttled() {
+                return;
+            }
+            capacity_freed.await
+        }
+    }
+}
+
+impl Drop for PacketSenderHandle {
+    fn drop(&mut self) {
+        self.sender
+            .usage
+            .active_senders
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+struct QueuedPacket {
+    packet: MixPacket,
+    forward_at: Instant,
+    id: u64,
+    size: usize,
+    sender_share: Option<Arc<AtomicUsize>>,
+}
 
 /// Entity responsible for delaying received sphinx packet and forwarding it to next node.
 pub(crate) struct DelayForwarder<C>
 where
     C: mixnet_client::SendWithoutResponse,
 {
-    delay_queue: NonExhaustiveDelayQueue<MixPacket>,
+    delay_queue: NonExhaustiveDelayQueue<QueuedPacket>,
+
+    /// Keys of the queued packets ordered by their forwarding instant, used for finding ones with
+    /// the longest remaining delay. It is only maintained with the `DropLongestDelay` overflow policy.
+    forwarding_order: BTreeMap<(Instant, u64), QueueKey>,
+    next_packet_id: u64,
+    usage: Arc<DelayQueueUsage>,
     mixnet_client: C,
     packet_sender: PacketDelayForwardSender,
     packet_receiver: PacketDelayForwardReceiver,
     node_stats_update_sender: UpdateSender,
+    metrics: NodeMetrics,
     shutdown: ShutdownListener,
 }
 
@@ -37,17 +244,32 @@ where
 {
     pub(crate) fn new(
         client: C,
+        limits: DelayQueueLimits,
         node_stats_update_sender: UpdateSender,
+        metrics: NodeMetrics,
         shutdown: ShutdownListener,
     ) -> DelayForwarder<C> {
-        let (packet_sender, packet_receiver) = mpsc::unbounded();
+        let channel_capacity = limits
+            .maximum_packets
+            .clamp(1, MAXIMUM_FORWARDER_CHANNEL_CAPACITY);
+        let (packet_sender, packet_receiver) = mpsc::channel(channel_capacity);
+        let usage = Arc::new(DelayQueueUsage::new(limits));
 
         DelayForwarder::<C> {
             delay_queue: NonExhaustiveDelayQueue::new(),
+            forwarding_order: BTreeMap::new(),
+            next_packet_id: 0,
+            usage: Arc::clone(&usage),
             mixnet_client: client,
-            packet_sender,
+            packet_sender: PacketDelayForwardSender {
+                channel: packet_sender,
+                usage,
+                node_stats_update_sender: node_stats_update_sender.clone(),
+                metrics: metrics.clone(),
+            },
             packet_receiver,
             node_stats_update_sender,
+            metrics,
             shutdown,
         }
     }
@@ -56,21 +278,32 @@ where
         self.packet_sender.clone()
     }
 
+    fn tracks_forwarding_order(&self) -> bool {
+        self.usage.limits.overflow_policy == DelayQueueOverflowPolicy::DropLongestDelay
+    }
+
+    fn report_dropped(&self, reason: DropReason) {
+        self.node_stats_update_sender
+            .report_dropped_with_reason(reason);
+        self.metrics.report_dropped(reason);
+    }
+
     fn forward_packet(&mut self, packet: MixPacket) {
         let next_hop = packet.next_hop();
         let packet_mode = packet.packet_mode();
-        let sphinx_packet = packet.into_sphinx_packet();
+        let packet = packet.into_packet();
 
-        if let Err(err) =
-            self.mixnet_client
-                .send_without_response(next_hop, sphinx_packet, packet_mode)
+        if let Err(err) = self
+            .mixnet_client
+            .send_without_response(next_hop, packet, packet_mode)
         {
             if err.kind() == io::ErrorKind::WouldBlock {
                 // we only know for sure if we dropped a packet if our sending queue was full
                 // in any other case the connection might still be re-established (or created for the first time)
                 // and the packet might get sent, but we won't know about it
                 self.node_stats_update_sender
-                    .report_dropped(next_hop.to_string())
+                    .report_dropped(next_hop.to_string());
+                self.report_dropped(DropReason::ForwardQueueFull);
             } else if err.kind() == io::ErrorKind::NotConnected {
                 // let's give the benefit of the doubt and assume we manage to establish connection
                 self.node_stats_update_sender
@@ -82,25 +315,100 @@ where
         }
     }
 
+    /// Updates the queue utilisation after the packet got removed from the delay queue.
+    fn handle_dequeued(&mut self, queued: &QueuedPacket) {
+        if self.tracks_forwarding_order() {
+            self.forwarding_order
+                .remove(&(queued.forward_at, queued.id));
+        }
+        if let Some(sender_share) = &queued.sender_share {
+            sender_share.fetch_sub(1, Ordering::Relaxed);
+        }
+        let remaining = self.usage.removed(queued.size);
+        self.metrics.set_delay_queue_depth(remaining);
+    }
+
     /// Upon packet being finished getting delayed, forward it to the mixnet.
-    fn handle_done_delaying(&mut self, packet: Expired<MixPacket>) {
+    fn handle_done_delaying(&mut self, packet: Expired<QueuedPacket>) {
         let delayed_packet = packet.into_inner();
-        self.forward_packet(delayed_packet)
+        self.handle_dequeued(&delayed_packet);
+        self.forward_packet(delayed_packet.packet)
+    }
+
+    /// Attempts to make space for the new packet by evicting the queued ones that would
+    /// have been forwarded after it.
+    fn evict_for(&mut self, packet_size: usize, forward_at: Instant) -> bool {
+        while self.usage.would_overflow(packet_size) {
+            let latest = match self.forwarding_order.keys().next_back() {
+                Some(latest) => *latest,
+                None => return false,
+            };
+            if latest.0 <= forward_at {
+                // the new packet itself has the longest remaining delay
+                return false;
+            }
+
+            // the entry is guaranteed to exist as we have just found it
+            let key = self.forwarding_order.remove(&latest).unwrap();
+            let evicted = self.delay_queue.remove(&key).into_inner();
+            self.handle_dequeued(&evicted);
+            self.report_dropped(DropReason::DelayQueueEvicted);
+        }
+        true
     }
 
-    fn handle_new_packet(&mut self, new_packet: (MixPacket, Option<Instant>)) {
+    fn delay_packet(&mut self, delayed_packet: DelayedPacket, forward_at: Instant) {
+        let size = delayed_packet.packet.packet().len();
+
+        if self.usage.would_overflow(size) {
+            let made_space = match self.usage.limits.overflow_policy {
+                DelayQueueOverflowPolicy::DropLongestDelay => self.evict_for(size, forward_at),
+                DelayQueueOverflowPolicy::DropNewest | DelayQueueOverflowPolicy::Backpressure => {
+                    false
+                }
+            };
+            if !made_space {
+                self.report_dropped(DropReason::DelayQueueFull);
+                return;
+            }
+        }
+
+        let id = self.next_packet_id;
+        self.next_packet_id = self.next_packet_id.wrapping_add(1);
+
+        if let Some(sender_share) = &delayed_packet.sender_share {
+            sender_share.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let queued = QueuedPacket {
+            packet: delayed_packet.packet,
+            forward_at,
+            id,
+            size,
+            sender_share: delayed_packet.sender_share,
+        };
+        let key = self.delay_queue.insert_at(queued, forward_at);
+        if self.tracks_forwarding_order() {
+            self.forwarding_order.insert((forward_at, id), key);
+        }
+
+        let queued_packets = self.usage.added(size);
+        self.metrics.set_delay_queue_depth(queued_packets);
+    }
+
+    fn handle_new_packet(&mut self, new_packet: DelayedPacket) {
         // in case of a zero delay packet, don't bother putting it in the delay queue,
         // just forward it immediately
-        if let Some(instant) = new_packet.1 {
+        if let Some(instant) = new_packet.forward_at {
             // check if the delay has already expired, if so, don't bother putting it through
             // the delay queue only to retrieve it immediately. Just forward it.
             if instant.checked_duration_since(Instant::now()).is_none() {
-                self.forward_packet(new_packet.0)
+                self.forward_packet(new_packet.packet)
             } else {
-                self.delay_queue.insert_at(new_packet.0, instant);
+                self.delay_packet(new_packet, instant)
             }
         } else {
-            self.forward_packet(new_packet.0)
+            self.forward_packet(new_packet.packet)
         }
     }
 
@@ -111,7 +419,7 @@ where
                 delayed = self.delay_queue.next() => {
                     self.handle_done_delaying(delayed.unwrap());
                 }
-                new_packet = self.packet_receiver.next() => {
+                new_packet = self.packet_receiver.recv() => {
                     // this one is impossible to ever panic - the object itself contains a sender
                     // and hence it can't happen that ALL senders are dropped
                     self.handle_new_packet(new_packet.unwrap())
@@ -134,6 +442,9 @@ mod tests {
     use std::sync::{Arc, Mutex};
     use std::time::Duration;
 
+    use crate::node::node_statistics::{DropCounters, PacketEvent};
+    use futures::channel::mpsc as futures_mpsc;
+    use std::collections::HashMap;
     use task::ShutdownNotifier;
 
     use nymsphinx::addressing::nodes::NymNodeRoutingAddress;
@@ -142,19 +453,19 @@ mod tests {
     use nymsphinx_types::builder::SphinxPacketBuilder;
     use nymsphinx_types::{
         crypto, Delay as SphinxDelay, Destination, DestinationAddressBytes, Node, NodeAddressBytes,
-        SphinxPacket, DESTINATION_ADDRESS_LENGTH, IDENTIFIER_LENGTH, NODE_ADDRESS_LENGTH,
+        NymPacket, DESTINATION_ADDRESS_LENGTH, IDENTIFIER_LENGTH, NODE_ADDRESS_LENGTH,
     };
 
     #[derive(Default)]
     struct TestClient {
-        pub packets_sent: Arc<Mutex<Vec<(NymNodeRoutingAddress, SphinxPacket, PacketMode)>>>,
+        pub packets_sent: Arc<Mutex<Vec<(NymNodeRoutingAddress, NymPacket, PacketMode)>>>,
     }
 
     impl mixnet_client::SendWithoutResponse for TestClient {
         fn send_without_response(
             &mut self,
             address: NymNodeRoutingAddress,
-            packet: SphinxPacket,
+            packet: NymPacket,
             packet_mode: PacketMode,
         ) -> io::Result<()> {
             self.packets_sent
@@ -165,7 +476,7 @@ mod tests {
         }
     }
 
-    fn make_valid_sphinx_packet(size: PacketSize) -> SphinxPacket {
+    fn make_valid_sphinx_packet(size: PacketSize) -> NymPacket {
         let (_, node1_pk) = crypto::keygen();
         let node1 = Node::new(
             NodeAddressBytes::from_bytes([5u8; NODE_ADDRESS_LENGTH]),
@@ -196,18 +507,191 @@ mod tests {
             .with_payload_size(size.payload_size())
             .build_packet(b"foomp".to_vec(), &route, &destination, &delays)
             .unwrap()
+            .into()
+    }
+
+    fn test_limits(
+        maximum_packets: usize,
+        overflow_policy: DelayQueueOverflowPolicy,
+    ) -> DelayQueueLimits {
+        DelayQueueLimits {
+            maximum_packets,
+            maximum_bytes: usize::MAX,
+            overflow_policy,
+        }
+    }
+
+    // note: the stats receiver has to be kept alive for as long as the forwarder is used
+    fn test_forwarder(
+        limits: DelayQueueLimits,
+    ) -> (
+        DelayForwarder<TestClient>,
+        futures_mpsc::UnboundedReceiver<PacketEvent>,
+        DropCounters,
+    ) {
+        let (stats_sender, stats_receiver) = futures_mpsc::unbounded();
+        let dropped_by_reason = DropCounters::default();
+        // the notifier can be dropped straight away as the forwarder is not going to be run
+        let shutdown = ShutdownNotifier::default();
+        let forwarder = DelayForwarder::new(
+            TestClient::default(),
+            limits,
+            UpdateSender::new(stats_sender, dropped_by_reason.clone()),
+            NodeMetrics::new(),
+            shutdown.subscribe(),
+        );
+        (forwarder, stats_receiver, dropped_by_reason)
+    }
+
+    fn delayed_test_packet(
+        forward_at: Instant,
+        sender: Option<&PacketSenderHandle>,
+    ) -> (DelayedPacket, Instant) {
+        let next_hop =
+            NymNodeRoutingAddress::from(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), 42));
+        let packet = MixPacket::new(
+            next_hop,
+            make_valid_sphinx_packet(PacketSize::AckPacket),
+            PacketMode::default(),
+        );
+        let delayed = DelayedPacket {
+            packet,
+            forward_at: Some(forward_at),
+            sender_share: sender.map(|sender| Arc::clone(&sender.share)),
+        };
+        (delayed, forward_at)
+    }
+
+    #[tokio::test]
+    async fn newest_packets_are_dropped_when_queue_is_full() {
+        let (mut forwarder, _stats_receiver, _) =
+            test_forwarder(test_limits(2, DelayQueueOverflowPolicy::DropNewest));
+        let now = Instant::now();
+
+        for delay in [10, 20, 5] {
+            let (packet, at) = delayed_test_packet(now + Duration::from_secs(delay), None);
+            forwarder.delay_packet(packet, at);
+        }
+
+        assert_eq!(forwarder.delay_queue.len(), 2);
+        assert_eq!(forwarder.usage.packets.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn packets_with_longest_delay_are_evicted_when_queue_is_full() {
+        let (mut forwarder, _stats_receiver, _) =
+            test_forwarder(test_limits(2, DelayQueueOverflowPolicy::DropLongestDelay));
+        let now = Instant::now();
+
+        for delay in [10, 20, 5, 30] {
+            let (packet, at) = delayed_test_packet(now + Duration::from_secs(delay), None);
+            forwarder.delay_packet(packet, at);
+        }
+
+        // the 20s packet got evicted in favour of the 5s one and the 30s one got dropped straight away
+        let remaining = forwarder
+            .forwarding_order
+            .keys()
+            .map(|(at, _)| *at - now)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            remaining,
+            vec![Duration::from_secs(5), Duration::from_secs(10)]
+        );
+        assert_eq!(forwarder.delay_queue.len(), 2);
+        assert_eq!(forwarder.usage.packets.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn only_heaviest_senders_are_throttled() {
+        let (mut forwarder, _stats_receiver, _) =
+            test_forwarder(test_limits(10, DelayQueueOverflowPolicy::Backpressure));
+        let sender = forwarder.sender();
+        let heavy_sender = sender.register_sender();
+        let light_sender = sender.register_sender();
+        let now = Instant::now();
+
+        for _ in 0..8 {
+            let (packet, at) =
+                delayed_test_packet(now + Duration::from_secs(10), Some(&heavy_sender));
+            forwarder.delay_packet(packet, at);
+        }
+        let (packet, at) = delayed_test_packet(now + Duration::from_secs(10), Some(&light_sender));
+        forwarder.delay_packet(packet, at);
+
+        assert!(heavy_sender.is_throttled());
+        assert!(!light_sender.is_throttled());
+
+        // the light sender can carry on straight away
+        tokio::time::timeout(Duration::from_millis(100), light_sender.wait_for_capacity())
+            .await
+            .unwrap();
+    }
+
+    fn only_dropped_with(reason: DropReason, count: u64) -> HashMap<String, u64> {
+        [(reason.as_str().to_string(), count)].into_iter().collect()
+    }
+
+    #[tokio::test]
+    async fn delayed_packets_that_would_not_fit_are_dropped_by_the_sender() {
+        let (mut forwarder, _stats_receiver, dropped_by_reason) =
+            test_forwarder(test_limits(2, DelayQueueOverflowPolicy::DropNewest));
+        let sender = forwarder.sender();
+        let now = Instant::now();
+
+        for _ in 0..2 {
+            let (packet, at) = delayed_test_packet(now + Duration::from_secs(10), None);
+            forwarder.delay_packet(packet, at);
+        }
+
+        let (packet, at) = delayed_test_packet(now + Duration::from_secs(10), None);
+        sender.forward_packet(packet.packet, Some(at));
+
+        // the packet never made it to the channel
+        assert!(forwarder.packet_receiver.try_recv().is_err());
+        assert_eq!(
+            dropped_by_reason.take(),
+            only_dropped_with(DropReason::DelayQueueFull, 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn packets_are_dropped_when_forwarder_is_backlogged() {
+        let (mut forwarder, _stats_receiver, dropped_by_reason) =
+            test_forwarder(test_limits(2, DelayQueueOverflowPolicy::DropLongestDelay));
+        let sender = forwarder.sender();
+        let now = Instant::now();
+
+        // the forwarder is not running, so nothing is taken out of the channel
+        for _ in 0..3 {
+            let (packet, at) = delayed_test_packet(now + Duration::from_secs(10), None);
+            sender.forward_packet(packet.packet, Some(at));
+        }
+
+        assert!(forwarder.packet_receiver.try_recv().is_ok());
+        assert!(forwarder.packet_receiver.try_recv().is_ok());
+        assert!(forwarder.packet_receiver.try_recv().is_err());
+        assert_eq!(
+            dropped_by_reason.take(),
+            only_dropped_with(DropReason::ForwarderBacklogged, 1)
+        );
     }
 
     #[tokio::test]
     async fn packets_received_are_forwarded() {
         // Wire up the DelayForwarder
-        let (stats_sender, _stats_receiver) = mpsc::unbounded();
-        let node_stats_update_sender = UpdateSender::new(stats_sender);
+        let (stats_sender, _stats_receiver) = futures_mpsc::unbounded();
+        let node_stats_update_sender = UpdateSender::new(stats_sender, DropCounters::default());
         let client = TestClient::default();
         let client_packets_sent = client.packets_sent.clone();
         let shutdown = ShutdownNotifier::default();
-        let mut delay_forwarder =
-            DelayForwarder::new(client, node_stats_update_sender, shutdown.subscribe());
+        let mut delay_forwarder = DelayForwarder::new(
+            client,
+            test_limits(100, DelayQueueOverflowPolicy::DropNewest),
+            node_stats_update_sender,
+            NodeMetrics::new(),
+            shutdown.subscribe(),
+        );
         let packet_sender = delay_forwarder.sender();
 
         // Spawn the worker, listening on packet_sender channel
@@ -222,9 +706,7 @@ mod tests {
             PacketMode::default(),
         );
         let forward_instant = None;
-        packet_sender
-            .unbounded_send((mix_packet, forward_instant))
-            .unwrap();
+        packet_sender.forward_packet(mix_packet, forward_instant);
 
         // Give the the worker a chance to act
         tokio::time::sleep(Duration::from_millis(10)).await;
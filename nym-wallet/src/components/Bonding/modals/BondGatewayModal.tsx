@@ -80,6 +80,8 @@ export const BondGatewayModal = ({
         version: gatewayData.version,
         mix_port: gatewayData.mixPort,
         clients_port: gatewayData.clientsPort,
+        clients_wss_port: null,
+        http_api_port: null,
         sphinx_key: gatewayData.sphinxKey,
         identity_key: gatewayData.identityKey,
         location: gatewayData.location,
@@ -104,6 +106,8 @@ export const BondGatewayModal = ({
           version: gatewayData.version,
           mix_port: gatewayData.mixPort,
           clients_port: gatewayData.clientsPort,
+          clients_wss_port: null,
+          http_api_port: null,
           sphinx_key: gatewayData.sphinxKey,
           identity_key: gatewayData.identityKey,
           location: gatewayData.location,
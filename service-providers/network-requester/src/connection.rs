@@ -1,10 +1,11 @@
 // Copyright 2020 - Nym Technologies SA <contact@nymtech.net>
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::reply::{self, ReturnAddress};
 use futures::channel::mpsc;
-use nymsphinx::addressing::clients::Recipient;
 use proxy_helpers::connection_controller::ConnectionReceiver;
 use proxy_helpers::proxy_runner::ProxyRunner;
+use proxy_helpers::send_window::SendWindow;
 use socks5_requests::{ConnectionId, Message as Socks5Message, RemoteAddress, Response};
 use std::io;
 use task::ShutdownListener;
@@ -18,14 +19,14 @@ pub(crate) struct Connection {
     id: ConnectionId,
     address: RemoteAddress,
     conn: Option<TcpStream>,
-    return_address: Recipient,
+    return_address: ReturnAddress,
 }
 
 impl Connection {
     pub(crate) async fn new(
         id: ConnectionId,
         address: RemoteAddress,
-        return_address: Recipient,
+        return_address: ReturnAddress,
     ) -> io::Result<Self> {
         let conn = TcpStream::connect(&address).await?;
 
@@ -40,14 +41,15 @@ impl Connection {
     pub(crate) async fn run_proxy(
         &mut self,
         mix_receiver: ConnectionReceiver,
-        mix_sender: mpsc::UnboundedSender<(Socks5Message, Recipient)>,
+        mix_sender: mpsc::UnboundedSender<(Socks5Message, ReturnAddress)>,
+        send_window: Option<SendWindow>,
         shutdown: ShutdownListener,
     ) {
         let stream = self.conn.take().unwrap();
         let remote_source_address = "???".to_string(); // we don't know ip address of requester
         let connection_id = self.id;
-        let recipient = self.return_address;
-        let (stream, _) = ProxyRunner::new(
+        let return_address = self.return_address;
+        let mut proxy_runner = ProxyRunner::new(
             stream,
             self.address.clone(),
             remote_source_address,
@@ -55,15 +57,25 @@ impl Connection {
             mix_sender,
             connection_id,
             shutdown,
-        )
-        .run(move |conn_id, read_data, socket_closed| {
-            (
-                Socks5Message::Response(Response::new(conn_id, read_data, socket_closed)),
-                recipient,
-            )
-        })
-        .await
-        .into_inner();
+        );
+        // replies can't be split across multiple reply SURBs
+        if let ReturnAddress::Anonymous = return_address {
+            proxy_runner = proxy_runner.with_max_data_size(reply::max_reply_data_size());
+        }
+        // and the remote stops being read while too much data is waiting for them
+        if let Some(send_window) = send_window {
+            proxy_runner = proxy_runner.with_send_window(send_window);
+        }
+
+        let (stream, _) = proxy_runner
+            .run(move |conn_id, read_data, socket_closed| {
+                (
+                    Socks5Message::Response(Response::new(conn_id, read_data, socket_closed)),
+                    return_address,
+                )
+            })
+            .await
+            .into_inner();
         self.conn = Some(stream);
     }
 }
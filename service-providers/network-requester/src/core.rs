@@ -4,6 +4,7 @@
 use crate::allowed_hosts::{HostsStore, OutboundRequestFilter};
 use crate::connection::Connection;
 use crate::error::NetworkRequesterError;
+use crate::reply::{self, AnonymousConnections, ReceivedReplySurbs, ReturnAddress};
 use crate::statistics::ServiceStatisticsCollector;
 use crate::websocket;
 use crate::websocket::TSWebsocketStream;
@@ -13,8 +14,10 @@ use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
 use log::*;
 use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::anonymous_replies::ReplySurb;
 use nymsphinx::receiver::ReconstructedMessage;
 use proxy_helpers::connection_controller::{Controller, ControllerCommand, ControllerSender};
+use proxy_helpers::send_window::SendWindow;
 use socks5_requests::{
     ConnectionId, Message as Socks5Message, NetworkRequesterResponse, Request, Response,
 };
@@ -64,16 +67,29 @@ impl ServiceProvider {
     }
 
     /// Listens for any messages from `mix_reader` that should be written back to the mix network
-    /// via the `websocket_writer`.
+    /// via the `websocket_writer`. Messages on anonymous connections are sent using the reply SURBs
+    /// received on `reply_surbs_reader`.
     async fn mixnet_response_listener(
         mut websocket_writer: SplitSink<TSWebsocketStream, Message>,
-        mut mix_reader: mpsc::UnboundedReceiver<(Socks5Message, Recipient)>,
+        mut mix_reader: mpsc::UnboundedReceiver<(Socks5Message, ReturnAddress)>,
+        mut reply_surbs_reader: mpsc::UnboundedReceiver<ReceivedReplySurbs>,
         stats_collector: Option<ServiceStatisticsCollector>,
         mut closed_connection_rx: ClosedConnectionReceiver,
     ) {
+        let mut anonymous_connections = AnonymousConnections::default();
+        let mut connections_check = tokio::time::interval(reply::CONNECTIONS_CHECK_INTERVAL);
+
         loop {
             tokio::select! {
-                // TODO: wire SURBs in here once they're available
+                // the reply SURBs of a connection are always sent before any of its messages,
+                // so make sure they are also always handled first
+                biased;
+                Some(received_reply_surbs) = reply_surbs_reader.next() => {
+                    for response_message in anonymous_connections.insert_reply_surbs(received_reply_surbs) {
+                        let message = Message::Binary(response_message.serialize());
+                        websocket_writer.send(message).await.unwrap();
+                    }
+                },
                 socks5_msg = mix_reader.next() => {
                     if let Some((msg, return_address)) = socks5_msg {
                         if let Some(stats_collector) = stats_collector.as_ref() {
@@ -93,15 +109,33 @@ impl ServiceProvider {
                         let conn_id = msg.conn_id();
 
                         // make 'request' to native-websocket client
-                        let response_message = ClientRequest::Send {
-                            recipient: return_address,
-                            message: msg.into_bytes(),
-                            with_reply_surb: false,
-                            connection_id: conn_id,
+                        let response_messages = match return_address {
+                            ReturnAddress::Known(recipient) => vec![ClientRequest::Send {
+                                recipient,
+                                message: msg.into_bytes(),
+                                with_reply_surb: false,
+                                connection_id: conn_id,
+                            }],
+                            ReturnAddress::Anonymous => {
+                                // nothing else is going to be sent on the connection after it's closed
+                                // (or if it was never established to begin with)
+                                let is_final = matches!(
+                                    &msg,
+                                    Socks5Message::Response(response) if response.is_closed
+                                ) || matches!(&msg, Socks5Message::NetworkRequesterResponse(_));
+
+                                let requests = anonymous_connections.send(conn_id, msg);
+                                if is_final {
+                                    anonymous_connections.close(conn_id);
+                                }
+                                requests
+                            }
                         };
 
-                        let message = Message::Binary(response_message.serialize());
-                        websocket_writer.send(message).await.unwrap();
+                        for response_message in response_messages {
+                            let message = Message::Binary(response_message.serialize());
+                            websocket_writer.send(message).await.unwrap();
+                        }
                     } else {
                         log::error!("Exiting: channel closed!");
                         break;
@@ -112,6 +146,12 @@ impl ServiceProvider {
                     let ws_msg = Message::Binary(msg.serialize());
                     websocket_writer.send(ws_msg).await.unwrap();
                 }
+                _ = connections_check.tick() => {
+                    for response_message in anonymous_connections.check_connections() {
+                        let message = Message::Binary(response_message.serialize());
+                        websocket_writer.send(message).await.unwrap();
+                    }
+                }
             }
         }
     }
@@ -151,9 +191,10 @@ impl ServiceProvider {
     async fn start_proxy(
         conn_id: ConnectionId,
         remote_addr: String,
-        return_address: Recipient,
+        return_address: ReturnAddress,
+        send_window: Option<SendWindow>,
         controller_sender: ControllerSender,
-        mix_input_sender: mpsc::UnboundedSender<(Socks5Message, Recipient)>,
+        mix_input_sender: mpsc::UnboundedSender<(Socks5Message, ReturnAddress)>,
         shutdown: ShutdownListener,
     ) {
         let mut conn = match Connection::new(conn_id, remote_addr.clone(), return_address).await {
@@ -191,7 +232,7 @@ impl ServiceProvider {
         );
 
         // run the proxy on the connection
-        conn.run_proxy(mix_receiver, mix_input_sender, shutdown)
+        conn.run_proxy(mix_receiver, mix_input_sender, send_window, shutdown)
             .await;
 
         // proxy is done - remove the access channel from the controller
@@ -207,15 +248,33 @@ impl ServiceProvider {
         );
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn handle_proxy_connect(
         &mut self,
         controller_sender: &mut ControllerSender,
-        mix_input_sender: &mpsc::UnboundedSender<(Socks5Message, Recipient)>,
+        mix_input_sender: &mpsc::UnboundedSender<(Socks5Message, ReturnAddress)>,
+        reply_surbs_sender: &mpsc::UnboundedSender<ReceivedReplySurbs>,
         conn_id: ConnectionId,
         remote_addr: String,
-        return_address: Recipient,
+        return_address: ReturnAddress,
+        reply_surbs: Vec<ReplySurb>,
         shutdown: ShutdownListener,
     ) {
+        let send_window = if let ReturnAddress::Anonymous = return_address {
+            // the SURBs must be available before anything is sent back on the connection
+            let send_window = SendWindow::new(reply::SEND_WINDOW_SIZE);
+            reply_surbs_sender
+                .unbounded_send(ReceivedReplySurbs {
+                    connection_id: conn_id,
+                    reply_surbs,
+                    send_window: Some(send_window.clone()),
+                })
+                .unwrap();
+            Some(send_window)
+        } else {
+            None
+        };
+
         if !self.open_proxy && !self.outbound_request_filter.check(&remote_addr) {
             let log_msg = format!("Domain {:?} failed filter check", remote_addr);
             log::info!("{}", log_msg);
@@ -239,6 +298,7 @@ impl ServiceProvider {
                 conn_id,
                 remote_addr,
                 return_address,
+                send_window,
                 controller_sender_clone,
                 mix_input_sender_clone,
                 shutdown,
@@ -259,11 +319,28 @@ impl ServiceProvider {
             .unwrap()
     }
 
+    fn handle_reply_surbs(
+        &self,
+        reply_surbs_sender: &mpsc::UnboundedSender<ReceivedReplySurbs>,
+        conn_id: ConnectionId,
+        reply_surbs: Vec<ReplySurb>,
+    ) {
+        reply_surbs_sender
+            .unbounded_send(ReceivedReplySurbs {
+                connection_id: conn_id,
+                reply_surbs,
+                send_window: None,
+            })
+            .unwrap()
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn handle_proxy_message(
         &mut self,
         raw_request: &[u8],
         controller_sender: &mut ControllerSender,
-        mix_input_sender: &mpsc::UnboundedSender<(Socks5Message, Recipient)>,
+        mix_input_sender: &mpsc::UnboundedSender<(Socks5Message, ReturnAddress)>,
+        reply_surbs_sender: &mpsc::UnboundedSender<ReceivedReplySurbs>,
         stats_collector: Option<ServiceStatisticsCollector>,
         shutdown: ShutdownListener,
     ) {
@@ -287,13 +364,39 @@ impl ServiceProvider {
                     self.handle_proxy_connect(
                         controller_sender,
                         mix_input_sender,
+                        reply_surbs_sender,
                         req.conn_id,
                         req.remote_addr,
-                        req.return_address,
+                        ReturnAddress::Known(req.return_address),
+                        Vec::new(),
                         shutdown,
                     )
                 }
 
+                Request::ConnectAnonymous(req) => {
+                    if let Some(stats_collector) = stats_collector {
+                        stats_collector
+                            .connected_services
+                            .write()
+                            .await
+                            .insert(req.conn_id, req.remote_addr.clone());
+                    }
+                    self.handle_proxy_connect(
+                        controller_sender,
+                        mix_input_sender,
+                        reply_surbs_sender,
+                        req.conn_id,
+                        req.remote_addr,
+                        ReturnAddress::Anonymous,
+                        req.reply_surbs,
+                        shutdown,
+                    )
+                }
+
+                Request::ReplySurbs(conn_id, reply_surbs) => {
+                    self.handle_reply_surbs(reply_surbs_sender, conn_id, reply_surbs)
+                }
+
                 Request::Send(conn_id, data, closed) => {
                     if let Some(stats_collector) = stats_collector {
                         if let Some(remote_addr) = stats_collector
@@ -312,7 +415,9 @@ impl ServiceProvider {
                     self.handle_proxy_send(controller_sender, conn_id, data, closed)
                 }
             },
-            Socks5Message::Response(_) | Socks5Message::NetworkRequesterResponse(_) => {}
+            Socks5Message::Response(_)
+            | Socks5Message::NetworkRequesterResponse(_)
+            | Socks5Message::ReplySurbsRequest(_) => {}
         }
     }
 
@@ -326,7 +431,11 @@ impl ServiceProvider {
         // channels responsible for managing messages that are to be sent to the mix network. The receiver is
         // going to be used by `mixnet_response_listener`
         let (mix_input_sender, mix_input_receiver) =
-            mpsc::unbounded::<(Socks5Message, Recipient)>();
+            mpsc::unbounded::<(Socks5Message, ReturnAddress)>();
+
+        // channels responsible for passing the reply SURBs of anonymous connections to
+        // the `mixnet_response_listener`
+        let (reply_surbs_sender, reply_surbs_receiver) = mpsc::unbounded();
 
         // Used to notify tasks to shutdown. Not all tasks fully supports this (yet).
         let shutdown = task::ShutdownNotifier::default();
@@ -367,6 +476,7 @@ impl ServiceProvider {
             Self::mixnet_response_listener(
                 websocket_writer,
                 mix_input_receiver,
+                reply_surbs_receiver,
                 stats_collector_clone,
                 closed_connection_rx,
             )
@@ -391,6 +501,7 @@ impl ServiceProvider {
                 &raw_message,
                 &mut controller_sender,
                 &mix_input_sender,
+                &reply_surbs_sender,
                 stats_collector.clone(),
                 shutdown.subscribe(),
             )
@@ -15,6 +15,7 @@ mod allowed_hosts;
 mod connection;
 mod core;
 mod error;
+mod reply;
 mod statistics;
 mod websocket;
 
@@ -31,7 +32,8 @@ struct Run {
     /// Enable service anonymized statistics that get sent to a statistics aggregator server
     enable_statistics: bool,
 
-    /// Mixnet client address where a statistics aggregator is running. The default value is a Nym aggregator client
+    /// Mixnet client address, either in the base58 or the checksummed format, where a statistics aggregator
+    /// is running. The default value is a Nym aggregator client
     statistics_recipient: Option<String>,
 }
 
@@ -48,7 +50,7 @@ impl Run {
         let stats_provider_addr = self
             .statistics_recipient
             .as_ref()
-            .map(Recipient::try_from_base58_string)
+            .map(|address| Recipient::try_from_string(address, &network_defaults::recipient_hrp()))
             .transpose()
             .unwrap_or(None);
 
@@ -0,0 +1,556 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use log::*;
+use nymsphinx::addressing::clients::Recipient;
+use nymsphinx::anonymous_replies::{ReplySurb, REPLY_SURB_MAX_AGE};
+use proxy_helpers::send_window::SendWindow;
+use socks5_requests::{ConnectionId, Message as Socks5Message, ReplySurbsRequest};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use websocket_requests::requests::ClientRequest;
+
+// Number of reply SURBs asked for whenever an anonymous connection is about to run out of them.
+const REPLY_SURBS_REQUEST_SIZE: u32 = 50;
+// Once a connection has this few reply SURBs left, the requester is asked for more of them.
+const REPLY_SURBS_LOW_WATERMARK: usize = 10;
+// Maximum number of reply SURBs held for a single connection. Any excess ones, starting
+// from the oldest, are discarded.
+const MAX_REPLY_SURBS_PER_CONNECTION: usize = 500;
+// Maximum number of anonymous connections served at the same time.
+const MAX_ANONYMOUS_CONNECTIONS: usize = 10_000;
+// Maximum amount of data queued for a single connection before it gets dropped. The remote stops
+// being read once the send window is full, so the window is only ever overshot by a single read.
+const MAX_QUEUED_BYTES_PER_CONNECTION: usize = 2 * SEND_WINDOW_SIZE;
+// How long we wait for the requested reply SURBs to arrive before asking for them again.
+// If the connection has no SURBs left to ask with, it is dropped instead.
+const REPLY_SURBS_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Amount of data read from the remote of an anonymous connection that can be waiting for
+/// reply SURBs before the remote stops being read.
+pub(crate) const SEND_WINDOW_SIZE: usize = 1_000_000;
+
+/// How often the anonymous connections are checked for the timed out reply SURB requests.
+pub(crate) const CONNECTIONS_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// Message flag, closing flag and connection id of the response alongside the index of the ordered message.
+const RESPONSE_OVERHEAD: usize = 1 + 1 + 8 + 8;
+
+/// Maximum amount of data read from the remote that can be put in a single response,
+/// so that it would still fit into a reply SURB.
+pub(crate) fn max_reply_data_size() -> usize {
+    ReplySurb::max_msg_len(Default::default()) - RESPONSE_OVERHEAD
+}
+
+/// Destination of the messages sent back by the network requester.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ReturnAddress {
+    /// The requester has revealed its address, so the messages are sent directly to it.
+    Known(Recipient),
+
+    /// The requester has stayed anonymous, so the messages are sent using the reply SURBs
+    /// it has provided for the connection.
+    Anonymous,
+}
+
+/// Reply SURBs received from an anonymous requester.
+pub(crate) struct ReceivedReplySurbs {
+    pub(crate) connection_id: ConnectionId,
+    pub(crate) reply_surbs: Vec<ReplySurb>,
+
+    /// Send window of the connection, present only if the SURBs came alongside the request
+    /// opening the connection rather than as a replenishment of an existing one.
+    pub(crate) send_window: Option<SendWindow>,
+}
+
+struct StoredReplySurb {
+    reply_surb: ReplySurb,
+    received_at: Instant,
+}
+
+impl StoredReplySurb {
+    fn is_expired(&self) -> bool {
+        self.received_at.elapsed() >= REPLY_SURB_MAX_AGE
+    }
+
+    // we ask for fresh SURBs well before the ones we hold expire, as once they all do,
+    // there's no way of reaching the requester anymore
+    fn is_ageing(&self) -> bool {
+        self.received_at.elapsed() >= REPLY_SURB_MAX_AGE / 2
+    }
+}
+
+struct PendingMessage {
+    message: Vec<u8>,
+
+    // amount of data read from the remote that is sent with this message
+    read_bytes: usize,
+}
+
+struct AnonymousConnection {
+    // ordered from the oldest to the most recently received
+    reply_surbs: VecDeque<StoredReplySurb>,
+    last_reply_surbs_at: Instant,
+    pending_messages: VecDeque<PendingMessage>,
+    pending_bytes: usize,
+    send_window: SendWindow,
+    requested_more_at: Option<Instant>,
+    closed: bool,
+}
+
+impl AnonymousConnection {
+    fn new(send_window: SendWindow) -> Self {
+        AnonymousConnection {
+            reply_surbs: VecDeque::new(),
+            last_reply_surbs_at: Instant::now(),
+            pending_messages: VecDeque::new(),
+            pending_bytes: 0,
+            send_window,
+            requested_more_at: None,
+            closed: false,
+        }
+    }
+
+    fn pop_pending(&mut self) -> Option<Vec<u8>> {
+        let pending = self.pending_messages.pop_front()?;
+        self.pending_bytes -= pending.message.len();
+        self.send_window.release(pending.read_bytes);
+        Some(pending.message)
+    }
+
+    fn is_awaiting_reply_surbs(&self) -> bool {
+        self.requested_more_at.map_or(false, |requested_at| {
+            requested_at.elapsed() < REPLY_SURBS_REQUEST_TIMEOUT
+        })
+    }
+
+    // without any reply SURBs, the connection can't even ask for more of them,
+    // so once the ones it has already asked for don't arrive in time, it's stuck for good
+    fn is_stuck(&self) -> bool {
+        self.reply_surbs.is_empty()
+            && self
+                .requested_more_at
+                .unwrap_or(self.last_reply_surbs_at)
+                .elapsed()
+                >= REPLY_SURBS_REQUEST_TIMEOUT
+    }
+}
+
+/// Keeps track of the reply SURBs of all anonymous connections alongside the messages
+/// that are waiting for more of them to arrive.
+#[derive(Default)]
+pub(crate) struct AnonymousConnections {
+    connections: HashMap<ConnectionId, AnonymousConnection>,
+}
+
+impl AnonymousConnections {
+    /// Stores the received reply SURBs and returns the requests for sending any messages
+    /// that were waiting for them.
+    pub(crate) fn insert_reply_surbs(
+        &mut self,
+        received: ReceivedReplySurbs,
+    ) -> Vec<ClientRequest> {
+        let connection_id = received.connection_id;
+        let connection = if let Some(send_window) = received.send_window {
+            if !self.connections.contains_key(&connection_id)
+                && self.connections.len() >= MAX_ANONYMOUS_CONNECTIONS
+            {
+                warn!("Too many anonymous connections - rejecting connection {connection_id}");
+                send_window.close();
+                return Vec::new();
+            }
+            self.connections
+                .entry(connection_id)
+                .or_insert_with(|| AnonymousConnection::new(send_window))
+        } else {
+            match self.connections.get_mut(&connection_id) {
+                Some(connection) => connection,
+                None => {
+                    debug!("Received reply SURBs for already closed connection {connection_id}");
+                    return Vec::new();
+                }
+            }
+        };
+
+        let received_at = Instant::now();
+        connection.last_reply_surbs_at = received_at;
+        connection
+            .reply_surbs
+            .extend(
+                received
+                    .reply_surbs
+                    .into_iter()
+                    .map(|reply_surb| StoredReplySurb {
+                        reply_surb,
+                        received_at,
+                    }),
+            );
+        let excess = connection
+            .reply_surbs
+            .len()
+            .saturating_sub(MAX_REPLY_SURBS_PER_CONNECTION);
+        if excess > 0 {
+            debug!("Discarded {excess} excess reply SURBs of connection {connection_id}");
+            connection.reply_surbs.drain(..excess);
+        }
+        connection.requested_more_at = None;
+        self.flush(connection_id)
+    }
+
+    /// Queues the message to be sent on the connection and returns the requests for sending
+    /// as many of the queued messages as there are reply SURBs available.
+    /// Note that the reply SURBs of the connection must have been inserted beforehand.
+    pub(crate) fn send(
+        &mut self,
+        connection_id: ConnectionId,
+        message: Socks5Message,
+    ) -> Vec<ClientRequest> {
+        let connection = match self.connections.get_mut(&connection_id) {
+            Some(connection) => connection,
+            None => {
+                debug!("Dropping message for unknown anonymous connection {connection_id}");
+                return Vec::new();
+            }
+        };
+
+        let read_bytes = match &message {
+            Socks5Message::Response(response) => response.data.len(),
+            _ => 0,
+        };
+        let message = message.into_bytes();
+        connection.pending_bytes += message.len();
+        connection.pending_messages.push_back(PendingMessage {
+            message,
+            read_bytes,
+        });
+        if connection.pending_bytes > MAX_QUEUED_BYTES_PER_CONNECTION {
+            warn!("Too much data queued for anonymous connection {connection_id} - dropping it");
+            self.remove(connection_id);
+            return Vec::new();
+        }
+
+        self.flush(connection_id)
+    }
+
+    /// Marks the connection as closed so that it would be removed once all of its messages are sent.
+    pub(crate) fn close(&mut self, connection_id: ConnectionId) {
+        if let Some(connection) = self.connections.get_mut(&connection_id) {
+            if connection.pending_messages.is_empty() {
+                self.remove(connection_id);
+            } else {
+                connection.closed = true;
+            }
+        }
+    }
+
+    /// Asks again for the reply SURBs that didn't arrive in time and drops the connections
+    /// that are stuck without any of them, alongside any data still queued for them.
+    /// Returns the requests for sending the re-requests.
+    pub(crate) fn check_connections(&mut self) -> Vec<ClientRequest> {
+        let connection_ids: Vec<_> = self.connections.keys().copied().collect();
+
+        let mut requests = Vec::new();
+        for connection_id in connection_ids {
+            requests.append(&mut self.flush(connection_id));
+            if let Some(connection) = self.connections.get(&connection_id) {
+                if connection.is_stuck() {
+                    warn!(
+                        "Anonymous connection {connection_id} ran out of reply SURBs - dropping it alongside {} bytes of queued data",
+                        connection.pending_bytes
+                    );
+                    self.remove(connection_id);
+                }
+            }
+        }
+        requests
+    }
+
+    fn remove(&mut self, connection_id: ConnectionId) {
+        if let Some(connection) = self.connections.remove(&connection_id) {
+            // make sure nothing more is read from the remote
+            connection.send_window.close();
+        }
+    }
+
+    fn flush(&mut self, connection_id: ConnectionId) -> Vec<ClientRequest> {
+        let connection = match self.connections.get_mut(&connection_id) {
+            Some(connection) => connection,
+            None => return Vec::new(),
+        };
+
+        let available = connection.reply_surbs.len();
+        connection.reply_surbs.retain(|stored| !stored.is_expired());
+        let expired = available - connection.reply_surbs.len();
+        if expired > 0 {
+            debug!("Discarded {expired} expired reply SURBs of connection {connection_id}");
+        }
+
+        let mut requests = Vec::new();
+        // the oldest SURBs are used first, while the most recent one is kept aside
+        // so that we could always ask for more of them
+        while connection.reply_surbs.len() > 1 {
+            match connection.pop_pending() {
+                Some(message) => requests.push(ClientRequest::Reply {
+                    message,
+                    reply_surb: connection.reply_surbs.pop_front().unwrap().reply_surb,
+                }),
+                None => break,
+            }
+        }
+
+        if connection.closed && connection.pending_messages.is_empty() {
+            self.remove(connection_id);
+            return requests;
+        }
+
+        // ask for more SURBs once we're running low or the ones we have are about to expire,
+        // unless we're still waiting for the ones we've already asked for. If they don't arrive
+        // in time, we assume the request got lost and ask again
+        let ageing = connection
+            .reply_surbs
+            .back()
+            .map_or(false, StoredReplySurb::is_ageing);
+        let running_low = connection.reply_surbs.len() <= REPLY_SURBS_LOW_WATERMARK || ageing;
+        if running_low && !connection.is_awaiting_reply_surbs() {
+            if let Some(StoredReplySurb { reply_surb, .. }) = connection.reply_surbs.pop_back() {
+                trace!("Asking for more reply SURBs for connection {connection_id}");
+                let request = ReplySurbsRequest::new(connection_id, REPLY_SURBS_REQUEST_SIZE);
+                requests.push(ClientRequest::Reply {
+                    message: Socks5Message::ReplySurbsRequest(request).into_bytes(),
+                    reply_surb,
+                });
+                connection.requested_more_at = Some(Instant::now());
+            }
+        }
+
+        requests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nymsphinx::params::PacketMode;
+    use socks5_requests::Response;
+
+    fn reply_surbs(amount: usize) -> Vec<ReplySurb> {
+        // the content of the SURBs doesn't matter as they're never applied
+        let bytes = vec![1u8; ReplySurb::serialized_len(3, PacketMode::Mix)];
+        (0..amount)
+            .map(|_| ReplySurb::from_bytes(&bytes).unwrap())
+            .collect()
+    }
+
+    fn received(
+        connection_id: ConnectionId,
+        amount: usize,
+        new_connection: bool,
+    ) -> ReceivedReplySurbs {
+        ReceivedReplySurbs {
+            connection_id,
+            reply_surbs: reply_surbs(amount),
+            send_window: new_connection.then(|| SendWindow::new(SEND_WINDOW_SIZE)),
+        }
+    }
+
+    fn response(connection_id: ConnectionId, data: Vec<u8>) -> Socks5Message {
+        Socks5Message::Response(Response::new(connection_id, data, false))
+    }
+
+    fn timed_out() -> Option<Instant> {
+        Instant::now().checked_sub(REPLY_SURBS_REQUEST_TIMEOUT)
+    }
+
+    fn is_reply_surbs_request(request: &ClientRequest) -> bool {
+        match request {
+            ClientRequest::Reply { message, .. } => matches!(
+                Socks5Message::try_from_bytes(message),
+                Ok(Socks5Message::ReplySurbsRequest(_))
+            ),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn more_reply_surbs_are_requested_when_running_low() {
+        let mut connections = AnonymousConnections::default();
+        assert!(connections
+            .insert_reply_surbs(received(1, 20, true))
+            .is_empty());
+
+        let mut requests = Vec::new();
+        for i in 0..12u8 {
+            requests.append(&mut connections.send(1, response(1, vec![i])));
+        }
+
+        // all messages got sent and once we were running low, one of the SURBs was used
+        // for asking for more of them
+        assert_eq!(requests.len(), 13);
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|request| is_reply_surbs_request(request))
+                .count(),
+            1
+        );
+        assert_eq!(connections.connections[&1].reply_surbs.len(), 7);
+    }
+
+    #[test]
+    fn messages_wait_for_more_reply_surbs() {
+        let mut connections = AnonymousConnections::default();
+        connections.insert_reply_surbs(received(1, 1, true));
+
+        // the only SURB has been used for asking for more of them
+        for request in connections.send(1, response(1, vec![42])) {
+            assert!(is_reply_surbs_request(&request));
+        }
+        assert_eq!(connections.connections[&1].pending_messages.len(), 1);
+
+        // the message goes out as soon as more SURBs arrive
+        let requests = connections.insert_reply_surbs(received(1, 20, false));
+        assert_eq!(requests.len(), 1);
+        assert!(!is_reply_surbs_request(&requests[0]));
+        assert!(connections.connections[&1].pending_messages.is_empty());
+    }
+
+    #[test]
+    fn closed_connections_are_removed_once_drained() {
+        let mut connections = AnonymousConnections::default();
+        connections.insert_reply_surbs(received(1, 1, true));
+        connections.send(1, response(1, vec![42]));
+        connections.close(1);
+
+        // the message is still waiting for a SURB
+        assert!(connections.connections.contains_key(&1));
+
+        connections.insert_reply_surbs(received(1, 20, false));
+        assert!(!connections.connections.contains_key(&1));
+
+        // and any SURBs arriving afterwards are ignored
+        assert!(connections
+            .insert_reply_surbs(received(1, 20, false))
+            .is_empty());
+        assert!(connections.connections.is_empty());
+    }
+
+    #[test]
+    fn messages_for_unknown_connections_are_dropped() {
+        let mut connections = AnonymousConnections::default();
+        assert!(connections.send(1, response(1, vec![42])).is_empty());
+        assert!(connections.connections.is_empty());
+    }
+
+    #[test]
+    fn reply_surbs_per_connection_are_capped() {
+        let mut connections = AnonymousConnections::default();
+        connections.insert_reply_surbs(received(1, MAX_REPLY_SURBS_PER_CONNECTION, true));
+        connections.insert_reply_surbs(received(1, 100, false));
+
+        assert_eq!(
+            connections.connections[&1].reply_surbs.len(),
+            MAX_REPLY_SURBS_PER_CONNECTION
+        );
+    }
+
+    #[test]
+    fn number_of_connections_is_capped() {
+        let mut connections = AnonymousConnections::default();
+        for connection_id in 0..MAX_ANONYMOUS_CONNECTIONS as ConnectionId {
+            connections.insert_reply_surbs(received(connection_id, 0, true));
+        }
+
+        let rejected = MAX_ANONYMOUS_CONNECTIONS as ConnectionId;
+        connections.insert_reply_surbs(received(rejected, 20, true));
+        assert!(!connections.connections.contains_key(&rejected));
+        assert_eq!(connections.connections.len(), MAX_ANONYMOUS_CONNECTIONS);
+
+        // the existing connections can still get more SURBs
+        connections.insert_reply_surbs(received(0, 20, false));
+        assert_eq!(connections.connections[&0].reply_surbs.len(), 20);
+    }
+
+    #[test]
+    fn sent_messages_release_the_send_window() {
+        let mut connections = AnonymousConnections::default();
+        let send_window = SendWindow::new(SEND_WINDOW_SIZE);
+        connections.insert_reply_surbs(ReceivedReplySurbs {
+            connection_id: 1,
+            reply_surbs: reply_surbs(1),
+            send_window: Some(send_window.clone()),
+        });
+
+        // the data read from the remote is waiting for more SURBs
+        let data = vec![42; SEND_WINDOW_SIZE];
+        send_window.consume(data.len());
+        connections.send(1, response(1, data));
+        assert!(send_window.is_full());
+
+        connections.insert_reply_surbs(received(1, 20, false));
+        assert!(connections.connections[&1].pending_messages.is_empty());
+        assert_eq!(send_window.queued(), 0);
+        assert!(!send_window.is_closed());
+    }
+
+    #[test]
+    fn connections_with_too_much_queued_data_are_dropped() {
+        let mut connections = AnonymousConnections::default();
+        let send_window = SendWindow::new(SEND_WINDOW_SIZE);
+        connections.insert_reply_surbs(ReceivedReplySurbs {
+            connection_id: 1,
+            reply_surbs: reply_surbs(1),
+            send_window: Some(send_window.clone()),
+        });
+
+        for _ in 0..MAX_QUEUED_BYTES_PER_CONNECTION / SEND_WINDOW_SIZE {
+            connections.send(1, response(1, vec![42; SEND_WINDOW_SIZE]));
+        }
+        assert!(connections.connections.is_empty());
+        assert!(send_window.is_closed());
+    }
+
+    #[test]
+    fn reply_surbs_are_requested_again_after_timeout() {
+        let mut connections = AnonymousConnections::default();
+        connections.insert_reply_surbs(received(1, 5, true));
+
+        // we're still waiting for the SURBs asked for when the connection got opened
+        assert!(connections.check_connections().is_empty());
+
+        connections
+            .connections
+            .get_mut(&1)
+            .unwrap()
+            .requested_more_at = timed_out();
+        let requests = connections.check_connections();
+        assert_eq!(requests.len(), 1);
+        assert!(is_reply_surbs_request(&requests[0]));
+        assert_eq!(connections.connections[&1].reply_surbs.len(), 3);
+    }
+
+    #[test]
+    fn stuck_connections_are_dropped() {
+        let mut connections = AnonymousConnections::default();
+        let send_window = SendWindow::new(SEND_WINDOW_SIZE);
+        connections.insert_reply_surbs(ReceivedReplySurbs {
+            connection_id: 1,
+            reply_surbs: reply_surbs(1),
+            send_window: Some(send_window.clone()),
+        });
+        connections.send(1, response(1, vec![42]));
+        connections.close(1);
+
+        // the only SURB was used for asking for more of them, so there's still hope
+        assert!(connections.check_connections().is_empty());
+        assert!(connections.connections.contains_key(&1));
+
+        // but not once they don't arrive in time
+        connections
+            .connections
+            .get_mut(&1)
+            .unwrap()
+            .requested_more_at = timed_out();
+        assert!(connections.check_connections().is_empty());
+        assert!(connections.connections.is_empty());
+        assert!(send_window.is_closed());
+    }
+}
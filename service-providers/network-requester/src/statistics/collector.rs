@@ -25,6 +25,7 @@ use statistics_common::{
 };
 
 use super::error::StatsError;
+use crate::reply::ReturnAddress;
 
 const REMOTE_SOURCE_OF_STATS_PROVIDER_CONFIG: &str =
     "https://nymtech.net/.wellknown/network-requester/stats-provider.json";
@@ -77,13 +78,13 @@ pub struct ServiceStatisticsCollector {
     pub(crate) response_stats_data: Arc<RwLock<StatsData>>,
     pub(crate) connected_services: Arc<RwLock<HashMap<ConnectionId, RemoteAddress>>>,
     stats_provider_addr: Recipient,
-    mix_input_sender: mpsc::UnboundedSender<(Socks5Message, Recipient)>,
+    mix_input_sender: mpsc::UnboundedSender<(Socks5Message, ReturnAddress)>,
 }
 
 impl ServiceStatisticsCollector {
     pub async fn new(
         stats_provider_addr: Option<Recipient>,
-        mix_input_sender: mpsc::UnboundedSender<(Socks5Message, Recipient)>,
+        mix_input_sender: mpsc::UnboundedSender<(Socks5Message, ReturnAddress)>,
     ) -> Result<Self, StatsError> {
         let client = reqwest::Client::builder()
             .timeout(Duration::from_secs(3))
@@ -95,10 +96,11 @@ impl ServiceStatisticsCollector {
             .json()
             .await?;
         let stats_provider_addr = stats_provider_addr.unwrap_or(
-            Recipient::try_from_base58_string(
+            Recipient::try_from_string(
                 stats_provider_config
                     .stats_client_address()
                     .ok_or(StatsError::InvalidClientAddress)?,
+                &network_defaults::recipient_hrp(),
             )
             .map_err(|_| StatsError::InvalidClientAddress)?,
         );
@@ -178,7 +180,7 @@ impl StatisticsCollector for ServiceStatisticsCollector {
         self.mix_input_sender
             .unbounded_send((
                 Socks5Message::Request(connect_req),
-                self.stats_provider_addr,
+                ReturnAddress::Known(self.stats_provider_addr),
             ))
             .unwrap();
 
@@ -187,7 +189,10 @@ impl StatisticsCollector for ServiceStatisticsCollector {
         let ordered_msg = message_sender.wrap_message(msg).into_bytes();
         let send_req = Request::new_send(conn_id, ordered_msg, true);
         self.mix_input_sender
-            .unbounded_send((Socks5Message::Request(send_req), self.stats_provider_addr))
+            .unbounded_send((
+                Socks5Message::Request(send_req),
+                ReturnAddress::Known(self.stats_provider_addr),
+            ))
             .unwrap();
 
         Ok(())
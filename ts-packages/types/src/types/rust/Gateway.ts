@@ -2,6 +2,8 @@ export interface Gateway {
   host: string;
   mix_port: number;
   clients_port: number;
+  clients_wss_port: number | null;
+  http_api_port: number | null;
   location: string;
   sphinx_key: string;
   identity_key: string;
@@ -24,11 +24,12 @@ use std::time::Duration;
 use task::ShutdownListener;
 use tokio::sync::{watch, RwLock};
 use tokio::time;
-use validator_api_requests::models::{MixNodeBondAnnotated, MixnodeStatus};
+use validator_api_requests::models::{MixNodeBondAnnotated, MixnodeStatus, SphinxKeyAnnouncements};
 use validator_client::nymd::CosmWasmClient;
 
 pub(crate) mod reward_estimate;
 pub(crate) mod routes;
+mod sphinx_keys;
 
 // The cache can emit notifications to listeners about the current state
 #[derive(Debug, PartialEq, Eq)]
@@ -67,6 +68,8 @@ struct ValidatorCacheInner {
 
     current_reward_params: Cache<Option<RewardingParams>>,
     current_interval: Cache<Option<Interval>>,
+
+    sphinx_key_announcements: Cache<SphinxKeyAnnouncements>,
 }
 
 fn current_unix_timestamp() -> i64 {
@@ -227,6 +230,9 @@ impl<C> ValidatorCacheRefresher<C> {
         let gateways = self.nymd_client.get_gateways().await?;
 
         let rewarded_set = self.get_rewarded_set_map().await;
+        let sphinx_key_announcements =
+            sphinx_keys::collect_sphinx_key_announcements(&mixnodes, &gateways, &rewarded_set)
+                .await;
 
         let mixnodes = self
             .annotate_node_with_details(mixnodes, rewarding_params, current_interval, &rewarded_set)
@@ -249,6 +255,7 @@ impl<C> ValidatorCacheRefresher<C> {
                 active_set,
                 rewarding_params,
                 current_interval,
+                sphinx_key_announcements,
             )
             .await;
 
@@ -304,7 +311,8 @@ pub(crate) fn validator_cache_routes(settings: &OpenApiSettings) -> (Vec<Route>,
         routes::get_blacklisted_mixnodes,
         routes::get_blacklisted_gateways,
         routes::get_interval_reward_params,
-        routes::get_current_epoch
+        routes::get_current_epoch,
+        routes::get_sphinx_key_announcements
     ]
 }
 
@@ -330,6 +338,7 @@ impl ValidatorCache {
         active_set: Vec<MixNodeBondAnnotated>,
         rewarding_params: RewardingParams,
         current_interval: Interval,
+        sphinx_key_announcements: SphinxKeyAnnouncements,
     ) {
         match time::timeout(Duration::from_millis(100), self.inner.write()).await {
             Ok(mut cache) => {
@@ -339,6 +348,9 @@ impl ValidatorCache {
                 cache.active_set.update(active_set);
                 cache.current_reward_params.update(Some(rewarding_params));
                 cache.current_interval.update(Some(current_interval));
+                cache
+                    .sphinx_key_announcements
+                    .update(sphinx_key_announcements);
             }
             Err(e) => {
                 error!("{}", e);
@@ -558,6 +570,16 @@ impl ValidatorCache {
         }
     }
 
+    pub(crate) async fn sphinx_key_announcements(&self) -> Cache<SphinxKeyAnnouncements> {
+        match time::timeout(Duration::from_millis(100), self.inner.read()).await {
+            Ok(cache) => cache.sphinx_key_announcements.clone(),
+            Err(e) => {
+                error!("{}", e);
+                Cache::new(SphinxKeyAnnouncements::default())
+            }
+        }
+    }
+
     pub async fn mixnode_details(
         &self,
         mix_id: MixId,
@@ -615,6 +637,7 @@ impl ValidatorCacheInner {
             gateways_blacklist: Cache::default(),
             current_interval: Cache::default(),
             current_reward_params: Cache::default(),
+            sphinx_key_announcements: Cache::default(),
         }
     }
 }
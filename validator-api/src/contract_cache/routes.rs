@@ -9,7 +9,7 @@ use rocket::serde::json::Json;
 use rocket::State;
 use rocket_okapi::openapi;
 use std::collections::HashSet;
-use validator_api_requests::models::MixNodeBondAnnotated;
+use validator_api_requests::models::{MixNodeBondAnnotated, SphinxKeyAnnouncements};
 
 #[openapi(tag = "contract-cache")]
 #[get("/mixnodes")]
@@ -88,3 +88,11 @@ pub async fn get_interval_reward_params(
 pub async fn get_current_epoch(cache: &State<ValidatorCache>) -> Json<Option<Interval>> {
     Json(cache.current_interval().await.value)
 }
+
+#[openapi(tag = "contract-cache")]
+#[get("/sphinx-keys")]
+pub async fn get_sphinx_key_announcements(
+    cache: &State<ValidatorCache>,
+) -> Json<SphinxKeyAnnouncements> {
+    Json(cache.sphinx_key_announcements().await.value)
+}
@@ -0,0 +1,151 @@
+// Copyright 2022 - Nym Technologies SA <contact@nymtech.net>
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::asymmetric::{encryption, identity};
+use futures::{stream, StreamExt};
+use log::{debug, error};
+use mixnet_contract_common::mixnode::MixNodeDetails;
+use mixnet_contract_common::sphinx_keys::{sphinx_key_epoch, SphinxKeyAnnouncement};
+use mixnet_contract_common::{GatewayBond, MixId, RewardedSetNodeStatus};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use validator_api_requests::models::SphinxKeyAnnouncements;
+
+const SPHINX_KEYS_REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_CONCURRENT_SPHINX_KEYS_REQUESTS: usize = 32;
+
+fn is_valid_announcement(
+    identity_key: &identity::PublicKey,
+    announcement: &SphinxKeyAnnouncement,
+    current_epoch: u64,
+) -> bool {
+    // we only care about keys that could possibly be used by the clients right now
+    if announcement.epoch + 1 < current_epoch || announcement.epoch > current_epoch + 1 {
+        return false;
+    }
+    if encryption::PublicKey::from_base58_string(&announcement.sphinx_key).is_err() {
+        return false;
+    }
+    let signature = match identity::Signature::from_base58_string(&announcement.signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    identity_key
+        .verify(&announcement.plaintext(), &signature)
+        .is_ok()
+}
+
+async fn fetch_announcements(
+    client: &reqwest::Client,
+    identity_key: &str,
+    host: &str,
+    http_api_port: u16,
+    current_epoch: u64,
+) -> Vec<SphinxKeyAnnouncement> {
+    let identity_key = match identity::PublicKey::from_base58_string(identity_key) {
+        Ok(key) => key,
+        Err(_) => return Vec::new(),
+    };
+
+    let url = format!("http://{}:{}/sphinx-keys", host, http_api_port);
+    let announcements: Vec<SphinxKeyAnnouncement> = match client.get(&url).send().await {
+        Ok(response) => match response.json().await {
+            Ok(announcements) => announcements,
+            Err(err) => {
+                debug!(
+                    "node {} returned invalid sphinx keys - {}",
+                    identity_key, err
+                );
+                return Vec::new();
+            }
+        },
+        Err(err) => {
+            debug!(
+                "failed to query sphinx keys of node {} - {}",
+                identity_key, err
+            );
+            return Vec::new();
+        }
+    };
+
+    announcements
+        .into_iter()
+        .filter(|announcement| is_valid_announcement(&identity_key, announcement, current_epoch))
+        .collect()
+}
+
+/// Queries all the rewarded set mixnodes and all the gateways for their rotated sphinx keys
+/// and collects the (correctly signed) announcements. Nodes that do not rotate their keys
+/// (or gateways that have not announced their HTTP API port) are simply omitted.
+pub(crate) async fn collect_sphinx_key_announcements(
+    mixnodes: &[MixNodeDetails],
+    gateways: &[GatewayBond],
+    rewarded_set: &HashMap<MixId, RewardedSetNodeStatus>,
+) -> SphinxKeyAnnouncements {
+    let client = match reqwest::Client::builder()
+        .timeout(SPHINX_KEYS_REQUEST_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(err) => {
+            error!(
+                "failed to build the http client for querying sphinx keys - {}",
+                err
+            );
+            return SphinxKeyAnnouncements::default();
+        }
+    };
+
+    // the unwrap is fine as the current time is always after the unix epoch
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let current_epoch = sphinx_key_epoch(now.as_secs());
+
+    let client = &client;
+    let mixnodes = stream::iter(
+        mixnodes
+            .iter()
+            .filter(|mixnode| rewarded_set.contains_key(&mixnode.mix_id())),
+    )
+    .map(|mixnode| async move {
+        let bond = &mixnode.bond_information;
+        let announcements = fetch_announcements(
+            client,
+            bond.identity(),
+            &bond.mix_node.host,
+            bond.mix_node.http_api_port,
+            current_epoch,
+        )
+        .await;
+        (bond.mix_id, announcements)
+    })
+    .buffer_unordered(MAX_CONCURRENT_SPHINX_KEYS_REQUESTS)
+    .filter(|(_, announcements)| futures::future::ready(!announcements.is_empty()))
+    .collect()
+    .await;
+
+    let gateways = stream::iter(gateways)
+        .filter_map(|bond| {
+            futures::future::ready(
+                bond.gateway
+                    .http_api_port
+                    .map(|http_api_port| (bond, http_api_port)),
+            )
+        })
+        .map(|(bond, http_api_port)| async move {
+            let announcements = fetch_announcements(
+                client,
+                bond.identity(),
+                &bond.gateway.host,
+                http_api_port,
+                current_epoch,
+            )
+            .await;
+            (bond.identity().clone(), announcements)
+        })
+        .buffer_unordered(MAX_CONCURRENT_SPHINX_KEYS_REQUESTS)
+        .filter(|(_, announcements)| futures::future::ready(!announcements.is_empty()))
+        .collect()
+        .await;
+
+    SphinxKeyAnnouncements { mixnodes, gateways }
+}
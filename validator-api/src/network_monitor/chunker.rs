@@ -3,7 +3,9 @@
 
 use nymsphinx::forwarding::packet::MixPacket;
 use nymsphinx::{
-    acknowledgements::AckKey, addressing::clients::Recipient, preparer::MessagePreparer,
+    acknowledgements::{AckDelayDistribution, AckKey},
+    addressing::clients::Recipient,
+    preparer::MessagePreparer,
 };
 use rand_07::rngs::OsRng;
 use std::time::Duration;
@@ -26,7 +28,7 @@ impl Chunker {
                 OsRng,
                 tested_mix_me,
                 DEFAULT_AVERAGE_PACKET_DELAY,
-                DEFAULT_AVERAGE_ACK_DELAY,
+                AckDelayDistribution::Exponential(DEFAULT_AVERAGE_ACK_DELAY),
             ),
         }
     }
@@ -481,7 +481,7 @@ impl PacketPreparer {
                 let mut gateway_mix_packets = Vec::new();
                 let test_packet = TestPacket::from_gateway(gateway, test_route.id(), test_nonce);
                 let gateway_identity = gateway.identity_key;
-                let gateway_address = gateway.clients_address();
+                let gateway_address = gateway.clients_address(false);
                 let gateway_owner = gateway.owner.clone();
                 let recipient = self.create_packet_sender(gateway);
                 let topology = test_route.substitute_gateway(gateway);
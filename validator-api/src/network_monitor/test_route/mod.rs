@@ -59,7 +59,7 @@ impl TestRoute {
     }
 
     pub(crate) fn gateway_clients_address(&self) -> String {
-        self.gateway().clients_address()
+        self.gateway().clients_address(false)
     }
 
     pub(crate) fn gateway_identity(&self) -> identity::PublicKey {
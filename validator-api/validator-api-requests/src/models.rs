@@ -6,10 +6,11 @@ use mixnet_contract_common::mixnode::MixNodeDetails;
 use mixnet_contract_common::reward_params::{Performance, RewardingParams};
 use mixnet_contract_common::rewarding::RewardEstimate;
 use mixnet_contract_common::{
-    IdentityKey, Interval, MixId, MixNode, Percent, RewardedSetNodeStatus,
+    IdentityKey, Interval, MixId, MixNode, Percent, RewardedSetNodeStatus, SphinxKeyAnnouncement,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::{fmt, time::Duration};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
@@ -112,6 +113,16 @@ impl MixNodeBondAnnotated {
     }
 }
 
+/// Rotated sphinx keys announced by the nodes for the current and the adjacent epochs.
+/// They are not part of the contract state, but are rather collected by the validator API
+/// from the nodes themselves. The signatures are not trusted - clients verify them against
+/// the identity keys of the nodes.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct SphinxKeyAnnouncements {
+    pub mixnodes: HashMap<MixId, Vec<SphinxKeyAnnouncement>>,
+    pub gateways: HashMap<IdentityKey, Vec<SphinxKeyAnnouncement>>,
+}
+
 #[derive(Deserialize, JsonSchema)]
 pub struct ComputeRewardEstParam {
     pub performance: Option<Performance>,